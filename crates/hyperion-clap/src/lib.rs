@@ -7,12 +7,15 @@ use flecs_ecs::{
 };
 use hyperion::{
     net::{Compose, agnostic},
-    simulation::{command::get_root_command_entity, handlers::PacketSwitchQuery},
+    simulation::{
+        command::{Permissions, get_root_command_entity},
+        handlers::PacketSwitchQuery,
+    },
     storage::{CommandCompletionRequest, EventFn},
     system_registry::SystemId,
 };
 pub use hyperion_command;
-use hyperion_command::{CommandHandler, CommandRegistry};
+use hyperion_command::{CommandHandler, CommandRegisterError, CommandRegistry};
 use valence_protocol::{
     VarInt,
     packets::{
@@ -22,31 +25,48 @@ use valence_protocol::{
 };
 
 pub trait MinecraftCommand: Parser {
+    /// What players need to see and run the command.
+    const PERMISSIONS: Permissions = Permissions::PLAYER;
+
     fn execute(self, world: &World, caller: Entity);
 
-    fn register(registry: &mut CommandRegistry, world: &World) {
+    /// Registers the command and its visible aliases, and adds them to the command tree.
+    #[track_caller]
+    fn register(registry: &mut CommandRegistry, world: &World) -> Result<(), CommandRegisterError> {
         let cmd = Self::command();
         let name = cmd.get_name();
-
-        let node_to_register = hyperion::simulation::command::Command::literal(name);
-
-        let mut on = world
-            .entity()
-            .set(node_to_register)
-            .child_of_id(get_root_command_entity());
-
-        for arg in cmd.get_arguments() {
-            use valence_protocol::packets::play::command_tree_s2c::Parser as ValenceParser;
-            let name = arg.get_value_names().unwrap().first().unwrap();
-            let name = name.to_ascii_lowercase();
-            let node_to_register = hyperion::simulation::command::Command::argument(
-                name,
-                ValenceParser::String(StringArg::SingleWord),
-            );
-
-            on = world.entity().set(node_to_register).child_of_id(on);
+        let aliases = cmd.get_visible_aliases().collect::<Vec<_>>();
+
+        registry.register_raw_aliased(name, &aliases, Self::PERMISSIONS, Self::handler())?;
+        tracing::info!("registered command {name}");
+
+        for literal in std::iter::once(name).chain(aliases) {
+            let node_to_register = hyperion::simulation::command::Command::literal(literal);
+
+            let mut on = world
+                .entity()
+                .set(node_to_register)
+                .set(Self::PERMISSIONS)
+                .child_of_id(get_root_command_entity());
+
+            for arg in cmd.get_arguments() {
+                use valence_protocol::packets::play::command_tree_s2c::Parser as ValenceParser;
+                let name = arg.get_value_names().unwrap().first().unwrap();
+                let name = name.to_ascii_lowercase();
+                let node_to_register = hyperion::simulation::command::Command::argument(
+                    name,
+                    ValenceParser::String(StringArg::SingleWord),
+                );
+
+                on = world.entity().set(node_to_register).child_of_id(on);
+            }
         }
 
+        Ok(())
+    }
+
+    /// Parses and runs the command, and completes its possible values.
+    fn handler() -> CommandHandler {
         let on_execute = |input: &str, world: &World, caller: Entity| {
             let input = input.split_whitespace();
 
@@ -61,15 +81,7 @@ pub trait MinecraftCommand: Parser {
 
                     // minecraft red
                     let msg = format!("{prefix}{e}");
-
-                    world.get::<&Compose>(|compose| {
-                        caller
-                            .entity_view(world)
-                            .get::<&hyperion::net::NetworkStreamRef>(|stream| {
-                                let msg = agnostic::chat(msg);
-                                compose.unicast(&msg, *stream, SystemId(8), world).unwrap();
-                            });
-                    });
+                    send_error(world, caller, &msg);
 
                     tracing::warn!("could not parse command {e}");
                 }
@@ -195,17 +207,24 @@ pub trait MinecraftCommand: Parser {
                     .unwrap();
             };
 
-        let handler = CommandHandler {
+        CommandHandler {
             on_execute,
             on_tab_complete,
-        };
-
-        tracing::info!("registering command {name}");
-
-        registry.register(name, handler);
+        }
     }
 }
 
+fn send_error(world: &World, caller: Entity, msg: &str) {
+    world.get::<&Compose>(|compose| {
+        caller
+            .entity_view(world)
+            .get::<&hyperion::net::NetworkStreamRef>(|stream| {
+                let msg = agnostic::chat(msg);
+                compose.unicast(&msg, *stream, SystemId(8), world).unwrap();
+            });
+    });
+}
+
 pub enum Arg {
     Player,
 }
@@ -0,0 +1,199 @@
+//! Reading the input of a command one argument at a time, for commands that do not fit a
+//! [`crate::Signature`].
+//!
+//! ```ignore
+//! registry.register_args("warp", |args, world, caller| {
+//!     let name = args.next_word("name")?;
+//!     let radius = args.next_i32("radius")?;
+//!     args.finish()?;
+//!     // ...
+//!     Ok(())
+//! });
+//! ```
+
+use crate::signature::{ParseError, Reason};
+
+/// The input of a command after its name. Words are separated by whitespace, and a word in double
+/// quotes may contain spaces, with `\"` and `\\` standing for a quote and a backslash.
+#[derive(Clone, Debug)]
+pub struct Args<'a> {
+    input: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    #[must_use]
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            rest: input.trim_start(),
+        }
+    }
+
+    /// The whole input, including what was already read.
+    #[must_use]
+    pub const fn input(&self) -> &'a str {
+        self.input
+    }
+
+    /// Whether everything was read, not counting whitespace.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rest.trim_end().is_empty()
+    }
+
+    /// The next word, without its quotes.
+    pub fn next_word(&mut self, name: &'static str) -> Result<String, ParseError> {
+        if self.is_empty() {
+            return Err(ParseError::invalid(name, Reason::Missing));
+        }
+
+        let Some(quoted) = self.rest.strip_prefix('"') else {
+            let (word, after) = self
+                .rest
+                .split_once(char::is_whitespace)
+                .unwrap_or((self.rest, ""));
+
+            self.rest = after.trim_start();
+            return Ok(word.to_owned());
+        };
+
+        let mut word = String::new();
+        let mut chars = quoted.char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = quoted[i + 1..].trim_start();
+                    return Ok(word);
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => word.push(escaped),
+                    Some((_, other)) => {
+                        word.push('\\');
+                        word.push(other);
+                    }
+                    None => break,
+                },
+                _ => word.push(c),
+            }
+        }
+
+        Err(ParseError::invalid(name, Reason::UnclosedQuote))
+    }
+
+    /// The next word as a whole number that fits in an `i32`.
+    pub fn next_i32(&mut self, name: &'static str) -> Result<i32, ParseError> {
+        let word = self.next_word(name)?;
+
+        let value = word
+            .parse::<i64>()
+            .map_err(|_| ParseError::invalid(name, Reason::NotAnInteger(word.clone())))?;
+
+        i32::try_from(value).map_err(|_| {
+            ParseError::invalid(name, Reason::OutOfRange {
+                value: word,
+                min: i32::MIN.to_string(),
+                max: i32::MAX.to_string(),
+            })
+        })
+    }
+
+    /// The next word as a finite number.
+    pub fn next_f64(&mut self, name: &'static str) -> Result<f64, ParseError> {
+        let word = self.next_word(name)?;
+
+        word.parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| ParseError::invalid(name, Reason::NotANumber(word)))
+    }
+
+    /// Everything that was not read yet, spaces and quotes included, without surrounding
+    /// whitespace. Nothing is left afterwards.
+    pub fn rest(&mut self) -> &'a str {
+        std::mem::take(&mut self.rest).trim_end()
+    }
+
+    /// Fails if anything is left to read.
+    pub fn finish(&self) -> Result<(), ParseError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        Err(ParseError::TrailingInput(self.rest.trim_end().to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+    use crate::signature::{ParseError, Reason};
+
+    #[test]
+    fn quoted_words_keep_their_spaces() {
+        let mut args = Args::new(r#" "Spawn  Town" "say \"hi\"" plain "#);
+
+        assert_eq!(args.next_word("name").unwrap(), "Spawn  Town");
+        assert_eq!(args.next_word("greeting").unwrap(), r#"say "hi""#);
+        assert_eq!(args.next_word("last").unwrap(), "plain");
+        assert!(args.is_empty());
+
+        let mut args = Args::new(r#""unclosed \" word"#);
+        assert_eq!(
+            args.next_word("name"),
+            Err(ParseError::Invalid {
+                argument: "name",
+                reason: Reason::UnclosedQuote,
+            })
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_is_ignored() {
+        let mut args = Args::new("12  2.5   for the  sword  \t");
+
+        assert_eq!(args.next_i32("amount"), Ok(12));
+        assert_eq!(args.next_f64("speed"), Ok(2.5));
+        assert_eq!(args.rest(), "for the  sword");
+        assert_eq!(args.rest(), "");
+        assert_eq!(args.finish(), Ok(()));
+
+        let mut args = Args::new("12   ");
+        assert_eq!(args.next_i32("amount"), Ok(12));
+        assert_eq!(
+            args.next_word("name"),
+            Err(ParseError::Invalid {
+                argument: "name",
+                reason: Reason::Missing,
+            })
+        );
+        assert_eq!(args.input(), "12   ");
+    }
+
+    #[test]
+    fn words_of_the_wrong_type_are_rejected() {
+        let mut args = Args::new("five 3000000000 inf 7 extra");
+
+        assert_eq!(
+            args.next_i32("amount"),
+            Err(ParseError::Invalid {
+                argument: "amount",
+                reason: Reason::NotAnInteger("five".to_owned()),
+            })
+        );
+        assert_eq!(
+            args.next_i32("amount").unwrap_err().to_string(),
+            "<amount> must be between -2147483648 and 2147483647, not 3000000000"
+        );
+        assert_eq!(
+            args.next_f64("speed").unwrap_err().to_string(),
+            "<speed> must be a number, not `inf`"
+        );
+        assert_eq!(args.next_i32("count"), Ok(7));
+        assert_eq!(
+            args.finish(),
+            Err(ParseError::TrailingInput("extra".to_owned()))
+        );
+    }
+}
@@ -1,30 +1,483 @@
+use std::{
+    fmt::{self, Display},
+    panic::Location,
+    time::Duration,
+};
+
 use flecs_ecs::{
     core::{Entity, World},
     macros::Component,
     prelude::Module,
 };
-use hyperion::storage::{CommandCompletionRequest, EventFn};
+use hyperion::{
+    simulation::command::{Command, Permissions, get_root_command_entity},
+    storage::{CommandCompletionRequest, EventFn},
+};
 use indexmap::IndexMap;
 
+use crate::{
+    args::Args,
+    signature::{ParsedArgs, Signature},
+};
+
+/// A command that parses its input itself, registered with [`CommandRegistry::register_raw`].
 pub struct CommandHandler {
     pub on_execute: fn(input: &str, world: &World, caller: Entity),
     pub on_tab_complete: EventFn<CommandCompletionRequest<'static>>,
 }
 
-#[derive(Component)]
+/// Runs a command registered with [`CommandRegistry::register`] once its input matched the
+/// signature. An error is shown to the caller.
+pub type Executor = fn(args: &ParsedArgs, world: &World, caller: Entity) -> anyhow::Result<()>;
+
+/// Runs a command registered with [`CommandRegistry::register_args`], reading its arguments from
+/// `args`. An error, such as a missing or invalid argument, is shown to the caller.
+pub type ArgsExecutor =
+    fn(args: &mut Args<'_>, world: &World, caller: Entity) -> anyhow::Result<()>;
+
+/// Suggests values for the argument being typed, given `args`, the input after the name of the
+/// command. Only the suggestions that start with what was typed of the argument are shown. Set one
+/// with [`CommandRegistry::set_completion`].
+pub type CompletionFn = fn(args: &str, world: &World, caller: Entity) -> Vec<String>;
+
+/// Told about every command that ran, with the name it was registered as, who ran it and how long
+/// it took, such as to log slow commands or count how often each player runs them. Set one with
+/// [`CommandRegistry::set_on_executed`].
+pub type ExecutedFn = fn(name: &str, caller: Entity, elapsed: Duration);
+
+/// How a command was registered.
+pub enum Registered {
+    Raw(CommandHandler),
+    Args(ArgsExecutor),
+    Typed {
+        signature: Signature,
+        on_execute: Executor,
+        /// The literal nodes of the command and its aliases in the command tree.
+        nodes: Vec<Entity>,
+    },
+}
+
+/// A registered command and who may run it.
+pub struct Entry {
+    pub(crate) permissions: Permissions,
+    pub(crate) command: Registered,
+    pub(crate) aliases: Vec<String>,
+    pub(crate) completion: Option<CompletionFn>,
+    /// Where the command was registered, to tell which module a conflicting command comes from.
+    pub(crate) owner: &'static Location<'static>,
+}
+
+/// What became of a command a player sent, as returned by [`CommandRegistry::dispatch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dispatch {
+    /// The command ran, or its input was answered with what is wrong with it.
+    Executed,
+    /// No command with the name is registered.
+    Unknown,
+    /// The caller's [`Permissions`] do not allow the command.
+    Denied,
+}
+
+/// Why a command could not be registered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandRegisterError {
+    /// `name` is already the name or an alias of a command, which was registered at
+    /// `existing_owner`.
+    Duplicate {
+        name: String,
+        existing_owner: &'static Location<'static>,
+    },
+}
+
+impl Display for CommandRegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate {
+                name,
+                existing_owner,
+            } => write!(f, "/{name} is already registered at {existing_owner}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandRegisterError {}
+
+#[derive(Component, Default)]
 pub struct CommandRegistry {
-    pub(crate) commands: IndexMap<String, CommandHandler, gxhash::GxBuildHasher>,
+    pub(crate) commands: IndexMap<String, Entry, gxhash::GxBuildHasher>,
+    /// The name of the command each alias stands for.
+    aliases: IndexMap<String, String, gxhash::GxBuildHasher>,
+    /// Whether commands were registered since the command tree was last sent to players online.
+    pub(crate) tree_changed: bool,
+    /// Called after each command [`CommandRegistry::dispatch`] ran. Commands are only timed if
+    /// there is one.
+    pub(crate) on_executed: Option<ExecutedFn>,
 }
 
 impl CommandRegistry {
-    pub fn register(&mut self, name: impl Into<String>, handler: CommandHandler) {
+    /// Registers a command whose input is checked against `signature` before `on_execute` runs.
+    /// Input that does not match is answered with what is wrong and how to use the command.
+    ///
+    /// The command is added to the command tree, which is resent to players who are online.
+    #[track_caller]
+    pub fn register(
+        &mut self,
+        world: &World,
+        name: impl Into<String>,
+        signature: Signature,
+        on_execute: Executor,
+    ) -> Result<(), CommandRegisterError> {
+        self.register_with(world, name, signature, Permissions::PLAYER, on_execute)
+    }
+
+    /// Like [`CommandRegistry::register`], but only players with `permissions` can see and run the
+    /// command.
+    #[track_caller]
+    pub fn register_with(
+        &mut self,
+        world: &World,
+        name: impl Into<String>,
+        signature: Signature,
+        permissions: Permissions,
+        on_execute: Executor,
+    ) -> Result<(), CommandRegisterError> {
+        self.register_aliased(world, name, &[], signature, permissions, on_execute)
+    }
+
+    /// Like [`CommandRegistry::register_with`], but the command can also be run as any of
+    /// `aliases`, such as `/teleport` for `/tp`.
+    #[track_caller]
+    pub fn register_aliased(
+        &mut self,
+        world: &World,
+        name: impl Into<String>,
+        aliases: &[&str],
+        signature: Signature,
+        permissions: Permissions,
+        on_execute: Executor,
+    ) -> Result<(), CommandRegisterError> {
+        let name = name.into();
+        self.check_free(&name, aliases)?;
+
+        let root = get_root_command_entity();
+        let nodes = std::iter::once(name.as_str())
+            .chain(aliases.iter().copied())
+            .map(|literal| add_nodes(world, root, literal, &signature, permissions))
+            .collect();
+
+        let command = Registered::Typed {
+            signature,
+            on_execute,
+            nodes,
+        };
+
+        self.insert(name, aliases, permissions, command);
+        Ok(())
+    }
+
+    /// Registers a command that is given its input as it was sent. Its nodes in the command tree
+    /// are up to the caller.
+    #[track_caller]
+    pub fn register_raw(
+        &mut self,
+        name: impl Into<String>,
+        handler: CommandHandler,
+    ) -> Result<(), CommandRegisterError> {
+        self.register_raw_with(name, Permissions::PLAYER, handler)
+    }
+
+    /// Like [`CommandRegistry::register_raw`], but only players with `permissions` can run the
+    /// command.
+    #[track_caller]
+    pub fn register_raw_with(
+        &mut self,
+        name: impl Into<String>,
+        permissions: Permissions,
+        handler: CommandHandler,
+    ) -> Result<(), CommandRegisterError> {
+        self.register_raw_aliased(name, &[], permissions, handler)
+    }
+
+    /// Like [`CommandRegistry::register_raw_with`], but the command can also be run as any of
+    /// `aliases`.
+    #[track_caller]
+    pub fn register_raw_aliased(
+        &mut self,
+        name: impl Into<String>,
+        aliases: &[&str],
+        permissions: Permissions,
+        handler: CommandHandler,
+    ) -> Result<(), CommandRegisterError> {
         let name = name.into();
-        self.commands.insert(name, handler);
+        self.check_free(&name, aliases)?;
+
+        self.insert(name, aliases, permissions, Registered::Raw(handler));
+        Ok(())
+    }
+
+    /// Registers a command that reads its arguments one at a time from [`Args`], the input after
+    /// its name. Like [`CommandRegistry::register_raw`], its nodes in the command tree are up to
+    /// the caller.
+    #[track_caller]
+    pub fn register_args(
+        &mut self,
+        name: impl Into<String>,
+        on_execute: ArgsExecutor,
+    ) -> Result<(), CommandRegisterError> {
+        self.register_args_with(name, Permissions::PLAYER, on_execute)
+    }
+
+    /// Like [`CommandRegistry::register_args`], but only players with `permissions` can run the
+    /// command.
+    #[track_caller]
+    pub fn register_args_with(
+        &mut self,
+        name: impl Into<String>,
+        permissions: Permissions,
+        on_execute: ArgsExecutor,
+    ) -> Result<(), CommandRegisterError> {
+        let name = name.into();
+        self.check_free(&name, &[])?;
+
+        self.insert(name, &[], permissions, Registered::Args(on_execute));
+        Ok(())
+    }
+
+    /// Makes `alias` also run the command `target`, or the command `target` is an alias of. Like
+    /// [`CommandRegistry::register_aliased`], commands registered with a signature get nodes in the
+    /// command tree for the alias. Returns false if there is no such command or `alias` is taken.
+    pub fn register_alias(
+        &mut self,
+        world: &World,
+        alias: impl Into<String>,
+        target: &str,
+    ) -> bool {
+        let alias = alias.into();
+        let target = self
+            .aliases
+            .get(target)
+            .map_or(target, String::as_str)
+            .to_owned();
+
+        if self.get(&alias).is_some() {
+            return false;
+        }
+
+        let Some(entry) = self.commands.get_mut(&target) else {
+            return false;
+        };
+
+        if let Registered::Typed {
+            signature, nodes, ..
+        } = &mut entry.command
+        {
+            let root = get_root_command_entity();
+            nodes.push(add_nodes(world, root, &alias, signature, entry.permissions));
+        }
+
+        entry.aliases.push(alias.clone());
+        self.aliases.insert(alias, target);
+
+        self.tree_changed = true;
+        true
     }
 
+    /// Removes the command `name`, or the command it is an alias of, along with all its aliases.
+    /// The nodes of commands registered with a signature are removed from the command tree too.
+    /// Returns whether there was such a command.
+    pub fn unregister(&mut self, world: &World, name: &str) -> bool {
+        let name = self
+            .aliases
+            .get(name)
+            .map_or(name, String::as_str)
+            .to_owned();
+
+        let Some(entry) = self.commands.shift_remove(&name) else {
+            return false;
+        };
+
+        for alias in &entry.aliases {
+            self.aliases.shift_remove(alias);
+        }
+
+        if let Registered::Typed { nodes, .. } = entry.command {
+            for node in nodes {
+                world.entity_from_id(node).destruct();
+            }
+        }
+
+        self.tree_changed = true;
+        true
+    }
+
+    /// Makes the command `name`, or the command it is an alias of, suggest its arguments with
+    /// `completion` instead of how it was registered. Returns whether there is such a command.
+    pub fn set_completion(&mut self, name: &str, completion: CompletionFn) -> bool {
+        let name = self.aliases.get(name).map_or(name, String::as_str);
+
+        let Some(entry) = self.commands.get_mut(name) else {
+            return false;
+        };
+
+        entry.completion = Some(completion);
+        true
+    }
+
+    /// Calls `on_executed` after each command that runs from now on, or stops timing commands if
+    /// it is `None`.
+    pub fn set_on_executed(&mut self, on_executed: Option<ExecutedFn>) {
+        self.on_executed = on_executed;
+    }
+
+    /// The command called `name`, which may be an alias.
+    pub(crate) fn get(&self, name: &str) -> Option<&Entry> {
+        self.get_named(name).map(|(_, entry)| entry)
+    }
+
+    /// Like [`CommandRegistry::get`], along with the name the command was registered as.
+    pub(crate) fn get_named(&self, name: &str) -> Option<(&str, &Entry)> {
+        let name = self.aliases.get(name).map_or(name, String::as_str);
+        let (name, entry) = self.commands.get_key_value(name)?;
+        Some((name.as_str(), entry))
+    }
+
+    /// The names and aliases of the commands that start with the first word of `partial`, ignoring
+    /// case, such as `tp` and `teleport` for `T`. A leading `/` is ignored.
+    #[must_use]
+    pub fn suggest(&self, partial: &str) -> Vec<&str> {
+        let typed = partial
+            .trim_start()
+            .trim_start_matches('/')
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        self.all_with_aliases()
+            .filter(|name| name.to_lowercase().starts_with(&typed))
+            .collect()
+    }
+
+    /// Fails if `name` or any of `aliases` is taken, including by each other.
+    #[track_caller]
+    fn check_free(&self, name: &str, aliases: &[&str]) -> Result<(), CommandRegisterError> {
+        let names = std::iter::once(name)
+            .chain(aliases.iter().copied())
+            .collect::<Vec<_>>();
+
+        for (i, &taken) in names.iter().enumerate() {
+            let existing_owner = match self.get(taken) {
+                Some(existing) => existing.owner,
+                // given twice in this registration
+                None if names[..i].contains(&taken) => Location::caller(),
+                None => continue,
+            };
+
+            return Err(CommandRegisterError::Duplicate {
+                name: taken.to_owned(),
+                existing_owner,
+            });
+        }
+
+        Ok(())
+    }
+
+    #[track_caller]
+    fn insert(
+        &mut self,
+        name: String,
+        aliases: &[&str],
+        permissions: Permissions,
+        command: Registered,
+    ) {
+        for alias in aliases {
+            self.aliases.insert((*alias).to_owned(), name.clone());
+        }
+
+        self.commands.insert(name, Entry {
+            permissions,
+            command,
+            aliases: aliases.iter().map(|alias| (*alias).to_owned()).collect(),
+            completion: None,
+            owner: Location::caller(),
+        });
+
+        self.tree_changed = true;
+    }
+
+    /// The names of the commands players with `permissions` may run, without aliases.
+    pub fn allowed(&self, permissions: Permissions) -> impl Iterator<Item = &str> {
+        self.commands
+            .iter()
+            .filter(move |(_, entry)| permissions.allows(entry.permissions))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The names of all commands, without aliases.
     pub fn all(&self) -> impl Iterator<Item = &str> {
         self.commands.keys().map(String::as_str)
     }
+
+    /// All aliases, each with the name of the command it stands for.
+    pub fn aliases(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases
+            .iter()
+            .map(|(alias, name)| (alias.as_str(), name.as_str()))
+    }
+
+    /// The names of all commands, followed by all aliases.
+    pub fn all_with_aliases(&self) -> impl Iterator<Item = &str> {
+        self.all().chain(self.aliases.keys().map(String::as_str))
+    }
+}
+
+impl Entry {
+    /// What the [`CompletionFn`] of the command suggests for `args`, the input after its name,
+    /// along with the offset in `args` the suggestions replace from. `None` if the command has no
+    /// completion function.
+    pub(crate) fn complete(
+        &self,
+        args: &str,
+        world: &World,
+        caller: Entity,
+    ) -> Option<(usize, Vec<String>)> {
+        let completion = self.completion?;
+
+        // the argument being typed, which is empty if the input ends in a space
+        let partial = args.rsplit(char::is_whitespace).next().unwrap_or_default();
+        let offset = args.len() - partial.len();
+        let partial = partial.to_lowercase();
+
+        let suggestions = completion(args.trim_start(), world, caller)
+            .into_iter()
+            .filter(|suggestion| suggestion.to_lowercase().starts_with(&partial))
+            .collect();
+
+        Some((offset, suggestions))
+    }
+}
+
+/// Adds `literal` with the nodes of `signature` under `root` of the command tree, returning the
+/// literal node.
+fn add_nodes(
+    world: &World,
+    root: Entity,
+    literal: &str,
+    signature: &Signature,
+    permissions: Permissions,
+) -> Entity {
+    let node = world
+        .entity()
+        .set(Command::literal(literal))
+        .set(permissions)
+        .child_of_id(root);
+
+    let mut parent = node;
+    for child in signature.nodes() {
+        parent = world.entity().set(child).child_of_id(parent);
+    }
+
+    node.id()
 }
 
 #[derive(Component)]
@@ -33,8 +486,234 @@ pub struct CommandComponentModule;
 impl Module for CommandComponentModule {
     fn module(world: &World) {
         world.component::<CommandRegistry>();
-        world.set(CommandRegistry {
-            commands: IndexMap::default(),
+        world.set(CommandRegistry::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use flecs_ecs::core::{Entity, World};
+    use hyperion::{
+        simulation::command::{Command, Permissions, get_command_packet},
+        valence_protocol::packets::play::command_tree_s2c::{NodeData, Parser, Suggestion},
+    };
+
+    use super::{CommandHandler, CommandRegisterError, CommandRegistry, Dispatch, add_nodes};
+    use crate::signature::Signature;
+
+    fn handler() -> CommandHandler {
+        CommandHandler {
+            on_execute: |_, _, _| {},
+            on_tab_complete: |_, _| {},
+        }
+    }
+
+    #[test]
+    fn aliases_stand_for_their_command() {
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_aliased("tp", &["teleport"], Permissions::OWNER, handler())
+            .unwrap();
+
+        let entry = registry.get("teleport").unwrap();
+        assert_eq!(entry.permissions, Permissions::OWNER);
+        assert_eq!(registry.all().collect::<Vec<_>>(), ["tp"]);
+        assert_eq!(registry.all_with_aliases().collect::<Vec<_>>(), [
+            "tp", "teleport"
+        ]);
+    }
+
+    #[test]
+    fn names_cannot_be_taken_twice() {
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_aliased("tp", &["teleport"], Permissions::PLAYER, handler())
+            .unwrap();
+
+        let mut duplicate = |name: &str| {
+            matches!(
+                registry.register_raw(name, handler()),
+                Err(CommandRegisterError::Duplicate { name: taken, .. }) if taken == name
+            )
+        };
+
+        assert!(duplicate("tp"));
+        assert!(duplicate("teleport"));
+
+        let error = registry
+            .register_raw_aliased("warp", &["go", "go"], Permissions::PLAYER, handler())
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("/go is already registered at ")
+        );
+        assert!(registry.get("warp").is_none());
+    }
+
+    #[test]
+    fn unregistering_removes_aliases() {
+        let world = World::new();
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_aliased("tp", &["teleport"], Permissions::PLAYER, handler())
+            .unwrap();
+
+        assert!(registry.unregister(&world, "teleport"));
+        assert!(registry.get("tp").is_none());
+        assert!(registry.get("teleport").is_none());
+        assert!(!registry.unregister(&world, "tp"));
+
+        registry.register_raw("teleport", handler()).unwrap();
+    }
+
+    #[test]
+    fn signatures_become_argument_nodes() {
+        let world = World::new();
+        world.component::<Command>();
+        let root = world.entity().id();
+
+        let give = Signature::new().integer("amount", 1..=64);
+        add_nodes(&world, root, "give", &give, Permissions::PLAYER);
+        add_nodes(
+            &world,
+            root,
+            "spawn",
+            &Signature::new(),
+            Permissions::PLAYER,
+        );
+
+        let packet = get_command_packet(&world, root);
+        let node = |idx: i32| &packet.commands[usize::try_from(idx).unwrap()];
+        let literal = |name: &str| NodeData::Literal {
+            name: name.to_owned(),
+        };
+
+        let root = node(packet.root_index.0);
+        let literals = root
+            .children
+            .iter()
+            .map(|idx| node(idx.0))
+            .collect::<Vec<_>>();
+        assert_eq!(literals.len(), 2);
+
+        let give = literals
+            .iter()
+            .find(|node| node.data == literal("give"))
+            .unwrap();
+        let [amount] = give.children.as_slice() else {
+            panic!("expected one argument, got {:?}", give.children);
+        };
+        assert_eq!(node(amount.0).data, NodeData::Argument {
+            name: "amount".to_owned(),
+            parser: Parser::Long {
+                min: Some(1),
+                max: Some(64),
+            },
+            suggestion: Some(Suggestion::AskServer),
         });
+
+        let spawn = literals
+            .iter()
+            .find(|node| node.data == literal("spawn"))
+            .unwrap();
+        assert!(spawn.children.is_empty());
+    }
+
+    #[test]
+    fn aliases_can_be_added_later() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let world = World::new();
+        let caller = world.entity().id();
+
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw("spawn", CommandHandler {
+                on_execute: |_, _, _| {
+                    RUNS.fetch_add(1, Ordering::Relaxed);
+                },
+                on_tab_complete: |_, _| {},
+            })
+            .unwrap();
+
+        assert!(registry.register_alias(&world, "hub", "spawn"));
+        assert!(registry.register_alias(&world, "lobby", "hub"));
+        assert!(!registry.register_alias(&world, "home", "warp"));
+        assert!(!registry.register_alias(&world, "hub", "spawn"));
+        assert!(!registry.register_alias(&world, "spawn", "hub"));
+
+        assert_eq!(
+            registry.dispatch("lobby now", &world, caller),
+            Dispatch::Executed
+        );
+        assert_eq!(registry.dispatch("home", &world, caller), Dispatch::Unknown);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+
+        let aliases = registry.aliases().collect::<Vec<_>>();
+        assert_eq!(aliases, [("hub", "spawn"), ("lobby", "spawn")]);
+
+        assert!(registry.unregister(&world, "spawn"));
+        assert_eq!(registry.aliases().count(), 0);
+        assert_eq!(registry.dispatch("hub", &world, caller), Dispatch::Unknown);
+    }
+
+    #[test]
+    fn command_names_are_suggested_by_prefix() {
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_aliased("tp", &["teleport"], Permissions::PLAYER, handler())
+            .unwrap();
+        registry.register_raw("Time", handler()).unwrap();
+        registry.register_raw("give", handler()).unwrap();
+
+        assert_eq!(registry.suggest("t"), ["tp", "Time", "teleport"]);
+        assert_eq!(registry.suggest("/TE"), ["teleport"]);
+        assert_eq!(registry.suggest("ti set day"), ["Time"]);
+        assert_eq!(registry.suggest("").len(), 4);
+        assert!(registry.suggest("kill").is_empty());
+    }
+
+    #[test]
+    fn commands_suggest_their_own_arguments() {
+        fn colors(args: &str, _: &World, _: Entity) -> Vec<String> {
+            if !args.starts_with("set") {
+                return vec!["set".to_owned(), "reset".to_owned()];
+            }
+
+            ["red", "green", "Grey"].map(str::to_owned).to_vec()
+        }
+
+        let world = World::new();
+        let caller = world.entity().id();
+
+        let mut registry = CommandRegistry::default();
+        registry.register_raw("color", handler()).unwrap();
+        assert!(
+            registry
+                .get("color")
+                .unwrap()
+                .complete(" ", &world, caller)
+                .is_none()
+        );
+
+        assert!(registry.set_completion("color", colors));
+        assert!(!registry.set_completion("colour", colors));
+
+        let entry = registry.get("color").unwrap();
+        assert_eq!(
+            entry.complete(" ", &world, caller),
+            Some((1, vec!["set".to_owned(), "reset".to_owned(),]))
+        );
+        assert_eq!(
+            entry.complete(" set g", &world, caller),
+            Some((5, vec!["green".to_owned(), "Grey".to_owned(),]))
+        );
+        assert_eq!(
+            entry.complete(" set blue", &world, caller),
+            Some((5, vec![]))
+        );
     }
 }
@@ -2,10 +2,17 @@
 
 use flecs_ecs::{core::World, macros::Component, prelude::Module};
 
+mod args;
 mod component;
+mod signature;
 mod system;
 
-pub use component::{CommandHandler, CommandRegistry};
+pub use args::Args;
+pub use component::{
+    ArgsExecutor, CommandHandler, CommandRegisterError, CommandRegistry, CompletionFn, Dispatch,
+    ExecutedFn, Executor,
+};
+pub use signature::{Argument, FromArgument, ParseError, ParsedArgs, Reason, Signature};
 
 #[derive(Component)]
 pub struct CommandModule;
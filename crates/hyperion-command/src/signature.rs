@@ -0,0 +1,593 @@
+//! The arguments a command takes, so input is checked before the command runs.
+//!
+//! ```ignore
+//! registry.register(
+//!     world,
+//!     "pay",
+//!     Signature::new().player("target").integer("amount", 1..=1000),
+//!     |args, world, caller| {
+//!         let target = args.get::<Entity>("target")?;
+//!         let amount = args.get::<i32>("amount")?;
+//!         // ...
+//!         Ok(())
+//!     },
+//! );
+//! ```
+
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+use flecs_ecs::core::{Entity, World, WorldGet};
+use hyperion::{
+    simulation::{
+        command::{Command, Parser},
+        roster::PlayerRoster,
+    },
+    valence_protocol::packets::play::command_tree_s2c::StringArg,
+};
+
+/// What a parameter of a [`Signature`] accepts.
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    /// Exactly the word, which is not stored.
+    Literal,
+    Integer(RangeInclusive<i64>),
+    Float(RangeInclusive<f64>),
+    /// The name of a player who is online.
+    Player,
+    /// The rest of the input, spaces included.
+    Greedy,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Parameter {
+    /// The word of a literal, or the name of an argument.
+    name: &'static str,
+    kind: Kind,
+}
+
+/// The parameters of a command, in the order they are given.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[must_use]
+pub struct Signature {
+    parameters: Vec<Parameter>,
+}
+
+impl Signature {
+    pub const fn new() -> Self {
+        Self {
+            parameters: Vec::new(),
+        }
+    }
+
+    fn with(mut self, name: &'static str, kind: Kind) -> Self {
+        assert!(
+            self.parameters
+                .last()
+                .is_none_or(|last| last.kind != Kind::Greedy),
+            "`{name}` comes after a greedy string, which takes the rest of the input"
+        );
+
+        self.parameters.push(Parameter { name, kind });
+        self
+    }
+
+    /// Expects exactly `word`, such as a subcommand.
+    pub fn literal(self, word: &'static str) -> Self {
+        self.with(word, Kind::Literal)
+    }
+
+    /// A whole number within `range`.
+    pub fn integer(self, name: &'static str, range: RangeInclusive<i64>) -> Self {
+        self.with(name, Kind::Integer(range))
+    }
+
+    /// A number within `range`.
+    pub fn float(self, name: &'static str, range: RangeInclusive<f64>) -> Self {
+        self.with(name, Kind::Float(range))
+    }
+
+    /// The name of a player who is online, which is read as their [`Entity`].
+    pub fn player(self, name: &'static str) -> Self {
+        self.with(name, Kind::Player)
+    }
+
+    /// The rest of the input, spaces included. It must be the last parameter.
+    pub fn greedy_string(self, name: &'static str) -> Self {
+        self.with(name, Kind::Greedy)
+    }
+
+    /// How to use `command`, such as `/pay <target> <amount>`.
+    #[must_use]
+    pub fn usage(&self, command: &str) -> String {
+        let mut usage = format!("/{command}");
+
+        for parameter in &self.parameters {
+            usage.push(' ');
+
+            match parameter.kind {
+                Kind::Literal => usage.push_str(parameter.name),
+                Kind::Greedy => usage.push_str(&format!("<{}...>", parameter.name)),
+                _ => usage.push_str(&format!("<{}>", parameter.name)),
+            }
+        }
+
+        usage
+    }
+
+    /// Parses `input`, the arguments after the name of the command. Players are looked up in the
+    /// [`PlayerRoster`] of `world`.
+    pub fn parse(&self, input: &str, world: &World) -> Result<ParsedArgs, ParseError> {
+        world.get::<&PlayerRoster>(|roster| {
+            self.parse_with(input, |name| {
+                roster.by_name(name).map(|player| player.entity)
+            })
+        })
+    }
+
+    /// Parses `input`, looking up players with `find_player`.
+    fn parse_with(
+        &self,
+        input: &str,
+        find_player: impl Fn(&str) -> Option<Entity>,
+    ) -> Result<ParsedArgs, ParseError> {
+        let mut rest = input.trim();
+        let mut args = ParsedArgs::default();
+
+        for parameter in &self.parameters {
+            let name = parameter.name;
+
+            if rest.is_empty() {
+                return Err(ParseError::invalid(name, Reason::Missing));
+            }
+
+            if parameter.kind == Kind::Greedy {
+                args.values.push((name, Argument::String(rest.to_owned())));
+                rest = "";
+                break;
+            }
+
+            let (word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            rest = after.trim_start();
+
+            let value = match &parameter.kind {
+                Kind::Literal if word == name => continue,
+                Kind::Literal => return Err(ParseError::invalid(name, Reason::ExpectedLiteral)),
+                Kind::Integer(range) => {
+                    let value = word.parse::<i64>().map_err(|_| {
+                        ParseError::invalid(name, Reason::NotAnInteger(word.to_owned()))
+                    })?;
+
+                    check_range(name, value, range)?;
+                    Argument::Integer(value)
+                }
+                Kind::Float(range) => {
+                    let value = word
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|value| value.is_finite())
+                        .ok_or_else(|| {
+                            ParseError::invalid(name, Reason::NotANumber(word.to_owned()))
+                        })?;
+
+                    check_range(name, value, range)?;
+                    Argument::Float(value)
+                }
+                Kind::Player => {
+                    let player = find_player(word).ok_or_else(|| {
+                        ParseError::invalid(name, Reason::UnknownPlayer(word.to_owned()))
+                    })?;
+
+                    Argument::Player(player)
+                }
+                Kind::Greedy => unreachable!("greedy strings are handled above"),
+            };
+
+            args.values.push((name, value));
+        }
+
+        if !rest.is_empty() {
+            return Err(ParseError::TrailingInput(rest.to_owned()));
+        }
+
+        Ok(args)
+    }
+}
+
+impl Signature {
+    /// The nodes of the command tree for each parameter, so clients can check and complete the
+    /// input as it is typed.
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = Command> + '_ {
+        self.parameters.iter().map(|parameter| {
+            let name = parameter.name;
+
+            let parser = match &parameter.kind {
+                Kind::Literal => return Command::literal(name),
+                Kind::Integer(range) => Parser::Long {
+                    min: Some(*range.start()),
+                    max: Some(*range.end()),
+                },
+                Kind::Float(range) => Parser::Double {
+                    min: Some(*range.start()),
+                    max: Some(*range.end()),
+                },
+                Kind::Player => Parser::Entity {
+                    single: true,
+                    only_players: true,
+                },
+                Kind::Greedy => Parser::String(StringArg::GreedyPhrase),
+            };
+
+            Command::argument(name, parser)
+        })
+    }
+
+    /// Suggestions for the word `input` ends in, where `input` is everything after the name of the
+    /// command. Returns the offset in `input` the suggestions replace from, along with literals
+    /// and the names of `players` that start with the word. Other arguments have nothing to
+    /// suggest.
+    pub(crate) fn suggest<'a>(
+        &self,
+        input: &str,
+        players: impl IntoIterator<Item = &'a str>,
+    ) -> Option<(usize, Vec<String>)> {
+        let mut words = input.split_whitespace().collect::<Vec<_>>();
+
+        // a word is being typed unless the input ends in a space
+        let partial = if input.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            words.pop().unwrap_or_default()
+        };
+
+        let index = words.len();
+        let offset = input.len() - partial.len();
+
+        let before = self.parameters.get(..index)?;
+        if before
+            .iter()
+            .any(|parameter| parameter.kind == Kind::Greedy)
+        {
+            return None;
+        }
+
+        let parameter = self.parameters.get(index)?;
+        let partial = partial.to_lowercase();
+
+        let suggestions = match parameter.kind {
+            Kind::Literal => vec![parameter.name.to_owned()],
+            Kind::Player => players.into_iter().map(str::to_owned).collect(),
+            _ => return None,
+        };
+
+        let suggestions = suggestions
+            .into_iter()
+            .filter(|suggestion| suggestion.to_lowercase().starts_with(&partial))
+            .collect();
+
+        Some((offset, suggestions))
+    }
+}
+
+fn check_range<T>(name: &'static str, value: T, range: &RangeInclusive<T>) -> Result<(), ParseError>
+where
+    T: PartialOrd + Display,
+{
+    if range.contains(&value) {
+        return Ok(());
+    }
+
+    Err(ParseError::invalid(name, Reason::OutOfRange {
+        value: value.to_string(),
+        min: range.start().to_string(),
+        max: range.end().to_string(),
+    }))
+}
+
+/// The value of a parsed argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Argument {
+    Integer(i64),
+    Float(f64),
+    Player(Entity),
+    String(String),
+}
+
+/// A type an [`Argument`] can be read as with [`ParsedArgs::get`].
+pub trait FromArgument: Sized {
+    /// What the type is called in errors, such as `a whole number`.
+    const DESCRIPTION: &'static str;
+
+    /// `None` if `argument` is of another kind or does not fit.
+    fn from_argument(argument: &Argument) -> Option<Self>;
+}
+
+macro_rules! impl_from_argument_for_integers {
+    ($($ty:ty),*) => {
+        $(
+            impl FromArgument for $ty {
+                const DESCRIPTION: &'static str =
+                    concat!("a whole number that fits in ", stringify!($ty));
+
+                fn from_argument(argument: &Argument) -> Option<Self> {
+                    match argument {
+                        Argument::Integer(value) => Self::try_from(*value).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_argument_for_integers!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+impl FromArgument for f64 {
+    const DESCRIPTION: &'static str = "a number";
+
+    fn from_argument(argument: &Argument) -> Option<Self> {
+        match argument {
+            Argument::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for f32 {
+    const DESCRIPTION: &'static str = "a number";
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "losing precision is expected when asking for an f32"
+    )]
+    fn from_argument(argument: &Argument) -> Option<Self> {
+        f64::from_argument(argument).map(|value| value as Self)
+    }
+}
+
+impl FromArgument for Entity {
+    const DESCRIPTION: &'static str = "a player";
+
+    fn from_argument(argument: &Argument) -> Option<Self> {
+        match argument {
+            Argument::Player(player) => Some(*player),
+            _ => None,
+        }
+    }
+}
+
+impl FromArgument for String {
+    const DESCRIPTION: &'static str = "a string";
+
+    fn from_argument(argument: &Argument) -> Option<Self> {
+        match argument {
+            Argument::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The arguments of a command, by name, as checked by [`Signature::parse`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedArgs {
+    values: Vec<(&'static str, Argument)>,
+}
+
+impl ParsedArgs {
+    /// The argument called `name`, without converting it.
+    #[must_use]
+    pub fn argument(&self, name: &str) -> Option<&Argument> {
+        self.values
+            .iter()
+            .find(|(argument, _)| *argument == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The argument called `name` as a `T`. As the input was already checked, this only fails if
+    /// the signature has no such argument or it is of another kind.
+    pub fn get<T: FromArgument>(&self, name: &'static str) -> Result<T, ParseError> {
+        let argument = self
+            .argument(name)
+            .ok_or_else(|| ParseError::invalid(name, Reason::Missing))?;
+
+        T::from_argument(argument).ok_or_else(|| {
+            ParseError::invalid(name, Reason::WrongType {
+                expected: T::DESCRIPTION,
+            })
+        })
+    }
+}
+
+/// Why an argument is invalid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Missing,
+    /// The input is not the literal word.
+    ExpectedLiteral,
+    NotAnInteger(String),
+    NotANumber(String),
+    OutOfRange {
+        value: String,
+        min: String,
+        max: String,
+    },
+    /// No player with this name is online.
+    UnknownPlayer(String),
+    /// The argument was read as another type than it was parsed as.
+    WrongType {
+        expected: &'static str,
+    },
+    /// A quoted argument has no closing quote.
+    UnclosedQuote,
+}
+
+/// Why the input of a command does not match its [`Signature`]. It is worded to be shown to the
+/// player who sent the command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The argument or literal `argument` is missing or invalid.
+    Invalid {
+        argument: &'static str,
+        reason: Reason,
+    },
+    /// There is more input than the signature takes.
+    TrailingInput(String),
+}
+
+impl ParseError {
+    pub(crate) const fn invalid(argument: &'static str, reason: Reason) -> Self {
+        Self::Invalid { argument, reason }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (argument, reason) = match self {
+            Self::Invalid { argument, reason } => (argument, reason),
+            Self::TrailingInput(input) => return write!(f, "Unexpected `{input}`"),
+        };
+
+        match reason {
+            Reason::Missing => write!(f, "Missing <{argument}>"),
+            Reason::ExpectedLiteral => write!(f, "Expected `{argument}`"),
+            Reason::NotAnInteger(input) => {
+                write!(f, "<{argument}> must be a whole number, not `{input}`")
+            }
+            Reason::NotANumber(input) => write!(f, "<{argument}> must be a number, not `{input}`"),
+            Reason::OutOfRange { value, min, max } => {
+                write!(
+                    f,
+                    "<{argument}> must be between {min} and {max}, not {value}"
+                )
+            }
+            Reason::UnknownPlayer(name) => write!(f, "No player called `{name}` is online"),
+            Reason::WrongType { expected } => write!(f, "<{argument}> is not {expected}"),
+            Reason::UnclosedQuote => write!(f, "<{argument}> is missing its closing quote"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::{Entity, World};
+
+    use super::{Argument, ParseError, ParsedArgs, Reason, Signature};
+
+    fn pay() -> Signature {
+        Signature::new()
+            .player("target")
+            .integer("amount", 1..=1000)
+            .greedy_string("reason")
+    }
+
+    /// Parses `input` with Alex as the only player online.
+    fn parse(signature: &Signature, input: &str, alex: Entity) -> Result<ParsedArgs, ParseError> {
+        signature.parse_with(input, |name| (name == "Alex").then_some(alex))
+    }
+
+    #[test]
+    fn arguments_are_parsed_by_kind() {
+        let world = World::new();
+        let alex = world.entity().id();
+
+        let args = parse(&pay(), "Alex 25  for the  sword ", alex).unwrap();
+
+        assert_eq!(args.get::<Entity>("target"), Ok(alex));
+        assert_eq!(args.get::<i32>("amount"), Ok(25));
+        assert_eq!(args.get::<String>("reason").unwrap(), "for the  sword");
+
+        let signature = Signature::new().literal("set").float("speed", 0.0..=1.0);
+        let args = parse(&signature, "set 0.5", alex).unwrap();
+        assert_eq!(args.argument("speed"), Some(&Argument::Float(0.5)));
+        assert_eq!(args.argument("set"), None);
+    }
+
+    #[test]
+    fn errors_name_the_invalid_argument() {
+        let world = World::new();
+        let alex = world.entity().id();
+
+        let error = |input| parse(&pay(), input, alex).unwrap_err();
+
+        assert_eq!(error(""), ParseError::Invalid {
+            argument: "target",
+            reason: Reason::Missing,
+        });
+        assert_eq!(error("Steve 5 gift"), ParseError::Invalid {
+            argument: "target",
+            reason: Reason::UnknownPlayer("Steve".to_owned()),
+        });
+        assert_eq!(error("Alex five gift"), ParseError::Invalid {
+            argument: "amount",
+            reason: Reason::NotAnInteger("five".to_owned()),
+        });
+        assert_eq!(
+            error("Alex 5000 gift").to_string(),
+            "<amount> must be between 1 and 1000, not 5000"
+        );
+
+        let signature = Signature::new().literal("set").float("speed", 0.0..=1.0);
+        let error = |input| parse(&signature, input, alex).unwrap_err();
+
+        assert_eq!(error("get 0.5").to_string(), "Expected `set`");
+        assert_eq!(
+            error("set NaN").to_string(),
+            "<speed> must be a number, not `NaN`"
+        );
+        assert_eq!(
+            error("set 0.5 fast"),
+            ParseError::TrailingInput("fast".to_owned())
+        );
+    }
+
+    #[test]
+    fn arguments_are_read_as_the_kind_they_were_parsed_as() {
+        let world = World::new();
+        let signature = Signature::new().integer("amount", 0..=1000);
+        let args = parse(&signature, "300", world.entity().id()).unwrap();
+
+        assert_eq!(args.get::<u16>("amount"), Ok(300));
+        assert!(args.get::<u8>("amount").is_err());
+        assert!(args.get::<String>("amount").is_err());
+        assert!(args.get::<i32>("missing").is_err());
+    }
+
+    #[test]
+    fn usage_lists_every_parameter() {
+        let signature = Signature::new()
+            .literal("give")
+            .player("target")
+            .greedy_string("message");
+
+        assert_eq!(signature.usage("mail"), "/mail give <target> <message...>");
+    }
+
+    #[test]
+    fn players_and_literals_are_suggested() {
+        let players = ["Alex", "alice", "Steve"];
+        let mail = Signature::new()
+            .literal("give")
+            .player("target")
+            .greedy_string("message");
+
+        assert_eq!(
+            mail.suggest(" g", players),
+            Some((1, vec!["give".to_owned()]))
+        );
+        assert_eq!(
+            mail.suggest(" give al", players),
+            Some((6, vec!["Alex".to_owned(), "alice".to_owned()]))
+        );
+        assert_eq!(
+            mail.suggest(" give ", players)
+                .map(|(_, names)| names.len()),
+            Some(3)
+        );
+        assert_eq!(mail.suggest(" give Steve hi", players), None);
+        assert_eq!(pay().suggest(" Alex 2", players), None);
+    }
+}
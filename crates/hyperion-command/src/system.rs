@@ -1,19 +1,227 @@
-use std::{fmt::Write, sync::OnceLock};
+use std::{sync::OnceLock, time::Instant};
 
 use flecs_ecs::{
-    core::{EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet},
+    core::{Entity, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet},
     macros::{Component, system},
     prelude::Module,
 };
 use hyperion::{
-    net::agnostic,
-    simulation::event,
-    storage::{EventQueue, GlobalEventHandlers},
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{
+        PacketState,
+        command::{Console, Permissions, resend_command_tree},
+        event,
+        handlers::PacketSwitchQuery,
+        roster::PlayerRoster,
+    },
+    storage::{CommandCompletionRequest, EventQueue, GlobalEventHandlers},
     system_registry::SystemId,
+    valence_protocol::{
+        VarInt,
+        packets::play::{self, command_suggestions_s2c::CommandSuggestionsMatch},
+    },
 };
 use regex::Regex;
 
-use crate::component::CommandRegistry;
+use crate::{
+    args::Args,
+    component::{CommandRegistry, Dispatch, Entry, Executor, Registered},
+    signature::Signature,
+};
+
+/// Sends `msg` to `player`, or logs it if they have no connection, like the [`Console`].
+fn send_message(world: &World, player: Entity, msg: String) {
+    let Some(stream) = player
+        .entity_view(world)
+        .try_get::<&NetworkStreamRef>(|stream| *stream)
+    else {
+        tracing::info!("{msg}");
+        return;
+    };
+
+    let chat = agnostic::chat(msg);
+
+    world.get::<&Compose>(|compose| {
+        compose.unicast(&chat, stream, SystemId(8), world).unwrap();
+    });
+}
+
+/// Runs a command registered with a signature, or tells `caller` why its `input` is wrong.
+fn execute_typed(
+    name: &str,
+    input: &str,
+    signature: &Signature,
+    on_execute: Executor,
+    world: &World,
+    caller: Entity,
+) {
+    let args = match signature.parse(input, world) {
+        Ok(args) => args,
+        Err(e) => {
+            let usage = signature.usage(name);
+            let msg = msg!(
+                caller.entity_view(world),
+                "command.usage",
+                error = e,
+                usage = usage
+            );
+            send_message(world, caller, msg);
+            return;
+        }
+    };
+
+    if let Err(e) = on_execute(&args, world, caller) {
+        tracing::debug!("command {name} failed: {e:#}");
+        send_message(world, caller, format!("§c{e}"));
+    }
+}
+
+/// Answers the completion request `id` with `suggestions`, which replace `length` bytes of the
+/// request from `start` on.
+fn send_suggestions(
+    query: &PacketSwitchQuery<'_>,
+    id: i32,
+    start: usize,
+    length: usize,
+    suggestions: &[String],
+) -> anyhow::Result<()> {
+    let matches = suggestions
+        .iter()
+        .map(|suggestion| CommandSuggestionsMatch {
+            suggested_match: suggestion,
+            tooltip: None,
+        })
+        .collect();
+
+    let packet = play::CommandSuggestionsS2c {
+        id: VarInt(id),
+        start: VarInt(i32::try_from(start)?),
+        length: VarInt(i32::try_from(length)?),
+        matches,
+    };
+
+    query
+        .compose
+        .unicast(&packet, query.io_ref, SystemId(0), query.world)?;
+
+    Ok(())
+}
+
+/// Suggests the online players or literal that can come next in `args`, the input after the name
+/// of a command registered with a signature, which starts at `args_start` of the request.
+fn suggest_arguments(
+    query: &PacketSwitchQuery<'_>,
+    completion: &CommandCompletionRequest<'_>,
+    signature: &Signature,
+    args_start: usize,
+) -> anyhow::Result<()> {
+    let args = &completion.query[args_start..];
+
+    let suggestions = query.world.get::<&PlayerRoster>(|roster| {
+        let players = roster.iter().map(|player| &*player.name);
+        signature.suggest(args, players)
+    });
+
+    let Some((offset, suggestions)) = suggestions else {
+        return Ok(());
+    };
+
+    let start = args_start + offset;
+    send_suggestions(
+        query,
+        completion.id,
+        start,
+        args.len() - offset,
+        &suggestions,
+    )
+}
+
+/// Suggests what the [`crate::CompletionFn`] of `entry` returns for the input after the name of
+/// the command, which starts at `args_start` of the request. `None` if the command has none.
+fn complete_arguments(
+    query: &PacketSwitchQuery<'_>,
+    completion: &CommandCompletionRequest<'_>,
+    entry: &Entry,
+    args_start: usize,
+) -> Option<anyhow::Result<()>> {
+    let args = &completion.query[args_start..];
+    let (offset, suggestions) = entry.complete(args, query.world, query.id)?;
+
+    let start = args_start + offset;
+    Some(send_suggestions(
+        query,
+        completion.id,
+        start,
+        args.len() - offset,
+        &suggestions,
+    ))
+}
+
+fn permissions_of(world: &World, player: Entity) -> Permissions {
+    player
+        .entity_view(world)
+        .try_get::<&Permissions>(|permissions| *permissions)
+        .unwrap_or_default()
+}
+
+impl CommandRegistry {
+    /// Runs the command `raw`, which starts with its name, on behalf of `caller` if their
+    /// [`Permissions`] allow it. What went wrong is left for the caller to tell the player, except
+    /// for input that does not match the signature of the command, which is answered right away.
+    ///
+    /// Commands that ran are passed to the [`crate::ExecutedFn`] set with
+    /// [`CommandRegistry::set_on_executed`], if any.
+    pub fn dispatch(&self, raw: &str, world: &World, caller: Entity) -> Dispatch {
+        let Some(first_word) = raw.split_whitespace().next() else {
+            return Dispatch::Unknown;
+        };
+
+        let Some((name, entry)) = self.get_named(first_word) else {
+            tracing::debug!("command {first_word} not found");
+            return Dispatch::Unknown;
+        };
+
+        if !permissions_of(world, caller).allows(entry.permissions) {
+            return Dispatch::Denied;
+        }
+
+        tracing::debug!("executing command {first_word}");
+
+        let input = raw
+            .trim_start()
+            .strip_prefix(first_word)
+            .unwrap_or_default();
+
+        // without a callback there is no need to look at the clock
+        let timing = self
+            .on_executed
+            .map(|on_executed| (on_executed, Instant::now()));
+
+        match &entry.command {
+            Registered::Raw(handler) => (handler.on_execute)(raw, world, caller),
+            Registered::Args(on_execute) => {
+                if let Err(e) = on_execute(&mut Args::new(input), world, caller) {
+                    tracing::debug!("command {first_word} failed: {e:#}");
+                    send_message(world, caller, format!("§c{e}"));
+                }
+            }
+            Registered::Typed {
+                signature,
+                on_execute,
+                ..
+            } => {
+                execute_typed(first_word, input, signature, *on_execute, world, caller);
+            }
+        }
+
+        if let Some((on_executed, start)) = timing {
+            on_executed(name, caller, start.elapsed());
+        }
+
+        Dispatch::Executed
+    }
+}
 
 #[derive(Component)]
 pub struct CommandSystemModule;
@@ -30,43 +238,38 @@ impl Module for CommandSystemModule {
         )
         .each_iter(|it, _, (event_queue, registry)| {
             let world = it.world();
-            for event::Command { raw, by } in event_queue.drain() {
-                let Some(first_word) = raw.split_whitespace().next() else {
-                    tracing::warn!("command is empty");
-                    continue;
-                };
-
-                let Some(command) = registry.commands.get(first_word) else {
-                    tracing::debug!("command {first_word} not found");
 
-                    let mut msg = String::new();
-                    write!(&mut msg, "§cAvailable commands: §r[").unwrap();
+            let run = |raw: &str, by: Entity| {
+                let msg = match registry.dispatch(raw, &world, by) {
+                    Dispatch::Executed => return,
+                    Dispatch::Denied => msg!(by.entity_view(&world), "command.denied"),
+                    Dispatch::Unknown => {
+                        let permissions = permissions_of(&world, by);
+                        let commands = registry
+                            .allowed(permissions)
+                            .intersperse(", ")
+                            .collect::<String>();
 
-                    for w in registry.all().intersperse(", ") {
-                        write!(&mut msg, "{w}").unwrap();
+                        msg!(
+                            by.entity_view(&world),
+                            "command.unknown",
+                            commands = commands
+                        )
                     }
-
-                    write!(&mut msg, "]").unwrap();
-
-                    let chat = agnostic::chat(msg);
-
-                    world.get::<&hyperion::net::Compose>(|compose| {
-                        by.entity_view(world)
-                            .get::<&hyperion::net::NetworkStreamRef>(|stream| {
-                                compose
-                                    .unicast(&chat, *stream, SystemId(8), &world)
-                                    .unwrap();
-                            });
-                    });
-
-                    continue;
                 };
 
-                tracing::debug!("executing command {first_word}");
+                send_message(&world, by, msg);
+            };
 
-                let command = command.on_execute;
-                command(raw, &world, by);
+            for event::Command { raw, by } in event_queue.drain() {
+                run(raw, by);
             }
+
+            world.try_get::<&Console>(|console| {
+                for raw in console.drain() {
+                    run(&raw, console.entity);
+                }
+            });
         });
 
         let command_regex = Regex::new(r"/(?P<command>\w+).*").unwrap();
@@ -86,16 +289,161 @@ impl Module for CommandSystemModule {
                     return;
                 };
 
-                let command = command.as_str();
-
                 query.world.get::<&CommandRegistry>(|registry| {
-                    let Some(cmd) = registry.commands.get(command) else {
+                    let permissions = permissions_of(query.world, query.id);
+                    let allowed = |name: &str| {
+                        registry
+                            .get(name)
+                            .filter(|entry| permissions.allows(entry.permissions))
+                    };
+
+                    let entry = allowed(command.as_str());
+
+                    let completed = entry.and_then(|entry| {
+                        complete_arguments(query, completion, entry, command.end())
+                    });
+
+                    if let Some(result) = completed {
+                        if let Err(e) = result {
+                            tracing::warn!("failed to suggest completions: {e}");
+                        }
+
                         return;
+                    }
+
+                    let result = match entry.map(|entry| &entry.command) {
+                        Some(Registered::Raw(cmd)) => {
+                            let on_tab = cmd.on_tab_complete;
+                            on_tab(query, completion);
+                            Ok(())
+                        }
+                        Some(Registered::Typed { signature, .. }) => {
+                            suggest_arguments(query, completion, signature, command.end())
+                        }
+                        // the name is still being typed
+                        None if command.end() == input.len() => {
+                            let names = registry
+                                .suggest(command.as_str())
+                                .into_iter()
+                                .filter(|name| allowed(name).is_some())
+                                .map(str::to_owned)
+                                .collect::<Vec<_>>();
+
+                            let (start, length) = (command.start(), command.len());
+                            send_suggestions(query, completion.id, start, length, &names)
+                        }
+                        // there is nothing to suggest without a signature
+                        Some(Registered::Args(_)) | None => Ok(()),
                     };
-                    let on_tab = cmd.on_tab_complete;
-                    on_tab(query, completion);
+
+                    if let Err(e) = result {
+                        tracing::warn!("failed to suggest completions: {e}");
+                    }
                 });
             });
         });
+
+        let players = world
+            .query::<()>()
+            .with::<&NetworkStreamRef>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!("resend_command_tree", world, &mut CommandRegistry($), &Compose($)).each_iter(
+            move |_, _, (registry, compose)| {
+                if !std::mem::take(&mut registry.tree_changed) {
+                    return;
+                }
+
+                // players who are still joining get the new tree with the other join packets
+                players.each_entity(|player, ()| {
+                    if let Err(e) = resend_command_tree(player, compose) {
+                        tracing::warn!("failed to resend the command tree: {e}");
+                    }
+                });
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use flecs_ecs::core::{Entity, World};
+    use hyperion::simulation::command::Permissions;
+
+    use crate::component::{CommandHandler, CommandRegistry, Dispatch};
+
+    #[test]
+    fn dispatch_checks_the_permissions_of_the_caller() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let world = World::new();
+        let player = world.entity().id();
+        let owner = world.entity().set(Permissions::OWNER).id();
+
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_with("stop", Permissions::OWNER, CommandHandler {
+                on_execute: |_, _, _| {
+                    RUNS.fetch_add(1, Ordering::Relaxed);
+                },
+                on_tab_complete: |_, _| {},
+            })
+            .unwrap();
+
+        assert_eq!(registry.dispatch("stop", &world, player), Dispatch::Denied);
+        assert_eq!(RUNS.load(Ordering::Relaxed), 0);
+
+        assert_eq!(
+            registry.dispatch("stop now", &world, owner),
+            Dispatch::Executed
+        );
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+
+        assert_eq!(registry.dispatch("halt", &world, owner), Dispatch::Unknown);
+        assert_eq!(registry.dispatch("   ", &world, owner), Dispatch::Unknown);
+    }
+
+    #[test]
+    fn executed_commands_are_reported_by_name() {
+        static EXECUTED: Mutex<Vec<(String, Entity)>> = Mutex::new(Vec::new());
+
+        let world = World::new();
+        let player = world.entity().id();
+
+        let mut registry = CommandRegistry::default();
+        registry
+            .register_raw_aliased("tp", &["teleport"], Permissions::PLAYER, CommandHandler {
+                on_execute: |_, _, _| {},
+                on_tab_complete: |_, _| {},
+            })
+            .unwrap();
+        registry
+            .register_raw_with("stop", Permissions::OWNER, CommandHandler {
+                on_execute: |_, _, _| {},
+                on_tab_complete: |_, _| {},
+            })
+            .unwrap();
+
+        registry.dispatch("tp", &world, player);
+        assert!(EXECUTED.lock().unwrap().is_empty());
+
+        registry.set_on_executed(Some(|name, caller, _| {
+            EXECUTED.lock().unwrap().push((name.to_owned(), caller));
+        }));
+
+        assert_eq!(
+            registry.dispatch("teleport 0 64 0", &world, player),
+            Dispatch::Executed
+        );
+        assert_eq!(registry.dispatch("stop", &world, player), Dispatch::Denied);
+        assert_eq!(registry.dispatch("warp", &world, player), Dispatch::Unknown);
+
+        assert_eq!(*EXECUTED.lock().unwrap(), [("tp".to_owned(), player)]);
     }
 }
@@ -0,0 +1,610 @@
+//! What an anvil makes of the two items put into it: renaming, repairing and combining
+//! enchantments, with the same level costs as vanilla.
+
+use valence_protocol::{
+    ItemKind, ItemStack,
+    nbt::{Compound, List, Value},
+};
+
+use crate::ItemTags;
+
+/// Anvil operations costing this many levels or more are "too expensive" and cannot be done.
+pub const TOO_EXPENSIVE: u32 = 40;
+
+/// Which items an enchantment can be put on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Target {
+    Armor,
+    Helmet,
+    Leggings,
+    Boots,
+    Sword,
+    /// Swords and axes, for sharpness and the like.
+    Damage,
+    Digger,
+    Breakable,
+    Wearable,
+    Bow,
+    Crossbow,
+    Trident,
+    FishingRod,
+}
+
+impl Target {
+    fn accepts(self, kind: ItemKind) -> bool {
+        let name = kind.to_str();
+
+        let helmet = name.ends_with("_helmet");
+        let armor = helmet
+            || name.ends_with("_chestplate")
+            || name.ends_with("_leggings")
+            || name.ends_with("_boots");
+
+        match self {
+            Self::Armor => armor,
+            Self::Helmet => helmet,
+            Self::Leggings => name.ends_with("_leggings"),
+            Self::Boots => name.ends_with("_boots"),
+            Self::Sword => name.ends_with("_sword"),
+            Self::Damage => name.ends_with("_sword") || name.ends_with("_axe"),
+            Self::Digger => ["_pickaxe", "_shovel", "_axe", "_hoe"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix)),
+            Self::Breakable => kind.max_durability() > 0,
+            Self::Wearable => armor || kind == ItemKind::Elytra,
+            Self::Bow => kind == ItemKind::Bow,
+            Self::Crossbow => kind == ItemKind::Crossbow,
+            Self::Trident => kind == ItemKind::Trident,
+            Self::FishingRod => kind == ItemKind::FishingRod,
+        }
+    }
+}
+
+struct Enchantment {
+    id: &'static str,
+    max_level: u16,
+    /// Levels charged per level of the enchantment, from its rarity.
+    weight: u32,
+    target: Target,
+    /// Enchantments in the same group cannot be combined.
+    group: Option<&'static str>,
+}
+
+const fn enchantment(
+    id: &'static str,
+    max_level: u16,
+    weight: u32,
+    target: Target,
+    group: Option<&'static str>,
+) -> Enchantment {
+    Enchantment {
+        id,
+        max_level,
+        weight,
+        target,
+        group,
+    }
+}
+
+const COMMON: u32 = 1;
+const UNCOMMON: u32 = 2;
+const RARE: u32 = 4;
+const VERY_RARE: u32 = 8;
+
+const ENCHANTMENTS: &[Enchantment] = &[
+    enchantment("protection", 4, COMMON, Target::Armor, Some("protection")),
+    enchantment(
+        "fire_protection",
+        4,
+        UNCOMMON,
+        Target::Armor,
+        Some("protection"),
+    ),
+    enchantment(
+        "blast_protection",
+        4,
+        RARE,
+        Target::Armor,
+        Some("protection"),
+    ),
+    enchantment(
+        "projectile_protection",
+        4,
+        UNCOMMON,
+        Target::Armor,
+        Some("protection"),
+    ),
+    enchantment("feather_falling", 4, UNCOMMON, Target::Boots, None),
+    enchantment("respiration", 3, RARE, Target::Helmet, None),
+    enchantment("aqua_affinity", 1, RARE, Target::Helmet, None),
+    enchantment("thorns", 3, VERY_RARE, Target::Armor, None),
+    enchantment(
+        "depth_strider",
+        3,
+        RARE,
+        Target::Boots,
+        Some("water_walking"),
+    ),
+    enchantment(
+        "frost_walker",
+        2,
+        RARE,
+        Target::Boots,
+        Some("water_walking"),
+    ),
+    enchantment("soul_speed", 3, VERY_RARE, Target::Boots, None),
+    enchantment("swift_sneak", 3, VERY_RARE, Target::Leggings, None),
+    enchantment("binding_curse", 1, VERY_RARE, Target::Wearable, None),
+    enchantment("sharpness", 5, COMMON, Target::Damage, Some("damage")),
+    enchantment("smite", 5, UNCOMMON, Target::Damage, Some("damage")),
+    enchantment(
+        "bane_of_arthropods",
+        5,
+        UNCOMMON,
+        Target::Damage,
+        Some("damage"),
+    ),
+    enchantment("knockback", 2, UNCOMMON, Target::Sword, None),
+    enchantment("fire_aspect", 2, RARE, Target::Sword, None),
+    enchantment("looting", 3, RARE, Target::Sword, None),
+    enchantment("sweeping", 3, RARE, Target::Sword, None),
+    enchantment("efficiency", 5, COMMON, Target::Digger, None),
+    enchantment("silk_touch", 1, VERY_RARE, Target::Digger, Some("drops")),
+    enchantment("fortune", 3, RARE, Target::Digger, Some("drops")),
+    enchantment("unbreaking", 3, UNCOMMON, Target::Breakable, None),
+    enchantment("mending", 1, RARE, Target::Breakable, Some("mending")),
+    enchantment("vanishing_curse", 1, VERY_RARE, Target::Breakable, None),
+    enchantment("power", 5, COMMON, Target::Bow, None),
+    enchantment("punch", 2, RARE, Target::Bow, None),
+    enchantment("flame", 1, RARE, Target::Bow, None),
+    enchantment("infinity", 1, VERY_RARE, Target::Bow, Some("mending")),
+    enchantment("multishot", 1, RARE, Target::Crossbow, Some("projectiles")),
+    enchantment("piercing", 4, COMMON, Target::Crossbow, Some("projectiles")),
+    enchantment("quick_charge", 3, UNCOMMON, Target::Crossbow, None),
+    enchantment("loyalty", 3, UNCOMMON, Target::Trident, None),
+    enchantment("impaling", 5, RARE, Target::Trident, None),
+    enchantment("riptide", 3, RARE, Target::Trident, None),
+    enchantment("channeling", 1, VERY_RARE, Target::Trident, None),
+    enchantment("luck_of_the_sea", 3, RARE, Target::FishingRod, None),
+    enchantment("lure", 3, RARE, Target::FishingRod, None),
+];
+
+fn find_enchantment(id: &str) -> Option<&'static Enchantment> {
+    let id = id.strip_prefix("minecraft:").unwrap_or(id);
+    ENCHANTMENTS.iter().find(|enchantment| enchantment.id == id)
+}
+
+fn compatible(a: &Enchantment, b: &Enchantment) -> bool {
+    // riptide pulls the player along, so it excludes both other trident enchantments
+    let riptide = |e: &Enchantment| e.id == "riptide";
+    let riptide_conflict = |e: &Enchantment| e.id == "loyalty" || e.id == "channeling";
+
+    if (riptide(a) && riptide_conflict(b)) || (riptide(b) && riptide_conflict(a)) {
+        return false;
+    }
+
+    a.group.is_none() || a.group != b.group
+}
+
+/// The enchantments on a stack, or stored in it if it is an enchanted book.
+fn enchantments(stack: &ItemStack) -> Vec<(String, u16)> {
+    let Some(Value::List(List::Compound(list))) = stack
+        .nbt
+        .as_ref()
+        .and_then(|nbt| nbt.get(enchantments_key(stack.item)))
+    else {
+        return Vec::new();
+    };
+
+    list.iter()
+        .filter_map(|entry| {
+            let Some(Value::String(id)) = entry.get("id") else {
+                return None;
+            };
+
+            let level = match entry.get("lvl") {
+                Some(&Value::Short(level)) => u16::try_from(level).ok()?,
+                Some(&Value::Int(level)) => u16::try_from(level).ok()?,
+                _ => return None,
+            };
+
+            let id = id.strip_prefix("minecraft:").unwrap_or(id).to_owned();
+
+            Some((id, level))
+        })
+        .collect()
+}
+
+fn set_enchantments(stack: &mut ItemStack, enchantments: &[(String, u16)]) {
+    let key = enchantments_key(stack.item);
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    if enchantments.is_empty() {
+        nbt.remove(key);
+        return;
+    }
+
+    let list = enchantments
+        .iter()
+        .map(|(id, level)| {
+            let mut entry = Compound::new();
+            entry.insert("id", format!("minecraft:{id}"));
+            entry.insert("lvl", i16::try_from(*level).unwrap_or(i16::MAX));
+            entry
+        })
+        .collect();
+
+    nbt.insert(key, List::Compound(list));
+}
+
+const fn enchantments_key(kind: ItemKind) -> &'static str {
+    match kind {
+        ItemKind::EnchantedBook => "StoredEnchantments",
+        _ => "Enchantments",
+    }
+}
+
+fn int_tag(stack: &ItemStack, key: &str) -> i32 {
+    match stack.nbt.as_ref().and_then(|nbt| nbt.get(key)) {
+        Some(&Value::Int(value)) => value,
+        _ => 0,
+    }
+}
+
+fn set_int_tag(stack: &mut ItemStack, key: &str, value: i32) {
+    stack
+        .nbt
+        .get_or_insert_with(Compound::new)
+        .insert(key, value);
+}
+
+/// The prior-work penalty, which doubles every time an item goes through an anvil.
+fn repair_cost(stack: &ItemStack) -> u32 {
+    u32::try_from(int_tag(stack, "RepairCost")).unwrap_or(0)
+}
+
+/// The custom name of a stack, as plain text.
+#[must_use]
+pub fn custom_name(stack: &ItemStack) -> Option<String> {
+    let Some(Value::Compound(display)) = stack.nbt.as_ref()?.get("display") else {
+        return None;
+    };
+
+    let Some(Value::String(name)) = display.get("Name") else {
+        return None;
+    };
+
+    let json: serde_json::Value = serde_json::from_str(name).ok()?;
+
+    match json {
+        serde_json::Value::String(text) => Some(text),
+        json => json.get("text")?.as_str().map(str::to_owned),
+    }
+}
+
+fn set_custom_name(stack: &mut ItemStack, name: Option<&str>) {
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    let mut display = match nbt.remove("display") {
+        Some(Value::Compound(display)) => display,
+        _ => Compound::new(),
+    };
+
+    match name {
+        Some(name) => {
+            let json = serde_json::json!({ "text": name }).to_string();
+            display.insert("Name", json);
+        }
+        None => {
+            display.remove("Name");
+        }
+    }
+
+    if !display.is_empty() {
+        nbt.insert("display", display);
+    }
+}
+
+/// Whether `material` repairs `tool`, e.g. diamonds for diamond tools and armor.
+#[must_use]
+pub fn is_repair_material(tool: ItemKind, material: ItemKind) -> bool {
+    let tag = |name: &str| {
+        ItemTags::vanilla()
+            .get(name)
+            .is_some_and(|kinds| kinds.contains(&material))
+    };
+
+    match tool {
+        ItemKind::Shield => tag("minecraft:planks"),
+        ItemKind::Elytra => material == ItemKind::PhantomMembrane,
+        ItemKind::TurtleHelmet => material == ItemKind::Scute,
+        _ => match tool.to_str().split('_').next() {
+            Some("wooden") => tag("minecraft:planks"),
+            Some("stone") => tag("minecraft:stone_tool_materials"),
+            Some("leather") => material == ItemKind::Leather,
+            Some("chainmail" | "iron") => material == ItemKind::IronIngot,
+            Some("golden") => material == ItemKind::GoldIngot,
+            Some("diamond") => material == ItemKind::Diamond,
+            Some("netherite") => material == ItemKind::NetheriteIngot,
+            _ => false,
+        },
+    }
+}
+
+/// How many items of `right` a repair with raw materials uses up, or `0` if `right` is not a
+/// repair material for `left`. Anything else put into the right slot is used up entirely.
+#[must_use]
+pub fn repair_units(left: &ItemStack, right: &ItemStack) -> i8 {
+    let max = i32::from(left.item.max_durability());
+
+    if max == 0 || right.is_empty() || !is_repair_material(left.item, right.item) {
+        return 0;
+    }
+
+    let mut damage = int_tag(left, "Damage");
+    let mut units = 0;
+
+    while damage > 0 && units < right.count {
+        damage -= damage.min(max / 4);
+        units += 1;
+    }
+
+    units
+}
+
+/// The item an anvil makes of `left` and `right`, and how many levels it costs. `name` is what the
+/// player typed into the name field, or `None` if they did not touch it; an empty name removes a
+/// custom name.
+///
+/// Returns `None` if the anvil cannot do anything with the items, or if it would be too
+/// expensive.
+#[must_use]
+pub fn anvil_result(
+    left: &ItemStack,
+    right: &ItemStack,
+    name: Option<&str>,
+) -> Option<(ItemStack, u32)> {
+    if left.is_empty() {
+        return None;
+    }
+
+    let mut result = left.clone();
+    let mut cost = 0;
+    let mut base_cost = repair_cost(left);
+
+    if !right.is_empty() {
+        base_cost += repair_cost(right);
+
+        let max = i32::from(left.item.max_durability());
+        let right_enchantments = enchantments(right);
+        let right_is_book = right.item == ItemKind::EnchantedBook && !right_enchantments.is_empty();
+
+        if max > 0 && is_repair_material(left.item, right.item) {
+            let mut damage = int_tag(left, "Damage");
+
+            if damage.min(max / 4) <= 0 {
+                return None;
+            }
+
+            for _ in 0..repair_units(left, right) {
+                damage -= damage.min(max / 4);
+                cost += 1;
+            }
+
+            set_int_tag(&mut result, "Damage", damage);
+        } else {
+            if !right_is_book && (left.item != right.item || max == 0) {
+                return None;
+            }
+
+            if max > 0 && !right_is_book {
+                // the durability left on both, plus a 12% bonus
+                let remaining = (max - int_tag(left, "Damage")) + (max - int_tag(right, "Damage"));
+                let damage = (max - remaining - max * 12 / 100).max(0);
+
+                if damage < int_tag(left, "Damage") {
+                    set_int_tag(&mut result, "Damage", damage);
+                    cost += 2;
+                }
+            }
+
+            let left_is_book = left.item == ItemKind::EnchantedBook;
+            let mut combined = enchantments(left);
+            let (mut any_applied, mut any_rejected) = (false, false);
+
+            for (id, level) in right_enchantments {
+                let Some(enchantment) = find_enchantment(&id) else {
+                    any_rejected = true;
+                    continue;
+                };
+
+                let current = combined
+                    .iter()
+                    .find(|(other, _)| *other == id)
+                    .map_or(0, |&(_, level)| level);
+
+                let level = if current == level {
+                    level + 1
+                } else {
+                    level.max(current)
+                };
+
+                let mut applicable = left_is_book || enchantment.target.accepts(left.item);
+
+                for (other, _) in &combined {
+                    let conflicts = *other != id
+                        && find_enchantment(other)
+                            .is_some_and(|other| !compatible(enchantment, other));
+
+                    if conflicts {
+                        applicable = false;
+                        cost += 1;
+                    }
+                }
+
+                if !applicable {
+                    any_rejected = true;
+                    continue;
+                }
+
+                any_applied = true;
+
+                let level = level.min(enchantment.max_level);
+
+                match combined.iter_mut().find(|(other, _)| *other == id) {
+                    Some(entry) => entry.1 = level,
+                    None => combined.push((id, level)),
+                }
+
+                let weight = if right_is_book {
+                    (enchantment.weight / 2).max(1)
+                } else {
+                    enchantment.weight
+                };
+
+                cost += weight * u32::from(level);
+
+                if left.count > 1 {
+                    cost = TOO_EXPENSIVE;
+                }
+            }
+
+            if any_rejected && !any_applied {
+                return None;
+            }
+
+            set_enchantments(&mut result, &combined);
+        }
+    }
+
+    let mut rename_cost = 0;
+    let current_name = custom_name(left);
+
+    match name {
+        Some(name) if name.trim().is_empty() => {
+            if current_name.is_some() {
+                rename_cost = 1;
+                set_custom_name(&mut result, None);
+            }
+        }
+        Some(name) if current_name.as_deref() != Some(name) => {
+            rename_cost = 1;
+            set_custom_name(&mut result, Some(name));
+        }
+        _ => {}
+    }
+
+    cost += rename_cost;
+
+    if cost == 0 {
+        return None;
+    }
+
+    let mut total = base_cost + cost;
+
+    // renaming alone is never too expensive
+    if rename_cost == cost && total >= TOO_EXPENSIVE {
+        total = TOO_EXPENSIVE - 1;
+    }
+
+    if total >= TOO_EXPENSIVE {
+        return None;
+    }
+
+    let mut penalty = repair_cost(left).max(repair_cost(right));
+
+    if rename_cost != cost {
+        penalty = penalty * 2 + 1;
+    }
+
+    set_int_tag(
+        &mut result,
+        "RepairCost",
+        i32::try_from(penalty).unwrap_or(i32::MAX),
+    );
+
+    Some((result, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{
+        ItemKind, ItemStack,
+        nbt::{Compound, List},
+    };
+
+    use super::{anvil_result, custom_name, enchantments, repair_units};
+
+    fn book(enchantment: &str, level: i16) -> ItemStack {
+        let mut entry = Compound::new();
+        entry.insert("id", format!("minecraft:{enchantment}"));
+        entry.insert("lvl", level);
+
+        let mut nbt = Compound::new();
+        nbt.insert("StoredEnchantments", List::Compound(vec![entry]));
+
+        ItemStack::new(ItemKind::EnchantedBook, 1, Some(nbt))
+    }
+
+    fn damaged(kind: ItemKind, damage: i32) -> ItemStack {
+        let mut nbt = Compound::new();
+        nbt.insert("Damage", damage);
+
+        ItemStack::new(kind, 1, Some(nbt))
+    }
+
+    #[test]
+    fn renaming_costs_one_level() {
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+
+        let (result, cost) = anvil_result(&sword, &ItemStack::EMPTY, Some("Excalibur")).unwrap();
+
+        assert_eq!(cost, 1);
+        assert_eq!(custom_name(&result).as_deref(), Some("Excalibur"));
+
+        // renaming again to the same name does nothing
+        assert!(anvil_result(&result, &ItemStack::EMPTY, Some("Excalibur")).is_none());
+        assert!(anvil_result(&sword, &ItemStack::EMPTY, None).is_none());
+    }
+
+    #[test]
+    fn repairing_uses_one_unit_per_quarter() {
+        let sword = damaged(ItemKind::DiamondSword, 1000);
+        let diamonds = ItemStack::new(ItemKind::Diamond, 5, None);
+
+        let (result, cost) = anvil_result(&sword, &diamonds, None).unwrap();
+
+        // 390 durability per diamond, so three diamonds repair all 1000 damage
+        assert_eq!(cost, 3);
+        assert_eq!(repair_units(&sword, &diamonds), 3);
+        assert_eq!(super::int_tag(&result, "Damage"), 0);
+        assert_eq!(super::repair_cost(&result), 1);
+
+        let iron = ItemStack::new(ItemKind::IronIngot, 5, None);
+        assert!(anvil_result(&sword, &iron, None).is_none());
+    }
+
+    #[test]
+    fn two_sharpness_three_books_make_sharpness_four() {
+        let (result, cost) =
+            anvil_result(&book("sharpness", 3), &book("sharpness", 3), None).unwrap();
+
+        assert_eq!(enchantments(&result), [("sharpness".to_owned(), 4)]);
+        // a common enchantment from a book costs one level per level
+        assert_eq!(cost, 4);
+        assert_eq!(super::repair_cost(&result), 1);
+
+        // the prior-work penalty of both books is added on the next use
+        let (_, cost) = anvil_result(&result, &result, None).unwrap();
+        assert_eq!(cost, 1 + 1 + 5);
+    }
+
+    #[test]
+    fn conflicting_enchantments_are_rejected() {
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        let (sharp, _) = anvil_result(&sword, &book("sharpness", 1), None).unwrap();
+
+        assert!(anvil_result(&sharp, &book("smite", 1), None).is_none());
+        assert!(anvil_result(&sword, &book("efficiency", 1), None).is_none());
+    }
+}
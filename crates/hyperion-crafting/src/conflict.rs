@@ -0,0 +1,127 @@
+//! Finds recipes that match exactly the same grids, of which only the first registered could
+//! ever be crafted.
+
+use std::fmt;
+
+use valence_protocol::ItemKind;
+
+use crate::{Ingredient, ShapedRecipe3x3};
+
+/// What registering a recipe that conflicts with an existing one does.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Refuse the new recipe with a [`RecipeConflict`].
+    #[default]
+    Reject,
+    /// Remove the existing recipe in favour of the new one, with a warning.
+    Replace,
+}
+
+/// A recipe was refused because it matches the same grids as one registered before it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecipeConflict {
+    /// The recipe that was refused.
+    pub recipe_id: String,
+    /// The recipe that was registered first.
+    pub existing: String,
+}
+
+impl fmt::Display for RecipeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` matches the same grids as `{}`",
+            self.recipe_id, self.existing
+        )
+    }
+}
+
+impl std::error::Error for RecipeConflict {}
+
+/// A cell of a shaped key: the raw ids an ingredient accepts, or `None` if it must be empty.
+type Cell = Option<Vec<u16>>;
+
+/// A recipe with everything that does not change which grids it matches taken out, so two
+/// recipes get the same key exactly when they match the same grids.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RecipeKey {
+    /// The rows of the pattern without empty rows and columns around it, mirrored if the mirror
+    /// image sorts first.
+    Shaped(Vec<Vec<Cell>>),
+    /// The ingredients, sorted.
+    Shapeless(Vec<Vec<u16>>),
+}
+
+impl RecipeKey {
+    /// The key of a shaped recipe, or `None` if a predicate decides what it accepts, since
+    /// predicates cannot be compared.
+    pub(crate) fn shaped(recipe: &ShapedRecipe3x3) -> Option<Self> {
+        let mut cells = Vec::with_capacity(recipe.cells.len());
+
+        for cell in &recipe.cells {
+            cells.push(match cell {
+                Some(ingredient) => Some(item_ids(ingredient)?),
+                None => None,
+            });
+        }
+
+        let width = recipe.width;
+        let filled = |x: usize, y: usize| cells[y * width + x].is_some();
+
+        let columns: Vec<_> = (0..width)
+            .filter(|&x| (0..recipe.height).any(|y| filled(x, y)))
+            .collect();
+        let rows: Vec<_> = (0..recipe.height)
+            .filter(|&y| (0..width).any(|x| filled(x, y)))
+            .collect();
+
+        let (&left, &right) = (columns.first()?, columns.last()?);
+        let (&top, &bottom) = (rows.first()?, rows.last()?);
+
+        let rows: Vec<Vec<Cell>> = (top..=bottom)
+            .map(|y| {
+                (left..=right)
+                    .map(|x| cells[y * width + x].clone())
+                    .collect()
+            })
+            .collect();
+
+        let mirrored = rows
+            .iter()
+            .map(|row| row.iter().rev().cloned().collect())
+            .collect();
+
+        Some(Self::Shaped(rows.min(mirrored)))
+    }
+
+    /// The key of a shapeless recipe, or `None` if a predicate decides what it accepts.
+    pub(crate) fn shapeless(ingredients: &[Ingredient]) -> Option<Self> {
+        let mut ingredients = ingredients
+            .iter()
+            .map(item_ids)
+            .collect::<Option<Vec<_>>>()?;
+
+        ingredients.sort_unstable();
+
+        Some(Self::Shapeless(ingredients))
+    }
+}
+
+/// The sorted raw ids of the items `ingredient` accepts, or `None` if it has a predicate.
+fn item_ids(ingredient: &Ingredient) -> Option<Vec<u16>> {
+    if matches!(ingredient, Ingredient::Matching { .. }) {
+        return None;
+    }
+
+    let mut ids: Vec<_> = ingredient
+        .items()
+        .iter()
+        .copied()
+        .map(ItemKind::to_raw)
+        .collect();
+
+    ids.sort_unstable();
+    ids.dedup();
+
+    Some(ids)
+}
@@ -0,0 +1,253 @@
+//! Recipes that look at more than the kind of their items: ingredients that check the whole stack,
+//! such as a custom item recognised by its NBT, and results that depend on what was crafted with.
+
+use std::{fmt, sync::Arc};
+
+use valence_protocol::ItemStack;
+
+/// A square crafting grid, row by row.
+#[derive(Copy, Clone, Debug)]
+pub struct GridView<'a> {
+    stacks: &'a [ItemStack],
+    size: usize,
+}
+
+impl<'a> GridView<'a> {
+    /// # Panics
+    /// If `stacks` does not hold `size * size` stacks.
+    #[must_use]
+    pub fn new(stacks: &'a [ItemStack], size: usize) -> Self {
+        assert_eq!(stacks.len(), size * size, "a crafting grid must be square");
+        Self { stacks, size }
+    }
+
+    /// How many cells wide the grid is.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    #[must_use]
+    pub const fn stacks(&self) -> &'a [ItemStack] {
+        self.stacks
+    }
+
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a ItemStack> {
+        if x >= self.size {
+            return None;
+        }
+
+        self.stacks.get(y * self.size + x)
+    }
+
+    /// The stacks that are not empty.
+    pub fn items(&self) -> impl Iterator<Item = &'a ItemStack> {
+        self.stacks.iter().filter(|stack| !stack.is_empty())
+    }
+}
+
+/// A check on a whole stack, for ingredients that care about more than the item kind.
+#[derive(Clone)]
+pub struct StackPredicate(Arc<dyn Fn(&ItemStack) -> bool + Send + Sync>);
+
+impl StackPredicate {
+    pub fn new(predicate: impl Fn(&ItemStack) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    #[must_use]
+    pub fn test(&self, stack: &ItemStack) -> bool {
+        (self.0)(stack)
+    }
+
+    /// Stacks with `key` in their NBT.
+    #[must_use]
+    pub fn has_nbt(key: &'static str) -> Self {
+        Self::new(move |stack| stack.nbt.as_ref().is_some_and(|nbt| nbt.contains_key(key)))
+    }
+
+    /// Stacks with at least `min` durability left.
+    #[must_use]
+    pub fn min_durability(min: i32) -> Self {
+        Self::new(move |stack| {
+            let max = i32::from(stack.item.max_durability());
+
+            let damage = stack
+                .nbt
+                .as_ref()
+                .and_then(|nbt| nbt.get("Damage"))
+                .and_then(|damage| match damage {
+                    valence_protocol::nbt::Value::Int(damage) => Some(*damage),
+                    _ => None,
+                })
+                .unwrap_or(0);
+
+            max - damage >= min
+        })
+    }
+}
+
+impl fmt::Debug for StackPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StackPredicate(..)")
+    }
+}
+
+/// Predicates cannot be compared, so two are only equal if they are the same closure.
+impl PartialEq for StackPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StackPredicate {}
+
+/// Builds the result of a recipe from the grid it was crafted in, e.g. to copy the enchantments of
+/// an ingredient onto the result.
+#[derive(Clone)]
+pub struct ResultFn(Arc<dyn Fn(&GridView<'_>) -> ItemStack + Send + Sync>);
+
+impl ResultFn {
+    pub fn new(result: impl Fn(&GridView<'_>) -> ItemStack + Send + Sync + 'static) -> Self {
+        Self(Arc::new(result))
+    }
+
+    #[must_use]
+    pub fn call(&self, grid: &GridView<'_>) -> ItemStack {
+        (self.0)(grid)
+    }
+}
+
+impl fmt::Debug for ResultFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResultFn(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{
+        ItemKind, ItemStack,
+        nbt::{Compound, Value},
+    };
+
+    use super::{GridView, StackPredicate};
+    use crate::{CraftingRegistry, Ingredient};
+
+    const EMPTY: ItemStack = ItemStack::EMPTY;
+
+    fn infection_core() -> ItemStack {
+        let mut nbt = Compound::new();
+        nbt.insert("InfectionCore", true);
+
+        ItemStack::new(ItemKind::HeartOfTheSea, 1, Some(nbt))
+    }
+
+    fn infection_blade() -> ItemStack {
+        let mut nbt = Compound::new();
+        nbt.insert("InfectionBlade", true);
+
+        ItemStack::new(ItemKind::IronSword, 1, Some(nbt))
+    }
+
+    fn registry() -> CraftingRegistry {
+        let mut registry = CraftingRegistry::default();
+
+        let core = Ingredient::matching(
+            ItemKind::HeartOfTheSea,
+            StackPredicate::has_nbt("InfectionCore"),
+        );
+
+        registry
+            .register_shaped(
+                "hyperion:infection_blade".to_owned(),
+                &["III", "ICI", "III"],
+                [('I', Ingredient::Item(ItemKind::IronIngot)), ('C', core)],
+                infection_blade(),
+            )
+            .unwrap();
+
+        registry
+    }
+
+    fn grid(center: ItemStack) -> Vec<ItemStack> {
+        let iron = ItemStack::new(ItemKind::IronIngot, 1, None);
+
+        let mut grid = vec![iron; 9];
+        grid[4] = center;
+        grid
+    }
+
+    #[test]
+    fn nbt_gated_ingredient_accepts_the_custom_item() {
+        let registry = registry();
+        let grid = grid(infection_core());
+
+        let result = registry.get_result(&GridView::new(&grid, 3)).unwrap();
+
+        assert_eq!(result, infection_blade());
+    }
+
+    #[test]
+    fn nbt_gated_ingredient_rejects_the_plain_item() {
+        let registry = registry();
+        let grid = grid(ItemStack::new(ItemKind::HeartOfTheSea, 1, None));
+
+        assert!(registry.get_result(&GridView::new(&grid, 3)).is_none());
+    }
+
+    #[test]
+    fn dynamic_results_copy_from_the_grid() {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shapeless_dynamic(
+                "hyperion:renamed_sword".to_owned(),
+                vec![
+                    Ingredient::Item(ItemKind::IronSword),
+                    Ingredient::Item(ItemKind::NameTag),
+                ],
+                ItemStack::new(ItemKind::IronSword, 1, None),
+                |grid| {
+                    let sword = grid
+                        .items()
+                        .find(|stack| stack.item == ItemKind::IronSword)
+                        .unwrap();
+
+                    let mut nbt = sword.nbt.clone().unwrap_or_default();
+                    nbt.insert("Renamed", true);
+
+                    ItemStack::new(ItemKind::IronSword, 1, Some(nbt))
+                },
+            )
+            .unwrap();
+
+        let mut enchanted = Compound::new();
+        enchanted.insert("Enchanted", true);
+
+        let grid = [
+            ItemStack::new(ItemKind::NameTag, 1, None),
+            EMPTY,
+            EMPTY,
+            ItemStack::new(ItemKind::IronSword, 1, Some(enchanted)),
+        ];
+
+        let result = registry.get_result(&GridView::new(&grid, 2)).unwrap();
+        let nbt = result.nbt.unwrap();
+
+        assert_eq!(nbt.get("Enchanted"), Some(&Value::Byte(1)));
+        assert_eq!(nbt.get("Renamed"), Some(&Value::Byte(1)));
+    }
+
+    #[test]
+    fn durability_predicate_checks_damage() {
+        let worn = StackPredicate::min_durability(100);
+
+        let mut damaged = Compound::new();
+        damaged.insert("Damage", 200);
+
+        assert!(worn.test(&ItemStack::new(ItemKind::IronSword, 1, None)));
+        assert!(!worn.test(&ItemStack::new(ItemKind::IronSword, 1, Some(damaged))));
+    }
+}
@@ -1,9 +1,28 @@
 use std::{collections::HashMap, io::Write};
 
+use anyhow::Context;
 use derive_build::Build;
 use flecs_ecs::macros::Component;
 use slotmap::{SecondaryMap, SlotMap, new_key_type};
-use valence_protocol::{Encode, ItemKind, ItemStack, Packet};
+use smallvec::SmallVec;
+use tracing::warn;
+use valence_protocol::{Encode, ItemKind, ItemStack, Packet, VarInt};
+
+use crate::conflict::RecipeKey;
+
+mod anvil;
+mod conflict;
+mod custom;
+mod recipe_book;
+mod smelting;
+mod vanilla;
+
+pub use anvil::{TOO_EXPENSIVE, anvil_result, custom_name, is_repair_material, repair_units};
+pub use conflict::{OnConflict, RecipeConflict};
+pub use custom::{GridView, ResultFn, StackPredicate};
+pub use recipe_book::{BookKind, RecipeBook, UnlockPolicy};
+pub use smelting::{DEFAULT_COOK_TICKS, SmeltingRecipe, SmeltingRegistry, burn_ticks};
+pub use vanilla::{ItemTags, LoadSummary, VANILLA_TAGS};
 
 /// Represents a packet sent from the server to the client to synchronize recipes.
 #[derive(Clone, Debug, Encode, Packet)]
@@ -27,7 +46,7 @@ pub struct Recipe {
 #[derive(Clone, Debug)]
 pub enum RecipeData {
     CraftingShapeless(CraftingShapelessData),
-    // CraftingShaped(CraftingShapedData<'a>),
+    CraftingShaped(CraftingShapedData),
     // CraftingSpecialArmordye(CraftingSpecialData),
     // CraftingSpecialBookcloning(CraftingSpecialData),
     // CraftingSpecialMapcloning(CraftingSpecialData),
@@ -55,7 +74,7 @@ impl Encode for RecipeData {
     fn encode(&self, w: impl Write) -> anyhow::Result<()> {
         match self {
             Self::CraftingShapeless(data) => data.encode(w),
-            // RecipeData::CraftingShaped(data) => data.encode(w),
+            Self::CraftingShaped(data) => data.encode(w),
             // RecipeData::CraftingSpecialArmordye(data) => data.encode(w),
             // RecipeData::CraftingSpecialBookcloning(data) => data.encode(w),
             // RecipeData::CraftingSpecialMapcloning(data) => data.encode(w),
@@ -95,25 +114,25 @@ pub struct CraftingShapelessData {
     result: ItemStack,
 }
 
-// /// Represents data for a shaped crafting recipe.
-// #[derive(Clone, Debug)]
-// pub struct CraftingShapedData<'a> {
-//     /// The width of the crafting grid.
-//     pub width: u32,
-//     /// The height of the crafting grid.
-//     pub height: u32,
-//     /// Used to group similar recipes together in the recipe book.
-//     pub group: Cow<'a, str>,
-//     /// The category of the recipe.
-//     pub category: CraftingCategory,
-//     /// The ingredients for the recipe, indexed by x + (y * width).
-//     pub ingredients: Cow<'a, [Ingredient<'a>]>,
-//     /// The result of the crafting recipe.
-//     pub result: ItemStack,
-//     /// Whether to show a notification when the recipe is added.
-//     pub show_notification: bool,
-// }
-//
+/// Represents data for a shaped crafting recipe.
+#[derive(Clone, Debug)]
+pub struct CraftingShapedData {
+    /// The width of the recipe.
+    width: VarInt,
+    /// The height of the recipe.
+    height: VarInt,
+    /// Used to group similar recipes together in the recipe book.
+    group: String,
+    /// The category of the recipe.
+    category: CraftingCategory,
+    /// The ingredients for the recipe, indexed by x + (y * width). Empty cells have no items.
+    ingredients: Vec<Ingredient>,
+    /// The result of the crafting recipe.
+    result: ItemStack,
+    /// Whether to show a notification when the recipe is added.
+    show_notification: bool,
+}
+
 /// Represents data for special crafting recipes.
 #[derive(Clone, Debug)]
 pub struct CraftingSpecialData {
@@ -192,24 +211,94 @@ pub enum SmeltingCategory {
 }
 
 /// Represents an ingredient in a recipe, which can be multiple possible items.
-#[derive(Encode, Clone, Debug)]
-struct Ingredient(Vec<ItemStack>);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ingredient {
+    Item(ItemKind),
+    AnyOf(SmallVec<[ItemKind; 4]>),
+    /// An item tag such as `minecraft:planks`, looked up in [`ItemTags::vanilla`].
+    Tag(&'static str),
+    /// Items of `kinds` that also pass `predicate`, such as a custom item recognised by its NBT.
+    Matching {
+        kinds: Box<Ingredient>,
+        predicate: StackPredicate,
+    },
+}
 
-impl From<Vec<ItemStack>> for Ingredient {
-    fn from(value: Vec<ItemStack>) -> Self {
-        Self(value)
+impl Ingredient {
+    #[must_use]
+    pub fn any_of(items: impl IntoIterator<Item = ItemKind>) -> Self {
+        Self::AnyOf(items.into_iter().collect())
+    }
+
+    /// Narrows `kinds` down to the stacks that pass `predicate`. Clients only know about `kinds`,
+    /// so the recipe book shows every stack of those kinds.
+    #[must_use]
+    pub fn matching(kinds: impl Into<Self>, predicate: StackPredicate) -> Self {
+        Self::Matching {
+            kinds: Box::new(kinds.into()),
+            predicate,
+        }
     }
-}
 
-impl From<ItemStack> for Ingredient {
-    fn from(value: ItemStack) -> Self {
-        Self(vec![value])
+    /// An ingredient no item fills, used for the empty cells of a shaped recipe.
+    fn empty() -> Self {
+        Self::AnyOf(SmallVec::new())
+    }
+
+    /// The items this ingredient accepts. Unknown tags accept nothing.
+    #[must_use]
+    pub fn items(&self) -> &[ItemKind] {
+        match self {
+            Self::Item(kind) => std::slice::from_ref(kind),
+            Self::AnyOf(kinds) => kinds,
+            Self::Tag(tag) => ItemTags::vanilla().get(tag).unwrap_or_default(),
+            Self::Matching { kinds, .. } => kinds.items(),
+        }
+    }
+
+    /// Whether `kind` is one of the items this ingredient accepts. This does not run predicates,
+    /// so it is used to rule out grids cheaply before [`Self::accepts_stack`].
+    #[must_use]
+    pub fn accepts(&self, kind: ItemKind) -> bool {
+        self.items().contains(&kind)
+    }
+
+    /// Whether `stack` fills this ingredient. Predicates only run once the kind matches.
+    #[must_use]
+    pub fn accepts_stack(&self, stack: &ItemStack) -> bool {
+        self.accepts(stack.item) && self.passes(stack)
+    }
+
+    /// Whether `stack` passes the predicates of this ingredient, regardless of its kind.
+    fn passes(&self, stack: &ItemStack) -> bool {
+        match self {
+            Self::Matching { kinds, predicate } => kinds.passes(stack) && predicate.test(stack),
+            _ => true,
+        }
+    }
+
+    fn accepts_kind_of(&self, stack: &ItemStack) -> bool {
+        self.accepts(stack.item)
     }
 }
 
 impl From<ItemKind> for Ingredient {
     fn from(value: ItemKind) -> Self {
-        Self(vec![ItemStack::new(value, 1, None)])
+        Self::Item(value)
+    }
+}
+
+impl Encode for Ingredient {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        let items = self.items();
+
+        VarInt(i32::try_from(items.len())?).encode(&mut w)?;
+
+        for &kind in items {
+            ItemStack::new(kind, 1, None).encode(&mut w)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -223,17 +312,22 @@ impl Encode for CraftingShapelessData {
     }
 }
 
-// impl Encode for CraftingShapedData<'_> {
-//     fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
-//         self.width.encode(&mut w)?;
-//         self.height.encode(&mut w)?;
-//         self.group.encode(&mut w)?;
-//         self.category.encode(&mut w)?;
-//         self.ingredients.encode(&mut w)?;
-//         self.result.encode(&mut w)?;
-//         self.show_notification.encode(w)
-//     }
-// }
+impl Encode for CraftingShapedData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.width.encode(&mut w)?;
+        self.height.encode(&mut w)?;
+        self.group.encode(&mut w)?;
+        self.category.encode(&mut w)?;
+
+        // the number of ingredients follows from the width and height, so it is not prefixed
+        for ingredient in &self.ingredients {
+            ingredient.encode(&mut w)?;
+        }
+
+        self.result.encode(&mut w)?;
+        self.show_notification.encode(w)
+    }
+}
 
 impl Encode for CraftingSpecialData {
     fn encode(&self, w: impl Write) -> anyhow::Result<()> {
@@ -277,7 +371,7 @@ impl Encode for CraftingSpecialData {
 //     }
 // }
 
-#[derive(Debug, Encode, Packet)]
+#[derive(Debug, Packet)]
 pub struct UnlockRecipesS2c {
     pub action: Action,
     pub crafting_recipe_book: RecipeBookState,
@@ -285,17 +379,35 @@ pub struct UnlockRecipesS2c {
     pub blast_furnace_recipe_book: RecipeBookState,
     pub smoker_recipe_book: RecipeBookState,
     pub recipe_ids_1: Vec<String>,
+    /// Only sent with [`Action::Init`]: the recipes highlighted as new.
     pub recipe_ids_2: Vec<String>,
 }
 
-#[derive(Debug, Encode)]
+impl Encode for UnlockRecipesS2c {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.action.encode(&mut w)?;
+        self.crafting_recipe_book.encode(&mut w)?;
+        self.smelting_recipe_book.encode(&mut w)?;
+        self.blast_furnace_recipe_book.encode(&mut w)?;
+        self.smoker_recipe_book.encode(&mut w)?;
+        self.recipe_ids_1.encode(&mut w)?;
+
+        if self.action == Action::Init {
+            self.recipe_ids_2.encode(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Encode)]
 pub enum Action {
     Init,
     Add,
     Remove,
 }
 
-#[derive(Debug, Encode)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Encode)]
 pub struct RecipeBookState {
     pub open: bool,
     pub filter_active: bool,
@@ -312,26 +424,239 @@ impl RecipeBookState {
 pub type Crafting3x3 = [ItemKind; 9];
 pub type Crafting2x2 = [ItemKind; 4];
 
-#[derive(Debug, Hash, PartialEq, Eq)]
-struct SortedItemList(Crafting3x3);
+impl CraftingShapelessData {
+    /// Whether the non-empty items of a grid are exactly the ingredients, in any order. Every
+    /// ingredient takes one item, so extra items fail the match.
+    #[must_use]
+    pub fn matches(&self, grid: &GridView<'_>) -> bool {
+        let items: Vec<_> = grid.items().collect();
+
+        if items.len() != self.ingredients.len() {
+            return false;
+        }
+
+        let mut used = vec![false; items.len()];
+
+        // only kinds first, so predicates do not run for grids that cannot match anyway
+        if !assign_ingredients(
+            &self.ingredients,
+            &items,
+            &mut used,
+            &Ingredient::accepts_kind_of,
+        ) {
+            return false;
+        }
 
-impl From<Crafting3x3> for SortedItemList {
-    fn from(mut list: Crafting3x3) -> Self {
-        list.sort_unstable();
-        Self(list)
+        used.fill(false);
+        assign_ingredients(
+            &self.ingredients,
+            &items,
+            &mut used,
+            &Ingredient::accepts_stack,
+        )
     }
 }
 
-impl FromIterator<ItemKind> for SortedItemList {
-    fn from_iter<T: IntoIterator<Item = ItemKind>>(iter: T) -> Self {
-        let mut list: Crafting3x3 = [ItemKind::Air; 9];
+/// Whether every ingredient can be given its own item out of the unused `items`.
+///
+/// An ingredient may accept several items, so the first item that fits is not always the right
+/// one; grids hold at most 9 items, so trying every assignment is cheap.
+fn assign_ingredients(
+    ingredients: &[Ingredient],
+    items: &[&ItemStack],
+    used: &mut [bool],
+    fits: &impl Fn(&Ingredient, &ItemStack) -> bool,
+) -> bool {
+    let Some((ingredient, rest)) = ingredients.split_first() else {
+        return true;
+    };
+
+    for (i, &item) in items.iter().enumerate() {
+        if used[i] || !fits(ingredient, item) {
+            continue;
+        }
+
+        used[i] = true;
 
-        for (i, item) in iter.into_iter().enumerate() {
-            // todo: more idiomatic way to do this? also without panic?
-            list[i] = item;
+        if assign_ingredients(rest, items, used, fits) {
+            return true;
         }
 
-        Self::from(list)
+        used[i] = false;
+    }
+
+    false
+}
+
+/// A shaped recipe of up to 3x3 cells. Like in vanilla, the pattern can sit anywhere in a grid it
+/// fits in and may be mirrored horizontally, but every cell around it must be empty.
+#[derive(Clone, Debug)]
+pub struct ShapedRecipe3x3 {
+    width: usize,
+    height: usize,
+    /// Row by row. `None` cells must be left empty.
+    cells: Vec<Option<Ingredient>>,
+    /// The result shown in the recipe book, and crafted unless there is a `dynamic` result.
+    result: ItemStack,
+    dynamic: Option<ResultFn>,
+}
+
+impl ShapedRecipe3x3 {
+    /// Parses a recipe from up to three rows of the same width. Each character is looked up in
+    /// `key`, except for spaces, which are empty cells.
+    pub fn new<I: Into<Ingredient>>(
+        pattern: &[&str],
+        key: impl IntoIterator<Item = (char, I)>,
+        result: ItemStack,
+    ) -> anyhow::Result<Self> {
+        let key: HashMap<_, Ingredient> = key
+            .into_iter()
+            .map(|(symbol, ingredient)| (symbol, ingredient.into()))
+            .collect();
+
+        let height = pattern.len();
+        anyhow::ensure!(
+            (1..=3).contains(&height),
+            "a pattern has 1 to 3 rows, not {height}"
+        );
+
+        let width = pattern[0].chars().count();
+        anyhow::ensure!(
+            (1..=3).contains(&width),
+            "a pattern is 1 to 3 cells wide, not {width}"
+        );
+
+        let mut cells = Vec::with_capacity(width * height);
+
+        for row in pattern {
+            anyhow::ensure!(
+                row.chars().count() == width,
+                "every row of the pattern must be {width} cells wide, but `{row}` is not"
+            );
+
+            for symbol in row.chars() {
+                let cell = match symbol {
+                    ' ' => None,
+                    symbol => Some(
+                        key.get(&symbol)
+                            .with_context(|| format!("`{symbol}` is not in the key"))?
+                            .clone(),
+                    ),
+                };
+
+                cells.push(cell);
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            cells,
+            result,
+            dynamic: None,
+        })
+    }
+
+    /// Crafts the result with `result` instead of cloning the fixed result.
+    #[must_use]
+    pub fn with_result_fn(mut self, result: ResultFn) -> Self {
+        self.dynamic = Some(result);
+        self
+    }
+
+    /// The result shown in the recipe book.
+    #[must_use]
+    pub const fn result(&self) -> &ItemStack {
+        &self.result
+    }
+
+    /// The result of crafting the recipe in `grid`.
+    #[must_use]
+    pub fn craft(&self, grid: &GridView<'_>) -> ItemStack {
+        match &self.dynamic {
+            Some(dynamic) => dynamic.call(grid),
+            None => self.result.clone(),
+        }
+    }
+
+    /// Whether the recipe matches `grid`.
+    #[must_use]
+    pub fn matches(&self, grid: &GridView<'_>) -> bool {
+        let size = grid.size();
+
+        if self.width > size || self.height > size {
+            return false;
+        }
+
+        (0..=size - self.height).any(|dy| {
+            (0..=size - self.width).any(|dx| {
+                [false, true].into_iter().any(|mirrored| {
+                    // only kinds first, so predicates do not run for grids that cannot match
+                    self.matches_at(grid, (dx, dy), mirrored, &Ingredient::accepts_kind_of)
+                        && self.matches_at(grid, (dx, dy), mirrored, &Ingredient::passes)
+                })
+            })
+        })
+    }
+
+    /// Whether the recipe matches `grid` with its top left corner at `offset`, checking each cell
+    /// with `fits`.
+    fn matches_at(
+        &self,
+        grid: &GridView<'_>,
+        (dx, dy): (usize, usize),
+        mirrored: bool,
+        fits: &impl Fn(&Ingredient, &ItemStack) -> bool,
+    ) -> bool {
+        let size = grid.size();
+
+        grid.stacks().iter().enumerate().all(|(i, stack)| {
+            let (x, y) = (i % size, i / size);
+
+            let inside = (dx..dx + self.width).contains(&x) && (dy..dy + self.height).contains(&y);
+
+            let expected = if inside {
+                let column = if mirrored {
+                    self.width - 1 - (x - dx)
+                } else {
+                    x - dx
+                };
+
+                self.cells[(y - dy) * self.width + column].as_ref()
+            } else {
+                None
+            };
+
+            match expected {
+                Some(ingredient) => fits(ingredient, stack),
+                None => stack.is_empty(),
+            }
+        })
+    }
+
+    fn data(&self) -> CraftingShapedData {
+        let ingredients = self
+            .cells
+            .iter()
+            .map(|cell| cell.clone().unwrap_or(Ingredient::empty()))
+            .collect();
+
+        #[expect(
+            clippy::cast_possible_wrap,
+            clippy::cast_possible_truncation,
+            reason = "patterns are at most 3 cells wide and high"
+        )]
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        CraftingShapedData {
+            width: VarInt(width),
+            height: VarInt(height),
+            group: String::new(),
+            category: CraftingCategory::default(),
+            ingredients,
+            result: self.result.clone(),
+            show_notification: false,
+        }
     }
 }
 
@@ -343,24 +668,63 @@ pub struct CraftingRegistry {
     // changes when the registry is updated
     epoch: u64,
 
-    shapeless_lookup: HashMap<SortedItemList, SortedItemId>,
+    /// Checked after the shaped recipes.
     shapeless: SlotMap<SortedItemId, CraftingShapelessData>,
     shapeless_ids: SecondaryMap<SortedItemId, String>,
+    shapeless_results: SecondaryMap<SortedItemId, ResultFn>,
+
+    /// Checked in order before the shapeless recipes.
+    shaped: Vec<(String, ShapedRecipe3x3)>,
+
+    /// The recipe holding each key, to find recipes that would shadow each other.
+    keys: HashMap<RecipeKey, String>,
+    on_conflict: OnConflict,
+
+    /// What is left in the grid after an ingredient is used up, such as the bucket of a milk
+    /// bucket.
+    remainders: HashMap<ItemKind, ItemStack>,
+
+    unlock_policy: UnlockPolicy,
 }
 
 impl Default for CraftingRegistry {
     fn default() -> Self {
         let mut result = Self {
             epoch: 0,
-            shapeless_lookup: HashMap::default(),
             shapeless: SlotMap::default(),
             shapeless_ids: SecondaryMap::default(),
+            shapeless_results: SecondaryMap::default(),
+            shaped: Vec::new(),
+            keys: HashMap::default(),
+            on_conflict: OnConflict::default(),
+            remainders: HashMap::default(),
+            unlock_policy: UnlockPolicy::default(),
         };
 
-        let shapeless = CraftingShapelessData::new(ItemStack::new(ItemKind::OakPlanks, 4, None))
-            .ingredient(ItemKind::OakLog);
+        let bucket = ItemStack::new(ItemKind::Bucket, 1, None);
+        let bottle = ItemStack::new(ItemKind::GlassBottle, 1, None);
 
-        result.register_shapeless("hyperion:plank".to_string(), shapeless);
+        for filled in [
+            ItemKind::MilkBucket,
+            ItemKind::WaterBucket,
+            ItemKind::LavaBucket,
+            ItemKind::PowderSnowBucket,
+        ] {
+            result.register_remainder(filled, bucket.clone());
+        }
+
+        for filled in [ItemKind::HoneyBottle, ItemKind::DragonBreath] {
+            result.register_remainder(filled, bottle.clone());
+        }
+
+        // nothing else is registered yet, so this cannot conflict
+        result
+            .register_shapeless(
+                "hyperion:plank".to_string(),
+                vec![Ingredient::from(ItemKind::OakLog)],
+                ItemStack::new(ItemKind::OakPlanks, 4, None),
+            )
+            .unwrap();
 
         result
     }
@@ -369,6 +733,18 @@ impl Default for CraftingRegistry {
 pub struct ShapelessRecipe<'a> {
     // recipe_id: &'a RecipeIdentifier,
     pub data: &'a CraftingShapelessData,
+    pub dynamic: Option<&'a ResultFn>,
+}
+
+impl ShapelessRecipe<'_> {
+    /// The result of crafting the recipe in `grid`.
+    #[must_use]
+    pub fn craft(&self, grid: &GridView<'_>) -> ItemStack {
+        match self.dynamic {
+            Some(dynamic) => dynamic.call(grid),
+            None => self.data.result.clone(),
+        }
+    }
 }
 
 impl CraftingRegistry {
@@ -383,58 +759,675 @@ impl CraftingRegistry {
             return None;
         }
 
-        let recipes: Vec<_> = self
-            .shapeless
-            .iter()
-            .map(|(id, data)| {
-                let recipe_id = self.shapeless_ids.get(id).unwrap();
+        let shapeless = self.shapeless.iter().map(|(id, data)| {
+            let recipe_id = self.shapeless_ids.get(id).unwrap();
 
-                Recipe {
-                    kind: "minecraft:crafting_shapeless",
-                    recipe_id: recipe_id.to_string(),
-                    data: RecipeData::CraftingShapeless(data.clone()),
-                }
-            })
-            .collect();
+            Recipe {
+                kind: "minecraft:crafting_shapeless",
+                recipe_id: recipe_id.to_string(),
+                data: RecipeData::CraftingShapeless(data.clone()),
+            }
+        });
+
+        let shaped = self.shaped.iter().map(|(recipe_id, recipe)| Recipe {
+            kind: "minecraft:crafting_shaped",
+            recipe_id: recipe_id.clone(),
+            data: RecipeData::CraftingShaped(recipe.data()),
+        });
+
+        let recipes: Vec<_> = shapeless.chain(shaped).collect();
 
         Some(SynchronizeRecipesS2c { recipes })
     }
 
-    pub fn get_shapeless(
-        &self,
-        input: impl IntoIterator<Item = ItemKind>,
-    ) -> Option<ShapelessRecipe<'_>> {
-        let list: SortedItemList = input.into_iter().collect();
-        let id = self.shapeless_lookup.get(&list).copied()?;
+    pub fn get_shapeless(&self, grid: &GridView<'_>) -> Option<ShapelessRecipe<'_>> {
+        let (id, data) = self.shapeless.iter().find(|(_, data)| data.matches(grid))?;
 
-        // let recipe_id = self.shapeless_ids.get(id).unwrap();
-        let data = self.shapeless.get(id).unwrap();
+        Some(ShapelessRecipe {
+            data,
+            dynamic: self.shapeless_results.get(id),
+        })
+    }
 
-        Some(ShapelessRecipe { data })
+    /// Registers a recipe that takes exactly `ingredients`, placed anywhere in the grid. The same
+    /// ingredient may be listed more than once.
+    ///
+    /// Fails with a [`RecipeConflict`] if another recipe takes the same ingredients, unless
+    /// [`Self::set_on_conflict`] says to replace it.
+    pub fn register_shapeless(
+        &mut self,
+        recipe_id: String,
+        ingredients: Vec<Ingredient>,
+        result: ItemStack,
+    ) -> anyhow::Result<()> {
+        self.insert_shapeless(recipe_id, ingredients, result)?;
+        Ok(())
     }
 
-    fn register_shapeless(&mut self, recipe_id: String, data: CraftingShapelessData) {
-        let list: SortedItemList = data
-            .ingredients
-            .iter()
-            .flat_map(|x| &x.0)
-            .map(|x| x.item)
-            .collect();
+    /// Like [`Self::register_shapeless`], but the result is built by `result` from the grid.
+    /// `display` is what the recipe book shows.
+    pub fn register_shapeless_dynamic(
+        &mut self,
+        recipe_id: String,
+        ingredients: Vec<Ingredient>,
+        display: ItemStack,
+        result: impl Fn(&GridView<'_>) -> ItemStack + Send + Sync + 'static,
+    ) -> anyhow::Result<()> {
+        let id = self.insert_shapeless(recipe_id, ingredients, display)?;
+        self.shapeless_results.insert(id, ResultFn::new(result));
+        Ok(())
+    }
+
+    fn insert_shapeless(
+        &mut self,
+        recipe_id: String,
+        ingredients: Vec<Ingredient>,
+        result: ItemStack,
+    ) -> Result<SortedItemId, RecipeConflict> {
+        self.claim(&recipe_id, RecipeKey::shapeless(&ingredients))?;
+
+        let data = ingredients
+            .into_iter()
+            .fold(CraftingShapelessData::new(result), |data, ingredient| {
+                data.ingredient(ingredient)
+            });
 
         let entity_id = self.shapeless.insert(data);
         self.shapeless_ids.insert(entity_id, recipe_id);
 
-        self.shapeless_lookup.insert(list, entity_id);
+        self.mark_changed();
+
+        Ok(entity_id)
+    }
+
+    /// Registers a shaped recipe; see [`ShapedRecipe3x3::new`] for the pattern format.
+    ///
+    /// Fails with a [`RecipeConflict`] if another shaped recipe has the same pattern, even if
+    /// moved or mirrored, unless [`Self::set_on_conflict`] says to replace it.
+    pub fn register_shaped<I: Into<Ingredient>>(
+        &mut self,
+        recipe_id: String,
+        pattern: &[&str],
+        key: impl IntoIterator<Item = (char, I)>,
+        result: ItemStack,
+    ) -> anyhow::Result<()> {
+        let recipe = ShapedRecipe3x3::new(pattern, key, result)
+            .with_context(|| format!("invalid pattern for {recipe_id}"))?;
+
+        self.register_shaped_recipe(recipe_id, recipe)?;
+
+        Ok(())
+    }
+
+    /// Like [`Self::register_shaped`], but the result is built by `result` from the grid.
+    /// `display` is what the recipe book shows.
+    pub fn register_shaped_dynamic<I: Into<Ingredient>>(
+        &mut self,
+        recipe_id: String,
+        pattern: &[&str],
+        key: impl IntoIterator<Item = (char, I)>,
+        display: ItemStack,
+        result: impl Fn(&GridView<'_>) -> ItemStack + Send + Sync + 'static,
+    ) -> anyhow::Result<()> {
+        let recipe = ShapedRecipe3x3::new(pattern, key, display)
+            .with_context(|| format!("invalid pattern for {recipe_id}"))?
+            .with_result_fn(ResultFn::new(result));
+
+        self.register_shaped_recipe(recipe_id, recipe)?;
+
+        Ok(())
+    }
+
+    fn register_shaped_recipe(
+        &mut self,
+        recipe_id: String,
+        recipe: ShapedRecipe3x3,
+    ) -> Result<(), RecipeConflict> {
+        self.claim(&recipe_id, RecipeKey::shaped(&recipe))?;
+
+        self.shaped.push((recipe_id, recipe));
 
         self.mark_changed();
+
+        Ok(())
+    }
+
+    /// Records that `recipe_id` has `key`, first removing or refusing whichever recipe had it
+    /// before. Recipes without a key never conflict.
+    fn claim(&mut self, recipe_id: &str, key: Option<RecipeKey>) -> Result<(), RecipeConflict> {
+        let Some(key) = key else {
+            return Ok(());
+        };
+
+        if let Some(existing) = self.keys.get(&key).cloned() {
+            let conflict = RecipeConflict {
+                recipe_id: recipe_id.to_owned(),
+                existing,
+            };
+
+            match self.on_conflict {
+                OnConflict::Reject => return Err(conflict),
+                OnConflict::Replace => {
+                    warn!("{conflict}, replacing it");
+                    self.remove_recipe(&conflict.existing);
+                }
+            }
+        }
+
+        self.keys.insert(key, recipe_id.to_owned());
+
+        Ok(())
+    }
+
+    /// Unregisters the recipe called `recipe_id`. Returns whether there was one.
+    pub fn remove_recipe(&mut self, recipe_id: &str) -> bool {
+        let shapeless = self
+            .shapeless_ids
+            .iter()
+            .find(|(_, id)| *id == recipe_id)
+            .map(|(key, _)| key);
+
+        if let Some(key) = shapeless {
+            self.shapeless.remove(key);
+            self.shapeless_ids.remove(key);
+            self.shapeless_results.remove(key);
+        } else if let Some(i) = self.shaped.iter().position(|(id, _)| id == recipe_id) {
+            self.shaped.remove(i);
+        } else {
+            return false;
+        }
+
+        self.keys.retain(|_, id| id != recipe_id);
+        self.mark_changed();
+
+        true
     }
 
     #[must_use]
-    pub fn get_result_2x2(&self, grid: Crafting2x2) -> Option<&ItemStack> {
+    pub const fn on_conflict(&self) -> OnConflict {
+        self.on_conflict
+    }
+
+    /// Sets what registering a recipe that matches the same grids as an existing one does.
+    pub const fn set_on_conflict(&mut self, on_conflict: OnConflict) {
+        self.on_conflict = on_conflict;
+    }
+
+    /// The ids of every recipe that matches `grid`, in the order they are checked, so only the
+    /// first is ever crafted. Meant for finding out why a recipe never matches.
+    #[must_use]
+    pub fn find_matching(&self, grid: &GridView<'_>) -> Vec<&str> {
+        let shaped = self
+            .shaped
+            .iter()
+            .filter(|(_, recipe)| recipe.matches(grid))
+            .map(|(id, _)| id.as_str());
+
+        let shapeless = self
+            .shapeless
+            .iter()
+            .filter(|(_, data)| data.matches(grid))
+            .map(|(key, _)| self.shapeless_ids[key].as_str());
+
+        shaped.chain(shapeless).collect()
+    }
+
+    /// The result of crafting `grid`.
+    #[must_use]
+    pub fn get_result(&self, grid: &GridView<'_>) -> Option<ItemStack> {
+        if let Some((_, shaped)) = self.shaped.iter().find(|(_, recipe)| recipe.matches(grid)) {
+            return Some(shaped.craft(grid));
+        }
+
         if let Some(shapeless) = self.get_shapeless(grid) {
-            return Some(&shapeless.data.result);
+            return Some(shapeless.craft(grid));
         }
 
         None
     }
+
+    /// How many times the recipe matching `grid` can be crafted in a row before a slot runs out,
+    /// for shift-clicking the result. Every craft takes one item from each filled slot.
+    #[must_use]
+    pub fn max_crafts(&self, grid: &GridView<'_>) -> u8 {
+        if self.get_result(grid).is_none() {
+            return 0;
+        }
+
+        grid.items()
+            .map(|stack| u8::try_from(stack.count).unwrap_or(0))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// The result of crafting plain items in the player's inventory.
+    #[must_use]
+    pub fn get_result_2x2(&self, grid: Crafting2x2) -> Option<ItemStack> {
+        let stacks = grid.map(plain_stack);
+        self.get_result(&GridView::new(&stacks, 2))
+    }
+
+    /// The result of crafting plain items in a crafting table.
+    #[must_use]
+    pub fn get_result_3x3(&self, grid: Crafting3x3) -> Option<ItemStack> {
+        let stacks = grid.map(plain_stack);
+        self.get_result(&GridView::new(&stacks, 3))
+    }
+
+    /// The ids of every registered recipe.
+    pub fn recipe_ids(&self) -> impl Iterator<Item = &str> {
+        let shapeless = self.shapeless_ids.values().map(String::as_str);
+        let shaped = self.shaped.iter().map(|(id, _)| id.as_str());
+
+        shapeless.chain(shaped)
+    }
+
+    #[must_use]
+    pub const fn unlock_policy(&self) -> UnlockPolicy {
+        self.unlock_policy
+    }
+
+    /// Sets which recipes players who join from now on start with.
+    pub const fn set_unlock_policy(&mut self, policy: UnlockPolicy) {
+        self.unlock_policy = policy;
+    }
+
+    /// Makes crafting with `kind` leave `remainder` behind.
+    pub fn register_remainder(&mut self, kind: ItemKind, remainder: ItemStack) {
+        self.remainders.insert(kind, remainder);
+    }
+
+    /// What is left behind when one `kind` is used in a recipe, if anything.
+    #[must_use]
+    pub fn remainder(&self, kind: ItemKind) -> Option<&ItemStack> {
+        self.remainders.get(&kind)
+    }
+}
+
+/// One `kind` without NBT, or nothing for air.
+fn plain_stack(kind: ItemKind) -> ItemStack {
+    if kind == ItemKind::Air {
+        ItemStack::EMPTY
+    } else {
+        ItemStack::new(kind, 1, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{
+        CraftingRegistry, GridView, Ingredient, OnConflict, RecipeConflict, ShapedRecipe3x3,
+        plain_stack,
+    };
+
+    const AIR: ItemKind = ItemKind::Air;
+    const STONE: ItemKind = ItemKind::Cobblestone;
+    const STICK: ItemKind = ItemKind::Stick;
+
+    fn matches(recipe: &ShapedRecipe3x3, grid: &[ItemKind], size: usize) -> bool {
+        let stacks: Vec<_> = grid.iter().copied().map(plain_stack).collect();
+        recipe.matches(&GridView::new(&stacks, size))
+    }
+
+    fn pickaxe() -> ShapedRecipe3x3 {
+        ShapedRecipe3x3::new(
+            &["XXX", " # ", " # "],
+            [('X', STONE), ('#', STICK)],
+            ItemStack::new(ItemKind::StonePickaxe, 1, None),
+        )
+        .unwrap()
+    }
+
+    fn hoe() -> ShapedRecipe3x3 {
+        ShapedRecipe3x3::new(
+            &["XX", " #", " #"],
+            [('X', STONE), ('#', STICK)],
+            ItemStack::new(ItemKind::StoneHoe, 1, None),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pickaxe_matches_in_both_mirrored_forms() {
+        let grid = [
+            STONE, STONE, STONE, //
+            AIR, STICK, AIR, //
+            AIR, STICK, AIR,
+        ];
+
+        assert!(matches(&pickaxe(), &grid, 3));
+
+        // the hoe is not symmetric, so mirroring it gives a different grid
+        let hoe_right = [
+            STONE, STONE, AIR, //
+            AIR, STICK, AIR, //
+            AIR, STICK, AIR,
+        ];
+        let hoe_left = [
+            AIR, STONE, STONE, //
+            AIR, STICK, AIR, //
+            AIR, STICK, AIR,
+        ];
+
+        assert!(matches(&hoe(), &hoe_right, 3));
+        assert!(matches(&hoe(), &hoe_left, 3));
+    }
+
+    #[test]
+    fn extra_item_in_an_unused_cell_is_rejected() {
+        let grid = [
+            STONE, STONE, STONE, //
+            AIR, STICK, STICK, //
+            AIR, STICK, AIR,
+        ];
+
+        assert!(!matches(&pickaxe(), &grid, 3));
+
+        let grid = [
+            STONE, STONE, STONE, //
+            AIR, STICK, AIR, //
+            STONE, STICK, AIR,
+        ];
+
+        assert!(!matches(&pickaxe(), &grid, 3));
+    }
+
+    #[test]
+    fn small_patterns_match_anywhere_they_fit() {
+        let mut registry = CraftingRegistry::default();
+        registry
+            .register_shaped(
+                "hyperion:crafting_table".to_owned(),
+                &["##", "##"],
+                [('#', ItemKind::OakPlanks)],
+                ItemStack::new(ItemKind::CraftingTable, 1, None),
+            )
+            .unwrap();
+
+        let planks = ItemKind::OakPlanks;
+
+        let result = registry.get_result_2x2([planks; 4]).unwrap();
+        assert_eq!(result.item, ItemKind::CraftingTable);
+
+        let bottom_right = [
+            AIR, AIR, AIR, //
+            AIR, planks, planks, //
+            AIR, planks, planks,
+        ];
+        let result = registry.get_result_3x3(bottom_right).unwrap();
+        assert_eq!(result.item, ItemKind::CraftingTable);
+
+        // a pattern taller than the player's grid never matches it
+        assert!(!matches(&pickaxe(), &[STONE, STONE, STICK, AIR], 2));
+
+        // shapeless recipes still work through the same lookup
+        let result = registry
+            .get_result_2x2([AIR, ItemKind::OakLog, AIR, AIR])
+            .unwrap();
+        assert_eq!(result.item, ItemKind::OakPlanks);
+    }
+
+    fn firework_registry() -> CraftingRegistry {
+        let mut registry = CraftingRegistry::default();
+        registry
+            .register_shapeless(
+                "hyperion:firework_rocket".to_owned(),
+                vec![
+                    Ingredient::from(ItemKind::Gunpowder),
+                    Ingredient::from(ItemKind::Gunpowder),
+                    Ingredient::from(ItemKind::Paper),
+                ],
+                ItemStack::new(ItemKind::FireworkRocket, 3, None),
+            )
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn shapeless_recipes_match_in_any_placement() {
+        let registry = firework_registry();
+        let (powder, paper) = (ItemKind::Gunpowder, ItemKind::Paper);
+
+        let result = registry
+            .get_result_2x2([powder, AIR, paper, powder])
+            .unwrap();
+        assert_eq!(result.item, ItemKind::FireworkRocket);
+
+        let result = registry
+            .get_result_2x2([paper, powder, powder, AIR])
+            .unwrap();
+        assert_eq!(result.item, ItemKind::FireworkRocket);
+
+        let scattered = [
+            AIR, AIR, powder, //
+            AIR, paper, AIR, //
+            powder, AIR, AIR,
+        ];
+        let result = registry.get_result_3x3(scattered).unwrap();
+        assert_eq!(result.item, ItemKind::FireworkRocket);
+    }
+
+    #[test]
+    fn shapeless_near_misses_are_rejected() {
+        let registry = firework_registry();
+        let (powder, paper) = (ItemKind::Gunpowder, ItemKind::Paper);
+
+        // one gunpowder swapped for a second paper
+        assert!(
+            registry
+                .get_result_2x2([powder, paper, paper, AIR])
+                .is_none()
+        );
+
+        // an extra item next to a complete recipe
+        assert!(
+            registry
+                .get_result_2x2([powder, powder, paper, STICK])
+                .is_none()
+        );
+
+        // missing an ingredient
+        assert!(registry.get_result_2x2([powder, paper, AIR, AIR]).is_none());
+    }
+
+    #[test]
+    fn ingredients_with_alternatives_take_one_item_each() {
+        let mut registry = CraftingRegistry::default();
+        registry
+            .register_shapeless(
+                "hyperion:mixed".to_owned(),
+                vec![
+                    Ingredient::any_of([ItemKind::Coal, ItemKind::Charcoal]),
+                    Ingredient::from(ItemKind::Coal),
+                ],
+                ItemStack::new(ItemKind::Torch, 8, None),
+            )
+            .unwrap();
+
+        // the charcoal must go to the first ingredient even though coal fits it too
+        let result = registry
+            .get_result_2x2([ItemKind::Coal, ItemKind::Charcoal, AIR, AIR])
+            .unwrap();
+        assert_eq!(result.item, ItemKind::Torch);
+
+        assert!(
+            registry
+                .get_result_2x2([ItemKind::Charcoal, ItemKind::Charcoal, AIR, AIR])
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn chest_accepts_any_mix_of_planks() {
+        let mut registry = CraftingRegistry::default();
+        registry
+            .register_shaped(
+                "hyperion:chest".to_owned(),
+                &["###", "# #", "###"],
+                [('#', Ingredient::Tag("minecraft:planks"))],
+                ItemStack::new(ItemKind::Chest, 1, None),
+            )
+            .unwrap();
+
+        let (oak, birch) = (ItemKind::OakPlanks, ItemKind::BirchPlanks);
+        let mixed = [
+            oak, birch, oak, //
+            birch, AIR, oak, //
+            oak, oak, birch,
+        ];
+
+        let result = registry.get_result_3x3(mixed).unwrap();
+        assert_eq!(result.item, ItemKind::Chest);
+
+        let with_stone = [
+            oak, birch, oak, //
+            birch, AIR, oak, //
+            oak, STONE, birch,
+        ];
+        assert!(registry.get_result_3x3(with_stone).is_none());
+    }
+
+    #[test]
+    fn max_crafts_is_limited_by_the_smallest_stack() {
+        let registry = firework_registry();
+
+        let grid = [
+            ItemStack::new(ItemKind::Gunpowder, 64, None),
+            ItemStack::new(ItemKind::Gunpowder, 12, None),
+            ItemStack::new(ItemKind::Paper, 30, None),
+            ItemStack::EMPTY,
+        ];
+        assert_eq!(registry.max_crafts(&GridView::new(&grid, 2)), 12);
+
+        let no_recipe = [
+            ItemStack::new(ItemKind::Paper, 30, None),
+            ItemStack::EMPTY,
+            ItemStack::EMPTY,
+            ItemStack::EMPTY,
+        ];
+        assert_eq!(registry.max_crafts(&GridView::new(&no_recipe, 2)), 0);
+    }
+
+    #[test]
+    fn moved_and_mirrored_patterns_conflict() {
+        let mut registry = CraftingRegistry::default();
+        let hoe = ItemStack::new(ItemKind::StoneHoe, 1, None);
+
+        registry
+            .register_shaped(
+                "hyperion:hoe".to_owned(),
+                &["XX", " #", " #"],
+                [('X', STONE), ('#', STICK)],
+                hoe.clone(),
+            )
+            .unwrap();
+
+        // the same hoe, mirrored and with an empty column next to it
+        let error = registry
+            .register_shaped(
+                "hyperion:other_hoe".to_owned(),
+                &["XX ", "#  ", "#  "],
+                [('X', STONE), ('#', STICK)],
+                hoe,
+            )
+            .unwrap_err();
+
+        let conflict = error.downcast_ref::<RecipeConflict>().unwrap();
+        assert_eq!(conflict.recipe_id, "hyperion:other_hoe");
+        assert_eq!(conflict.existing, "hyperion:hoe");
+
+        // a different shape out of the same items is fine
+        registry
+            .register_shaped(
+                "hyperion:pickaxe".to_owned(),
+                &["XXX", " # ", " # "],
+                [('X', STONE), ('#', STICK)],
+                ItemStack::new(ItemKind::StonePickaxe, 1, None),
+            )
+            .unwrap();
+
+        // shapeless ingredients conflict in any order
+        let mut registry = firework_registry();
+
+        let error = registry
+            .register_shapeless(
+                "hyperion:other_rocket".to_owned(),
+                vec![
+                    Ingredient::from(ItemKind::Paper),
+                    Ingredient::any_of([ItemKind::Gunpowder]),
+                    Ingredient::from(ItemKind::Gunpowder),
+                ],
+                ItemStack::new(ItemKind::FireworkRocket, 8, None),
+            )
+            .unwrap_err();
+
+        let conflict = error.downcast_ref::<RecipeConflict>().unwrap();
+        assert_eq!(conflict.existing, "hyperion:firework_rocket");
+    }
+
+    #[test]
+    fn conflicting_recipes_can_replace_the_existing_one() {
+        let mut registry = firework_registry();
+        registry.set_on_conflict(OnConflict::Replace);
+
+        registry
+            .register_shapeless(
+                "hyperion:big_rocket".to_owned(),
+                vec![
+                    Ingredient::from(ItemKind::Paper),
+                    Ingredient::from(ItemKind::Gunpowder),
+                    Ingredient::from(ItemKind::Gunpowder),
+                ],
+                ItemStack::new(ItemKind::FireworkRocket, 8, None),
+            )
+            .unwrap();
+
+        let (powder, paper) = (ItemKind::Gunpowder, ItemKind::Paper);
+        let grid = [powder, powder, paper, AIR].map(plain_stack);
+        let grid = GridView::new(&grid, 2);
+
+        assert_eq!(registry.find_matching(&grid), ["hyperion:big_rocket"]);
+        assert_eq!(registry.get_result(&grid).unwrap().count, 8);
+        assert!(
+            !registry
+                .recipe_ids()
+                .any(|id| id == "hyperion:firework_rocket")
+        );
+    }
+
+    #[test]
+    fn find_matching_lists_recipes_in_lookup_order() {
+        let mut registry = firework_registry();
+
+        // shaped and shapeless recipes never conflict, but one can still shadow the other
+        registry
+            .register_shaped(
+                "hyperion:shaped_rocket".to_owned(),
+                &["##", "P "],
+                [('#', ItemKind::Gunpowder), ('P', ItemKind::Paper)],
+                ItemStack::new(ItemKind::FireworkRocket, 1, None),
+            )
+            .unwrap();
+
+        let (powder, paper) = (ItemKind::Gunpowder, ItemKind::Paper);
+        let grid = [powder, powder, paper, AIR].map(plain_stack);
+        let grid = GridView::new(&grid, 2);
+
+        assert_eq!(registry.find_matching(&grid), [
+            "hyperion:shaped_rocket",
+            "hyperion:firework_rocket"
+        ]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let result = ItemStack::new(ItemKind::Stick, 4, None);
+
+        assert!(ShapedRecipe3x3::new(&["#", "##"], [('#', STONE)], result.clone()).is_err());
+        assert!(ShapedRecipe3x3::new(&["#X"], [('#', STONE)], result.clone()).is_err());
+        assert!(ShapedRecipe3x3::new(&["####"], [('#', STONE)], result.clone()).is_err());
+        assert!(ShapedRecipe3x3::new(&[], [('#', STONE)], result).is_err());
+    }
 }
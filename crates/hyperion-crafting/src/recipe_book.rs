@@ -0,0 +1,191 @@
+//! The recipes each player has unlocked, and how they left their recipe book.
+
+use std::collections::BTreeSet;
+
+use flecs_ecs::macros::Component;
+
+use crate::{Action, CraftingRegistry, RecipeBookState, UnlockRecipesS2c};
+
+/// Which recipes players start with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum UnlockPolicy {
+    /// Every registered recipe, like vanilla with `doLimitedCrafting` off.
+    #[default]
+    All,
+    /// None; the game unlocks recipes as players progress.
+    Progressive,
+}
+
+/// The four recipe books of the client.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BookKind {
+    Crafting,
+    Furnace,
+    BlastFurnace,
+    Smoker,
+}
+
+/// A player's unlocked recipes, by the same ids they are registered under, and the state of each
+/// of their recipe books.
+#[derive(Component, Clone, Debug, Default)]
+pub struct RecipeBook {
+    unlocked: BTreeSet<String>,
+    states: [RecipeBookState; 4],
+}
+
+impl RecipeBook {
+    /// The recipe book of a player who just joined.
+    #[must_use]
+    pub fn new(registry: &CraftingRegistry) -> Self {
+        let unlocked = match registry.unlock_policy() {
+            UnlockPolicy::All => registry.recipe_ids().map(str::to_owned).collect(),
+            UnlockPolicy::Progressive => BTreeSet::new(),
+        };
+
+        Self {
+            unlocked,
+            states: [RecipeBookState::FALSE; 4],
+        }
+    }
+
+    #[must_use]
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.contains(id)
+    }
+
+    /// The unlocked recipe ids, in order.
+    pub fn unlocked(&self) -> impl Iterator<Item = &str> {
+        self.unlocked.iter().map(String::as_str)
+    }
+
+    /// Returns `true` if the recipe was not unlocked yet.
+    pub fn unlock(&mut self, id: &str) -> bool {
+        if self.unlocked.contains(id) {
+            return false;
+        }
+
+        self.unlocked.insert(id.to_owned())
+    }
+
+    #[must_use]
+    pub const fn state(&self, book: BookKind) -> RecipeBookState {
+        self.states[book as usize]
+    }
+
+    /// Remembers whether `book` is open and filtered, as reported by the client.
+    pub const fn set_state(&mut self, book: BookKind, state: RecipeBookState) {
+        self.states[book as usize] = state;
+    }
+
+    /// The packet that fills the client's recipe book on join.
+    #[must_use]
+    pub fn init_packet(&self) -> UnlockRecipesS2c {
+        let ids: Vec<_> = self.unlocked.iter().cloned().collect();
+
+        // nothing is highlighted as new on join
+        self.packet(Action::Init, ids, Vec::new())
+    }
+
+    /// The packet that adds `ids` to the client's recipe book, showing a toast for them.
+    #[must_use]
+    pub fn add_packet(&self, ids: Vec<String>) -> UnlockRecipesS2c {
+        self.packet(Action::Add, ids, Vec::new())
+    }
+
+    fn packet(
+        &self,
+        action: Action,
+        ids: Vec<String>,
+        highlighted: Vec<String>,
+    ) -> UnlockRecipesS2c {
+        UnlockRecipesS2c {
+            action,
+            crafting_recipe_book: self.state(BookKind::Crafting),
+            smelting_recipe_book: self.state(BookKind::Furnace),
+            blast_furnace_recipe_book: self.state(BookKind::BlastFurnace),
+            smoker_recipe_book: self.state(BookKind::Smoker),
+            recipe_ids_1: ids,
+            recipe_ids_2: highlighted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{BookKind, RecipeBook, UnlockPolicy};
+    use crate::{Action, CraftingRegistry, Ingredient, RecipeBookState};
+
+    fn registry() -> CraftingRegistry {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shapeless(
+                "minecraft:firework_rocket".to_owned(),
+                vec![
+                    Ingredient::from(ItemKind::Gunpowder),
+                    Ingredient::from(ItemKind::Paper),
+                ],
+                ItemStack::new(ItemKind::FireworkRocket, 3, None),
+            )
+            .unwrap();
+
+        registry
+            .register_shaped(
+                "minecraft:crafting_table".to_owned(),
+                &["##", "##"],
+                [('#', ItemKind::OakPlanks)],
+                ItemStack::new(ItemKind::CraftingTable, 1, None),
+            )
+            .unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn init_packet_lists_every_unlocked_recipe() {
+        let book = RecipeBook::new(&registry());
+        let pkt = book.init_packet();
+
+        assert_eq!(pkt.action, Action::Init);
+        assert_eq!(pkt.recipe_ids_1, [
+            "hyperion:plank",
+            "minecraft:crafting_table",
+            "minecraft:firework_rocket"
+        ]);
+        assert!(pkt.recipe_ids_2.is_empty());
+    }
+
+    #[test]
+    fn progressive_books_start_empty() {
+        let mut registry = registry();
+        registry.set_unlock_policy(UnlockPolicy::Progressive);
+
+        let mut book = RecipeBook::new(&registry);
+        assert!(book.init_packet().recipe_ids_1.is_empty());
+
+        assert!(book.unlock("minecraft:crafting_table"));
+        assert!(!book.unlock("minecraft:crafting_table"));
+        assert!(book.is_unlocked("minecraft:crafting_table"));
+
+        let pkt = book.add_packet(vec!["minecraft:crafting_table".to_owned()]);
+        assert_eq!(pkt.action, Action::Add);
+        assert_eq!(pkt.recipe_ids_1, ["minecraft:crafting_table"]);
+    }
+
+    #[test]
+    fn book_state_is_sent_back() {
+        let mut book = RecipeBook::new(&registry());
+
+        let open = RecipeBookState {
+            open: true,
+            filter_active: true,
+        };
+        book.set_state(BookKind::Crafting, open);
+
+        let pkt = book.init_packet();
+        assert_eq!(pkt.crafting_recipe_book, open);
+        assert_eq!(pkt.smelting_recipe_book, RecipeBookState::FALSE);
+    }
+}
@@ -0,0 +1,149 @@
+//! Furnace recipes and the fuels that burn in a furnace.
+
+use flecs_ecs::macros::Component;
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::{Ingredient, ItemTags};
+
+/// How long a vanilla furnace takes to smelt one item.
+pub const DEFAULT_COOK_TICKS: u16 = 200;
+
+/// Turns one item of `input` into `result`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmeltingRecipe {
+    pub input: Ingredient,
+    pub result: ItemStack,
+    pub cook_ticks: u16,
+    /// Experience granted per item smelted.
+    pub experience: f32,
+}
+
+impl SmeltingRecipe {
+    #[must_use]
+    pub fn new(input: impl Into<Ingredient>, result: ItemStack) -> Self {
+        Self {
+            input: input.into(),
+            result,
+            cook_ticks: DEFAULT_COOK_TICKS,
+            experience: 0.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, Default)]
+pub struct SmeltingRegistry {
+    recipes: Vec<(String, SmeltingRecipe)>,
+}
+
+impl SmeltingRegistry {
+    pub fn register(&mut self, id: String, recipe: SmeltingRecipe) {
+        self.recipes.push((id, recipe));
+    }
+
+    /// The recipe that smelts `input`, if any. Earlier registrations win.
+    #[must_use]
+    pub fn get(&self, input: &ItemStack) -> Option<&SmeltingRecipe> {
+        if input.is_empty() {
+            return None;
+        }
+
+        self.recipes
+            .iter()
+            .map(|(_, recipe)| recipe)
+            .find(|recipe| recipe.input.accepts_stack(input))
+    }
+
+    pub fn recipe_ids(&self) -> impl Iterator<Item = &str> {
+        self.recipes.iter().map(|(id, _)| id.as_str())
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+}
+
+/// Fuels that burn for the same time as everything in their tag.
+const TAG_FUELS: &[(&str, u16)] = &[
+    ("minecraft:logs_that_burn", 300),
+    ("minecraft:planks", 300),
+    ("minecraft:wooden_stairs", 300),
+    ("minecraft:wooden_fences", 300),
+    ("minecraft:wooden_slabs", 150),
+    ("minecraft:boats", 1200),
+    ("minecraft:saplings", 100),
+    ("minecraft:wool", 100),
+    ("minecraft:wool_carpets", 67),
+];
+
+/// How many ticks one item of `kind` burns for, or `None` if it is not a fuel.
+#[must_use]
+pub fn burn_ticks(kind: ItemKind) -> Option<u16> {
+    let ticks = match kind {
+        ItemKind::LavaBucket => 20000,
+        ItemKind::CoalBlock => 16000,
+        ItemKind::DriedKelpBlock => 4001,
+        ItemKind::BlazeRod => 2400,
+        ItemKind::Coal | ItemKind::Charcoal => 1600,
+        ItemKind::CraftingTable | ItemKind::Bookshelf | ItemKind::Chest | ItemKind::Barrel => 300,
+        ItemKind::Stick | ItemKind::Bowl => 100,
+        ItemKind::Bamboo | ItemKind::Scaffolding => 50,
+        _ => {
+            let tags = ItemTags::vanilla();
+
+            return TAG_FUELS.iter().find_map(|&(tag, ticks)| {
+                tags.get(tag)
+                    .is_some_and(|kinds| kinds.contains(&kind))
+                    .then_some(ticks)
+            });
+        }
+    };
+
+    Some(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{SmeltingRecipe, SmeltingRegistry, burn_ticks};
+    use crate::Ingredient;
+
+    #[test]
+    fn fuels_burn_for_their_vanilla_time() {
+        assert_eq!(burn_ticks(ItemKind::Coal), Some(1600));
+        assert_eq!(burn_ticks(ItemKind::LavaBucket), Some(20000));
+        assert_eq!(burn_ticks(ItemKind::BirchPlanks), Some(300));
+        assert_eq!(burn_ticks(ItemKind::OakSlab), Some(150));
+        assert_eq!(burn_ticks(ItemKind::IronOre), None);
+    }
+
+    #[test]
+    fn recipes_are_found_by_their_input() {
+        let mut registry = SmeltingRegistry::default();
+
+        registry.register(
+            "minecraft:iron_ingot_from_smelting_iron_ore".to_owned(),
+            SmeltingRecipe::new(
+                Ingredient::any_of([ItemKind::IronOre, ItemKind::DeepslateIronOre]),
+                ItemStack::new(ItemKind::IronIngot, 1, None),
+            ),
+        );
+
+        let deepslate = ItemStack::new(ItemKind::DeepslateIronOre, 5, None);
+        let recipe = registry.get(&deepslate).unwrap();
+        assert_eq!(recipe.result.item, ItemKind::IronIngot);
+
+        assert!(
+            registry
+                .get(&ItemStack::new(ItemKind::GoldOre, 1, None))
+                .is_none()
+        );
+        assert!(registry.get(&ItemStack::EMPTY).is_none());
+    }
+}
@@ -0,0 +1,441 @@
+//! Loads recipes written in the vanilla data pack format, so the vanilla recipes do not have to
+//! be registered by hand.
+//!
+//! [`CraftingRegistry`] reads `minecraft:crafting_shaped` and `minecraft:crafting_shapeless`
+//! recipes, and [`SmeltingRegistry`] reads `minecraft:smelting` ones, so both can load the same
+//! folder. Each leaves the other's recipes alone. Special crafting recipes, and any recipe that
+//! names an item or tag we do not know, are skipped with a warning rather than failing the whole
+//! load.
+
+use std::{collections::HashMap, ffi::OsStr, fs, path::Path, sync::LazyLock};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use tracing::{info, warn};
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::{CraftingRegistry, DEFAULT_COOK_TICKS, Ingredient, SmeltingRecipe, SmeltingRegistry};
+
+/// The tags sent to clients when they join, which include the vanilla item tags. They live here
+/// rather than with the join packets so recipes resolve tags against exactly what clients are sent.
+pub const VANILLA_TAGS: &[u8] = include_bytes!("../data/tags.json");
+
+static VANILLA: LazyLock<ItemTags> = LazyLock::new(|| {
+    ItemTags::from_registry_json(VANILLA_TAGS).unwrap_or_else(|e| {
+        warn!("failed to read the vanilla item tags, recipes using tags will not match: {e}");
+        ItemTags::default()
+    })
+});
+
+/// Item tags such as `minecraft:planks`, which recipes can use as ingredients.
+#[derive(Clone, Debug, Default)]
+pub struct ItemTags {
+    tags: HashMap<String, Vec<ItemKind>>,
+}
+
+impl ItemTags {
+    /// The vanilla item tags, which [`Ingredient::Tag`] is resolved against.
+    #[must_use]
+    pub fn vanilla() -> &'static Self {
+        &VANILLA
+    }
+
+    /// Reads the `minecraft:item` group of a registry tag dump, in which every tag lists raw
+    /// item ids. This is the same file that is sent to clients in `SynchronizeTagsS2c`.
+    pub fn from_registry_json(json: &[u8]) -> anyhow::Result<Self> {
+        let groups: HashMap<String, HashMap<String, Vec<u16>>> = serde_json::from_slice(json)?;
+
+        let items = groups
+            .get("minecraft:item")
+            .context("there are no `minecraft:item` tags")?;
+
+        let tags = items
+            .iter()
+            .map(|(name, ids)| {
+                let kinds = ids
+                    .iter()
+                    .filter_map(|&id| ItemKind::from_raw(id))
+                    .collect();
+                (name.clone(), kinds)
+            })
+            .collect();
+
+        Ok(Self { tags })
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[ItemKind]> {
+        self.tags.get(name).map(Vec::as_slice)
+    }
+
+    /// The name of a tag as stored in the table, which lives as long as the table does.
+    fn name(&self, name: &str) -> Option<&str> {
+        self.tags.get_key_value(name).map(|(name, _)| name.as_str())
+    }
+}
+
+/// How many recipes a load registered and how many it skipped.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadSummary {
+    pub loaded: usize,
+    pub skipped: usize,
+}
+
+/// Whether a recipe file was registered, or belongs to another registry.
+enum Loaded {
+    Registered,
+    Ignored,
+}
+
+#[derive(Deserialize)]
+struct ShapedJson {
+    pattern: Vec<String>,
+    key: HashMap<String, IngredientJson>,
+    result: ResultJson,
+}
+
+#[derive(Deserialize)]
+struct ShapelessJson {
+    ingredients: Vec<IngredientJson>,
+    result: ResultJson,
+}
+
+#[derive(Deserialize)]
+struct SmeltingJson {
+    ingredient: IngredientJson,
+    result: SmeltingResultJson,
+    #[serde(default)]
+    experience: f32,
+    #[serde(default = "default_cook_ticks")]
+    cookingtime: u16,
+}
+
+/// Up to 1.20.4 the result of a smelting recipe is a bare item id.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SmeltingResultJson {
+    Id(String),
+    Stack(ResultJson),
+}
+
+/// Either a single choice or a list of choices, any of which is accepted.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IngredientJson {
+    One(ChoiceJson),
+    Any(Vec<ChoiceJson>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChoiceJson {
+    Item { item: String },
+    Tag { tag: String },
+}
+
+#[derive(Deserialize)]
+struct ResultJson {
+    item: String,
+    #[serde(default = "one")]
+    count: i8,
+}
+
+const fn one() -> i8 {
+    1
+}
+
+const fn default_cook_ticks() -> u16 {
+    DEFAULT_COOK_TICKS
+}
+
+fn item_kind(id: &str) -> anyhow::Result<ItemKind> {
+    let name = id.strip_prefix("minecraft:").unwrap_or(id);
+    ItemKind::from_str(name).with_context(|| format!("unknown item `{id}`"))
+}
+
+impl ChoiceJson {
+    fn kinds(&self, tags: &ItemTags) -> anyhow::Result<Vec<ItemKind>> {
+        match self {
+            Self::Item { item } => Ok(vec![item_kind(item)?]),
+            Self::Tag { tag } => {
+                let kinds = tags
+                    .get(tag)
+                    .with_context(|| format!("unknown tag `{tag}`"))?;
+                Ok(kinds.to_vec())
+            }
+        }
+    }
+}
+
+impl IngredientJson {
+    fn resolve(&self, tags: &'static ItemTags) -> anyhow::Result<Ingredient> {
+        match self {
+            Self::One(ChoiceJson::Item { item }) => Ok(Ingredient::Item(item_kind(item)?)),
+            Self::One(ChoiceJson::Tag { tag }) => {
+                let name = tags
+                    .name(tag)
+                    .with_context(|| format!("unknown tag `{tag}`"))?;
+                Ok(Ingredient::Tag(name))
+            }
+            Self::Any(choices) => {
+                let mut kinds = Vec::new();
+
+                for choice in choices {
+                    kinds.extend(choice.kinds(tags)?);
+                }
+
+                anyhow::ensure!(!kinds.is_empty(), "an ingredient accepts no items");
+
+                Ok(Ingredient::any_of(kinds))
+            }
+        }
+    }
+}
+
+impl ResultJson {
+    fn stack(&self) -> anyhow::Result<ItemStack> {
+        Ok(ItemStack::new(item_kind(&self.item)?, self.count, None))
+    }
+}
+
+impl SmeltingResultJson {
+    fn stack(&self) -> anyhow::Result<ItemStack> {
+        match self {
+            Self::Id(id) => Ok(ItemStack::new(item_kind(id)?, 1, None)),
+            Self::Stack(result) => result.stack(),
+        }
+    }
+}
+
+/// Calls `load` with the id, type and contents of every recipe in `dir`, in file name order.
+fn load_dir(
+    dir: &Path,
+    what: &str,
+    mut load: impl FnMut(String, &str, serde_json::Value) -> anyhow::Result<Loaded>,
+) -> anyhow::Result<LoadSummary> {
+    let mut paths = fs::read_dir(dir)
+        .with_context(|| format!("failed to read recipes from {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // so recipes that match the same input always resolve the same way
+    paths.sort();
+
+    let mut summary = LoadSummary::default();
+
+    for path in paths {
+        if path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+
+        let loaded = read_recipe(&path).and_then(|(id, kind, json)| load(id, &kind, json));
+
+        match loaded {
+            Ok(Loaded::Registered) => summary.loaded += 1,
+            Ok(Loaded::Ignored) => {}
+            Err(e) => {
+                warn!("skipping recipe {}: {e:#}", path.display());
+                summary.skipped += 1;
+            }
+        }
+    }
+
+    info!(
+        "loaded {} {what} recipes from {}, skipped {}",
+        summary.loaded,
+        dir.display(),
+        summary.skipped
+    );
+
+    Ok(summary)
+}
+
+/// Reads a recipe file, naming the recipe after it.
+fn read_recipe(path: &Path) -> anyhow::Result<(String, String, serde_json::Value)> {
+    let name = path
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .context("recipe file name is not valid UTF-8")?;
+
+    let contents = fs::read_to_string(path)?;
+    let json: serde_json::Value = serde_json::from_str(&contents)?;
+
+    let kind = json
+        .get("type")
+        .and_then(serde_json::Value::as_str)
+        .context("recipe has no type")?
+        .to_owned();
+
+    Ok((format!("minecraft:{name}"), kind, json))
+}
+
+impl CraftingRegistry {
+    /// Registers every recipe in `dir`, laid out like the `recipes` folder of a data pack. Recipes
+    /// are named after their file, e.g. `stone_pickaxe.json` becomes `minecraft:stone_pickaxe`.
+    /// Tags are resolved against [`ItemTags::vanilla`].
+    ///
+    /// Only failing to read `dir` itself is an error; recipes that cannot be used are skipped.
+    pub fn load_vanilla(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<LoadSummary> {
+        let tags = ItemTags::vanilla();
+
+        load_dir(dir.as_ref(), "crafting", |id, kind, json| {
+            self.load_vanilla_recipe(id, kind, json, tags)
+        })
+    }
+
+    fn load_vanilla_recipe(
+        &mut self,
+        recipe_id: String,
+        kind: &str,
+        json: serde_json::Value,
+        tags: &'static ItemTags,
+    ) -> anyhow::Result<Loaded> {
+        match kind {
+            "minecraft:crafting_shaped" => {
+                let recipe: ShapedJson = serde_json::from_value(json)?;
+
+                let mut key = Vec::with_capacity(recipe.key.len());
+
+                for (symbol, ingredient) in &recipe.key {
+                    let mut chars = symbol.chars();
+                    let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                        bail!("key `{symbol}` is not a single character");
+                    };
+
+                    key.push((symbol, ingredient.resolve(tags)?));
+                }
+
+                let pattern: Vec<_> = recipe.pattern.iter().map(String::as_str).collect();
+
+                self.register_shaped(recipe_id, &pattern, key, recipe.result.stack()?)?;
+            }
+            "minecraft:crafting_shapeless" => {
+                let recipe: ShapelessJson = serde_json::from_value(json)?;
+
+                let ingredients = recipe
+                    .ingredients
+                    .iter()
+                    .map(|ingredient| ingredient.resolve(tags))
+                    .collect::<anyhow::Result<_>>()?;
+
+                self.register_shapeless(recipe_id, ingredients, recipe.result.stack()?)?;
+            }
+            kind if kind.starts_with("minecraft:crafting_") => {
+                bail!("`{kind}` recipes are not supported")
+            }
+            _ => return Ok(Loaded::Ignored),
+        }
+
+        Ok(Loaded::Registered)
+    }
+}
+
+impl SmeltingRegistry {
+    /// Registers every `minecraft:smelting` recipe in `dir`, like
+    /// [`CraftingRegistry::load_vanilla`].
+    pub fn load_vanilla(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<LoadSummary> {
+        let tags = ItemTags::vanilla();
+
+        load_dir(dir.as_ref(), "smelting", |id, kind, json| {
+            if kind != "minecraft:smelting" {
+                return Ok(Loaded::Ignored);
+            }
+
+            let recipe: SmeltingJson = serde_json::from_value(json)?;
+
+            self.register(id, SmeltingRecipe {
+                input: recipe.ingredient.resolve(tags)?,
+                result: recipe.result.stack()?,
+                cook_ticks: recipe.cookingtime,
+                experience: recipe.experience,
+            });
+
+            Ok(Loaded::Registered)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{ItemTags, LoadSummary};
+    use crate::{CraftingRegistry, SmeltingRegistry};
+
+    const AIR: ItemKind = ItemKind::Air;
+
+    #[test]
+    fn fixture_recipes_are_loaded() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/recipes");
+
+        let mut registry = CraftingRegistry::default();
+        let summary = registry.load_vanilla(dir).unwrap();
+
+        // the smelting recipe is left to the smelting registry
+        assert_eq!(summary, LoadSummary {
+            loaded: 2,
+            skipped: 0
+        });
+
+        let (stone, stick) = (ItemKind::Blackstone, ItemKind::Stick);
+        let pickaxe = [
+            stone, stone, stone, //
+            AIR, stick, AIR, //
+            AIR, stick, AIR,
+        ];
+        let result = registry.get_result_3x3(pickaxe).unwrap();
+        assert_eq!(result.item, ItemKind::StonePickaxe);
+
+        let (powder, paper) = (ItemKind::Gunpowder, ItemKind::Paper);
+        let result = registry
+            .get_result_2x2([paper, powder, AIR, powder])
+            .unwrap();
+        assert_eq!((result.item, result.count), (ItemKind::FireworkRocket, 3));
+
+        // the plank recipe, the pickaxe and the rocket
+        assert_eq!(registry.packet().unwrap().recipes.len(), 3);
+    }
+
+    #[test]
+    fn fixture_smelting_recipes_are_loaded() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/recipes");
+
+        let mut registry = SmeltingRegistry::default();
+        let summary = registry.load_vanilla(dir).unwrap();
+
+        assert_eq!(summary, LoadSummary {
+            loaded: 1,
+            skipped: 0
+        });
+
+        let recipe = registry
+            .get(&ItemStack::new(ItemKind::IronOre, 1, None))
+            .unwrap();
+
+        assert_eq!(recipe.result, ItemStack::new(ItemKind::IronIngot, 1, None));
+        assert_eq!(recipe.cook_ticks, 200);
+        assert!((recipe.experience - 0.7).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn unknown_items_and_tags_skip_the_recipe() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/invalid_recipes");
+
+        let mut registry = CraftingRegistry::default();
+        let summary = registry.load_vanilla(dir).unwrap();
+
+        assert_eq!(summary, LoadSummary {
+            loaded: 0,
+            skipped: 2
+        });
+    }
+
+    #[test]
+    fn vanilla_tags_are_bundled() {
+        let planks = ItemTags::vanilla().get("minecraft:planks").unwrap();
+
+        assert!(planks.contains(&ItemKind::OakPlanks));
+        assert!(planks.contains(&ItemKind::BirchPlanks));
+        assert!(!planks.contains(&ItemKind::OakLog));
+    }
+}
@@ -1,6 +1,13 @@
-use valence_protocol::ItemStack;
+use std::ops::{Range, RangeInclusive};
 
-use super::{OFFHAND_SLOT, PlayerInventory, slot_index_from_hand};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+use valence_protocol::{ItemKind, ItemStack};
+
+use super::{
+    OFFHAND_SLOT, PlayerInventory, armor_slot, is_valid_for_slot,
+    parser::{self, create_inventory_action},
+    slot_index_from_hand,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FullMouseButton {
@@ -87,7 +94,7 @@ fn take_one(item: &mut ItemStack) -> ItemStack {
 
 impl InventoryAndCursor {
     fn swap_cursor(&mut self, slot: u16, mode: Amount) {
-        let in_inventory = self.inventory.get_mut(slot).unwrap();
+        let mut in_inventory = self.inventory.get_mut(slot).unwrap();
 
         let cursor = &mut self.cursor;
 
@@ -98,12 +105,12 @@ impl InventoryAndCursor {
                     *in_inventory = single_cursor;
                 } else {
                     // we must swap as we cannot just put one item down
-                    core::mem::swap(in_inventory, cursor);
+                    core::mem::swap(&mut *in_inventory, cursor);
                 }
             }
             Amount::All => {
                 // swap the cursor and the item in the slot
-                core::mem::swap(in_inventory, cursor);
+                core::mem::swap(&mut *in_inventory, cursor);
             }
         }
     }
@@ -138,11 +145,12 @@ impl InventoryAndCursor {
                 // identical behavior so we combine branches
             }
             InventoryAction::NumberKey { key, slot } => {
+                // slots that do not exist are ignored
                 let other = slot_index_from_hand(key - 1);
-                self.inventory.swap(slot, other);
+                let _ = self.inventory.swap(slot, other);
             }
             InventoryAction::OffhandSwap { slot } => {
-                self.inventory.swap(slot, OFFHAND_SLOT);
+                let _ = self.inventory.swap(slot, OFFHAND_SLOT);
             }
             InventoryAction::MiddleClick { .. } => {
                 unimplemented!("Middle click");
@@ -169,3 +177,525 @@ impl InventoryAndCursor {
         }
     }
 }
+
+const CRAFTING_RESULT_SLOT: u16 = 0;
+const ARMOR_SLOTS: RangeInclusive<u16> = PlayerInventory::HELMET_SLOT..=PlayerInventory::BOOTS_SLOT;
+const MAIN_SLOTS: Range<u16> = 9..36;
+const HOTBAR_SLOTS: Range<u16> = 36..45;
+
+/// A click in the player's own inventory window, as sent in `ClickSlotC2s`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct InventoryClick {
+    pub mode: u8,
+    pub button: u8,
+    pub slot: i16,
+}
+
+/// What an [`InventoryClick`] changed, to compare with what the client says it changed.
+#[derive(Debug, PartialEq)]
+pub struct ClickOutcome {
+    /// Every slot that changed, in the order they first changed.
+    pub changed_slots: Vec<u16>,
+    /// What the player holds on their cursor now.
+    pub cursor: ItemStack,
+}
+
+#[derive(Debug, Snafu)]
+pub enum ClickError {
+    #[snafu(display("Invalid click: {source}"))]
+    Parse { source: parser::Error },
+
+    #[snafu(display("Slot {slot} is not in the inventory"))]
+    OutOfRange { slot: u16 },
+
+    #[snafu(display("{item:?} cannot be put in slot {slot}"))]
+    Incompatible { slot: u16, item: ItemKind },
+
+    #[snafu(display("The crafting result cannot be clicked like other slots"))]
+    CraftingResult,
+
+    #[snafu(display("Click mode {mode} is not supported"))]
+    Unsupported { mode: u8 },
+}
+
+/// Whether `stack` may be put in `slot`.
+fn fits(slot: u16, stack: &ItemStack) -> bool {
+    if stack.is_empty() {
+        return true;
+    }
+
+    if slot == CRAFTING_RESULT_SLOT {
+        return false;
+    }
+
+    is_valid_for_slot(slot, stack.item)
+}
+
+/// How many of `kind` fit in `slot`.
+fn slot_max(slot: u16, kind: ItemKind) -> i8 {
+    if ARMOR_SLOTS.contains(&slot) {
+        1
+    } else {
+        kind.max_stack()
+    }
+}
+
+/// `stack`, or [`ItemStack::EMPTY`] once nothing is left of it.
+fn or_empty(stack: ItemStack) -> ItemStack {
+    if stack.is_empty() {
+        ItemStack::EMPTY
+    } else {
+        stack
+    }
+}
+
+fn stacks_with(a: &ItemStack, b: &ItemStack) -> bool {
+    a.item == b.item && a.nbt == b.nbt
+}
+
+impl InventoryClick {
+    /// Applies the click to `inventory`, with `cursor` being what the player held before it.
+    ///
+    /// Nothing is changed if the click is illegal, so the caller can resync the player.
+    pub fn apply(
+        self,
+        inventory: &mut PlayerInventory,
+        cursor: ItemStack,
+    ) -> Result<ClickOutcome, ClickError> {
+        let action =
+            create_inventory_action(self.mode, self.button, self.slot).context(ParseSnafu)?;
+
+        let mut click = Click {
+            inventory: inventory.clone(),
+            cursor,
+            changed_slots: Vec::new(),
+        };
+
+        match action {
+            InventoryAction::NormalClick { button, slot } => click.pickup(slot, button)?,
+            InventoryAction::ShiftClick { slot, .. } => click.quick_move(slot)?,
+            InventoryAction::NumberKey { key, slot } => {
+                click.swap(slot, slot_index_from_hand(key - 1))?;
+            }
+            InventoryAction::OffhandSwap { slot } => click.swap(slot, OFFHAND_SLOT)?,
+            InventoryAction::DoubleClick { slot } => click.collect(slot)?,
+            _ => return UnsupportedSnafu { mode: self.mode }.fail(),
+        }
+
+        for &slot in &click.changed_slots {
+            let stack = click.stack(slot)?.clone();
+            inventory
+                .set(slot, stack)
+                .ok()
+                .context(OutOfRangeSnafu { slot })?;
+        }
+
+        Ok(ClickOutcome {
+            changed_slots: click.changed_slots,
+            cursor: click.cursor,
+        })
+    }
+}
+
+/// A click being worked out on a copy of the slots, so an illegal click changes nothing.
+struct Click {
+    inventory: PlayerInventory,
+    cursor: ItemStack,
+    changed_slots: Vec<u16>,
+}
+
+impl Click {
+    fn stack(&self, slot: u16) -> Result<&ItemStack, ClickError> {
+        self.inventory
+            .get(slot)
+            .ok()
+            .context(OutOfRangeSnafu { slot })
+    }
+
+    fn set(&mut self, slot: u16, stack: ItemStack) -> Result<(), ClickError> {
+        let stack = or_empty(stack);
+
+        ensure!(fits(slot, &stack), IncompatibleSnafu {
+            slot,
+            item: stack.item
+        });
+
+        self.inventory
+            .set(slot, stack)
+            .ok()
+            .context(OutOfRangeSnafu { slot })?;
+
+        if !self.changed_slots.contains(&slot) {
+            self.changed_slots.push(slot);
+        }
+
+        Ok(())
+    }
+
+    /// Mode 0: picks up, puts down or swaps with the cursor.
+    fn pickup(&mut self, slot: u16, button: MouseButton) -> Result<(), ClickError> {
+        let in_slot = self.stack(slot)?.clone();
+        let cursor = self.cursor.clone();
+
+        if slot == CRAFTING_RESULT_SLOT {
+            return CraftingResultSnafu.fail();
+        }
+
+        if cursor.is_empty() {
+            if in_slot.is_empty() {
+                return Ok(());
+            }
+
+            let taken = match button {
+                MouseButton::Left => in_slot.count,
+                // the bigger half
+                MouseButton::Right => in_slot.count - in_slot.count / 2,
+            };
+
+            self.set(slot, in_slot.clone().with_count(in_slot.count - taken))?;
+            self.cursor = or_empty(in_slot.with_count(taken));
+
+            return Ok(());
+        }
+
+        ensure!(fits(slot, &cursor), IncompatibleSnafu {
+            slot,
+            item: cursor.item
+        });
+
+        let max = slot_max(slot, cursor.item);
+
+        if in_slot.is_empty() || stacks_with(&in_slot, &cursor) {
+            let space = max - in_slot.count.min(max);
+
+            let wanted = match button {
+                MouseButton::Left => cursor.count,
+                MouseButton::Right => 1,
+            };
+
+            let moved = wanted.min(space);
+
+            if moved == 0 {
+                return Ok(());
+            }
+
+            self.set(slot, cursor.clone().with_count(in_slot.count + moved))?;
+            self.cursor = or_empty(cursor.clone().with_count(cursor.count - moved));
+
+            return Ok(());
+        }
+
+        // a different item is swapped with the cursor, unless it holds too many for the slot
+        if cursor.count > max {
+            return Ok(());
+        }
+
+        self.set(slot, cursor)?;
+        self.cursor = in_slot;
+
+        Ok(())
+    }
+
+    /// Mode 1: moves a stack between the hotbar and the main inventory, or puts on armor.
+    fn quick_move(&mut self, slot: u16) -> Result<(), ClickError> {
+        let mut stack = self.stack(slot)?.clone();
+
+        if slot == CRAFTING_RESULT_SLOT {
+            return CraftingResultSnafu.fail();
+        }
+
+        if stack.is_empty() {
+            return Ok(());
+        }
+
+        let armor = armor_slot(stack.item)
+            .filter(|&armor| armor != slot)
+            .filter(|&armor| self.stack(armor).is_ok_and(ItemStack::is_empty));
+
+        let targets: Vec<u16> = if let Some(armor) = armor {
+            vec![armor]
+        } else if HOTBAR_SLOTS.contains(&slot) {
+            MAIN_SLOTS.collect()
+        } else if MAIN_SLOTS.contains(&slot) {
+            HOTBAR_SLOTS.collect()
+        } else {
+            MAIN_SLOTS.chain(HOTBAR_SLOTS).collect()
+        };
+
+        // fill up matching stacks before starting new ones
+        for into_empty in [false, true] {
+            for &target in &targets {
+                if stack.is_empty() {
+                    break;
+                }
+
+                let existing = self.stack(target)?.clone();
+
+                if existing.is_empty() != into_empty {
+                    continue;
+                }
+
+                if !into_empty && !stacks_with(&existing, &stack) {
+                    continue;
+                }
+
+                let max = slot_max(target, stack.item);
+                let moved = stack.count.min(max - existing.count.min(max));
+
+                if moved == 0 {
+                    continue;
+                }
+
+                let count = existing.count + moved;
+                self.set(target, stack.clone().with_count(count))?;
+                stack.count -= moved;
+            }
+        }
+
+        let stack = or_empty(stack);
+
+        if self.stack(slot)? != &stack {
+            self.set(slot, stack)?;
+        }
+
+        Ok(())
+    }
+
+    /// Mode 2: swaps a slot with a hotbar slot or the offhand.
+    fn swap(&mut self, slot: u16, other: u16) -> Result<(), ClickError> {
+        if slot == CRAFTING_RESULT_SLOT {
+            return CraftingResultSnafu.fail();
+        }
+
+        if slot == other {
+            return Ok(());
+        }
+
+        let a = self.stack(slot)?.clone();
+        let b = self.stack(other)?.clone();
+
+        if a.is_empty() && b.is_empty() {
+            return Ok(());
+        }
+
+        self.set(slot, b)?;
+        self.set(other, a)?;
+
+        Ok(())
+    }
+
+    /// Mode 6: gathers items like the cursor's onto it, up to a full stack, taking from partial
+    /// stacks first.
+    fn collect(&mut self, slot: u16) -> Result<(), ClickError> {
+        self.stack(slot)?;
+
+        if self.cursor.is_empty() {
+            return Ok(());
+        }
+
+        let max = self.cursor.item.max_stack();
+        let slots = (CRAFTING_RESULT_SLOT + 1)..=OFFHAND_SLOT;
+
+        for full_stacks in [false, true] {
+            for source in slots.clone() {
+                if self.cursor.count >= max {
+                    return Ok(());
+                }
+
+                let stack = self.stack(source)?.clone();
+
+                if stack.is_empty() || !stacks_with(&stack, &self.cursor) {
+                    continue;
+                }
+
+                if (stack.count >= stack.item.max_stack()) != full_stacks {
+                    continue;
+                }
+
+                let moved = stack.count.min(max - self.cursor.count);
+                let left = stack.count - moved;
+
+                self.set(source, stack.with_count(left))?;
+                self.cursor.count += moved;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{ClickError, InventoryClick};
+    use crate::PlayerInventory;
+
+    fn stack(item: ItemKind, count: i8) -> ItemStack {
+        ItemStack::new(item, count, None)
+    }
+
+    fn click(mode: u8, button: u8, slot: i16) -> InventoryClick {
+        InventoryClick { mode, button, slot }
+    }
+
+    #[test]
+    fn test_pickup_and_place() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(9, stack(ItemKind::Stone, 5)).unwrap();
+
+        // right click takes the bigger half
+        let outcome = click(0, 1, 9)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(outcome.changed_slots, vec![9]);
+        assert_eq!(outcome.cursor, stack(ItemKind::Stone, 3));
+        assert_eq!(inventory.get(9).unwrap(), &stack(ItemKind::Stone, 2));
+
+        // right click puts down one
+        let outcome = click(0, 1, 10)
+            .apply(&mut inventory, outcome.cursor)
+            .unwrap();
+        assert_eq!(outcome.cursor, stack(ItemKind::Stone, 2));
+        assert_eq!(inventory.get(10).unwrap(), &stack(ItemKind::Stone, 1));
+
+        // left click puts down the rest
+        let outcome = click(0, 0, 9)
+            .apply(&mut inventory, outcome.cursor)
+            .unwrap();
+        assert_eq!(outcome.cursor, ItemStack::EMPTY);
+        assert_eq!(inventory.get(9).unwrap(), &stack(ItemKind::Stone, 4));
+
+        // a different item is swapped
+        let dirt = stack(ItemKind::Dirt, 1);
+        let outcome = click(0, 0, 9).apply(&mut inventory, dirt.clone()).unwrap();
+        assert_eq!(outcome.cursor, stack(ItemKind::Stone, 4));
+        assert_eq!(inventory.get(9).unwrap(), &dirt);
+    }
+
+    #[test]
+    fn test_shift_click_between_hotbar_and_main() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(36, stack(ItemKind::Stone, 40)).unwrap();
+        inventory.set(20, stack(ItemKind::Stone, 60)).unwrap();
+
+        // tops up the matching stack first, then takes the first empty slot
+        let outcome = click(1, 0, 36)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(outcome.changed_slots, vec![20, 9, 36]);
+        assert_eq!(inventory.get(20).unwrap().count, 64);
+        assert_eq!(inventory.get(9).unwrap().count, 36);
+        assert!(inventory.get(36).unwrap().is_empty());
+
+        // and back to the hotbar
+        click(1, 0, 9)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(inventory.get(36).unwrap().count, 36);
+        assert!(inventory.get(9).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shift_click_armor() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(36, stack(ItemKind::IronHelmet, 1)).unwrap();
+        inventory.set(37, stack(ItemKind::IronHelmet, 1)).unwrap();
+
+        let outcome = click(1, 0, 36)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(outcome.changed_slots, vec![
+            PlayerInventory::HELMET_SLOT,
+            36
+        ]);
+        assert_eq!(inventory.get_helmet().item, ItemKind::IronHelmet);
+
+        // the helmet slot is taken, so the second one goes to the main inventory
+        click(1, 0, 37)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(inventory.get(9).unwrap().item, ItemKind::IronHelmet);
+
+        // taking it off again
+        click(1, 0, 5)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert!(inventory.get_helmet().is_empty());
+        assert_eq!(inventory.get(10).unwrap().item, ItemKind::IronHelmet);
+    }
+
+    #[test]
+    fn test_number_key_and_offhand_swap() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(9, stack(ItemKind::Stone, 1)).unwrap();
+        inventory.set(38, stack(ItemKind::Dirt, 1)).unwrap();
+
+        // key 3 is hotbar slot 38
+        let outcome = click(2, 2, 9)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert_eq!(outcome.changed_slots, vec![9, 38]);
+        assert_eq!(inventory.get(9).unwrap().item, ItemKind::Dirt);
+        assert_eq!(inventory.get(38).unwrap().item, ItemKind::Stone);
+
+        click(2, 40, 9)
+            .apply(&mut inventory, ItemStack::EMPTY)
+            .unwrap();
+        assert!(inventory.get(9).unwrap().is_empty());
+        assert_eq!(inventory.get(45).unwrap().item, ItemKind::Dirt);
+    }
+
+    #[test]
+    fn test_double_click_collects() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(9, stack(ItemKind::Stone, 64)).unwrap();
+        inventory.set(10, stack(ItemKind::Stone, 10)).unwrap();
+        inventory.set(36, stack(ItemKind::Stone, 50)).unwrap();
+
+        let outcome = click(6, 0, 11)
+            .apply(&mut inventory, stack(ItemKind::Stone, 1))
+            .unwrap();
+
+        // partial stacks are emptied before full ones are touched
+        assert_eq!(outcome.cursor.count, 64);
+        assert_eq!(outcome.changed_slots, vec![10, 36, 9]);
+        assert_eq!(inventory.get(9).unwrap().count, 61);
+        assert!(inventory.get(10).unwrap().is_empty());
+        assert!(inventory.get(36).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_illegal_clicks_change_nothing() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(36, stack(ItemKind::Stone, 1)).unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        let result = click(0, 0, 50).apply(&mut inventory, ItemStack::EMPTY);
+        assert!(matches!(result, Err(ClickError::OutOfRange { slot: 50 })));
+
+        let result = click(0, 0, 5).apply(&mut inventory, stack(ItemKind::Stone, 1));
+        assert!(matches!(
+            result,
+            Err(ClickError::Incompatible { slot: 5, .. })
+        ));
+
+        // putting stone on your head by swapping with the hotbar is no better
+        let result = click(2, 0, 5).apply(&mut inventory, ItemStack::EMPTY);
+        assert!(matches!(
+            result,
+            Err(ClickError::Incompatible { slot: 5, .. })
+        ));
+        assert_eq!(inventory.get(36).unwrap().item, ItemKind::Stone);
+        assert!(inventory.get_helmet().is_empty());
+
+        let result = click(0, 0, 0).apply(&mut inventory, ItemStack::EMPTY);
+        assert!(matches!(result, Err(ClickError::CraftingResult)));
+
+        // dropping needs the world, so it is not handled here
+        let result = click(4, 0, 9).apply(&mut inventory, ItemStack::EMPTY);
+        assert!(matches!(result, Err(ClickError::Unsupported { mode: 4 })));
+
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+}
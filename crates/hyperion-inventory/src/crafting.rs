@@ -0,0 +1,727 @@
+use flecs_ecs::macros::Component;
+use hyperion_crafting::{CraftingRegistry, GridView};
+use valence_protocol::ItemStack;
+
+use crate::{Inventory, PlayerInventory};
+
+/// The outcome of taking a crafting result.
+#[derive(Debug)]
+#[must_use]
+pub struct Crafted {
+    pub result: ItemStack,
+    /// Remainders that fit neither in the grid nor in the inventory. The caller should drop them
+    /// at the player's feet.
+    pub dropped: Vec<ItemStack>,
+}
+
+/// The outcome of shift-clicking a crafting result.
+#[derive(Debug)]
+#[must_use]
+pub struct BulkCrafted {
+    /// How many times the recipe was crafted. The results are already in the inventory.
+    pub times: u8,
+    /// Remainders that fit neither in the grid nor in the inventory, as in [`Crafted::dropped`].
+    pub dropped: Vec<ItemStack>,
+}
+
+/// `result` with as many items as `grid` makes at once, up to a full stack.
+pub(crate) fn scale_to_ingredients(result: ItemStack, grid: &[ItemStack]) -> ItemStack {
+    if result.is_empty() {
+        return ItemStack::EMPTY;
+    }
+
+    // air is not part of the recipe, so only filled slots limit how often it can be crafted
+    let min_count = grid
+        .iter()
+        .filter(|stack| !stack.is_empty())
+        .map(|stack| stack.count)
+        .min()
+        .unwrap_or_default();
+
+    let max_stack = i32::from(result.item.max_stack());
+    let count = max_stack.min(i32::from(min_count) * i32::from(result.count));
+
+    let count = i8::try_from(count).unwrap_or(i8::MAX);
+    result.with_count(count)
+}
+
+impl<const N: usize> Inventory<N> {
+    /// The result of the 3x3 grid made up of the slots in `grid`, row by row, counted like
+    /// [`PlayerInventory::crafting_result`]. This lets inventories of crafting tables, whatever
+    /// their layout, show a result.
+    #[must_use]
+    pub fn crafting_result_3x3(&self, grid: [u16; 9], registry: &CraftingRegistry) -> ItemStack {
+        let stacks = grid.map(|idx| self.get(idx).cloned().unwrap_or(ItemStack::EMPTY));
+
+        let result = registry
+            .get_result(&GridView::new(&stacks, 3))
+            .unwrap_or(ItemStack::EMPTY);
+
+        scale_to_ingredients(result, &stacks)
+    }
+
+    /// Takes one item out of each filled slot in `slots` once their recipe has been crafted.
+    /// Recipes such as "any planks" take whichever item the player actually placed.
+    ///
+    /// An ingredient with a remainder, such as the bucket of a milk bucket, leaves it behind in
+    /// its slot if the slot is now empty. Otherwise the slot still holds the ingredient, so the
+    /// remainder is returned for the caller to put elsewhere.
+    pub fn consume_ingredients(
+        &mut self,
+        slots: impl IntoIterator<Item = u16>,
+        registry: &CraftingRegistry,
+    ) -> Vec<ItemStack> {
+        let mut leftover = Vec::new();
+
+        for idx in slots {
+            let Ok(mut slot) = self.get_mut(idx) else {
+                continue;
+            };
+
+            if slot.is_empty() {
+                continue;
+            }
+
+            let remainder = registry.remainder(slot.item).cloned();
+
+            slot.count -= 1;
+
+            if slot.count <= 0 {
+                *slot = remainder.unwrap_or(ItemStack::EMPTY);
+            } else if let Some(remainder) = remainder {
+                leftover.push(remainder);
+            }
+        }
+
+        leftover
+    }
+}
+
+impl PlayerInventory {
+    /// The result of crafting the recipe in the inventory's own 2x2 grid once.
+    #[must_use]
+    pub fn crafting_result_once(&self, registry: &CraftingRegistry) -> ItemStack {
+        let grid = self.crafting_grid();
+
+        registry
+            .get_result(&GridView::new(&grid, 2))
+            .unwrap_or(ItemStack::EMPTY)
+    }
+
+    /// Crafts the recipe in the inventory's own 2x2 grid once.
+    pub fn take_crafting_result(&mut self, registry: &CraftingRegistry) -> Crafted {
+        let result = self.crafting_result_once(registry);
+
+        if result.is_empty() {
+            return Crafted {
+                result,
+                dropped: Vec::new(),
+            };
+        }
+
+        let leftover = self.consume_ingredients(1..=4, registry);
+        let dropped = self.add_or_overflow(leftover);
+
+        Crafted { result, dropped }
+    }
+
+    /// Crafts the recipe in the inventory's own 2x2 grid as many times as the ingredients allow,
+    /// putting the results straight into the inventory like shift-clicking the result slot.
+    ///
+    /// Crafting stops early, leaving the rest of the ingredients in the grid, once the next result
+    /// would not fit. Every touched slot is marked as updated once, however often it changed.
+    pub fn take_crafting_result_bulk(&mut self, registry: &CraftingRegistry) -> BulkCrafted {
+        let grid = self.crafting_grid();
+        let max = registry.max_crafts(&GridView::new(&grid, 2));
+
+        let mut crafted = BulkCrafted {
+            times: 0,
+            dropped: Vec::new(),
+        };
+
+        while crafted.times < max {
+            // dynamic recipes may give a different result each time
+            let result = self.crafting_result_once(registry);
+
+            if result.is_empty() || self.space_for(&result) < result.count {
+                break;
+            }
+
+            let rest = self.try_add_item(result).remaining;
+            debug_assert!(rest.is_none(), "the result was checked to fit");
+
+            let leftover = self.consume_ingredients(1..=4, registry);
+            crafted.dropped.extend(self.add_or_overflow(leftover));
+
+            crafted.times += 1;
+        }
+
+        crafted
+    }
+
+    fn crafting_grid(&self) -> [ItemStack; 4] {
+        core::array::from_fn(|i| self.slots()[i + 1].clone())
+    }
+
+    /// How many items of `stack`'s kind fit in the main inventory and hotbar.
+    #[must_use]
+    pub fn space_for(&self, stack: &ItemStack) -> i8 {
+        let max = stack.item.max_stack();
+
+        let space: i32 = self.slots()[9..45]
+            .iter()
+            .map(|slot| {
+                if slot.is_empty() {
+                    i32::from(max)
+                } else if slot.item == stack.item && slot.nbt == stack.nbt {
+                    i32::from(max - slot.count)
+                } else {
+                    0
+                }
+            })
+            .sum();
+
+        i8::try_from(space).unwrap_or(i8::MAX)
+    }
+
+    /// Adds every item with [`Self::try_add_item`], returning whatever did not fit.
+    pub fn add_or_overflow(
+        &mut self,
+        items: impl IntoIterator<Item = ItemStack>,
+    ) -> Vec<ItemStack> {
+        items
+            .into_iter()
+            .filter_map(|item| self.try_add_item(item).remaining)
+            .collect()
+    }
+}
+
+/// A crafting grid that belongs to an open window instead of the player's inventory, such as the
+/// 3x3 grid of a crafting table. `N` is the width of the grid.
+///
+/// Each player has their own grid, so players crafting at the same table never share items.
+#[derive(Component, Debug)]
+pub struct CraftingGrid<const N: usize> {
+    /// Row by row. Only the first `N * N` cells are used.
+    cells: Inventory<9>,
+    result: ItemStack,
+    /// Whether a cell changed since the result was last worked out.
+    dirty: bool,
+}
+
+impl<const N: usize> Default for CraftingGrid<N> {
+    fn default() -> Self {
+        let () = Self::WIDTH_FITS;
+
+        Self {
+            cells: Inventory::default(),
+            result: ItemStack::EMPTY,
+            dirty: false,
+        }
+    }
+}
+
+impl<const N: usize> CraftingGrid<N> {
+    pub const CELLS: usize = N * N;
+    /// Stops grids wider than the nine cells there are room for from compiling, as soon as one
+    /// is created.
+    const WIDTH_FITS: () = assert!(matches!(N, 1..=3), "crafting grids are 1 to 3 cells wide");
+
+    #[must_use]
+    pub fn get(&self, cell: u16) -> Option<&ItemStack> {
+        if usize::from(cell) >= Self::CELLS {
+            return None;
+        }
+
+        self.cells.get(cell).ok()
+    }
+
+    /// The stack in `cell`. The result is worked out again on the next [`Self::update`].
+    pub fn get_mut(&mut self, cell: u16) -> Option<&mut ItemStack> {
+        if usize::from(cell) >= Self::CELLS {
+            return None;
+        }
+
+        self.dirty = true;
+        self.cells.slots.get_mut(usize::from(cell))
+    }
+
+    /// Every cell, row by row.
+    #[must_use]
+    pub fn stacks(&self) -> &[ItemStack] {
+        &self.cells.slots()[..Self::CELLS]
+    }
+
+    /// The result as of the last [`Self::update`].
+    #[must_use]
+    pub const fn result(&self) -> &ItemStack {
+        &self.result
+    }
+
+    /// Works out the result again if a cell changed since the last time. Returns whether it did.
+    pub fn update(&mut self, registry: &CraftingRegistry) -> bool {
+        if !self.dirty {
+            return false;
+        }
+
+        self.dirty = false;
+        self.result = registry
+            .get_result(&GridView::new(self.stacks(), N))
+            .unwrap_or(ItemStack::EMPTY);
+
+        true
+    }
+
+    /// Crafts the recipe in the grid once. Remainders that do not fit back into the grid go to
+    /// `inventory`.
+    pub fn take_result(
+        &mut self,
+        registry: &CraftingRegistry,
+        inventory: &mut PlayerInventory,
+    ) -> Crafted {
+        self.update(registry);
+
+        let result = self.result.clone();
+
+        if result.is_empty() {
+            return Crafted {
+                result,
+                dropped: Vec::new(),
+            };
+        }
+
+        let dropped = self.consume_ingredients(registry, inventory);
+        self.update(registry);
+
+        Crafted { result, dropped }
+    }
+
+    /// Crafts the recipe in the grid as many times as the ingredients allow, putting the results
+    /// into `inventory`, like [`PlayerInventory::take_crafting_result_bulk`].
+    pub fn take_result_bulk(
+        &mut self,
+        registry: &CraftingRegistry,
+        inventory: &mut PlayerInventory,
+    ) -> BulkCrafted {
+        let max = registry.max_crafts(&GridView::new(self.stacks(), N));
+
+        let mut crafted = BulkCrafted {
+            times: 0,
+            dropped: Vec::new(),
+        };
+
+        while crafted.times < max {
+            // dynamic recipes may give a different result each time
+            self.update(registry);
+
+            let result = self.result.clone();
+
+            if result.is_empty() || inventory.space_for(&result) < result.count {
+                break;
+            }
+
+            let rest = inventory.try_add_item(result).remaining;
+            debug_assert!(rest.is_none(), "the result was checked to fit");
+
+            let dropped = self.consume_ingredients(registry, inventory);
+            crafted.dropped.extend(dropped);
+
+            crafted.times += 1;
+        }
+
+        self.update(registry);
+
+        crafted
+    }
+
+    /// Uses up one craft's worth of ingredients, returning the remainders that fit neither in the
+    /// grid nor in `inventory`.
+    fn consume_ingredients(
+        &mut self,
+        registry: &CraftingRegistry,
+        inventory: &mut PlayerInventory,
+    ) -> Vec<ItemStack> {
+        let cells = (0..9).take(Self::CELLS);
+        let leftover = self.cells.consume_ingredients(cells, registry);
+
+        self.dirty = true;
+
+        inventory.add_or_overflow(leftover)
+    }
+
+    /// Empties the grid into `inventory`, returning whatever does not fit.
+    pub fn return_to(&mut self, inventory: &mut PlayerInventory) -> Vec<ItemStack> {
+        let stacks: Vec<_> = self.cells.items().map(|(_, stack)| stack.clone()).collect();
+
+        self.cells.clear();
+        self.dirty = true;
+
+        inventory.add_or_overflow(stacks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_crafting::{CraftingRegistry, Ingredient};
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use crate::{CraftingGrid, Inventory, PlayerInventory};
+
+    /// Slot 0 is the result, followed by the 3x3 grid.
+    type CraftingTable = Inventory<10>;
+
+    fn registry() -> CraftingRegistry {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shaped(
+                "minecraft:cake".to_owned(),
+                &["AAA", "BEB", "CCC"],
+                [
+                    ('A', ItemKind::MilkBucket),
+                    ('B', ItemKind::Sugar),
+                    ('E', ItemKind::Egg),
+                    ('C', ItemKind::Wheat),
+                ],
+                ItemStack::new(ItemKind::Cake, 1, None),
+            )
+            .unwrap();
+
+        registry
+            .register_shaped(
+                "minecraft:honey_block".to_owned(),
+                &["##", "##"],
+                [('#', Ingredient::Item(ItemKind::HoneyBottle))],
+                ItemStack::new(ItemKind::HoneyBlock, 1, None),
+            )
+            .unwrap();
+
+        registry
+    }
+
+    fn sticks() -> CraftingRegistry {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shaped(
+                "minecraft:stick".to_owned(),
+                &["#", "#"],
+                [('#', Ingredient::Tag("minecraft:planks"))],
+                ItemStack::new(ItemKind::Stick, 4, None),
+            )
+            .unwrap();
+
+        registry
+    }
+
+    /// A crafting table holding the ingredients of a cake, with `milk` milk buckets per slot.
+    fn cake_table(registry: &CraftingRegistry, milk: i8) -> CraftingTable {
+        let mut table = CraftingTable::default();
+
+        let grid = [
+            ItemKind::MilkBucket,
+            ItemKind::MilkBucket,
+            ItemKind::MilkBucket,
+            ItemKind::Sugar,
+            ItemKind::Egg,
+            ItemKind::Sugar,
+            ItemKind::Wheat,
+            ItemKind::Wheat,
+            ItemKind::Wheat,
+        ];
+
+        for (idx, kind) in (1..).zip(grid) {
+            let count = if kind == ItemKind::MilkBucket {
+                milk
+            } else {
+                1
+            };
+            table.set(idx, ItemStack::new(kind, count, None)).unwrap();
+        }
+
+        let result = registry.get_result_3x3(grid).unwrap();
+        assert_eq!(result.item, ItemKind::Cake);
+
+        table.updated_since_last_tick.clear();
+        table
+    }
+
+    #[test]
+    fn cake_leaves_its_buckets_in_the_grid() {
+        let registry = registry();
+        let mut table = cake_table(&registry, 1);
+
+        let leftover = table.consume_ingredients(1..=9, &registry);
+        assert!(leftover.is_empty());
+
+        for idx in 1..=3 {
+            let slot = table.get(idx).unwrap();
+            assert_eq!((slot.item, slot.count), (ItemKind::Bucket, 1));
+        }
+
+        for idx in 4..=9 {
+            assert!(table.get(idx).unwrap().is_empty());
+        }
+
+        let dirty: Vec<_> = table.updated_since_last_tick.iter().collect();
+        assert_eq!(dirty, (1..=9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cake_gives_buckets_back_when_milk_is_left() {
+        let registry = registry();
+        let mut table = cake_table(&registry, 2);
+        let mut player = PlayerInventory::default();
+
+        let leftover = table.consume_ingredients(1..=9, &registry);
+        assert_eq!(leftover.len(), 3);
+
+        let dropped = player.add_or_overflow(leftover);
+        assert!(dropped.is_empty());
+
+        let buckets: i8 = player
+            .items()
+            .filter(|(_, stack)| stack.item == ItemKind::Bucket)
+            .map(|(_, stack)| stack.count)
+            .sum();
+        assert_eq!(buckets, 3);
+
+        for idx in 1..=3 {
+            let slot = table.get(idx).unwrap();
+            assert_eq!((slot.item, slot.count), (ItemKind::MilkBucket, 1));
+        }
+    }
+
+    #[test]
+    fn bulk_crafting_sticks_stops_when_the_inventory_is_full() {
+        let registry = sticks();
+        let mut player = PlayerInventory::default();
+
+        let planks = ItemStack::new(ItemKind::OakPlanks, 64, None);
+        player.set(1, planks.clone()).unwrap();
+        player.set(3, planks).unwrap();
+
+        // two free slots hold 128 sticks, enough for 32 of the 64 crafts
+        for idx in 11..45 {
+            player
+                .set(idx, ItemStack::new(ItemKind::Dirt, 64, None))
+                .unwrap();
+        }
+
+        let crafted = player.take_crafting_result_bulk(&registry);
+
+        assert_eq!(crafted.times, 32);
+        assert!(crafted.dropped.is_empty());
+
+        for idx in [9, 10] {
+            assert_eq!(
+                *player.get(idx).unwrap(),
+                ItemStack::new(ItemKind::Stick, 64, None)
+            );
+        }
+
+        for idx in [1, 3] {
+            assert_eq!(player.get(idx).unwrap().count, 32);
+        }
+    }
+
+    #[test]
+    fn crafting_result_counts_every_set_in_a_full_grid() {
+        let registry = registry();
+        let mut player = PlayerInventory::default();
+
+        for (idx, count) in (1..=4).zip([3, 5, 4, 3]) {
+            player
+                .set(idx, ItemStack::new(ItemKind::HoneyBottle, count, None))
+                .unwrap();
+        }
+
+        let result = player.crafting_result(&registry);
+        assert_eq!((result.item, result.count), (ItemKind::HoneyBlock, 3));
+
+        // taking the result still crafts one at a time
+        let crafted = player.take_crafting_result(&registry);
+        assert_eq!(crafted.result.count, 1);
+    }
+
+    #[test]
+    fn crafting_result_ignores_air_in_a_partial_grid() {
+        let registry = sticks();
+        let mut player = PlayerInventory::default();
+
+        player
+            .set(1, ItemStack::new(ItemKind::OakPlanks, 5, None))
+            .unwrap();
+        player
+            .set(3, ItemStack::new(ItemKind::OakPlanks, 2, None))
+            .unwrap();
+
+        let result = player.crafting_result(&registry);
+        assert_eq!((result.item, result.count), (ItemKind::Stick, 8));
+
+        // not a recipe
+        player.set(3, ItemStack::EMPTY).unwrap();
+        assert!(player.crafting_result(&registry).is_empty());
+    }
+
+    #[test]
+    fn tables_show_shaped_results() {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shaped(
+                "minecraft:golden_apple".to_owned(),
+                &["###", "#A#", "###"],
+                [('#', ItemKind::GoldIngot), ('A', ItemKind::Apple)],
+                ItemStack::new(ItemKind::GoldenApple, 1, None),
+            )
+            .unwrap();
+
+        let mut table = CraftingTable::default();
+        let grid = core::array::from_fn(|i| u16::try_from(i).unwrap() + 1);
+
+        for idx in 1..=9 {
+            let kind = if idx == 5 {
+                ItemKind::Apple
+            } else {
+                ItemKind::GoldIngot
+            };
+
+            table.set(idx, ItemStack::new(kind, 3, None)).unwrap();
+        }
+
+        table.get_mut(7).unwrap().count = 2;
+
+        let result = table.crafting_result_3x3(grid, &registry);
+        assert_eq!((result.item, result.count), (ItemKind::GoldenApple, 2));
+
+        // a table laid out differently, with the grid after its result and fuel slots
+        let mut other = Inventory::<11>::default();
+
+        for idx in 1..=9 {
+            let stack = table.get(idx).unwrap().clone();
+            other.set(idx + 1, stack).unwrap();
+        }
+
+        let result = other.crafting_result_3x3(grid.map(|idx| idx + 1), &registry);
+        assert_eq!((result.item, result.count), (ItemKind::GoldenApple, 2));
+
+        // the ingredients are in the wrong place
+        assert!(other.crafting_result_3x3(grid, &registry).is_empty());
+    }
+
+    #[test]
+    fn tables_show_shapeless_results() {
+        let mut registry = CraftingRegistry::default();
+
+        registry
+            .register_shapeless(
+                "minecraft:firework_rocket".to_owned(),
+                vec![
+                    Ingredient::from(ItemKind::Gunpowder),
+                    Ingredient::from(ItemKind::Paper),
+                ],
+                ItemStack::new(ItemKind::FireworkRocket, 3, None),
+            )
+            .unwrap();
+
+        let mut table = CraftingTable::default();
+        let grid = core::array::from_fn(|i| u16::try_from(i).unwrap() + 1);
+
+        table
+            .set(9, ItemStack::new(ItemKind::Gunpowder, 10, None))
+            .unwrap();
+        table
+            .set(2, ItemStack::new(ItemKind::Paper, 4, None))
+            .unwrap();
+
+        let result = table.crafting_result_3x3(grid, &registry);
+        assert_eq!((result.item, result.count), (ItemKind::FireworkRocket, 12));
+
+        table
+            .set(5, ItemStack::new(ItemKind::Stick, 1, None))
+            .unwrap();
+        assert!(table.crafting_result_3x3(grid, &registry).is_empty());
+    }
+
+    #[test]
+    fn crafting_result_is_capped_at_a_stack() {
+        let registry = sticks();
+        let mut player = PlayerInventory::default();
+
+        let planks = ItemStack::new(ItemKind::OakPlanks, 64, None);
+        player.set(1, planks.clone()).unwrap();
+        player.set(3, planks).unwrap();
+
+        let result = player.crafting_result(&registry);
+        assert_eq!((result.item, result.count), (ItemKind::Stick, 64));
+    }
+
+    #[test]
+    fn remainders_are_dropped_when_the_inventory_is_full() {
+        let registry = registry();
+        let mut player = PlayerInventory::default();
+
+        for idx in 1..=4 {
+            player
+                .set(idx, ItemStack::new(ItemKind::HoneyBottle, 2, None))
+                .unwrap();
+        }
+
+        for idx in 9..=44 {
+            player
+                .set(idx, ItemStack::new(ItemKind::Stone, 64, None))
+                .unwrap();
+        }
+
+        let crafted = player.take_crafting_result(&registry);
+
+        assert_eq!(crafted.result.item, ItemKind::HoneyBlock);
+        assert_eq!(crafted.dropped.len(), 4);
+        assert!(
+            crafted
+                .dropped
+                .iter()
+                .all(|stack| stack.item == ItemKind::GlassBottle)
+        );
+
+        for idx in 1..=4 {
+            let slot = player.get(idx).unwrap();
+            assert_eq!((slot.item, slot.count), (ItemKind::HoneyBottle, 1));
+        }
+    }
+
+    #[test]
+    fn crafting_grid_keeps_its_result_up_to_date() {
+        let registry = registry();
+        let mut grid = CraftingGrid::<2>::default();
+        let mut player = PlayerInventory::default();
+
+        for cell in 0..4 {
+            *grid.get_mut(cell).unwrap() = ItemStack::new(ItemKind::HoneyBottle, 2, None);
+        }
+
+        assert!(grid.get_mut(4).is_none());
+        assert!(grid.result().is_empty());
+
+        assert!(grid.update(&registry));
+        assert_eq!(grid.result().item, ItemKind::HoneyBlock);
+        assert!(!grid.update(&registry));
+
+        let crafted = grid.take_result(&registry, &mut player);
+        assert_eq!(crafted.result.item, ItemKind::HoneyBlock);
+        assert!(crafted.dropped.is_empty());
+
+        // the bottles still hold honey, so the empty ones go to the player
+        let bottles = player.get(36).unwrap();
+        assert_eq!((bottles.item, bottles.count), (ItemKind::GlassBottle, 4));
+
+        assert_eq!(grid.result().item, ItemKind::HoneyBlock);
+
+        assert!(grid.return_to(&mut player).is_empty());
+        assert!(grid.stacks().iter().all(ItemStack::is_empty));
+
+        let honey = player.get(37).unwrap();
+        assert_eq!((honey.item, honey.count), (ItemKind::HoneyBottle, 4));
+    }
+}
@@ -0,0 +1,102 @@
+//! Reading and writing the `Enchantments` list vanilla keeps in an item's NBT.
+
+use valence_protocol::{
+    ItemStack,
+    nbt::{Compound, List, Value},
+};
+
+const KEY: &str = "Enchantments";
+
+/// The enchantments on `stack` as `(id, level)` pairs, with ids as they are stored, such as
+/// `minecraft:sharpness`. Entries without a valid id or level are skipped.
+#[must_use]
+pub fn enchantments(stack: &ItemStack) -> Vec<(String, i32)> {
+    let Some(Value::List(List::Compound(list))) = stack.nbt.as_ref().and_then(|nbt| nbt.get(KEY))
+    else {
+        return Vec::new();
+    };
+
+    list.iter()
+        .filter_map(|entry| {
+            let Some(Value::String(id)) = entry.get("id") else {
+                return None;
+            };
+
+            let level = match entry.get("lvl") {
+                Some(&Value::Short(level)) => i32::from(level),
+                Some(&Value::Int(level)) => level,
+                _ => return None,
+            };
+
+            Some((id.clone(), level))
+        })
+        .collect()
+}
+
+/// Enchants `stack` with `id` at `level`, replacing the level it already has if it is enchanted
+/// with `id` already. Ids without a namespace are in `minecraft`.
+///
+/// Vanilla stores levels as shorts, so levels outside of that range are clamped.
+pub fn add_enchantment(stack: &mut ItemStack, id: &str, level: i32) {
+    let id = if id.contains(':') {
+        id.to_owned()
+    } else {
+        format!("minecraft:{id}")
+    };
+
+    let level = i16::try_from(level).unwrap_or(if level < 0 { i16::MIN } else { i16::MAX });
+
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    let mut list = match nbt.remove(KEY) {
+        Some(Value::List(List::Compound(list))) => list,
+        _ => Vec::new(),
+    };
+
+    list.retain(|entry| !matches!(entry.get("id"), Some(Value::String(other)) if *other == id));
+
+    let mut entry = Compound::new();
+    entry.insert("id", id);
+    entry.insert("lvl", level);
+    list.push(entry);
+
+    nbt.insert(KEY, List::Compound(list));
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ItemKind;
+
+    use super::*;
+
+    #[test]
+    fn added_enchantments_are_read_back() {
+        let mut nbt = Compound::new();
+        nbt.insert("RepairCost", 3);
+        let mut stack = ItemStack::new(ItemKind::DiamondSword, 1, Some(nbt));
+
+        add_enchantment(&mut stack, "sharpness", 5);
+        add_enchantment(&mut stack, "minecraft:unbreaking", 3);
+
+        assert_eq!(enchantments(&stack), vec![
+            ("minecraft:sharpness".to_owned(), 5),
+            ("minecraft:unbreaking".to_owned(), 3),
+        ]);
+
+        add_enchantment(&mut stack, "sharpness", 2);
+
+        assert_eq!(enchantments(&stack), vec![
+            ("minecraft:unbreaking".to_owned(), 3),
+            ("minecraft:sharpness".to_owned(), 2),
+        ]);
+
+        let nbt = stack.nbt.as_ref().unwrap();
+        assert_eq!(nbt.get("RepairCost"), Some(&Value::Int(3)));
+    }
+
+    #[test]
+    fn plain_items_have_no_enchantments() {
+        let stack = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        assert!(enchantments(&stack).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use valence_protocol::ItemStack;
+
+use crate::PlayerInventory;
+
+/// A slot others can see an entity wear or hold an item in, numbered like in
+/// <https://wiki.vg/index.php?title=Protocol&oldid=18375#Set_Equipment>.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(i8)]
+#[expect(missing_docs, reason = "self explanatory")]
+pub enum EquipmentSlot {
+    MainHand = 0,
+    OffHand = 1,
+    Boots = 2,
+    Leggings = 3,
+    Chestplate = 4,
+    Helmet = 5,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [Self; 6] = [
+        Self::MainHand,
+        Self::OffHand,
+        Self::Boots,
+        Self::Leggings,
+        Self::Chestplate,
+        Self::Helmet,
+    ];
+
+    /// The slot of `inventory` this is. The main hand is the selected hotbar slot.
+    #[must_use]
+    pub const fn inventory_slot(self, inventory: &PlayerInventory) -> u16 {
+        match self {
+            Self::MainHand => inventory.get_cursor_index(),
+            Self::OffHand => PlayerInventory::OFFHAND_SLOT,
+            Self::Boots => PlayerInventory::BOOTS_SLOT,
+            Self::Leggings => PlayerInventory::LEGGINGS_SLOT,
+            Self::Chestplate => PlayerInventory::CHESTPLATE_SLOT,
+            Self::Helmet => PlayerInventory::HELMET_SLOT,
+        }
+    }
+}
+
+/// Everything a player holds and wears, as returned by [`PlayerInventory::equipment`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Equipment {
+    /// The item in the selected hotbar slot.
+    pub main_hand: ItemStack,
+    pub off_hand: ItemStack,
+    pub helmet: ItemStack,
+    pub chestplate: ItemStack,
+    pub leggings: ItemStack,
+    pub boots: ItemStack,
+}
+
+impl Equipment {
+    #[must_use]
+    pub const fn get(&self, slot: EquipmentSlot) -> &ItemStack {
+        match slot {
+            EquipmentSlot::MainHand => &self.main_hand,
+            EquipmentSlot::OffHand => &self.off_hand,
+            EquipmentSlot::Boots => &self.boots,
+            EquipmentSlot::Leggings => &self.leggings,
+            EquipmentSlot::Chestplate => &self.chestplate,
+            EquipmentSlot::Helmet => &self.helmet,
+        }
+    }
+
+    /// Every slot with its item, empty or not, in the order the protocol numbers them.
+    pub fn iter(&self) -> impl Iterator<Item = (EquipmentSlot, &ItemStack)> {
+        EquipmentSlot::ALL
+            .into_iter()
+            .map(|slot| (slot, self.get(slot)))
+    }
+}
+
+impl PlayerInventory {
+    /// A copy of what the player holds and wears, such as for a
+    /// [`valence_protocol::packets::play::EntityEquipmentUpdateS2c`] with all of it at once.
+    #[must_use]
+    pub fn equipment(&self) -> Equipment {
+        Equipment {
+            main_hand: self.get_cursor().clone(),
+            off_hand: self.get(Self::OFFHAND_SLOT).unwrap().clone(),
+            helmet: self.get_helmet().clone(),
+            chestplate: self.get_chestplate().clone(),
+            leggings: self.get_leggings().clone(),
+            boots: self.get_boots().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::EquipmentSlot;
+    use crate::PlayerInventory;
+
+    #[test]
+    fn equipment_is_what_is_held_and_worn() {
+        let mut inventory = PlayerInventory::default();
+
+        inventory.set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None));
+        inventory.set_chestplate(ItemStack::new(ItemKind::IronChestplate, 1, None));
+        inventory.set_leggings(ItemStack::new(ItemKind::IronLeggings, 1, None));
+        inventory.set_boots(ItemStack::new(ItemKind::IronBoots, 1, None));
+        inventory.set_offhand(ItemStack::new(ItemKind::Shield, 1, None));
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::Stone, 64, None));
+        inventory.set_hotbar(4, ItemStack::new(ItemKind::IronSword, 1, None));
+        inventory.set_cursor(4);
+
+        let equipment = inventory.equipment();
+        let items = equipment
+            .iter()
+            .map(|(slot, item)| (slot, item.item))
+            .collect::<Vec<_>>();
+
+        assert_eq!(items, [
+            (EquipmentSlot::MainHand, ItemKind::IronSword),
+            (EquipmentSlot::OffHand, ItemKind::Shield),
+            (EquipmentSlot::Boots, ItemKind::IronBoots),
+            (EquipmentSlot::Leggings, ItemKind::IronLeggings),
+            (EquipmentSlot::Chestplate, ItemKind::IronChestplate),
+            (EquipmentSlot::Helmet, ItemKind::IronHelmet),
+        ]);
+
+        inventory.set_cursor(0);
+        assert_eq!(inventory.equipment().main_hand.count, 64);
+    }
+}
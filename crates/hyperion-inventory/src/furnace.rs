@@ -0,0 +1,323 @@
+use hyperion_crafting::{SmeltingRecipe, SmeltingRegistry, burn_ticks};
+use valence_protocol::{
+    ItemKind, ItemStack,
+    nbt::{Compound, Value},
+};
+
+use crate::Inventory;
+
+/// A furnace: the item being smelted, its fuel and the output, and how far along both are.
+///
+/// Ticks the same way a vanilla furnace does, so a piece of coal smelts eight items.
+#[derive(Debug, Default)]
+pub struct Furnace {
+    pub inventory: Inventory<3>,
+    /// Ticks until the current fuel burns out.
+    burn_left: u16,
+    /// How long the current fuel burns in total, for the flame in the window.
+    burn_total: u16,
+    cook_progress: u16,
+    cook_total: u16,
+    /// Experience earned by smelting that has not been collected yet.
+    experience: f32,
+}
+
+impl Furnace {
+    pub const FUEL_SLOT: u16 = 1;
+    pub const INPUT_SLOT: u16 = 0;
+    pub const OUTPUT_SLOT: u16 = 2;
+
+    #[must_use]
+    pub const fn is_burning(&self) -> bool {
+        self.burn_left > 0
+    }
+
+    #[must_use]
+    pub const fn burn_left(&self) -> u16 {
+        self.burn_left
+    }
+
+    #[must_use]
+    pub const fn cook_progress(&self) -> u16 {
+        self.cook_progress
+    }
+
+    /// The values of the four furnace window properties: the flame, how long the fuel burns in
+    /// total, the arrow, and how long the arrow takes to fill.
+    #[must_use]
+    pub fn properties(&self) -> [i16; 4] {
+        [
+            self.burn_left,
+            self.burn_total,
+            self.cook_progress,
+            self.cook_total,
+        ]
+        .map(|value| i16::try_from(value).unwrap_or(i16::MAX))
+    }
+
+    /// Advances the furnace by one tick. Returns `true` if the burn time or cook progress
+    /// changed, in which case open windows need new properties.
+    pub fn tick(&mut self, registry: &SmeltingRegistry) -> bool {
+        let before = self.properties();
+
+        if self.is_burning() {
+            self.burn_left -= 1;
+        }
+
+        let input = self.inventory.get(Self::INPUT_SLOT).unwrap();
+        let has_fuel = !self.inventory.get(Self::FUEL_SLOT).unwrap().is_empty();
+
+        if (self.is_burning() || has_fuel) && !input.is_empty() {
+            let recipe = registry.get(input).filter(|recipe| self.can_smelt(recipe));
+
+            if let Some(recipe) = recipe {
+                self.cook_total = recipe.cook_ticks;
+
+                if !self.is_burning() {
+                    self.light();
+                }
+
+                if self.is_burning() {
+                    self.cook_progress += 1;
+
+                    if self.cook_progress >= self.cook_total {
+                        self.cook_progress = 0;
+                        self.smelt(recipe);
+                    }
+                }
+            } else {
+                self.cook_progress = 0;
+            }
+        } else if !self.is_burning() {
+            self.cook_progress = self.cook_progress.saturating_sub(2);
+        }
+
+        before != self.properties()
+    }
+
+    /// Takes the experience earned so far, keeping the fraction of a point that is left over.
+    pub fn take_experience(&mut self) -> u16 {
+        #[expect(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "experience is never negative and a furnace cannot earn 65535 points"
+        )]
+        let points = self.experience.floor() as u16;
+
+        self.experience -= f32::from(points);
+        points
+    }
+
+    fn can_smelt(&self, recipe: &SmeltingRecipe) -> bool {
+        let output = self.inventory.get(Self::OUTPUT_SLOT).unwrap();
+
+        if output.is_empty() {
+            return true;
+        }
+
+        output.item == recipe.result.item
+            && output.nbt == recipe.result.nbt
+            && output.count + recipe.result.count <= output.item.max_stack()
+    }
+
+    /// Burns one item of fuel, if the fuel slot holds any.
+    fn light(&mut self) {
+        let fuel = self.inventory.get(Self::FUEL_SLOT).unwrap().clone();
+
+        let Some(ticks) = burn_ticks(fuel.item) else {
+            return;
+        };
+
+        self.burn_left = ticks;
+        self.burn_total = ticks;
+
+        let rest = if fuel.count > 1 {
+            fuel.with_count(fuel.count - 1)
+        } else if fuel.item == ItemKind::LavaBucket {
+            ItemStack::new(ItemKind::Bucket, 1, None)
+        } else {
+            ItemStack::EMPTY
+        };
+
+        self.inventory.set(Self::FUEL_SLOT, rest).unwrap();
+    }
+
+    fn smelt(&mut self, recipe: &SmeltingRecipe) {
+        let input = self.inventory.get(Self::INPUT_SLOT).unwrap().clone();
+
+        let input = if input.count > 1 {
+            input.with_count(input.count - 1)
+        } else {
+            ItemStack::EMPTY
+        };
+
+        self.inventory.set(Self::INPUT_SLOT, input).unwrap();
+
+        let output = self.inventory.get(Self::OUTPUT_SLOT).unwrap().clone();
+
+        let output = if output.is_empty() {
+            recipe.result.clone()
+        } else {
+            output.with_count(output.count + recipe.result.count)
+        };
+
+        self.inventory.set(Self::OUTPUT_SLOT, output).unwrap();
+
+        self.experience += recipe.experience;
+    }
+
+    /// The furnace as a vanilla block entity, without its position and id.
+    #[must_use]
+    pub fn to_nbt(&self) -> Compound {
+        let [burn_time, _, cook_time, cook_time_total] = self.properties();
+
+        let mut nbt = Compound::new();
+        nbt.insert("BurnTime", burn_time);
+        nbt.insert("CookTime", cook_time);
+        nbt.insert("CookTimeTotal", cook_time_total);
+        nbt.insert("Items", self.inventory.to_nbt());
+        nbt
+    }
+
+    /// Reads a furnace saved by [`Self::to_nbt`] or by vanilla. Missing fields and unknown
+    /// items are left empty.
+    #[must_use]
+    pub fn from_nbt(nbt: &Compound) -> Self {
+        let short = |key: &str| match nbt.get(key) {
+            Some(&Value::Short(value)) => u16::try_from(value).unwrap_or(0),
+            _ => 0,
+        };
+
+        let mut furnace = Self {
+            burn_left: short("BurnTime"),
+            cook_progress: short("CookTime"),
+            cook_total: short("CookTimeTotal"),
+            ..Self::default()
+        };
+
+        if let Some(Value::List(items)) = nbt.get("Items") {
+            furnace.inventory.load_nbt(items);
+        }
+
+        // vanilla does not save how long the fuel burns in total
+        let fuel = furnace.inventory.get(Self::FUEL_SLOT).unwrap();
+        furnace.burn_total = burn_ticks(fuel.item).unwrap_or(furnace.burn_left);
+
+        furnace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_crafting::{SmeltingRecipe, SmeltingRegistry};
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::Furnace;
+
+    fn registry() -> SmeltingRegistry {
+        let mut registry = SmeltingRegistry::default();
+
+        registry.register(
+            "minecraft:iron_ingot_from_smelting_iron_ore".to_owned(),
+            SmeltingRecipe {
+                experience: 0.7,
+                ..SmeltingRecipe::new(
+                    ItemKind::IronOre,
+                    ItemStack::new(ItemKind::IronIngot, 1, None),
+                )
+            },
+        );
+
+        registry
+    }
+
+    fn furnace(input: ItemStack, fuel: ItemStack) -> Furnace {
+        let mut furnace = Furnace::default();
+        furnace.inventory.set(Furnace::INPUT_SLOT, input).unwrap();
+        furnace.inventory.set(Furnace::FUEL_SLOT, fuel).unwrap();
+        furnace
+    }
+
+    #[test]
+    fn one_coal_smelts_an_iron_ore_in_200_ticks() {
+        let registry = registry();
+        let mut furnace = furnace(
+            ItemStack::new(ItemKind::IronOre, 1, None),
+            ItemStack::new(ItemKind::Coal, 1, None),
+        );
+
+        for _ in 0..199 {
+            furnace.tick(&registry);
+        }
+
+        assert!(
+            furnace
+                .inventory
+                .get(Furnace::OUTPUT_SLOT)
+                .unwrap()
+                .is_empty()
+        );
+
+        furnace.tick(&registry);
+
+        let output = furnace.inventory.get(Furnace::OUTPUT_SLOT).unwrap();
+        assert_eq!(*output, ItemStack::new(ItemKind::IronIngot, 1, None));
+        assert!(
+            furnace
+                .inventory
+                .get(Furnace::INPUT_SLOT)
+                .unwrap()
+                .is_empty()
+        );
+
+        // the coal was burnt on the first tick and has burnt for 199 more
+        assert!(
+            furnace
+                .inventory
+                .get(Furnace::FUEL_SLOT)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(furnace.burn_left(), 1600 - 199);
+        assert_eq!(furnace.cook_progress(), 0);
+
+        assert_eq!(furnace.take_experience(), 0);
+    }
+
+    #[test]
+    fn smelting_stops_when_the_output_is_full() {
+        let registry = registry();
+        let mut furnace = furnace(
+            ItemStack::new(ItemKind::IronOre, 2, None),
+            ItemStack::new(ItemKind::LavaBucket, 1, None),
+        );
+
+        let full = ItemStack::new(ItemKind::IronIngot, 64, None);
+        furnace.inventory.set(Furnace::OUTPUT_SLOT, full).unwrap();
+
+        assert!(!furnace.tick(&registry));
+
+        // the lava is not wasted on an item that cannot be smelted
+        let fuel = furnace.inventory.get(Furnace::FUEL_SLOT).unwrap();
+        assert_eq!(fuel.item, ItemKind::LavaBucket);
+        assert!(!furnace.is_burning());
+    }
+
+    #[test]
+    fn state_survives_a_save() {
+        let registry = registry();
+        let mut furnace = furnace(
+            ItemStack::new(ItemKind::IronOre, 3, None),
+            ItemStack::new(ItemKind::Coal, 2, None),
+        );
+
+        for _ in 0..250 {
+            furnace.tick(&registry);
+        }
+
+        let loaded = Furnace::from_nbt(&furnace.to_nbt());
+
+        assert_eq!(loaded.properties(), furnace.properties());
+        assert_eq!(loaded.inventory.slots(), furnace.inventory.slots());
+    }
+}
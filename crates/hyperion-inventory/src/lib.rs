@@ -1,28 +1,114 @@
-use std::cmp::min;
+use std::{
+    cmp::{Ordering, min},
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::{Deref, DerefMut, Range},
+};
 
 use flecs_ecs::{core::World, macros::Component, prelude::Module};
 use roaring::RoaringBitmap;
-use valence_protocol::{ItemKind, ItemStack};
+use valence_protocol::{Encode, ItemKind, ItemStack};
 
 pub mod action;
+mod crafting;
+mod enchantment;
+mod equipment;
+mod furnace;
+mod nbt;
 pub mod parser;
+#[cfg(feature = "serde")]
+mod serialize;
 
-pub type PlayerInventory = Inventory<46>;
+pub use crafting::{BulkCrafted, Crafted, CraftingGrid};
+pub use enchantment::{add_enchantment, enchantments};
+pub use equipment::{Equipment, EquipmentSlot};
+pub use furnace::Furnace;
+pub use nbt::InventoryLoadError;
+
+pub type PlayerInventory = Inventory<PLAYER_INVENTORY_SIZE>;
+pub type ChestInventory = Inventory<CHEST_INVENTORY_SIZE>;
+
+const PLAYER_INVENTORY_SIZE: usize = 46;
+/// A single chest.
+const CHEST_INVENTORY_SIZE: usize = 27;
 
 /// Placeholder; this will be added later.
-#[derive(Component, Debug)]
+#[derive(Component, Clone, Debug)]
 pub struct Inventory<const T: usize> {
     slots: [ItemStack; T],
     hand_slot: u16,
-    pub updated_since_last_tick: RoaringBitmap, // todo: maybe make this private
-    pub hand_slot_updated_since_last_tick: bool, // todo: maybe make this private
+    updated_since_last_tick: RoaringBitmap,
+    hand_slot_updated_since_last_tick: bool,
+    /// Bumped together with every change that is marked as updated, see [`Self::state_id`].
+    state_id: u32,
+}
+
+/// Inventories are equal if they hold the same stacks in the same slots and have the same hotbar
+/// slot selected, whatever was marked as changed in them and whatever their state ids.
+impl<const N: usize> PartialEq for Inventory<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.slots == other.slots && self.hand_slot == other.hand_slot
+    }
+}
+
+/// A slot borrowed with [`Inventory::get_mut`]. Once dropped, the slot is marked as updated and
+/// the state id is bumped if its stack changed.
+#[derive(Debug)]
+pub struct SlotMut<'a> {
+    index: u16,
+    stack: &'a mut ItemStack,
+    before: ItemStack,
+    updated: &'a mut RoaringBitmap,
+    state_id: &'a mut u32,
+}
+
+impl Deref for SlotMut<'_> {
+    type Target = ItemStack;
+
+    fn deref(&self) -> &ItemStack {
+        self.stack
+    }
+}
+
+impl DerefMut for SlotMut<'_> {
+    fn deref_mut(&mut self) -> &mut ItemStack {
+        self.stack
+    }
+}
+
+impl Drop for SlotMut<'_> {
+    fn drop(&mut self) {
+        if *self.stack != self.before {
+            self.updated.insert(u32::from(self.index));
+            *self.state_id = self.state_id.wrapping_add(1);
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct AddItemResult {
+    /// The slots that were changed, in the order they were changed.
+    pub changed_slots: Vec<u16>,
     pub remaining: Option<ItemStack>,
 }
 
+/// What changed in an inventory since the last [`Inventory::drain_updates`].
+#[derive(Debug, Default, PartialEq)]
+pub struct InventoryUpdates {
+    /// Every changed slot with the stack it holds now, in slot order. A slot that was changed and
+    /// then emptied again is included, as the client may have seen it change.
+    pub slots: Vec<(u16, ItemStack)>,
+    /// The selected hotbar slot, from 0 to 8, if another one was selected.
+    pub hand_slot: Option<u16>,
+}
+
+#[derive(Debug)]
+pub struct RemoveItemResult {
+    /// The slots that were changed, in the order they were changed.
+    pub changed_slots: Vec<u16>,
+    /// How many items could not be removed because there were not enough.
+    pub remaining: i8,
+}
+
 impl<const T: usize> Default for Inventory<T> {
     fn default() -> Self {
         Self {
@@ -30,17 +116,22 @@ impl<const T: usize> Default for Inventory<T> {
             hand_slot: 0,
             updated_since_last_tick: RoaringBitmap::new(),
             hand_slot_updated_since_last_tick: false,
+            state_id: 0,
         }
     }
 }
 
-use hyperion_crafting::{Crafting2x2, CraftingRegistry};
+use hyperion_crafting::CraftingRegistry;
 use snafu::prelude::*;
 
 #[derive(Debug, Snafu)]
 pub enum InventoryAccessError {
     #[snafu(display("Invalid slot index: {index}"))]
     InvalidSlot { index: u16 },
+    #[snafu(display("{kind:?} cannot be put in slot {index}"))]
+    InvalidItemForSlot { index: u16, kind: ItemKind },
+    #[snafu(display("Invalid hotbar index: {index}"))]
+    InvalidHotbarIndex { index: u8 },
 }
 
 enum TryAddSlot {
@@ -52,10 +143,18 @@ enum TryAddSlot {
 const HAND_START_SLOT: u16 = 36;
 
 impl<const N: usize> Inventory<N> {
+    /// Puts `stack` in slot `index`. In a player inventory, only matching armor can be put in the
+    /// armor slots.
     pub fn set(&mut self, index: u16, stack: ItemStack) -> Result<(), InventoryAccessError> {
-        let item = self.get_mut(index)?;
-        *item = stack;
-        self.updated_since_last_tick.insert(u32::from(index));
+        ensure!(
+            N != PLAYER_INVENTORY_SIZE || stack.is_empty() || is_valid_for_slot(index, stack.item),
+            InvalidItemForSlotSnafu {
+                index,
+                kind: stack.item
+            }
+        );
+
+        *self.get_mut(index)? = stack;
         Ok(())
     }
 
@@ -69,19 +168,130 @@ impl<const N: usize> Inventory<N> {
         })
     }
 
+    /// Everything that changed since the last call, so that only those slots have to be sent.
+    /// Afterwards, nothing is marked as changed.
+    pub fn drain_updates(&mut self) -> InventoryUpdates {
+        let slots = self
+            .drain_changed()
+            .filter_map(|slot| Some((slot, self.get(slot).ok()?.clone())))
+            .collect();
+
+        let hand_slot =
+            std::mem::take(&mut self.hand_slot_updated_since_last_tick).then_some(self.hand_slot);
+
+        InventoryUpdates { slots, hand_slot }
+    }
+
+    /// The slots that changed since the last call, in order. Afterwards, no slot is marked as
+    /// changed.
+    pub fn drain_changed(&mut self) -> impl Iterator<Item = u16> + use<N> {
+        std::mem::take(&mut self.updated_since_last_tick)
+            .into_iter()
+            .filter_map(|slot| u16::try_from(slot).ok())
+    }
+
+    /// Whether slot `index` changed since the last [`Self::drain_changed`] or
+    /// [`Self::drain_updates`].
+    #[must_use]
+    pub fn is_updated(&self, index: u16) -> bool {
+        self.updated_since_last_tick.contains(u32::from(index))
+    }
+
+    /// Whether another hotbar slot was selected since the last [`Self::drain_updates`].
+    #[must_use]
+    pub const fn is_hand_slot_updated(&self) -> bool {
+        self.hand_slot_updated_since_last_tick
+    }
+
+    /// The revision of the inventory, which changes along with every slot or the selected hotbar
+    /// slot and wraps around. Packets that send slots to the client carry it, and the client sends
+    /// back the last one it got with every click.
+    #[must_use]
+    pub const fn state_id(&self) -> u32 {
+        self.state_id
+    }
+
+    /// Whether a click tagged with `client_state_id` was made on the inventory as it is now. If
+    /// not, the client has not seen every change yet, so instead of applying the click it should
+    /// be sent the whole inventory again.
+    #[must_use]
+    pub const fn validate_state(&self, client_state_id: u32) -> bool {
+        self.state_id == client_state_id
+    }
+
+    /// Marks slot `index` as updated and bumps the state id.
+    fn mark_updated(&mut self, index: u16) {
+        self.updated_since_last_tick.insert(u32::from(index));
+        self.state_id = self.state_id.wrapping_add(1);
+    }
+
     #[must_use]
     pub const fn slots(&self) -> &[ItemStack; N] {
         &self.slots
     }
 
+    /// A hash of the non-empty slots and their stacks. Inventories holding the same stacks in the
+    /// same slots hash the same, so this is a cheap way to tell whether a client and the server
+    /// disagree about what a window holds before sending all of it again.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let mut encoded = Vec::new();
+
+        for (index, stack) in self.items() {
+            encoded.clear();
+            // writing to a Vec cannot fail. NBT has no Hash, so the stack is hashed as it is sent
+            stack.encode(&mut encoded).unwrap();
+
+            index.hash(&mut hasher);
+            encoded.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// How many items of `kind` there are, whatever their NBT. Like the other lookups below, this
+    /// only looks at the slots [`Self::try_remove_item`] takes from.
+    #[must_use]
+    pub fn count_of(&self, kind: ItemKind) -> u32 {
+        self.removable_items()
+            .filter(|(_, stack)| stack.item == kind)
+            .map(|(_, stack)| u32::try_from(stack.count).unwrap_or_default())
+            .sum()
+    }
+
+    /// How many items would stack with `stack`, which means they have the same kind and NBT. The
+    /// count of `stack` does not matter.
+    #[must_use]
+    pub fn count_of_stack(&self, stack: &ItemStack) -> u32 {
+        self.removable_items()
+            .filter(|(_, other)| other.item == stack.item && other.nbt == stack.nbt)
+            .map(|(_, other)| u32::try_from(other.count).unwrap_or_default())
+            .sum()
+    }
+
+    /// Whether [`Self::try_remove_item`] could remove `at_least` items of `kind`.
+    #[must_use]
+    pub fn contains(&self, kind: ItemKind, at_least: u32) -> bool {
+        self.count_item(kind) >= at_least
+    }
+
+    /// The first slot holding `kind`, whatever its NBT.
+    #[must_use]
+    pub fn first_slot_of(&self, kind: ItemKind) -> Option<u16> {
+        self.removable_items()
+            .find(|(_, stack)| stack.item == kind)
+            .map(|(slot, _)| slot)
+    }
+
     pub fn clear(&mut self) {
-        for (idx, slot) in self.slots.iter_mut().enumerate() {
+        for idx in 0..u16::try_from(N).unwrap() {
+            let slot = &mut self.slots[usize::from(idx)];
             if slot.is_empty() {
                 continue;
             }
             *slot = ItemStack::EMPTY;
-            self.updated_since_last_tick
-                .insert(u32::try_from(idx).unwrap());
+            self.mark_updated(idx);
         }
     }
 
@@ -92,6 +302,7 @@ impl<const N: usize> Inventory<N> {
 
         self.hand_slot = index;
         self.hand_slot_updated_since_last_tick = true;
+        self.state_id = self.state_id.wrapping_add(1);
     }
 
     #[must_use]
@@ -104,21 +315,104 @@ impl<const N: usize> Inventory<N> {
         self.hand_slot + HAND_START_SLOT
     }
 
-    pub fn get_cursor_mut(&mut self) -> &mut ItemStack {
+    /// The selected hotbar slot, from 0 to 8.
+    #[must_use]
+    pub const fn get_cursor_hand_slot(&self) -> u16 {
+        self.hand_slot
+    }
+
+    pub fn get_cursor_mut(&mut self) -> SlotMut<'_> {
         self.get_hand_slot_mut(self.hand_slot).unwrap()
     }
 
+    /// Takes a single item out of the selected hotbar slot.
     pub fn take_one_held(&mut self) -> ItemStack {
-        // decrement the held item
-        let held_item = self.get_cursor_mut();
+        self.drop_cursor(false)
+    }
+
+    /// Empties the selected hotbar slot, returning the whole stack that was in it.
+    pub fn take_held(&mut self) -> ItemStack {
+        self.drop_cursor(true)
+    }
 
-        if held_item.is_empty() {
-            return ItemStack::EMPTY;
+    /// Takes one item, or the whole stack if `whole_stack` is set, out of the selected hotbar
+    /// slot, like pressing Q does. The returned stack is empty if there was nothing to drop.
+    pub fn drop_cursor(&mut self, whole_stack: bool) -> ItemStack {
+        take_from(&mut self.get_cursor_mut(), whole_stack)
+    }
+
+    /// Takes one item, or the whole stack if `whole_stack` is set, out of slot `index`, such as
+    /// when an item is thrown out of an open window. The returned stack is empty if there was
+    /// nothing to drop.
+    pub fn drop_slot(
+        &mut self,
+        index: u16,
+        whole_stack: bool,
+    ) -> Result<ItemStack, InventoryAccessError> {
+        Ok(take_from(&mut self.get_mut(index)?, whole_stack))
+    }
+
+    /// Puts down `carried`, the stack held on the mouse while a window is open, in slot `index`
+    /// like a click on the slot does. This is not the selected hotbar slot that
+    /// [`Self::get_cursor`] refers to.
+    ///
+    /// The whole stack, or a single item if `split` is set, is merged into a matching or empty
+    /// slot as far as it fits. A different stack in the slot is swapped with `carried` instead.
+    pub fn deposit_cursor(
+        &mut self,
+        index: u16,
+        carried: &mut ItemStack,
+        split: bool,
+    ) -> Result<(), InventoryAccessError> {
+        if carried.is_empty() {
+            return Ok(());
+        }
+
+        let is_player = N == PLAYER_INVENTORY_SIZE;
+
+        ensure!(
+            !is_player || is_valid_for_slot(index, carried.item),
+            InvalidItemForSlotSnafu {
+                index,
+                kind: carried.item
+            }
+        );
+
+        // armor is worn one piece at a time
+        let armor_slots = PlayerInventory::HELMET_SLOT..=PlayerInventory::BOOTS_SLOT;
+        let max = if is_player && armor_slots.contains(&index) {
+            1
+        } else {
+            carried.item.max_stack()
+        };
+
+        let mut slot = self.get_mut(index)?;
+
+        if !slot.is_empty() && (slot.item != carried.item || slot.nbt != carried.nbt) {
+            // a stack too big for the slot stays on the cursor
+            if carried.count <= max {
+                std::mem::swap(&mut *slot, carried);
+            }
+
+            return Ok(());
+        }
+
+        let in_slot = if slot.is_empty() { 0 } else { slot.count };
+        let wanted = if split { 1 } else { carried.count };
+        let moved = wanted.min(max - in_slot.min(max));
+
+        if moved == 0 {
+            return Ok(());
         }
 
-        held_item.count -= 1;
+        *slot = carried.clone().with_count(in_slot + moved);
 
-        ItemStack::new(held_item.item, 1, held_item.nbt.clone())
+        carried.count -= moved;
+        if carried.count == 0 {
+            *carried = ItemStack::EMPTY;
+        }
+
+        Ok(())
     }
 
     pub fn get(&self, index: u16) -> Result<&ItemStack, InventoryAccessError> {
@@ -127,22 +421,97 @@ impl<const N: usize> Inventory<N> {
             .ok_or(InventoryAccessError::InvalidSlot { index })
     }
 
-    pub fn get_mut(&mut self, index: u16) -> Result<&mut ItemStack, InventoryAccessError> {
-        let Some(slot) = self.slots.get_mut(index as usize) else {
+    /// The stack in slot `index`. The slot is marked as updated if the stack is changed.
+    pub fn get_mut(&mut self, index: u16) -> Result<SlotMut<'_>, InventoryAccessError> {
+        let Some(stack) = self.slots.get_mut(usize::from(index)) else {
             return Err(InventoryAccessError::InvalidSlot { index });
         };
 
-        // assume that the slot is updated
-        self.updated_since_last_tick.insert(u32::from(index));
+        Ok(SlotMut {
+            index,
+            before: stack.clone(),
+            stack,
+            updated: &mut self.updated_since_last_tick,
+            state_id: &mut self.state_id,
+        })
+    }
 
-        Ok(slot)
+    /// Swaps the stacks in two slots, marking both as updated unless they held the same stack.
+    pub fn swap(&mut self, index_a: u16, index_b: u16) -> Result<(), InventoryAccessError> {
+        for index in [index_a, index_b] {
+            ensure!(usize::from(index) < N, InvalidSlotSnafu { index });
+        }
+
+        if index_a == index_b {
+            return Ok(());
+        }
+
+        self.slots.swap(usize::from(index_a), usize::from(index_b));
+
+        if self.slots[usize::from(index_a)] != self.slots[usize::from(index_b)] {
+            self.mark_updated(index_a);
+            self.mark_updated(index_b);
+        }
+
+        Ok(())
+    }
+
+    /// Merges partial stacks of the same kind and NBT into as few slots as possible, filling the
+    /// earliest slots first. Only the slots [`Self::try_remove_item`] takes from are compacted, so
+    /// a player's armor, crafting grid and offhand are left alone.
+    pub fn compact(&mut self) {
+        let slots: Vec<u16> = Self::removal_order().into_iter().flatten().collect();
+
+        for (i, &into) in slots.iter().enumerate() {
+            for &from in &slots[i + 1..] {
+                let target = &self.slots[usize::from(into)];
+
+                if target.is_empty() || target.count >= target.item.max_stack() {
+                    break;
+                }
+
+                let source = &self.slots[usize::from(from)];
+
+                if source.item != target.item || source.nbt != target.nbt {
+                    continue;
+                }
+
+                let moved = source.count.min(target.item.max_stack() - target.count);
+
+                self.get_mut(into).unwrap().count += moved;
+
+                let mut source = self.get_mut(from).unwrap();
+                source.count -= moved;
+
+                if source.count == 0 {
+                    *source = ItemStack::EMPTY;
+                }
+            }
+        }
     }
 
-    pub fn swap(&mut self, index_a: u16, index_b: u16) {
-        let index_a = usize::from(index_a);
-        let index_b = usize::from(index_b);
+    /// Reorders the stacks in `range` by `cmp`, keeping stacks that compare equal in the order
+    /// they were in. Empty slots end up last. Only slots whose stack changed are marked as
+    /// updated.
+    pub fn sort_range(
+        &mut self,
+        range: Range<u16>,
+        mut cmp: impl FnMut(&ItemStack, &ItemStack) -> Ordering,
+    ) {
+        let end = usize::from(range.end).min(N);
+        let start = usize::from(range.start).min(end);
+
+        let mut sorted = self.slots[start..end].to_vec();
+        sorted.sort_by(|a, b| match (a.is_empty(), b.is_empty()) {
+            (false, false) => cmp(a, b),
+            (a_empty, b_empty) => a_empty.cmp(&b_empty),
+        });
 
-        self.slots.swap(index_a, index_b);
+        for (idx, stack) in (range.start..).zip(sorted) {
+            if self.slots[usize::from(idx)] != stack {
+                self.set(idx, stack).unwrap();
+            }
+        }
     }
 
     pub fn get_hand_slot(&self, idx: u16) -> Result<&ItemStack, InventoryAccessError> {
@@ -157,7 +526,7 @@ impl<const N: usize> Inventory<N> {
         self.get(idx)
     }
 
-    pub fn get_hand_slot_mut(&mut self, idx: u16) -> Result<&mut ItemStack, InventoryAccessError> {
+    pub fn get_hand_slot_mut(&mut self, idx: u16) -> Result<SlotMut<'_>, InventoryAccessError> {
         const HAND_START_SLOT: u16 = 36;
         const HAND_END_SLOT: u16 = 45;
 
@@ -179,14 +548,13 @@ impl<const N: usize> Inventory<N> {
     ) -> Result<TryAddSlot, InventoryAccessError> {
         let max_stack_size: i8 = to_add.item.max_stack();
 
-        let existing_stack = self.get_mut(slot)?;
+        let mut existing_stack = self.get_mut(slot)?;
 
         if existing_stack.is_empty() {
             return if can_add_to_empty {
                 let new_count = min(to_add.count, max_stack_size);
                 *existing_stack = to_add.clone().with_count(new_count);
                 to_add.count -= new_count;
-                self.updated_since_last_tick.insert(u32::from(slot));
                 return if to_add.count > 0 {
                     Ok(TryAddSlot::Partial)
                 } else {
@@ -205,59 +573,265 @@ impl<const N: usize> Inventory<N> {
             return if to_add.count <= space_left {
                 existing_stack.count += to_add.count;
                 *to_add = ItemStack::EMPTY;
-                self.updated_since_last_tick.insert(u32::from(slot));
                 Ok(TryAddSlot::Complete)
             } else {
                 existing_stack.count = max_stack_size;
                 to_add.count -= space_left;
-                self.updated_since_last_tick.insert(u32::from(slot));
                 Ok(TryAddSlot::Partial)
             };
         }
 
         Ok(TryAddSlot::Skipped)
     }
-}
 
-impl PlayerInventory {
-    pub const BOOTS_SLOT: u16 = 8;
-    pub const CHESTPLATE_SLOT: u16 = 6;
-    pub const HELMET_SLOT: u16 = 5;
-    pub const HOTBAR_START_SLOT: u16 = 36;
-    pub const LEGGINGS_SLOT: u16 = 7;
-    pub const OFFHAND_SLOT: u16 = OFFHAND_SLOT;
+    /// Adds `item` to `slots`, topping up matching stacks before using empty slots, each in the
+    /// order of `slots`.
+    pub fn try_add_item_to(
+        &mut self,
+        mut item: ItemStack,
+        slots: impl IntoIterator<Item = u16, IntoIter: Clone>,
+    ) -> AddItemResult {
+        let slots = slots.into_iter();
+
+        let mut result = AddItemResult {
+            changed_slots: Vec::new(),
+            remaining: None,
+        };
+
+        // try to stack first, then add to empty slots
+        for can_add_to_empty in [false, true] {
+            for slot in slots.clone() {
+                let Ok(add_slot) = self.try_add_to_slot(slot, &mut item, can_add_to_empty) else {
+                    continue;
+                };
+
+                match add_slot {
+                    TryAddSlot::Complete => {
+                        result.changed_slots.push(slot);
+                        return result;
+                    }
+                    TryAddSlot::Partial => result.changed_slots.push(slot),
+                    TryAddSlot::Skipped => {}
+                }
+            }
+        }
+
+        // If there's any remaining item, set it in the result
+        if item.count > 0 {
+            result.remaining = Some(item);
+        }
 
+        result
+    }
+
+    /// The slots items are removed from, in the order they are preferred. A player inventory keeps
+    /// its hotbar for last and never gives up its armor, crafting grid or offhand.
+    fn removal_order() -> [Range<u16>; 2] {
+        if N == PLAYER_INVENTORY_SIZE {
+            [9..HAND_START_SLOT, HAND_START_SLOT..OFFHAND_SLOT]
+        } else {
+            [0..u16::try_from(N).unwrap(), 0..0]
+        }
+    }
+
+    /// The stacks in the slots [`Self::try_remove_item`] takes from, in the order it does.
+    fn removable_items(&self) -> impl Iterator<Item = (u16, &ItemStack)> + '_ {
+        Self::removal_order()
+            .into_iter()
+            .flatten()
+            .filter_map(|slot| Some((slot, self.get(slot).ok()?)))
+            .filter(|(_, stack)| !stack.is_empty())
+    }
+
+    /// How many items of `kind` [`Self::try_remove_item`] could remove.
     #[must_use]
-    pub fn crafting_result(&self, registry: &CraftingRegistry) -> ItemStack {
-        let indices = core::array::from_fn::<u16, 4, _>(|i| (u16::try_from(i).unwrap() + 1));
+    pub fn count_item(&self, kind: ItemKind) -> u32 {
+        Self::removal_order()
+            .into_iter()
+            .flatten()
+            .filter_map(|slot| self.get(slot).ok())
+            .filter(|stack| is_plain(stack, kind))
+            .map(|stack| u32::try_from(stack.count).unwrap_or_default())
+            .sum()
+    }
+
+    /// Removes up to `count` items of `kind`, taking from partially filled stacks first so that
+    /// as few slots as possible end up empty.
+    ///
+    /// Only stacks without NBT count as `kind`, so a renamed or enchanted item is never used up by
+    /// accident, just as such items only stack with identical ones.
+    pub fn try_remove_item(&mut self, kind: ItemKind, count: i8) -> RemoveItemResult {
+        let mut result = RemoveItemResult {
+            changed_slots: Vec::new(),
+            remaining: count.max(0),
+        };
+
+        if result.remaining == 0 {
+            return result;
+        }
+
+        let max_stack_size = kind.max_stack();
+
+        for slots in Self::removal_order() {
+            for partial_first in [true, false] {
+                for slot in slots.clone() {
+                    let Ok(stack) = self.get(slot) else {
+                        continue;
+                    };
+
+                    let partial = stack.count < max_stack_size;
+
+                    if !is_plain(stack, kind) || partial != partial_first {
+                        continue;
+                    }
+
+                    let Ok(mut stack) = self.get_mut(slot) else {
+                        continue;
+                    };
+
+                    let taken = stack.count.min(result.remaining);
+                    stack.count -= taken;
+
+                    if stack.count == 0 {
+                        *stack = ItemStack::EMPTY;
+                    }
+
+                    result.remaining -= taken;
+                    result.changed_slots.push(slot);
+
+                    if result.remaining == 0 {
+                        return result;
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Removes `count` items of `kind` like [`Self::try_remove_item`], returning how many could
+    /// not be removed. Callers that must not take anything unless all of it is there should check
+    /// [`Self::count_item`] first.
+    pub fn remove_item(&mut self, kind: ItemKind, count: i8) -> i8 {
+        self.try_remove_item(kind, count).remaining
+    }
 
-        let mut min_count = i8::MAX;
+    /// Moves every stack into `other` with [`Inventory::try_add_item`], like dumping a chest into
+    /// a player's inventory. Stacks that only partly fit leave the rest in their slot, and those
+    /// slots are returned with what is left in them.
+    ///
+    /// Only the slots [`Self::try_remove_item`] takes from are moved, so a player's armor,
+    /// crafting grid and offhand stay where they are.
+    pub fn drain_into<const M: usize>(
+        &mut self,
+        other: &mut Inventory<M>,
+    ) -> Vec<(u16, ItemStack)> {
+        let mut remaining = Vec::new();
 
-        let items: Crafting2x2 = indices.map(|idx| {
-            let stack = self.get(idx).unwrap();
+        for slot in Self::removal_order().into_iter().flatten() {
+            let mut stack = self.get_mut(slot).unwrap();
 
             if stack.is_empty() {
-                return ItemKind::Air;
+                continue;
             }
 
-            min_count = min_count.min(stack.count);
-            stack.item
-        });
+            let moved = std::mem::replace(&mut *stack, ItemStack::EMPTY);
+
+            // the hotbar first order of `try_add_item` only fits player inventories
+            let rest = if M == PLAYER_INVENTORY_SIZE {
+                other.try_add_item(moved)
+            } else {
+                other.try_add_item_to(moved, 0..u16::try_from(M).unwrap())
+            };
 
-        let result = registry
-            .get_result_2x2(items)
-            .cloned()
-            .unwrap_or(ItemStack::EMPTY);
+            if let Some(rest) = rest.remaining {
+                remaining.push((slot, rest.clone()));
+                *stack = rest;
+            }
+        }
 
-        // if result.is_empty() {
-        //     return ItemStack::EMPTY;
-        // }
-        //
-        // let new_count = 64.min(min_count as i32 * result.count as i32);
-        //
-        // result.count = new_count as i8;
+        remaining
+    }
+}
 
-        result
+/// The armor slot of a player inventory `kind` is worn in, if it is worn at all.
+pub(crate) fn armor_slot(kind: ItemKind) -> Option<u16> {
+    let name = kind.to_str();
+
+    if name.ends_with("_helmet")
+        || name.ends_with("_head")
+        || name.ends_with("_skull")
+        || kind == ItemKind::CarvedPumpkin
+    {
+        Some(PlayerInventory::HELMET_SLOT)
+    } else if name.ends_with("_chestplate") || kind == ItemKind::Elytra {
+        Some(PlayerInventory::CHESTPLATE_SLOT)
+    } else if name.ends_with("_leggings") {
+        Some(PlayerInventory::LEGGINGS_SLOT)
+    } else if name.ends_with("_boots") {
+        Some(PlayerInventory::BOOTS_SLOT)
+    } else {
+        None
+    }
+}
+
+/// Whether `kind` may be put in slot `index` of a player inventory. The armor slots only take
+/// the armor worn there, and every other slot takes anything.
+#[must_use]
+pub fn is_valid_for_slot(index: u16, kind: ItemKind) -> bool {
+    let armor_slots = PlayerInventory::HELMET_SLOT..=PlayerInventory::BOOTS_SLOT;
+
+    kind == ItemKind::Air || !armor_slots.contains(&index) || armor_slot(kind) == Some(index)
+}
+
+/// Orders stacks by item kind, putting the fuller stack of a kind first.
+#[must_use]
+pub fn default_order(a: &ItemStack, b: &ItemStack) -> Ordering {
+    a.item
+        .to_raw()
+        .cmp(&b.item.to_raw())
+        .then(b.count.cmp(&a.count))
+}
+
+/// Takes one item, or the whole stack if `whole_stack` is set, out of `slot`.
+fn take_from(slot: &mut ItemStack, whole_stack: bool) -> ItemStack {
+    if slot.is_empty() {
+        return ItemStack::EMPTY;
+    }
+
+    if whole_stack || slot.count == 1 {
+        return std::mem::replace(slot, ItemStack::EMPTY);
+    }
+
+    slot.count -= 1;
+    slot.clone().with_count(1)
+}
+
+/// Whether `stack` holds `kind` without any NBT.
+fn is_plain(stack: &ItemStack, kind: ItemKind) -> bool {
+    !stack.is_empty() && stack.item == kind && stack.nbt.is_none()
+}
+
+impl PlayerInventory {
+    pub const BOOTS_SLOT: u16 = 8;
+    pub const CHESTPLATE_SLOT: u16 = 6;
+    pub const HELMET_SLOT: u16 = 5;
+    pub const HOTBAR_START_SLOT: u16 = 36;
+    pub const LEGGINGS_SLOT: u16 = 7;
+    pub const OFFHAND_SLOT: u16 = OFFHAND_SLOT;
+
+    /// Sorts the main inventory with [`default_order`], leaving the armor, hotbar and offhand as
+    /// they are.
+    pub fn sort_main(&mut self) {
+        self.sort_range(9..HAND_START_SLOT, default_order);
+    }
+
+    /// The result of the inventory's own 2x2 grid, with as many items as the ingredients make at
+    /// once, up to a full stack. This is what the result slot shows.
+    #[must_use]
+    pub fn crafting_result(&self, registry: &CraftingRegistry) -> ItemStack {
+        let result = self.crafting_result_once(registry);
+        crafting::scale_to_ingredients(result, &self.slots[1..=4])
     }
 
     pub fn set_hotbar(&mut self, idx: u16, stack: ItemStack) {
@@ -276,18 +850,26 @@ impl PlayerInventory {
         self.set(Self::OFFHAND_SLOT, stack).unwrap();
     }
 
+    /// # Panics
+    /// If `stack` is not worn as a helmet.
     pub fn set_helmet(&mut self, stack: ItemStack) {
         self.set(Self::HELMET_SLOT, stack).unwrap();
     }
 
+    /// # Panics
+    /// If `stack` is not worn as a chestplate.
     pub fn set_chestplate(&mut self, stack: ItemStack) {
         self.set(Self::CHESTPLATE_SLOT, stack).unwrap();
     }
 
+    /// # Panics
+    /// If `stack` is not worn as leggings.
     pub fn set_leggings(&mut self, stack: ItemStack) {
         self.set(Self::LEGGINGS_SLOT, stack).unwrap();
     }
 
+    /// # Panics
+    /// If `stack` is not worn as boots.
     pub fn set_boots(&mut self, stack: ItemStack) {
         self.set(Self::BOOTS_SLOT, stack).unwrap();
     }
@@ -312,45 +894,91 @@ impl PlayerInventory {
         self.get(Self::BOOTS_SLOT).unwrap()
     }
 
-    pub fn try_add_item(&mut self, mut item: ItemStack) -> AddItemResult {
-        let mut result = AddItemResult { remaining: None };
+    /// Puts `stack` on in the armor slot it is worn in, like right-clicking with it, and returns
+    /// what was worn there before so it can be put in the hand instead. A stack that is not worn
+    /// anywhere is returned as it is.
+    pub fn equip(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        let Some(index) = armor_slot(stack.item) else {
+            return Some(stack);
+        };
 
-        // Try to add to hot bar (36-45) first, then the rest of the inventory (9-35)
-        // try to stack first
-        for slot in (36..=44).chain(9..36) {
-            let Ok(add_slot) = self.try_add_to_slot(slot, &mut item, false) else {
-                unreachable!("try_add_item should always return Ok");
-            };
+        let previous = std::mem::replace(&mut *self.get_mut(index).unwrap(), stack);
 
-            match add_slot {
-                TryAddSlot::Complete => {
-                    return result;
-                }
-                TryAddSlot::Partial | TryAddSlot::Skipped => {}
-            }
-        }
+        (!previous.is_empty()).then_some(previous)
+    }
 
-        // Try to add to hot bar (36-44) first, then the rest of the inventory (9-35)
-        // now try to add to empty slots
-        for slot in (36..=44).chain(9..36) {
-            let Ok(add_slot) = self.try_add_to_slot(slot, &mut item, true) else {
-                unreachable!("try_add_item should always return Ok");
-            };
+    pub fn try_add_item(&mut self, item: ItemStack) -> AddItemResult {
+        // the hotbar is filled before the rest of the inventory
+        self.try_add_item_to(item, (36..=44).chain(9..36))
+    }
+
+    /// Moves the stack in `slot` from the main inventory to the hotbar, or from the hotbar to the
+    /// main inventory, like a shift-click does. Matching stacks are topped up before empty slots
+    /// are used, and whatever does not fit stays in `slot`.
+    ///
+    /// Only the main inventory and the hotbar are moved from, so armor, the crafting grid and the
+    /// offhand are left as they are.
+    pub fn quick_move(&mut self, slot: u16) -> Result<(), InventoryAccessError> {
+        let mut stack = self.get(slot)?.clone();
+
+        let targets = match slot {
+            9..HAND_START_SLOT => HAND_START_SLOT..OFFHAND_SLOT,
+            HAND_START_SLOT..OFFHAND_SLOT => 9..HAND_START_SLOT,
+            _ => return Ok(()),
+        };
+
+        if stack.is_empty() {
+            return Ok(());
+        }
 
-            match add_slot {
-                TryAddSlot::Complete => {
-                    return result;
+        for can_add_to_empty in [false, true] {
+            for target in targets.clone() {
+                if stack.is_empty() {
+                    break;
                 }
-                TryAddSlot::Partial | TryAddSlot::Skipped => {}
+
+                self.try_add_to_slot(target, &mut stack, can_add_to_empty)?;
             }
         }
 
-        // If there's any remaining item, set it in the result
-        if item.count > 0 {
-            result.remaining = Some(item);
+        *self.get_mut(slot)? = if stack.is_empty() {
+            ItemStack::EMPTY
+        } else {
+            stack
+        };
+
+        Ok(())
+    }
+
+    /// Swaps `slot` with hotbar slot `hotbar_index` (0 to 8), like pressing a number key while
+    /// hovering `slot`.
+    pub fn swap_hotbar(&mut self, slot: u16, hotbar_index: u8) -> Result<(), InventoryAccessError> {
+        ensure!(hotbar_index < 9, InvalidHotbarIndexSnafu {
+            index: hotbar_index
+        });
+
+        self.swap_checked(slot, HAND_START_SLOT + u16::from(hotbar_index))
+    }
+
+    /// Swaps `slot` with the offhand, like pressing F while hovering `slot`.
+    pub fn swap_offhand(&mut self, slot: u16) -> Result<(), InventoryAccessError> {
+        self.swap_checked(slot, OFFHAND_SLOT)
+    }
+
+    /// [`Self::swap`], but nothing is swapped if either slot would end up with armor it cannot
+    /// hold.
+    fn swap_checked(&mut self, index_a: u16, index_b: u16) -> Result<(), InventoryAccessError> {
+        let kind_a = self.get(index_a)?.item;
+        let kind_b = self.get(index_b)?.item;
+
+        for (index, kind) in [(index_a, kind_b), (index_b, kind_a)] {
+            ensure!(is_valid_for_slot(index, kind), InvalidItemForSlotSnafu {
+                index,
+                kind
+            });
         }
 
-        result
+        self.swap(index_a, index_b)
     }
 }
 
@@ -372,117 +1000,1122 @@ pub fn slot_index_from_hand(hand_idx: u8) -> u16 {
 // todo: not sure if this is correct
 pub const OFFHAND_SLOT: u16 = 45;
 
-// #[cfg(test)]
-// mod tests {
-//     use valence_protocol::ItemKind;
-//
-//     use super::*;
-//
-//     #[test]
-//     fn test_try_add_item_empty_inventory() {
-//         let mut inventory = PlayerInventory::default();
-//         let item = ItemStack::new(ItemKind::Stone, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert_eq!(result.changed_slots, vec![36]);
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().count, 64);
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_partially_filled_slot() {
-//         let mut inventory = PlayerInventory::default();
-//         inventory
-//             .set(36, ItemStack::new(ItemKind::Stone, 32, None))
-//             .unwrap();
-//         let item = ItemStack::new(ItemKind::Stone, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert_eq!(result.changed_slots, vec![36, 37]);
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().count, 64);
-//         assert_eq!(inventory.get(37).unwrap().count, 32);
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_full_inventory() {
-//         let mut inventory = PlayerInventory::default();
-//         for slot in 0..46 {
-//             inventory
-//                 .set(slot, ItemStack::new(ItemKind::Stone, 64, None))
-//                 .unwrap();
-//         }
-//         let item = ItemStack::new(ItemKind::Stone, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert!(result.changed_slots.is_empty());
-//         assert_eq!(
-//             result.remaining,
-//             Some(ItemStack::new(ItemKind::Stone, 64, None))
-//         );
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_different_items() {
-//         let mut inventory = PlayerInventory::default();
-//         inventory
-//             .set(36, ItemStack::new(ItemKind::Stone, 64, None))
-//             .unwrap();
-//         let item = ItemStack::new(ItemKind::Dirt, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert_eq!(result.changed_slots, vec![37]);
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().item, ItemKind::Stone);
-//         assert_eq!(inventory.get(37).unwrap().item, ItemKind::Dirt);
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_partial_stack() {
-//         let mut inventory = PlayerInventory::default();
-//         let item = ItemStack::new(ItemKind::Stone, 32, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert_eq!(result.changed_slots, vec![36]);
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().count, 32);
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_multiple_partial_stacks() {
-//         let mut inventory = PlayerInventory::default();
-//         inventory
-//             .set(36, ItemStack::new(ItemKind::Stone, 32, None))
-//             .unwrap();
-//         inventory
-//             .set(37, ItemStack::new(ItemKind::Stone, 32, None))
-//             .unwrap();
-//         let item = ItemStack::new(ItemKind::Stone, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().count, 64);
-//         assert_eq!(inventory.get(37).unwrap().count, 64);
-//         assert_eq!(inventory.get(38).unwrap().count, 0);
-//
-//         assert_eq!(result.changed_slots, vec![36, 37]);
-//     }
-//
-//     #[test]
-//     fn test_try_add_item_overflow() {
-//         let mut inventory = PlayerInventory::default();
-//         inventory
-//             .set(36, ItemStack::new(ItemKind::Stone, 63, None))
-//             .unwrap();
-//         let item = ItemStack::new(ItemKind::Stone, 64, None);
-//         let result = inventory.try_add_item(item);
-//
-//         assert_eq!(result.changed_slots, vec![36, 37]);
-//         assert!(result.remaining.is_none());
-//         assert_eq!(inventory.get(36).unwrap().count, 64);
-//         assert_eq!(inventory.get(37).unwrap().count, 63);
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, nbt::Compound};
+
+    use super::*;
+
+    #[test]
+    fn test_try_add_item_empty_inventory() {
+        let mut inventory = PlayerInventory::default();
+        let item = ItemStack::new(ItemKind::Stone, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert_eq!(result.changed_slots, vec![36]);
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().count, 64);
+    }
+
+    #[test]
+    fn test_try_add_item_partially_filled_slot() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 32, None))
+            .unwrap();
+        let item = ItemStack::new(ItemKind::Stone, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert_eq!(result.changed_slots, vec![36, 37]);
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().count, 64);
+        assert_eq!(inventory.get(37).unwrap().count, 32);
+    }
+
+    #[test]
+    fn test_try_add_item_full_inventory() {
+        let mut inventory = PlayerInventory::default();
+        for slot in 0..46 {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Stone, 64, None))
+                .unwrap();
+        }
+        let item = ItemStack::new(ItemKind::Stone, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert!(result.changed_slots.is_empty());
+        assert_eq!(
+            result.remaining,
+            Some(ItemStack::new(ItemKind::Stone, 64, None))
+        );
+    }
+
+    #[test]
+    fn test_try_add_item_different_items() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 64, None))
+            .unwrap();
+        let item = ItemStack::new(ItemKind::Dirt, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert_eq!(result.changed_slots, vec![37]);
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().item, ItemKind::Stone);
+        assert_eq!(inventory.get(37).unwrap().item, ItemKind::Dirt);
+    }
+
+    #[test]
+    fn test_try_add_item_partial_stack() {
+        let mut inventory = PlayerInventory::default();
+        let item = ItemStack::new(ItemKind::Stone, 32, None);
+        let result = inventory.try_add_item(item);
+
+        assert_eq!(result.changed_slots, vec![36]);
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().count, 32);
+    }
+
+    #[test]
+    fn test_try_add_item_multiple_partial_stacks() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 32, None))
+            .unwrap();
+        inventory
+            .set(37, ItemStack::new(ItemKind::Stone, 32, None))
+            .unwrap();
+        let item = ItemStack::new(ItemKind::Stone, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().count, 64);
+        assert_eq!(inventory.get(37).unwrap().count, 64);
+        assert_eq!(inventory.get(38).unwrap().count, 0);
+
+        assert_eq!(result.changed_slots, vec![36, 37]);
+    }
+
+    #[test]
+    fn test_try_add_item_overflow() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 63, None))
+            .unwrap();
+        let item = ItemStack::new(ItemKind::Stone, 64, None);
+        let result = inventory.try_add_item(item);
+
+        assert_eq!(result.changed_slots, vec![36, 37]);
+        assert!(result.remaining.is_none());
+        assert_eq!(inventory.get(36).unwrap().count, 64);
+        assert_eq!(inventory.get(37).unwrap().count, 63);
+    }
+
+    #[test]
+    fn test_try_add_item_to_hotbar_only() {
+        let mut inventory = PlayerInventory::default();
+        for slot in 36..=44 {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Dirt, 64, None))
+                .unwrap();
+        }
+        inventory
+            .set(40, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        let item = ItemStack::new(ItemKind::Stone, 20, None);
+        let result = inventory.try_add_item_to(item, 36..=44);
+
+        assert_eq!(result.changed_slots, vec![40]);
+        assert_eq!(
+            result.remaining,
+            Some(ItemStack::new(ItemKind::Stone, 16, None))
+        );
+        assert_eq!(inventory.get(40).unwrap().count, 64);
+
+        // the main inventory had room, but was not asked for
+        assert!(inventory.slots()[9..36].iter().all(ItemStack::is_empty));
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![40]);
+    }
+
+    #[test]
+    fn test_try_remove_item_drains_partial_stacks_and_the_hotbar_last() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 64, None))
+            .unwrap();
+        inventory
+            .set(20, ItemStack::new(ItemKind::Stone, 5, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        assert_eq!(inventory.count_item(ItemKind::Stone), 79);
+
+        let result = inventory.try_remove_item(ItemKind::Stone, 70);
+
+        assert_eq!(result.changed_slots, vec![20, 9, 36]);
+        assert_eq!(result.remaining, 0);
+        assert!(inventory.get(20).unwrap().is_empty());
+        assert!(inventory.get(9).unwrap().is_empty());
+        assert_eq!(inventory.get(36).unwrap().count, 9);
+
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![9, 20, 36]);
+    }
+
+    #[test]
+    fn test_try_remove_item_not_enough() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(10, ItemStack::new(ItemKind::Stone, 3, None))
+            .unwrap();
+
+        // armor, crafting and offhand slots are never used up
+        inventory
+            .set(1, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+        inventory
+            .set(OFFHAND_SLOT, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+
+        assert_eq!(inventory.count_item(ItemKind::Stone), 3);
+
+        let result = inventory.try_remove_item(ItemKind::Stone, 5);
+
+        assert_eq!(result.changed_slots, vec![10]);
+        assert_eq!(result.remaining, 2);
+        assert!(inventory.get(10).unwrap().is_empty());
+        assert_eq!(inventory.get(1).unwrap().count, 1);
+        assert_eq!(inventory.get(OFFHAND_SLOT).unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_count_of_across_slots() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(40, ItemStack::new(ItemKind::Diamond, 3, None))
+            .unwrap();
+        // the offhand and armor are not counted, as they cannot be removed
+        inventory.set_offhand(ItemStack::new(ItemKind::Diamond, 5, None));
+        inventory.set_helmet(ItemStack::new(ItemKind::DiamondHelmet, 1, None));
+        inventory
+            .set(12, ItemStack::new(ItemKind::Diamond, 64, None))
+            .unwrap();
+        inventory
+            .set(13, ItemStack::new(ItemKind::Stone, 5, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        assert_eq!(inventory.count_of(ItemKind::Diamond), 67);
+        assert!(inventory.contains(ItemKind::Diamond, 67));
+        assert!(!inventory.contains(ItemKind::Diamond, 68));
+        assert!(inventory.contains(ItemKind::Emerald, 0));
+        assert!(!inventory.contains(ItemKind::Emerald, 1));
+        assert_eq!(inventory.count_of(ItemKind::DiamondHelmet), 0);
+
+        assert_eq!(inventory.first_slot_of(ItemKind::Diamond), Some(12));
+        assert_eq!(inventory.first_slot_of(ItemKind::Emerald), None);
+
+        // only reading never marks slots as updated
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn test_count_of_stack_matches_nbt() {
+        let mut named = Compound::new();
+        named.insert("display", "Lucky Stone");
+
+        let lucky = ItemStack::new(ItemKind::Stone, 1, Some(named));
+        let plain = ItemStack::new(ItemKind::Stone, 1, None);
+
+        let mut inventory = PlayerInventory::default();
+        inventory.set(9, lucky.clone().with_count(2)).unwrap();
+        inventory.set(10, plain.clone().with_count(7)).unwrap();
+        inventory.set(36, lucky.clone().with_count(4)).unwrap();
+
+        assert_eq!(inventory.count_of(ItemKind::Stone), 13);
+        assert_eq!(inventory.count_of_stack(&lucky), 6);
+        assert_eq!(inventory.count_of_stack(&plain), 7);
+        assert_eq!(inventory.first_slot_of(ItemKind::Stone), Some(9));
+    }
+
+    #[test]
+    fn test_try_remove_item_skips_stacks_with_nbt() {
+        let mut named = Compound::new();
+        named.insert("display", "Lucky Stone");
+
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 5, Some(named)))
+            .unwrap();
+        inventory
+            .set(10, ItemStack::new(ItemKind::Stone, 5, None))
+            .unwrap();
+
+        assert_eq!(inventory.count_item(ItemKind::Stone), 5);
+        assert!(inventory.contains(ItemKind::Stone, 5));
+        assert!(!inventory.contains(ItemKind::Stone, 6));
+
+        let result = inventory.try_remove_item(ItemKind::Stone, 5);
+
+        assert_eq!(result.changed_slots, vec![10]);
+        assert_eq!(result.remaining, 0);
+        assert_eq!(inventory.get(9).unwrap().count, 5);
+    }
+
+    #[test]
+    fn test_remove_item_across_partial_stacks() {
+        let mut inventory = PlayerInventory::default();
+        for slot in [9, 15, 40] {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Dirt, 20, None))
+                .unwrap();
+        }
+
+        assert_eq!(inventory.remove_item(ItemKind::Dirt, 50), 0);
+
+        // the main inventory goes first
+        assert!(inventory.get(9).unwrap().is_empty());
+        assert!(inventory.get(15).unwrap().is_empty());
+        assert_eq!(inventory.get(40).unwrap().count, 10);
+    }
+
+    #[test]
+    fn test_remove_item_insufficient_count() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Dirt, 4, None))
+            .unwrap();
+        inventory
+            .set(
+                PlayerInventory::HELMET_SLOT,
+                ItemStack::new(ItemKind::Dirt, 1, None),
+            )
+            .unwrap();
+
+        assert_eq!(inventory.remove_item(ItemKind::Dirt, 10), 6);
+        assert!(inventory.get(12).unwrap().is_empty());
+        assert_eq!(
+            inventory.get(PlayerInventory::HELMET_SLOT).unwrap().count,
+            1
+        );
+
+        assert_eq!(inventory.remove_item(ItemKind::Stone, 1), 1);
+    }
+
+    #[test]
+    fn test_quick_move_between_main_and_hotbar() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(38, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        // tops up the stack in the hotbar, then starts a new one in its first empty slot
+        inventory.quick_move(12).unwrap();
+        assert!(inventory.get(12).unwrap().is_empty());
+        assert_eq!(inventory.get(38).unwrap().count, 64);
+        assert_eq!(inventory.get(36).unwrap().count, 6);
+        assert!(inventory.updated_since_last_tick.contains(12));
+        assert!(inventory.updated_since_last_tick.contains(36));
+        assert!(inventory.updated_since_last_tick.contains(38));
+
+        inventory.quick_move(36).unwrap();
+        assert!(inventory.get(36).unwrap().is_empty());
+        assert_eq!(inventory.get(9).unwrap().count, 6);
+    }
+
+    #[test]
+    fn test_quick_move_into_full_hotbar() {
+        let mut inventory = PlayerInventory::default();
+        for slot in 36..45 {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Dirt, 64, None))
+                .unwrap();
+        }
+        inventory
+            .set(40, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+        inventory
+            .set(20, ItemStack::new(ItemKind::Stone, 30, None))
+            .unwrap();
+
+        inventory.quick_move(20).unwrap();
+
+        // only 4 fit, the rest stays where it was
+        assert_eq!(inventory.get(40).unwrap().count, 64);
+        assert_eq!(
+            inventory.get(20).unwrap(),
+            &ItemStack::new(ItemKind::Stone, 26, None)
+        );
+    }
+
+    #[test]
+    fn test_quick_move_ignores_other_slots() {
+        let mut inventory = PlayerInventory::default();
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        inventory.set_helmet(helmet.clone());
+
+        inventory.quick_move(PlayerInventory::HELMET_SLOT).unwrap();
+        assert_eq!(inventory.get_helmet(), &helmet);
+        assert!(inventory.get(9).unwrap().is_empty());
+        assert!(inventory.get(36).unwrap().is_empty());
+
+        assert!(inventory.quick_move(46).is_err());
+    }
+
+    #[test]
+    fn armor_slots_only_take_matching_armor() {
+        let mut inventory = PlayerInventory::default();
+
+        let helmet = ItemStack::new(ItemKind::DiamondHelmet, 1, None);
+        inventory.set(5, helmet.clone()).unwrap();
+        assert_eq!(*inventory.get_helmet(), helmet);
+
+        let stone = ItemStack::new(ItemKind::Stone, 1, None);
+        let result = inventory.set(5, stone.clone());
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidItemForSlot {
+                index: 5,
+                kind: ItemKind::Stone
+            })
+        ));
+        assert_eq!(*inventory.get_helmet(), helmet);
+
+        // a helmet is not worn on the feet
+        let result = inventory.set(PlayerInventory::BOOTS_SLOT, helmet);
+        assert!(result.is_err());
+
+        // other slots, and other inventories, take anything
+        inventory.set(9, stone.clone()).unwrap();
+        Inventory::<27>::default().set(5, stone).unwrap();
+        inventory.set(5, ItemStack::EMPTY).unwrap();
+
+        assert!(is_valid_for_slot(5, ItemKind::CarvedPumpkin));
+        assert!(is_valid_for_slot(6, ItemKind::Elytra));
+        assert!(!is_valid_for_slot(7, ItemKind::IronBoots));
+    }
+
+    #[test]
+    fn draining_updates_reports_every_changed_slot_once() {
+        let mut inventory = PlayerInventory::default();
+        let stone = ItemStack::new(ItemKind::Stone, 3, None);
+
+        inventory.set(9, stone.clone()).unwrap();
+        inventory.try_add_item(ItemStack::new(ItemKind::Dirt, 1, None));
+
+        // set and emptied again within the same tick
+        inventory.set(20, stone.clone()).unwrap();
+        inventory.set(20, ItemStack::EMPTY).unwrap();
+
+        inventory.set_cursor(4);
+
+        let updates = inventory.drain_updates();
+        assert_eq!(updates, InventoryUpdates {
+            slots: vec![
+                (9, stone),
+                (20, ItemStack::EMPTY),
+                (36, ItemStack::new(ItemKind::Dirt, 1, None)),
+            ],
+            hand_slot: Some(4),
+        });
+
+        assert_eq!(inventory.drain_updates(), InventoryUpdates::default());
+        assert!(inventory.updated_since_last_tick.is_empty());
+        assert!(!inventory.hand_slot_updated_since_last_tick);
+    }
+
+    #[test]
+    fn only_real_changes_mark_slots() {
+        let mut inventory = PlayerInventory::default();
+        let stone = ItemStack::new(ItemKind::Stone, 5, None);
+        inventory.set(9, stone.clone()).unwrap();
+        inventory.drain_updates();
+
+        // reading through a mutable borrow, or writing back the same stack
+        assert_eq!(inventory.get_mut(9).unwrap().count, 5);
+        *inventory.get_mut(10).unwrap() = ItemStack::EMPTY;
+        inventory.set(9, stone).unwrap();
+        assert_eq!(inventory.drain_changed().count(), 0);
+
+        inventory.get_mut(9).unwrap().count -= 1;
+        inventory.set_hotbar(2, ItemStack::new(ItemKind::Dirt, 1, None));
+        inventory.take_one_held();
+
+        assert!(inventory.is_updated(9));
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![9, 38]);
+        assert!(!inventory.is_updated(9));
+
+        inventory.clear();
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![9, 38]);
+    }
+
+    #[test]
+    fn taking_the_held_stack_empties_the_slot() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(3, ItemStack::new(ItemKind::Stone, 5, None));
+        inventory.set_cursor(3);
+
+        assert_eq!(
+            inventory.take_one_held(),
+            ItemStack::new(ItemKind::Stone, 1, None)
+        );
+        assert_eq!(
+            inventory.take_held(),
+            ItemStack::new(ItemKind::Stone, 4, None)
+        );
+        assert!(inventory.get_cursor().is_empty());
+        assert_eq!(inventory.take_held(), ItemStack::EMPTY);
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![39]);
+    }
+
+    #[test]
+    fn dropping_one_item_leaves_the_rest() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        let dropped = inventory.drop_slot(12, false).unwrap();
+
+        assert_eq!(dropped, ItemStack::new(ItemKind::Stone, 1, None));
+        assert_eq!(inventory.get(12).unwrap().count, 9);
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![12]);
+
+        // dropping the last item empties the slot
+        inventory
+            .set(13, ItemStack::new(ItemKind::Dirt, 1, None))
+            .unwrap();
+        assert_eq!(inventory.drop_slot(13, false).unwrap().count, 1);
+        assert!(inventory.get(13).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dropping_the_whole_stack_empties_the_slot() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(4, ItemStack::new(ItemKind::Stone, 10, None));
+        inventory.set_cursor(4);
+        inventory.updated_since_last_tick.clear();
+
+        assert_eq!(
+            inventory.drop_cursor(true),
+            ItemStack::new(ItemKind::Stone, 10, None)
+        );
+        assert!(inventory.get_cursor().is_empty());
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![40]);
+
+        // nothing is dropped from an empty slot, and the slot is not marked
+        assert_eq!(inventory.drop_cursor(true), ItemStack::EMPTY);
+        assert_eq!(inventory.drop_slot(9, false).unwrap(), ItemStack::EMPTY);
+        assert_eq!(inventory.drain_changed().count(), 0);
+
+        let result = inventory.drop_slot(46, true);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidSlot { index: 46 })
+        ));
+    }
+
+    #[test]
+    fn depositing_the_whole_cursor() {
+        let mut chest = ChestInventory::default();
+        let mut carried = ItemStack::new(ItemKind::Stone, 20, None);
+
+        chest.deposit_cursor(4, &mut carried, false).unwrap();
+        assert_eq!(
+            chest.get(4).unwrap(),
+            &ItemStack::new(ItemKind::Stone, 20, None)
+        );
+        assert_eq!(carried, ItemStack::EMPTY);
+
+        // a matching stack is merged into the slot
+        let mut carried = ItemStack::new(ItemKind::Stone, 30, None);
+        chest.deposit_cursor(4, &mut carried, false).unwrap();
+        assert_eq!(chest.get(4).unwrap().count, 50);
+        assert_eq!(carried, ItemStack::EMPTY);
+
+        assert_eq!(chest.drain_changed().collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    fn depositing_into_a_nearly_full_slot_keeps_the_rest() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        let mut carried = ItemStack::new(ItemKind::Stone, 10, None);
+        inventory.deposit_cursor(12, &mut carried, false).unwrap();
+
+        assert_eq!(inventory.get(12).unwrap().count, 64);
+        assert_eq!(carried, ItemStack::new(ItemKind::Stone, 6, None));
+        assert!(inventory.is_updated(12));
+
+        // nothing fits in a full slot, so nothing changes
+        inventory.updated_since_last_tick.clear();
+        inventory.deposit_cursor(12, &mut carried, false).unwrap();
+        assert_eq!(carried.count, 6);
+        assert!(!inventory.is_updated(12));
+    }
+
+    #[test]
+    fn splitting_deposits_a_single_item() {
+        let mut inventory = PlayerInventory::default();
+        let mut carried = ItemStack::new(ItemKind::Dirt, 5, None);
+
+        inventory.deposit_cursor(20, &mut carried, true).unwrap();
+        inventory.deposit_cursor(20, &mut carried, true).unwrap();
+        inventory.deposit_cursor(21, &mut carried, true).unwrap();
+
+        assert_eq!(
+            inventory.get(20).unwrap(),
+            &ItemStack::new(ItemKind::Dirt, 2, None)
+        );
+        assert_eq!(
+            inventory.get(21).unwrap(),
+            &ItemStack::new(ItemKind::Dirt, 1, None)
+        );
+        assert_eq!(carried.count, 2);
+        assert_eq!(inventory.drain_changed().collect::<Vec<_>>(), vec![20, 21]);
+    }
+
+    #[test]
+    fn depositing_a_different_item_swaps_it() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 3, None))
+            .unwrap();
+
+        let mut carried = ItemStack::new(ItemKind::Dirt, 7, None);
+        inventory.deposit_cursor(9, &mut carried, true).unwrap();
+
+        assert_eq!(
+            inventory.get(9).unwrap(),
+            &ItemStack::new(ItemKind::Dirt, 7, None)
+        );
+        assert_eq!(carried, ItemStack::new(ItemKind::Stone, 3, None));
+
+        // armor slots only take matching armor, one piece at a time
+        let mut helmets = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        assert!(inventory.deposit_cursor(8, &mut helmets, false).is_err());
+        inventory
+            .deposit_cursor(PlayerInventory::HELMET_SLOT, &mut helmets, false)
+            .unwrap();
+        assert_eq!(inventory.get_helmet().item, ItemKind::IronHelmet);
+        assert!(helmets.is_empty());
+    }
+
+    #[test]
+    fn swapping_marks_both_slots() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        inventory.swap(9, 40).unwrap();
+
+        assert_eq!(inventory.get(40).unwrap().item, ItemKind::Stone);
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![9, 40]);
+
+        // swapping two empty slots changes nothing
+        inventory.updated_since_last_tick.clear();
+        inventory.swap(10, 11).unwrap();
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn swapping_out_of_range_is_an_error() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+        inventory.updated_since_last_tick.clear();
+
+        let result = inventory.swap(9, 46);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidSlot { index: 46 })
+        ));
+
+        let result = inventory.swap(100, 9);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidSlot { index: 100 })
+        ));
+
+        // swapping a slot with itself changes nothing
+        inventory.swap(9, 9).unwrap();
+
+        assert_eq!(inventory.get(9).unwrap().item, ItemKind::Stone);
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn number_keys_and_f_swap_with_the_hotbar_and_offhand() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Stone, 5, None))
+            .unwrap();
+        inventory.set_hotbar(2, ItemStack::new(ItemKind::Dirt, 3, None));
+        inventory.updated_since_last_tick.clear();
+
+        inventory.swap_hotbar(12, 2).unwrap();
+
+        assert_eq!(inventory.get(12).unwrap().item, ItemKind::Dirt);
+        assert_eq!(inventory.get(38).unwrap().item, ItemKind::Stone);
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![12, 38]);
+
+        inventory.updated_since_last_tick.clear();
+        inventory.swap_offhand(38).unwrap();
+
+        assert!(inventory.get(38).unwrap().is_empty());
+        assert_eq!(inventory.get(OFFHAND_SLOT).unwrap().item, ItemKind::Stone);
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![38, u32::from(OFFHAND_SLOT)]);
+    }
+
+    #[test]
+    fn invalid_hotbar_swaps_change_nothing() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::Stone, 1, None));
+        inventory.updated_since_last_tick.clear();
+
+        let result = inventory.swap_hotbar(12, 9);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidHotbarIndex { index: 9 })
+        ));
+
+        let result = inventory.swap_hotbar(60, 0);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidSlot { index: 60 })
+        ));
+
+        // stone is no helmet
+        let result = inventory.swap_hotbar(PlayerInventory::HELMET_SLOT, 0);
+        assert!(matches!(
+            result,
+            Err(InventoryAccessError::InvalidItemForSlot {
+                index: 5,
+                kind: ItemKind::Stone
+            })
+        ));
+
+        assert_eq!(inventory.get(36).unwrap().item, ItemKind::Stone);
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn inventories_with_the_same_stacks_are_equal() {
+        let mut a = PlayerInventory::default();
+        let mut b = PlayerInventory::default();
+        assert_eq!(a, b);
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        a.set(12, ItemStack::new(ItemKind::Stone, 5, None)).unwrap();
+        b.set(12, ItemStack::new(ItemKind::Stone, 5, None)).unwrap();
+
+        // only `a` still has slot 12 marked as changed
+        b.updated_since_last_tick.clear();
+        assert_eq!(a, b);
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        b.set_cursor(1);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn content_hash_changes_with_the_slots() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(12, ItemStack::new(ItemKind::Stone, 5, None))
+            .unwrap();
+        let before = inventory.content_hash();
+
+        inventory.get_mut(12).unwrap().count = 6;
+        assert_ne!(inventory.content_hash(), before);
+
+        inventory.get_mut(12).unwrap().count = 5;
+        assert_eq!(inventory.content_hash(), before);
+
+        // the same stack in another slot is another inventory
+        inventory.swap(12, 13).unwrap();
+        assert_ne!(inventory.content_hash(), before);
+    }
+
+    #[test]
+    fn equipping_armor_swaps_with_what_was_worn() {
+        let pieces = [
+            (
+                ItemKind::IronHelmet,
+                ItemKind::DiamondHelmet,
+                PlayerInventory::HELMET_SLOT,
+            ),
+            (
+                ItemKind::IronChestplate,
+                ItemKind::Elytra,
+                PlayerInventory::CHESTPLATE_SLOT,
+            ),
+            (
+                ItemKind::IronLeggings,
+                ItemKind::DiamondLeggings,
+                PlayerInventory::LEGGINGS_SLOT,
+            ),
+            (
+                ItemKind::IronBoots,
+                ItemKind::DiamondBoots,
+                PlayerInventory::BOOTS_SLOT,
+            ),
+        ];
+
+        let mut inventory = PlayerInventory::default();
+
+        for (worn, new, slot) in pieces {
+            let previous = inventory.equip(ItemStack::new(worn, 1, None));
+            assert_eq!(previous, None);
+
+            let previous = inventory.equip(ItemStack::new(new, 1, None));
+            assert_eq!(previous, Some(ItemStack::new(worn, 1, None)));
+            assert_eq!(inventory.get(slot).unwrap().item, new);
+            assert!(inventory.is_updated(slot));
+        }
+    }
+
+    #[test]
+    fn equipping_other_items_gives_them_back() {
+        let mut inventory = PlayerInventory::default();
+        let stone = ItemStack::new(ItemKind::Stone, 5, None);
+
+        assert_eq!(inventory.equip(stone.clone()), Some(stone));
+        assert!(inventory.updated_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn compacting_merges_identical_stacks() {
+        let mut named = Compound::new();
+        named.insert("display", "Kit Stone");
+
+        let mut inventory = PlayerInventory::default();
+        let stacks = [
+            (9, ItemStack::new(ItemKind::Stone, 40, None)),
+            (12, ItemStack::new(ItemKind::Stone, 10, Some(named.clone()))),
+            (20, ItemStack::new(ItemKind::Stone, 30, None)),
+            (30, ItemStack::new(ItemKind::Stone, 5, Some(named))),
+            (37, ItemStack::new(ItemKind::Stone, 20, None)),
+            (
+                PlayerInventory::HELMET_SLOT,
+                ItemStack::new(ItemKind::IronHelmet, 1, None),
+            ),
+        ];
+
+        for (idx, stack) in stacks {
+            inventory.set(idx, stack).unwrap();
+        }
+        inventory.updated_since_last_tick.clear();
+
+        inventory.compact();
+
+        let counts: Vec<_> = inventory
+            .items()
+            .map(|(idx, stack)| (idx, stack.count))
+            .collect();
+        assert_eq!(counts, vec![(5, 1), (9, 64), (12, 15), (20, 26)]);
+
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![9, 12, 20, 30, 37]);
+    }
+
+    #[test]
+    fn sorting_only_touches_the_main_inventory() {
+        let mut inventory = PlayerInventory::default();
+        let stacks = [
+            (9, ItemStack::new(ItemKind::Stone, 3, None)),
+            (11, ItemStack::new(ItemKind::Dirt, 64, None)),
+            (15, ItemStack::new(ItemKind::Stone, 64, None)),
+            (36, ItemStack::new(ItemKind::Dirt, 1, None)),
+            (45, ItemStack::new(ItemKind::Shield, 1, None)),
+        ];
+
+        for (idx, stack) in stacks {
+            inventory.set(idx, stack).unwrap();
+        }
+        inventory.updated_since_last_tick.clear();
+
+        inventory.sort_main();
+
+        let kinds: Vec<_> = inventory
+            .items()
+            .map(|(idx, stack)| (idx, stack.item, stack.count))
+            .collect();
+        assert_eq!(kinds, vec![
+            (9, ItemKind::Stone, 64),
+            (10, ItemKind::Stone, 3),
+            (11, ItemKind::Dirt, 64),
+            (36, ItemKind::Dirt, 1),
+            (45, ItemKind::Shield, 1),
+        ]);
+
+        // the dirt stayed where it was
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![9, 10, 15]);
+
+        inventory.sort_range(9..36, |a, b| b.count.cmp(&a.count));
+        assert_eq!(inventory.get(10).unwrap().item, ItemKind::Dirt);
+    }
+
+    #[test]
+    fn test_drain_into_moves_everything_that_fits() {
+        let mut chest = ChestInventory::default();
+        chest
+            .set(0, ItemStack::new(ItemKind::Stone, 64, None))
+            .unwrap();
+        chest
+            .set(26, ItemStack::new(ItemKind::Dirt, 10, None))
+            .unwrap();
+        chest.updated_since_last_tick.clear();
+
+        let mut inventory = PlayerInventory::default();
+        let remaining = chest.drain_into(&mut inventory);
+
+        assert!(remaining.is_empty());
+        assert_eq!(chest.items().count(), 0);
+        assert_eq!(
+            inventory.get(36).unwrap(),
+            &ItemStack::new(ItemKind::Stone, 64, None)
+        );
+        assert_eq!(
+            inventory.get(37).unwrap(),
+            &ItemStack::new(ItemKind::Dirt, 10, None)
+        );
+
+        let updated: Vec<_> = chest.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![0, 26]);
+        let updated: Vec<_> = inventory.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![36, 37]);
+    }
+
+    #[test]
+    fn test_drain_into_leaves_what_does_not_fit() {
+        let mut inventory = PlayerInventory::default();
+        for slot in 9..45 {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Dirt, 64, None))
+                .unwrap();
+        }
+        inventory
+            .set(20, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+
+        let mut chest = ChestInventory::default();
+        chest
+            .set(3, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        chest
+            .set(4, ItemStack::new(ItemKind::Dirt, 5, None))
+            .unwrap();
+        chest.updated_since_last_tick.clear();
+
+        let remaining = chest.drain_into(&mut inventory);
+
+        assert_eq!(remaining, vec![
+            (3, ItemStack::new(ItemKind::Stone, 6, None)),
+            (4, ItemStack::new(ItemKind::Dirt, 5, None)),
+        ]);
+        assert_eq!(chest.get(3).unwrap().count, 6);
+        assert_eq!(inventory.get(20).unwrap().count, 64);
+
+        // the dirt did not move at all
+        let updated: Vec<_> = chest.updated_since_last_tick.iter().collect();
+        assert_eq!(updated, vec![3]);
+    }
+
+    #[test]
+    fn test_drain_into_keeps_armor_on() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None));
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::Stone, 1, None));
+
+        let mut chest = ChestInventory::default();
+        let remaining = inventory.drain_into(&mut chest);
+
+        assert!(remaining.is_empty());
+        assert_eq!(chest.get(0).unwrap().item, ItemKind::Stone);
+        assert_eq!(inventory.get_helmet().item, ItemKind::IronHelmet);
+    }
+
+    #[test]
+    fn test_try_add_item_partial_fill_with_remaining() {
+        let mut inventory = PlayerInventory::default();
+        for slot in 9..45 {
+            inventory
+                .set(slot, ItemStack::new(ItemKind::Dirt, 64, None))
+                .unwrap();
+        }
+        inventory
+            .set(10, ItemStack::new(ItemKind::Stone, 63, None))
+            .unwrap();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Stone, 62, None))
+            .unwrap();
+        let item = ItemStack::new(ItemKind::Stone, 10, None);
+        let result = inventory.try_add_item(item);
+
+        // the hotbar is filled first
+        assert_eq!(result.changed_slots, vec![36, 10]);
+        assert_eq!(
+            result.remaining,
+            Some(ItemStack::new(ItemKind::Stone, 7, None))
+        );
+        assert_eq!(inventory.get(36).unwrap().count, 64);
+        assert_eq!(inventory.get(10).unwrap().count, 64);
+    }
+
+    #[test]
+    fn every_change_bumps_the_state_id() {
+        fn stone(count: i8) -> ItemStack {
+            ItemStack::new(ItemKind::Stone, count, None)
+        }
+
+        type Mutation = fn(&mut PlayerInventory);
+
+        let mutations: [(&str, Mutation); 27] = [
+            ("set", |inv| inv.set(20, stone(1)).unwrap()),
+            ("get_mut", |inv| inv.get_mut(9).unwrap().count = 1),
+            ("get_cursor_mut", |inv| inv.get_cursor_mut().count = 1),
+            ("get_hand_slot_mut", |inv| {
+                inv.get_hand_slot_mut(2).unwrap().count = 1
+            }),
+            ("clear", PlayerInventory::clear),
+            ("set_cursor", |inv| inv.set_cursor(3)),
+            ("take_one_held", |inv| {
+                inv.take_one_held();
+            }),
+            ("take_held", |inv| {
+                inv.take_held();
+            }),
+            ("drop_cursor", |inv| {
+                inv.drop_cursor(false);
+            }),
+            ("drop_slot", |inv| {
+                inv.drop_slot(9, true).unwrap();
+            }),
+            ("deposit_cursor", |inv| {
+                inv.deposit_cursor(9, &mut stone(5), false).unwrap()
+            }),
+            ("swap", |inv| inv.swap(9, 10).unwrap()),
+            ("compact", PlayerInventory::compact),
+            ("sort_main", PlayerInventory::sort_main),
+            ("try_add_item", |inv| {
+                inv.try_add_item(stone(1));
+            }),
+            ("try_remove_item", |inv| {
+                inv.try_remove_item(ItemKind::Stone, 1);
+            }),
+            ("remove_item", |inv| {
+                inv.remove_item(ItemKind::Stone, 1);
+            }),
+            ("set_hotbar", |inv| inv.set_hotbar(2, stone(1))),
+            ("set_offhand", |inv| inv.set_offhand(stone(1))),
+            ("set_helmet", |inv| {
+                inv.set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None))
+            }),
+            ("set_chestplate", |inv| {
+                inv.set_chestplate(ItemStack::new(ItemKind::IronChestplate, 1, None));
+            }),
+            ("set_leggings", |inv| {
+                inv.set_leggings(ItemStack::new(ItemKind::IronLeggings, 1, None));
+            }),
+            ("set_boots", |inv| {
+                inv.set_boots(ItemStack::new(ItemKind::IronBoots, 1, None))
+            }),
+            ("equip", |inv| {
+                inv.equip(ItemStack::new(ItemKind::IronHelmet, 1, None));
+            }),
+            ("quick_move", |inv| inv.quick_move(9).unwrap()),
+            ("swap_hotbar", |inv| inv.swap_hotbar(9, 1).unwrap()),
+            ("swap_offhand", |inv| inv.swap_offhand(9).unwrap()),
+        ];
+
+        for (name, mutate) in mutations {
+            let mut inventory = PlayerInventory::default();
+            inventory.set(9, stone(10)).unwrap();
+            inventory.set(11, stone(20)).unwrap();
+            inventory
+                .set(36, ItemStack::new(ItemKind::Dirt, 3, None))
+                .unwrap();
+            inventory
+                .set(38, ItemStack::new(ItemKind::Dirt, 3, None))
+                .unwrap();
+            inventory.drain_updates();
+
+            let before = inventory.state_id();
+            mutate(&mut inventory);
+
+            let marked =
+                inventory.is_hand_slot_updated() || !inventory.drain_updates().slots.is_empty();
+            assert!(marked, "{name} did not mark anything as updated");
+            assert_ne!(
+                inventory.state_id(),
+                before,
+                "{name} did not bump the state id"
+            );
+            assert!(
+                !inventory.validate_state(before),
+                "{name} left an old state id valid"
+            );
+        }
+    }
+
+    #[test]
+    fn changes_that_change_nothing_keep_the_state_id() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        inventory.drain_updates();
+
+        let before = inventory.state_id();
+
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 10, None))
+            .unwrap();
+        inventory.swap(10, 11).unwrap();
+        inventory.set_cursor(0);
+        inventory.quick_move(20).unwrap();
+        drop(inventory.get_mut(9).unwrap());
+
+        assert_eq!(inventory.drain_updates(), InventoryUpdates::default());
+        assert!(inventory.validate_state(before));
+    }
+
+    #[test]
+    fn state_ids_wrap_around() {
+        let mut inventory = PlayerInventory {
+            state_id: u32::MAX,
+            ..PlayerInventory::default()
+        };
+
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+        assert_eq!(inventory.state_id(), 0);
+    }
+}
 
 #[derive(Component)]
 pub struct InventoryModule;
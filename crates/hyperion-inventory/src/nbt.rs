@@ -0,0 +1,283 @@
+//! Inventories in the format vanilla saves containers in: a list of item compounds, each naming
+//! the slot it is in.
+
+use snafu::{OptionExt, ResultExt, Snafu};
+use tracing::warn;
+use valence_protocol::{
+    ItemKind, ItemStack,
+    nbt::{Compound, List, Value},
+};
+
+use crate::{Inventory, InventoryAccessError};
+
+#[derive(Debug, Snafu)]
+pub enum InventoryLoadError {
+    #[snafu(display("expected a list of item compounds"))]
+    NotCompounds,
+    #[snafu(display("item {entry} has no valid `{field}`"))]
+    MissingField { entry: usize, field: &'static str },
+    #[snafu(display("slot {slot} is outside of the inventory"))]
+    SlotOutOfRange { slot: i8 },
+    #[snafu(display("item {entry} does not fit in its slot: {source}"))]
+    InvalidSlot {
+        entry: usize,
+        source: InventoryAccessError,
+    },
+}
+
+/// An item compound as written by [`Inventory::to_nbt`] or by vanilla.
+struct SavedItem<'a> {
+    slot: i8,
+    /// Without the `minecraft:` namespace.
+    id: &'a str,
+    count: i8,
+    tag: Option<&'a Compound>,
+}
+
+impl<'a> SavedItem<'a> {
+    /// Fails with the name of the first field that is missing or has the wrong type.
+    fn read(item: &'a Compound) -> Result<Self, &'static str> {
+        let Some(&Value::Byte(slot)) = item.get("Slot") else {
+            return Err("Slot");
+        };
+
+        let Some(Value::String(id)) = item.get("id") else {
+            return Err("id");
+        };
+
+        let Some(&Value::Byte(count)) = item.get("Count") else {
+            return Err("Count");
+        };
+
+        let tag = match item.get("tag") {
+            Some(Value::Compound(tag)) => Some(tag),
+            _ => None,
+        };
+
+        Ok(Self {
+            slot,
+            id: id.strip_prefix("minecraft:").unwrap_or(id),
+            count,
+            tag,
+        })
+    }
+
+    fn stack(&self, kind: ItemKind, count: i8) -> ItemStack {
+        ItemStack::new(kind, count, self.tag.cloned())
+    }
+}
+
+impl<const N: usize> Inventory<N> {
+    /// Every stack as a compound with its `Slot`, `id`, `Count` and, if it has any, `tag`.
+    #[must_use]
+    pub fn to_nbt(&self) -> List {
+        let items = self
+            .items()
+            .map(|(slot, stack)| {
+                let mut item = Compound::new();
+                item.insert("Slot", i8::try_from(slot).unwrap());
+                item.insert("id", format!("minecraft:{}", stack.item.to_str()));
+                item.insert("Count", stack.count);
+
+                if let Some(tag) = &stack.nbt {
+                    item.insert("tag", tag.clone());
+                }
+
+                item
+            })
+            .collect();
+
+        List::Compound(items)
+    }
+
+    /// Reads an inventory written by [`Self::to_nbt`] or by vanilla.
+    ///
+    /// Items with unknown ids are skipped with a warning, so that data from newer versions still
+    /// loads, and counts above a full stack are clamped. Anything else that is wrong, such as a
+    /// slot outside the inventory, fails the whole load rather than losing items.
+    pub fn from_nbt(items: &List) -> Result<Self, InventoryLoadError> {
+        let items = match items {
+            List::Compound(items) => items.as_slice(),
+            // an empty list has no element type
+            List::End => &[],
+            _ => return NotCompoundsSnafu.fail(),
+        };
+
+        let mut inventory = Self::default();
+
+        for (entry, item) in items.iter().enumerate() {
+            let item = SavedItem::read(item)
+                .map_err(|field| MissingFieldSnafu { entry, field }.build())?;
+
+            let slot = u16::try_from(item.slot)
+                .ok()
+                .filter(|&slot| usize::from(slot) < N)
+                .context(SlotOutOfRangeSnafu { slot: item.slot })?;
+
+            let Some(kind) = ItemKind::from_str(item.id) else {
+                warn!("skipping unknown item `{}` in slot {slot}", item.id);
+                continue;
+            };
+
+            if item.count <= 0 {
+                continue;
+            }
+
+            let count = item.count.min(kind.max_stack());
+            inventory
+                .set(slot, item.stack(kind, count))
+                .context(InvalidSlotSnafu { entry })?;
+        }
+
+        Ok(inventory)
+    }
+
+    /// Fills the slots listed in `items`, as written by [`Self::to_nbt`] or by vanilla. Unlike
+    /// [`Self::from_nbt`], items that cannot be read or do not fit in their slot are skipped.
+    pub fn load_nbt(&mut self, items: &List) {
+        let List::Compound(items) = items else {
+            return;
+        };
+
+        for item in items {
+            let Ok(item) = SavedItem::read(item) else {
+                continue;
+            };
+
+            let (Ok(slot), Some(kind)) = (u16::try_from(item.slot), ItemKind::from_str(item.id))
+            else {
+                continue;
+            };
+
+            // slots outside the inventory and armor slots holding something else are skipped
+            let _ = self.set(slot, item.stack(kind, item.count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{
+        ItemKind, ItemStack,
+        nbt::{Compound, List},
+    };
+
+    use super::InventoryLoadError;
+    use crate::{Inventory, PlayerInventory};
+
+    fn random_stack(rng: &mut fastrand::Rng) -> ItemStack {
+        let kind = ItemKind::ALL[rng.usize(1..ItemKind::ALL.len())];
+        let count = rng.i8(1..=kind.max_stack());
+
+        let tag = rng.bool().then(|| {
+            let mut tag = Compound::new();
+            tag.insert("Damage", rng.i32(..));
+            tag.insert("Lore", List::String(vec![rng.u64(..).to_string()]));
+            tag
+        });
+
+        ItemStack::new(kind, count, tag)
+    }
+
+    fn random_inventory<const N: usize>(rng: &mut fastrand::Rng) -> Inventory<N> {
+        let mut inventory = Inventory::default();
+
+        for slot in 0..N {
+            if rng.u8(..4) == 0 {
+                let slot = u16::try_from(slot).unwrap();
+
+                // the armor slots of a player inventory stay empty unless armor was picked
+                let _ = inventory.set(slot, random_stack(rng));
+            }
+        }
+
+        inventory
+    }
+
+    fn item(slot: i8, id: &str, count: i8) -> Compound {
+        let mut item = Compound::new();
+        item.insert("Slot", slot);
+        item.insert("id", id.to_owned());
+        item.insert("Count", count);
+        item
+    }
+
+    #[test]
+    fn random_inventories_round_trip() {
+        let mut rng = fastrand::Rng::with_seed(7);
+
+        for _ in 0..200 {
+            let inventory: PlayerInventory = random_inventory(&mut rng);
+            let loaded = PlayerInventory::from_nbt(&inventory.to_nbt()).unwrap();
+            assert_eq!(loaded.slots(), inventory.slots());
+
+            let chest: Inventory<27> = random_inventory(&mut rng);
+            let loaded = Inventory::<27>::from_nbt(&chest.to_nbt()).unwrap();
+            assert_eq!(loaded.slots(), chest.slots());
+        }
+    }
+
+    #[test]
+    fn empty_inventories_round_trip() {
+        let loaded = PlayerInventory::from_nbt(&List::End).unwrap();
+        assert_eq!(loaded.items().count(), 0);
+
+        let inventory = PlayerInventory::default();
+        let loaded = PlayerInventory::from_nbt(&inventory.to_nbt()).unwrap();
+        assert_eq!(loaded.items().count(), 0);
+    }
+
+    #[test]
+    fn unknown_items_are_skipped_and_counts_clamped() {
+        let items = List::Compound(vec![
+            item(0, "minecraft:stone", 100),
+            item(1, "minecraft:not_an_item", 1),
+            item(2, "ender_pearl", 64),
+        ]);
+
+        let loaded = PlayerInventory::from_nbt(&items).unwrap();
+
+        assert_eq!(loaded.items().collect::<Vec<_>>(), vec![
+            (0, &ItemStack::new(ItemKind::Stone, 64, None)),
+            (2, &ItemStack::new(ItemKind::EnderPearl, 16, None)),
+        ]);
+    }
+
+    #[test]
+    fn broken_inventories_fail_to_load() {
+        let out_of_range = List::Compound(vec![item(27, "minecraft:stone", 1)]);
+        let result = Inventory::<27>::from_nbt(&out_of_range);
+        assert!(matches!(
+            result,
+            Err(InventoryLoadError::SlotOutOfRange { slot: 27 })
+        ));
+
+        let negative = List::Compound(vec![item(-106, "minecraft:shield", 1)]);
+        let result = PlayerInventory::from_nbt(&negative);
+        assert!(matches!(
+            result,
+            Err(InventoryLoadError::SlotOutOfRange { slot: -106 })
+        ));
+
+        let mut no_count = item(0, "minecraft:stone", 1);
+        no_count.remove("Count");
+        let result = PlayerInventory::from_nbt(&List::Compound(vec![no_count]));
+        assert!(matches!(
+            result,
+            Err(InventoryLoadError::MissingField {
+                entry: 0,
+                field: "Count"
+            })
+        ));
+
+        let stone_helmet = List::Compound(vec![item(5, "minecraft:stone", 1)]);
+        let result = PlayerInventory::from_nbt(&stone_helmet);
+        assert!(matches!(
+            result,
+            Err(InventoryLoadError::InvalidSlot { entry: 0, .. })
+        ));
+
+        let result = PlayerInventory::from_nbt(&List::Int(vec![1]));
+        assert!(matches!(result, Err(InventoryLoadError::NotCompounds)));
+    }
+}
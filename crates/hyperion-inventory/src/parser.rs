@@ -122,11 +122,11 @@ pub fn create_inventory_action(mode: u8, button: u8, slot: i16) -> InventoryActi
         0 if slot == -999 => handle_outside_click(button),
         0 => handle_normal_click(button, slot),
         1 => handle_shift_click(button, slot.try_into().context(NegativeSlotSnafu)?),
+        2 if button == 40 => Ok(InventoryAction::OffhandSwap {
+            slot: slot.try_into().context(NegativeSlotSnafu)?,
+        }),
         2 => handle_number_key(button, slot.try_into().context(NegativeSlotSnafu)?),
         3 => match button {
-            40 => Ok(InventoryAction::OffhandSwap {
-                slot: slot.try_into().context(NegativeSlotSnafu)?,
-            }),
             2 => Ok(InventoryAction::MiddleClick {
                 slot: slot.try_into().context(NegativeSlotSnafu)?,
             }),
@@ -187,6 +187,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_offhand_swap() {
+        assert_eq!(
+            create_inventory_action(2, 40, 12).unwrap(),
+            InventoryAction::OffhandSwap { slot: 12 }
+        );
+    }
+
     #[test]
     fn test_drag() {
         assert_eq!(
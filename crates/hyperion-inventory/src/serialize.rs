@@ -0,0 +1,145 @@
+//! Inventories with `serde`, for saving them alongside the rest of a player.
+//!
+//! Only the stacks and the selected hotbar slot are saved. What changed since the last tick is
+//! only needed for syncing, so a loaded inventory starts with nothing changed. Item tags are kept
+//! as binary NBT so that they load back exactly as they were.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use valence_protocol::{ItemKind, ItemStack, nbt};
+
+use crate::Inventory;
+
+#[derive(Serialize, Deserialize)]
+struct SavedStack {
+    slot: u16,
+    item: String,
+    count: i8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbt: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedInventory {
+    slots: Vec<SavedStack>,
+    hand_slot: u16,
+}
+
+impl<const N: usize> Serialize for Inventory<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let slots = self
+            .items()
+            .map(|(slot, stack)| SavedStack {
+                slot,
+                item: stack.item.to_str().to_owned(),
+                count: stack.count,
+                nbt: stack.nbt.as_ref().map(|tag| {
+                    let mut bytes = Vec::new();
+
+                    // writing to a vec cannot fail
+                    nbt::to_binary(tag, &mut bytes, "").unwrap();
+
+                    bytes
+                }),
+            })
+            .collect();
+
+        SavedInventory {
+            slots,
+            hand_slot: self.hand_slot,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Inventory<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = SavedInventory::deserialize(deserializer)?;
+
+        if saved.hand_slot > 8 {
+            return Err(D::Error::custom(format!(
+                "invalid hand slot {}",
+                saved.hand_slot
+            )));
+        }
+
+        let mut inventory = Self {
+            hand_slot: saved.hand_slot,
+            ..Self::default()
+        };
+
+        for stack in saved.slots {
+            let kind = ItemKind::from_str(&stack.item)
+                .ok_or_else(|| D::Error::custom(format!("unknown item `{}`", stack.item)))?;
+
+            let tag = stack
+                .nbt
+                .map(|bytes| nbt::from_binary(&mut bytes.as_slice()).map(|(tag, _)| tag))
+                .transpose()
+                .map_err(|e| D::Error::custom(format!("invalid nbt: {e}")))?;
+
+            inventory
+                .set(stack.slot, ItemStack::new(kind, stack.count, tag))
+                .map_err(D::Error::custom)?;
+        }
+
+        inventory.updated_since_last_tick.clear();
+
+        Ok(inventory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{
+        ItemKind, ItemStack,
+        nbt::{Compound, List},
+    };
+
+    use crate::PlayerInventory;
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut enchantment = Compound::new();
+        enchantment.insert("id", "minecraft:sharpness");
+        enchantment.insert("lvl", 5_i16);
+
+        let mut tag = Compound::new();
+        tag.insert("Enchantments", List::Compound(vec![enchantment]));
+        tag.insert("Damage", 12_i32);
+
+        let mut inventory = PlayerInventory::default();
+        inventory.set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None));
+        inventory.set_boots(ItemStack::new(ItemKind::DiamondBoots, 1, None));
+        inventory
+            .set(36, ItemStack::new(ItemKind::DiamondSword, 1, Some(tag)))
+            .unwrap();
+        inventory
+            .set(39, ItemStack::new(ItemKind::Cobblestone, 37, None))
+            .unwrap();
+        inventory.set_cursor(3);
+
+        let json = serde_json::to_string(&inventory).unwrap();
+        let loaded: PlayerInventory = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            loaded.items().collect::<Vec<_>>(),
+            inventory.items().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.get_cursor_hand_slot(), 3);
+
+        // nothing needs syncing until the loaded inventory changes
+        assert!(loaded.updated_since_last_tick.is_empty());
+        assert!(!loaded.hand_slot_updated_since_last_tick);
+    }
+
+    #[test]
+    fn invalid_inventories_are_rejected() {
+        let unknown_item = r#"{"slots":[{"slot":9,"item":"nope","count":1}],"hand_slot":0}"#;
+        let out_of_range = r#"{"slots":[{"slot":46,"item":"stone","count":1}],"hand_slot":0}"#;
+        let bad_hand_slot = r#"{"slots":[],"hand_slot":9}"#;
+
+        for json in [unknown_item, out_of_range, bad_hand_slot] {
+            assert!(serde_json::from_str::<PlayerInventory>(json).is_err());
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use clap::ValueEnum;
 use flecs_ecs::{
     core::{QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet},
@@ -6,12 +7,16 @@ use flecs_ecs::{
 };
 use hyperion::{simulation::Uuid, storage::LocalDb};
 use num_derive::{FromPrimitive, ToPrimitive};
+use tracing::error;
 
 #[derive(Component)]
 pub struct PermissionModule;
 
+mod operators;
 mod storage;
 
+pub use operators::{Operators, set_permissions};
+
 #[derive(
     Default,
     Component,
@@ -58,21 +63,43 @@ impl Module for PermissionModule {
         world.component::<Group>();
         world.component::<storage::PermissionStorage>();
 
+        world.component::<Operators>();
+
         world.get::<&LocalDb>(|db| {
-            let storage = storage::PermissionStorage::new(db).unwrap();
-            world.set(storage);
+            match storage::PermissionStorage::new(db)
+                .context("failed to open the permission groups")
+            {
+                Ok(storage) => {
+                    world.set(storage);
+                }
+                Err(e) => error!("{e:#}"),
+            }
+
+            match Operators::new(db).context("failed to open the operator list") {
+                Ok(operators) => {
+                    world.set(operators);
+                }
+                Err(e) => error!("{e:#}"),
+            }
         });
 
+        operators::apply_on_join(world);
+
         observer!(world, flecs::OnSet, &Uuid, &storage::PermissionStorage($)).each_entity(
             |entity, (uuid, permissions)| {
-                let group = permissions.get(**uuid);
+                let group = permissions.get(**uuid).unwrap_or_else(|e| {
+                    error!("failed to load the permission group of {}: {e}", **uuid);
+                    Group::default()
+                });
                 entity.set(group);
             },
         );
 
         observer!(world, flecs::OnRemove, &Uuid, &Group, &storage::PermissionStorage($)).each(
             |(uuid, group, permissions)| {
-                permissions.set(**uuid, *group).unwrap();
+                if let Err(e) = permissions.set(**uuid, *group) {
+                    error!("failed to save the permission group of {}: {e}", **uuid);
+                }
             },
         );
     }
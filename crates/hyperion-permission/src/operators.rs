@@ -0,0 +1,228 @@
+use anyhow::Context;
+use flecs_ecs::prelude::*;
+use heed::{Database, byteorder::NativeEndian, types};
+use hyperion::{
+    config::ServerConfig,
+    simulation::{Name, Uuid, command::Permissions},
+    storage::LocalDb,
+};
+use tracing::{error, info};
+
+/// The [`Permissions`] of operators by UUID.
+///
+/// Players who were made regular players again are stored with [`Permissions::PLAYER`], so that
+/// [`ServerConfig::operators`] only makes someone an operator the first time they join.
+#[derive(Component, Debug, Clone)]
+pub struct Operators {
+    db: LocalDb,
+    levels: Database<types::U128<NativeEndian>, types::U8>,
+}
+
+impl Operators {
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let levels = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some("uuid-to-op-level"))?;
+            wtxn.commit()?;
+            db
+        };
+
+        Ok(Self {
+            db: db.clone(),
+            levels,
+        })
+    }
+
+    /// The permissions of a player, or `None` if they were never given any.
+    pub fn get(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<Permissions>> {
+        let rtxn = self.db.read_txn()?;
+        let level = self.levels.get(&rtxn, &uuid.as_u128())?;
+
+        Ok(level.map(Permissions))
+    }
+
+    pub fn set(&self, uuid: uuid::Uuid, permissions: Permissions) -> anyhow::Result<()> {
+        let mut wtxn = self.db.write_txn()?;
+        self.levels
+            .put(&mut wtxn, &uuid.as_u128(), &permissions.0)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Gives `player` new permissions, both now and whenever they join again.
+pub fn set_permissions(player: EntityView<'_>, permissions: Permissions) -> anyhow::Result<()> {
+    let uuid = player
+        .try_get::<&Uuid>(|uuid| uuid.0)
+        .context("only players have permissions")?;
+
+    player
+        .world()
+        .try_get::<&Operators>(|operators| operators.set(uuid, permissions))
+        .context("the operator list could not be opened")??;
+
+    // resends their command tree
+    player.set(permissions);
+
+    Ok(())
+}
+
+/// Whether `operators`, names or UUIDs from [`ServerConfig::operators`], lists a player.
+fn is_listed(operators: &[String], uuid: uuid::Uuid, name: &str) -> bool {
+    operators.iter().any(|operator| {
+        uuid::Uuid::parse_str(operator).map_or_else(
+            |_| operator.eq_ignore_ascii_case(name),
+            |operator| operator == uuid,
+        )
+    })
+}
+
+pub(crate) fn apply_on_join(world: &World) {
+    observer!(world, flecs::OnSet, &Uuid, &Operators($), &ServerConfig($)).each_entity(
+        |player, (uuid, operators, config)| {
+            let name = player
+                .try_get::<&Name>(|name| name.to_string())
+                .unwrap_or_default();
+
+            let permissions = match operators.get(uuid.0) {
+                Ok(Some(permissions)) => permissions,
+                Ok(None) if is_listed(&config.operators, uuid.0, &name) => {
+                    info!("{name} is listed in `operators`, making them an operator");
+
+                    if let Err(e) = operators.set(uuid.0, Permissions::OWNER) {
+                        error!("failed to save that {name} is an operator: {e}");
+                    }
+
+                    Permissions::OWNER
+                }
+                Ok(None) => Permissions::PLAYER,
+                Err(e) => {
+                    error!("failed to load the permissions of {name}: {e}");
+                    Permissions::PLAYER
+                }
+            };
+
+            player.set(permissions);
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use flecs_ecs::prelude::*;
+    use hyperion::{
+        config::ServerConfig,
+        simulation::{Name, Uuid, command::Permissions},
+        storage::LocalDb,
+    };
+    use tempfile::TempDir;
+
+    use super::{Operators, is_listed, set_permissions};
+    use crate::PermissionModule;
+
+    /// A database in a directory that is deleted along with the returned [`TempDir`].
+    fn db() -> (TempDir, LocalDb) {
+        let dir = TempDir::new().unwrap();
+        let db = LocalDb::open(dir.path()).unwrap();
+
+        (dir, db)
+    }
+
+    fn world(db: LocalDb, operators: &[&str]) -> World {
+        let world = World::new();
+
+        world.set(db);
+        world.set(ServerConfig {
+            operators: operators.iter().map(ToString::to_string).collect(),
+            ..ServerConfig::default()
+        });
+        world.import::<PermissionModule>();
+
+        world
+    }
+
+    fn join<'a>(world: &'a World, id: u128, name: &str) -> EntityView<'a> {
+        world
+            .entity()
+            .set(Name::from(Arc::from(name)))
+            .set(Uuid::from(uuid::Uuid::from_u128(id)))
+    }
+
+    fn permissions(player: EntityView<'_>) -> Permissions {
+        player.get::<&Permissions>(|permissions| *permissions)
+    }
+
+    #[test]
+    fn permissions_survive_a_restart() {
+        let (_dir, db) = db();
+        let uuid = uuid::Uuid::from_u128(1);
+
+        {
+            let operators = Operators::new(&db).unwrap();
+            assert_eq!(operators.get(uuid).unwrap(), None);
+
+            operators.set(uuid, Permissions::MANAGE_PLAYERS).unwrap();
+        }
+
+        let operators = Operators::new(&db).unwrap();
+        assert_eq!(
+            operators.get(uuid).unwrap(),
+            Some(Permissions::MANAGE_PLAYERS)
+        );
+
+        // the next time they join, they get them back
+        let world = world(db, &[]);
+        assert_eq!(
+            permissions(join(&world, 1, "Alex")),
+            Permissions::MANAGE_PLAYERS
+        );
+        assert_eq!(permissions(join(&world, 2, "Steve")), Permissions::PLAYER);
+    }
+
+    #[test]
+    fn changes_apply_to_online_players_right_away() {
+        let (_dir, db) = db();
+        let world = world(db, &[]);
+        let player = join(&world, 1, "Alex");
+
+        set_permissions(player, Permissions::OWNER).unwrap();
+        assert_eq!(permissions(player), Permissions::OWNER);
+
+        set_permissions(player, Permissions::PLAYER).unwrap();
+        assert_eq!(permissions(player), Permissions::PLAYER);
+
+        world.get::<&Operators>(|operators| {
+            let stored = operators.get(uuid::Uuid::from_u128(1)).unwrap();
+            assert_eq!(stored, Some(Permissions::PLAYER));
+        });
+
+        // only players have permissions
+        assert!(set_permissions(world.entity(), Permissions::OWNER).is_err());
+    }
+
+    #[test]
+    fn listed_players_become_operators_once() {
+        let (_dir, db) = db();
+        let world = world(db, &["notch"]);
+
+        let notch = join(&world, 1, "Notch");
+        assert_eq!(permissions(notch), Permissions::OWNER);
+
+        // deopping them sticks, even though they are still listed
+        set_permissions(notch, Permissions::PLAYER).unwrap();
+        assert_eq!(permissions(join(&world, 1, "Notch")), Permissions::PLAYER);
+    }
+
+    #[test]
+    fn operators_are_listed_by_name_or_uuid() {
+        let uuid = uuid::Uuid::from_u128(7);
+        let operators = ["Notch".to_owned(), uuid.to_string()];
+
+        assert!(is_listed(&operators, uuid::Uuid::from_u128(1), "notch"));
+        assert!(is_listed(&operators, uuid, "Alex"));
+        assert!(!is_listed(&operators, uuid::Uuid::from_u128(1), "Alex"));
+    }
+}
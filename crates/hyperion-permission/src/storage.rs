@@ -1,5 +1,5 @@
 use flecs_ecs::macros::Component;
-use heed::{Database, Env, byteorder::NativeEndian, types};
+use heed::{Database, byteorder::NativeEndian, types};
 use hyperion::storage::LocalDb;
 use num_traits::{FromPrimitive, ToPrimitive};
 
@@ -7,7 +7,7 @@ use crate::Group;
 
 #[derive(Component)]
 pub struct PermissionStorage {
-    env: Env,
+    db: LocalDb,
     perms: Database<types::U128<NativeEndian>, types::U8>,
 }
 
@@ -22,29 +22,29 @@ impl PermissionStorage {
         };
 
         Ok(Self {
-            env: (**db).clone(),
+            db: db.clone(),
             perms,
         })
     }
 
-    pub fn get(&self, uuid: uuid::Uuid) -> Group {
+    pub fn get(&self, uuid: uuid::Uuid) -> anyhow::Result<Group> {
         let uuid = uuid.as_u128();
-        let rtxn = self.env.read_txn().unwrap();
-        let Some(perms) = self.perms.get(&rtxn, &uuid).unwrap() else {
-            return Group::default();
+        let rtxn = self.db.read_txn()?;
+        let Some(perms) = self.perms.get(&rtxn, &uuid)? else {
+            return Ok(Group::default());
         };
 
         let Some(group) = Group::from_u8(perms) else {
             tracing::error!("invalid group {perms:?}");
-            return Group::default();
+            return Ok(Group::default());
         };
 
-        group
+        Ok(group)
     }
 
     pub fn set(&self, uuid: uuid::Uuid, group: Group) -> anyhow::Result<()> {
         let uuid = uuid.as_u128();
-        let mut wtxn = self.env.write_txn()?;
+        let mut wtxn = self.db.write_txn()?;
         self.perms.put(&mut wtxn, &uuid, &group.to_u8().unwrap())?;
         wtxn.commit()?;
         Ok(())
@@ -29,9 +29,22 @@ pub enum PlayerDisconnectReason<'a> {
     Other(#[rkyv(with = InlineAsBox)] &'a str),
 }
 
+/// How far behind a player is on the packets the proxy writes to them. The proxy reports this
+/// for every player once a second.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct StreamStats {
+    pub stream: u64,
+    /// Bytes of broadcasts dropped since the last report because of the player's
+    /// [`crate::SetStreamBufferLimit`].
+    pub dropped_bytes: u64,
+    /// Bytes waiting to be written to the player.
+    pub queued_bytes: u64,
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub enum ProxyToServerMessage<'a> {
     PlayerConnect(PlayerConnect),
     PlayerDisconnect(PlayerDisconnect<'a>),
     PlayerPackets(PlayerPackets<'a>),
+    StreamStats(StreamStats),
 }
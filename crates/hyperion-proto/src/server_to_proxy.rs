@@ -15,11 +15,27 @@ pub struct SetReceiveBroadcasts {
     pub stream: u64,
 }
 
+/// Caps how many bytes the proxy may hold for a player who is not reading them fast enough.
+/// Broadcasts that would go over the limit are dropped for that player instead of queued, so a
+/// slow client cannot make the proxy use more and more memory.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[rkyv(derive(Debug))]
+pub struct SetStreamBufferLimit {
+    pub stream: u64,
+    /// `0` removes the limit.
+    pub max_bytes: u64,
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub struct BroadcastGlobal<'a> {
     pub exclude: u64,
     pub order: u32,
 
+    /// Additional players that should not receive this broadcast. Most broadcasts exclude at most
+    /// one player, so [`BroadcastGlobal::exclude`] is enough and this is empty.
+    #[rkyv(with = InlineAsBox)]
+    pub exclude_many: &'a [u64],
+
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
 }
@@ -31,6 +47,11 @@ pub struct BroadcastLocal<'a> {
     pub exclude: u64,
     pub order: u32,
 
+    /// Additional players that should not receive this broadcast, e.g. because they are hiding
+    /// the entity it is about.
+    #[rkyv(with = InlineAsBox)]
+    pub exclude_many: &'a [u64],
+
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
 }
@@ -45,6 +66,19 @@ pub struct Unicast<'a> {
     pub data: &'a [u8],
 }
 
+/// The same data sent to each of several players, so it is only encoded and sent to the proxy
+/// once.
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Multicast<'a> {
+    pub order: u32,
+
+    #[rkyv(with = InlineAsBox)]
+    pub streams: &'a [u64],
+
+    #[rkyv(with = InlineAsBox)]
+    pub data: &'a [u8],
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
 #[rkyv(derive(Debug))]
 pub struct Flush;
@@ -56,6 +90,8 @@ pub enum ServerToProxyMessage<'a> {
     BroadcastGlobal(BroadcastGlobal<'a>),
     BroadcastLocal(BroadcastLocal<'a>),
     Unicast(Unicast<'a>),
+    Multicast(Multicast<'a>),
     SetReceiveBroadcasts(SetReceiveBroadcasts),
     Flush(Flush),
+    SetStreamBufferLimit(SetStreamBufferLimit),
 }
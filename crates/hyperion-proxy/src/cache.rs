@@ -159,6 +159,9 @@ struct LocalBroadcastData {
     range_start: usize,
     range_end: usize,
     player_id_to_exclude: u64,
+    /// Range into [`BufferedEgress::local_exclusions`] of additional players to exclude.
+    exclusions_start: usize,
+    exclusions_end: usize,
 }
 
 impl LocalBroadcastData {
@@ -183,20 +186,34 @@ impl Data for LocalBroadcastData {
     }
 }
 
+/// Whether two orders were given out by the same system.
+const fn same_system(a: u32, b: u32) -> bool {
+    a >> 16 == b >> 16
+}
+
 /// Buffers egress operations for optimized processing.
+///
+/// Every frame the server sends carries an order, and each player's packets are written sorted by
+/// it. Consecutive broadcasts of one kind are merged into a run, which is delivered with the
+/// order of its first broadcast. So that nothing is delivered ahead of a frame that was sent
+/// before it, a run ends at any frame of another kind that reaches players, at a broadcast of
+/// another system, and at [`ArchivedServerToProxyMessage::Flush`].
 pub struct BufferedEgress {
     /// Buffer for required broadcast data.
     global_broadcast_buffer: Vec<u8>,
 
     raw_local_broadcast_data: Vec<u8>,
     local_broadcast_buffer: Vec<LocalBroadcastData>,
+    local_exclusions: Vec<u64>,
 
     /// Manages player-specific exclusions.
     exclusion_manager: ExclusionsManager,
     /// Reference to the underlying egress handler.
     egress: Egress,
-    /// Tracks the current broadcast order.
+    /// The order of the first global broadcast of the current run.
     current_broadcast_order: Option<u32>,
+    /// The order of the first local broadcast of the current run.
+    current_local_order: Option<u32>,
     local_flush_counter: u32,
 }
 
@@ -208,9 +225,11 @@ impl BufferedEgress {
             global_broadcast_buffer: Vec::new(),
             raw_local_broadcast_data: vec![],
             local_broadcast_buffer: Vec::default(),
+            local_exclusions: Vec::new(),
             exclusion_manager: ExclusionsManager::default(),
             egress,
             current_broadcast_order: None,
+            current_local_order: None,
             local_flush_counter: 0,
         }
     }
@@ -225,31 +244,51 @@ impl BufferedEgress {
             ArchivedServerToProxyMessage::BroadcastGlobal(packet) => {
                 let Ok(packet_order) = rkyv::deserialize::<u32, !>(&packet.order);
 
+                self.flush_local();
+
                 if let Some(order) = self.current_broadcast_order
-                    && order != packet_order
+                    && !same_system(order, packet_order)
                 {
                     // send the current broadcasts to all players
-                    self.flush_broadcast(order);
+                    self.flush_global();
                 }
 
-                self.current_broadcast_order = Some(packet_order);
+                self.current_broadcast_order.get_or_insert(packet_order);
 
                 let current_len = self.global_broadcast_buffer.len();
                 self.global_broadcast_buffer.extend_from_slice(&packet.data);
 
                 let Ok(packet_exclude) = rkyv::deserialize::<u64, !>(&packet.exclude);
+                let new_len = self.global_broadcast_buffer.len();
 
                 if packet_exclude != 0 {
                     // we need to exclude a player
-                    let new_len = self.global_broadcast_buffer.len();
                     self.exclusion_manager
                         .append_exclusion(packet_exclude, current_len..new_len);
                 }
 
+                for id in packet.exclude_many.iter() {
+                    let Ok(id) = rkyv::deserialize::<u64, !>(id);
+                    self.exclusion_manager
+                        .append_exclusion(id, current_len..new_len);
+                }
+
                 // TODO: Consider implementing auto-flush based on buffer size
                 // to optimize cache usage.
             }
             ArchivedServerToProxyMessage::BroadcastLocal(packet) => {
+                let Ok(packet_order) = rkyv::deserialize::<u32, !>(&packet.order);
+
+                self.flush_global();
+
+                if let Some(order) = self.current_local_order
+                    && !same_system(order, packet_order)
+                {
+                    self.flush_local();
+                }
+
+                self.current_local_order.get_or_insert(packet_order);
+
                 let Ok(center_x) = rkyv::deserialize::<i16, !>(&packet.center.x);
                 let Ok(center_z) = rkyv::deserialize::<i16, !>(&packet.center.z);
                 let Ok(player_id_to_exclude) = rkyv::deserialize::<u64, !>(&packet.exclude);
@@ -261,6 +300,14 @@ impl BufferedEgress {
                     .extend_from_slice(&packet.data);
                 let after_len = self.raw_local_broadcast_data.len();
 
+                let exclusions_start = self.local_exclusions.len();
+                self.local_exclusions
+                    .extend(packet.exclude_many.iter().map(|id| {
+                        let Ok(id) = rkyv::deserialize::<u64, !>(id);
+                        id
+                    }));
+                let exclusions_end = self.local_exclusions.len();
+
                 // println!("broadcast local with {player_id_to_exclude} to {center_x} {center_z}");
 
                 self.local_broadcast_buffer.push(LocalBroadcastData {
@@ -269,63 +316,90 @@ impl BufferedEgress {
                     range_start: before_len,
                     range_end: after_len,
                     player_id_to_exclude,
+                    exclusions_start,
+                    exclusions_end,
                 });
             }
             ArchivedServerToProxyMessage::Unicast(unicast) => {
+                self.flush_global();
+                self.flush_local();
                 self.egress.handle_unicast(unicast);
             }
+            ArchivedServerToProxyMessage::Multicast(multicast) => {
+                self.flush_global();
+                self.flush_local();
+                self.egress.handle_multicast(multicast);
+            }
             ArchivedServerToProxyMessage::SetReceiveBroadcasts(pkt) => {
                 self.egress.handle_set_receive_broadcasts(pkt);
             }
+            ArchivedServerToProxyMessage::SetStreamBufferLimit(pkt) => {
+                self.egress.handle_set_stream_buffer_limit(pkt);
+            }
             ArchivedServerToProxyMessage::Flush(_) => {
-                if let Some(order) = self.current_broadcast_order.take() {
-                    self.flush_broadcast(order);
-                }
+                self.flush_global();
+                self.flush_local();
 
                 self.egress.handle_flush();
                 self.local_flush_counter = 0;
+            }
+        }
+    }
 
-                if self.local_broadcast_buffer.is_empty() {
-                    return;
-                }
+    /// Sends the current run of global broadcasts to all players.
+    fn flush_global(&mut self) {
+        if let Some(order) = self.current_broadcast_order.take() {
+            self.flush_broadcast(order);
+        }
+    }
 
-                let bvh = Bvh::build(
-                    &mut self.local_broadcast_buffer,
-                    &self.raw_local_broadcast_data,
-                );
+    /// Sends the current run of local broadcasts to the players near them.
+    fn flush_local(&mut self) {
+        let Some(order) = self.current_local_order.take() else {
+            return;
+        };
 
-                let mut exclusions = ExclusionsManager::default();
-                let mut idx_on = 0;
+        let bvh = Bvh::build(
+            &mut self.local_broadcast_buffer,
+            &self.raw_local_broadcast_data,
+        );
 
-                for packet in &self.local_broadcast_buffer {
-                    // todo: is there a more idiomatic way to do this?
-                    let packet_len = packet.len();
-                    let range = idx_on..idx_on + packet_len;
+        let mut exclusions = ExclusionsManager::default();
+        let mut idx_on = 0;
 
-                    if packet.player_id_to_exclude != 0 {
-                        exclusions.append_exclusion(packet.player_id_to_exclude, range);
-                    }
+        for packet in &self.local_broadcast_buffer {
+            // todo: is there a more idiomatic way to do this?
+            let packet_len = packet.len();
+            let range = idx_on..idx_on + packet_len;
 
-                    idx_on += packet_len;
-                }
+            if packet.player_id_to_exclude != 0 {
+                exclusions.append_exclusion(packet.player_id_to_exclude, range.clone());
+            }
 
-                self.local_broadcast_buffer.clear();
-                self.raw_local_broadcast_data.clear();
+            for &player_id in &self.local_exclusions[packet.exclusions_start..packet.exclusions_end]
+            {
+                exclusions.append_exclusion(player_id, range.clone());
+            }
 
-                let egress = self.egress;
-                tokio::spawn(async move {
-                    let bvh = bvh.into_bytes();
+            idx_on += packet_len;
+        }
 
-                    let instruction = BroadcastLocalInstruction {
-                        order: 0,
-                        bvh: Arc::new(bvh),
-                        exclusions: Arc::new(exclusions),
-                    };
+        self.local_broadcast_buffer.clear();
+        self.raw_local_broadcast_data.clear();
+        self.local_exclusions.clear();
 
-                    egress.handle_broadcast_local(instruction);
-                });
-            }
-        }
+        let egress = self.egress;
+        tokio::spawn(async move {
+            let bvh = bvh.into_bytes();
+
+            let instruction = BroadcastLocalInstruction {
+                order,
+                bvh: Arc::new(bvh),
+                exclusions: Arc::new(exclusions),
+            };
+
+            egress.handle_broadcast_local(instruction);
+        });
     }
 
     /// Flushes the current broadcast buffer.
@@ -335,6 +409,7 @@ impl BufferedEgress {
             data,
             exclude: 0,
             order,
+            exclude_many: &[],
         };
 
         let exclusions = self.exclusion_manager.take();
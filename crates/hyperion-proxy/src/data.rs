@@ -1,4 +1,7 @@
-use std::sync::{Arc, atomic, atomic::AtomicBool};
+use std::sync::{
+    Arc, atomic,
+    atomic::{AtomicBool, AtomicU64},
+};
 
 use anyhow::bail;
 use bytes::Bytes;
@@ -97,10 +100,51 @@ impl Ord for OrderedBytes {
     }
 }
 
+/// How many bytes are waiting to be written to a player, shared by their [`PlayerHandle`] and the
+/// task writing to them.
+#[derive(Debug, Default)]
+pub struct StreamBuffer {
+    /// Bytes sent to the writer task which it has not written yet.
+    queued_bytes: AtomicU64,
+    /// The most bytes broadcasts may be queued up to, or `0` if there is no limit.
+    max_bytes: AtomicU64,
+    /// Bytes of broadcasts dropped since the last [`Self::take_dropped`].
+    dropped_bytes: AtomicU64,
+}
+
+impl StreamBuffer {
+    pub fn queued_bytes(&self) -> u64 {
+        self.queued_bytes.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Called by the writer task once it wrote `bytes`, or when they could not be sent to it.
+    pub fn written(&self, bytes: u64) {
+        self.queued_bytes
+            .fetch_sub(bytes, atomic::Ordering::Relaxed);
+    }
+
+    pub fn set_max_bytes(&self, max_bytes: u64) {
+        self.max_bytes.store(max_bytes, atomic::Ordering::Relaxed);
+    }
+
+    /// The bytes dropped since the last call.
+    pub fn take_dropped(&self) -> u64 {
+        self.dropped_bytes.swap(0, atomic::Ordering::Relaxed)
+    }
+
+    /// Whether `bytes` more would go over the limit.
+    fn is_full_for(&self, bytes: u64) -> bool {
+        let max_bytes = self.max_bytes.load(atomic::Ordering::Relaxed);
+        max_bytes != 0 && self.queued_bytes() + bytes > max_bytes
+    }
+}
+
 #[derive(Debug)]
 pub struct PlayerHandle {
     writer: kanal::AsyncSender<OrderedBytes>,
 
+    buffer: Arc<StreamBuffer>,
+
     /// Whether the player is allowed to send broadcasts.
     ///
     /// This exists because the player is not automatically in the play state,
@@ -112,13 +156,18 @@ pub struct PlayerHandle {
 
 impl PlayerHandle {
     #[must_use]
-    pub const fn new(writer: kanal::AsyncSender<OrderedBytes>) -> Self {
+    pub const fn new(writer: kanal::AsyncSender<OrderedBytes>, buffer: Arc<StreamBuffer>) -> Self {
         Self {
             writer,
+            buffer,
             can_receive_broadcasts: AtomicBool::new(false),
         }
     }
 
+    pub fn buffer(&self) -> &StreamBuffer {
+        &self.buffer
+    }
+
     pub fn shutdown(&self) {
         let _ = self.writer.try_send(OrderedBytes::SHUTDOWN);
         self.writer.close();
@@ -133,16 +182,40 @@ impl PlayerHandle {
         self.can_receive_broadcasts.load(atomic::Ordering::Relaxed)
     }
 
+    /// Sends a broadcast, unless the player already has as many bytes queued as their
+    /// [`StreamBuffer`] allows. Dropped broadcasts are counted instead.
+    pub fn send_broadcast(&self, ordered_bytes: OrderedBytes) -> anyhow::Result<()> {
+        let len = ordered_bytes.data.len() as u64;
+
+        if self.buffer.is_full_for(len) {
+            self.buffer
+                .dropped_bytes
+                .fetch_add(len, atomic::Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.send(ordered_bytes)
+    }
+
     pub fn send(&self, ordered_bytes: OrderedBytes) -> anyhow::Result<()> {
+        let len = ordered_bytes.data.len() as u64;
+
+        // counted before sending, as the writer task may already have written it afterwards
+        self.buffer
+            .queued_bytes
+            .fetch_add(len, atomic::Ordering::Relaxed);
+
         match self.writer.try_send(ordered_bytes) {
             Ok(true) => Ok(()),
 
             Ok(false) => {
+                self.buffer.written(len);
                 let is_full = self.writer.is_full();
                 self.shutdown();
                 bail!("failed to send packet to player, channel is full: {is_full}");
             }
             Err(e) => {
+                self.buffer.written(len);
                 self.writer.close();
                 bail!("failed to send packet to player: {e}");
             }
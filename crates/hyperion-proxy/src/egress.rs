@@ -4,8 +4,8 @@ use bvh::{Aabb, Bvh};
 use bytes::Bytes;
 use glam::I16Vec2;
 use hyperion_proto::{
-    ArchivedSetReceiveBroadcasts, ArchivedUnicast, ArchivedUpdatePlayerChunkPositions,
-    ChunkPosition,
+    ArchivedMulticast, ArchivedSetReceiveBroadcasts, ArchivedSetStreamBufferLimit, ArchivedUnicast,
+    ArchivedUpdatePlayerChunkPositions, ChunkPosition,
 };
 use rustc_hash::FxBuildHasher;
 use tracing::{Instrument, debug, error, info_span, instrument, warn};
@@ -89,7 +89,7 @@ impl Egress {
                     let to_send =
                         OrderedBytes::with_exclusions(pkt.order, data.clone(), exclusions.clone());
 
-                    if let Err(e) = player.send(to_send) {
+                    if let Err(e) = player.send_broadcast(to_send) {
                         warn!("Failed to send data to player: {:?}", e);
                         if let Some(result) = players.remove(player_id) {
                             result.shutdown();
@@ -169,7 +169,7 @@ impl Egress {
                             exclusions: Some(exclusions.clone()),
                         };
 
-                        if let Err(e) = player.send(to_send) {
+                        if let Err(e) = player.send_broadcast(to_send) {
                             warn!("Failed to send data to player: {:?}", e);
                             if let Some(result) = players.remove(id) {
                                 result.shutdown();
@@ -198,6 +198,30 @@ impl Egress {
 
         let Ok(id) = rkyv::deserialize::<u64, !>(&pkt.stream);
 
+        self.send_to(id, ordered);
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_multicast(&self, pkt: &ArchivedMulticast<'_>) {
+        // copied once, as cloning `Bytes` only bumps a reference count
+        let data = bytes::Bytes::from(pkt.data.to_vec());
+
+        let Ok(order) = rkyv::deserialize::<u32, !>(&pkt.order);
+
+        for id in pkt.streams.iter() {
+            let Ok(id) = rkyv::deserialize::<u64, !>(id);
+
+            let ordered = OrderedBytes {
+                order,
+                data: data.clone(),
+                ..OrderedBytes::DEFAULT
+            };
+
+            self.send_to(id, ordered);
+        }
+    }
+
+    fn send_to(&self, id: u64, ordered: OrderedBytes) {
         let players = self.player_registry.pin();
 
         let Some(player) = players.get(&id) else {
@@ -230,4 +254,18 @@ impl Egress {
 
         player.enable_receive_broadcasts();
     }
+
+    #[instrument(skip_all)]
+    pub fn handle_set_stream_buffer_limit(&self, pkt: &ArchivedSetStreamBufferLimit) {
+        let players = self.player_registry.pin();
+        let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
+        let Ok(max_bytes) = rkyv::deserialize::<u64, !>(&pkt.max_bytes);
+
+        let Some(player) = players.get(&stream) else {
+            debug!("Player not found for stream {stream:?}");
+            return;
+        };
+
+        player.buffer().set_max_bytes(max_bytes);
+    }
 }
@@ -17,11 +17,13 @@
     clippy::future_not_send
 )]
 
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use colored::Colorize;
-use hyperion_proto::{ArchivedServerToProxyMessage, ChunkPosition};
+use hyperion_proto::{
+    ArchivedServerToProxyMessage, ChunkPosition, ProxyToServerMessage, StreamStats,
+};
 use rustc_hash::FxBuildHasher;
 use tokio::{
     io::{AsyncReadExt, BufReader},
@@ -31,8 +33,11 @@ use tokio_util::net::Listener;
 use tracing::{Instrument, debug, error, info, info_span, instrument, trace, warn};
 
 use crate::{
-    cache::BufferedEgress, data::PlayerHandle, egress::Egress, player::initiate_player_connection,
-    server_sender::launch_server_writer,
+    cache::BufferedEgress,
+    data::{PlayerHandle, StreamBuffer},
+    egress::Egress,
+    player::initiate_player_connection,
+    server_sender::{ServerSender, launch_server_writer},
 };
 
 /// 4 KiB
@@ -43,6 +48,9 @@ const DEFAULT_BUFFER_SIZE: usize = 4 * 1024;
 /// memory exhaustion from slow or unresponsive clients.
 const MAX_PLAYER_PENDING_MESSAGES: usize = 1_024;
 
+/// How often [`StreamStats`] are sent to the server.
+const STREAM_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
 pub mod cache;
 pub mod data;
 pub mod egress;
@@ -188,6 +196,19 @@ async fn connect_to_server_and_run_proxy(
                 .instrument(info_span!("server_reader_loop"))
     });
 
+    tokio::spawn({
+        let mut shutdown_rx = shutdown_rx.clone();
+        let server_sender = server_sender.clone();
+
+        async move {
+            tokio::select! {
+                _ = shutdown_rx.wait_for(Option::is_some) => {}
+                () = report_stream_stats(player_registry, server_sender) => {}
+            }
+        }
+        .instrument(info_span!("stream_stats_loop"))
+    });
+
     // 0 is reserved for "None" value
     let mut player_id_on = 1;
 
@@ -207,7 +228,8 @@ async fn connect_to_server_and_run_proxy(
 
         // todo: re-add bounding but issues if have MASSIVE number of packets
         let (tx, rx) = kanal::bounded_async(MAX_PLAYER_PENDING_MESSAGES);
-        registry.insert(player_id_on, PlayerHandle::new(tx));
+        let buffer = Arc::new(StreamBuffer::default());
+        registry.insert(player_id_on, PlayerHandle::new(tx, buffer.clone()));
 
         // todo: some SlotMap like thing
         debug!("got player with id {player_id_on:?}");
@@ -217,6 +239,7 @@ async fn connect_to_server_and_run_proxy(
             shutdown_rx.clone(),
             player_id_on,
             rx,
+            buffer,
             server_sender.clone(),
             player_registry,
             player_positions,
@@ -226,6 +249,41 @@ async fn connect_to_server_and_run_proxy(
     }
 }
 
+/// Tells the server how far behind each player is, every [`STREAM_STATS_INTERVAL`]. Returns once
+/// the server can no longer be sent to.
+async fn report_stream_stats(
+    player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+    server_sender: ServerSender,
+) {
+    let mut interval = tokio::time::interval(STREAM_STATS_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        // collected first, as the registry cannot be held across an await
+        let stats: Vec<_> = player_registry
+            .pin()
+            .iter()
+            .map(|(&stream, player)| StreamStats {
+                stream,
+                dropped_bytes: player.buffer().take_dropped(),
+                queued_bytes: player.buffer().queued_bytes(),
+            })
+            .collect();
+
+        for stats in stats {
+            let message =
+                rkyv::to_bytes::<rkyv::rancor::Error>(&ProxyToServerMessage::StreamStats(stats))
+                    .unwrap();
+
+            if let Err(e) = server_sender.send(message).await {
+                warn!("failed to send stream stats to server: {e}");
+                return;
+            }
+        }
+    }
+}
+
 struct IngressHandler {
     server_read: BufReader<tokio::net::tcp::OwnedReadHalf>,
     buffer: Vec<u8>,
@@ -1,6 +1,6 @@
 //! Player connection handling and packet processing.
 
-use std::io::IoSlice;
+use std::{io::IoSlice, sync::Arc};
 
 use hyperion_proto::{
     ChunkPosition, PlayerConnect, PlayerDisconnect, PlayerDisconnectReason, PlayerPackets,
@@ -17,7 +17,7 @@ use tracing::{info, info_span, instrument, warn};
 use crate::{
     ShutdownType,
     cache::ExclusionsManager,
-    data::{OrderedBytes, PlayerHandle},
+    data::{OrderedBytes, PlayerHandle, StreamBuffer},
     server_sender::ServerSender,
     util::AsyncWriteVectoredExt,
 };
@@ -33,11 +33,13 @@ const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
 ///
 /// It also handles player disconnection and shutdown scenarios.
 #[instrument(skip_all, fields(player_id = player_id))]
+#[expect(clippy::too_many_arguments, reason = "both tasks need all of these")]
 pub fn initiate_player_connection(
     socket: impl tokio::io::AsyncRead + AsyncWrite + Send + 'static,
     mut shutdown_signal: tokio::sync::watch::Receiver<Option<ShutdownType>>,
     player_id: u64,
     incoming_packet_receiver: kanal::AsyncReceiver<OrderedBytes>,
+    buffer: Arc<StreamBuffer>,
     server_sender: ServerSender,
     player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
     player_positions: &'static papaya::HashMap<u64, ChunkPosition, FxBuildHasher>,
@@ -112,7 +114,7 @@ pub fn initiate_player_connection(
 
     // Task for handling outgoing packets (proxy -> player)
     let mut packet_writer_task = tokio::spawn(async move {
-        let mut packet_writer = PlayerPacketWriter::new(socket_writer, player_id);
+        let mut packet_writer = PlayerPacketWriter::new(socket_writer, player_id, buffer);
 
         while let Ok(outgoing_packet) = incoming_packet_receiver.recv().await {
             if outgoing_packet.is_shutdown() {
@@ -192,25 +194,37 @@ struct PlayerPacketWriter<W> {
     writer: W,
     player_id: u64,
     pending_packets: Vec<OrderedBytes>,
+    /// The length of the data of [`Self::pending_packets`], as counted by [`PlayerHandle::send`].
+    pending_bytes: u64,
+    buffer: Arc<StreamBuffer>,
     io_vecs: Vec<IoSlice<'static>>,
 }
 
 impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
     /// Creates a new [`PlayerPacketWriter`] instance.
-    const fn new(writer: W, player_id: u64) -> Self {
+    const fn new(writer: W, player_id: u64, buffer: Arc<StreamBuffer>) -> Self {
         Self {
             writer,
             player_id,
             pending_packets: Vec::new(),
+            pending_bytes: 0,
+            buffer,
             io_vecs: vec![],
         }
     }
 
     /// Adds a packet to the queue for writing.
     fn enqueue_packet(&mut self, packet: OrderedBytes) {
+        self.pending_bytes += packet.data.len() as u64;
         self.pending_packets.push(packet);
     }
 
+    /// Clears the pending packets once they were written.
+    fn clear_pending(&mut self) {
+        self.pending_packets.clear();
+        self.buffer.written(std::mem::take(&mut self.pending_bytes));
+    }
+
     /// Flushes all pending packets to the TCP writer.
     #[instrument(skip(self), fields(player_id = ?self.player_id), level = "trace")]
     async fn flush_pending_packets(&mut self) -> anyhow::Result<()> {
@@ -221,7 +235,7 @@ impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
         }
 
         if self.io_vecs.is_empty() {
-            self.pending_packets.clear();
+            self.clear_pending();
             return Ok(());
         }
 
@@ -234,7 +248,7 @@ impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
         }
 
         self.writer.write_vectored_all(&mut self.io_vecs).await?;
-        self.pending_packets.clear();
+        self.clear_pending();
         self.io_vecs.clear();
 
         Ok(())
@@ -246,7 +260,7 @@ fn prepare_io_vectors(
     packet_queue: &mut [OrderedBytes],
     player_id: u64,
 ) -> impl Iterator<Item = IoSlice<'_>> + '_ {
-    packet_queue.sort_unstable_by_key(|packet| packet.order);
+    packet_queue.sort_unstable_by_key(|packet| (packet.order, packet.offset));
 
     packet_queue.iter_mut().flat_map(move |packet| {
         let packet_data = packet.data.as_ref();
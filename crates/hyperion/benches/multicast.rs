@@ -0,0 +1,53 @@
+//! Whether queuing a multicast allocates for the streams it is sent to. The streams are borrowed
+//! by [`Multicast`], so nothing is allocated for them, unlike when they are copied into a `Vec`
+//! first.
+
+use std::hint::black_box;
+
+use divan::{AllocProfiler, Bencher};
+use hyperion_proto::{Multicast, ServerToProxyMessage};
+use rkyv::util::AlignedVec;
+
+#[global_allocator]
+static ALLOC: AllocProfiler = AllocProfiler::system();
+
+const STREAM_COUNTS: &[u64] = &[1, 16, 256];
+
+fn main() {
+    divan::main();
+}
+
+fn serialize(buffer: &mut AlignedVec, streams: &[u64]) {
+    buffer.clear();
+
+    let message = ServerToProxyMessage::Multicast(Multicast {
+        order: 0,
+        streams,
+        data: b"packet",
+    });
+
+    rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&message, &mut *buffer).unwrap();
+}
+
+#[divan::bench(
+    args = STREAM_COUNTS,
+)]
+fn borrowed_streams(bencher: Bencher<'_, '_>, count: u64) {
+    let streams: Vec<_> = (0..count).collect();
+    let mut buffer = AlignedVec::new();
+
+    bencher.bench_local(|| serialize(&mut buffer, black_box(&streams)));
+}
+
+#[divan::bench(
+    args = STREAM_COUNTS,
+)]
+fn copied_streams(bencher: Bencher<'_, '_>, count: u64) {
+    let streams: Vec<_> = (0..count).collect();
+    let mut buffer = AlignedVec::new();
+
+    bencher.bench_local(|| {
+        let streams = black_box(&streams).to_vec();
+        serialize(&mut buffer, &streams);
+    });
+}
@@ -0,0 +1,333 @@
+//! See [`BanList`].
+//!
+//! Only players can be banned, not addresses: the proxy does not tell the server where players
+//! connect from.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use byteorder::NativeEndian;
+use flecs_ecs::macros::Component;
+use heed::{Database, types};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{l10n::Localization, storage::LocalDb};
+
+/// A player who may not join.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Ban {
+    pub uuid: Uuid,
+    /// The name of the player when they were banned.
+    pub name: String,
+    pub reason: String,
+    /// Who banned the player.
+    pub source: String,
+    /// When the player was banned, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// When the ban ends, in seconds since the Unix epoch, or `None` if it is permanent.
+    pub expires_at: Option<u64>,
+}
+
+impl Ban {
+    #[must_use]
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// The message the banned player is disconnected with, in `locale`.
+    #[must_use]
+    pub fn disconnect_message(&self, l10n: &Localization, locale: &str, now: u64) -> String {
+        let ends = match self.expires_at {
+            Some(expires_at) => {
+                let left = format_duration(Duration::from_secs(expires_at.saturating_sub(now)));
+                l10n.translate(locale, "ban.screen.ends_in", &[("duration", &left)])
+            }
+            None => l10n.translate(locale, "ban.screen.permanent", &[]),
+        };
+
+        l10n.translate(locale, "ban.screen.message", &[
+            ("reason", &self.reason),
+            ("ends", &ends),
+        ])
+    }
+}
+
+/// The current time in seconds since the Unix epoch, as [`Ban`] uses.
+#[must_use]
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Parses durations like `30m`, `12h` or `1d12h`. The units are `s`, `m`, `h`, `d` and `w`.
+#[must_use]
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+    let mut secs = 0_u64;
+    let mut number = None;
+
+    for c in duration.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let n: u64 = number.unwrap_or(0);
+            number = Some(n.checked_mul(10)?.checked_add(u64::from(digit))?);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+
+        secs = secs.checked_add(number.take()?.checked_mul(unit)?)?;
+    }
+
+    // a number without a unit is not a duration
+    if number.is_some() || secs == 0 {
+        return None;
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+/// Formats a duration with its two largest units, such as `1d 12h`.
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: [(u64, &str); 5] = [
+        (7 * 24 * 60 * 60, "w"),
+        (24 * 60 * 60, "d"),
+        (60 * 60, "h"),
+        (60, "m"),
+        (1, "s"),
+    ];
+
+    let mut rest = duration.as_secs();
+    let mut parts = Vec::new();
+
+    for (unit, suffix) in UNITS {
+        let n = rest / unit;
+        rest %= unit;
+
+        if n > 0 && parts.len() < 2 {
+            parts.push(format!("{n}{suffix}"));
+        }
+    }
+
+    if parts.is_empty() {
+        return "0s".to_owned();
+    }
+
+    parts.join(" ")
+}
+
+/// Banned players by UUID, stored as JSON in the [`LocalDb`].
+///
+/// Expired bans are removed whenever they are looked at.
+#[derive(Component, Debug, Clone)]
+pub struct BanList {
+    db: LocalDb,
+    bans: Database<types::U128<NativeEndian>, types::Bytes>,
+}
+
+impl BanList {
+    /// Creates a new [`BanList`] from a given [`LocalDb`].
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let bans = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some("uuid-to-ban"))?;
+            wtxn.commit()?;
+            db
+        };
+
+        Ok(Self {
+            db: db.clone(),
+            bans,
+        })
+    }
+
+    /// Bans a player, replacing any ban they already have.
+    pub fn ban(&self, ban: &Ban) -> anyhow::Result<()> {
+        let mut wtxn = self.db.write_txn()?;
+        self.bans
+            .put(&mut wtxn, &ban.uuid.as_u128(), &serde_json::to_vec(ban)?)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    /// Lifts the ban of a player. Returns whether they were banned.
+    pub fn unban(&self, uuid: Uuid) -> anyhow::Result<bool> {
+        let mut wtxn = self.db.write_txn()?;
+        let removed = self.bans.delete(&mut wtxn, &uuid.as_u128())?;
+        wtxn.commit()?;
+
+        Ok(removed)
+    }
+
+    /// The ban of a player, if they are banned at `now`.
+    pub fn get(&self, uuid: Uuid, now: u64) -> anyhow::Result<Option<Ban>> {
+        let ban: Ban = {
+            let rtxn = self.db.read_txn()?;
+
+            let Some(bytes) = self.bans.get(&rtxn, &uuid.as_u128())? else {
+                return Ok(None);
+            };
+
+            serde_json::from_slice(bytes).context("invalid ban")?
+        };
+
+        if ban.is_expired(now) {
+            self.unban(uuid)?;
+            return Ok(None);
+        }
+
+        Ok(Some(ban))
+    }
+
+    /// Every ban in effect at `now`, oldest first.
+    pub fn list(&self, now: u64) -> anyhow::Result<Vec<Ban>> {
+        let mut wtxn = self.db.write_txn()?;
+
+        let mut bans = Vec::new();
+        let mut expired = Vec::new();
+
+        for entry in self.bans.iter(&wtxn)? {
+            let (uuid, bytes) = entry?;
+
+            match serde_json::from_slice::<Ban>(bytes) {
+                Ok(ban) if ban.is_expired(now) => expired.push(uuid),
+                Ok(ban) => bans.push(ban),
+                Err(e) => warn!("skipping invalid ban of {}: {e}", Uuid::from_u128(uuid)),
+            }
+        }
+
+        for uuid in expired {
+            self.bans.delete(&mut wtxn, &uuid)?;
+        }
+
+        wtxn.commit()?;
+
+        bans.sort_by_key(|ban| ban.created_at);
+
+        Ok(bans)
+    }
+
+    /// The ban in effect at `now` of the player who had the name `name` when they were banned.
+    pub fn find_by_name(&self, name: &str, now: u64) -> anyhow::Result<Option<Ban>> {
+        let ban = self
+            .list(now)?
+            .into_iter()
+            .find(|ban| ban.name.eq_ignore_ascii_case(name));
+
+        Ok(ban)
+    }
+
+    /// The message to disconnect a joining player with if they are banned. Joining players have
+    /// not said which locale they use yet, so it is in the default one.
+    #[must_use]
+    pub fn rejection(&self, l10n: &Localization, uuid: Uuid, now: u64) -> Option<String> {
+        match self.get(uuid, now) {
+            Ok(ban) => ban.map(|ban| ban.disconnect_message(l10n, l10n.default_locale(), now)),
+            Err(e) => {
+                warn!("failed to check whether {uuid} is banned: {e}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    use super::{Ban, BanList, format_duration, parse_duration};
+    use crate::{l10n::Localization, storage::LocalDb};
+
+    const HOUR: u64 = 60 * 60;
+
+    /// A ban list in a directory that is deleted along with the returned [`TempDir`].
+    fn ban_list() -> (TempDir, BanList) {
+        let dir = TempDir::new().unwrap();
+        let bans = BanList::new(&LocalDb::open(dir.path()).unwrap()).unwrap();
+
+        (dir, bans)
+    }
+
+    fn ban(expires_at: Option<u64>) -> Ban {
+        Ban {
+            uuid: Uuid::from_u128(1),
+            name: "Griefer".to_owned(),
+            reason: "Griefing spawn".to_owned(),
+            source: "Moderator".to_owned(),
+            created_at: 1000,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn temporary_bans_expire() {
+        let (_dir, bans) = ban_list();
+        let ban = ban(Some(1000 + HOUR));
+        let uuid = ban.uuid;
+
+        bans.ban(&ban).unwrap();
+
+        assert_eq!(bans.get(uuid, 1000 + HOUR - 1).unwrap(), Some(ban.clone()));
+        assert_eq!(bans.find_by_name("griefer", 1000).unwrap(), Some(ban));
+
+        assert_eq!(bans.get(uuid, 1000 + HOUR).unwrap(), None);
+
+        // looking at the expired ban removed it for good
+        assert!(bans.list(1000).unwrap().is_empty());
+        assert!(!bans.unban(uuid).unwrap());
+    }
+
+    #[test]
+    fn banned_players_are_told_why() {
+        let (_dir, bans) = ban_list();
+        let l10n = Localization::built_in().unwrap();
+        let uuid = Uuid::from_u128(1);
+
+        assert_eq!(bans.rejection(&l10n, uuid, 1000), None);
+
+        bans.ban(&ban(Some(1000 + 36 * HOUR))).unwrap();
+
+        let msg = bans.rejection(&l10n, uuid, 1000).unwrap();
+        assert!(msg.contains("Griefing spawn"));
+        assert!(msg.contains("1d 12h"));
+
+        bans.ban(&ban(None)).unwrap();
+
+        let msg = bans.rejection(&l10n, uuid, 1000).unwrap();
+        assert!(msg.contains("Griefing spawn"));
+        assert!(msg.contains("permanent"));
+
+        // players who are already online read it in their own locale
+        let msg = ban(None).disconnect_message(&l10n, "de_de", 1000);
+        assert!(msg.contains("Grund: §fGriefing spawn"));
+
+        assert!(bans.unban(uuid).unwrap());
+        assert_eq!(bans.rejection(&l10n, uuid, 1000), None);
+    }
+
+    #[test]
+    fn durations_round_trip() {
+        let duration = parse_duration("1d12h").unwrap();
+
+        assert_eq!(duration, Duration::from_secs(36 * HOUR));
+        assert_eq!(format_duration(duration), "1d 12h");
+
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("12"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("2x"), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! See [`PlayerCapacity`].
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rustc_hash::FxHashSet;
+use uuid::Uuid;
+
+use crate::config::ServerConfig;
+
+/// How many players may be online at once.
+///
+/// A joining player takes a slot when their login is accepted, before anything is spawned for
+/// them, and gives it back once their entity is removed. Slots are counted atomically, so logins
+/// processed in parallel within a tick cannot overshoot the limit.
+///
+/// On top of [`ServerConfig::max_players`], there are [`ServerConfig::reserved_slots`] that only
+/// the players in [`ServerConfig::reserved_players`] may use, so staff can join a full server.
+#[derive(Debug)]
+pub struct PlayerCapacity {
+    max_players: usize,
+    reserved_slots: usize,
+    reserved_uuids: FxHashSet<Uuid>,
+    /// Lowercase.
+    reserved_names: FxHashSet<String>,
+    /// Players online plus players that are still joining.
+    taken: AtomicUsize,
+}
+
+impl PlayerCapacity {
+    /// `reserved_players` are names or UUIDs.
+    #[must_use]
+    pub fn new(max_players: usize, reserved_slots: usize, reserved_players: &[String]) -> Self {
+        let mut reserved_uuids = FxHashSet::default();
+        let mut reserved_names = FxHashSet::default();
+
+        for player in reserved_players {
+            match Uuid::parse_str(player) {
+                Ok(uuid) => {
+                    reserved_uuids.insert(uuid);
+                }
+                Err(_) => {
+                    reserved_names.insert(player.to_lowercase());
+                }
+            }
+        }
+
+        Self {
+            max_players,
+            reserved_slots,
+            reserved_uuids,
+            reserved_names,
+            taken: AtomicUsize::new(0),
+        }
+    }
+
+    #[must_use]
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(
+            usize::try_from(config.max_players).unwrap_or_default(),
+            usize::try_from(config.reserved_slots).unwrap_or_default(),
+            &config.reserved_players,
+        )
+    }
+
+    /// The number of slots anyone may use. This is what the server list shows.
+    #[must_use]
+    pub const fn max_players(&self) -> usize {
+        self.max_players
+    }
+
+    #[must_use]
+    pub const fn reserved_slots(&self) -> usize {
+        self.reserved_slots
+    }
+
+    /// The number of slots taken, including those of players that are still joining.
+    #[must_use]
+    pub fn taken(&self) -> usize {
+        self.taken.load(Ordering::Relaxed)
+    }
+
+    /// Whether the player may use the reserved slots.
+    #[must_use]
+    pub fn is_reserved_for(&self, uuid: Uuid, name: &str) -> bool {
+        self.reserved_uuids.contains(&uuid) || self.reserved_names.contains(&name.to_lowercase())
+    }
+
+    /// Takes a slot for a joining player. Returns whether there was one left for them.
+    #[must_use]
+    pub fn try_take(&self, uuid: Uuid, name: &str) -> bool {
+        let mut limit = self.max_players;
+
+        if self.is_reserved_for(uuid, name) {
+            limit += self.reserved_slots;
+        }
+
+        self.taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |taken| {
+                (taken < limit).then_some(taken + 1)
+            })
+            .is_ok()
+    }
+
+    /// Gives back the slot of a player that left.
+    pub fn release(&self) {
+        let released = self
+            .taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |taken| {
+                taken.checked_sub(1)
+            });
+
+        debug_assert!(
+            released.is_ok(),
+            "released more player slots than were taken"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::PlayerCapacity;
+
+    const MAX_PLAYERS: usize = 20;
+    const RESERVED_SLOTS: usize = 3;
+
+    fn player(id: u128) -> (Uuid, String) {
+        (Uuid::from_u128(id), format!("player{id}"))
+    }
+
+    fn capacity() -> PlayerCapacity {
+        let staff = Uuid::from_u128(1_000).to_string();
+        PlayerCapacity::new(MAX_PLAYERS, RESERVED_SLOTS, &[
+            staff,
+            "Moderator".to_owned(),
+        ])
+    }
+
+    /// Joins everyone at once, one thread per login, and returns how many got in.
+    fn burst(capacity: &PlayerCapacity, players: &[(Uuid, String)]) -> usize {
+        std::thread::scope(|scope| {
+            let joins: Vec<_> = players
+                .iter()
+                .map(|(uuid, name)| scope.spawn(|| capacity.try_take(*uuid, name)))
+                .collect();
+
+            joins
+                .into_iter()
+                .map(|join| join.join().unwrap())
+                .filter(|&joined| joined)
+                .count()
+        })
+    }
+
+    #[test]
+    fn a_burst_of_joins_fills_exactly_the_slots() {
+        let capacity = capacity();
+
+        let players: Vec<_> = (0..100).map(player).collect();
+        assert_eq!(burst(&capacity, &players), MAX_PLAYERS);
+        assert_eq!(capacity.taken(), MAX_PLAYERS);
+
+        // the reserved slots are still free for staff, by uuid or by name
+        let staff: Vec<_> = (0..10)
+            .map(|id| {
+                if id % 2 == 0 {
+                    (Uuid::from_u128(1_000), "staff".to_owned())
+                } else {
+                    (Uuid::from_u128(2_000 + id), "moderator".to_owned())
+                }
+            })
+            .collect();
+
+        assert_eq!(burst(&capacity, &staff), RESERVED_SLOTS);
+        assert_eq!(capacity.taken(), MAX_PLAYERS + RESERVED_SLOTS);
+
+        let more = [player(200), player(201)];
+        assert_eq!(burst(&capacity, &more), 0);
+    }
+
+    #[test]
+    fn reserved_players_cannot_go_beyond_the_reserved_slots() {
+        let capacity = PlayerCapacity::new(MAX_PLAYERS, RESERVED_SLOTS, &["Staff".to_owned()]);
+
+        // staff may also use the regular slots, but never more than both together
+        let staff = vec![(Uuid::from_u128(1), "staff".to_owned()); 100];
+        assert_eq!(burst(&capacity, &staff), MAX_PLAYERS + RESERVED_SLOTS);
+
+        assert!(!capacity.try_take(Uuid::from_u128(2), "someone"));
+    }
+
+    #[test]
+    fn leaving_frees_a_slot() {
+        let capacity = PlayerCapacity::new(1, 0, &[]);
+        let (uuid, name) = player(1);
+
+        assert!(capacity.try_take(uuid, &name));
+        assert!(!capacity.try_take(uuid, &name));
+
+        capacity.release();
+        assert_eq!(capacity.taken(), 0);
+        assert!(capacity.try_take(uuid, &name));
+    }
+}
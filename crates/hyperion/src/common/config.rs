@@ -1,23 +1,146 @@
 //! Configuration for the server.
 
-use std::{fmt::Debug, fs::File, io::Read, path::Path};
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
 
+use anyhow::{Context, bail};
 use flecs_ecs::macros::Component;
 use serde::{Deserialize, Serialize};
 use tracing::{info, instrument, warn};
 
-/// The configuration for the server representing a `toml` file.
-#[derive(Serialize, Deserialize, Debug, Component)]
-pub struct Config {
+/// The environment variable that overrides where [`ServerConfig::load_default`] looks.
+pub const CONFIG_PATH_VAR: &str = "HYPERION_CONFIG";
+
+/// Where [`ServerConfig::load_default`] looks unless [`CONFIG_PATH_VAR`] is set.
+pub const DEFAULT_CONFIG_PATH: &str = "hyperion.toml";
+
+/// Where the configuration used to be. It is still read if there is nothing at
+/// [`DEFAULT_CONFIG_PATH`], so existing servers keep their settings after updating.
+pub const LEGACY_CONFIG_PATH: &str = "run/config.toml";
+
+/// The file written when there is no configuration yet. It holds the same values as
+/// [`ServerConfig::default`].
+const DEFAULT_CONFIG: &str = r#"# Hyperion server configuration. Every key is optional, and
+# missing keys use the values below.
+
+# The address the server listens on for the proxy, as an IP address or host name and a port.
+proxy_address = "0.0.0.0:35565"
+# Whether to derive the UUIDs of players from their names instead of using the ones clients send.
+offline_mode = false
+# An Anvil save to load the world from at startup.
+# world_path = "run/world"
+
+# Packets this many bytes or longer are compressed. A negative value turns compression off.
+compression_threshold = 256
+# From 0 (no compression) to 12 (smallest packets, slowest).
+compression_level = 2
+
+max_players = 10000
+# Slots on top of `max_players` that only `reserved_players` may use.
+reserved_slots = 0
+# Names or UUIDs of the players who may use the reserved slots.
+reserved_players = []
+# Names or UUIDs of players who become operators the first time they join, so a new server has
+# someone who can `/op` others.
+operators = []
+# In chunks, from 2 to 32.
+view_distance = 32
+# In chunks, from 2 to 32.
+simulation_distance = 10
+server_desc = "Hyperion Test Server"
+# In blocks.
+border_diameter = 100.0
+# Whether the weather changes on its own. When off, it only changes with `/weather`.
+weather_cycle = true
+
+# The message players who are not on the whitelist are kicked with.
+whitelist_message = "You are not whitelisted on this server"
+# The message players are kicked with when every slot they may use is taken.
+server_full_message = "The server is full"
+# The message players are kicked with when the server shuts down.
+shutdown_message = "The server is shutting down"
+# How many seconds saving may take when shutting down before the server exits without finishing.
+shutdown_timeout_secs = 30
+
+# The port metrics are served on when the `metrics` feature is enabled.
+metrics_port = 9464
+# Whether to time every system, for `/tps` and slow tick warnings. Costs a little each tick.
+profile_ticks = false
+# How many milliseconds a tick may take before a warning lists the slowest systems.
+slow_tick_ms = 50
+# How many bytes a tick may queue for the proxy before a warning shows what they were spent on.
+# tick_byte_budget = 4194304
+
+# The world spawn, which players join and respawn at unless a game moves them elsewhere.
+[spawn]
+kind = "Chebyshev"
+radius = 1000
+x = 0
+y = 64
+z = 0
+"#;
+
+/// Keys that are not written when they have their default value of `None`.
+const OPTIONAL_KEYS: [&str; 2] = ["world_path", "tick_byte_budget"];
+
+/// The configuration for the server representing a `toml` file. Missing keys have their
+/// [`Default`] value.
+#[derive(Serialize, Deserialize, Debug, Component, PartialEq)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// The address the server listens on for the proxy, as an IP address or host name and a
+    /// port, see [`split_host_port`].
+    pub proxy_address: String,
+    /// Whether to derive the UUIDs of players from their names instead of using the ones clients
+    /// send.
+    pub offline_mode: bool,
+    /// An Anvil save to load the world from at startup.
+    pub world_path: Option<PathBuf>,
+    /// Packets this many bytes or longer are compressed. A negative value turns compression off.
+    pub compression_threshold: i32,
+    /// The [`libdeflater`] compression level, from 0 to 12.
+    pub compression_level: i32,
     pub border_diameter: Option<f64>,
     pub max_players: i32,
+    /// Slots on top of `max_players` that only `reserved_players` may use.
+    pub reserved_slots: u32,
+    /// Names or UUIDs of the players who may use the reserved slots.
+    pub reserved_players: Vec<String>,
+    /// Names or UUIDs of players who become operators the first time they join, so a new server
+    /// has someone who can `/op` others.
+    pub operators: Vec<String>,
     pub view_distance: i32,
     pub simulation_distance: i32,
     pub server_desc: String,
+    /// Whether the weather changes on its own. When off, it only changes by command.
+    pub weather_cycle: bool,
+    /// The initial [`crate::simulation::spawn::SpawnPoint`].
     pub spawn: Spawn,
+    /// The message players who are not on the whitelist are kicked with.
+    pub whitelist_message: String,
+    /// The message players are kicked with when every slot they may use is taken.
+    pub server_full_message: String,
+    /// The message players are kicked with when the server shuts down.
+    pub shutdown_message: String,
+    /// How many seconds saving may take when shutting down before the server exits without
+    /// finishing.
+    pub shutdown_timeout_secs: u64,
+    /// The port metrics are served on when the `metrics` feature is enabled.
+    pub metrics_port: u16,
+    /// Whether to time every system, for `/tps` and slow tick warnings. Costs a little each tick.
+    pub profile_ticks: bool,
+    /// How many milliseconds a tick may take before a warning lists the slowest systems.
+    pub slow_tick_ms: u64,
+    /// How many bytes a tick may queue for the proxy before a warning shows what they were spent
+    /// on. See [`crate::net::NetworkMetrics`].
+    pub tick_byte_budget: Option<u64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Component)]
+#[derive(Serialize, Deserialize, Debug, Component, PartialEq, Eq)]
+#[serde(default)]
 pub struct Spawn {
     pub kind: Radius,
     pub radius: i32,
@@ -26,7 +149,7 @@ pub struct Spawn {
     pub z: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Radius {
     Chebyshev,
     Euclidean,
@@ -57,15 +180,32 @@ pub enum Radius {
 //     }
 // }
 
-impl Default for Config {
+impl Default for ServerConfig {
     fn default() -> Self {
         Self {
+            proxy_address: "0.0.0.0:35565".to_owned(),
+            offline_mode: false,
+            world_path: None,
+            compression_threshold: 256,
+            compression_level: 2,
             border_diameter: Some(100.0),
             max_players: 10_000,
+            reserved_slots: 0,
+            reserved_players: Vec::new(),
+            operators: Vec::new(),
             view_distance: 32,
             simulation_distance: 10,
             server_desc: "Hyperion Test Server".to_owned(),
+            weather_cycle: true,
             spawn: Spawn::default(),
+            whitelist_message: "You are not whitelisted on this server".to_owned(),
+            server_full_message: "The server is full".to_owned(),
+            shutdown_message: "The server is shutting down".to_owned(),
+            shutdown_timeout_secs: 30,
+            metrics_port: 9464,
+            profile_ticks: false,
+            slow_tick_ms: 50,
+            tick_byte_budget: None,
         }
     }
 }
@@ -82,7 +222,9 @@ impl Default for Spawn {
     }
 }
 
-impl Config {
+impl ServerConfig {
+    /// Loads the configuration at `path`. If there is none, a commented default configuration is
+    /// written there.
     #[instrument]
     pub fn load<P>(path: P) -> anyhow::Result<Self>
     where
@@ -90,36 +232,300 @@ impl Config {
     {
         info!("loading configuration file");
 
-        if path.as_ref().exists() {
-            let mut file = File::open(path)?;
-            let mut contents = String::default();
-            file.read_to_string(&mut contents)?;
-            let config = toml::from_str::<Self>(contents.as_str())?;
-            return Ok(config);
+        let path = path.as_ref();
+
+        if path.exists() {
+            let contents =
+                fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+            return Self::parse(&contents).with_context(|| format!("invalid config at {path:?}"));
         }
 
         info!("configuration file not found, using defaults");
 
         // make required folders
-        if let Some(parent) = path.as_ref().parent() {
-            if let Err(e) = std::fs::create_dir_all(parent) {
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
                 // this might happen on a read-only filesystem (i.e.,
                 // when running on a CI, profiling in Instruments, etc.)
-                warn!(
-                    "failed to create parent directories for {:?}: {}, using defaults",
-                    path.as_ref(),
-                    e
-                );
+                warn!("failed to create parent directories for {path:?}: {e}, using defaults");
                 return Ok(Self::default());
             }
         };
 
-        // write default config to file
-        let default_config = Self::default();
-        std::fs::write(&path, toml::to_string(&default_config)?.as_bytes())?;
+        fs::write(path, DEFAULT_CONFIG)
+            .with_context(|| format!("failed to write the default config to {path:?}"))?;
 
-        info!("wrote default configuration to {:?}", path.as_ref());
+        info!("wrote default configuration to {path:?}");
 
         Ok(Self::default())
     }
+
+    /// Loads the configuration at [`CONFIG_PATH_VAR`], or at [`DEFAULT_CONFIG_PATH`] if it is
+    /// not set. A configuration left at [`LEGACY_CONFIG_PATH`] is used instead of writing a new
+    /// one.
+    pub fn load_default() -> anyhow::Result<Self> {
+        let path = std::env::var_os(CONFIG_PATH_VAR)
+            .map_or_else(|| default_path(Path::new(".")), PathBuf::from);
+
+        Self::load(path)
+    }
+
+    /// Parses and validates a configuration. Unknown keys are ignored with a warning.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let table: toml::Table = toml::from_str(contents)?;
+
+        for key in unknown_keys(&table) {
+            warn!("ignoring unknown configuration key `{key}`");
+        }
+
+        let config: Self = toml::from_str(contents)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the values that have the right type but make no sense.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+
+        if split_host_port(&self.proxy_address).is_none() {
+            problems.push(format!(
+                "`proxy_address` must be a host and port like \"0.0.0.0:35565\" or \
+                 \"proxy.local:35565\", but is \"{}\"",
+                self.proxy_address
+            ));
+        }
+
+        if let Some(path) = &self.world_path
+            && !path.exists()
+        {
+            problems.push(format!("`world_path` is {path:?}, which does not exist"));
+        }
+
+        if !(0..=12).contains(&self.compression_level) {
+            problems.push(format!(
+                "`compression_level` must be between 0 and 12, but is {}",
+                self.compression_level
+            ));
+        }
+
+        if self.border_diameter.is_some_and(|diameter| diameter <= 0.0) {
+            problems.push("`border_diameter` must be positive".to_owned());
+        }
+
+        if self.max_players < 1 {
+            problems.push(format!(
+                "`max_players` must be at least 1, but is {}",
+                self.max_players
+            ));
+        }
+
+        for (key, distance) in [
+            ("view_distance", self.view_distance),
+            ("simulation_distance", self.simulation_distance),
+        ] {
+            if !(2..=32).contains(&distance) {
+                problems.push(format!(
+                    "`{key}` must be between 2 and 32, but is {distance}"
+                ));
+            }
+        }
+
+        if self.spawn.radius < 0 {
+            problems.push(format!(
+                "`spawn.radius` must not be negative, but is {}",
+                self.spawn.radius
+            ));
+        }
+
+        if self.slow_tick_ms == 0 {
+            problems.push("`slow_tick_ms` must be at least 1".to_owned());
+        }
+
+        if self.shutdown_timeout_secs == 0 {
+            problems.push("`shutdown_timeout_secs` must be at least 1".to_owned());
+        }
+
+        if !problems.is_empty() {
+            bail!(problems.join("\n"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the configuration of a server running in `dir` is, see [`ServerConfig::load_default`].
+fn default_path(dir: &Path) -> PathBuf {
+    let path = dir.join(DEFAULT_CONFIG_PATH);
+    let legacy = dir.join(LEGACY_CONFIG_PATH);
+
+    if !path.exists() && legacy.exists() {
+        warn!(
+            "no configuration at {DEFAULT_CONFIG_PATH}, using the one at {LEGACY_CONFIG_PATH}. \
+             Move it to {DEFAULT_CONFIG_PATH} to keep using it"
+        );
+        return legacy;
+    }
+
+    path
+}
+
+/// Splits an address like `0.0.0.0:35565`, `[::1]:35565` or `proxy.local:35565` into its host
+/// and port. Host names are only resolved when the server starts listening.
+#[must_use]
+pub fn split_host_port(address: &str) -> Option<(&str, u16)> {
+    let (host, port) = address.rsplit_once(':')?;
+
+    if host.is_empty() {
+        return None;
+    }
+
+    Some((host, port.parse().ok()?))
+}
+
+/// The keys in `table` that are not configuration keys, like `spawn.radiu`, in order.
+fn unknown_keys(table: &toml::Table) -> Vec<String> {
+    // serializing cannot fail, as the configuration only holds plain values
+    let toml::Value::Table(mut known) = toml::Value::try_from(ServerConfig::default()).unwrap()
+    else {
+        unreachable!("the configuration is a table");
+    };
+
+    for key in OPTIONAL_KEYS {
+        known.insert(key.to_owned(), toml::Value::Boolean(false));
+    }
+
+    let mut unknown = Vec::new();
+    collect_unknown_keys(table, &known, "", &mut unknown);
+
+    unknown.sort_unstable();
+    unknown
+}
+
+fn collect_unknown_keys(
+    table: &toml::Table,
+    known: &toml::Table,
+    prefix: &str,
+    unknown: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let path = format!("{prefix}{key}");
+
+        match (value, known.get(key)) {
+            (toml::Value::Table(table), Some(toml::Value::Table(known))) => {
+                collect_unknown_keys(table, known, &format!("{path}."), unknown);
+            }
+            (_, Some(_)) => {}
+            (_, None) => unknown.push(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DEFAULT_CONFIG, DEFAULT_CONFIG_PATH, LEGACY_CONFIG_PATH, Radius, ServerConfig,
+        default_path, split_host_port, unknown_keys,
+    };
+
+    #[test]
+    fn the_default_file_matches_the_defaults() {
+        assert_eq!(
+            ServerConfig::parse(DEFAULT_CONFIG).unwrap(),
+            ServerConfig::default()
+        );
+    }
+
+    #[test]
+    fn missing_keys_use_the_defaults() {
+        let config = ServerConfig::parse(
+            r#"
+            view_distance = 12
+            offline_mode = true
+
+            [spawn]
+            kind = "Euclidean"
+            "#,
+        )
+        .unwrap();
+
+        let default = ServerConfig::default();
+
+        assert_eq!(config.view_distance, 12);
+        assert!(config.offline_mode);
+        assert_eq!(config.spawn.kind, Radius::Euclidean);
+        assert_eq!(config.spawn.radius, default.spawn.radius);
+        assert_eq!(config.max_players, default.max_players);
+        assert_eq!(config.proxy_address, default.proxy_address);
+    }
+
+    #[test]
+    fn invalid_values_name_their_key() {
+        let wrong_type = ServerConfig::parse("view_distance = \"far\"").unwrap_err();
+        assert!(wrong_type.to_string().contains("view_distance"));
+
+        let out_of_range = ServerConfig::parse("view_distance = 64\nmax_players = 0").unwrap_err();
+        let message = out_of_range.to_string();
+
+        assert!(message.contains("`view_distance` must be between 2 and 32, but is 64"));
+        assert!(message.contains("`max_players` must be at least 1"));
+
+        let bad_address = ServerConfig::parse("proxy_address = \"somewhere\"").unwrap_err();
+        assert!(bad_address.to_string().contains("proxy_address"));
+    }
+
+    #[test]
+    fn proxy_addresses_can_be_host_names() {
+        let config = ServerConfig::parse("proxy_address = \"proxy.local:25577\"").unwrap();
+        assert_eq!(config.proxy_address, "proxy.local:25577");
+
+        assert_eq!(
+            split_host_port("proxy.local:25577"),
+            Some(("proxy.local", 25577))
+        );
+        assert_eq!(split_host_port("[::1]:35565"), Some(("[::1]", 35565)));
+        assert_eq!(split_host_port(":35565"), None);
+        assert_eq!(split_host_port("proxy.local:port"), None);
+    }
+
+    #[test]
+    fn the_old_config_is_used_until_it_is_moved() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            default_path(dir.path()),
+            dir.path().join(DEFAULT_CONFIG_PATH)
+        );
+
+        let legacy = dir.path().join(LEGACY_CONFIG_PATH);
+        std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        std::fs::write(&legacy, "view_distance = 12").unwrap();
+
+        assert_eq!(default_path(dir.path()), legacy);
+        assert_eq!(ServerConfig::load(&legacy).unwrap().view_distance, 12);
+
+        // once there is a new config, the old one is ignored
+        std::fs::write(dir.path().join(DEFAULT_CONFIG_PATH), "").unwrap();
+        assert_eq!(
+            default_path(dir.path()),
+            dir.path().join(DEFAULT_CONFIG_PATH)
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_found() {
+        let table = toml::from_str(
+            r#"
+            view_distanse = 12
+            world_path = "world"
+
+            [spawn]
+            radiu = 10
+            x = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(unknown_keys(&table), ["spawn.radiu", "view_distanse"]);
+    }
 }
@@ -0,0 +1,492 @@
+//! Player-facing text in the language each player has chosen.
+//!
+//! Messages are looked up by key, such as `shop.purchase.success`, in a catalog per locale. A
+//! catalog is a `toml` file whose nested tables make up the key:
+//!
+//! ```toml
+//! [shop.purchase]
+//! success = "§aBought for {cost} coins"
+//! ```
+//!
+//! A player's locale comes from the client settings they send after joining. Keys missing from
+//! their locale fall back to [`Localization::default_locale`], and keys missing there too are shown
+//! as they are, so a missing translation is noticeable but never fatal.
+//!
+//! The messages of the server itself, such as the ban screen, are built in. Games add their own
+//! with [`Localization::merge`].
+
+use std::{fmt::Display, fs, path::Path};
+
+use anyhow::Context;
+use flecs_ecs::prelude::*;
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{info, warn};
+
+use crate::{
+    net::{Compose, NetworkStreamRef, SendReport, agnostic},
+    system_registry::SystemId,
+};
+
+/// The locale of players whose client has not said which one it uses.
+pub const DEFAULT_LOCALE: &str = "en_us";
+
+/// The messages of the server itself, such as the ban screen, by locale.
+const BUILT_IN: [(&str, &str); 2] = [
+    ("en_us", include_str!("../../locales/en_us.toml")),
+    ("de_de", include_str!("../../locales/de_de.toml")),
+];
+
+/// A value substituted into a message for `{name}`.
+pub type Arg<'a> = (&'a str, &'a dyn Display);
+
+/// The locale a player's client is set to, such as `en_us`.
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct Locale(pub String);
+
+/// Messages by key for one locale.
+#[derive(Debug, Default)]
+struct Catalog {
+    messages: FxHashMap<String, String>,
+}
+
+impl Catalog {
+    /// Adds every string in `table` under `prefix`, replacing messages with the same key.
+    fn extend(&mut self, prefix: &str, table: toml::Table) -> anyhow::Result<()> {
+        for (name, value) in table {
+            let key = if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}.{name}")
+            };
+
+            match value {
+                toml::Value::String(message) => {
+                    self.messages.insert(key, message);
+                }
+                toml::Value::Table(table) => self.extend(&key, table)?,
+                other => anyhow::bail!("`{key}` must be a string, but is a {}", other.type_str()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Every catalog, loaded at startup.
+#[derive(Component, Debug)]
+pub struct Localization {
+    default_locale: String,
+    catalogs: FxHashMap<String, Catalog>,
+    /// Locales and keys that were reported missing, so each is only logged once.
+    reported: Mutex<FxHashSet<(String, String)>>,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCALE)
+    }
+}
+
+impl Localization {
+    #[must_use]
+    pub fn new(default_locale: &str) -> Self {
+        Self {
+            default_locale: default_locale.to_lowercase(),
+            catalogs: FxHashMap::default(),
+            reported: Mutex::default(),
+        }
+    }
+
+    /// The messages of the server itself, which games add theirs to with [`Self::merge`].
+    pub fn built_in() -> anyhow::Result<Self> {
+        let mut l10n = Self::default();
+
+        for (locale, source) in BUILT_IN {
+            l10n.add_toml(locale, source)?;
+        }
+
+        Ok(l10n)
+    }
+
+    #[must_use]
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    /// Whether there are any messages for `locale`.
+    #[must_use]
+    pub fn has_locale(&self, locale: &str) -> bool {
+        self.catalogs.contains_key(&locale.to_lowercase())
+    }
+
+    /// Adds the messages in the `toml` `source` to `locale`, replacing those with the same key.
+    pub fn add_toml(&mut self, locale: &str, source: &str) -> anyhow::Result<()> {
+        let table: toml::Table = toml::from_str(source)?;
+
+        self.catalogs
+            .entry(locale.to_lowercase())
+            .or_default()
+            .extend("", table)
+            .with_context(|| format!("invalid messages for `{locale}`"))
+    }
+
+    /// Adds the messages of `other`, replacing those with the same key, such as to add the
+    /// messages of a game to those of the server. The default locale stays the same.
+    pub fn merge(&mut self, other: Self) {
+        for (locale, catalog) in other.catalogs {
+            self.catalogs
+                .entry(locale)
+                .or_default()
+                .messages
+                .extend(catalog.messages);
+        }
+    }
+
+    /// Adds every `<locale>.toml` file in `dir`, such as `de_de.toml`. A missing directory is not
+    /// an error, as it only holds translations on top of the built-in ones.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().is_none_or(|extension| extension != "toml") {
+                continue;
+            }
+
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let source = fs::read_to_string(&path)?;
+            self.add_toml(locale, &source)
+                .with_context(|| format!("failed to load {}", path.display()))?;
+
+            info!("loaded messages for {locale} from {}", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// The message for `key` in `locale`, before any arguments are substituted.
+    #[must_use]
+    pub fn message<'a>(&'a self, locale: &str, key: &'a str) -> &'a str {
+        let locale = locale.to_lowercase();
+
+        let find = |locale: &str| {
+            self.catalogs
+                .get(locale)
+                .and_then(|catalog| catalog.messages.get(key))
+        };
+
+        if let Some(message) = find(&locale) {
+            return message;
+        }
+
+        let fallback = find(&self.default_locale);
+
+        // a locale nobody translated anything to is not worth a warning for every key
+        if locale != self.default_locale && self.catalogs.contains_key(&locale) {
+            self.report(&locale, key);
+        }
+
+        fallback.map_or_else(
+            || {
+                self.report(&self.default_locale, key);
+                key
+            },
+            String::as_str,
+        )
+    }
+
+    /// The message for `key` in `locale` with `args` substituted.
+    #[must_use]
+    pub fn translate(&self, locale: &str, key: &str, args: &[Arg<'_>]) -> String {
+        substitute(self.message(locale, key), args)
+    }
+
+    fn report(&self, locale: &str, key: &str) {
+        let new = self
+            .reported
+            .lock()
+            .insert((locale.to_owned(), key.to_owned()));
+
+        if new {
+            warn!("the message `{key}` is missing for {locale}");
+        }
+    }
+}
+
+/// Replaces every `{name}` in `message` with the matching argument. Placeholders without an
+/// argument are left as they are.
+fn substitute(message: &str, args: &[Arg<'_>]) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+
+        let after = &rest[start + 1..];
+        let arg = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((end, value))
+        });
+
+        match arg {
+            Some((end, value)) => {
+                out.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Runs `f` with the messages and the locale of `player`, such as to translate several messages
+/// at once.
+pub fn with_locale<R>(player: EntityView<'_>, f: impl FnOnce(&Localization, &str) -> R) -> R {
+    let locale = player.try_get::<&Locale>(|locale| locale.0.clone());
+
+    player.world().get::<&Localization>(|l10n| {
+        let locale = locale.as_deref().unwrap_or_else(|| l10n.default_locale());
+        f(l10n, locale)
+    })
+}
+
+/// The message for `key` in the locale of `player`.
+#[must_use]
+pub fn translate(player: EntityView<'_>, key: &str, args: &[Arg<'_>]) -> String {
+    with_locale(player, |l10n, locale| l10n.translate(locale, key, args))
+}
+
+/// The message for `key` in the default locale, for text every player is shown the same, such as
+/// a boss bar shown to everyone.
+#[must_use]
+pub fn translate_default(world: &World, key: &str, args: &[Arg<'_>]) -> String {
+    world.get::<&Localization>(|l10n| l10n.translate(l10n.default_locale(), key, args))
+}
+
+/// Remembers the locale the client of `player` says it uses.
+pub fn update_locale(player: EntityView<'_>, locale: &str) {
+    let locale = locale.to_lowercase();
+
+    let changed = player
+        .try_get::<&Locale>(|current| current.0 != locale)
+        .unwrap_or(true);
+
+    if changed {
+        player.set(Locale(locale));
+    }
+}
+
+/// The message for a key in the locale of a player, with arguments substituted.
+///
+/// ```ignore
+/// let text = msg!(player, "shop.purchase.success", cost = 5);
+/// ```
+#[macro_export]
+macro_rules! msg {
+    ($player:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::l10n::translate(
+            $player,
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+}
+
+impl Compose {
+    /// Sends `player` the message for `key` in their locale.
+    pub fn chat_l10n(
+        &self,
+        player: EntityView<'_>,
+        key: &str,
+        args: &[Arg<'_>],
+        system_id: SystemId,
+    ) -> anyhow::Result<SendReport> {
+        let stream = player
+            .try_get::<&NetworkStreamRef>(|stream| *stream)
+            .context("only players can be sent messages")?;
+
+        let chat = agnostic::chat(translate(player, key, args));
+
+        self.unicast(&chat, stream, system_id, &player.world())
+    }
+}
+
+#[derive(Component)]
+pub struct LocalizationModule;
+
+impl Module for LocalizationModule {
+    fn module(world: &World) {
+        world.component::<Locale>();
+        world.component::<Localization>();
+
+        let l10n = Localization::built_in().unwrap_or_else(|e| {
+            warn!("failed to load the built-in messages: {e:#}");
+            Localization::default()
+        });
+
+        world.set(l10n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+
+    use super::{Localization, LocalizationModule, substitute, translate, update_locale};
+
+    const EN_US: &str = r#"
+        greeting = "Hello, {player}!"
+
+        [shop.purchase]
+        success = "Bought for {cost} coins"
+        broke = "You need {cost} coins"
+    "#;
+
+    const DE_DE: &str = r#"
+        greeting = "Hallo, {player}!"
+
+        [shop.purchase]
+        success = "Für {cost} Münzen gekauft"
+    "#;
+
+    fn localization() -> Localization {
+        let mut l10n = Localization::new("en_US");
+        l10n.add_toml("en_us", EN_US).unwrap();
+        l10n.add_toml("de_de", DE_DE).unwrap();
+        l10n
+    }
+
+    #[test]
+    fn each_locale_has_its_own_messages() {
+        let l10n = localization();
+
+        assert_eq!(
+            l10n.translate("en_us", "shop.purchase.success", &[("cost", &5)]),
+            "Bought for 5 coins"
+        );
+        assert_eq!(
+            l10n.translate("de_DE", "shop.purchase.success", &[("cost", &5)]),
+            "Für 5 Münzen gekauft"
+        );
+        assert_eq!(
+            l10n.translate("de_de", "greeting", &[("player", &"Notch")]),
+            "Hallo, Notch!"
+        );
+    }
+
+    #[test]
+    fn missing_messages_fall_back() {
+        let l10n = localization();
+
+        // not translated to german yet
+        assert_eq!(
+            l10n.translate("de_de", "shop.purchase.broke", &[("cost", &10)]),
+            "You need 10 coins"
+        );
+
+        // nothing is translated to french
+        assert_eq!(l10n.message("fr_fr", "greeting"), "Hello, {player}!");
+
+        // not even in the default locale
+        assert_eq!(l10n.message("de_de", "shop.refund"), "shop.refund");
+
+        // each missing message is reported once
+        assert_eq!(l10n.reported.lock().len(), 3);
+        assert_eq!(l10n.message("de_de", "shop.refund"), "shop.refund");
+        assert_eq!(l10n.reported.lock().len(), 3);
+    }
+
+    #[test]
+    fn players_read_messages_in_their_locale() {
+        let world = World::new();
+        world.import::<LocalizationModule>();
+        world.set(localization());
+
+        let german = world.entity();
+        update_locale(german, "de_DE");
+
+        // has not sent their client settings yet
+        let unknown = world.entity();
+
+        let greet = |player| translate(player, "greeting", &[("player", &"Alex")]);
+
+        assert_eq!(greet(german), "Hallo, Alex!");
+        assert_eq!(greet(unknown), "Hello, Alex!");
+
+        update_locale(german, "en_us");
+        assert_eq!(greet(german), "Hello, Alex!");
+        assert_eq!(
+            crate::msg!(german, "shop.purchase.success", cost = 5),
+            "Bought for 5 coins"
+        );
+    }
+
+    #[test]
+    fn built_in_messages_load() {
+        let l10n = Localization::built_in().unwrap();
+
+        assert_eq!(
+            l10n.translate("de_de", "ban.screen.permanent", &[]),
+            "§7Dein Bann ist dauerhaft"
+        );
+        assert!(l10n.reported.lock().is_empty());
+    }
+
+    #[test]
+    fn later_catalogs_replace_messages() {
+        let mut l10n = localization();
+        l10n.add_toml("en_us", "greeting = \"Hi\"").unwrap();
+
+        assert_eq!(l10n.message("en_us", "greeting"), "Hi");
+        assert_eq!(
+            l10n.message("en_us", "shop.purchase.success"),
+            "Bought for {cost} coins"
+        );
+
+        assert!(l10n.add_toml("en_us", "greeting = 5").is_err());
+    }
+
+    #[test]
+    fn merged_messages_are_added_to_the_existing_ones() {
+        let mut l10n = localization();
+
+        let mut game = Localization::new("de_de");
+        game.add_toml("en_us", "greeting = \"Welcome\"\nfarewell = \"Bye\"")
+            .unwrap();
+        game.add_toml("fr_fr", "farewell = \"Au revoir\"").unwrap();
+
+        l10n.merge(game);
+
+        assert_eq!(l10n.default_locale(), "en_us");
+        assert_eq!(l10n.message("en_us", "greeting"), "Welcome");
+        assert_eq!(l10n.message("en_us", "farewell"), "Bye");
+        assert_eq!(
+            l10n.message("en_us", "shop.purchase.success"),
+            "Bought for {cost} coins"
+        );
+        assert_eq!(l10n.message("de_de", "greeting"), "Hallo, {player}!");
+        assert_eq!(l10n.message("fr_fr", "farewell"), "Au revoir");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        assert_eq!(substitute("{a} and {b}", &[("a", &1)]), "1 and {b}");
+        assert_eq!(substitute("{unclosed", &[("unclosed", &1)]), "{unclosed");
+        assert_eq!(substitute("{}{a}{", &[("a", &"x")]), "{}x{");
+    }
+}
@@ -0,0 +1,533 @@
+//! Server metrics in the Prometheus text format, enabled with the `metrics` feature.
+//!
+//! Counters are pre-registered atomics in [`METRICS`], so recording an event costs a relaxed add.
+//! Gauges are sampled once per tick by [`MetricsModule`], and nothing is formatted until the
+//! endpoint started with [`serve`] is scraped. System timings come from [`PROFILER`] while it is
+//! enabled.
+
+use std::{
+    fmt::{self, Write as _},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use flecs_ecs::prelude::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::warn;
+use valence_protocol::{Packet, packets::play};
+
+use crate::{
+    net::Compose,
+    profiler::{PROFILER, SystemTiming},
+    simulation::{Position, blocks::Blocks},
+    util::mojang::{CacheStats, MojangClient},
+};
+
+/// The metrics of this server.
+pub static METRICS: Metrics = Metrics::new();
+
+/// The upper bounds of the tick duration buckets, in microseconds and as rendered in seconds.
+const TICK_BUCKETS: [(u64, &str); 8] = [
+    (1_000, "0.001"),
+    (2_500, "0.0025"),
+    (5_000, "0.005"),
+    (10_000, "0.01"),
+    (25_000, "0.025"),
+    (50_000, "0.05"),
+    (100_000, "0.1"),
+    (250_000, "0.25"),
+];
+
+/// Packets are counted by ID. Every serverbound play packet ID is below this.
+const PACKET_IDS: usize = 64;
+
+/// The packets the server handles, which are always exported by name. Other packets are only
+/// exported once received, by ID.
+const NAMED_PACKETS: [(i32, &str); 20] = [
+    (play::ChatMessageC2s::ID, play::ChatMessageC2s::NAME),
+    (play::ClickSlotC2s::ID, play::ClickSlotC2s::NAME),
+    (play::ClientCommandC2s::ID, play::ClientCommandC2s::NAME),
+    (
+        play::CloseHandledScreenC2s::ID,
+        play::CloseHandledScreenC2s::NAME,
+    ),
+    (
+        play::CommandExecutionC2s::ID,
+        play::CommandExecutionC2s::NAME,
+    ),
+    (
+        play::CreativeInventoryActionC2s::ID,
+        play::CreativeInventoryActionC2s::NAME,
+    ),
+    (play::CustomPayloadC2s::ID, play::CustomPayloadC2s::NAME),
+    (play::FullC2s::ID, play::FullC2s::NAME),
+    (play::HandSwingC2s::ID, play::HandSwingC2s::NAME),
+    (play::LookAndOnGroundC2s::ID, play::LookAndOnGroundC2s::NAME),
+    (play::PlayerActionC2s::ID, play::PlayerActionC2s::NAME),
+    (
+        play::PlayerInteractBlockC2s::ID,
+        play::PlayerInteractBlockC2s::NAME,
+    ),
+    (
+        play::PlayerInteractEntityC2s::ID,
+        play::PlayerInteractEntityC2s::NAME,
+    ),
+    (
+        play::PlayerInteractItemC2s::ID,
+        play::PlayerInteractItemC2s::NAME,
+    ),
+    (
+        play::PositionAndOnGroundC2s::ID,
+        play::PositionAndOnGroundC2s::NAME,
+    ),
+    (
+        play::RecipeCategoryOptionsC2s::ID,
+        play::RecipeCategoryOptionsC2s::NAME,
+    ),
+    (play::RenameItemC2s::ID, play::RenameItemC2s::NAME),
+    (
+        play::RequestCommandCompletionsC2s::ID,
+        play::RequestCommandCompletionsC2s::NAME,
+    ),
+    (play::TeleportConfirmC2s::ID, play::TeleportConfirmC2s::NAME),
+    (
+        play::UpdateSelectedSlotC2s::ID,
+        play::UpdateSelectedSlotC2s::NAME,
+    ),
+];
+
+/// Every metric the server exports.
+pub struct Metrics {
+    /// Ticks by the first bucket they fit in. Ticks longer than every bucket are only counted in
+    /// `ticks`.
+    tick_buckets: [AtomicU64; TICK_BUCKETS.len()],
+    ticks: AtomicU64,
+    tick_micros: AtomicU64,
+
+    entities: AtomicUsize,
+    players: AtomicUsize,
+    loaded_chunks: AtomicUsize,
+
+    unicast_bytes: AtomicU64,
+    multicast_bytes: AtomicU64,
+    broadcast_bytes: AtomicU64,
+    broadcast_local_bytes: AtomicU64,
+
+    packets: [AtomicU64; PACKET_IDS],
+    /// Packets with an ID of at least [`PACKET_IDS`].
+    other_packets: AtomicU64,
+
+    commands: AtomicU64,
+
+    mojang_cache_hits: AtomicU64,
+    mojang_cache_misses: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tick_buckets: [const { AtomicU64::new(0) }; TICK_BUCKETS.len()],
+            ticks: AtomicU64::new(0),
+            tick_micros: AtomicU64::new(0),
+            entities: AtomicUsize::new(0),
+            players: AtomicUsize::new(0),
+            loaded_chunks: AtomicUsize::new(0),
+            unicast_bytes: AtomicU64::new(0),
+            multicast_bytes: AtomicU64::new(0),
+            broadcast_bytes: AtomicU64::new(0),
+            broadcast_local_bytes: AtomicU64::new(0),
+            packets: [const { AtomicU64::new(0) }; PACKET_IDS],
+            other_packets: AtomicU64::new(0),
+            commands: AtomicU64::new(0),
+            mojang_cache_hits: AtomicU64::new(0),
+            mojang_cache_misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe_tick(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+
+        let bucket = TICK_BUCKETS.iter().position(|&(bound, _)| micros <= bound);
+
+        if let Some(bucket) = bucket {
+            self.tick_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    pub fn record_packet(&self, id: i32) {
+        let counter = usize::try_from(id)
+            .ok()
+            .and_then(|id| self.packets.get(id))
+            .unwrap_or(&self.other_packets);
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_command(&self) {
+        self.commands.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records how often the profile cache of the [`MojangClient`] answered lookups so far.
+    pub fn record_mojang_cache(&self, stats: CacheStats) {
+        self.mojang_cache_hits.store(stats.hits, Ordering::Relaxed);
+        self.mojang_cache_misses
+            .store(stats.misses, Ordering::Relaxed);
+    }
+
+    /// The metrics in the Prometheus text format.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        // writing to a string cannot fail
+        self.write(&mut out).unwrap();
+
+        out
+    }
+
+    fn write(&self, out: &mut String) -> fmt::Result {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        header(
+            out,
+            "hyperion_tick_duration_seconds",
+            "histogram",
+            "Time spent simulating a tick",
+        )?;
+
+        let mut cumulative = 0;
+
+        for (&(_, le), count) in TICK_BUCKETS.iter().zip(&self.tick_buckets) {
+            cumulative += load(count);
+            writeln!(
+                out,
+                "hyperion_tick_duration_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            )?;
+        }
+
+        let ticks = load(&self.ticks);
+        let seconds = load(&self.tick_micros) as f64 / 1_000_000.0;
+
+        writeln!(
+            out,
+            "hyperion_tick_duration_seconds_bucket{{le=\"+Inf\"}} {ticks}"
+        )?;
+        writeln!(out, "hyperion_tick_duration_seconds_sum {seconds}")?;
+        writeln!(out, "hyperion_tick_duration_seconds_count {ticks}")?;
+
+        let gauges = [
+            (
+                "hyperion_entities",
+                "Entities with a position",
+                &self.entities,
+            ),
+            ("hyperion_players", "Players in play", &self.players),
+            (
+                "hyperion_loaded_chunks",
+                "Chunks in memory",
+                &self.loaded_chunks,
+            ),
+        ];
+
+        for (name, help, gauge) in gauges {
+            header(out, name, "gauge", help)?;
+            writeln!(out, "{name} {}", gauge.load(Ordering::Relaxed))?;
+        }
+
+        header(
+            out,
+            "hyperion_sent_bytes_total",
+            "counter",
+            "Bytes queued for the proxy",
+        )?;
+
+        let sent = [
+            ("unicast", &self.unicast_bytes),
+            ("multicast", &self.multicast_bytes),
+            ("broadcast", &self.broadcast_bytes),
+            ("broadcast_local", &self.broadcast_local_bytes),
+        ];
+
+        for (kind, bytes) in sent {
+            writeln!(
+                out,
+                "hyperion_sent_bytes_total{{kind=\"{kind}\"}} {}",
+                load(bytes)
+            )?;
+        }
+
+        header(
+            out,
+            "hyperion_received_packets_total",
+            "counter",
+            "Play packets received",
+        )?;
+
+        for (id, count) in self.packets.iter().enumerate() {
+            let count = load(count);
+
+            let name = NAMED_PACKETS
+                .iter()
+                .find(|&&(named, _)| usize::try_from(named) == Ok(id))
+                .map(|&(_, name)| name);
+
+            match name {
+                Some(name) => {
+                    writeln!(
+                        out,
+                        "hyperion_received_packets_total{{packet=\"{name}\"}} {count}"
+                    )?;
+                }
+                None if count > 0 => {
+                    let id = format!("{id:#04x}");
+                    writeln!(
+                        out,
+                        "hyperion_received_packets_total{{packet=\"{id}\"}} {count}"
+                    )?;
+                }
+                None => {}
+            }
+        }
+
+        let other = load(&self.other_packets);
+
+        if other > 0 {
+            writeln!(
+                out,
+                "hyperion_received_packets_total{{packet=\"other\"}} {other}"
+            )?;
+        }
+
+        // empty unless the profiler is enabled
+        let report = PROFILER.report();
+
+        let stats: [(&str, &str, fn(&SystemTiming) -> Duration); 2] = [
+            (
+                "hyperion_system_average_seconds",
+                "Average time per tick of each system",
+                |system| system.average,
+            ),
+            (
+                "hyperion_system_max_seconds",
+                "Longest time in a tick of each system",
+                |system| system.max,
+            ),
+        ];
+
+        for (name, help, stat) in stats {
+            header(out, name, "gauge", help)?;
+
+            for system in &report.systems {
+                let seconds = stat(system).as_secs_f64();
+                writeln!(out, "{name}{{system=\"{}\"}} {seconds}", system.name)?;
+            }
+        }
+
+        header(
+            out,
+            "hyperion_commands_total",
+            "counter",
+            "Commands run by players",
+        )?;
+        writeln!(out, "hyperion_commands_total {}", load(&self.commands))?;
+
+        let hits = load(&self.mojang_cache_hits);
+        let misses = load(&self.mojang_cache_misses);
+
+        let lookups = [("hit", hits), ("miss", misses)];
+
+        header(
+            out,
+            "hyperion_mojang_cache_lookups_total",
+            "counter",
+            "Profile lookups by whether the Mojang client had them cached",
+        )?;
+
+        for (result, count) in lookups {
+            writeln!(
+                out,
+                "hyperion_mojang_cache_lookups_total{{result=\"{result}\"}} {count}"
+            )?;
+        }
+
+        // there is no hit rate before the first lookup
+        let hit_rate = if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 / (hits + misses) as f64
+        };
+
+        header(
+            out,
+            "hyperion_mojang_cache_hit_rate",
+            "gauge",
+            "Share of profile lookups answered from the Mojang client cache",
+        )?;
+        writeln!(out, "hyperion_mojang_cache_hit_rate {hit_rate}")
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+/// Answers every request on `listener` with [`METRICS`].
+pub async fn serve(listener: TcpListener) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("failed to accept a metrics connection: {e}");
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream).await {
+                warn!("failed to answer a metrics request: {e}");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    // only the request line matters, which always fits in the first read
+    let mut request = [0; 1024];
+    let len = stream.read(&mut request).await?;
+    let request = &request[..len];
+
+    let response = if request.starts_with(b"GET /metrics ") || request.starts_with(b"GET / ") {
+        let body = METRICS.render();
+
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+             {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Samples the gauges of [`METRICS`] every tick. Ticks are timed by
+/// [`crate::profiler::ProfilerModule`].
+#[derive(Component)]
+pub struct MetricsModule;
+
+impl Module for MetricsModule {
+    fn module(world: &World) {
+        let entities = world.query::<()>().with::<&Position>().build();
+
+        system!("record_server_metrics", world, &Compose($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(move |_, _, compose| {
+                let entities = usize::try_from(entities.count()).unwrap_or_default();
+                METRICS.entities.store(entities, Ordering::Relaxed);
+
+                let players = compose.global().player_count.load(Ordering::Relaxed);
+                METRICS.players.store(players, Ordering::Relaxed);
+
+                let sent = compose.io_buf().stats();
+                METRICS
+                    .unicast_bytes
+                    .store(sent.unicast.bytes, Ordering::Relaxed);
+                METRICS
+                    .multicast_bytes
+                    .store(sent.multicast.bytes, Ordering::Relaxed);
+                METRICS
+                    .broadcast_bytes
+                    .store(sent.broadcast.bytes, Ordering::Relaxed);
+                METRICS
+                    .broadcast_local_bytes
+                    .store(sent.broadcast_local.bytes, Ordering::Relaxed);
+            });
+
+        system!("record_mojang_metrics", world, &MojangClient($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(|_, _, mojang| {
+                METRICS.record_mojang_cache(mojang.cache_stats());
+            });
+
+        system!("record_chunk_metrics", world, &Blocks($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(|_, _, blocks| {
+                METRICS
+                    .loaded_chunks
+                    .store(blocks.loaded_chunk_count(), Ordering::Relaxed);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use valence_protocol::{Packet, packets::play};
+
+    use super::Metrics;
+    use crate::util::mojang::CacheStats;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::new();
+
+        metrics.observe_tick(Duration::from_millis(3));
+        metrics.observe_tick(Duration::from_millis(40));
+        metrics.observe_tick(Duration::from_secs(1));
+
+        let out = metrics.render();
+
+        assert!(out.contains("hyperion_tick_duration_seconds_bucket{le=\"0.0025\"} 0\n"));
+        assert!(out.contains("hyperion_tick_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(out.contains("hyperion_tick_duration_seconds_bucket{le=\"0.05\"} 2\n"));
+        assert!(out.contains("hyperion_tick_duration_seconds_bucket{le=\"0.25\"} 2\n"));
+        assert!(out.contains("hyperion_tick_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("hyperion_tick_duration_seconds_sum 1.043\n"));
+    }
+
+    #[test]
+    fn packets_are_counted_by_name() {
+        let metrics = Metrics::new();
+
+        metrics.record_packet(play::FullC2s::ID);
+        metrics.record_packet(play::FullC2s::ID);
+        metrics.record_packet(1000);
+
+        let out = metrics.render();
+
+        assert!(out.contains(&format!(
+            "hyperion_received_packets_total{{packet=\"{}\"}} 2\n",
+            play::FullC2s::NAME
+        )));
+        assert!(out.contains("hyperion_received_packets_total{packet=\"other\"} 1\n"));
+
+        metrics.record_mojang_cache(CacheStats {
+            hits: 3,
+            misses: 1,
+            size: 2,
+        });
+
+        assert!(
+            metrics
+                .render()
+                .contains("hyperion_mojang_cache_hit_rate 0.75\n")
+        );
+    }
+}
@@ -8,10 +8,20 @@ use flecs_ecs::macros::Component;
 use libdeflater::CompressionLvl;
 use valence_protocol::CompressionThreshold;
 
+use crate::capacity::PlayerCapacity;
+
+pub mod ban;
+pub mod capacity;
 pub mod config;
+pub mod l10n;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod profiler;
 pub mod runtime;
+pub mod shutdown;
 pub mod system_registry;
 pub mod util;
+pub mod whitelist;
 
 /// Shared data that is shared between the ECS framework and the IO thread.
 pub struct Shared {
@@ -42,12 +52,15 @@ pub struct Global {
     pub ms_last_tick: f32,
 
     pub player_count: AtomicUsize,
+
+    /// The player slots, which are taken as soon as a login is accepted.
+    pub capacity: PlayerCapacity,
 }
 
 impl Global {
     /// Creates a new [`Global`] with the given shared data.
     #[must_use]
-    pub const fn new(shared: Arc<Shared>) -> Self {
+    pub const fn new(shared: Arc<Shared>, capacity: PlayerCapacity) -> Self {
         Self {
             tick: 0,
             max_hurt_resistant_time: 20, // actually kinda like 10 vanilla mc is weird
@@ -55,6 +68,7 @@ impl Global {
             keep_alive_timeout: Duration::from_secs(20),
             ms_last_tick: 0.0,
             player_count: AtomicUsize::new(0),
+            capacity,
         }
     }
 }
@@ -0,0 +1,356 @@
+//! See [`TickProfiler`].
+
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    fmt::Write as _,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use flecs_ecs::prelude::*;
+use parking_lot::{Mutex, const_mutex};
+use tracing::warn;
+
+/// The profiler of this server.
+pub static PROFILER: TickProfiler = TickProfiler::new();
+
+/// How many ticks [`TickProfiler::report`] covers, which is five seconds at 20 ticks per second.
+const WINDOW: usize = 100;
+
+/// How many systems a slow tick warning lists.
+const TOP_OFFENDERS: usize = 5;
+
+/// Times ticks and, while enabled, the systems that run in them.
+///
+/// Systems built with [`crate::util::TracingExt`] are timed automatically. Other systems time
+/// themselves with [`Self::time`]. While profiling is disabled, timing a system costs a single
+/// relaxed load.
+pub struct TickProfiler {
+    enabled: AtomicBool,
+    slow_tick_micros: AtomicU64,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// The time spent in each system so far this tick.
+    current: Vec<(&'static str, Duration)>,
+    /// The most recent ticks, oldest first.
+    window: VecDeque<TickTimings>,
+    last_slow_tick: Option<TickTimings>,
+}
+
+/// How long a tick took and how long each timed system took in it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TickTimings {
+    pub duration: Duration,
+    /// The timed systems, slowest first. Empty while profiling is disabled.
+    pub systems: Vec<(&'static str, Duration)>,
+}
+
+/// How long a system took per tick over the recent ticks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SystemTiming {
+    pub name: &'static str,
+    pub average: Duration,
+    pub max: Duration,
+}
+
+/// The recent ticks, as summarized by [`TickProfiler::report`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TickReport {
+    pub ticks: usize,
+    pub average: Duration,
+    pub max: Duration,
+    /// The timed systems, slowest on average first.
+    pub systems: Vec<SystemTiming>,
+}
+
+/// Records how long a system took when dropped. See [`TickProfiler::time`].
+#[must_use]
+pub struct SystemTimer<'a> {
+    profiler: &'a TickProfiler,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for SystemTimer<'_> {
+    fn drop(&mut self) {
+        self.profiler.record(self.name, self.start.elapsed());
+    }
+}
+
+impl Default for TickProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TickProfiler {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            slow_tick_micros: AtomicU64::new(50_000),
+            state: const_mutex(State {
+                current: Vec::new(),
+                window: VecDeque::new(),
+                last_slow_tick: None,
+            }),
+        }
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turns system timing on or off. Tick durations are always recorded.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+
+        if !enabled {
+            self.state.lock().current.clear();
+        }
+    }
+
+    #[must_use]
+    pub fn slow_tick_threshold(&self) -> Duration {
+        Duration::from_micros(self.slow_tick_micros.load(Ordering::Relaxed))
+    }
+
+    /// Sets how long a tick may take before it is reported as slow.
+    pub fn set_slow_tick_threshold(&self, threshold: Duration) {
+        let micros = u64::try_from(threshold.as_micros()).unwrap_or(u64::MAX);
+        self.slow_tick_micros.store(micros, Ordering::Relaxed);
+    }
+
+    /// Times the system called `name` until the returned timer is dropped, or returns `None` if
+    /// profiling is disabled.
+    pub fn time(&self, name: &'static str) -> Option<SystemTimer<'_>> {
+        self.is_enabled().then(|| SystemTimer {
+            profiler: self,
+            name,
+            start: Instant::now(),
+        })
+    }
+
+    /// Times a flecs system by the name of the system entity, which is looked up once and cached
+    /// in `name`.
+    pub(crate) fn time_system(
+        &self,
+        system: impl FnOnce() -> String,
+        name: &OnceLock<&'static str>,
+    ) -> Option<SystemTimer<'_>> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let name = *name.get_or_init(|| system().leak());
+
+        self.time(name)
+    }
+
+    /// Adds `duration` to the time `name` took this tick.
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        let mut state = self.state.lock();
+
+        match state.current.iter_mut().find(|(system, _)| *system == name) {
+            Some((_, total)) => *total += duration,
+            None => state.current.push((name, duration)),
+        }
+    }
+
+    /// Ends the current tick. Returns its timings if it was slow.
+    pub fn finish_tick(&self, duration: Duration) -> Option<TickTimings> {
+        let mut systems = std::mem::take(&mut self.state.lock().current);
+        systems.sort_unstable_by_key(|&(_, time)| Reverse(time));
+
+        let tick = TickTimings { duration, systems };
+        let slow = duration > self.slow_tick_threshold();
+
+        let mut state = self.state.lock();
+
+        if state.window.len() == WINDOW {
+            state.window.pop_front();
+        }
+
+        state.window.push_back(tick.clone());
+
+        if slow {
+            state.last_slow_tick = Some(tick.clone());
+        }
+
+        drop(state);
+
+        slow.then_some(tick)
+    }
+
+    /// The last tick that was slow, if any.
+    #[must_use]
+    pub fn last_slow_tick(&self) -> Option<TickTimings> {
+        self.state.lock().last_slow_tick.clone()
+    }
+
+    /// Summarizes the recent ticks.
+    #[must_use]
+    pub fn report(&self) -> TickReport {
+        let state = self.state.lock();
+
+        let ticks = state.window.len();
+
+        if ticks == 0 {
+            return TickReport::default();
+        }
+
+        let total: Duration = state.window.iter().map(|tick| tick.duration).sum();
+        let max = state.window.iter().map(|tick| tick.duration).max();
+
+        let mut systems: Vec<(&'static str, Duration, Duration)> = Vec::new();
+
+        for &(name, duration) in state.window.iter().flat_map(|tick| &tick.systems) {
+            match systems.iter_mut().find(|(system, ..)| *system == name) {
+                Some((_, total, max)) => {
+                    *total += duration;
+                    *max = (*max).max(duration);
+                }
+                None => systems.push((name, duration, duration)),
+            }
+        }
+
+        drop(state);
+
+        // the window never holds more than `WINDOW` ticks
+        let count = u32::try_from(ticks).unwrap_or(u32::MAX);
+
+        let mut systems: Vec<_> = systems
+            .into_iter()
+            .map(|(name, total, max)| SystemTiming {
+                name,
+                average: total / count,
+                max,
+            })
+            .collect();
+
+        systems.sort_unstable_by_key(|system| Reverse(system.average));
+
+        TickReport {
+            ticks,
+            average: total / count,
+            max: max.unwrap_or_default(),
+            systems,
+        }
+    }
+}
+
+impl TickTimings {
+    /// The slowest systems, like `egress 12.3ms, recv_data 8.1ms`.
+    #[must_use]
+    pub fn top_offenders(&self) -> String {
+        let mut offenders = String::new();
+
+        for (i, (name, duration)) in self.systems.iter().take(TOP_OFFENDERS).enumerate() {
+            if i > 0 {
+                offenders.push_str(", ");
+            }
+
+            // writing to a string cannot fail
+            write!(offenders, "{name} {:.1}ms", duration.as_secs_f64() * 1000.0).unwrap();
+        }
+
+        offenders
+    }
+}
+
+/// When the current tick started.
+#[derive(Component)]
+struct TickStart(Instant);
+
+/// Feeds the durations of ticks to [`PROFILER`] and warns about slow ones.
+#[derive(Component)]
+pub struct ProfilerModule;
+
+impl Module for ProfilerModule {
+    fn module(world: &World) {
+        world.component::<TickStart>();
+        world.set(TickStart(Instant::now()));
+
+        system!("start_tick_timer", world, &mut TickStart($))
+            .kind::<flecs::pipeline::OnLoad>()
+            .each_iter(|_, _, start| {
+                start.0 = Instant::now();
+            });
+
+        // the time spent flushing to the proxy afterwards is not included
+        system!("finish_tick_timer", world, &TickStart($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(|_, _, start| {
+                let duration = start.0.elapsed();
+
+                #[cfg(feature = "metrics")]
+                crate::metrics::METRICS.observe_tick(duration);
+
+                let Some(tick) = PROFILER.finish_tick(duration) else {
+                    return;
+                };
+
+                let threshold = PROFILER.slow_tick_threshold();
+
+                warn!(
+                    duration_ms = %duration.as_millis(),
+                    threshold_ms = %threshold.as_millis(),
+                    top = %tick.top_offenders(),
+                    "slow tick"
+                );
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TickProfiler;
+
+    // a profiler of its own, since enabling the global one would time the systems of every
+    // other test running at the same time
+    #[test]
+    fn slow_systems_are_reported() {
+        let profiler = TickProfiler::new();
+        profiler.set_slow_tick_threshold(Duration::from_millis(20));
+
+        // nothing is timed until profiling is enabled
+        assert!(profiler.time("artificially_slow").is_none());
+
+        profiler.set_enabled(true);
+
+        {
+            let _timer = profiler.time("artificially_slow");
+            std::thread::sleep(Duration::from_millis(30));
+        }
+        profiler.record("quick", Duration::from_millis(1));
+
+        assert!(profiler.finish_tick(Duration::from_millis(10)).is_none());
+        assert!(profiler.last_slow_tick().is_none());
+
+        {
+            let _timer = profiler.time("artificially_slow");
+            std::thread::sleep(Duration::from_millis(30));
+        }
+        profiler.record("quick", Duration::from_millis(1));
+
+        let tick = profiler.finish_tick(Duration::from_millis(35)).unwrap();
+        let (name, duration) = tick.systems[0];
+
+        assert_eq!(name, "artificially_slow");
+        assert!(duration >= Duration::from_millis(30));
+        assert!(tick.top_offenders().starts_with("artificially_slow "));
+        assert_eq!(profiler.last_slow_tick().unwrap().systems[1].0, "quick");
+
+        let report = profiler.report();
+        assert_eq!(report.systems[0].name, "artificially_slow");
+    }
+}
@@ -5,17 +5,35 @@ use std::sync::Arc;
 use derive_more::{Deref, DerefMut};
 use flecs_ecs::{core::World, macros::Component};
 use kanal::{Receiver, Sender};
+use tracing::{debug, warn};
 
 /// Type alias for world callback functions
-pub type WorldCallback = Box<dyn FnOnce(&World)>;
+pub type WorldCallback = Box<dyn FnOnce(&World) + Send>;
+
+/// What to do with the result of a task when the queue of callbacks waiting for the next tick is
+/// full.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CallbackOverflow {
+    /// Wait until there is room again. The task holds on to its result until then, but the
+    /// runtime thread is free to run other tasks.
+    #[default]
+    Wait,
+    /// Drop the callback and log a warning, for results that are only worth applying soon.
+    Drop,
+}
 
 /// Wrapper around [`tokio::runtime::Runtime`]
+///
+/// Tasks cannot touch the [`World`], as it is only available on the tick thread. Instead, they
+/// can resolve to a callback with [`Self::spawn_for_world`], which is called with the world by
+/// the `run_tasks` system during the next tick.
 #[derive(Component, Deref, DerefMut, Clone)]
 pub struct AsyncRuntime {
     #[deref]
     #[deref_mut]
     runtime: Arc<tokio::runtime::Runtime>,
     callback_sender: Sender<WorldCallback>,
+    overflow: CallbackOverflow,
 }
 
 #[derive(Component)]
@@ -23,25 +41,67 @@ pub struct Tasks {
     pub(crate) tasks: Receiver<WorldCallback>,
 }
 
+impl Tasks {
+    /// Calls every callback that is ready, in the order their tasks finished.
+    pub(crate) fn run(&self, world: &World) {
+        while let Ok(Some(task)) = self.tasks.try_recv() {
+            task(world);
+        }
+    }
+}
+
 impl AsyncRuntime {
-    pub fn schedule<T: 'static>(
+    pub fn schedule<T: Send + 'static>(
         &self,
         future: impl Future<Output = T> + Send + 'static,
         handler: fn(T, &World),
     ) {
+        self.spawn_for_world(async move {
+            let result = future.await;
+            move |world: &World| handler(result, world)
+        });
+    }
+
+    /// Runs `future` on the runtime, then calls the callback it resolves to with the world during
+    /// the next tick, e.g. to attach a skin that was fetched while the player was joining.
+    ///
+    /// Callbacks are called in the order their tasks finished. A task that finishes after the
+    /// server stopped is dropped along with its callback.
+    pub fn spawn_for_world<C>(&self, future: impl Future<Output = C> + Send + 'static)
+    where
+        C: FnOnce(&World) + Send + 'static,
+    {
         let sender = self.callback_sender.clone();
+        let overflow = self.overflow;
 
         self.spawn(async move {
-            let result = future.await;
+            let callback: WorldCallback = Box::new(future.await);
 
-            let to_send = move |world: &World| {
-                handler(result, world);
+            let sent = match overflow {
+                CallbackOverflow::Wait => sender.as_async().send(callback).await.is_ok(),
+                CallbackOverflow::Drop => match sender.try_send(callback) {
+                    Ok(true) => true,
+                    Ok(false) => {
+                        warn!("dropping the result of a task as too many are waiting");
+                        return;
+                    }
+                    Err(_) => false,
+                },
             };
 
-            sender.send(Box::new(to_send)).unwrap();
+            if !sent {
+                debug!("dropping the result of a task as the server stopped");
+            }
         });
     }
 
+    /// Uses `overflow` for tasks spawned with this handle when too many callbacks are waiting for
+    /// the next tick.
+    #[must_use]
+    pub const fn with_overflow(self, overflow: CallbackOverflow) -> Self {
+        Self { overflow, ..self }
+    }
+
     pub(crate) fn new(sender: Sender<WorldCallback>) -> Self {
         Self {
             runtime: Arc::new(
@@ -53,6 +113,96 @@ impl AsyncRuntime {
                     .unwrap(),
             ),
             callback_sender: sender,
+            overflow: CallbackOverflow::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use flecs_ecs::{core::World, macros::Component};
+
+    use super::{AsyncRuntime, CallbackOverflow, Tasks};
+
+    #[derive(Component, Default)]
+    struct Applied(Vec<(usize, thread::ThreadId)>);
+
+    #[test]
+    fn callbacks_are_applied_on_the_tick_thread() {
+        let world = World::new();
+        world.component::<Applied>();
+        world.set(Applied::default());
+
+        // far fewer slots than tasks, so most of them wait for room
+        let (tx, rx) = kanal::bounded(32);
+        let runtime = AsyncRuntime::new(tx);
+        let tasks = Tasks { tasks: rx };
+
+        for i in 0..1000 {
+            runtime.spawn_for_world(async move {
+                tokio::task::yield_now().await;
+
+                move |world: &World| {
+                    world.get::<&mut Applied>(|applied| {
+                        applied.0.push((i, thread::current().id()));
+                    });
+                }
+            });
         }
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        // each iteration stands in for a tick
+        while world.get::<&Applied>(|applied| applied.0.len()) < 1000 {
+            assert!(Instant::now() < deadline, "tasks did not finish in time");
+
+            tasks.run(&world);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        world.get::<&Applied>(|applied| {
+            let mut indices: Vec<_> = applied.0.iter().map(|&(i, _)| i).collect();
+            indices.sort_unstable();
+
+            assert_eq!(indices, (0..1000).collect::<Vec<_>>());
+            assert!(
+                applied
+                    .0
+                    .iter()
+                    .all(|&(_, id)| id == thread::current().id())
+            );
+        });
+    }
+
+    #[test]
+    fn full_queues_drop_callbacks_when_asked_to() {
+        let world = World::new();
+        world.component::<Applied>();
+        world.set(Applied::default());
+
+        let (tx, rx) = kanal::bounded(1);
+        let runtime = AsyncRuntime::new(tx).with_overflow(CallbackOverflow::Drop);
+        let tasks = Tasks { tasks: rx };
+
+        for i in 0..2 {
+            runtime.spawn_for_world(async move {
+                move |world: &World| {
+                    world.get::<&mut Applied>(|applied| {
+                        applied.0.push((i, thread::current().id()));
+                    });
+                }
+            });
+        }
+
+        // both tasks are done, and only one of them found room
+        thread::sleep(Duration::from_millis(200));
+        tasks.run(&world);
+
+        assert_eq!(world.get::<&Applied>(|applied| applied.0.len()), 1);
     }
 }
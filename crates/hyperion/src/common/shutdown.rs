@@ -0,0 +1,309 @@
+//! Shutting the server down without losing progress.
+//!
+//! A shutdown is requested with a [`ShutdownHandle`], which signals, the `stop` console command and
+//! `/stop` all use. It then takes two ticks:
+//!
+//! 1. Everyone is told the server is shutting down and kicked with
+//!    [`ServerConfig::shutdown_message`], including players that are still logging in. Kicked
+//!    players are saved like any other player leaving.
+//! 2. The [`on_shutdown`] hooks run and the app stops after this tick, whose egress flushes the
+//!    last packets to the proxy.
+//!
+//! If this takes longer than [`ServerConfig::shutdown_timeout_secs`], or a second shutdown is
+//! requested while shutting down, the process exits without finishing.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use flecs_ecs::prelude::*;
+use tracing::{error, info, warn};
+use valence_protocol::packets::login;
+use valence_text::IntoText;
+
+use crate::{
+    config::ServerConfig,
+    ingress::PendingRemove,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{PacketState, Player},
+    system_registry::SHUTDOWN,
+};
+
+/// The exit code when a shutdown does not finish.
+const FORCED_EXIT_CODE: i32 = 1;
+
+/// Requests a shutdown. Clones share the same request, so a clone can be moved to another thread.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    requests: Arc<AtomicUsize>,
+    finished: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    /// Requests a shutdown and returns how many have been requested, including this one.
+    pub fn request(&self) -> usize {
+        self.requests.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        self.requests.load(Ordering::Relaxed) > 0
+    }
+
+    /// Whether everything was saved and the app is about to stop.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    /// Requests a shutdown, or exits right away if one was already requested. `source` is what
+    /// asked for it, for the log.
+    pub fn request_or_exit(&self, source: &str) {
+        if self.request() == 1 {
+            warn!("{source} received, shutting down");
+        } else {
+            error!("{source} received while shutting down, exiting without finishing");
+            std::process::exit(FORCED_EXIT_CODE);
+        }
+    }
+}
+
+type Hook = Box<dyn FnOnce(&World) + Send + Sync>;
+
+/// What runs once all players are kicked and saved.
+#[derive(Component, Default)]
+pub struct ShutdownHooks {
+    hooks: Vec<Hook>,
+}
+
+/// Runs `hook` when the server shuts down, after players are saved. Anything else that must be
+/// saved, such as changed chunks, belongs here.
+pub fn on_shutdown(world: &World, hook: impl FnOnce(&World) + Send + Sync + 'static) {
+    world.get::<&mut ShutdownHooks>(|hooks| hooks.hooks.push(Box::new(hook)));
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Running,
+    Saving,
+    Stopped,
+}
+
+#[derive(Component, Debug, Default)]
+struct ShutdownPhase(Phase);
+
+/// Exits the process if `handle` has not finished after `timeout`, so a stuck save cannot keep the
+/// server running forever.
+fn spawn_deadline(handle: ShutdownHandle, timeout: Duration) {
+    let deadline = std::thread::Builder::new()
+        .name("shutdown-deadline".to_owned())
+        .spawn(move || {
+            std::thread::sleep(timeout);
+
+            if !handle.is_finished() {
+                error!("shutting down took longer than {timeout:?}, exiting without finishing");
+                std::process::exit(FORCED_EXIT_CODE);
+            }
+        });
+
+    if let Err(e) = deadline {
+        warn!("failed to start the shutdown deadline, shutting down without one: {e}");
+    }
+}
+
+/// Requests a shutdown on SIGINT, SIGTERM or SIGQUIT, and exits on the one after that.
+pub async fn watch_signals(handle: ShutdownHandle) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let (mut sigterm, mut sigquit) =
+        match (signal(SignalKind::terminate()), signal(SignalKind::quit())) {
+            (Ok(sigterm), Ok(sigquit)) => (sigterm, sigquit),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("failed to listen for signals, only ctrl-c shuts down gracefully: {e}");
+
+                loop {
+                    if tokio::signal::ctrl_c().await.is_err() {
+                        return;
+                    }
+                    handle.request_or_exit("SIGINT/ctrl-c");
+                }
+            }
+        };
+
+    loop {
+        #[allow(clippy::redundant_pub_crate)]
+        let source = tokio::select! {
+            _ = tokio::signal::ctrl_c() => "SIGINT/ctrl-c",
+            _ = sigterm.recv() => "SIGTERM",
+            _ = sigquit.recv() => "SIGQUIT",
+        };
+
+        handle.request_or_exit(source);
+    }
+}
+
+/// Reads commands typed into the terminal the server runs in. `stop` (or `/stop`) requests a
+/// shutdown right away, even if ticks are stuck. Other commands are sent to `commands`, to be run
+/// as the [`Console`](crate::simulation::command::Console).
+pub fn spawn_console(handle: ShutdownHandle, commands: kanal::Sender<String>) {
+    let console = std::thread::Builder::new()
+        .name("console".to_owned())
+        .spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                match line.trim().trim_start_matches('/') {
+                    "stop" => handle.request_or_exit("stop command"),
+                    "" => {}
+                    command => {
+                        if commands.send(command.to_owned()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+    if let Err(e) = console {
+        warn!("failed to read the console: {e}");
+    }
+}
+
+/// Tells everyone the server is shutting down and kicks them, along with the players still
+/// logging in.
+fn kick_everyone(
+    world: &World,
+    players: &Query<()>,
+    joining: &Query<&NetworkStreamRef>,
+    message: &str,
+) {
+    world.try_get::<&Compose>(|compose| {
+        let chat = agnostic::chat(message);
+
+        if let Err(e) = compose.broadcast(&chat, SHUTDOWN).send(world) {
+            warn!("failed to announce the shutdown: {e}");
+        }
+    });
+
+    let mut kicked = 0_usize;
+
+    players.each_entity(|player, ()| {
+        player.set(PendingRemove::new(message));
+        kicked += 1;
+    });
+
+    // they would otherwise finish joining while the server saves
+    joining.each_entity(|player, &stream| {
+        world.try_get::<&Compose>(|compose| {
+            let pkt = login::LoginDisconnectS2c {
+                reason: message.into_cow_text(),
+            };
+
+            if let Err(e) = compose.unicast(&pkt, stream, SHUTDOWN, world) {
+                warn!("failed to disconnect a player that was logging in: {e}");
+            }
+        });
+
+        // without a reason, they are not sent a disconnect packet meant for players in play
+        player.set(PendingRemove::new(String::new()));
+        kicked += 1;
+    });
+
+    info!("kicked {kicked} players");
+}
+
+#[derive(Component)]
+pub struct ShutdownModule;
+
+impl Module for ShutdownModule {
+    fn module(world: &World) {
+        world.component::<ShutdownHandle>();
+        world.component::<ShutdownHooks>();
+        world.component::<ShutdownPhase>();
+
+        world.set(ShutdownHandle::default());
+        world.set(ShutdownHooks::default());
+        world.set(ShutdownPhase::default());
+
+        let players = world
+            .query::<()>()
+            .with::<Player>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        let joining = world
+            .query::<&NetworkStreamRef>()
+            .with::<Player>()
+            .with_enum(PacketState::Login)
+            .build();
+
+        system!(
+            "shutdown",
+            world,
+            &ShutdownHandle($),
+            &mut ShutdownHooks($),
+            &mut ShutdownPhase($),
+            &ServerConfig($),
+        )
+        .kind::<flecs::pipeline::OnLoad>()
+        .each_iter(move |it, _, (handle, hooks, phase, config)| {
+            let world = it.world();
+
+            match phase.0 {
+                Phase::Running if handle.is_requested() => {
+                    info!("shutting down");
+
+                    let timeout = Duration::from_secs(config.shutdown_timeout_secs);
+                    spawn_deadline(handle.clone(), timeout);
+
+                    // kicked players are saved when they are removed later this tick
+                    kick_everyone(&world, &players, &joining, &config.shutdown_message);
+                    phase.0 = Phase::Saving;
+                }
+                Phase::Saving => {
+                    for hook in std::mem::take(&mut hooks.hooks) {
+                        hook(&world);
+                    }
+
+                    info!("everything is saved, stopping");
+                    handle.finish();
+                    world.quit();
+                    phase.0 = Phase::Stopped;
+                }
+                Phase::Running | Phase::Stopped => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShutdownHandle;
+
+    #[test]
+    fn clones_share_the_request() {
+        let handle = ShutdownHandle::default();
+        let signals = handle.clone();
+
+        assert!(!handle.is_requested());
+
+        assert_eq!(signals.request(), 1);
+        assert!(handle.is_requested());
+        assert_eq!(handle.request(), 2);
+
+        assert!(!signals.is_finished());
+        handle.finish();
+        assert!(signals.is_finished());
+    }
+}
@@ -6,6 +6,27 @@ pub const SEND_FULL_LOADED_CHUNKS: SystemId = SystemId(4);
 pub const LOCAL_STATS: SystemId = SystemId(5);
 pub const RECV_DATA: SystemId = SystemId(0); // todo: change back to 6
 pub const SYNC_ENTITY_POSITION: SystemId = SystemId(7);
+pub const TELEPORT: SystemId = SystemId(8);
+pub const VISIBILITY: SystemId = SystemId(9);
+pub const MENU: SystemId = SystemId(10);
+pub const RECIPE_BOOK: SystemId = SystemId(11);
+pub const FURNACE: SystemId = SystemId(12);
+pub const ANVIL: SystemId = SystemId(13);
+pub const CRAFTING_TABLE: SystemId = SystemId(14);
+pub const WORLD_TIME: SystemId = SystemId(15);
+pub const WEATHER: SystemId = SystemId(16);
+pub const SPAWN: SystemId = SystemId(17);
+pub const NETWORK_ENTITIES: SystemId = SystemId(18);
+pub const SHUTDOWN: SystemId = SystemId(19);
+pub const COMMAND_TREE: SystemId = SystemId(20);
+pub const EQUIPMENT: SystemId = SystemId(21);
+pub const CONTAINER: SystemId = SystemId(22);
+pub const DROPPED_ITEMS: SystemId = SystemId(23);
+pub const WORLD_BORDER: SystemId = SystemId(24);
+pub const SCOREBOARD: SystemId = SystemId(25);
+pub const BOSS_BARS: SystemId = SystemId(26);
+pub const KEEP_ALIVE: SystemId = SystemId(27);
+pub const GAME_MODE: SystemId = SystemId(28);
 
 #[derive(Copy, Clone, Debug)]
 pub struct SystemId(pub u16);
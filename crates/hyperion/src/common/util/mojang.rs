@@ -1,44 +1,124 @@
 //! See [`MojangClient`].
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::{Context, bail};
+use anyhow::{Context, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose};
 use flecs_ecs::macros::Component;
+use futures_util::{StreamExt as _, stream};
+use lru::LruCache;
+use parking_lot::Mutex;
+use reqwest::{RequestBuilder, StatusCode, header::CONTENT_TYPE};
 use serde_json::Value;
 use tokio::{
-    sync::Semaphore,
-    time::{MissedTickBehavior, interval},
+    sync::{OnceCell, Semaphore},
+    time::{MissedTickBehavior, interval, sleep, sleep_until},
 };
 use tracing::warn;
 use uuid::Uuid;
 
-use crate::runtime::AsyncRuntime;
+use crate::{runtime::AsyncRuntime, simulation::skin::PlayerSkin};
 
 /// The API provider to use for Minecraft profile lookups
-#[derive(Clone, Copy)]
+///
+/// Its URLs can be changed to point at a self-hosted mirror or a mock, e.g.
+/// `ApiProvider::default().with_uuid_url("http://localhost:8080/profile")`.
+#[derive(Clone, Debug)]
 pub struct ApiProvider {
-    username_base_url: &'static str,
-    uuid_base_url: &'static str,
+    username_base_url: Cow<'static, str>,
+    uuid_base_url: Cow<'static, str>,
+    bulk_username_url: Cow<'static, str>,
     max_requests: usize,
     interval: Duration,
+    /// How long to wait for an answer before trying again, or trying the next provider.
+    timeout: Duration,
+}
+
+impl Default for ApiProvider {
+    fn default() -> Self {
+        Self::MAT_DOES_DEV
+    }
 }
 
 impl ApiProvider {
     /// The matdoes.dev API mirror provider with higher rate limits
     pub const MAT_DOES_DEV: Self = Self {
-        username_base_url: "https://mowojang.matdoes.dev/users/profiles/minecraft",
-        uuid_base_url: "https://mowojang.matdoes.dev/session/minecraft/profile",
+        username_base_url: Cow::Borrowed("https://mowojang.matdoes.dev/users/profiles/minecraft"),
+        uuid_base_url: Cow::Borrowed("https://mowojang.matdoes.dev/session/minecraft/profile"),
+        bulk_username_url: Cow::Borrowed(
+            "https://mowojang.matdoes.dev/minecraft/profile/lookup/bulk/byname",
+        ),
         max_requests: 10_000,
         interval: Duration::from_secs(1),
+        timeout: DEFAULT_TIMEOUT,
     };
     /// The official Mojang API provider
     pub const MOJANG: Self = Self {
-        username_base_url: "https://api.mojang.com/users/profiles/minecraft",
-        uuid_base_url: "https://sessionserver.mojang.com/session/minecraft/profile",
+        username_base_url: Cow::Borrowed("https://api.mojang.com/users/profiles/minecraft"),
+        uuid_base_url: Cow::Borrowed("https://sessionserver.mojang.com/session/minecraft/profile"),
+        bulk_username_url: Cow::Borrowed(
+            "https://api.minecraftservices.com/minecraft/profile/lookup/bulk/byname",
+        ),
         max_requests: 600,
         interval: Duration::from_mins(10),
+        timeout: DEFAULT_TIMEOUT,
     };
 
+    /// [`Self::MAT_DOES_DEV`], falling back to [`Self::MOJANG`] while it is unavailable.
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        vec![Self::MAT_DOES_DEV, Self::MOJANG]
+    }
+
+    /// Gives up on a request that was not answered within `timeout`, so it is retried or sent to
+    /// the next provider.
+    #[must_use]
+    pub const fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Looks up profiles by username at `url`/`<username>`.
+    #[must_use]
+    pub fn with_username_url(self, url: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            username_base_url: url.into(),
+            ..self
+        }
+    }
+
+    /// Looks up profiles by UUID, with their skin, at `url`/`<uuid>`.
+    #[must_use]
+    pub fn with_uuid_url(self, url: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            uuid_base_url: url.into(),
+            ..self
+        }
+    }
+
+    /// Looks up many profiles by username at once by posting them to `url`.
+    #[must_use]
+    pub fn with_bulk_username_url(self, url: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            bulk_username_url: url.into(),
+            ..self
+        }
+    }
+
+    /// The host profiles are looked up at, to tell providers apart in logs.
+    fn host(&self) -> &str {
+        let url = self.username_base_url.as_ref();
+        let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+        url.split_once('/').map_or(url, |(host, _)| host)
+    }
+
     fn username_url(&self, username: &str) -> String {
         format!("{}/{username}", self.username_base_url)
     }
@@ -56,20 +136,277 @@ impl ApiProvider {
     }
 }
 
-/// A client to interface with the Minecraft profile API.
-///
-/// Can use either the official Mojang API or [matdoes/mowojang](https://matdoes.dev/minecraft-uuids) as a data source.
-/// This does not include caching, this should be done separately probably using [`crate::storage::LocalDb`].
-#[derive(Component, Clone)]
-pub struct MojangClient {
-    req: reqwest::Client,
-    rate_limit: Arc<Semaphore>,
+/// How long a provider has to answer a request by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a provider that kept failing is only tried after the others by default.
+const DEFAULT_PROVIDER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// How often a request is retried by default before giving up on a provider.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// How long to wait before the first retry by default. Every further retry waits twice as long.
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// How many requests are sent per second by default, to every provider together.
+const DEFAULT_RATE_LIMIT: u32 = 100;
+
+/// How many usernames a single bulk lookup may contain.
+const BULK_LOOKUP_LIMIT: usize = 10;
+
+/// How many bulk lookups [`MojangClient::get_uuids`] sends at the same time.
+const BULK_LOOKUP_CONCURRENCY: usize = 4;
+
+/// How many profiles are cached by default, both by username and by UUID.
+const DEFAULT_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(1024).unwrap();
+
+/// How long a cached profile is used by default before it is fetched again, as recommended by
+/// Mojang.
+const DEFAULT_CACHE_TTL: Duration = Duration::from_hours(4);
+
+/// How long it is remembered by default that there is no player with a username or UUID. Shorter
+/// than [`DEFAULT_CACHE_TTL`], as the username may be taken any time.
+const DEFAULT_NEGATIVE_CACHE_TTL: Duration = Duration::from_mins(5);
+
+/// What a profile is looked up by.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ProfileKey {
+    /// A lowercase username, as usernames are case-insensitive.
+    Username(String),
+    Uuid(Uuid),
+}
+
+impl ProfileKey {
+    fn username(username: &str) -> Self {
+        Self::Username(username.to_lowercase())
+    }
+}
+
+/// A cached profile, or `None` if there is no such player, and when it was fetched.
+type CacheEntry = (Instant, Option<Value>);
+
+/// Responses of the profile API by username and by UUID, each used until it is `ttl` old, or
+/// `negative_ttl` if there is no such player.
+struct ProfileCache {
+    by_username: LruCache<String, CacheEntry>,
+    by_uuid: LruCache<Uuid, CacheEntry>,
+    ttl: Duration,
+    negative_ttl: Duration,
+    hits: u64,
+    misses: u64,
+}
+
+impl ProfileCache {
+    fn new(capacity: NonZeroUsize, ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            by_username: LruCache::new(capacity),
+            by_uuid: LruCache::new(capacity),
+            ttl,
+            negative_ttl,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn get_fresh<K: Hash + Eq>(
+        cache: &mut LruCache<K, CacheEntry>,
+        key: &K,
+        ttl: Duration,
+        negative_ttl: Duration,
+    ) -> Option<Option<Value>> {
+        let (fetched, profile) = cache.get(key)?;
+
+        let ttl = if profile.is_some() { ttl } else { negative_ttl };
+
+        if fetched.elapsed() < ttl {
+            return Some(profile.clone());
+        }
+
+        cache.pop(key);
+        None
+    }
+
+    /// The cached profile at `key`, `Some(None)` if there is no such player, or `None` if it
+    /// needs to be fetched.
+    fn get(&mut self, key: &ProfileKey) -> Option<Option<Value>> {
+        let (ttl, negative_ttl) = (self.ttl, self.negative_ttl);
+
+        let cached = match key {
+            ProfileKey::Username(username) => {
+                Self::get_fresh(&mut self.by_username, username, ttl, negative_ttl)
+            }
+            ProfileKey::Uuid(uuid) => Self::get_fresh(&mut self.by_uuid, uuid, ttl, negative_ttl),
+        };
+
+        if cached.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+
+        cached
+    }
+
+    fn put(&mut self, key: ProfileKey, profile: Option<Value>) {
+        let entry = (Instant::now(), profile);
+
+        match key {
+            ProfileKey::Username(username) => {
+                self.by_username.put(username, entry);
+            }
+            ProfileKey::Uuid(uuid) => {
+                self.by_uuid.put(uuid, entry);
+            }
+        }
+    }
+
+    /// Forgets the profile at `key`, and the same profile by its other key. Returns whether
+    /// anything was cached.
+    fn invalidate(&mut self, key: &ProfileKey) -> bool {
+        let popped = match key {
+            ProfileKey::Username(username) => self.by_username.pop(username),
+            ProfileKey::Uuid(uuid) => self.by_uuid.pop(uuid),
+        };
+
+        let Some((_, profile)) = popped else {
+            return false;
+        };
+
+        if let Some(profile) = profile {
+            if let Ok(uuid) = profile_uuid(&profile) {
+                self.by_uuid.pop(&uuid);
+            }
+
+            if let Some(name) = profile.get("name").and_then(Value::as_str) {
+                self.by_username.pop(&name.to_lowercase());
+            }
+        }
+
+        true
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            size: self.by_username.len() + self.by_uuid.len(),
+        }
+    }
+}
+
+/// How often profiles were found in the cache of a [`MojangClient`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Lookups that were not cached, including those waiting on the same request as another.
+    pub misses: u64,
+    /// The cached profiles, and players known not to exist, by username and by UUID together.
+    pub size: usize,
+}
+
+/// The outcome of a lookup shared by everyone waiting on it. Errors are kept as text, as
+/// [`anyhow::Error`] cannot be cloned.
+type SharedLookup = Result<Option<Value>, String>;
+
+/// The arm width of a skin.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SkinModel {
+    /// Arms 4 pixels wide, like Steve's.
+    #[default]
+    Classic,
+    /// Arms 3 pixels wide, like Alex's.
+    Slim,
+}
+
+/// The skin and cape of a player, see [`MojangClient::get_textures`].
+#[derive(Clone, Debug)]
+pub struct PlayerTextures {
+    /// `None` if the player uses a default skin.
+    pub skin_url: Option<String>,
+    pub cape_url: Option<String>,
+    pub model: SkinModel,
+    /// The signed `textures` property they were decoded from, which is what clients are sent.
+    pub property: PlayerSkin,
+}
+
+impl PlayerTextures {
+    /// Decodes the `textures` property of a profile returned by [`MojangClient::data_from_uuid`].
+    pub fn from_profile(profile: &Value) -> anyhow::Result<Self> {
+        let property = textures_property(profile)?.context("the profile has no textures")?;
+
+        let payload = general_purpose::STANDARD
+            .decode(&property.textures)
+            .context("invalid texture value")?;
+        let payload: Value =
+            serde_json::from_slice(&payload).context("texture value is not valid json")?;
+
+        let textures = payload.get("textures").context("no textures in json")?;
+
+        let url = |kind: &str| {
+            textures
+                .get(kind)
+                .and_then(|texture| texture.get("url"))
+                .and_then(Value::as_str)
+                .map(String::from)
+        };
+
+        // only slim skins say which model they use
+        let model = match textures["SKIN"]["metadata"]["model"].as_str() {
+            Some("slim") => SkinModel::Slim,
+            _ => SkinModel::Classic,
+        };
+
+        Ok(Self {
+            skin_url: url("SKIN"),
+            cape_url: url("CAPE"),
+            model,
+            property,
+        })
+    }
+}
+
+/// The signed `textures` property of a profile, or `None` if it has none.
+fn textures_property(profile: &Value) -> anyhow::Result<Option<PlayerSkin>> {
+    let properties = profile["properties"]
+        .as_array()
+        .with_context(|| format!("no properties on {profile:?}"))?;
+
+    for property in properties {
+        let name = property["name"]
+            .as_str()
+            .with_context(|| format!("no name on {property:?}"))?;
+
+        if name != "textures" {
+            continue;
+        }
+
+        let textures = property["value"]
+            .as_str()
+            .with_context(|| format!("no value on {property:?}"))?;
+        let signature = property["signature"]
+            .as_str()
+            .with_context(|| format!("no signature on {property:?}"))?;
+
+        return Ok(Some(PlayerSkin::new(
+            textures.to_owned(),
+            signature.to_owned(),
+        )));
+    }
+
+    Ok(None)
+}
+
+/// A provider with its own rate limit.
+#[derive(Clone)]
+struct Upstream {
     provider: ApiProvider,
+    rate_limit: Arc<Semaphore>,
+    /// Until when the provider is only tried after the others, as requests to it kept failing.
+    cooldown_until: Arc<Mutex<Option<Instant>>>,
 }
 
-impl MojangClient {
-    #[must_use]
-    pub fn new(tasks: &AsyncRuntime, provider: ApiProvider) -> Self {
+impl Upstream {
+    fn new(tasks: &AsyncRuntime, provider: ApiProvider) -> Self {
         let rate_limit = Arc::new(Semaphore::new(provider.max_requests()));
         let interval_duration = provider.interval();
 
@@ -94,30 +431,303 @@ impl MojangClient {
         });
 
         Self {
-            req: reqwest::Client::new(),
-            rate_limit,
             provider,
+            rate_limit,
+            cooldown_until: Arc::default(),
+        }
+    }
+
+    fn is_cooling_down(&self) -> bool {
+        self.cooldown_until
+            .lock()
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    fn cool_down(&self, cooldown: Duration) {
+        *self.cooldown_until.lock() = Some(Instant::now() + cooldown);
+    }
+
+    fn recover(&self) {
+        *self.cooldown_until.lock() = None;
+    }
+}
+
+/// Spaces requests out evenly, so a burst of lookups, e.g. when many players join at once, is sent
+/// over time instead of getting rate limited by the provider.
+#[derive(Debug)]
+struct RequestPacer {
+    /// How long to wait between two requests.
+    spacing: Duration,
+    /// When the next request may be sent.
+    next: Instant,
+}
+
+impl RequestPacer {
+    /// Allows `per_second` requests per second, or any number of them if `per_second` is zero.
+    fn new(per_second: u32) -> Self {
+        let spacing = Duration::from_secs(1)
+            .checked_div(per_second)
+            .unwrap_or_default();
+
+        Self {
+            spacing,
+            next: Instant::now(),
+        }
+    }
+
+    /// Takes the next free slot, returning when the request may be sent.
+    fn reserve(&mut self, now: Instant) -> Instant {
+        let at = self.next.max(now);
+        self.next = at + self.spacing;
+        at
+    }
+}
+
+/// Why a request failed.
+enum RequestError {
+    /// The provider is unavailable or rate limited us, so trying again later or trying another
+    /// provider may work.
+    Transient(anyhow::Error),
+    /// There is no such player.
+    NotFound,
+    /// The provider answered, but not with the profile, so asking again will not help.
+    Permanent(anyhow::Error),
+}
+
+/// A client to interface with the Minecraft profile API.
+///
+/// Can use either the official Mojang API or [matdoes/mowojang](https://matdoes.dev/minecraft-uuids) as a data source.
+/// Responses are cached in memory, both by username and by UUID, so looking up the same player
+/// again does not count towards the rate limit. Players that do not exist are remembered for a
+/// shorter time. See [`Self::with_cache_capacity`] and [`Self::with_negative_cache_ttl`].
+/// Concurrent lookups of the same player, e.g. when many of them join at once, share a request.
+///
+/// Requests are spaced out so bursts of lookups do not get rate limited, see
+/// [`Self::with_rate_limit`]. Requests that fail because a provider is unavailable, rate limited
+/// or too slow, see
+/// [`ApiProvider::with_timeout`], are retried with exponential backoff, see
+/// [`Self::with_retries`], and then sent to the next provider. A provider that failed is asked
+/// after the others for a while, so an outage does not slow down every lookup, see
+/// [`Self::with_provider_cooldown`].
+#[derive(Component, Clone)]
+pub struct MojangClient {
+    req: reqwest::Client,
+    /// In the order they are tried.
+    upstreams: Vec<Upstream>,
+    max_retries: u32,
+    initial_backoff: Duration,
+    provider_cooldown: Duration,
+    /// Shared by clones, so they are limited together.
+    pacer: Arc<Mutex<RequestPacer>>,
+    /// `None` if caching is disabled.
+    cache: Option<Arc<Mutex<ProfileCache>>>,
+    /// The lookups being requested, which others looking up the same profile wait for.
+    in_flight: Arc<Mutex<HashMap<ProfileKey, Arc<OnceCell<SharedLookup>>>>>,
+}
+
+impl MojangClient {
+    /// Looks up profiles with the first of `providers`, and with the next one whenever a provider
+    /// is unavailable, such as [`ApiProvider::defaults`].
+    ///
+    /// # Panics
+    ///
+    /// If there are no `providers`.
+    #[must_use]
+    pub fn new(tasks: &AsyncRuntime, providers: Vec<ApiProvider>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "profiles need a provider to be looked up with"
+        );
+
+        let upstreams = providers
+            .into_iter()
+            .map(|provider| Upstream::new(tasks, provider))
+            .collect();
+
+        Self {
+            req: reqwest::Client::new(),
+            upstreams,
+            max_retries: DEFAULT_MAX_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            provider_cooldown: DEFAULT_PROVIDER_COOLDOWN,
+            pacer: Arc::new(Mutex::new(RequestPacer::new(DEFAULT_RATE_LIMIT))),
+            cache: Some(Arc::new(Mutex::new(ProfileCache::new(
+                DEFAULT_CACHE_CAPACITY,
+                DEFAULT_CACHE_TTL,
+                DEFAULT_NEGATIVE_CACHE_TTL,
+            )))),
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Caches up to `capacity` profiles, both by username and by UUID, for `ttl` each. A
+    /// capacity of zero disables caching.
+    #[must_use]
+    pub fn with_cache_capacity(self, capacity: usize, ttl: Duration) -> Self {
+        let negative_ttl = self
+            .cache
+            .as_ref()
+            .map_or(DEFAULT_NEGATIVE_CACHE_TTL, |cache| {
+                cache.lock().negative_ttl
+            });
+
+        let cache = NonZeroUsize::new(capacity)
+            .map(|capacity| Arc::new(Mutex::new(ProfileCache::new(capacity, ttl, negative_ttl))));
+
+        Self { cache, ..self }
+    }
+
+    /// Remembers that there is no player with a username or UUID for `ttl`.
+    #[must_use]
+    pub fn with_negative_cache_ttl(self, ttl: Duration) -> Self {
+        if let Some(cache) = &self.cache {
+            cache.lock().negative_ttl = ttl;
+        }
+
+        self
+    }
+
+    /// How often profiles were found in the cache so far.
+    #[must_use]
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.lock().stats())
+            .unwrap_or_default()
+    }
+
+    /// Forgets what is cached about a player, by username or UUID, so they are fetched again the
+    /// next time. Returns whether anything was cached.
+    pub fn invalidate(&self, username_or_uuid: &str) -> bool {
+        let Some(cache) = &self.cache else {
+            return false;
+        };
+
+        let key = Uuid::parse_str(username_or_uuid)
+            .map_or_else(|_| ProfileKey::username(username_or_uuid), ProfileKey::Uuid);
+
+        cache.lock().invalidate(&key)
+    }
+
+    /// Retries requests to a provider that is unavailable or rate limited up to `max_retries`
+    /// times, waiting `initial_backoff` before the first retry and twice as long before each
+    /// further one.
+    #[must_use]
+    pub const fn with_retries(self, max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            ..self
+        }
+    }
+
+    /// Tries a provider that kept failing only after the others for `cooldown`.
+    #[must_use]
+    pub const fn with_provider_cooldown(self, cooldown: Duration) -> Self {
+        Self {
+            provider_cooldown: cooldown,
+            ..self
+        }
+    }
+
+    /// Sends at most `per_second` requests per second, to every provider together, or any number
+    /// of them if `per_second` is zero. Lookups beyond that wait for their turn instead of failing.
+    ///
+    /// This comes on top of the limits of each [`ApiProvider`], and retries wait for their turn
+    /// like any other request.
+    #[must_use]
+    pub fn with_rate_limit(self, per_second: u32) -> Self {
+        Self {
+            pacer: Arc::new(Mutex::new(RequestPacer::new(per_second))),
+            ..self
         }
     }
 
     /// Gets a player's UUID from their username.
     pub async fn get_uuid(&self, username: &str) -> anyhow::Result<Uuid> {
-        let url = self.provider.username_url(username);
-        let json_object = self.response_raw(&url).await?;
+        let json_object = self.data_from_username(username).await?;
+        profile_uuid(&json_object)
+    }
 
-        let id = json_object
-            .get("id")
-            .context("no id in json")?
-            .as_str()
-            .context("id is not a string")?;
+    /// Gets the UUIDs of many players at once, using one request per ten usernames that are not
+    /// cached, of which a few are sent at the same time. Usernames that do not belong to a player
+    /// are missing from the result.
+    pub async fn get_uuids(&self, usernames: &[&str]) -> anyhow::Result<HashMap<String, Uuid>> {
+        let mut uuids = HashMap::with_capacity(usernames.len());
+        let mut uncached = Vec::new();
+
+        for &username in usernames {
+            match self.cached(&ProfileKey::username(username)) {
+                Some(Some(profile)) => {
+                    uuids.insert(username.to_owned(), profile_uuid(&profile)?);
+                }
+                Some(None) => {}
+                None => uncached.push(username),
+            }
+        }
+
+        let mut lookups = stream::iter(uncached.chunks(BULK_LOOKUP_LIMIT))
+            .map(|chunk| self.bulk_lookup(chunk))
+            .buffer_unordered(BULK_LOOKUP_CONCURRENCY);
+
+        while let Some(found) = lookups.next().await {
+            uuids.extend(found?);
+        }
 
-        Uuid::parse_str(id).map_err(Into::into)
+        Ok(uuids)
+    }
+
+    /// Looks up the UUIDs of at most [`BULK_LOOKUP_LIMIT`] usernames in one request, keyed by the
+    /// username as requested, whatever its capitalization.
+    async fn bulk_lookup(&self, usernames: &[&str]) -> anyhow::Result<Vec<(String, Uuid)>> {
+        let body = serde_json::to_string(usernames)?;
+
+        let response = self
+            .fetch(|provider| {
+                self.req
+                    .post(provider.bulk_username_url.as_ref())
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
+            .await?
+            .context("bulk lookups are not supported")?;
+        let profiles = response
+            .as_array()
+            .context("bulk lookup response is not an array")?;
+
+        let mut found = Vec::with_capacity(profiles.len());
+        let mut missing = usernames.to_vec();
+
+        for profile in profiles {
+            let name = profile
+                .get("name")
+                .and_then(Value::as_str)
+                .context("no name in json")?;
+
+            // the API answers with the capitalization of the profile
+            let Some(&username) = usernames
+                .iter()
+                .find(|username| username.eq_ignore_ascii_case(name))
+            else {
+                continue;
+            };
+
+            found.push((username.to_owned(), profile_uuid(profile)?));
+            missing.retain(|&other| other != username);
+
+            self.store(ProfileKey::username(username), Some(profile.clone()));
+        }
+
+        for username in missing {
+            self.store(ProfileKey::username(username), None);
+        }
+
+        Ok(found)
     }
 
     /// Gets a player's username from their UUID.
     pub async fn get_username(&self, uuid: Uuid) -> anyhow::Result<String> {
-        let url = self.provider.uuid_url(&uuid);
-        let json_object = self.response_raw(&url).await?;
+        let json_object = self.data_from_uuid(&uuid).await?;
 
         json_object
             .get("name")
@@ -127,66 +737,807 @@ impl MojangClient {
             .context("Username not found")
     }
 
+    /// Gets a player's skin and cape from their UUID.
+    pub async fn get_textures(&self, uuid: Uuid) -> anyhow::Result<PlayerTextures> {
+        let profile = self.data_from_uuid(&uuid).await?;
+        PlayerTextures::from_profile(&profile)
+    }
+
+    /// Gets the signed skin of a player from their UUID, as it is sent to clients. Players without
+    /// an account, like those on an offline mode server, and players using a default skin have
+    /// none.
+    pub async fn get_skin(&self, uuid: Uuid) -> anyhow::Result<Option<PlayerSkin>> {
+        let profile = self
+            .lookup(ProfileKey::Uuid(uuid), |provider| {
+                self.req.get(provider.uuid_url(&uuid))
+            })
+            .await?;
+
+        let Some(profile) = profile else {
+            return Ok(None);
+        };
+
+        let Some(skin) = textures_property(&profile)? else {
+            return Ok(None);
+        };
+
+        general_purpose::STANDARD
+            .decode(&skin.textures)
+            .context("invalid texture value")?;
+        general_purpose::STANDARD
+            .decode(&skin.signature)
+            .context("invalid signature value")?;
+
+        Ok(Some(skin))
+    }
+
     /// Gets player data from their UUID.
     pub async fn data_from_uuid(&self, uuid: &Uuid) -> anyhow::Result<Value> {
-        let url = self.provider.uuid_url(uuid);
-        self.response_raw(&url).await
+        self.lookup(ProfileKey::Uuid(*uuid), |provider| {
+            self.req.get(provider.uuid_url(uuid))
+        })
+        .await?
+        .with_context(|| format!("there is no player with the UUID {uuid}"))
     }
 
     /// Gets player data from their username.
     pub async fn data_from_username(&self, username: &str) -> anyhow::Result<Value> {
-        let url = self.provider.username_url(username);
-        self.response_raw(&url).await
+        self.lookup(ProfileKey::username(username), |provider| {
+            self.req.get(provider.username_url(username))
+        })
+        .await?
+        .with_context(|| format!("there is no player called {username}"))
     }
 
-    async fn response_raw(&self, url: &str) -> anyhow::Result<Value> {
-        self.rate_limit
+    fn cached(&self, key: &ProfileKey) -> Option<Option<Value>> {
+        self.cache.as_ref()?.lock().get(key)
+    }
+
+    fn store(&self, key: ProfileKey, profile: Option<Value>) {
+        if let Some(cache) = &self.cache {
+            cache.lock().put(key, profile);
+        }
+    }
+
+    /// The profile at `key`, or `None` if there is no such player. It is fetched with the request
+    /// built by `request` if it is not cached, once for everyone looking it up at the same time.
+    async fn lookup(
+        &self,
+        key: ProfileKey,
+        request: impl Fn(&ApiProvider) -> RequestBuilder,
+    ) -> anyhow::Result<Option<Value>> {
+        if let Some(cached) = self.cached(&key) {
+            return Ok(cached);
+        }
+
+        let shared = self
+            .in_flight
+            .lock()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let result = shared
+            .get_or_init(|| async {
+                let profile = self.fetch(&request).await.map_err(|e| format!("{e:#}"));
+
+                // cached before others can no longer find this lookup, so none of them fetch again
+                if let Ok(profile) = &profile {
+                    self.store(key.clone(), profile.clone());
+                }
+
+                profile
+            })
+            .await
+            .clone();
+
+        {
+            let mut in_flight = self.in_flight.lock();
+
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &shared))
+            {
+                in_flight.remove(&key);
+            }
+        }
+
+        result.map_err(|e| anyhow!(e))
+    }
+
+    /// Sends the request built by `request` to each provider in turn, until one answers. Returns
+    /// `None` if there is no such player.
+    async fn fetch(
+        &self,
+        request: impl Fn(&ApiProvider) -> RequestBuilder,
+    ) -> anyhow::Result<Option<Value>> {
+        let mut failures = Vec::new();
+
+        let mut upstreams: Vec<_> = self.upstreams.iter().collect();
+
+        // providers that recently kept failing are only asked if the others fail too. The sort is
+        // stable, so the rest are still asked in order
+        upstreams.sort_by_key(|upstream| upstream.is_cooling_down());
+
+        for upstream in upstreams {
+            let result = self.send_with_retries(upstream, &request).await;
+
+            if matches!(result, Err(RequestError::Transient(_))) {
+                upstream.cool_down(self.provider_cooldown);
+            } else {
+                upstream.recover();
+            }
+
+            match result {
+                Ok(value) => return Ok(Some(value)),
+                Err(RequestError::NotFound) => return Ok(None),
+                Err(RequestError::Permanent(e)) => return Err(e),
+                Err(RequestError::Transient(e)) => {
+                    warn!(
+                        "{} is unavailable, trying the next provider: {e:#}",
+                        upstream.provider.host()
+                    );
+                    failures.push(format!("{}: {e:#}", upstream.provider.host()));
+                }
+            }
+        }
+
+        Err(anyhow!("every provider failed: {}", failures.join("; ")))
+    }
+
+    async fn send_with_retries(
+        &self,
+        upstream: &Upstream,
+        request: &impl Fn(&ApiProvider) -> RequestBuilder,
+    ) -> Result<Value, RequestError> {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+
+        loop {
+            match self.send(upstream, request(&upstream.provider)).await {
+                Err(RequestError::Transient(e)) if retries < self.max_retries => {
+                    warn!("retrying in {backoff:?}: {e:#}");
+                    sleep(backoff).await;
+
+                    backoff *= 2;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        upstream: &Upstream,
+        request: RequestBuilder,
+    ) -> Result<Value, RequestError> {
+        let at = self.pacer.lock().reserve(Instant::now());
+        sleep_until(at.into()).await;
+
+        upstream
+            .rate_limit
             .acquire()
             .await
             .expect("semaphore is never closed")
             .forget();
 
-        if self.rate_limit.available_permits() == 0 {
+        if upstream.rate_limit.available_permits() == 0 {
             warn!(
-                "rate limiting will be applied: {} requests have been sent in the past {:?} \
+                "rate limiting will be applied: {} requests have been sent to {} in the past {:?} \
                  interval",
-                self.provider.max_requests(),
-                self.provider.interval()
+                upstream.provider.max_requests(),
+                upstream.provider.host(),
+                upstream.provider.interval()
             );
         }
 
-        let response = self.req.get(url).send().await?;
+        let response = request
+            .timeout(upstream.provider.timeout)
+            .send()
+            .await
+            .map_err(|e| RequestError::Transient(e.into()))?;
 
-        if response.status().is_success() {
-            let body = response.text().await?;
-            let json_object = serde_json::from_str::<Value>(&body)
-                .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+        let status = response.status();
 
-            if let Some(error) = json_object.get("error") {
-                bail!("API Error: {}", error.as_str().unwrap_or("Unknown error"));
-            };
-            Ok(json_object)
-        } else {
-            bail!("Failed to retrieve data from API");
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            return Err(RequestError::Transient(anyhow!(
+                "API responded with {status}"
+            )));
+        }
+
+        // the official API answers lookups of unknown usernames with no content
+        if status == StatusCode::NOT_FOUND || status == StatusCode::NO_CONTENT {
+            return Err(RequestError::NotFound);
+        }
+
+        if !status.is_success() {
+            return Err(RequestError::Permanent(anyhow!(
+                "Failed to retrieve data from API: {status}"
+            )));
         }
+
+        parse(response).await.map_err(RequestError::Permanent)
+    }
+}
+
+async fn parse(response: reqwest::Response) -> anyhow::Result<Value> {
+    let body = response.text().await?;
+    let json_object = serde_json::from_str::<Value>(&body)
+        .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+
+    if let Some(error) = json_object.get("error") {
+        bail!("API Error: {}", error.as_str().unwrap_or("Unknown error"));
     }
+
+    Ok(json_object)
+}
+
+/// The UUID in the `id` of a profile.
+fn profile_uuid(profile: &Value) -> anyhow::Result<Uuid> {
+    let id = profile
+        .get("id")
+        .context("no id in json")?
+        .as_str()
+        .context("id is not a string")?;
+
+    Uuid::parse_str(id).map_err(Into::into)
 }
 
 #[cfg(test)]
 #[expect(clippy::unwrap_used, reason = "these are tests")]
 mod tests {
-    use std::str::FromStr;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use base64::{Engine as _, engine::general_purpose};
+    use parking_lot::Mutex;
+    use serde_json::json;
 
     use crate::{
         runtime::AsyncRuntime,
-        util::mojang::{ApiProvider, MojangClient},
+        util::mojang::{ApiProvider, CacheStats, MojangClient, PlayerTextures, SkinModel},
     };
 
+    const NOTCH: &str = r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch"}"#;
+
+    /// The request line, like `GET /users/Notch HTTP/1.1`, of every request a server answered.
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Reads a request, including its body if it has one, and returns its request line.
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+
+        loop {
+            let read = stream.read(&mut buf).unwrap();
+            assert!(
+                read > 0,
+                "connection closed before the request was complete"
+            );
+            request.extend_from_slice(&buf[..read]);
+
+            let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+                continue;
+            };
+
+            let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+            let body_len = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map_or(0, |len| len.trim().parse::<usize>().unwrap());
+
+            if request.len() >= end + 4 + body_len {
+                let head = String::from_utf8_lossy(&request[..end]);
+                return head.lines().next().unwrap_or_default().to_owned();
+            }
+        }
+    }
+
+    /// A provider answering every request with `body`, and the requests it answered.
+    fn local_provider(body: &'static str) -> (ApiProvider, Requests) {
+        serve("200 OK", body)
+    }
+
+    /// A provider answering every request with `status` and `body`, and the requests it
+    /// answered.
+    fn serve(status: &'static str, body: &'static str) -> (ApiProvider, Requests) {
+        serve_after(Duration::ZERO, status, body)
+    }
+
+    /// Like [`serve`], but waiting `delay` before answering each request.
+    fn serve_after(
+        delay: Duration,
+        status: &'static str,
+        body: &'static str,
+    ) -> (ApiProvider, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        std::thread::spawn({
+            let requests = requests.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+
+                    let request = read_request(&mut stream);
+                    requests.lock().push(request);
+
+                    std::thread::sleep(delay);
+
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: \
+                         {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+            }
+        });
+
+        let provider = ApiProvider::default()
+            .with_username_url(format!("{base}/users"))
+            .with_uuid_url(format!("{base}/profiles"))
+            .with_bulk_username_url(format!("{base}/bulk"));
+
+        (provider, requests)
+    }
+
+    #[test]
+    fn requests_go_to_the_configured_urls() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = local_provider(NOTCH);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let uuid = tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        tasks.block_on(mojang.data_from_uuid(&uuid)).unwrap();
+
+        assert_eq!(*requests.lock(), [
+            "GET /users/Notch HTTP/1.1",
+            "GET /profiles/069a79f4-44e9-4726-a5be-fca90e38aaf5?unsigned=false HTTP/1.1",
+        ]);
+
+        // by default, the URLs of mowojang are used
+        let provider = ApiProvider::default();
+        assert_eq!(
+            provider.username_url("Notch"),
+            "https://mowojang.matdoes.dev/users/profiles/minecraft/Notch"
+        );
+        assert_eq!(provider.host(), "mowojang.matdoes.dev");
+    }
+
+    #[test]
+    fn lookups_are_cached() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = local_provider(NOTCH);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let first = tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        let second = tasks.block_on(mojang.get_uuid("notch")).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(requests.lock().len(), 1);
+
+        // usernames by UUID are cached separately
+        let name = tasks.block_on(mojang.get_username(first)).unwrap();
+        let name_again = tasks.block_on(mojang.get_username(first)).unwrap();
+        assert_eq!((name.as_str(), name_again.as_str()), ("Notch", "Notch"));
+        assert_eq!(requests.lock().len(), 2);
+    }
+
+    #[test]
+    fn expired_or_disabled_caches_fetch_again() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        for (capacity, ttl) in [(16, Duration::ZERO), (0, Duration::from_secs(60))] {
+            let (provider, requests) = local_provider(NOTCH);
+            let mojang =
+                MojangClient::new(&tasks, vec![provider]).with_cache_capacity(capacity, ttl);
+
+            tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+            tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+            assert_eq!(requests.lock().len(), 2);
+        }
+    }
+
+    #[test]
+    fn missing_players_are_remembered_for_a_shorter_time() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (provider, requests) = serve("404 Not Found", "");
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        assert!(tasks.block_on(mojang.get_uuid("Nobody")).is_err());
+        assert!(tasks.block_on(mojang.get_uuid("nobody")).is_err());
+        assert_eq!(requests.lock().len(), 1);
+
+        let (provider, requests) = serve("404 Not Found", "");
+        let mojang =
+            MojangClient::new(&tasks, vec![provider]).with_negative_cache_ttl(Duration::ZERO);
+
+        assert!(tasks.block_on(mojang.get_uuid("Nobody")).is_err());
+        assert!(tasks.block_on(mojang.get_uuid("Nobody")).is_err());
+        assert_eq!(requests.lock().len(), 2);
+
+        // players that do not exist are not looked up again in bulk either
+        let (provider, requests) = local_provider("[]");
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let uuids = tasks.block_on(mojang.get_uuids(&["Nobody"])).unwrap();
+        assert!(uuids.is_empty());
+        assert!(tasks.block_on(mojang.get_uuid("Nobody")).is_err());
+        assert_eq!(requests.lock().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_lookups_share_a_request() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = serve_after(Duration::from_millis(200), "200 OK", NOTCH);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let uuids = tasks.block_on(async {
+            let mut joins = tokio::task::JoinSet::new();
+
+            for _ in 0..50 {
+                let mojang = mojang.clone();
+                joins.spawn(async move { mojang.get_uuid("Notch").await.unwrap() });
+            }
+
+            joins.join_all().await
+        });
+
+        assert_eq!(uuids.len(), 50);
+        assert!(uuids.iter().all(|&uuid| uuid == uuids[0]));
+        assert_eq!(requests.lock().len(), 1);
+    }
+
+    #[test]
+    fn invalidated_players_are_fetched_again() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = local_provider(NOTCH);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let uuid = tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        tasks.block_on(mojang.get_username(uuid)).unwrap();
+        tasks.block_on(mojang.get_uuid("notch")).unwrap();
+
+        assert_eq!(mojang.cache_stats(), CacheStats {
+            hits: 1,
+            misses: 2,
+            size: 2,
+        });
+
+        // forgetting them by username forgets them by UUID too
+        assert!(mojang.invalidate("NOTCH"));
+        assert_eq!(mojang.cache_stats().size, 0);
+        assert!(!mojang.invalidate(&uuid.to_string()));
+
+        tasks.block_on(mojang.get_username(uuid)).unwrap();
+        assert_eq!(requests.lock().len(), 3);
+        assert!(mojang.invalidate(&uuid.to_string()));
+    }
+
+    #[test]
+    fn unavailable_providers_fall_back() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (fallback, fallback_requests) = local_provider(NOTCH);
+        let (primary, primary_requests) = serve("503 Service Unavailable", "");
+
+        let mojang =
+            MojangClient::new(&tasks, vec![primary, fallback]).with_retries(2, Duration::ZERO);
+
+        let uuid = tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        assert_eq!(
+            uuid.simple().to_string(),
+            "069a79f444e94726a5befca90e38aaf5"
+        );
+
+        // the first attempt and two retries
+        assert_eq!(primary_requests.lock().len(), 3);
+        assert_eq!(fallback_requests.lock().len(), 1);
+    }
+
+    #[test]
+    fn providers_are_tried_in_order() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (first, first_requests) = serve("503 Service Unavailable", "");
+        let (second, second_requests) = serve("500 Internal Server Error", "");
+        let (third, third_requests) = local_provider(NOTCH);
+        let (fourth, fourth_requests) = local_provider(NOTCH);
+
+        let mojang = MojangClient::new(&tasks, vec![first, second, third, fourth])
+            .with_retries(0, Duration::ZERO);
+
+        tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+
+        assert_eq!(first_requests.lock().len(), 1);
+        assert_eq!(second_requests.lock().len(), 1);
+        assert_eq!(third_requests.lock().len(), 1);
+        assert_eq!(fourth_requests.lock().len(), 0);
+    }
+
+    #[test]
+    fn failing_providers_are_asked_last_for_a_while() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (fallback, fallback_requests) = local_provider(NOTCH);
+        let (primary, primary_requests) = serve("503 Service Unavailable", "");
+
+        let mojang =
+            MojangClient::new(&tasks, vec![primary, fallback]).with_retries(0, Duration::ZERO);
+
+        tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        tasks.block_on(mojang.get_uuid("Jeb")).unwrap();
+
+        assert_eq!(primary_requests.lock().len(), 1);
+        assert_eq!(fallback_requests.lock().len(), 2);
+
+        // once the cooldown is over, the primary provider is asked first again
+        let (fallback, fallback_requests) = local_provider(NOTCH);
+        let (primary, primary_requests) = serve("503 Service Unavailable", "");
+
+        let mojang = MojangClient::new(&tasks, vec![primary, fallback])
+            .with_retries(0, Duration::ZERO)
+            .with_provider_cooldown(Duration::ZERO);
+
+        tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+        tasks.block_on(mojang.get_uuid("Jeb")).unwrap();
+
+        assert_eq!(primary_requests.lock().len(), 2);
+        assert_eq!(fallback_requests.lock().len(), 2);
+    }
+
+    #[test]
+    fn rate_limited_lookups_are_spaced_out() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = local_provider(NOTCH);
+        let mojang = MojangClient::new(&tasks, vec![provider]).with_rate_limit(1);
+
+        let start = Instant::now();
+
+        let mut finished = tasks.block_on(async {
+            let mut joins = tokio::task::JoinSet::new();
+
+            // different usernames, so the lookups do not share a request
+            for username in ["Notch", "Jeb", "Dinnerbone"] {
+                let mojang = mojang.clone();
+                joins.spawn(async move {
+                    mojang.get_uuid(username).await.unwrap();
+                    Instant::now()
+                });
+            }
+
+            joins.join_all().await
+        });
+
+        finished.sort();
+
+        assert_eq!(requests.lock().len(), 3);
+        assert!(finished[0] - start < Duration::from_millis(500));
+        assert!(finished[1] - start >= Duration::from_millis(950));
+        assert!(finished[2] - start >= Duration::from_millis(1950));
+    }
+
+    #[test]
+    fn slow_providers_time_out() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (fallback, _) = local_provider(NOTCH);
+        let (primary, primary_requests) = serve_after(Duration::from_secs(1), "200 OK", NOTCH);
+        let primary = primary.with_timeout(Duration::from_millis(50));
+
+        let mojang =
+            MojangClient::new(&tasks, vec![primary, fallback]).with_retries(0, Duration::ZERO);
+
+        let start = Instant::now();
+        tasks.block_on(mojang.get_uuid("Notch")).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(primary_requests.lock().len(), 1);
+    }
+
+    #[test]
+    fn failures_name_every_provider_tried() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let (fallback, fallback_requests) = serve("429 Too Many Requests", "");
+        let (primary, _) = serve("500 Internal Server Error", "");
+        let hosts = [primary.host().to_owned(), fallback.host().to_owned()];
+
+        let mojang =
+            MojangClient::new(&tasks, vec![primary, fallback]).with_retries(0, Duration::ZERO);
+
+        let error = tasks
+            .block_on(mojang.get_uuid("Notch"))
+            .unwrap_err()
+            .to_string();
+
+        for host in hosts {
+            assert!(error.contains(&host), "{error}");
+        }
+        assert_eq!(fallback_requests.lock().len(), 1);
+
+        // players that do not exist are not looked up again elsewhere
+        let (fallback, fallback_requests) = local_provider(NOTCH);
+        let (primary, _) = serve("404 Not Found", "");
+
+        let mojang = MojangClient::new(&tasks, vec![primary, fallback]);
+        assert!(tasks.block_on(mojang.get_uuid("Nobody")).is_err());
+        assert_eq!(fallback_requests.lock().len(), 0);
+    }
+
+    #[test]
+    fn bulk_lookups_are_chunked_and_cached() {
+        const PROFILES: &str = r#"[{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch"}]"#;
+
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let (provider, requests) = local_provider(PROFILES);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let mut usernames = vec!["notch"];
+        let unknown: Vec<_> = (0..11).map(|i| format!("unknown_{i}")).collect();
+        usernames.extend(unknown.iter().map(String::as_str));
+
+        let uuids = tasks.block_on(mojang.get_uuids(&usernames)).unwrap();
+        assert_eq!(requests.lock().len(), 2);
+
+        // unknown usernames are simply missing
+        let notch = uuids["notch"];
+        assert_eq!(uuids.len(), 1);
+        assert_eq!(
+            notch.simple().to_string(),
+            "069a79f444e94726a5befca90e38aaf5"
+        );
+
+        // resolved usernames are cached like single lookups
+        assert_eq!(tasks.block_on(mojang.get_uuid("Notch")).unwrap(), notch);
+        assert_eq!(requests.lock().len(), 2);
+    }
+
+    #[test]
+    fn test_get_uuids() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let mojang = MojangClient::new(&tasks, vec![ApiProvider::MOJANG]);
+
+        let uuids = tasks
+            .block_on(mojang.get_uuids(&["Emerald_Explorer", "Notch", "jeb_"]))
+            .unwrap();
+
+        assert_eq!(uuids.len(), 3);
+        assert_eq!(
+            uuids["Emerald_Explorer"],
+            uuid::Uuid::from_str("86271406-1188-44a5-8496-7af10c906204").unwrap()
+        );
+        assert_eq!(
+            uuids["Notch"],
+            uuid::Uuid::from_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").unwrap()
+        );
+        assert_eq!(
+            uuids["jeb_"],
+            uuid::Uuid::from_str("853c80ef-3c37-49fd-aa49-938b674adae6").unwrap()
+        );
+    }
+
+    #[test]
+    fn textures_are_decoded() {
+        let payload = json!({
+            "profileName": "Alex",
+            "textures": {
+                "SKIN": {
+                    "url": "http://textures.minecraft.net/texture/skin",
+                    "metadata": { "model": "slim" },
+                },
+                "CAPE": { "url": "http://textures.minecraft.net/texture/cape" },
+            },
+        });
+        let value = general_purpose::STANDARD.encode(payload.to_string());
+
+        let profile = json!({
+            "id": "069a79f444e94726a5befca90e38aaf5",
+            "properties": [{ "name": "textures", "value": value, "signature": "c2lnbmVk" }],
+        });
+
+        let textures = PlayerTextures::from_profile(&profile).unwrap();
+        assert_eq!(
+            textures.skin_url.as_deref(),
+            Some("http://textures.minecraft.net/texture/skin")
+        );
+        assert_eq!(
+            textures.cape_url.as_deref(),
+            Some("http://textures.minecraft.net/texture/cape")
+        );
+        assert_eq!(textures.model, SkinModel::Slim);
+        assert_eq!(textures.property.textures, value);
+        assert_eq!(textures.property.signature, "c2lnbmVk");
+
+        // default skins have no textures at all
+        let payload = json!({ "textures": {} }).to_string();
+        let profile = json!({
+            "properties": [{
+                "name": "textures",
+                "value": general_purpose::STANDARD.encode(payload),
+                "signature": "",
+            }],
+        });
+
+        let textures = PlayerTextures::from_profile(&profile).unwrap();
+        assert_eq!((textures.skin_url, textures.cape_url), (None, None));
+        assert_eq!(textures.model, SkinModel::Classic);
+
+        assert!(PlayerTextures::from_profile(&json!({ "properties": [] })).is_err());
+    }
+
+    #[test]
+    fn players_without_an_account_have_no_skin() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let uuid = uuid::Uuid::from_u128(1);
+
+        let (provider, _) = serve("404 Not Found", "");
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+        assert!(tasks.block_on(mojang.get_skin(uuid)).unwrap().is_none());
+
+        let (provider, _) = local_provider(
+            r#"{"properties":[{"name":"textures","value":"e30=","signature":"c2lnbmVk"}]}"#,
+        );
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+
+        let skin = tasks.block_on(mojang.get_skin(uuid)).unwrap().unwrap();
+        assert_eq!(
+            (skin.textures.as_str(), skin.signature.as_str()),
+            ("e30=", "c2lnbmVk")
+        );
+
+        // the property must hold base64, as clients would fail to decode it
+        let (provider, _) =
+            local_provider(r#"{"properties":[{"name":"textures","value":"%","signature":""}]}"#);
+        let mojang = MojangClient::new(&tasks, vec![provider]);
+        assert!(tasks.block_on(mojang.get_skin(uuid)).is_err());
+    }
+
+    #[test]
+    fn test_get_textures() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+        let mojang = MojangClient::new(&tasks, vec![ApiProvider::MAT_DOES_DEV]);
+
+        let textures = tasks
+            .block_on(mojang.get_textures(
+                uuid::Uuid::from_str("86271406-1188-44a5-8496-7af10c906204").unwrap(),
+            ))
+            .unwrap();
+
+        let skin_url = textures.skin_url.unwrap();
+        assert!(skin_url.starts_with("http://textures.minecraft.net/texture/"));
+        assert!(!textures.property.signature.is_empty());
+    }
+
     #[test]
     fn test_get_uuid() {
         let (tx, _rx) = kanal::bounded(1);
         let tasks = AsyncRuntime::new(tx);
-        let mojang = MojangClient::new(&tasks, ApiProvider::MAT_DOES_DEV);
+        let mojang = MojangClient::new(&tasks, vec![ApiProvider::MAT_DOES_DEV]);
 
         let uuid = tasks.block_on(mojang.get_uuid("Emerald_Explorer")).unwrap();
         let expected = uuid::Uuid::from_str("86271406-1188-44a5-8496-7af10c906204").unwrap();
@@ -197,7 +1548,7 @@ mod tests {
     fn test_get_username() {
         let (tx, _rx) = kanal::bounded(1);
         let tasks = AsyncRuntime::new(tx);
-        let mojang = MojangClient::new(&tasks, ApiProvider::MAT_DOES_DEV);
+        let mojang = MojangClient::new(&tasks, vec![ApiProvider::MAT_DOES_DEV]);
 
         let username = tasks
             .block_on(mojang.get_username(
@@ -211,7 +1562,7 @@ mod tests {
     fn test_retrieve_username() {
         let (tx, _rx) = kanal::bounded(1);
         let tasks = AsyncRuntime::new(tx);
-        let mojang = MojangClient::new(&tasks, ApiProvider::MAT_DOES_DEV);
+        let mojang = MojangClient::new(&tasks, vec![ApiProvider::MAT_DOES_DEV]);
 
         let res = tasks
             .block_on(mojang.data_from_uuid(
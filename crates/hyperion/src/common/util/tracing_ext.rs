@@ -1,5 +1,9 @@
+use std::sync::OnceLock;
+
 use flecs_ecs::core::{ComponentId, EntityView, QueryTuple, SystemAPI, builder};
 use tracing::Span;
+
+use crate::profiler::PROFILER;
 // SystemAPI<'a, P, T>: Builder<'a> + private::internal_SystemAPI<'a, P, T>
 // where
 //     T: QueryTuple,
@@ -19,9 +23,12 @@ where
     where
         Func: FnMut(EntityView<'_>, T::TupleType<'_>) + 'static,
     {
+        let name = OnceLock::new();
+
         self.run_each_entity(
             move |mut iter| {
                 let _enter = span.enter();
+                let _timer = PROFILER.time_system(|| iter.system().name(), &name);
 
                 while iter.next() {
                     iter.each();
@@ -39,9 +46,12 @@ where
     where
         Func: FnMut(T::TupleType<'_>) + 'static,
     {
+        let name = OnceLock::new();
+
         self.run_each(
             move |mut iter| {
                 let _enter = span.enter();
+                let _timer = PROFILER.time_system(|| iter.system().name(), &name);
 
                 while iter.next() {
                     iter.each();
@@ -0,0 +1,372 @@
+//! See [`Whitelist`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flecs_ecs::macros::Component;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::util::mojang::MojangClient;
+
+/// The players allowed to join while the whitelist is enabled, saved as JSON every time it
+/// changes.
+///
+/// Players can be added by name before anyone knows their UUID. Such an entry turns into a UUID
+/// entry as soon as the UUID is known, either from a [`NameResolver`] or from the player joining.
+#[derive(Component, Debug, Default)]
+pub struct Whitelist {
+    // logins are processed in parallel, and any of them may resolve a name entry
+    entries: RwLock<Entries>,
+    /// Where the whitelist is saved, or `None` if it only lives in memory.
+    path: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Entries {
+    enabled: bool,
+    /// Players by UUID, with the name they were added with.
+    players: BTreeMap<Uuid, String>,
+    /// Lowercase names that have not been resolved to a UUID yet.
+    names: BTreeSet<String>,
+}
+
+/// A player to add to the [`Whitelist`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WhitelistEntry {
+    Player {
+        uuid: Uuid,
+        name: String,
+    },
+    /// A player whose UUID is not known yet.
+    Name(String),
+}
+
+/// Finds the UUID of a player by name, and the name of a player by UUID, as [`MojangClient`]
+/// does.
+pub trait NameResolver {
+    fn resolve(&self, name: &str) -> impl Future<Output = anyhow::Result<Uuid>> + Send;
+
+    fn username(&self, uuid: Uuid) -> impl Future<Output = anyhow::Result<String>> + Send;
+}
+
+impl NameResolver for MojangClient {
+    fn resolve(&self, name: &str) -> impl Future<Output = anyhow::Result<Uuid>> + Send {
+        self.get_uuid(name)
+    }
+
+    fn username(&self, uuid: Uuid) -> impl Future<Output = anyhow::Result<String>> + Send {
+        self.get_username(uuid)
+    }
+}
+
+/// The entry for the player called `name`. If `resolver` cannot find their UUID, they are added
+/// by name until they join.
+pub async fn resolve_entry(resolver: &impl NameResolver, name: &str) -> WhitelistEntry {
+    match resolver.resolve(name).await {
+        Ok(uuid) => WhitelistEntry::Player {
+            uuid,
+            name: name.to_owned(),
+        },
+        Err(e) => {
+            warn!("failed to resolve the UUID of {name}, whitelisting them by name: {e}");
+            WhitelistEntry::Name(name.to_owned())
+        }
+    }
+}
+
+/// The entry for the player with `uuid`, under the name they go by. Fails if `resolver` does not
+/// know the player, since a UUID nobody plays with is most likely a typo.
+pub async fn resolve_uuid_entry(
+    resolver: &impl NameResolver,
+    uuid: Uuid,
+) -> anyhow::Result<WhitelistEntry> {
+    let name = resolver.username(uuid).await?;
+
+    Ok(WhitelistEntry::Player { uuid, name })
+}
+
+impl Whitelist {
+    /// Loads the whitelist saved at `path`, or creates an empty, disabled one that will be saved
+    /// there.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(path)?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse the whitelist at {path:?}"))?
+        } else {
+            info!("whitelist not found, starting with an empty one");
+            Entries::default()
+        };
+
+        Ok(Self {
+            entries: RwLock::new(entries),
+            path: Some(path.to_owned()),
+        })
+    }
+
+    fn save(&self, entries: &Entries) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(entries)?)
+            .with_context(|| format!("failed to save the whitelist to {path:?}"))
+    }
+
+    /// Applies `f` to the entries and saves them if it returns `true`.
+    fn update(&self, f: impl FnOnce(&mut Entries) -> bool) -> anyhow::Result<bool> {
+        let mut entries = self.entries.write();
+
+        if !f(&mut entries) {
+            return Ok(false);
+        }
+
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.entries.read().enabled
+    }
+
+    /// Turns enforcement on or off. Returns whether that changed anything.
+    pub fn set_enabled(&self, enabled: bool) -> anyhow::Result<bool> {
+        self.update(|entries| std::mem::replace(&mut entries.enabled, enabled) != enabled)
+    }
+
+    /// Returns whether the player was not already on the whitelist.
+    pub fn add(&self, entry: WhitelistEntry) -> anyhow::Result<bool> {
+        self.update(|entries| match entry {
+            WhitelistEntry::Player { uuid, name } => {
+                entries.names.remove(&name.to_lowercase());
+                entries.players.insert(uuid, name).is_none()
+            }
+            WhitelistEntry::Name(name) => {
+                let name = name.to_lowercase();
+
+                let known = entries
+                    .players
+                    .values()
+                    .any(|player| player.to_lowercase() == name);
+
+                !known && entries.names.insert(name)
+            }
+        })
+    }
+
+    /// Removes the player with the given name or UUID. Returns whether they were on the
+    /// whitelist.
+    pub fn remove(&self, player: &str) -> anyhow::Result<bool> {
+        let uuid = Uuid::parse_str(player).ok();
+        let name = player.to_lowercase();
+
+        self.update(|entries| {
+            let before = entries.players.len();
+
+            entries
+                .players
+                .retain(|&id, player| Some(id) != uuid && player.to_lowercase() != name);
+
+            let removed_name = entries.names.remove(&name);
+
+            removed_name || entries.players.len() != before
+        })
+    }
+
+    /// Every whitelisted player, as a name and the UUID if it is known.
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, Option<Uuid>)> {
+        let entries = self.entries.read();
+
+        let players = entries
+            .players
+            .iter()
+            .map(|(&uuid, name)| (name.clone(), Some(uuid)));
+
+        let names = entries.names.iter().map(|name| (name.clone(), None));
+
+        players.chain(names).collect()
+    }
+
+    /// Returns whether a player may join. A name entry matching the player is turned into an
+    /// entry for their UUID, and a player who changed their name is listed under the new one.
+    #[must_use]
+    pub fn admits(&self, uuid: Uuid, name: &str) -> bool {
+        {
+            let entries = self.entries.read();
+
+            if !entries.enabled {
+                return true;
+            }
+
+            match entries.players.get(&uuid) {
+                Some(known) if known == name => return true,
+                None if !entries.names.contains(&name.to_lowercase()) => return false,
+                // a new name for a known player, or a name entry to resolve
+                _ => {}
+            }
+        }
+
+        let entry = WhitelistEntry::Player {
+            uuid,
+            name: name.to_owned(),
+        };
+
+        if let Err(e) = self.add(entry) {
+            warn!("failed to save the resolved whitelist entry of {name}: {e}");
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::ready;
+
+    use uuid::Uuid;
+
+    use super::{NameResolver, Whitelist, WhitelistEntry, resolve_entry, resolve_uuid_entry};
+    use crate::runtime::AsyncRuntime;
+
+    const NOTCH: Uuid = Uuid::from_u128(0x069a_79f4_44e9_4726_a5be_fca9_0e38_aaf5);
+
+    /// Knows Notch and nobody else.
+    struct MockMojang;
+
+    impl NameResolver for MockMojang {
+        fn resolve(&self, name: &str) -> impl Future<Output = anyhow::Result<Uuid>> + Send {
+            let uuid = if name == "Notch" {
+                Ok(NOTCH)
+            } else {
+                Err(anyhow::anyhow!("no such player"))
+            };
+
+            ready(uuid)
+        }
+
+        fn username(&self, uuid: Uuid) -> impl Future<Output = anyhow::Result<String>> + Send {
+            let name = if uuid == NOTCH {
+                Ok("Notch".to_owned())
+            } else {
+                Err(anyhow::anyhow!("no such player"))
+            };
+
+            ready(name)
+        }
+    }
+
+    #[test]
+    fn only_whitelisted_players_join() {
+        let whitelist = Whitelist::default();
+        let stranger = Uuid::from_u128(1);
+
+        assert!(whitelist.admits(stranger, "Stranger"));
+
+        whitelist.set_enabled(true).unwrap();
+        whitelist
+            .add(WhitelistEntry::Player {
+                uuid: NOTCH,
+                name: "Notch".to_owned(),
+            })
+            .unwrap();
+
+        assert!(whitelist.admits(NOTCH, "Notch"));
+        assert!(!whitelist.admits(stranger, "Stranger"));
+
+        // name entries let anyone with that name in, and then only them
+        whitelist
+            .add(WhitelistEntry::Name("Stranger".to_owned()))
+            .unwrap();
+
+        assert!(whitelist.admits(stranger, "stranger"));
+        assert!(!whitelist.admits(Uuid::from_u128(2), "Stranger"));
+
+        whitelist.remove("Stranger").unwrap();
+        assert!(!whitelist.admits(stranger, "Stranger"));
+    }
+
+    #[test]
+    fn names_resolve_to_uuids() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let whitelist = Whitelist::default();
+        whitelist.set_enabled(true).unwrap();
+
+        let notch = tasks.block_on(resolve_entry(&MockMojang, "Notch"));
+        let unknown = tasks.block_on(resolve_entry(&MockMojang, "Unknown"));
+
+        assert_eq!(unknown, WhitelistEntry::Name("Unknown".to_owned()));
+
+        whitelist.add(notch).unwrap();
+        whitelist.add(unknown).unwrap();
+
+        assert_eq!(whitelist.entries(), [
+            ("Notch".to_owned(), Some(NOTCH)),
+            ("unknown".to_owned(), None),
+        ]);
+
+        // someone else calling themselves Notch is not Notch
+        assert!(!whitelist.admits(Uuid::from_u128(3), "Notch"));
+    }
+
+    #[test]
+    fn players_added_by_uuid_are_listed_by_name() {
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let whitelist = Whitelist::default();
+        whitelist.set_enabled(true).unwrap();
+
+        let notch = tasks
+            .block_on(resolve_uuid_entry(&MockMojang, NOTCH))
+            .unwrap();
+        assert!(
+            tasks
+                .block_on(resolve_uuid_entry(&MockMojang, Uuid::from_u128(4)))
+                .is_err()
+        );
+
+        whitelist.add(notch).unwrap();
+        assert_eq!(whitelist.entries(), [("Notch".to_owned(), Some(NOTCH))]);
+
+        // joining under a new name updates the entry
+        assert!(whitelist.admits(NOTCH, "NotNotch"));
+        assert_eq!(whitelist.entries(), [("NotNotch".to_owned(), Some(NOTCH))]);
+    }
+
+    #[test]
+    fn changes_are_saved() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("whitelist.json");
+
+        let whitelist = Whitelist::load(&path)?;
+        whitelist.set_enabled(true)?;
+        whitelist.add(WhitelistEntry::Name("Notch".to_owned()))?;
+
+        // joining turns the name into a UUID, which is saved too
+        assert!(whitelist.admits(NOTCH, "Notch"));
+
+        let loaded = Whitelist::load(&path)?;
+
+        assert!(loaded.is_enabled());
+        assert_eq!(loaded.entries(), [("Notch".to_owned(), Some(NOTCH))]);
+
+        Ok(())
+    }
+}
@@ -3,6 +3,11 @@
 use ouroboros::self_referencing;
 use valence_protocol::{Encode, RawBytes, VarInt, packets::play};
 
+use crate::simulation::{
+    event::Posture,
+    metadata::{Metadata, Pose, encode_value},
+};
+
 #[self_referencing]
 pub struct ShowAll {
     bytes: Vec<u8>,
@@ -42,3 +47,82 @@ pub fn show_all(id: i32) -> ShowAll {
     }
     .build()
 }
+
+#[self_referencing]
+pub struct PostureMetadata {
+    bytes: Vec<u8>,
+    #[borrows(bytes)]
+    #[covariant]
+    pub packet: play::EntityTrackerUpdateS2c<'this>,
+}
+
+/// Packet to show an entity in `posture`, such as after a
+/// [`crate::simulation::event::PostureUpdate`].
+#[must_use]
+pub fn posture_metadata(entity_id: VarInt, posture: Posture) -> PostureMetadata {
+    let mut bytes = vec![Pose::INDEX];
+    bytes.extend(encode_value(Pose::from(posture)));
+
+    // end with 0xff
+    bytes.push(0xff);
+
+    PostureMetadataBuilder {
+        bytes,
+        packet_builder: |bytes| play::EntityTrackerUpdateS2c {
+            entity_id,
+            tracked_values: RawBytes(bytes),
+        },
+    }
+    .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{Decode, Packet, VarInt, packets::play};
+
+    use super::posture_metadata;
+    use crate::simulation::{event::Posture, metadata::Pose};
+
+    #[test]
+    fn postures_map_to_the_pose_with_the_same_id() {
+        let postures = [
+            Posture::Standing,
+            Posture::FallFlying,
+            Posture::Sleeping,
+            Posture::Swimming,
+            Posture::SpinAttack,
+            Posture::Sneaking,
+            Posture::LongJumping,
+            Posture::Dying,
+            Posture::Croaking,
+            Posture::UsingTongue,
+            Posture::Sitting,
+            Posture::Roaring,
+            Posture::Sniffing,
+            Posture::Emerging,
+            Posture::Digging,
+        ];
+
+        for posture in postures {
+            assert_eq!(i32::from(Pose::from(posture) as u8), posture as i32);
+        }
+    }
+
+    #[test]
+    fn sneaking_is_pose_5_at_index_6() {
+        let metadata = posture_metadata(VarInt(42), Posture::Sneaking);
+
+        let mut bytes = Vec::new();
+        metadata.borrow_packet().encode_with_id(&mut bytes).unwrap();
+
+        let mut r = bytes.as_slice();
+        assert_eq!(
+            VarInt::decode(&mut r).unwrap().0,
+            play::EntityTrackerUpdateS2c::ID
+        );
+        assert_eq!(VarInt::decode(&mut r).unwrap().0, 42);
+
+        // index 6, type 20 (pose), pose 5 (sneaking), end of metadata
+        assert_eq!(r, [6, 20, 5, 0xff]);
+    }
+}
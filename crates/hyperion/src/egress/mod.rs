@@ -2,10 +2,14 @@ use byteorder::WriteBytesExt;
 use flecs_ecs::prelude::*;
 use hyperion_proto::{Flush, ServerToProxyMessage, UpdatePlayerChunkPositions};
 use rkyv::util::AlignedVec;
-use tracing::{error, info_span};
+use tracing::{error, info_span, warn};
 use valence_protocol::{VarInt, packets::play};
 
-use crate::{net::Compose, simulation::EgressComm};
+use crate::{
+    config::ServerConfig,
+    net::{Compose, NetworkMetrics},
+    simulation::EgressComm,
+};
 
 pub mod metadata;
 pub mod player_join;
@@ -20,6 +24,7 @@ use sync_entity_state::EntityStateSyncModule;
 
 use crate::{
     net::NetworkStreamRef,
+    profiler::PROFILER,
     simulation::{ChunkPosition, blocks::Blocks},
     system_registry::SystemId,
 };
@@ -55,6 +60,20 @@ impl Module for EgressModule {
         world.import::<SyncChunksModule>();
         world.import::<EntityStateSyncModule>();
 
+        system!(
+            "update_light",
+            world,
+            &mut Blocks($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(|_, _, blocks| {
+            let span = info_span!("update_light");
+            let _enter = span.enter();
+            let _timer = PROFILER.time("update_light");
+
+            blocks.update_light();
+        });
+
         system!(
             "broadcast_chunk_deltas",
             world,
@@ -66,16 +85,44 @@ impl Module for EgressModule {
         .each_iter(move |it: TableIter<'_, false>, _, (compose, mc)| {
             let span = info_span!("broadcast_chunk_deltas");
             let _enter = span.enter();
+            let _timer = PROFILER.time("broadcast_chunk_deltas");
 
             let world = it.world();
 
             mc.for_each_to_update_mut(|chunk| {
+                let center = chunk.position;
+
+                if chunk.needs_resend() {
+                    let packet = chunk.resend_packet();
+                    let broadcast = compose.broadcast_local(packet, center, SystemId(99));
+                    if let Err(e) = broadcast.send(&world) {
+                        error!("failed to resend chunk: {e}");
+                    }
+                    return;
+                }
+
                 for packet in chunk.delta_drain_packets() {
-                    if let Err(e) = compose.broadcast(packet, SystemId(99)).send(&world) {
+                    let broadcast = compose.broadcast_local(packet, center, SystemId(99));
+                    if let Err(e) = broadcast.send(&world) {
                         error!("failed to send chunk delta packet: {e}");
                         return;
                     }
                 }
+
+                for packet in chunk.block_entity_packets() {
+                    let broadcast = compose.broadcast_local(&packet, center, SystemId(99));
+                    if let Err(e) = broadcast.send(&world) {
+                        error!("failed to send block entity packet: {e}");
+                    }
+                }
+                chunk.reset_tick_block_entities();
+
+                if let Some(packet) = chunk.light_drain_packet() {
+                    let broadcast = compose.broadcast_local(&packet, center, SystemId(99));
+                    if let Err(e) = broadcast.send(&world) {
+                        error!("failed to send light update packet: {e}");
+                    }
+                }
             });
             mc.clear_should_update();
 
@@ -94,6 +141,38 @@ impl Module for EgressModule {
             }
         });
 
+        world.component::<NetworkMetrics>();
+        world.set(NetworkMetrics::default());
+
+        system!(
+            "record_network_metrics",
+            world,
+            &Compose($),
+            &mut NetworkMetrics($),
+            &ServerConfig($),
+        )
+        .kind_id(pipeline)
+        .each(|(compose, metrics, config)| {
+            let tick = metrics.take_snapshot(compose.io_buf());
+            let total = tick.total();
+
+            if let Some(budget) = config.tick_byte_budget
+                && total.bytes > budget
+            {
+                warn!(
+                    "a tick queued {} bytes in {} messages for the proxy, over the budget of \
+                     {budget}: {} bytes unicast, {} bytes multicast, {} bytes broadcast and {} \
+                     bytes broadcast locally",
+                    total.bytes,
+                    total.frames,
+                    tick.unicast.bytes,
+                    tick.multicast.bytes,
+                    tick.broadcast.bytes,
+                    tick.broadcast_local.bytes,
+                );
+            }
+        });
+
         let player_location_query = world.new_query::<(&NetworkStreamRef, &ChunkPosition)>();
 
         system!(
@@ -106,6 +185,7 @@ impl Module for EgressModule {
         .each(move |(compose, egress)| {
             let span = info_span!("egress");
             let _enter = span.enter();
+            let _timer = PROFILER.time("egress");
 
             {
                 let span = info_span!("chunk_positions");
@@ -115,7 +195,7 @@ impl Module for EgressModule {
                 let mut positions = Vec::new();
 
                 player_location_query.each(|(io, pos)| {
-                    stream.push(io.inner());
+                    stream.push(io.id());
 
                     let position = hyperion_proto::ChunkPosition {
                         x: i16::try_from(pos.position.x).unwrap(),
@@ -1,13 +1,14 @@
-use std::{borrow::Cow, collections::BTreeSet};
+use std::{borrow::Cow, collections::BTreeSet, time::Instant};
 
 use anyhow::Context;
 use flecs_ecs::prelude::*;
-use hyperion_crafting::{Action, CraftingRegistry, RecipeBookState};
+use hyperion_crafting::{CraftingRegistry, RecipeBook};
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tracing::{info, instrument};
 use valence_protocol::{
-    ByteAngle, GameMode, Ident, PacketEncoder, RawBytes, VarInt, Velocity,
+    ByteAngle, Ident, PacketEncoder, RawBytes, VarInt, Velocity,
     game_mode::OptGameMode,
     ident,
     packets::play::{
@@ -26,17 +27,29 @@ mod list;
 pub use list::*;
 
 use crate::{
-    config::Config,
+    config::ServerConfig,
     egress::metadata::show_all,
     ingress::PendingRemove,
     net::{Compose, DataBundle, NetworkStreamRef},
+    profiler::PROFILER,
     simulation::{
         Comms, Name, Position, Uuid, Yaw,
-        command::{Command, ROOT_COMMAND, get_command_packet},
+        command::{Command, Permissions, ROOT_COMMAND, get_command_packet_for},
+        entity::EntityMetadata,
+        game_mode,
+        keep_alive::{KeepAlive, Ping},
         metadata::{EntityFlags, MetadataBuilder},
+        persistence,
+        roster::PlayerRoster,
+        scoreboard::{Scoreboard, ScoreboardTeams},
         skin::PlayerSkin,
+        spawn::SpawnPoint,
+        time::WorldTime,
         util::registry_codec_raw,
+        weather::Weather,
+        world_border::WorldBorder,
     },
+    storage::PlayerDataHandler,
     system_registry::{PLAYER_JOINS, SystemId},
     util::{SendableQuery, SendableRef},
 };
@@ -67,9 +80,17 @@ pub fn player_join_world(
         &Pitch,
         &PlayerSkin,
         &EntityFlags,
+        Option<&EntityMetadata>,
     )>,
     crafting_registry: &CraftingRegistry,
-    config: &Config,
+    config: &ServerConfig,
+    roster: &PlayerRoster,
+    time: &WorldTime,
+    weather: &Weather,
+    border: &WorldBorder,
+    scoreboard: &Scoreboard,
+    teams: &ScoreboardTeams,
+    spawn: &SpawnPoint,
 ) -> anyhow::Result<()> {
     static CACHED_DATA: once_cell::sync::OnceCell<bytes::Bytes> = once_cell::sync::OnceCell::new();
 
@@ -86,6 +107,10 @@ pub fn player_join_world(
         .map(|value| value.name.as_str_ident().into())
         .collect();
 
+    let mode = entity
+        .try_get::<&game_mode::GameMode>(|mode| mode.0)
+        .unwrap_or_default();
+
     let dimension_name = ident!("overworld");
     // let dimension_name: Ident<Cow<str>> = chunk_layer.dimension_type_name().into();
 
@@ -101,11 +126,11 @@ pub fn player_join_world(
         enable_respawn_screen: false,
         dimension_name: dimension_name.into(),
         hashed_seed: 0,
-        game_mode: GameMode::Survival,
+        game_mode: mode,
         is_flat: false,
         last_death_location: None,
         portal_cooldown: 60.into(),
-        previous_game_mode: OptGameMode(Some(GameMode::Survival)),
+        previous_game_mode: OptGameMode(Some(mode)),
         dimension_type_name: ident!("minecraft:overworld").into(),
         is_debug: false,
     };
@@ -128,10 +153,8 @@ pub fn player_join_world(
     //
     // compose.io_buf().unicast_raw(chunk, io, system_id, world);
 
-    let pkt = play::PlayerSpawnPositionS2c {
-        position: position.as_dvec3().into(),
-        angle: **yaw,
-    };
+    // compasses point to the world spawn rather than wherever the player joined
+    let pkt = spawn.packet();
 
     bundle.add_packet(&pkt, world)?;
 
@@ -159,6 +182,17 @@ pub fn player_join_world(
 
     bundle.add_raw(&cached_data);
 
+    bundle.add_packet(&time.packet(), world)?;
+    weather.add_join_packets(&mut bundle, world)?;
+    border.add_join_packets(&mut bundle, world)?;
+    teams.add_join_packets(&mut bundle, world)?;
+    scoreboard.add_join_packets(&mut bundle, world)?;
+
+    // the recipes themselves are cached above, but which are unlocked differs per player
+    let recipe_book = RecipeBook::new(crafting_registry);
+    bundle.add_packet(&recipe_book.init_packet(), world)?;
+    entity.set(recipe_book);
+
     let text = play::GameMessageS2c {
         chat: format!("{name} joined the world").into_cow_text(),
         overlay: false,
@@ -180,42 +214,65 @@ pub fn player_join_world(
         world,
     )?;
 
+    // a returning player keeps the hotbar slot they had selected
+    let hotbar_slot = entity.try_get::<&PlayerInventory>(PlayerInventory::get_cursor_hand_slot);
+
+    if let Some(slot) = hotbar_slot {
+        bundle.add_packet(
+            &play::UpdateSelectedSlotS2c {
+                slot: u8::try_from(slot)?,
+            },
+            world,
+        )?;
+    }
+
     let mut entries = Vec::new();
     let mut all_player_names = Vec::new();
 
-    let count = query.iter_stage(world).count();
+    let count = roster.count();
 
     info!("sending skins for {count} players");
 
     {
         let scope = tracing::info_span!("generating_skins");
         let _enter = scope.enter();
-        query
-            .iter_stage(world)
-            .each(|(uuid, name, _, _, _, _skin, _)| {
-                // todo: in future, do not clone
-
-                let entry = PlayerListEntry {
-                    player_uuid: uuid.0,
-                    username: name.to_string().into(),
-                    // todo: eliminate alloc
-                    properties: Cow::Owned(vec![]),
-                    chat_data: None,
-                    listed: true,
-                    ping: 20,
-                    game_mode: GameMode::Creative,
-                    display_name: Some(name.to_string().into_cow_text()),
-                };
 
-                entries.push(entry);
-                all_player_names.push(name.to_string());
-            });
+        // the joining player is sent separately below
+        for player in roster.iter().filter(|player| player.entity != entity.id()) {
+            // players without a skin are shown with a default one
+            let player_entity = world.entity_from_id(player.entity);
+
+            let properties = player_entity
+                .try_get::<&PlayerSkin>(PlayerSkin::properties)
+                .unwrap_or_default();
+
+            let ping = player_entity
+                .try_get::<&Ping>(Ping::millis)
+                .unwrap_or_default();
+
+            let game_mode = player_entity
+                .try_get::<&game_mode::GameMode>(|mode| mode.0)
+                .unwrap_or_default();
+
+            let entry = PlayerListEntry {
+                player_uuid: player.uuid,
+                username: Cow::Borrowed(&player.name),
+                properties: Cow::Owned(properties),
+                chat_data: None,
+                listed: true,
+                ping,
+                game_mode,
+                display_name: Some(player.name.to_string().into_cow_text()),
+            };
+
+            entries.push(entry);
+            all_player_names.push(&*player.name);
+        }
     }
 
-    let all_player_names = all_player_names.iter().map(String::as_str).collect();
-
     let actions = PlayerListActions::default()
         .with_add_player(true)
+        .with_update_game_mode(true)
         .with_update_listed(true)
         .with_update_display_name(true);
 
@@ -241,9 +298,8 @@ pub fn player_join_world(
 
         let mut metadata = MetadataBuilder::default();
 
-        query
-            .iter_stage(world)
-            .each_iter(|it, idx, (uuid, _, position, yaw, pitch, _, flags)| {
+        query.iter_stage(world).each_iter(
+            |it, idx, (uuid, _, position, yaw, pitch, _, flags, entity_metadata)| {
                 let mut result = || {
                     let query_entity = it.entity(idx);
 
@@ -270,6 +326,10 @@ pub fn player_join_world(
 
                     metadata.encode(*flags);
 
+                    if let Some(entity_metadata) = entity_metadata {
+                        entity_metadata.add_all_to(&mut metadata);
+                    }
+
                     if let Some(view) = metadata.get_and_clear() {
                         let pkt = play::EntityTrackerUpdateS2c {
                             entity_id: VarInt(query_entity.minecraft_id()),
@@ -287,7 +347,8 @@ pub fn player_join_world(
                 if let Err(e) = result() {
                     query_errors.push(e);
                 }
-            });
+            },
+        );
 
         if !query_errors.is_empty() {
             return Err(anyhow::anyhow!(
@@ -296,28 +357,16 @@ pub fn player_join_world(
         }
     }
 
-    let PlayerSkin {
-        textures,
-        signature,
-    } = skin.clone();
-
-    // todo: in future, do not clone
-    let property = valence_protocol::profile::Property {
-        name: "textures".to_string(),
-        value: textures,
-        signature: Some(signature),
-    };
-
-    let property = &[property];
+    let properties = skin.properties();
 
     let singleton_entry = &[PlayerListEntry {
         player_uuid: uuid,
         username: Cow::Borrowed(name),
-        properties: Cow::Borrowed(property),
+        properties: Cow::Borrowed(&properties),
         chat_data: None,
         listed: true,
         ping: 20,
-        game_mode: GameMode::Survival,
+        game_mode: mode,
         display_name: Some(name.to_string().into_cow_text()),
     }];
 
@@ -384,7 +433,10 @@ pub fn player_join_world(
         )
         .context("failed to send team packet")?;
 
-    let command_packet = get_command_packet(world, root_command);
+    let permissions = entity
+        .try_get::<&Permissions>(|permissions| *permissions)
+        .unwrap_or_default();
+    let command_packet = get_command_packet_for(world, root_command, permissions);
 
     bundle.add_packet(&command_packet, world)?;
 
@@ -396,9 +448,7 @@ pub fn player_join_world(
 }
 
 fn send_sync_tags(encoder: &mut PacketEncoder) -> anyhow::Result<()> {
-    let bytes = include_bytes!("data/tags.json");
-
-    let groups = serde_json::from_slice(bytes)?;
+    let groups = serde_json::from_slice(hyperion_crafting::VANILLA_TAGS)?;
 
     let pkt = play::SynchronizeTagsS2c { groups };
 
@@ -473,19 +523,6 @@ fn generate_cached_packet_bytes(
         encoder.append_packet(&pkt)?;
     }
 
-    // unlock
-    let pkt = hyperion_crafting::UnlockRecipesS2c {
-        action: Action::Init,
-        crafting_recipe_book: RecipeBookState::FALSE,
-        smelting_recipe_book: RecipeBookState::FALSE,
-        blast_furnace_recipe_book: RecipeBookState::FALSE,
-        smoker_recipe_book: RecipeBookState::FALSE,
-        recipe_ids_1: vec!["hyperion:what".to_string()],
-        recipe_ids_2: vec!["hyperion:what".to_string()],
-    };
-
-    encoder.append_packet(&pkt)?;
-
     Ok(())
 }
 
@@ -528,6 +565,7 @@ impl Module for PlayerJoinModule {
             &Pitch,
             &PlayerSkin,
             &EntityFlags,
+            Option<&EntityMetadata>,
         )>();
 
         let query = SendableQuery(query);
@@ -565,71 +603,110 @@ impl Module for PlayerJoinModule {
             &Comms($),
             &Compose($),
             &CraftingRegistry($),
-            &Config($),
+            &ServerConfig($),
+            &PlayerRoster($),
+            &PlayerDataHandler($),
+            &WorldTime($),
+            &Weather($),
+            &WorldBorder($),
+            &Scoreboard($),
+            &ScoreboardTeams($),
+            &SpawnPoint($),
         )
         .kind::<flecs::pipeline::PreUpdate>()
-        .each(move |(comms, compose, crafting_registry, config)| {
-            let span = tracing::info_span!("joins");
-            let _enter = span.enter();
-
-            let mut skins = Vec::new();
-
-            while let Ok(Some((entity, skin))) = comms.skins_rx.try_recv() {
-                skins.push((entity, skin.clone()));
-            }
-
-            // todo: par_iter but bugs...
-            // for (entity, skin) in skins {
-            skins.into_par_iter().for_each(|(entity, skin)| {
-                // if we are not in rayon context that means we are in a single-threaded context and 0 will work
-                let idx = rayon::current_thread_index().unwrap_or(0);
-
-                #[expect(
-                    clippy::indexing_slicing,
-                    reason = "unless the number of rayon threads changes, this should never panic"
-                )]
-                let world = &stages[idx];
-                let world = world.0;
-
-                if !world.is_alive(entity) {
-                    return;
+        .each(
+            move |(
+                comms,
+                compose,
+                crafting,
+                config,
+                roster,
+                players,
+                time,
+                weather,
+                border,
+                scoreboard,
+                teams,
+                spawn,
+            )| {
+                let span = tracing::info_span!("joins");
+                let _enter = span.enter();
+                let _timer = PROFILER.time("player_joins");
+
+                let mut skins = Vec::new();
+
+                while let Ok(Some((entity, skin))) = comms.skins_rx.try_recv() {
+                    skins.push((entity, skin.clone()));
                 }
 
-                let entity = world.entity_from_id(entity);
-
-                entity.get::<(&Uuid, &Name, &Position, &Yaw, &Pitch, &NetworkStreamRef)>(
-                    |(uuid, name, position, yaw, pitch, &stream_id)| {
-                        let query = &query;
-                        let query = &query.0;
-
-                        // if we get an error joining, we should kick the player
-                        if let Err(e) = player_join_world(
-                            &entity,
-                            compose,
-                            uuid.0,
-                            name,
-                            stream_id,
-                            position,
-                            yaw,
-                            pitch,
-                            &world,
-                            &skin,
-                            system_id,
-                            root_command,
-                            query,
-                            crafting_registry,
-                            config,
-                        ) {
-                            entity.set(PendingRemove::new(e.to_string()));
-                        };
-                    },
-                );
-
-                let entity = world.entity_from_id(entity);
-                entity.set(skin);
+                // todo: par_iter but bugs...
+                // for (entity, skin) in skins {
+                skins.into_par_iter().for_each(|(entity, skin)| {
+                    // if we are not in rayon context that means we are in a single-threaded
+                    // context and 0 will work
+                    let idx = rayon::current_thread_index().unwrap_or(0);
+
+                    #[expect(
+                        clippy::indexing_slicing,
+                        reason = "unless the number of rayon threads changes, this should never \
+                                  panic"
+                    )]
+                    let world = &stages[idx];
+                    let world = world.0;
+
+                    if !world.is_alive(entity) {
+                        return;
+                    }
 
-                entity.add_enum(PacketState::Play);
-            });
-        });
+                    let entity = world.entity_from_id(entity);
+
+                    // players spawn where they left, so their data is loaded before anything is
+                    // sent
+                    persistence::load(&entity, players);
+
+                    entity.get::<(&Uuid, &Name, &Position, &Yaw, &Pitch, &NetworkStreamRef)>(
+                        |(uuid, name, position, yaw, pitch, &stream_id)| {
+                            let query = &query;
+                            let query = &query.0;
+
+                            // if we get an error joining, we should kick the player
+                            if let Err(e) = player_join_world(
+                                &entity,
+                                compose,
+                                uuid.0,
+                                name,
+                                stream_id,
+                                position,
+                                yaw,
+                                pitch,
+                                &world,
+                                &skin,
+                                system_id,
+                                root_command,
+                                query,
+                                crafting,
+                                config,
+                                roster,
+                                time,
+                                weather,
+                                border,
+                                scoreboard,
+                                teams,
+                                spawn,
+                            ) {
+                                entity.set(PendingRemove::new(e.to_string()));
+                            };
+                        },
+                    );
+
+                    let entity = world.entity_from_id(entity);
+                    entity.set(skin);
+                    entity.set(KeepAlive::new(Instant::now()));
+                    entity.set(Ping::default());
+
+                    entity.add_enum(PacketState::Play);
+                });
+            },
+        );
     }
 }
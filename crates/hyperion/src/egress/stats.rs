@@ -3,6 +3,7 @@ use tracing::{error, info_span};
 
 use crate::{
     net::Compose,
+    profiler::PROFILER,
     simulation::{PacketState, blocks::Blocks},
 };
 
@@ -45,6 +46,7 @@ impl Module for StatsModule {
         .each_iter(|_iter, _, blocks| {
             let span = info_span!("load_pending");
             let _enter = span.enter();
+            let _timer = PROFILER.time("load_pending");
             blocks.load_pending();
         });
     }
@@ -10,7 +10,7 @@ use valence_protocol::{
 };
 
 use crate::{
-    config::Config,
+    config::ServerConfig,
     net::{Compose, DataBundle, NetworkStreamRef},
     simulation::{
         ChunkPosition, PacketState, Position,
@@ -32,7 +32,7 @@ impl Module for SyncChunksModule {
     fn module(world: &World) {
         world.component::<ChunkSendQueue>();
 
-        let radius = world.get::<&Config>(|config| config.view_distance);
+        let radius = world.get::<&ServerConfig>(|config| config.view_distance);
         let liberal_radius = radius + 2;
 
         let system_id = GENERATE_CHUNK_CHANGES;
@@ -68,7 +68,10 @@ impl Module for SyncChunksModule {
                     chunk_z: current_chunk.y.into(),
                 };
 
-                if let Err(e) = compose.unicast(&center_chunk, stream_id, system_id, &world) {
+                // sent together with the chunks to unload
+                let mut bundle = DataBundle::new(compose);
+
+                if let Err(e) = bundle.add_packet(&center_chunk, &world) {
                     error!(
                         "failed to send chunk render distance center packet: {e}. Chunk location: \
                          {current_chunk:?}"
@@ -101,8 +104,6 @@ impl Module for SyncChunksModule {
                         !current_range_x.contains(&pos.x) || !current_range_z.contains(&pos.y)
                     });
 
-                let mut bundle = DataBundle::new(compose);
-
                 for chunk in removed_chunks {
                     let pos = ChunkPos::new(chunk.x, chunk.y);
                     let unload_chunk = play::UnloadChunkS2c { pos };
@@ -220,6 +221,20 @@ impl Module for SyncChunksModule {
                                     }
                                 }
 
+                                for packet in chunk.original_block_entity_packets() {
+                                    if let Err(e) = bundle.add_packet(&packet, &world) {
+                                        error!("failed to send block entity packet: {e}");
+                                        return;
+                                    }
+                                }
+
+                                if let Some(packet) = chunk.original_light_packet()
+                                    && let Err(e) = bundle.add_packet(&packet, &world)
+                                {
+                                    error!("failed to send light update packet: {e}");
+                                    return;
+                                }
+
                                 iter_count += 1;
                                 #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
                                 queue.changes.swap_remove(idx as usize);
@@ -8,9 +8,7 @@ use hyperion_utils::EntityExt;
 use tracing::{error, info_span};
 use valence_ident::ident;
 use valence_protocol::{
-    ByteAngle, GameMode, RawBytes, VarInt, Velocity,
-    game_mode::OptGameMode,
-    packets::{play, play::entity_equipment_update_s2c::EquipmentEntry},
+    ByteAngle, RawBytes, VarInt, Velocity, game_mode::OptGameMode, packets::play,
 };
 
 use crate::{
@@ -20,7 +18,13 @@ use crate::{
     simulation::{
         EntityReaction, Health, Pitch, Position, Xp, Yaw,
         animation::ActiveAnimation,
+        entity::EntityMetadata,
+        game_mode::GameMode,
+        menu,
         metadata::{EntityFlags, MetadataBuilder, Pose},
+        spawn::respawn_position,
+        teleport::PendingTeleport,
+        visibility::HiddenFrom,
     },
     storage::ThreadLocal,
     system_registry::{SYNC_ENTITY_POSITION, SystemId},
@@ -84,7 +88,9 @@ impl Module for EntityStateSyncModule {
             &mut EntityFlags,
             &mut Prev<EntityFlags>,
             &mut Pose,
-            &mut Prev<Pose>
+            &mut Prev<Pose>,
+            &HiddenFrom,
+            ?&mut EntityMetadata,
         )
             .multi_threaded()
             .kind::<flecs::pipeline::OnStore>()
@@ -106,6 +112,8 @@ impl Module for EntityStateSyncModule {
                           Prev(prev_entity_flags),
                           pose,
                           Prev(prev_pose),
+                          hidden_from,
+                          entity_metadata,
                       )| {
                     let mut run = || {
                         let entity_id = VarInt(entity.minecraft_id());
@@ -143,23 +151,34 @@ impl Module for EntityStateSyncModule {
                                     source_pos: None,
                                 };
 
-                                compose.broadcast_local(&pkt, chunk_pos, system_id).send(&world)?;
+                                compose
+                                    .broadcast_local(&pkt, chunk_pos, system_id)
+                                    .exclude_many(hidden_from.streams())
+                                    .send(&world)?;
 
                                 let packet = agnostic::sound(
                                     ident!("minecraft:entity.player.hurt"),
                                     **position,
                                 ).build();
 
-                                compose.broadcast_local(&packet, chunk_pos, system_id).send(&world)?;
+                                compose
+                                    .broadcast_local(&packet, chunk_pos, system_id)
+                                    .exclude_many(hidden_from.streams())
+                                    .send(&world)?;
                             }
 
                             if *to == 0.0 {
+                                // the client takes on the game mode in the respawn packet
+                                let game_mode = entity
+                                    .try_get::<&GameMode>(|mode| mode.0)
+                                    .unwrap_or_default();
+
                                 // send respawn packet
                                 let pkt = play::PlayerRespawnS2c {
                                     dimension_type_name: ident!("minecraft:overworld").into(),
                                     dimension_name: ident!("minecraft:overworld").into(),
                                     hashed_seed: 0,
-                                    game_mode: GameMode::Survival,
+                                    game_mode,
                                     previous_game_mode: OptGameMode::default(),
                                     is_debug: false,
                                     is_flat: false,
@@ -167,9 +186,21 @@ impl Module for EntityStateSyncModule {
                                     last_death_location: None,
                                     portal_cooldown: VarInt::default(),
                                 };
-                                // position.position = PLAYER_SPAWN_POSITION;
                                 compose.unicast(&pkt, io, system_id, &world)?;
 
+                                // players without a respawn anchor come back at the world spawn
+                                **position = respawn_position(entity);
+
+                                // the client forgets its position on respawn and must be told where it is
+                                let teleport = PendingTeleport::new(
+                                    **position,
+                                    **yaw,
+                                    **pitch,
+                                    compose.global().tick,
+                                );
+                                compose.unicast(&teleport.packet(), io, system_id, &world)?;
+                                entity.set(teleport);
+
                                 **health = 20.0;
 
                                 let show_all = show_all(entity.minecraft_id());
@@ -184,6 +215,10 @@ impl Module for EntityStateSyncModule {
                             *prev_entity_flags = *entity_flags;
                         }
 
+                        if let Some(entity_metadata) = entity_metadata {
+                            entity_metadata.add_changes_to(observer);
+                        }
+
                         let pkt = play::EntityPositionS2c {
                             entity_id,
                             position: position.as_dvec3(),
@@ -195,6 +230,7 @@ impl Module for EntityStateSyncModule {
                         compose
                             .broadcast_local(&pkt, chunk_pos, system_id)
                             .exclude(io)
+                            .exclude_many(hidden_from.streams())
                             .send(&world)?;
 
                         let pkt = play::EntitySetHeadYawS2c {
@@ -203,8 +239,9 @@ impl Module for EntityStateSyncModule {
                         };
 
                         compose
-                            .broadcast(&pkt, system_id)
+                            .broadcast_local(&pkt, chunk_pos, system_id)
                             .exclude(io)
+                            .exclude_many(hidden_from.streams())
                             .send(&world)?;
 
                         if reaction.velocity != Vec3::ZERO {
@@ -228,7 +265,11 @@ impl Module for EntityStateSyncModule {
                                 velocity,
                             };
 
-                            compose.unicast(&pkt, io, system_id, &world)?;
+                            // others see the hit land as well
+                            compose
+                                .broadcast_local(&pkt, chunk_pos, system_id)
+                                .exclude_many(hidden_from.streams())
+                                .send(&world)?;
 
                             reaction.velocity = Vec3::ZERO;
                         }
@@ -240,64 +281,40 @@ impl Module for EntityStateSyncModule {
                                 tracked_values: RawBytes(&view),
                             };
 
-                            compose.broadcast_local(&pkt, chunk_pos, system_id).send(&world)?;
+                            compose
+                                .broadcast_local(&pkt, chunk_pos, system_id)
+                                .exclude_many(hidden_from.streams())
+                                .send(&world)?;
                         }
 
                         for pkt in animation.packets(entity_id) {
                             compose
                                 .broadcast_local(&pkt, chunk_pos, system_id)
                                 .exclude(io)
+                                .exclude_many(hidden_from.streams())
                                 .send(&world)?;
                         }
 
                         animation.clear();
 
-                        for slot in &inventory.updated_since_last_tick {
-                            let Ok(slot) = u16::try_from(slot) else {
-                                error!("failed to convert slot to u16 {slot}");
-                                continue;
-                            };
-                            let item = inventory.get(slot).with_context(|| {
-                                format!("failed to get item for slot {slot}")
-                            })?;
+                        // equipment changes were already broadcast by `broadcast_equipment`, and
+                        // the client picked the hotbar slot itself
+                        let state_id = menu::state_id(inventory);
+
+                        for (slot, item) in inventory.drain_updates().slots {
                             let Ok(slot) = i16::try_from(slot) else {
                                 error!("failed to convert slot to i16 {slot}");
                                 continue;
                             };
                             let pkt = play::ScreenHandlerSlotUpdateS2c {
                                 window_id: 0,
-                                state_id: VarInt::default(),
+                                state_id,
                                 slot_idx: slot,
-                                slot_data: Cow::Borrowed(item),
+                                slot_data: Cow::Owned(item),
                             };
                             compose.unicast(&pkt, io, system_id, &world).context("failed to send inventory update")?;
                         }
 
-                        let cursor = inventory.get_cursor_index();
-
-                        if inventory
-                            .updated_since_last_tick
-                            .contains(u32::from(cursor))
-                            || inventory.hand_slot_updated_since_last_tick
-                        {
-                            let pkt = play::EntityEquipmentUpdateS2c {
-                                entity_id,
-                                equipment: vec![EquipmentEntry {
-                                    slot: 0,
-                                    item: inventory.get_cursor().clone(),
-                                }],
-                            };
-
-                            compose
-                                .broadcast_local(&pkt, chunk_pos, system_id)
-                                .exclude(io)
-                                .send(&world)
-                                .context("failed to send equipment update")?;
-                        }
-
-                        inventory.updated_since_last_tick.clear();
-                        inventory.hand_slot_updated_since_last_tick = false;
-
                         anyhow::Ok(())
                     };
                     if let Err(e) = run() {
@@ -16,12 +16,16 @@ use valence_protocol::{
 use valence_text::IntoText;
 
 use crate::{
-    Prev, Shutdown,
+    Prev,
+    ban::{BanList, unix_now},
+    config::ServerConfig,
     egress::sync_chunks::ChunkSendQueue,
+    l10n::Localization,
     net::{
-        Compose, MINECRAFT_VERSION, NetworkStreamRef, PROTOCOL_VERSION, PacketDecoder,
+        Compose, MINECRAFT_VERSION, NetworkStreamRef, PROTOCOL_VERSION, PacketDecoder, StreamStats,
         decoder::BorrowedPacketFrame, proxy::ReceiveState,
     },
+    profiler::PROFILER,
     runtime::AsyncRuntime,
     simulation::{
         AiTargetable, ChunkPosition, Comms, ConfirmBlockSequences, EntityReaction, EntitySize,
@@ -29,15 +33,26 @@ use crate::{
         Uuid, Xp, Yaw,
         animation::ActiveAnimation,
         blocks::Blocks,
+        fall::FallDistance,
+        game_mode::GameMode,
         handlers::PacketSwitchQuery,
         metadata::{EntityFlags, Pose},
+        roster::{PlayerEntry, PlayerRoster, PlayerRosterModule},
         skin::PlayerSkin,
+        spawn::SpawnPoint,
+        visibility::forget_player,
     },
-    storage::{Events, GlobalEventHandlers, PlayerJoinServer, SkinHandler},
+    storage::{Events, GlobalEventHandlers, PlayerJoinServer, PlayerLeaveServer, SkinHandler},
     system_registry::{RECV_DATA, REMOVE_PLAYER_FROM_VISIBILITY, SystemId},
     util::{SendableRef, TracingExt, mojang::MojangClient},
+    whitelist::Whitelist,
 };
 
+/// Marks a connection that took one of the [`crate::capacity::PlayerCapacity`] slots, which is
+/// given back when the entity is removed.
+#[derive(Component, Debug)]
+struct PlayerSlot;
+
 #[derive(Component, Debug)]
 pub struct PendingRemove {
     pub reason: String,
@@ -91,6 +106,7 @@ fn process_login(
     entity: &EntityView<'_>,
     system_id: SystemId,
     ign_map: &IgnMap,
+    roster: &PlayerRoster,
 ) -> anyhow::Result<()> {
     debug_assert!(
         *login_state == PacketState::Login,
@@ -123,10 +139,41 @@ fn process_login(
 
     let username = Arc::from(username);
 
-    let uuid = profile_id.unwrap_or_else(|| offline_uuid(&username));
+    let offline_mode = world.get::<&ServerConfig>(|config| config.offline_mode);
+
+    let uuid = profile_id
+        .filter(|_| !offline_mode)
+        .unwrap_or_else(|| offline_uuid(&username));
     let uuid_s = format!("{uuid:?}").dimmed();
     info!("Starting login: {username} {uuid_s}");
 
+    // banned players must not take a slot, so the capacity is checked last
+    let rejection = login_rejection(world, uuid, &username).or_else(|| {
+        let admitted = global.capacity.try_take(uuid, &username);
+        (!admitted).then(|| world.get::<&ServerConfig>(|config| config.server_full_message.clone()))
+    });
+
+    if let Some(reason) = rejection {
+        info!("{username} may not join");
+
+        // nothing has been spawned for them yet, so there is nothing else to undo
+        compose
+            .unicast(
+                &login::LoginDisconnectS2c {
+                    reason: reason.clone().into_cow_text(),
+                },
+                stream_id,
+                system_id,
+                world,
+            )
+            .context("failed to send login disconnect packet")?;
+
+        entity.set(PendingRemove::new(reason));
+        return Ok(());
+    }
+
+    entity.add::<PlayerSlot>();
+
     let skins = comms.skins_tx.clone();
     let id = entity.id();
 
@@ -137,10 +184,8 @@ fn process_login(
                 error!("failed to get skin {e}. Using empty skin");
                 PlayerSkin::EMPTY
             }
-            Ok(None) => {
-                error!("failed to get skin. Using empty skin");
-                PlayerSkin::EMPTY
-            }
+            // players without an account, such as on offline mode servers, use a default skin
+            Ok(None) => PlayerSkin::EMPTY,
         };
 
         skins.send((id, skin)).unwrap();
@@ -160,7 +205,21 @@ fn process_login(
 
     ign_map.insert(username.clone(), entity.id(), world);
 
+    roster.insert(
+        PlayerEntry {
+            entity: entity.id(),
+            uuid,
+            name: username.clone(),
+            stream: stream_id,
+        },
+        world,
+    );
+
+    // games may move players somewhere else once their uuid is known
+    let spawn = world.get::<&SpawnPoint>(|spawn| spawn.position);
+
     entity
+        .set(Position::from(spawn))
         .set(Name::from(username))
         .add::<AiTargetable>()
         .set(ImmuneStatus::default())
@@ -173,6 +232,7 @@ fn process_login(
         .set(EntityFlags::default())
         .set(Prev(Pose::default()))
         .add::<Pose>()
+        .add::<FallDistance>()
         .add::<ChunkSendQueue>()
         .add::<EntityReaction>()
         .set(ChunkPosition::null())
@@ -183,6 +243,26 @@ fn process_login(
     Ok(())
 }
 
+/// The message to kick a joining player with if they are banned, or if the whitelist is enabled and
+/// they are not on it.
+fn login_rejection(world: &WorldRef<'_>, uuid: uuid::Uuid, username: &str) -> Option<String> {
+    let ban = world.get::<&Localization>(|l10n| {
+        world.get::<&BanList>(|bans| bans.rejection(l10n, uuid, unix_now()))
+    });
+
+    if ban.is_some() {
+        return ban;
+    }
+
+    let admitted = world.get::<&Whitelist>(|whitelist| whitelist.admits(uuid, username));
+
+    if admitted {
+        return None;
+    }
+
+    Some(world.get::<&ServerConfig>(|config| config.whitelist_message.clone()))
+}
+
 /// Get a [`uuid::Uuid`] based on the given user's name.
 fn offline_uuid(username: &str) -> uuid::Uuid {
     let digest = sha2::Sha256::digest(username);
@@ -201,6 +281,7 @@ fn process_status(
     packets: NetworkStreamRef,
     compose: &Compose,
     world: &World,
+    roster: &PlayerRoster,
 ) -> anyhow::Result<()> {
     debug_assert!(
         *login_state == PacketState::Status,
@@ -216,10 +297,22 @@ fn process_status(
             // let favicon = general_purpose::STANDARD.encode(img_bytes);
             // let favicon = format!("data:image/png;base64,{favicon}");
 
-            let online = compose
-                .global()
-                .player_count
-                .load(std::sync::atomic::Ordering::Relaxed);
+            // players still joining count, as their slots are already taken
+            let capacity = &compose.global().capacity;
+            let online = capacity.taken();
+            let max = capacity.max_players();
+
+            // vanilla clients only display the first 12 entries of the sample
+            let sample: Vec<_> = roster
+                .iter()
+                .take(12)
+                .map(|entry| {
+                    json!({
+                        "name": &*entry.name,
+                        "id": entry.uuid.to_string(),
+                    })
+                })
+                .collect();
 
             // https://wiki.vg/Server_List_Ping#Response
             let json = json!({
@@ -229,8 +322,8 @@ fn process_status(
                 },
                 "players": {
                     "online": online,
-                    "max": 12_000,
-                    "sample": [],
+                    "max": max,
+                    "sample": sample,
                 },
                 "description": "Getting 10k Players to PvP at Once on a Minecraft Server to Break the Guinness World Record",
                 // "favicon": favicon,
@@ -271,6 +364,20 @@ pub struct IngressModule;
 impl Module for IngressModule {
     #[expect(clippy::too_many_lines)]
     fn module(world: &World) {
+        world.component::<PlayerSlot>();
+
+        world
+            .observer::<flecs::OnRemove, ()>()
+            .with::<PlayerSlot>()
+            .each_entity(|entity, ()| {
+                // the world drops its singletons too when it is torn down after a shutdown
+                entity
+                    .world()
+                    .try_get::<&Compose>(|compose| compose.global().capacity.release());
+            });
+
+        world.import::<PlayerRosterModule>();
+
         system!(
             "update_ign_map",
             world,
@@ -282,20 +389,6 @@ impl Module for IngressModule {
             ign_map.update();
         });
 
-        system!(
-            "shutdown",
-            world,
-            &Shutdown($),
-        )
-        .kind::<flecs::pipeline::OnLoad>()
-        .each_iter(|it, _, shutdown| {
-            let world = it.world();
-            if shutdown.value.load(std::sync::atomic::Ordering::Relaxed) {
-                info!("shutting down");
-                world.quit();
-            }
-        });
-
         system!(
             "generate_ingress_events",
             world,
@@ -312,6 +405,7 @@ impl Module for IngressModule {
 
             let span = info_span!("generate_ingress_events");
             let _enter = span.enter();
+            let _timer = PROFILER.time("generate_ingress_events");
 
             let world = it.world();
 
@@ -323,6 +417,7 @@ impl Module for IngressModule {
                     .entity()
                     .set(NetworkStreamRef::new(connect))
                     .set(hyperion_inventory::PlayerInventory::default())
+                    .set(GameMode::default())
                     .set(ConfirmBlockSequences::default())
                     .set(PacketState::Handshake)
                     .set(ActiveAnimation::NONE)
@@ -333,15 +428,31 @@ impl Module for IngressModule {
             }
 
             for disconnect in recv.player_disconnect.drain(..) {
-                // will initiate the removal of entity
-                info!("queue pending remove");
-                let Some(id) = lookup.get(&disconnect).copied() else {
-                    error!("failed to get id for disconnect stream {disconnect:?}");
+                // the stream is forgotten so a repeated disconnect cannot remove the player twice
+                let Some(id) = lookup.remove(&disconnect) else {
+                    warn!("got a disconnect for unknown stream {disconnect:?}");
+                    continue;
+                };
+
+                // will initiate the removal of entity, unless it was already removed, such as
+                // after being kicked
+                let entity = world.entity_from_id(id);
+                if entity.is_alive() {
+                    info!("queue pending remove");
+                    entity.set(PendingRemove::new("disconnected"));
+                }
+            }
+
+            for stats in recv.stream_stats.drain(..) {
+                // the player may have disconnected since the proxy sent these
+                let Some(&id) = lookup.get(&stats.stream) else {
                     continue;
                 };
-                world
-                    .entity_from_id(*id)
-                    .set(PendingRemove::new("disconnected"));
+
+                world.entity_from_id(id).set(StreamStats {
+                    dropped_bytes: stats.dropped_bytes,
+                    queued_bytes: stats.queued_bytes,
+                });
             }
         });
 
@@ -373,6 +484,7 @@ impl Module for IngressModule {
             // 150-208µs with regular drain
             let span = info_span!("ingress_to_ecs");
             let _enter = span.enter();
+            let _timer = PROFILER.time("ingress_to_ecs");
 
             let mut recv = receive.0.lock();
 
@@ -404,6 +516,28 @@ impl Module for IngressModule {
             });
         });
 
+        system!(
+            "leave_server",
+            world,
+            &GlobalEventHandlers($),
+            &PlayerRoster($),
+            &PendingRemove,
+        )
+        .kind::<flecs::pipeline::PostLoad>()
+        .each_entity(|entity, (handlers, roster, pending_remove)| {
+            // only players who finished logging in have joined
+            if roster.get(entity.id()).is_none() {
+                return;
+            }
+
+            let leave = PlayerLeaveServer {
+                entity: entity.id(),
+                reason: pending_remove.reason.clone(),
+            };
+
+            handlers.leave_server.trigger_all(&entity.world(), &leave);
+        });
+
         let system_id = REMOVE_PLAYER_FROM_VISIBILITY;
 
         system!(
@@ -453,13 +587,19 @@ impl Module for IngressModule {
             },
         );
 
-        world
-            .system_named::<()>("remove_player")
-            .kind::<flecs::pipeline::PostLoad>()
-            .with::<&PendingRemove>()
-            .tracing_each_entity(info_span!("remove_player"), |entity, ()| {
-                entity.destruct();
-            });
+        system!(
+            "remove_player",
+            world,
+            &mut PlayerRoster($),
+        )
+        .kind::<flecs::pipeline::PostLoad>()
+        .with::<&PendingRemove>()
+        .tracing_each_entity(info_span!("remove_player"), |entity, roster| {
+            // leave handlers have run by now, so the player can be dropped from the roster
+            roster.remove(entity.id());
+            forget_player(entity);
+            entity.destruct();
+        });
 
         let system_id = RECV_DATA;
 
@@ -477,6 +617,7 @@ impl Module for IngressModule {
             &mut PacketState,
             &NetworkStreamRef,
             ?&mut Pose,
+            ?&mut EntityFlags,
             &Events($),
             &EntitySize,
             ?&mut Position,
@@ -487,6 +628,7 @@ impl Module for IngressModule {
             &mut ActiveAnimation,
             &hyperion_crafting::CraftingRegistry($),
             &IgnMap($),
+            &PlayerRoster($),
         )
         .kind::<flecs::pipeline::OnUpdate>()
         .multi_threaded()
@@ -505,6 +647,7 @@ impl Module for IngressModule {
                 login_state,
                 &io_ref,
                 mut pose,
+                mut flags,
                 event_queue,
                 size,
                 mut position,
@@ -515,6 +658,7 @@ impl Module for IngressModule {
                 animation,
                 crafting_registry,
                 ign_map,
+                roster,
             )| {
                 let world = entity.world();
                 let bump = compose.bump.get(&world);
@@ -551,6 +695,7 @@ impl Module for IngressModule {
                                 io_ref,
                                 compose,
                                 &world,
+                                roster,
                             ) {
                                 error!("failed to process status packet: {e}");
                                 entity.destruct();
@@ -572,6 +717,7 @@ impl Module for IngressModule {
                                 &entity,
                                 system_id,
                                 ign_map,
+                                roster,
                             ) {
                                 error!("failed to process login packet");
                                 let msg = format!(
@@ -603,7 +749,9 @@ impl Module for IngressModule {
                             // Transitioning to play is just a way to make sure that the player is officially in play before we start sending them play packets.
                             // We have a certain duration that we wait before doing this.
                             // todo: better way?
-                            if let Some((position, pose)) = position.as_mut().zip(pose.as_mut()) {
+                            if let Some(((position, pose), flags)) =
+                                position.as_mut().zip(pose.as_mut()).zip(flags.as_mut())
+                            {
                                 let world = &world;
 
                                 let mut query = PacketSwitchQuery {
@@ -616,6 +764,7 @@ impl Module for IngressModule {
                                     pitch,
                                     size,
                                     pose,
+                                    flags,
                                     events: event_queue,
                                     world,
                                     blocks,
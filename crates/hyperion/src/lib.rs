@@ -25,15 +25,11 @@ pub const NUM_THREADS: usize = 8;
 pub const CHUNK_HEIGHT_SPAN: u32 = 384; // 512; // usually 384
 
 use std::{
-    alloc::Allocator,
-    cell::RefCell,
-    fmt::Debug,
-    io::Write,
-    net::ToSocketAddrs,
-    sync::{Arc, atomic::AtomicBool},
+    alloc::Allocator, cell::RefCell, fmt::Debug, io::Write, net::ToSocketAddrs, path::Path,
+    sync::Arc, time::Duration,
 };
 
-use anyhow::{Context, bail};
+use anyhow::Context;
 use derive_more::{Deref, DerefMut};
 use egress::EgressModule;
 use flecs_ecs::prelude::*;
@@ -44,7 +40,7 @@ use ingress::IngressModule;
 use libc::{RLIMIT_NOFILE, getrlimit, setrlimit};
 use libdeflater::CompressionLvl;
 use simulation::{Comms, SimModule, StreamLookup, blocks::Blocks};
-use storage::{Events, GlobalEventHandlers, LocalDb, SkinHandler, ThreadLocal};
+use storage::{Events, GlobalEventHandlers, LocalDb, PlayerDataHandler, SkinHandler, ThreadLocal};
 use tracing::{info, info_span, warn};
 use util::mojang::MojangClient;
 pub use uuid;
@@ -58,23 +54,31 @@ pub use valence_protocol::{
 };
 
 use crate::{
+    capacity::PlayerCapacity,
+    config::ServerConfig,
+    l10n::LocalizationModule,
     net::{Compose, Compressors, IoBuf, MAX_PACKET_SIZE, proxy::init_proxy_comms},
+    profiler::{PROFILER, ProfilerModule},
     runtime::AsyncRuntime,
+    shutdown::{ShutdownHandle, ShutdownModule},
     simulation::{Pitch, Yaw},
 };
 
 mod common;
 pub use common::*;
-use hyperion_crafting::CraftingRegistry;
+use hyperion_crafting::{CraftingRegistry, SmeltingRegistry};
 pub use valence_ident;
 
 use crate::{
     ingress::PendingRemove,
-    net::{NetworkStreamRef, PacketDecoder, proxy::ReceiveState},
+    net::{NetworkStreamRef, PacketDecoder, StreamStats, proxy::ReceiveState},
     runtime::Tasks,
     simulation::{
         EgressComm, EntitySize, IgnMap, PacketState, Player,
+        command::Console,
         metadata::{EntityFlags, Pose},
+        roster::PlayerRoster,
+        visibility::{HiddenEntities, HiddenFrom},
     },
     util::mojang::ApiProvider,
 };
@@ -144,20 +148,15 @@ pub fn adjust_file_descriptor_limits(recommended_min: u64) -> std::io::Result<()
 /// The central [`Hyperion`] struct which owns and manages the entire server.
 pub struct Hyperion;
 
-#[derive(Component)]
-struct Shutdown {
-    value: Arc<AtomicBool>,
-}
-
 impl Hyperion {
     /// Initializes the server.
-    pub fn init(address: impl ToSocketAddrs + Send + Sync + 'static) -> anyhow::Result<()> {
-        Self::init_with(address, |_| {})
+    pub fn init(config: ServerConfig) -> anyhow::Result<()> {
+        Self::init_with(config, |_| {})
     }
 
     /// Initializes the server with a custom handler.
     pub fn init_with(
-        address: impl ToSocketAddrs + Send + Sync + 'static,
+        config: ServerConfig,
         handlers: impl FnOnce(&World) + Send + Sync + 'static,
     ) -> anyhow::Result<()> {
         // Denormals (numbers very close to 0) are flushed to zero because doing computations on them
@@ -178,12 +177,12 @@ impl Hyperion {
             .build_global()
             .context("failed to build thread pool")?;
 
-        no_denormals::no_denormals(|| Self::init_with_helper(address, handlers))
+        no_denormals::no_denormals(|| Self::init_with_helper(config, handlers))
     }
 
     /// Initialize the server.
     fn init_with_helper(
-        address: impl ToSocketAddrs + Send + Sync + 'static,
+        config: ServerConfig,
         handlers: impl FnOnce(&World) + Send + Sync + 'static,
     ) -> anyhow::Result<()> {
         // 10k players * 2 file handles / player  = 20,000. We can probably get away with 16,384 file handles
@@ -191,8 +190,8 @@ impl Hyperion {
         adjust_file_descriptor_limits(32_768).context("failed to set file limits")?;
 
         let shared = Arc::new(Shared {
-            compression_threshold: CompressionThreshold(256),
-            compression_level: CompressionLvl::new(2)
+            compression_threshold: CompressionThreshold(config.compression_threshold),
+            compression_level: CompressionLvl::new(config.compression_level)
                 .map_err(|_| anyhow::anyhow!("failed to create compression level"))?,
         });
 
@@ -210,18 +209,12 @@ impl Hyperion {
 
         world.set_threads(i32::try_from(rayon::current_num_threads())?);
 
-        let address = address
+        let address = config
+            .proxy_address
             .to_socket_addrs()?
             .next()
             .context("could not get first address")?;
 
-        world.component::<Shutdown>();
-        let shutdown = Arc::new(AtomicBool::new(false));
-
-        world.set(Shutdown {
-            value: shutdown.clone(),
-        });
-
         world.component::<Pose>();
         world.component::<Prev<Pose>>();
 
@@ -251,12 +244,16 @@ impl Hyperion {
         world.component::<PacketState>();
 
         world.component::<NetworkStreamRef>();
+        world.component::<StreamStats>();
         world.component::<ReceiveState>();
         world.component::<Compose>();
         world.component::<CraftingRegistry>();
+        world.component::<SmeltingRegistry>();
 
         world.component::<LocalDb>();
         world.component::<SkinHandler>();
+        world.component::<PlayerDataHandler>();
+        world.component::<ban::BanList>();
         world.component::<MojangClient>();
         world.component::<Events>();
         world.component::<Comms>();
@@ -272,46 +269,40 @@ impl Hyperion {
                 let world = it.world();
                 let span = info_span!("run_tasks");
                 let _enter = span.enter();
-                while let Ok(Some(task)) = tasks.tasks.try_recv() {
-                    task(&world);
-                }
+                let _timer = PROFILER.time("run_tasks");
+                tasks.run(&world);
             });
 
         world.component::<StreamLookup>();
         world.component::<EntitySize>();
         world.component::<IgnMap>();
+        world.component::<PlayerRoster>();
 
-        world.component::<config::Config>();
+        world.component::<ServerConfig>();
 
         info!("starting hyperion");
-        let config = config::Config::load("run/config.toml")?;
+
+        PROFILER.set_enabled(config.profile_ticks);
+        PROFILER.set_slow_tick_threshold(Duration::from_millis(config.slow_tick_ms));
+
+        let world_path = config.world_path.clone();
         world.set(config);
 
+        world.component::<whitelist::Whitelist>();
+        world.set(whitelist::Whitelist::load("run/whitelist.json")?);
+
+        world.import::<ShutdownModule>();
+        world.import::<LocalizationModule>();
+        let shutdown = world.get::<&ShutdownHandle>(ShutdownHandle::clone);
+
         let (task_tx, task_rx) = kanal::bounded(32);
         let runtime = AsyncRuntime::new(task_tx);
 
-        #[allow(clippy::redundant_pub_crate)]
-        runtime.spawn(async move {
-            let mut sigterm =
-                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
-            let mut sigquit =
-                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::quit()).unwrap();
-
-            tokio::select! {
-                _ = tokio::signal::ctrl_c() => {
-                    warn!("SIGINT/ctrl-c received, shutting down");
-                    shutdown.store(true, std::sync::atomic::Ordering::Relaxed);
-                }
-                _ = sigterm.recv() => {
-                    warn!("SIGTERM received, shutting down");
-                    shutdown.store(true, std::sync::atomic::Ordering::Relaxed);
-                }
-                _ = sigquit.recv() => {
-                    warn!("SIGQUIT received, shutting down");
-                    shutdown.store(true, std::sync::atomic::Ordering::Relaxed);
-                }
-            }
-        });
+        runtime.spawn(shutdown::watch_signals(shutdown.clone()));
+
+        let (console_tx, console_rx) = kanal::unbounded();
+        shutdown::spawn_console(shutdown, console_tx);
+        world.set(Console::new(&world, console_rx));
 
         let tasks = Tasks { tasks: task_rx };
         world.set(tasks);
@@ -322,18 +313,23 @@ impl Hyperion {
         info!("initializing database");
         let db = LocalDb::new()?;
         let skins = SkinHandler::new(&db)?;
+        let player_data = PlayerDataHandler::new(&db)?;
+        let bans = ban::BanList::new(&db)?;
         info!("database initialized");
 
         world.set(db);
         world.set(skins);
+        world.set(player_data);
+        world.set(bans);
 
-        world.set(MojangClient::new(&runtime, ApiProvider::MAT_DOES_DEV));
+        world.set(MojangClient::new(&runtime, ApiProvider::defaults()));
 
         let (receive_state, egress_comm) = init_proxy_comms(&runtime, address);
 
         world.set(receive_state);
 
-        let global = Global::new(shared.clone());
+        let capacity = world.get::<&ServerConfig>(PlayerCapacity::from_config);
+        let global = Global::new(shared.clone(), capacity);
 
         world.set(Compose::new(
             Compressors::new(shared.compression_level),
@@ -342,7 +338,8 @@ impl Hyperion {
             IoBuf::default(),
         ));
 
-        world.set(CraftingRegistry::default());
+        world.set(load_crafting_registry());
+        world.set(load_smelting_registry());
 
         world.set(Comms::default());
 
@@ -354,26 +351,85 @@ impl Hyperion {
         world.set(runtime);
         world.set(StreamLookup::default());
 
+        world.import::<ProfilerModule>();
         world.import::<SimModule>();
+
+        if let Some(path) = world_path {
+            info!("loading the world from {path:?}");
+            world.set(Blocks::new(world, &path)?);
+        }
+
         world.import::<EgressModule>();
         world.import::<IngressModule>();
 
+        #[cfg(feature = "metrics")]
+        {
+            let port = world.get::<&ServerConfig>(|config| config.metrics_port);
+
+            world.get::<&AsyncRuntime>(|runtime| -> anyhow::Result<()> {
+                let listener =
+                    runtime.block_on(tokio::net::TcpListener::bind(("0.0.0.0", port)))?;
+                runtime.spawn(metrics::serve(listener));
+                Ok(())
+            })?;
+
+            info!("serving metrics on port {port}");
+            world.import::<metrics::MetricsModule>();
+        }
+
         world
             .component::<Player>()
             .add_trait::<(flecs::With, EntitySize)>()
             .add_trait::<(flecs::With, Yaw)>()
-            .add_trait::<(flecs::With, Pitch)>();
+            .add_trait::<(flecs::With, Pitch)>()
+            .add_trait::<(flecs::With, HiddenEntities)>()
+            .add_trait::<(flecs::With, HiddenFrom)>();
 
         world.set(IgnMap::default());
+        world.set(PlayerRoster::default());
 
         handlers(world);
 
         app.run();
 
-        bail!("app exited");
+        info!("stopped");
+        Ok(())
     }
 }
 
+/// The built-in recipes, plus any vanilla data pack recipes in `run/recipes`.
+fn load_crafting_registry() -> CraftingRegistry {
+    let mut registry = CraftingRegistry::default();
+
+    let dir = Path::new("run/recipes");
+
+    if !dir.exists() {
+        return registry;
+    }
+
+    if let Err(e) = registry.load_vanilla(dir) {
+        warn!("failed to load vanilla recipes: {e:#}");
+    }
+
+    registry
+}
+
+fn load_smelting_registry() -> SmeltingRegistry {
+    let mut registry = SmeltingRegistry::default();
+
+    let dir = Path::new("run/recipes");
+
+    if !dir.exists() {
+        return registry;
+    }
+
+    if let Err(e) = registry.load_vanilla(dir) {
+        warn!("failed to load vanilla smelting recipes: {e:#}");
+    }
+
+    registry
+}
+
 /// A scratch buffer for intermediate operations. This will return an empty [`Vec`] when calling [`Scratch::obtain`].
 #[derive(Debug)]
 pub struct Scratch<A: Allocator = std::alloc::Global> {
@@ -1,7 +1,13 @@
 //! Agnostic networking primitives. Translates to correct protocol version.
 
 mod chat;
-pub use chat::{Chat, chat};
+pub use chat::{ActionBar, Chat, action_bar, chat};
+
+mod particle;
+pub use particle::{Particles, ParticlesBuilder, particles};
 
 mod sound;
 pub use sound::{Sound, SoundBuilder, sound};
+
+mod title;
+pub use title::{Title, title};
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{borrow::Cow, io::Write};
 
 use valence_protocol::packets::play;
 use valence_text::IntoText;
@@ -31,3 +31,24 @@ impl PacketBundle for &Chat {
         self.raw.encode_including_ids(&mut w)
     }
 }
+
+/// A message shown above the hotbar.
+#[must_use]
+pub struct ActionBar {
+    raw: play::GameMessageS2c<'static>,
+}
+
+pub fn action_bar<'a>(text: impl IntoText<'a>) -> ActionBar {
+    ActionBar {
+        raw: play::GameMessageS2c {
+            chat: Cow::Owned(text.into_text()),
+            overlay: true,
+        },
+    }
+}
+
+impl PacketBundle for &ActionBar {
+    fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
+        self.raw.encode_including_ids(&mut w)
+    }
+}
@@ -0,0 +1,114 @@
+use std::{borrow::Cow, io::Write};
+
+use glam::{IVec2, Vec3};
+use valence_protocol::{Particle, packets::play};
+
+use crate::{
+    PacketBundle,
+    net::{BroadcastLocal, Compose},
+    system_registry::SystemId,
+};
+
+#[must_use]
+pub struct Particles {
+    raw: play::ParticleS2c<'static>,
+}
+
+#[must_use]
+pub struct ParticlesBuilder {
+    particle: Particle,
+    position: Vec3,
+    offset: Vec3,
+    speed: f32,
+    count: i32,
+    long_distance: bool,
+}
+
+impl ParticlesBuilder {
+    /// How far particles may be spread from the position on each axis. Particles are spread
+    /// randomly by the client, so there is no seed to pick.
+    pub const fn offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub const fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// How many particles to spawn. A count of 0 spawns a single particle which moves in the
+    /// direction of the offset instead.
+    pub const fn count(mut self, count: i32) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Shows the particles from up to 512 blocks away instead of 32.
+    pub const fn long_distance(mut self, long_distance: bool) -> Self {
+        self.long_distance = long_distance;
+        self
+    }
+
+    pub fn build(self) -> Particles {
+        Particles {
+            raw: play::ParticleS2c {
+                particle: Cow::Owned(self.particle),
+                long_distance: self.long_distance,
+                position: self.position.as_dvec3(),
+                offset: self.offset,
+                max_speed: self.speed,
+                count: self.count,
+            },
+        }
+    }
+}
+
+impl Particles {
+    /// The chunk the particles are spawned in.
+    #[must_use]
+    pub fn chunk(&self) -> IVec2 {
+        let position = self.raw.position.floor().as_ivec3();
+        IVec2::new(position.x >> 4, position.z >> 4)
+    }
+
+    /// Shows the particles to the players near them, like [`Compose::broadcast_local`].
+    pub fn broadcast_local<'a>(
+        &'a self,
+        compose: &'a Compose,
+        system_id: SystemId,
+    ) -> BroadcastLocal<'a, &'a Self> {
+        compose.broadcast_local(self, self.chunk(), system_id)
+    }
+}
+
+impl PacketBundle for &Particles {
+    fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
+        self.raw.encode_including_ids(&mut w)
+    }
+}
+
+pub const fn particles(particle: Particle, position: Vec3) -> ParticlesBuilder {
+    ParticlesBuilder {
+        particle,
+        position,
+        offset: Vec3::ZERO,
+        speed: 0.0,
+        count: 1,
+        long_distance: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{IVec2, Vec3};
+    use valence_protocol::Particle;
+
+    use crate::net::agnostic::particles;
+
+    #[test]
+    fn chunk_is_where_the_particles_are() {
+        let built = particles(Particle::Explosion, Vec3::new(-0.5, 64.0, 31.9)).build();
+        assert_eq!(built.chunk(), IVec2::new(-1, 1));
+    }
+}
@@ -1,12 +1,16 @@
 use std::io::Write;
 
-use glam::Vec3;
+use glam::{IVec2, Vec3};
 use valence_protocol::{
     packets::play,
     sound::{SoundCategory, SoundId},
 };
 
-use crate::PacketBundle;
+use crate::{
+    PacketBundle,
+    net::{BroadcastLocal, Compose},
+    system_registry::SystemId,
+};
 
 #[must_use]
 pub struct Sound {
@@ -19,6 +23,8 @@ pub struct SoundBuilder {
     pitch: f32,
     volume: f32,
     seed: Option<i64>,
+    range: Option<f32>,
+    category: SoundCategory,
     sound: valence_ident::Ident<&'static str>,
 }
 
@@ -38,23 +44,54 @@ impl SoundBuilder {
         self
     }
 
+    /// How far away the sound can be heard, in blocks. By default this is 16 blocks, or 16 times
+    /// the volume for sounds louder than 1.0.
+    pub const fn range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// The volume slider in the client's sound settings that applies to this sound.
+    pub const fn category(mut self, category: SoundCategory) -> Self {
+        self.category = category;
+        self
+    }
+
     pub fn build(self) -> Sound {
         Sound {
             raw: play::PlaySoundS2c {
                 id: SoundId::Direct {
                     id: self.sound.into(),
-                    range: None,
+                    range: self.range,
                 },
                 position: (self.position * 8.0).as_ivec3(),
                 volume: self.volume,
                 pitch: self.pitch,
                 seed: self.seed.unwrap_or_else(|| fastrand::i64(..)),
-                category: SoundCategory::Master,
+                category: self.category,
             },
         }
     }
 }
 
+impl Sound {
+    /// The chunk the sound is played in.
+    #[must_use]
+    pub const fn chunk(&self) -> IVec2 {
+        // the position is in eighths of a block
+        IVec2::new(self.raw.position.x >> 7, self.raw.position.z >> 7)
+    }
+
+    /// Plays the sound to the players near it, like [`Compose::broadcast_local`].
+    pub fn broadcast_local<'a>(
+        &'a self,
+        compose: &'a Compose,
+        system_id: SystemId,
+    ) -> BroadcastLocal<'a, &'a Self> {
+        compose.broadcast_local(self, self.chunk(), system_id)
+    }
+}
+
 impl PacketBundle for &Sound {
     fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
         self.raw.encode_including_ids(&mut w)
@@ -67,6 +104,24 @@ pub const fn sound(sound: valence_ident::Ident<&'static str>, position: Vec3) ->
         pitch: 1.0,
         volume: 1.0,
         seed: None,
+        range: None,
+        category: SoundCategory::Master,
         sound,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{IVec2, Vec3};
+    use valence_ident::ident;
+
+    use crate::net::agnostic::sound;
+
+    #[test]
+    fn chunk_is_where_the_sound_is() {
+        let position = Vec3::new(-0.5, 64.0, 31.9);
+        let built = sound(ident!("minecraft:block.stone.break"), position).build();
+
+        assert_eq!(built.chunk(), IVec2::new(-1, 1));
+    }
+}
@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+
+use flecs_ecs::core::World;
+use glam::IVec2;
+use hyperion_proto::ChunkPosition;
+use valence_protocol::packets::play;
+use valence_text::{IntoText, Text};
+
+use crate::{
+    net::{Compose, DataBundle, NetworkStreamRef, SendReport},
+    system_registry::SystemId,
+};
+
+/// A title in the middle of the screen, with an optional subtitle below it. Showing a title
+/// replaces the one players already see, including its subtitle.
+///
+/// A title takes several packets, so it is sent with its own methods instead of through
+/// [`Compose`] directly.
+#[must_use]
+pub struct Title {
+    title: Text,
+    subtitle: Option<Text>,
+    fade: play::TitleFadeS2c,
+}
+
+/// A title showing `text`, which fades and stays for as long as vanilla titles do.
+pub fn title<'a>(text: impl IntoText<'a>) -> Title {
+    Title {
+        title: text.into_text(),
+        subtitle: None,
+        fade: play::TitleFadeS2c {
+            fade_in: 10,
+            stay: 70,
+            fade_out: 20,
+        },
+    }
+}
+
+impl Title {
+    pub fn subtitle<'a>(mut self, text: impl IntoText<'a>) -> Self {
+        self.subtitle = Some(text.into_text());
+        self
+    }
+
+    /// How many ticks the title takes to fade in.
+    pub const fn fade_in(mut self, ticks: i32) -> Self {
+        self.fade.fade_in = ticks;
+        self
+    }
+
+    /// How many ticks the title is fully shown for.
+    pub const fn stay(mut self, ticks: i32) -> Self {
+        self.fade.stay = ticks;
+        self
+    }
+
+    /// How many ticks the title takes to fade out.
+    pub const fn fade_out(mut self, ticks: i32) -> Self {
+        self.fade.fade_out = ticks;
+        self
+    }
+
+    /// Adds the packets that show the title to `bundle`.
+    pub fn add_to(&self, bundle: &mut DataBundle<'_>, world: &World) -> anyhow::Result<()> {
+        // clearing first keeps the subtitle of the title being replaced from showing up again
+        bundle.add_packet(&play::ClearTitleS2c { reset: false }, world)?;
+        bundle.add_packet(&self.fade, world)?;
+
+        if let Some(subtitle) = &self.subtitle {
+            let pkt = play::SubtitleS2c {
+                subtitle_text: Cow::Borrowed(subtitle),
+            };
+            bundle.add_packet(&pkt, world)?;
+        }
+
+        // the subtitle and fade only take effect once the title is sent
+        let pkt = play::TitleS2c {
+            title_text: Cow::Borrowed(&self.title),
+        };
+        bundle.add_packet(&pkt, world)
+    }
+
+    /// Shows the title to a single player.
+    pub fn unicast(
+        &self,
+        compose: &Compose,
+        stream: NetworkStreamRef,
+        system_id: SystemId,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        let mut bundle = DataBundle::new(compose);
+        self.add_to(&mut bundle, world)?;
+        bundle.send(world, stream, system_id)
+    }
+
+    /// Shows the title to everyone.
+    pub fn broadcast(
+        &self,
+        compose: &Compose,
+        system_id: SystemId,
+        world: &World,
+    ) -> anyhow::Result<SendReport> {
+        let mut bundle = DataBundle::new(compose);
+        self.add_to(&mut bundle, world)?;
+
+        Ok(compose
+            .io_buf
+            .broadcast_raw(&bundle.data, &[], system_id, world))
+    }
+
+    /// Shows the title to the players near chunk `center`, like [`Compose::broadcast_local`].
+    pub fn broadcast_local(
+        &self,
+        compose: &Compose,
+        center: IVec2,
+        system_id: SystemId,
+        world: &World,
+    ) -> anyhow::Result<SendReport> {
+        let center = ChunkPosition {
+            x: i16::try_from(center.x)?,
+            z: i16::try_from(center.y)?,
+        };
+
+        let mut bundle = DataBundle::new(compose);
+        self.add_to(&mut bundle, world)?;
+
+        Ok(compose
+            .io_buf
+            .broadcast_local_raw(&bundle.data, center, &[], system_id, world))
+    }
+}
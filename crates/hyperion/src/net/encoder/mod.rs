@@ -126,7 +126,8 @@ impl PacketEncoder {
 
         let threshold = u64::from(self.threshold.0.unsigned_abs());
 
-        if data_len > threshold {
+        // like vanilla, packets shorter than the threshold are not worth compressing
+        if data_len >= threshold {
             let scratch = scratch.obtain();
 
             debug_assert!(scratch.is_empty());
@@ -463,3 +464,73 @@ impl PacketEncoder {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use libdeflater::{CompressionLvl, Compressor, Decompressor};
+    use valence_protocol::{CompressionThreshold, Decode, VarInt};
+
+    use super::{PacketEncoder, append_packet_without_compression};
+    use crate::{Scratch, net::agnostic};
+
+    /// The data length and the data after it of a packet encoded with compression.
+    fn encode(message: &str, threshold: i32) -> (i32, Vec<u8>) {
+        let encoder = PacketEncoder::new(CompressionThreshold(threshold));
+        let mut compressor = Compressor::new(CompressionLvl::default());
+        let mut scratch = Scratch::default();
+        let mut buf = Vec::new();
+
+        encoder
+            .append_packet(
+                &agnostic::chat(message),
+                &mut buf,
+                &mut scratch,
+                &mut compressor,
+            )
+            .unwrap();
+
+        let mut r = buf.as_slice();
+        let packet_len = VarInt::decode(&mut r).unwrap().0;
+        assert_eq!(usize::try_from(packet_len).unwrap(), r.len());
+
+        let data_len = VarInt::decode(&mut r).unwrap().0;
+        (data_len, r.to_vec())
+    }
+
+    /// The packet ID and data of `message`.
+    fn uncompressed(message: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        append_packet_without_compression(&agnostic::chat(message), &mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        VarInt::decode(&mut r).unwrap();
+        r.to_vec()
+    }
+
+    #[test]
+    fn packets_below_the_threshold_are_not_compressed() {
+        let message = "hi";
+        let (data_len, data) = encode(message, 256);
+
+        assert_eq!(data_len, 0);
+        assert_eq!(data, uncompressed(message));
+    }
+
+    #[test]
+    fn packets_at_or_above_the_threshold_are_compressed() {
+        let message = "a".repeat(1000);
+        let expected = uncompressed(&message);
+
+        for threshold in [256, i32::try_from(expected.len()).unwrap()] {
+            let (data_len, data) = encode(&message, threshold);
+            assert_eq!(usize::try_from(data_len).unwrap(), expected.len());
+            assert!(data.len() < expected.len());
+
+            let mut decompressed = vec![0; expected.len()];
+            Decompressor::new()
+                .zlib_decompress(&data, &mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, expected);
+        }
+    }
+}
@@ -2,19 +2,24 @@
 
 use std::{
     cell::{Cell, RefCell},
-    fmt::Debug,
+    fmt::{self, Debug, Display, Formatter},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use bumpalo::Bump;
 use byteorder::WriteBytesExt;
 use bytes::{Bytes, BytesMut};
 pub use decoder::PacketDecoder;
-use derive_more::Deref;
 use flecs_ecs::{core::World, macros::Component};
 use glam::IVec2;
 use hyperion_proto::{ChunkPosition, ServerToProxyMessage};
 use libdeflater::CompressionLvl;
+use parking_lot::Mutex;
 use rkyv::util::AlignedVec;
+use rustc_hash::FxHashMap;
+use slotmap::KeyData;
+use smallvec::SmallVec;
+use valence_protocol::CompressionThreshold;
 
 use crate::{
     Global, PacketBundle, Scratch, Scratches,
@@ -42,9 +47,17 @@ pub const MAX_PACKET_SIZE: usize = valence_protocol::MAX_PACKET_SIZE as usize;
 pub const MINECRAFT_VERSION: &str = "1.20.1";
 
 /// Thread-local [`libdeflater::Compressor`] for encoding packets.
-#[derive(Component, Deref)]
+///
+/// The compression level can be changed while the server is running. Compressors created at an
+/// older level are replaced the next time their thread uses them.
+#[derive(Component)]
 pub struct Compressors {
     compressors: ThreadLocal<RefCell<libdeflater::Compressor>>,
+    /// The generation and level each thread's compressor was created with.
+    created: ThreadLocal<Cell<(u64, CompressionLvl)>>,
+    /// Incremented whenever the level changes.
+    generation: AtomicU64,
+    level: Mutex<CompressionLvl>,
 }
 
 impl Compressors {
@@ -54,7 +67,46 @@ impl Compressors {
             compressors: ThreadLocal::new_with(|_| {
                 RefCell::new(libdeflater::Compressor::new(level))
             }),
+            created: ThreadLocal::new_with(|_| Cell::new((0, level))),
+            generation: AtomicU64::new(0),
+            level: Mutex::new(level),
+        }
+    }
+
+    /// The compressor of the current thread, at the current level.
+    ///
+    /// A compressor that is still borrowed when the level changed keeps its level until it is
+    /// used again after the borrow ends.
+    fn get(&self, world: &World) -> &RefCell<libdeflater::Compressor> {
+        let compressor = self.compressors.get(world);
+        let created = self.created.get(world);
+
+        let current = self.generation.load(Ordering::Acquire);
+
+        if created.get().0 != current
+            && let Ok(mut compressor) = compressor.try_borrow_mut()
+        {
+            let level = *self.level.lock();
+            *compressor = libdeflater::Compressor::new(level);
+            created.set((current, level));
         }
+
+        compressor
+    }
+
+    /// The level the compressor of the current thread was created with. If the level changed
+    /// since, the compressor is replaced the next time it is used.
+    #[must_use]
+    pub fn thread_level(&self, world: &World) -> CompressionLvl {
+        self.created.get(world).get().1
+    }
+
+    /// Compresses at `level` from now on. Compressors of other threads are replaced the next time
+    /// they are used, so threads that are compressing a packet right now finish it at the old
+    /// level.
+    pub fn set_level(&self, level: CompressionLvl) {
+        *self.level.lock() = level;
+        self.generation.fetch_add(1, Ordering::Release);
     }
 }
 
@@ -62,7 +114,7 @@ impl Compressors {
 ///
 /// This struct contains a stream ID that serves as a unique identifier for the network stream and a packet order counter
 /// that helps in maintaining the correct sequence of packets being sent through the proxy.
-#[derive(Component, Copy, Clone, Debug)]
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct NetworkStreamRef {
     /// Unique identifier for the network stream.
     stream_id: u64,
@@ -74,12 +126,33 @@ impl NetworkStreamRef {
         Self { stream_id }
     }
 
+    /// The raw stream ID the proxy assigned to this connection. It stays the same for as long as
+    /// the player is connected, so it can be used to tell connections apart in logs and metrics.
     #[must_use]
-    pub const fn inner(self) -> u64 {
+    pub const fn id(self) -> u64 {
         self.stream_id
     }
 }
 
+/// Prints the proxy's slot map key the ID is made of as `{index}v{version}`.
+impl Display for NetworkStreamRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let key = KeyData::from_ffi(self.stream_id);
+        write!(f, "{key:?}")
+    }
+}
+
+/// How far behind a player is on the packets sent to them, as the proxy last reported. The proxy
+/// reports this about once a second, so players only have it from the first report on.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Bytes of broadcasts dropped since the previous report because of the limit set with
+    /// [`Compose::set_stream_buffer_limit`].
+    pub dropped_bytes: u64,
+    /// Bytes waiting to be written to the player.
+    pub queued_bytes: u64,
+}
+
 /// A singleton that can be used to compose and encode packets.
 #[derive(Component)]
 pub struct Compose {
@@ -90,10 +163,17 @@ pub struct Compose {
     pub bump: ThreadLocal<Bump>,
 }
 
+/// Packets for a single player, sent to the proxy as one [`hyperion_proto::Unicast`] with one
+/// order number.
+///
+/// Prefer this over calling [`Compose::unicast`] for each packet when sending a player many
+/// packets at once, such as when they join or load chunks, as every message to the proxy has to be
+/// framed and ordered.
 #[must_use]
 pub struct DataBundle<'a> {
     compose: &'a Compose,
     data: BytesMut,
+    compression_threshold: Option<CompressionThreshold>,
 }
 
 impl<'a> DataBundle<'a> {
@@ -101,23 +181,36 @@ impl<'a> DataBundle<'a> {
         Self {
             compose,
             data: BytesMut::new(),
+            compression_threshold: None,
         }
     }
 
+    /// Compresses packets added from now on if they are at least `threshold` bytes long, or never
+    /// if `threshold` is `None`, instead of following the global compression threshold. This has
+    /// no effect if compression is disabled.
+    pub fn compression_threshold(&mut self, threshold: Option<u32>) {
+        self.compression_threshold = Some(threshold_override(threshold));
+    }
+
+    /// Encodes `pkt` after the packets added so far.
     pub fn add_packet(&mut self, pkt: impl PacketBundle, world: &World) -> anyhow::Result<()> {
-        let data = self
-            .compose
-            .io_buf
-            .encode_packet(pkt, self.compose, world)?;
+        let data = self.compose.io_buf.encode_packet(
+            pkt,
+            self.compose,
+            self.compression_threshold,
+            world,
+        )?;
         // todo: test to see if this ever actually unsplits
         self.data.unsplit(data);
         Ok(())
     }
 
+    /// Adds already encoded packets, such as cached chunk data.
     pub fn add_raw(&mut self, raw: &[u8]) {
         self.data.extend_from_slice(raw);
     }
 
+    /// Sends every packet added to `stream`. Nothing is sent if no packets were added.
     pub fn send(
         self,
         world: &World,
@@ -161,15 +254,33 @@ impl Compose {
     /// Broadcast globally to all players
     ///
     /// See <https://github.com/andrewgazelka/hyperion-proto/blob/main/src/server_to_proxy.proto#L17-L22>
-    pub const fn broadcast<P>(&self, packet: P, system_id: SystemId) -> Broadcast<'_, P>
+    pub fn broadcast<P>(&self, packet: P, system_id: SystemId) -> Broadcast<'_, P>
     where
         P: PacketBundle,
     {
         Broadcast {
             packet,
             compose: self,
-            exclude: 0,
+            exclude: SmallVec::new(),
+            compression_threshold: None,
             system_id,
+            latest: None,
+        }
+    }
+
+    /// Broadcast globally to all players, but only the latest packet broadcast with `key` this
+    /// tick is sent. Sending it replaces the one sent with the same key before.
+    ///
+    /// This is meant for packets that only matter as of the end of the tick, such as an entity's
+    /// absolute position keyed by the entity. Packets that build on the ones before them, like
+    /// relative moves, must not be sent this way.
+    pub fn broadcast_latest<P>(&self, packet: P, key: u64, system_id: SystemId) -> Broadcast<'_, P>
+    where
+        P: PacketBundle,
+    {
+        Broadcast {
+            latest: Some(key),
+            ..self.broadcast(packet, system_id)
         }
     }
 
@@ -186,6 +297,10 @@ impl Compose {
 
     /// Broadcast a packet within a certain region.
     ///
+    /// `center` is a chunk position. The proxy sends the packet to every player whose chunk is at
+    /// most 16 chunks away from it along both axes (Chebyshev distance), which is the same for
+    /// every local broadcast and cannot be changed per packet.
+    ///
     /// See <https://github.com/andrewgazelka/hyperion-proto/blob/main/src/server_to_proxy.proto#L17-L22>
     pub fn broadcast_local<P>(
         &self,
@@ -199,7 +314,8 @@ impl Compose {
         BroadcastLocal {
             packet,
             compose: self,
-            exclude: 0,
+            exclude: SmallVec::new(),
+            compression_threshold: None,
             center: ChunkPosition {
                 x: i16::try_from(center.x).unwrap(),
                 z: i16::try_from(center.y).unwrap(),
@@ -215,7 +331,7 @@ impl Compose {
         stream_id: NetworkStreamRef,
         system_id: SystemId,
         world: &World,
-    ) -> anyhow::Result<()>
+    ) -> anyhow::Result<SendReport>
     where
         P: PacketBundle,
     {
@@ -232,6 +348,42 @@ impl Compose {
         .send(world)
     }
 
+    /// Send a packet to each of `streams`. It is only encoded and sent to the proxy once, so this is
+    /// cheaper than unicasting it to each of them.
+    pub fn multicast<'a, P>(
+        &self,
+        packet: P,
+        streams: impl IntoIterator<Item = &'a NetworkStreamRef>,
+        system_id: SystemId,
+        world: &World,
+    ) -> anyhow::Result<SendReport>
+    where
+        P: PacketBundle,
+    {
+        let ids = self.io_buf.stream_ids.get(world);
+        let mut ids = ids.borrow_mut();
+
+        ids.clear();
+        ids.extend(streams.into_iter().map(|stream| stream.stream_id));
+
+        if ids.is_empty() {
+            return Ok(SendReport::default());
+        }
+
+        let bytes = self.io_buf.encode_packet(packet, self, None, world)?;
+
+        Ok(self.io_buf.multicast_raw(&bytes, &ids, system_id, world))
+    }
+
+    /// Caps how many bytes the proxy may hold for `stream`, or removes the cap if `max_bytes` is
+    /// `0`. Once a player has that many bytes waiting to be written, broadcasts to them are
+    /// dropped until they catch up, while unicasts and multicasts are still queued. How much was
+    /// dropped is reported in the player's [`StreamStats`].
+    pub fn set_stream_buffer_limit(&self, stream: NetworkStreamRef, max_bytes: u64, world: &World) {
+        self.io_buf
+            .set_stream_buffer_limit(stream, max_bytes, world);
+    }
+
     /// Send a packet to a single player without compression.
     pub fn unicast_no_compression<P>(
         &self,
@@ -239,7 +391,7 @@ impl Compose {
         stream_id: NetworkStreamRef,
         system_id: SystemId,
         world: &World,
-    ) -> anyhow::Result<()>
+    ) -> anyhow::Result<SendReport>
     where
         P: valence_protocol::Packet + valence_protocol::Encode,
     {
@@ -253,10 +405,43 @@ impl Compose {
         .send(world)
     }
 
+    /// Encodes `packet` once, so it can be sent any number of times with
+    /// [`Self::broadcast_precomputed`] or [`DataBundle::add_raw`], such as a packet that rarely
+    /// changes.
+    ///
+    /// The packet is compressed following the global compression threshold at the time it is
+    /// encoded. Clients reject packets compressed for another threshold, so whoever keeps the bytes
+    /// is responsible for encoding the packet again if the threshold changes.
+    pub fn encode<P>(&self, packet: P, world: &World) -> anyhow::Result<Bytes>
+    where
+        P: PacketBundle,
+    {
+        let bytes = self.io_buf.encode_packet(packet, self, None, world)?;
+        Ok(bytes.freeze())
+    }
+
+    /// Broadcast packets encoded by [`Self::encode`] to all players. `data` is sent as it is, so
+    /// it must be valid for the current compression threshold.
+    pub fn broadcast_precomputed(
+        &self,
+        data: &[u8],
+        system_id: SystemId,
+        world: &World,
+    ) -> SendReport {
+        self.io_buf.broadcast_raw(data, &[], system_id, world)
+    }
+
+    /// An encoder using the global compression threshold, unless `threshold` overrides it.
     #[must_use]
-    pub(crate) fn encoder(&self) -> PacketEncoder {
-        let threshold = self.global.shared.compression_threshold;
-        PacketEncoder::new(threshold)
+    pub(crate) fn encoder(&self, threshold: Option<CompressionThreshold>) -> PacketEncoder {
+        let global = self.global.shared.compression_threshold;
+        PacketEncoder::new(effective_threshold(global, threshold))
+    }
+
+    /// Compresses packets at `level` from now on. Compressors of other threads are replaced the
+    /// next time they are used.
+    pub fn set_compression_level(&self, level: CompressionLvl) {
+        self.compressor.set_level(level);
     }
 
     /// Obtain a thread-local scratch buffer.
@@ -279,7 +464,127 @@ pub struct IoBuf {
     // system_on: ThreadLocal<Cell<u32>>,
     // broadcast_buffer: ThreadLocal<RefCell<BytesMut>>,
     temp_buffer: ThreadLocal<RefCell<BytesMut>>,
+    /// The streams a multicast is sent to, kept so collecting them does not allocate every time.
+    stream_ids: ThreadLocal<RefCell<Vec<u64>>>,
     idx: ThreadLocal<Cell<u16>>,
+    stats: ThreadLocal<Cell<IoStats>>,
+    /// Frames sent with [`Compose::broadcast_latest`] by key, which are only written to `buffer`
+    /// once the tick is over.
+    latest: ThreadLocal<RefCell<FxHashMap<u64, LatestFrame>>>,
+    /// Tells which of the frames kept with the same key on different threads was sent last.
+    latest_sequence: AtomicU64,
+}
+
+/// A frame sent with [`Compose::broadcast_latest`], including its length.
+#[derive(Debug)]
+struct LatestFrame {
+    sequence: u64,
+    frame: AlignedVec,
+}
+
+/// What a send queued for the proxy. The proxy passes it on to players, so how many of them
+/// receive it is not known yet.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SendReport {
+    /// The length of the message queued for the proxy, as counted in [`IoStats`].
+    pub bytes: u64,
+}
+
+/// Messages queued for the proxy and their length in bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+impl Traffic {
+    const fn record(&mut self, bytes: u64) {
+        self.frames += 1;
+        self.bytes += bytes;
+    }
+
+    const fn add(self, other: Self) -> Self {
+        Self {
+            frames: self.frames + other.frames,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+
+    const fn since(self, earlier: Self) -> Self {
+        Self {
+            frames: self.frames.saturating_sub(earlier.frames),
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+        }
+    }
+}
+
+/// The traffic queued for the proxy since the server started, by kind of message.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub unicast: Traffic,
+    pub multicast: Traffic,
+    pub broadcast: Traffic,
+    pub broadcast_local: Traffic,
+}
+
+impl IoStats {
+    /// The traffic of every kind of message together.
+    #[must_use]
+    pub const fn total(&self) -> Traffic {
+        self.unicast
+            .add(self.multicast)
+            .add(self.broadcast)
+            .add(self.broadcast_local)
+    }
+
+    const fn add(self, other: Self) -> Self {
+        Self {
+            unicast: self.unicast.add(other.unicast),
+            multicast: self.multicast.add(other.multicast),
+            broadcast: self.broadcast.add(other.broadcast),
+            broadcast_local: self.broadcast_local.add(other.broadcast_local),
+        }
+    }
+
+    /// The traffic queued after `earlier` was taken.
+    #[must_use]
+    pub const fn since(self, earlier: Self) -> Self {
+        Self {
+            unicast: self.unicast.since(earlier.unicast),
+            multicast: self.multicast.since(earlier.multicast),
+            broadcast: self.broadcast.since(earlier.broadcast),
+            broadcast_local: self.broadcast_local.since(earlier.broadcast_local),
+        }
+    }
+}
+
+/// The traffic queued for the proxy each tick.
+///
+/// Every thread counts what it queues on its own, so queuing a message never touches shared
+/// state. The counts are only added up when a snapshot is taken.
+#[derive(Component, Debug, Default)]
+pub struct NetworkMetrics {
+    /// The totals when the last snapshot was taken.
+    taken: IoStats,
+    last_tick: IoStats,
+}
+
+impl NetworkMetrics {
+    /// The traffic queued since the last snapshot, which is remembered as [`Self::last_tick`].
+    pub fn take_snapshot(&mut self, io_buf: &IoBuf) -> IoStats {
+        let total = io_buf.stats();
+
+        self.last_tick = total.since(self.taken);
+        self.taken = total;
+
+        self.last_tick
+    }
+
+    /// The traffic of the last tick, updated before it is sent to the proxy.
+    #[must_use]
+    pub const fn last_tick(&self) -> IoStats {
+        self.last_tick
+    }
 }
 
 impl IoBuf {
@@ -290,9 +595,30 @@ impl IoBuf {
         result
     }
 
+    /// The order of the next message queued on this thread by `system_id`. Every kind of message
+    /// carries one, and the proxy writes each player's packets sorted by it.
+    ///
+    /// Orders only increase within a thread and across systems, as messages of one system queued
+    /// on different threads are not ordered among each other anyway.
     pub fn order_id(&self, system_id: SystemId, world: &World) -> u32 {
         u32::from(system_id.id()) << 16 | u32::from(self.fetch_add_idx(world))
     }
+
+    /// The traffic queued by every thread so far.
+    #[must_use]
+    pub fn stats(&self) -> IoStats {
+        self.stats
+            .iter()
+            .map(Cell::get)
+            .fold(IoStats::default(), IoStats::add)
+    }
+
+    fn count_sent(&self, world: &World, count: impl FnOnce(&mut IoStats)) {
+        let cell = self.stats.get(world);
+        let mut stats = cell.get();
+        count(&mut stats);
+        cell.set(stats);
+    }
 }
 
 /// A broadcast builder
@@ -300,8 +626,12 @@ impl IoBuf {
 pub struct Broadcast<'a, P> {
     packet: P,
     compose: &'a Compose,
-    exclude: u64,
+    /// Most broadcasts exclude at most one player, which fits without allocating.
+    exclude: SmallVec<[u64; 1]>,
+    compression_threshold: Option<CompressionThreshold>,
     system_id: SystemId,
+    /// The key of [`Compose::broadcast_latest`].
+    latest: Option<u64>,
 }
 
 /// A unicast builder
@@ -318,7 +648,7 @@ impl<P> Unicast<'_, P>
 where
     P: PacketBundle,
 {
-    fn send(self, world: &World) -> anyhow::Result<()> {
+    fn send(self, world: &World) -> anyhow::Result<SendReport> {
         self.compose.io_buf.unicast_private(
             self.packet,
             self.stream_id,
@@ -332,29 +662,53 @@ where
 
 impl<P> Broadcast<'_, P> {
     /// Send the packet to all players.
-    pub fn send(self, world: &World) -> anyhow::Result<()>
+    pub fn send(self, world: &World) -> anyhow::Result<SendReport>
     where
         P: PacketBundle,
     {
-        let bytes = self
-            .compose
-            .io_buf
-            .encode_packet(self.packet, self.compose, world)?;
+        let bytes = self.compose.io_buf.encode_packet(
+            self.packet,
+            self.compose,
+            self.compression_threshold,
+            world,
+        )?;
 
-        self.compose
-            .io_buf
-            .broadcast_raw(&bytes, self.exclude, self.system_id, world);
+        let io_buf = &self.compose.io_buf;
 
-        Ok(())
+        let report = match self.latest {
+            Some(key) => {
+                io_buf.broadcast_latest_raw(&bytes, &self.exclude, key, self.system_id, world)
+            }
+            None => io_buf.broadcast_raw(&bytes, &self.exclude, self.system_id, world),
+        };
+
+        Ok(report)
     }
 
-    /// Exclude a certain player from the broadcast. This can only be called once.
-    pub fn exclude(self, exclude: NetworkStreamRef) -> Self {
-        Broadcast {
-            packet: self.packet,
-            compose: self.compose,
-            system_id: self.system_id,
-            exclude: exclude.stream_id,
+    /// Exclude a certain player from the broadcast, in addition to those already excluded.
+    pub fn exclude(mut self, exclude: NetworkStreamRef) -> Self {
+        if !self.exclude.contains(&exclude.stream_id) {
+            self.exclude.push(exclude.stream_id);
+        }
+
+        self
+    }
+
+    /// Exclude every player in `streams` from the broadcast, in addition to those already
+    /// excluded.
+    pub fn exclude_all<'b>(self, streams: impl IntoIterator<Item = &'b NetworkStreamRef>) -> Self {
+        streams
+            .into_iter()
+            .fold(self, |broadcast, &stream| broadcast.exclude(stream))
+    }
+
+    /// Compresses the packet if it is at least `threshold` bytes long, or never if `threshold` is
+    /// `None`, instead of following the global compression threshold. This has no effect if
+    /// compression is disabled.
+    pub fn compression_threshold(self, threshold: Option<u32>) -> Self {
+        Self {
+            compression_threshold: Some(threshold_override(threshold)),
+            ..self
         }
     }
 }
@@ -365,47 +719,106 @@ pub struct BroadcastLocal<'a, P> {
     packet: P,
     compose: &'a Compose,
     center: ChunkPosition,
-    exclude: u64,
+    /// Most broadcasts exclude at most one player, which fits without allocating.
+    exclude: SmallVec<[u64; 1]>,
+    compression_threshold: Option<CompressionThreshold>,
     system_id: SystemId,
 }
 
 impl<P> BroadcastLocal<'_, P> {
     /// Send the packet
-    pub fn send(self, world: &World) -> anyhow::Result<()>
+    pub fn send(self, world: &World) -> anyhow::Result<SendReport>
     where
         P: PacketBundle,
     {
-        let bytes = self
-            .compose
-            .io_buf
-            .encode_packet(self.packet, self.compose, world)?;
+        let bytes = self.compose.io_buf.encode_packet(
+            self.packet,
+            self.compose,
+            self.compression_threshold,
+            world,
+        )?;
 
-        self.compose.io_buf.broadcast_local_raw(
+        Ok(self.compose.io_buf.broadcast_local_raw(
             &bytes,
             self.center,
-            self.exclude,
+            &self.exclude,
             self.system_id,
             world,
-        );
-
-        Ok(())
+        ))
     }
 
-    /// Exclude a certain player from the broadcast. This can only be called once.
+    /// Exclude a certain player from the broadcast, in addition to those already excluded.
     pub fn exclude(self, exclude: NetworkStreamRef) -> Self {
-        BroadcastLocal {
-            packet: self.packet,
-            compose: self.compose,
-            center: self.center,
-            exclude: exclude.stream_id,
-            system_id: self.system_id,
+        self.exclude_many(&[exclude.stream_id])
+    }
+
+    /// Exclude every stream in `streams` from the broadcast, in addition to those already
+    /// excluded.
+    pub fn exclude_many(mut self, streams: &[u64]) -> Self {
+        for &stream in streams {
+            if !self.exclude.contains(&stream) {
+                self.exclude.push(stream);
+            }
+        }
+
+        self
+    }
+
+    /// Exclude every player in `streams` from the broadcast, in addition to those already
+    /// excluded.
+    pub fn exclude_all<'b>(self, streams: impl IntoIterator<Item = &'b NetworkStreamRef>) -> Self {
+        streams
+            .into_iter()
+            .fold(self, |broadcast, &stream| broadcast.exclude(stream))
+    }
+
+    /// Compresses the packet if it is at least `threshold` bytes long, or never if `threshold` is
+    /// `None`, instead of following the global compression threshold. This has no effect if
+    /// compression is disabled.
+    pub fn compression_threshold(self, threshold: Option<u32>) -> Self {
+        Self {
+            compression_threshold: Some(threshold_override(threshold)),
+            ..self
         }
     }
 }
 
+/// The threshold `threshold` given to a builder's `compression_threshold` stands for.
+fn threshold_override(threshold: Option<u32>) -> CompressionThreshold {
+    // no packet is long enough to reach `i32::MAX`
+    let threshold = threshold.map_or(i32::MAX, |threshold| {
+        i32::try_from(threshold).unwrap_or(i32::MAX)
+    });
+
+    CompressionThreshold(threshold)
+}
+
+/// The threshold to encode a packet with. Whether packets are compressed at all is agreed on with
+/// each client when they join, so `threshold` only overrides `global` if compression is enabled.
+const fn effective_threshold(
+    global: CompressionThreshold,
+    threshold: Option<CompressionThreshold>,
+) -> CompressionThreshold {
+    match threshold {
+        Some(threshold) if global.0 >= 0 => threshold,
+        _ => global,
+    }
+}
+
+/// The `exclude` and `exclude_many` fields of a broadcast excluding `exclude`. The first exclusion
+/// has its own field, so the common case of one needs no list.
+const fn split_exclusions(exclude: &[u64]) -> (u64, &[u64]) {
+    match exclude {
+        [] => (0, exclude),
+        [first, rest @ ..] => (*first, rest),
+    }
+}
+
 impl IoBuf {
     /// Returns an iterator over the result of splitting the buffer into packets with [`BytesMut::split`].
     pub fn reset_and_split(&mut self) -> impl Iterator<Item = Bytes> + '_ {
+        self.write_latest();
+
         // reset idx
         for elem in &mut self.idx {
             elem.set(0);
@@ -418,10 +831,75 @@ impl IoBuf {
         })
     }
 
+    /// How many bytes are queued for the proxy across every thread, without taking them. Frames
+    /// kept by [`Compose::broadcast_latest`] are counted on every thread that kept one.
+    ///
+    /// This must not be called while a system may be writing to the buffer.
+    #[must_use]
+    pub fn pending_bytes(&self) -> usize {
+        let buffered: usize = self.buffer.iter().map(|buffer| buffer.borrow().len()).sum();
+
+        let latest: usize = self
+            .latest
+            .iter()
+            .map(|latest| -> usize { latest.borrow().values().map(|kept| kept.frame.len()).sum() })
+            .sum();
+
+        buffered + latest
+    }
+
+    /// Discards everything queued for the proxy on every thread.
+    pub fn clear(&mut self) {
+        for elem in &mut self.idx {
+            elem.set(0);
+        }
+
+        for buffer in &mut self.buffer {
+            buffer.get_mut().clear();
+        }
+
+        for latest in &mut self.latest {
+            latest.get_mut().clear();
+        }
+    }
+
+    /// Writes the frames kept by [`Compose::broadcast_latest`] to the buffer. Of the frames with
+    /// the same key, only the one sent last is written, whichever thread it was sent on.
+    fn write_latest(&mut self) {
+        let mut latest = FxHashMap::<u64, LatestFrame>::default();
+
+        for kept in &mut self.latest {
+            for (key, frame) in kept.get_mut().drain() {
+                if latest
+                    .get(&key)
+                    .is_none_or(|newest| newest.sequence < frame.sequence)
+                {
+                    latest.insert(key, frame);
+                }
+            }
+        }
+
+        // frames carry their order, so it does not matter which buffer they end up in
+        let (Some(buffer), Some(stats)) = (self.buffer.first_mut(), self.stats.first_mut()) else {
+            return;
+        };
+
+        let buffer = buffer.get_mut();
+        let stats = stats.get_mut();
+
+        for LatestFrame { frame, .. } in latest.into_values() {
+            buffer.extend_from_slice(&frame);
+
+            let packet_len = u64::try_from(frame.len() - size_of::<u64>()).unwrap();
+            stats.broadcast.record(packet_len);
+        }
+    }
+
     fn encode_packet<P>(
         &self,
         packet: P,
         compose: &Compose,
+        threshold: Option<CompressionThreshold>,
         world: &World,
     ) -> anyhow::Result<BytesMut>
     where
@@ -436,10 +914,12 @@ impl IoBuf {
         let scratch = compose.scratch.get(world);
         let mut scratch = scratch.borrow_mut();
 
-        let result =
-            compose
-                .encoder()
-                .append_packet(packet, temp_buffer, &mut *scratch, &mut compressor)?;
+        let result = compose.encoder(threshold).append_packet(
+            packet,
+            temp_buffer,
+            &mut *scratch,
+            &mut compressor,
+        )?;
 
         Ok(result)
     }
@@ -468,38 +948,40 @@ impl IoBuf {
         compress: bool,
         system_id: SystemId,
         world: &World,
-    ) -> anyhow::Result<()>
+    ) -> anyhow::Result<SendReport>
     where
         P: PacketBundle,
     {
         let bytes = if compress {
-            self.encode_packet(packet, compose, world)?
+            self.encode_packet(packet, compose, None, world)?
         } else {
             self.encode_packet_no_compression(packet, world)?
         };
 
-        self.unicast_raw(&bytes, id, system_id, world);
-        Ok(())
+        Ok(self.unicast_raw(&bytes, id, system_id, world))
     }
 
     fn broadcast_local_raw(
         &self,
         data: &[u8],
         center: ChunkPosition,
-        exclude: u64,
+        exclude: &[u64],
         system_id: SystemId,
         world: &World,
-    ) {
+    ) -> SendReport {
         let buffer = self.buffer.get(world);
         let buffer = &mut *buffer.borrow_mut();
 
-        let order = u32::from(system_id.id()) << 16;
+        let order = self.order_id(system_id, world);
+
+        let (exclude, exclude_many) = split_exclusions(exclude);
 
         let to_send = hyperion_proto::BroadcastLocal {
             data,
             center,
             exclude,
             order,
+            exclude_many,
         };
 
         let to_send = ServerToProxyMessage::BroadcastLocal(to_send);
@@ -512,19 +994,25 @@ impl IoBuf {
         let new_len = buffer.len();
         let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+
+        self.count_sent(world, |stats| stats.broadcast_local.record(packet_len));
+
+        SendReport { bytes: packet_len }
     }
 
     pub(crate) fn broadcast_raw(
         &self,
         data: &[u8],
-        exclude: u64,
+        exclude: &[u64],
         system_id: SystemId,
         world: &World,
-    ) {
+    ) -> SendReport {
         let buffer = self.buffer.get(world);
         let buffer = &mut *buffer.borrow_mut();
 
-        let order = u32::from(system_id.id()) << 16;
+        let order = self.order_id(system_id, world);
+
+        let (exclude, exclude_many) = split_exclusions(exclude);
 
         let to_send = hyperion_proto::BroadcastGlobal {
             data,
@@ -533,6 +1021,7 @@ impl IoBuf {
             // Fortunately, `to_vec` will not require any allocation if the buffer is empty.
             exclude,
             order,
+            exclude_many,
         };
 
         let to_send = ServerToProxyMessage::BroadcastGlobal(to_send);
@@ -545,6 +1034,55 @@ impl IoBuf {
         let new_len = buffer.len();
         let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+
+        self.count_sent(world, |stats| stats.broadcast.record(packet_len));
+
+        SendReport { bytes: packet_len }
+    }
+
+    /// Like [`Self::broadcast_raw`], but the frame replaces the one sent with the same `key`
+    /// before, and is only written to the buffer by [`Self::reset_and_split`].
+    fn broadcast_latest_raw(
+        &self,
+        data: &[u8],
+        exclude: &[u64],
+        key: u64,
+        system_id: SystemId,
+        world: &World,
+    ) -> SendReport {
+        let latest = self.latest.get(world);
+        let latest = &mut *latest.borrow_mut();
+
+        let order = self.order_id(system_id, world);
+
+        let (exclude, exclude_many) = split_exclusions(exclude);
+
+        let to_send = hyperion_proto::BroadcastGlobal {
+            data,
+            exclude,
+            order,
+            exclude_many,
+        };
+
+        let to_send = ServerToProxyMessage::BroadcastGlobal(to_send);
+
+        // the frame being replaced is reused
+        let mut frame = latest
+            .remove(&key)
+            .map_or_else(AlignedVec::new, |replaced| replaced.frame);
+        frame.clear();
+
+        frame.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut frame).unwrap();
+
+        let packet_len = u64::try_from(frame.len() - size_of::<u64>()).unwrap();
+        frame[..8].copy_from_slice(&packet_len.to_be_bytes());
+
+        let sequence = self.latest_sequence.fetch_add(1, Ordering::Relaxed);
+        latest.insert(key, LatestFrame { sequence, frame });
+
+        SendReport { bytes: packet_len }
     }
 
     pub(crate) fn unicast_raw(
@@ -553,7 +1091,7 @@ impl IoBuf {
         stream: NetworkStreamRef,
         system_id: SystemId,
         world: &World,
-    ) {
+    ) -> SendReport {
         let buffer = self.buffer.get(world);
         let buffer = &mut *buffer.borrow_mut();
 
@@ -576,6 +1114,44 @@ impl IoBuf {
         let new_len = buffer.len();
         let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+
+        self.count_sent(world, |stats| stats.unicast.record(packet_len));
+
+        SendReport { bytes: packet_len }
+    }
+
+    pub(crate) fn multicast_raw(
+        &self,
+        data: &[u8],
+        streams: &[u64],
+        system_id: SystemId,
+        world: &World,
+    ) -> SendReport {
+        let buffer = self.buffer.get(world);
+        let buffer = &mut *buffer.borrow_mut();
+
+        let order = self.order_id(system_id, world);
+
+        let to_send = hyperion_proto::Multicast {
+            order,
+            streams,
+            data,
+        };
+
+        let to_send = ServerToProxyMessage::Multicast(to_send);
+
+        let len = buffer.len();
+        buffer.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut *buffer).unwrap();
+
+        let new_len = buffer.len();
+        let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
+        buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+
+        self.count_sent(world, |stats| stats.multicast.record(packet_len));
+
+        SendReport { bytes: packet_len }
     }
 
     pub(crate) fn set_receive_broadcasts(&self, stream: NetworkStreamRef, world: &World) {
@@ -597,4 +1173,482 @@ impl IoBuf {
         let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
     }
+
+    pub(crate) fn set_stream_buffer_limit(
+        &self,
+        stream: NetworkStreamRef,
+        max_bytes: u64,
+        world: &World,
+    ) {
+        let buffer = self.buffer.get(world);
+        let buffer = &mut *buffer.borrow_mut();
+
+        let to_send = hyperion_proto::SetStreamBufferLimit {
+            stream: stream.stream_id,
+            max_bytes,
+        };
+
+        let to_send = ServerToProxyMessage::SetStreamBufferLimit(to_send);
+
+        let len = buffer.len();
+        buffer.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut *buffer).unwrap();
+
+        let new_len = buffer.len();
+        let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
+        buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+impl Compose {
+    /// A [`Compose`] compressing packets of at least 64 bytes, for tests that look at what is
+    /// queued for the proxy.
+    pub(crate) fn for_tests() -> Self {
+        let shared = std::sync::Arc::new(crate::Shared {
+            compression_threshold: CompressionThreshold(64),
+            compression_level: CompressionLvl::default(),
+        });
+        let capacity = crate::capacity::PlayerCapacity::new(10, 0, &[]);
+
+        Self::new(
+            Compressors::new(CompressionLvl::default()),
+            Scratches::default(),
+            Global::new(shared, capacity),
+            IoBuf::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::World;
+    use hyperion_proto::{ArchivedServerToProxyMessage, ChunkPosition, ServerToProxyMessage};
+    use libdeflater::CompressionLvl;
+    use rkyv::util::AlignedVec;
+    use slotmap::KeyData;
+    use valence_protocol::{CompressionThreshold, packets::play};
+
+    use super::{
+        Compose, Compressors, IoBuf, NetworkMetrics, NetworkStreamRef, SendReport, Traffic,
+        effective_threshold, threshold_override,
+    };
+    use crate::system_registry::SystemId;
+
+    #[test]
+    fn snapshots_hold_the_traffic_since_the_last_one() {
+        let world = World::new();
+        let io_buf = IoBuf::default();
+        let mut metrics = NetworkMetrics::default();
+
+        io_buf.count_sent(&world, |stats| stats.unicast.record(10));
+        io_buf.count_sent(&world, |stats| stats.unicast.record(5));
+        io_buf.count_sent(&world, |stats| stats.broadcast.record(100));
+
+        let tick = metrics.take_snapshot(&io_buf);
+        assert_eq!(tick.unicast, Traffic {
+            frames: 2,
+            bytes: 15
+        });
+        assert_eq!(tick.total(), Traffic {
+            frames: 3,
+            bytes: 115
+        });
+        assert_eq!(metrics.last_tick(), tick);
+
+        io_buf.count_sent(&world, |stats| stats.broadcast_local.record(7));
+
+        let tick = metrics.take_snapshot(&io_buf);
+        assert_eq!(tick.total(), Traffic {
+            frames: 1,
+            bytes: 7
+        });
+        assert_eq!(tick.broadcast_local.bytes, 7);
+
+        // the totals since the server started are kept
+        assert_eq!(io_buf.stats().total(), Traffic {
+            frames: 4,
+            bytes: 122
+        });
+    }
+
+    fn compose() -> Compose {
+        Compose::for_tests()
+    }
+
+    #[test]
+    fn compressors_pick_up_a_new_level_on_their_next_use() {
+        let world = World::new();
+        let compressors = Compressors::new(CompressionLvl::best());
+
+        compressors.get(&world);
+        assert_eq!(compressors.thread_level(&world), CompressionLvl::best());
+
+        // a compressor that is in use is not replaced under the borrow
+        let borrowed = compressors.get(&world).borrow_mut();
+        compressors.set_level(CompressionLvl::fastest());
+        compressors.get(&world);
+        assert_eq!(compressors.thread_level(&world), CompressionLvl::best());
+        drop(borrowed);
+
+        compressors.get(&world);
+        assert_eq!(compressors.thread_level(&world), CompressionLvl::fastest());
+    }
+
+    /// Appends `message` to `buffer` the way the proxy reads it, returning its length.
+    fn append_frame(buffer: &mut AlignedVec, message: &ServerToProxyMessage<'_>) -> u64 {
+        let start = buffer.len();
+        buffer.extend_from_slice(&[0; 8]);
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(message, &mut *buffer).unwrap();
+
+        let len = u64::try_from(buffer.len() - start - 8).unwrap();
+        buffer[start..start + 8].copy_from_slice(&len.to_be_bytes());
+
+        len
+    }
+
+    /// Everything queued on `io_buf`, which must all be on one thread.
+    fn sent(io_buf: &mut IoBuf) -> Vec<u8> {
+        let sent: Vec<_> = io_buf.reset_and_split().collect();
+        assert_eq!(sent.len(), 1);
+
+        sent[0].to_vec()
+    }
+
+    #[test]
+    fn multicasts_are_framed_like_the_proxy_expects() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+
+        io_buf.multicast_raw(b"packet", &[3, 1, 4], SystemId(7), &world);
+
+        let mut expected = AlignedVec::new();
+        let message = ServerToProxyMessage::Multicast(hyperion_proto::Multicast {
+            order: 7 << 16,
+            streams: &[3, 1, 4],
+            data: b"packet",
+        });
+        let len = append_frame(&mut expected, &message);
+
+        assert_eq!(sent(&mut io_buf), expected.as_slice());
+        assert_eq!(io_buf.stats().multicast, Traffic {
+            frames: 1,
+            bytes: len
+        });
+    }
+
+    #[test]
+    fn stream_buffer_limits_are_framed_like_the_proxy_expects() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+
+        io_buf.set_stream_buffer_limit(NetworkStreamRef::new(9), 1 << 20, &world);
+
+        let mut expected = AlignedVec::new();
+        let limit = hyperion_proto::SetStreamBufferLimit {
+            stream: 9,
+            max_bytes: 1 << 20,
+        };
+        append_frame(
+            &mut expected,
+            &ServerToProxyMessage::SetStreamBufferLimit(limit),
+        );
+
+        let sent = sent(&mut io_buf);
+        assert_eq!(sent, expected.as_slice());
+
+        let mut frame = AlignedVec::<16>::new();
+        frame.extend_from_slice(&sent[size_of::<u64>()..]);
+
+        // SAFETY: the frame was just serialized by `IoBuf`
+        let message = unsafe { rkyv::access_unchecked::<ArchivedServerToProxyMessage<'_>>(&frame) };
+        let ArchivedServerToProxyMessage::SetStreamBufferLimit(limit) = message else {
+            panic!("expected a buffer limit");
+        };
+        assert_eq!(limit.stream.to_native(), 9);
+        assert_eq!(limit.max_bytes.to_native(), 1 << 20);
+    }
+
+    #[test]
+    fn excluded_players_are_framed_like_the_proxy_expects() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+        let center = ChunkPosition::new(1, 2);
+
+        io_buf.broadcast_raw(b"global", &[5, 6, 7], SystemId(3), &world);
+        io_buf.broadcast_local_raw(b"local", center, &[5], SystemId(3), &world);
+        io_buf.broadcast_local_raw(b"nobody", center, &[], SystemId(3), &world);
+
+        let mut expected = AlignedVec::new();
+
+        let global = hyperion_proto::BroadcastGlobal {
+            exclude: 5,
+            order: 3 << 16,
+            exclude_many: &[6, 7],
+            data: b"global",
+        };
+        append_frame(
+            &mut expected,
+            &ServerToProxyMessage::BroadcastGlobal(global),
+        );
+
+        let local = hyperion_proto::BroadcastLocal {
+            center,
+            exclude: 5,
+            order: 3 << 16 | 1,
+            exclude_many: &[],
+            data: b"local",
+        };
+        append_frame(&mut expected, &ServerToProxyMessage::BroadcastLocal(local));
+
+        let nobody = hyperion_proto::BroadcastLocal {
+            center,
+            exclude: 0,
+            order: 3 << 16 | 2,
+            exclude_many: &[],
+            data: b"nobody",
+        };
+        append_frame(&mut expected, &ServerToProxyMessage::BroadcastLocal(nobody));
+
+        assert_eq!(sent(&mut io_buf), expected.as_slice());
+    }
+
+    #[test]
+    fn reports_hold_the_length_of_the_queued_frames() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+        let stream = NetworkStreamRef::new(1);
+        let center = ChunkPosition::new(0, 0);
+
+        let reports = [
+            io_buf.unicast_raw(b"unicast", stream, SystemId(2), &world),
+            io_buf.broadcast_raw(b"global", &[4], SystemId(2), &world),
+            io_buf.broadcast_local_raw(b"local", center, &[], SystemId(2), &world),
+            io_buf.multicast_raw(b"multicast", &[1, 2], SystemId(2), &world),
+        ];
+
+        let mut expected = AlignedVec::new();
+        let messages = [
+            ServerToProxyMessage::Unicast(hyperion_proto::Unicast {
+                stream: 1,
+                order: 2 << 16,
+                data: b"unicast",
+            }),
+            ServerToProxyMessage::BroadcastGlobal(hyperion_proto::BroadcastGlobal {
+                exclude: 4,
+                order: 2 << 16 | 1,
+                exclude_many: &[],
+                data: b"global",
+            }),
+            ServerToProxyMessage::BroadcastLocal(hyperion_proto::BroadcastLocal {
+                center,
+                exclude: 0,
+                order: 2 << 16 | 2,
+                exclude_many: &[],
+                data: b"local",
+            }),
+            ServerToProxyMessage::Multicast(hyperion_proto::Multicast {
+                order: 2 << 16 | 3,
+                streams: &[1, 2],
+                data: b"multicast",
+            }),
+        ];
+
+        for (report, message) in reports.into_iter().zip(&messages) {
+            assert_eq!(report, SendReport {
+                bytes: append_frame(&mut expected, message)
+            });
+        }
+
+        assert_eq!(sent(&mut io_buf), expected.as_slice());
+    }
+
+    #[test]
+    fn pending_bytes_count_what_has_not_been_split_off() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+        let stream = NetworkStreamRef::new(1);
+
+        assert_eq!(io_buf.pending_bytes(), 0);
+
+        let unicast = io_buf.unicast_raw(b"unicast", stream, SystemId(2), &world);
+        let multicast = io_buf.multicast_raw(b"multicast", &[1, 2], SystemId(2), &world);
+
+        // every frame also holds its length
+        let queued = usize::try_from(unicast.bytes + multicast.bytes + 16).unwrap();
+        assert_eq!(io_buf.pending_bytes(), queued);
+
+        let sent: usize = io_buf.reset_and_split().map(|bytes| bytes.len()).sum();
+        assert_eq!(sent, queued);
+        assert_eq!(io_buf.pending_bytes(), 0);
+
+        io_buf.unicast_raw(b"unicast", stream, SystemId(2), &world);
+        io_buf.clear();
+
+        assert_eq!(io_buf.pending_bytes(), 0);
+        assert_eq!(io_buf.order_id(SystemId(2), &world), 2 << 16);
+    }
+
+    #[test]
+    fn precomputed_packets_are_sent_as_they_are() {
+        let world = World::new();
+        let mut compose = compose();
+
+        let bytes = compose
+            .encode(&play::KeepAliveS2c { id: 7 }, &world)
+            .unwrap();
+
+        let first = compose.broadcast_precomputed(&bytes, SystemId(4), &world);
+        let second = compose.broadcast_precomputed(&bytes, SystemId(4), &world);
+        assert_eq!(first, second);
+
+        let mut expected = AlignedVec::new();
+
+        for order in [4 << 16, 4 << 16 | 1] {
+            let broadcast = hyperion_proto::BroadcastGlobal {
+                exclude: 0,
+                order,
+                exclude_many: &[],
+                data: &bytes,
+            };
+            append_frame(
+                &mut expected,
+                &ServerToProxyMessage::BroadcastGlobal(broadcast),
+            );
+        }
+
+        assert_eq!(sent(compose.io_buf_mut()), expected.as_slice());
+    }
+
+    #[test]
+    fn only_the_latest_broadcast_with_a_key_is_queued() {
+        let world = World::new();
+        let mut compose = compose();
+
+        let first = play::KeepAliveS2c { id: 1 };
+        let second = play::KeepAliveS2c { id: 2 };
+
+        compose
+            .broadcast_latest(&first, 9, SystemId(4))
+            .send(&world)
+            .unwrap();
+
+        let report = compose
+            .broadcast_latest(&second, 9, SystemId(4))
+            .exclude(NetworkStreamRef::new(3))
+            .send(&world)
+            .unwrap();
+
+        let data = compose.encode(&second, &world).unwrap();
+
+        let mut expected = AlignedVec::new();
+        let broadcast = hyperion_proto::BroadcastGlobal {
+            exclude: 3,
+            order: 4 << 16 | 1,
+            exclude_many: &[],
+            data: &data,
+        };
+        let bytes = append_frame(
+            &mut expected,
+            &ServerToProxyMessage::BroadcastGlobal(broadcast),
+        );
+
+        assert_eq!(report, SendReport { bytes });
+        assert_eq!(compose.io_buf().pending_bytes(), expected.len());
+        assert_eq!(sent(compose.io_buf_mut()), expected.as_slice());
+
+        // the kept frame is not sent again the next tick
+        assert_eq!(compose.io_buf().pending_bytes(), 0);
+    }
+
+    /// The order of each message queued on `io_buf`, in the order they are sent to the proxy.
+    fn orders(io_buf: &mut IoBuf) -> Vec<u32> {
+        let mut orders = Vec::new();
+
+        for bytes in io_buf.reset_and_split() {
+            let mut rest = &bytes[..];
+
+            while !rest.is_empty() {
+                let (len, after) = rest.split_at(size_of::<u64>());
+                let len = u64::from_be_bytes(len.try_into().unwrap());
+                let (frame, after) = after.split_at(usize::try_from(len).unwrap());
+                rest = after;
+
+                let mut aligned = AlignedVec::<16>::new();
+                aligned.extend_from_slice(frame);
+
+                // SAFETY: the frame was just serialized by `IoBuf`
+                let message =
+                    unsafe { rkyv::access_unchecked::<ArchivedServerToProxyMessage<'_>>(&aligned) };
+
+                let order = match message {
+                    ArchivedServerToProxyMessage::BroadcastGlobal(message) => message.order,
+                    ArchivedServerToProxyMessage::BroadcastLocal(message) => message.order,
+                    ArchivedServerToProxyMessage::Unicast(message) => message.order,
+                    ArchivedServerToProxyMessage::Multicast(message) => message.order,
+                    _ => continue,
+                };
+
+                orders.push(order.to_native());
+            }
+        }
+
+        orders
+    }
+
+    #[test]
+    fn orders_increase_across_kinds_of_messages() {
+        let world = World::new();
+        let mut io_buf = IoBuf::default();
+        let stream = NetworkStreamRef::new(1);
+        let center = ChunkPosition { x: 0, z: 0 };
+
+        io_buf.unicast_raw(b"a", stream, SystemId(3), &world);
+        io_buf.broadcast_raw(b"b", &[], SystemId(3), &world);
+        io_buf.unicast_raw(b"c", stream, SystemId(3), &world);
+        io_buf.broadcast_local_raw(b"d", center, &[], SystemId(3), &world);
+        io_buf.multicast_raw(b"e", &[1, 2], SystemId(3), &world);
+        io_buf.broadcast_raw(b"f", &[], SystemId(5), &world);
+        io_buf.unicast_raw(b"g", stream, SystemId(5), &world);
+
+        let orders = orders(&mut io_buf);
+
+        assert_eq!(orders.len(), 7);
+        assert!(
+            orders.is_sorted_by(|a, b| a < b),
+            "orders are not strictly increasing: {orders:?}"
+        );
+
+        // the system comes first, so the proxy can tell which broadcasts it may merge
+        assert_eq!(orders[0] >> 16, 3);
+        assert_eq!(orders[6] >> 16, 5);
+    }
+
+    #[test]
+    fn thresholds_are_only_overridden_with_compression_enabled() {
+        let global = CompressionThreshold(256);
+        let force = Some(threshold_override(Some(0)));
+        let skip = Some(threshold_override(None));
+
+        assert_eq!(effective_threshold(global, None), global);
+        assert_eq!(effective_threshold(global, force), CompressionThreshold(0));
+        assert_eq!(
+            effective_threshold(global, skip),
+            CompressionThreshold(i32::MAX)
+        );
+
+        let disabled = CompressionThreshold(-1);
+        assert_eq!(effective_threshold(disabled, force), disabled);
+        assert_eq!(effective_threshold(disabled, skip), disabled);
+    }
+
+    #[test]
+    fn stream_ids_round_trip_through_slot_map_keys() {
+        let key = KeyData::from_ffi((3 << 32) | 7);
+        let stream = NetworkStreamRef::new(key.as_ffi());
+
+        assert_eq!(KeyData::from_ffi(stream.id()), key);
+        assert_eq!(stream.to_string(), "7v3");
+    }
 }
@@ -4,7 +4,7 @@ use std::{collections::HashMap, io::Cursor, net::SocketAddr, process::Command, s
 
 use bytes::{Buf, BytesMut};
 use flecs_ecs::macros::Component;
-use hyperion_proto::ArchivedProxyToServerMessage;
+use hyperion_proto::{ArchivedProxyToServerMessage, StreamStats};
 use parking_lot::Mutex;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{error, info, warn};
@@ -20,6 +20,36 @@ pub struct ReceiveStateInner {
     pub player_disconnect: Vec<u64>,
     /// A map of stream ids to the corresponding [`BytesMut`] buffers. This represents data from the client to the server.
     pub packets: HashMap<u64, BytesMut>,
+    /// How far behind players are, as the proxy last reported.
+    pub stream_stats: Vec<StreamStats>,
+}
+
+impl ReceiveStateInner {
+    fn handle_message(&mut self, message: &ArchivedProxyToServerMessage<'_>) {
+        match message {
+            ArchivedProxyToServerMessage::PlayerConnect(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+
+                self.player_connect.push(stream);
+            }
+            ArchivedProxyToServerMessage::PlayerDisconnect(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+                self.player_disconnect.push(stream);
+            }
+            ArchivedProxyToServerMessage::PlayerPackets(message) => {
+                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+
+                self.packets
+                    .entry(stream)
+                    .or_default()
+                    .extend_from_slice(&message.data);
+            }
+            ArchivedProxyToServerMessage::StreamStats(message) => {
+                let Ok(stats) = rkyv::deserialize::<StreamStats, !>(message);
+                self.stream_stats.push(stats);
+            }
+        }
+    }
 }
 
 fn get_pid_from_port(port: u16) -> Result<Option<u32>, std::io::Error> {
@@ -116,27 +146,7 @@ async fn inner(
                             rkyv::access_unchecked::<ArchivedProxyToServerMessage<'_>>(&buffer)
                         };
 
-                        match result {
-                            ArchivedProxyToServerMessage::PlayerConnect(message) => {
-                                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
-
-                                shared.lock().player_connect.push(stream);
-                            }
-                            ArchivedProxyToServerMessage::PlayerDisconnect(message) => {
-                                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
-                                shared.lock().player_disconnect.push(stream);
-                            }
-                            ArchivedProxyToServerMessage::PlayerPackets(message) => {
-                                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
-
-                                shared
-                                    .lock()
-                                    .packets
-                                    .entry(stream)
-                                    .or_default()
-                                    .extend_from_slice(&message.data);
-                            }
-                        }
+                        shared.lock().handle_message(result);
                     }
                 });
 
@@ -211,3 +221,29 @@ impl ProxyReader {
         Ok(buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyperion_proto::{ArchivedProxyToServerMessage, ProxyToServerMessage, StreamStats};
+
+    use super::ReceiveStateInner;
+
+    #[test]
+    fn stream_stats_are_read_like_the_proxy_sends_them() {
+        let stats = StreamStats {
+            stream: 3,
+            dropped_bytes: 1024,
+            queued_bytes: 65_536,
+        };
+
+        let message = ProxyToServerMessage::StreamStats(stats);
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&message).unwrap();
+        let archived =
+            unsafe { rkyv::access_unchecked::<ArchivedProxyToServerMessage<'_>>(&bytes) };
+
+        let mut state = ReceiveStateInner::default();
+        state.handle_message(archived);
+
+        assert_eq!(state.stream_stats, [stats]);
+    }
+}
@@ -0,0 +1,396 @@
+//! The anvil window, which renames, repairs and combines items with [`anvil_result`].
+//!
+//! The two input slots belong to the window rather than the block, so whatever is left in them
+//! goes back to the player when the window closes.
+
+use std::borrow::Cow;
+
+use flecs_ecs::prelude::*;
+use hyperion_crafting::{anvil_result, repair_units};
+use hyperion_inventory::PlayerInventory;
+use tracing::warn;
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::{
+        play,
+        play::{click_slot_c2s::ClickMode, open_screen_s2c::WindowType},
+    },
+    text::IntoText,
+};
+
+use crate::{
+    ingress::PendingRemove,
+    net::DataBundle,
+    simulation::{
+        Position, Xp, event,
+        handlers::PacketSwitchQuery,
+        menu::{self, OpenMenu},
+    },
+    storage::Events,
+    system_registry::ANVIL,
+};
+
+/// The two inputs and the output.
+const ANVIL_SLOTS: u16 = 3;
+const OUTPUT_SLOT: i16 = 2;
+/// The window property holding the level cost.
+const COST_PROPERTY: i16 = 0;
+/// Vanilla clients cannot type longer names.
+const MAX_NAME_LENGTH: usize = 50;
+
+/// The anvil window a player has open.
+#[derive(Component, Clone, Debug)]
+pub struct OpenAnvil {
+    pub window_id: u8,
+    pub left: ItemStack,
+    pub right: ItemStack,
+    /// What the player typed into the name field, if they touched it since the left item changed.
+    pub name: Option<String>,
+}
+
+impl OpenAnvil {
+    fn new() -> Self {
+        Self {
+            window_id: menu::next_window_id(),
+            left: ItemStack::EMPTY,
+            right: ItemStack::EMPTY,
+            name: None,
+        }
+    }
+
+    /// Gives both inputs back to the player. Returns whatever does not fit.
+    fn close(self, inventory: &mut PlayerInventory) -> Vec<ItemStack> {
+        let inputs = [self.left, self.right]
+            .into_iter()
+            .filter(|stack| !stack.is_empty());
+
+        inventory.add_or_overflow(inputs)
+    }
+
+    /// The item in the output slot and how many levels taking it costs.
+    #[must_use]
+    pub fn output(&self) -> Option<(ItemStack, u32)> {
+        anvil_result(&self.left, &self.right, self.name.as_deref())
+    }
+
+    /// Applies a click on window slot `slot_idx`. A stack clicked in the player's inventory goes
+    /// into the first empty input, and a clicked input goes back into the inventory.
+    ///
+    /// Taking the output spends its cost from `xp`, and is refused if there are not enough levels
+    /// or the output does not fit in the inventory. Returns whether the output was taken.
+    fn click(&mut self, slot_idx: i16, inventory: &mut PlayerInventory, xp: &mut Xp) -> bool {
+        if let Some(slot) = menu::player_slot(slot_idx, ANVIL_SLOTS) {
+            let input = if self.left.is_empty() {
+                self.name = None;
+                &mut self.left
+            } else if self.right.is_empty() {
+                &mut self.right
+            } else {
+                return false;
+            };
+
+            *input = std::mem::replace(&mut *inventory.get_mut(slot).unwrap(), ItemStack::EMPTY);
+            return false;
+        }
+
+        match slot_idx {
+            0 | 1 => {
+                let input = if slot_idx == 0 {
+                    self.name = None;
+                    &mut self.left
+                } else {
+                    &mut self.right
+                };
+
+                if !input.is_empty() {
+                    let rest = inventory.try_add_item(input.clone()).remaining;
+                    *input = rest.unwrap_or(ItemStack::EMPTY);
+                }
+
+                false
+            }
+            OUTPUT_SLOT => self.take_output(inventory, xp),
+            _ => false,
+        }
+    }
+
+    fn take_output(&mut self, inventory: &mut PlayerInventory, xp: &mut Xp) -> bool {
+        let Some((result, cost)) = self.output() else {
+            return false;
+        };
+
+        if inventory.space_for(&result) < result.count {
+            return false;
+        }
+
+        let Ok(levels) = u8::try_from(cost) else {
+            return false;
+        };
+
+        if !xp.try_spend_levels(levels) {
+            return false;
+        }
+
+        // a repair with raw materials only uses up as many as it needed
+        let units = repair_units(&self.left, &self.right);
+
+        self.right = if units > 0 && self.right.count > units {
+            self.right.clone().with_count(self.right.count - units)
+        } else {
+            ItemStack::EMPTY
+        };
+
+        self.left = ItemStack::EMPTY;
+        self.name = None;
+
+        let rest = inventory.try_add_item(result).remaining;
+        debug_assert!(rest.is_none(), "the output was checked to fit");
+
+        true
+    }
+
+    /// Adds the window's slots and cost to `bundle`, undoing anything the client predicted.
+    fn add_contents(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        query: &PacketSwitchQuery<'_>,
+    ) -> anyhow::Result<()> {
+        let (output, cost) = self.output().unwrap_or((ItemStack::EMPTY, 0));
+        let slots = [self.left.clone(), self.right.clone(), output];
+
+        bundle.add_packet(
+            &menu::window_contents(self.window_id, &slots, query.inventory),
+            query.world,
+        )?;
+
+        bundle.add_packet(&self.cost_packet(cost), query.world)
+    }
+
+    fn cost_packet(&self, cost: u32) -> play::ScreenHandlerPropertyUpdateS2c {
+        play::ScreenHandlerPropertyUpdateS2c {
+            window_id: self.window_id,
+            property: COST_PROPERTY,
+            value: i16::try_from(cost).unwrap_or(i16::MAX),
+        }
+    }
+}
+
+/// Opens an anvil window for the player who right-clicked an anvil.
+pub fn open_anvil(query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let open = OpenAnvil::new();
+
+    let mut bundle = DataBundle::new(query.compose);
+
+    bundle.add_packet(
+        &play::OpenScreenS2c {
+            window_id: VarInt(i32::from(open.window_id)),
+            window_type: WindowType::Anvil,
+            window_title: "Repair & Name".into_cow_text(),
+        },
+        query.world,
+    )?;
+
+    open.add_contents(&mut bundle, query)?;
+    bundle.send(query.world, query.io_ref, ANVIL)?;
+
+    // the anvil window replaces any menu on the client
+    query.view.remove::<OpenMenu>();
+    query.view.set(open);
+
+    Ok(())
+}
+
+/// Handles a click in an open anvil window.
+pub fn click_anvil(
+    query: &mut PacketSwitchQuery<'_>,
+    pkt: &play::ClickSlotC2s,
+    open: &OpenAnvil,
+) -> anyhow::Result<()> {
+    let mut open = open.clone();
+
+    if matches!(pkt.mode, ClickMode::Click | ClickMode::ShiftClick) {
+        let inventory = &mut *query.inventory;
+
+        let clicked = query
+            .view
+            .try_get::<&mut Xp>(|xp| open.click(pkt.slot_idx, inventory, xp));
+
+        if clicked.is_none() {
+            // without any experience only free clicks work
+            open.click(pkt.slot_idx, inventory, &mut Xp::default());
+        }
+    }
+
+    let mut bundle = DataBundle::new(query.compose);
+    open.add_contents(&mut bundle, query)?;
+    bundle.add_packet(&menu::reset_cursor(), query.world)?;
+    bundle.send(query.world, query.io_ref, ANVIL)?;
+
+    query.view.set(open);
+
+    Ok(())
+}
+
+/// Updates the output of an open anvil window as the player types a new name.
+pub fn rename_item(query: &PacketSwitchQuery<'_>, pkt: &play::RenameItemC2s<'_>) {
+    let name: String = pkt.item_name.chars().take(MAX_NAME_LENGTH).collect();
+
+    let open = query.view.try_get::<&mut OpenAnvil>(|open| {
+        open.name = Some(name);
+        open.clone()
+    });
+
+    let Some(open) = open else {
+        return;
+    };
+
+    let (output, cost) = open.output().unwrap_or((ItemStack::EMPTY, 0));
+
+    let set_output = play::ScreenHandlerSlotUpdateS2c {
+        window_id: i8::try_from(open.window_id).unwrap(),
+        state_id: VarInt::default(),
+        slot_idx: OUTPUT_SLOT,
+        slot_data: Cow::Owned(output),
+    };
+
+    let mut bundle = DataBundle::new(query.compose);
+
+    let sent = bundle
+        .add_packet(&set_output, query.world)
+        .and_then(|()| bundle.add_packet(&open.cost_packet(cost), query.world))
+        .and_then(|()| bundle.send(query.world, query.io_ref, ANVIL));
+
+    if let Err(e) = sent {
+        warn!("failed to update anvil output: {e}");
+    }
+}
+
+/// Closes the anvil window, giving its inputs back to the player. Whatever does not fit is
+/// dropped at their feet.
+pub fn close_anvil(query: &mut PacketSwitchQuery<'_>) {
+    let Some(open) = query.view.try_get::<&OpenAnvil>(Clone::clone) else {
+        return;
+    };
+
+    query.view.remove::<OpenAnvil>();
+
+    let dropped = open.close(query.inventory);
+
+    for item in dropped {
+        let location = **query.position;
+        query
+            .events
+            .push(event::ItemDropEvent::new(item, location), query.world);
+    }
+}
+
+/// Gives the inputs of an open anvil back to players who leave, before they are saved.
+pub(crate) fn close_on_leave(world: &World) {
+    system!(
+        "close_anvils_of_leaving_players",
+        world,
+        &Events($),
+        &Position,
+        &mut PlayerInventory,
+        &OpenAnvil,
+    )
+    .kind::<flecs::pipeline::PostLoad>()
+    .with::<&PendingRemove>()
+    .each_entity(|player, (events, position, inventory, open)| {
+        let world = player.world();
+
+        for item in open.clone().close(inventory) {
+            let location = **position;
+            events.push(event::ItemDropEvent::new(item, location), &world);
+        }
+
+        player.remove::<OpenAnvil>();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_crafting::custom_name;
+    use hyperion_inventory::PlayerInventory;
+    use valence_protocol::{ItemKind, ItemStack, nbt::Compound};
+
+    use super::OpenAnvil;
+    use crate::simulation::Xp;
+
+    fn anvil() -> OpenAnvil {
+        OpenAnvil {
+            window_id: 1,
+            left: ItemStack::EMPTY,
+            right: ItemStack::EMPTY,
+            name: None,
+        }
+    }
+
+    #[test]
+    fn renaming_needs_a_level() {
+        let mut inventory = PlayerInventory::default();
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        inventory.set(9, sword).unwrap();
+
+        let mut anvil = anvil();
+        let mut xp = Xp::default();
+
+        // window slot 3 is the first slot of the main inventory
+        assert!(!anvil.click(3, &mut inventory, &mut xp));
+        assert_eq!(anvil.left.item, ItemKind::IronSword);
+
+        anvil.name = Some("Excalibur".to_owned());
+
+        assert!(!anvil.click(2, &mut inventory, &mut xp));
+        assert_eq!(anvil.left.item, ItemKind::IronSword);
+
+        xp.amount = Xp::level_bounds(3).0;
+
+        assert!(anvil.click(2, &mut inventory, &mut xp));
+        assert!(anvil.left.is_empty());
+        assert_eq!(xp.get_visual().level, 2);
+
+        let renamed = inventory.get(36).unwrap();
+        assert_eq!(custom_name(renamed).as_deref(), Some("Excalibur"));
+    }
+
+    #[test]
+    fn repairing_keeps_unused_materials() {
+        let mut inventory = PlayerInventory::default();
+
+        let mut nbt = Compound::new();
+        nbt.insert("Damage", 1000);
+
+        let mut anvil = OpenAnvil {
+            left: ItemStack::new(ItemKind::DiamondSword, 1, Some(nbt)),
+            right: ItemStack::new(ItemKind::Diamond, 5, None),
+            ..anvil()
+        };
+
+        let mut xp = Xp {
+            amount: Xp::level_bounds(10).0,
+        };
+
+        assert!(anvil.click(2, &mut inventory, &mut xp));
+        assert_eq!(anvil.right, ItemStack::new(ItemKind::Diamond, 2, None));
+        assert_eq!(xp.get_visual().level, 7);
+    }
+
+    #[test]
+    fn closing_returns_both_inputs() {
+        let mut inventory = PlayerInventory::default();
+
+        let anvil = OpenAnvil {
+            left: ItemStack::new(ItemKind::IronSword, 1, None),
+            right: ItemStack::new(ItemKind::IronIngot, 3, None),
+            ..anvil()
+        };
+
+        assert!(anvil.close(&mut inventory).is_empty());
+        assert_eq!(inventory.get(36).unwrap().item, ItemKind::IronSword);
+        assert_eq!(
+            *inventory.get(37).unwrap(),
+            ItemStack::new(ItemKind::IronIngot, 3, None)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{collections::BTreeSet, fmt::Debug};
 
 use bytes::Bytes;
 use glam::{IVec2, IVec3};
@@ -67,6 +67,18 @@ pub struct Column {
     pub data: ColumnData,
 
     pub position: IVec2,
+
+    /// The blocks that changed in a way that affects light since the light was last updated.
+    pub light_queue: Vec<IVec3>,
+
+    /// Whether light was sent since the column was loaded, so it may differ from
+    /// [`Column::base_packet_bytes`].
+    pub light_edited: bool,
+
+    /// The keys in [`ColumnData::block_entities`] of the block entities set since the column was
+    /// loaded.
+    pub block_entities_changed: BTreeSet<u32>,
+    pub block_entities_changed_since_last_tick: BTreeSet<u32>,
 }
 
 fn y_index(y: i16) -> u16 {
@@ -79,6 +91,10 @@ impl Column {
             base_packet_bytes,
             data,
             position,
+            light_queue: Vec::new(),
+            light_edited: false,
+            block_entities_changed: BTreeSet::new(),
+            block_entities_changed_since_last_tick: BTreeSet::new(),
         }
     }
 
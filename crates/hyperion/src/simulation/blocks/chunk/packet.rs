@@ -1,19 +1,113 @@
-use std::io::Write;
+use std::{borrow::Cow, io::Write};
 
 use glam::IVec2;
 use valence_protocol::{
-    ChunkSectionPos, Encode, Packet, VarInt,
-    packets::play::{ChunkDeltaUpdateS2c, chunk_delta_update_s2c::ChunkDeltaUpdateEntry},
+    BlockPos, ChunkSectionPos, Encode, FixedArray, Packet, VarInt, VarLong,
+    packets::play::{self, ChunkDeltaUpdateS2c},
 };
 
 use crate::{
     PacketBundle,
     simulation::blocks::{
         chunk::{Column, START_Y},
-        loader::parse::section::Section,
+        loader::{parse::section::Section, with_chunk_data_packet},
     },
 };
 
+/// A section with more blocks changed in a tick than this is resent whole instead of as a delta,
+/// which would be about as large as the section itself.
+pub const FULL_RESEND_CHANGES: u64 = 4096 / 2;
+
+/// Packs the block at `idx` of a section, which is `y << 8 | z << 4 | x`, and its raw `state` into
+/// an entry of [`ChunkDeltaUpdateS2c`]: the state, then x, z and y within the section in four bits
+/// each.
+fn pack_delta(idx: u16, state: u16) -> i64 {
+    let x = idx & 0xF;
+    let z = (idx >> 4) & 0xF;
+    let y = (idx >> 8) & 0xF;
+
+    (i64::from(state) << 12) | i64::from((x << 8) | (z << 4) | y)
+}
+
+fn encode_deltas(
+    position: ChunkSectionPos,
+    len: u64,
+    deltas: impl Iterator<Item = (u16, u16)>,
+    mut write: impl Write,
+) -> anyhow::Result<()> {
+    VarInt(ChunkDeltaUpdateS2c::ID).encode(&mut write)?;
+
+    position.encode(&mut write)?;
+    VarInt(i32::try_from(len)?).encode(&mut write)?;
+
+    for (idx, state) in deltas {
+        VarLong(pack_delta(idx, state)).encode(&mut write)?;
+    }
+
+    Ok(())
+}
+
+/// The light of `sections`, by index, of the column at `position`. `None` if there are none.
+fn light_packet<'a>(
+    position: IVec2,
+    sections: impl Iterator<Item = (usize, &'a Section)>,
+) -> Option<play::LightUpdateS2c<'static>> {
+    let mut sky_light_mask = 0_u64;
+    let mut block_light_mask = 0_u64;
+    let mut empty_block_light_mask = 0_u64;
+
+    let mut sky_light_arrays = Vec::new();
+    let mut block_light_arrays = Vec::new();
+
+    for (i, section) in sections {
+        // the first bit is for the section below the world
+        let bit = 1 << (i + 1);
+
+        sky_light_mask |= bit;
+        sky_light_arrays.push(FixedArray(section.sky_light.unwrap_or([0xff; 2048])));
+
+        match section.block_light {
+            Some(block_light) => {
+                block_light_mask |= bit;
+                block_light_arrays.push(FixedArray(block_light));
+            }
+            None => empty_block_light_mask |= bit,
+        }
+    }
+
+    if sky_light_mask == 0 {
+        return None;
+    }
+
+    Some(play::LightUpdateS2c {
+        chunk_x: VarInt(position.x),
+        chunk_z: VarInt(position.y),
+        sky_light_mask: Cow::Owned(vec![sky_light_mask]),
+        block_light_mask: Cow::Owned(vec![block_light_mask]),
+        empty_sky_light_mask: Cow::Borrowed(&[]),
+        empty_block_light_mask: Cow::Owned(vec![empty_block_light_mask]),
+        sky_light_arrays: Cow::Owned(sky_light_arrays),
+        block_light_arrays: Cow::Owned(block_light_arrays),
+    })
+}
+
+/// The block entity stored under `idx` in `column`, for players who already have the column.
+fn block_entity_packet(column: &Column, idx: u32) -> Option<play::BlockEntityUpdateS2c<'_>> {
+    let (offset, kind, data) = column.data.block_entity_at(idx)?;
+
+    let offset = offset.as_ivec3();
+    let IVec2 { x, y: z } = column.position;
+    let y = offset.y + i32::from(START_Y);
+
+    Some(play::BlockEntityUpdateS2c {
+        position: BlockPos::new(x * 16 + offset.x, y, z * 16 + offset.z),
+        kind: VarInt(i32::try_from(kind.id()).ok()?),
+        data: Cow::Borrowed(data),
+    })
+}
+
+/// The blocks of a section that changed since the last tick. A single change is sent as a
+/// [`play::BlockUpdateS2c`]. Encoding it resets the changes.
 #[derive(derive_more::Debug)]
 pub struct DeltaDrainPacket<'a> {
     position: ChunkSectionPos,
@@ -23,34 +117,29 @@ pub struct DeltaDrainPacket<'a> {
 
 impl PacketBundle for DeltaDrainPacket<'_> {
     fn encode_including_ids(self, mut write: impl Write) -> anyhow::Result<()> {
-        VarInt(ChunkDeltaUpdateS2c::ID).encode(&mut write)?;
-
-        self.position.encode(&mut write)?;
-
-        let deltas = &mut self.section.changed_since_last_tick;
-        let len = deltas.len();
-        VarInt(i32::try_from(len)?).encode(&mut write)?;
+        let len = self.section.changed_since_last_tick.len();
 
-        for delta_idx in deltas.iter() {
-            let block_state =
-                unsafe { self.section.block_states.get_unchecked(delta_idx as usize) };
+        if len == 1
+            && let Some((idx, block_id)) = self.section.iter_changed_since_last_tick().next()
+        {
+            let offset = Section::idx_to_xyz(usize::from(idx));
+            let ChunkSectionPos { x, y, z } = self.position;
 
-            // Convert delta (u16) to y, z, x
-            let y = (delta_idx >> 8) & 0xF;
-            let z = (delta_idx >> 4) & 0xF;
-            let x = delta_idx & 0xF;
+            let pkt = play::BlockUpdateS2c {
+                position: BlockPos::new(x * 16 + offset.x, y * 16 + offset.y, z * 16 + offset.z),
+                block_id,
+            };
 
-            let entry = ChunkDeltaUpdateEntry::new()
-                .with_off_x(x as u8)
-                .with_off_y(y as u8)
-                .with_off_z(z as u8)
-                .with_block_state(u32::from(block_state));
+            pkt.encode_with_id(&mut write)?;
+        } else {
+            let deltas = self
+                .section
+                .iter_changed_since_last_tick()
+                .map(|(idx, state)| (idx, state.to_raw()));
 
-            entry.encode(&mut write)?;
+            encode_deltas(self.position, len, deltas, &mut write)?;
         }
 
-        deltas.clear();
-
         self.section.reset_tick_deltas();
 
         Ok(())
@@ -65,31 +154,43 @@ pub struct DeltaPacket<'a> {
 }
 
 impl PacketBundle for DeltaPacket<'_> {
-    fn encode_including_ids(self, mut write: impl Write) -> anyhow::Result<()> {
-        VarInt(ChunkDeltaUpdateS2c::ID).encode(&mut write)?;
+    fn encode_including_ids(self, write: impl Write) -> anyhow::Result<()> {
+        let deltas = self
+            .section
+            .iter_changed()
+            .map(|(idx, state)| (idx, state.to_raw()));
 
-        self.position.encode(&mut write)?;
+        encode_deltas(self.position, self.section.changed.len(), deltas, write)
+    }
+}
+
+/// The whole column, sent instead of its deltas when a section changed too much in a tick.
+/// Encoding it resets the changes of every section.
+#[derive(derive_more::Debug)]
+pub struct ColumnDrainPacket<'a> {
+    #[debug(skip)]
+    column: &'a mut Column,
+}
 
-        let deltas = &self.section.changed;
-        let len = deltas.len();
-        VarInt(i32::try_from(len)?).encode(&mut write)?;
+impl PacketBundle for ColumnDrainPacket<'_> {
+    fn encode_including_ids(self, mut write: impl Write) -> anyhow::Result<()> {
+        let Column {
+            data,
+            position,
+            light_edited,
+            block_entities_changed_since_last_tick,
+            ..
+        } = self.column;
 
-        for delta_idx in deltas {
-            let block_state =
-                unsafe { self.section.block_states.get_unchecked(delta_idx as usize) };
+        with_chunk_data_packet(data, *position, |pkt| pkt.encode_with_id(&mut write))??;
 
-            // Convert delta (u16) to y, z, x
-            let y = (delta_idx >> 8) & 0xF;
-            let z = (delta_idx >> 4) & 0xF;
-            let x = delta_idx & 0xF;
+        block_entities_changed_since_last_tick.clear();
 
-            let entry = ChunkDeltaUpdateEntry::new()
-                .with_off_x(x as u8)
-                .with_off_y(y as u8)
-                .with_off_z(z as u8)
-                .with_block_state(u32::from(block_state));
+        for section in &mut data.sections {
+            section.reset_tick_deltas();
 
-            entry.encode(&mut write)?;
+            *light_edited |= section.light_changed;
+            section.light_changed = false;
         }
 
         Ok(())
@@ -97,6 +198,74 @@ impl PacketBundle for DeltaPacket<'_> {
 }
 
 impl Column {
+    /// Whether a section changed so much since the last tick that the column is resent whole
+    /// rather than as deltas. See [`FULL_RESEND_CHANGES`].
+    #[must_use]
+    pub fn needs_resend(&self) -> bool {
+        self.data
+            .sections
+            .iter()
+            .any(|section| section.changed_since_last_tick.len() > FULL_RESEND_CHANGES)
+    }
+
+    pub const fn resend_packet(&mut self) -> ColumnDrainPacket<'_> {
+        ColumnDrainPacket { column: self }
+    }
+
+    /// The block entities set since the last tick, which stay marked until
+    /// [`Column::reset_tick_block_entities`].
+    pub fn block_entity_packets(
+        &self,
+    ) -> impl Iterator<Item = play::BlockEntityUpdateS2c<'_>> + '_ {
+        self.block_entities_changed_since_last_tick
+            .iter()
+            .filter_map(|&idx| block_entity_packet(self, idx))
+    }
+
+    pub fn reset_tick_block_entities(&mut self) {
+        self.block_entities_changed_since_last_tick.clear();
+    }
+
+    /// The block entities set since the column was loaded, for players who were sent
+    /// [`Column::base_packet_bytes`].
+    pub fn original_block_entity_packets(
+        &self,
+    ) -> impl Iterator<Item = play::BlockEntityUpdateS2c<'_>> + '_ {
+        self.block_entities_changed
+            .iter()
+            .filter_map(|&idx| block_entity_packet(self, idx))
+    }
+
+    /// The light of the sections whose light changed since it was last sent, which is then marked
+    /// sent.
+    pub fn light_drain_packet(&mut self) -> Option<play::LightUpdateS2c<'static>> {
+        let changed = self
+            .data
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(_, section)| section.light_changed);
+
+        let packet = light_packet(self.position, changed)?;
+
+        for section in &mut self.data.sections {
+            section.light_changed = false;
+        }
+
+        self.light_edited = true;
+        Some(packet)
+    }
+
+    /// The light of every section if it was sent since the column was loaded, for players who
+    /// were sent [`Column::base_packet_bytes`].
+    pub fn original_light_packet(&self) -> Option<play::LightUpdateS2c<'static>> {
+        if !self.light_edited {
+            return None;
+        }
+
+        light_packet(self.position, self.data.sections.iter().enumerate())
+    }
+
     pub fn delta_drain_packets(&mut self) -> impl Iterator<Item = DeltaDrainPacket<'_>> + '_ {
         let IVec2 { x, y: z } = self.position;
 
@@ -135,3 +304,55 @@ impl Column {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ChunkSectionPos, Decode, VarInt, VarLong};
+
+    use super::{encode_deltas, pack_delta};
+    use crate::simulation::blocks::loader::parse::section::Section;
+
+    #[test]
+    fn deltas_pack_state_then_x_z_y() {
+        assert_eq!(pack_delta(0, 0), 0);
+        assert_eq!(pack_delta(0, 1), 1 << 12);
+
+        // idx is y << 8 | z << 4 | x, the entry is state << 12 | x << 8 | z << 4 | y
+        assert_eq!(pack_delta(0x001, 0), 0x100);
+        assert_eq!(pack_delta(0x010, 0), 0x010);
+        assert_eq!(pack_delta(0x100, 0), 0x001);
+        assert_eq!(pack_delta(0x123, 0), 0x321);
+        assert_eq!(pack_delta(0xFFF, 0), 0xFFF);
+
+        let max = pack_delta(0xFFF, u16::MAX);
+        assert_eq!(max >> 12, i64::from(u16::MAX));
+        assert_eq!(max & 0xFFF, 0xFFF);
+
+        for idx in 0..4096_u16 {
+            let entry = pack_delta(idx, 9);
+            let offset = Section::idx_to_xyz(usize::from(idx));
+
+            assert_eq!(entry >> 12, 9);
+            assert_eq!((entry >> 8) & 0xF, i64::from(offset.x));
+            assert_eq!((entry >> 4) & 0xF, i64::from(offset.z));
+            assert_eq!(entry & 0xF, i64::from(offset.y));
+        }
+    }
+
+    #[test]
+    fn deltas_follow_the_section_position() {
+        let position = ChunkSectionPos::new(-3, 4, 7);
+        let deltas = [(0x000, 1), (0xFFF, 2)];
+
+        let mut bytes = Vec::new();
+        encode_deltas(position, 2, deltas.into_iter(), &mut bytes).unwrap();
+
+        let mut r = bytes.as_slice();
+        VarInt::decode(&mut r).unwrap();
+        assert_eq!(ChunkSectionPos::decode(&mut r).unwrap(), position);
+        assert_eq!(VarInt::decode(&mut r).unwrap().0, 2);
+        assert_eq!(VarLong::decode(&mut r).unwrap().0, 1 << 12);
+        assert_eq!(VarLong::decode(&mut r).unwrap().0, (2 << 12) | 0xFFF);
+        assert!(r.is_empty());
+    }
+}
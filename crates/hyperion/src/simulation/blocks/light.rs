@@ -0,0 +1,521 @@
+//! Block and sky light, kept up to date as blocks change.
+//!
+//! Light moves to the six neighbours of a block, one level dimmer each step, and does not enter
+//! opaque blocks. Sky light is 15 above the world and goes straight down without dimming until it
+//! reaches an opaque block. Water and leaves do not dim light more than air does.
+//!
+//! A block that changes is relit in two passes: the light that came through it is removed, then
+//! light spreads back in from the lit blocks around what was removed.
+
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use glam::{IVec2, IVec3};
+use indexmap::IndexMap;
+use roaring::RoaringBitmap;
+use rustc_hash::FxBuildHasher;
+use valence_generated::block::BlockState;
+
+use crate::{
+    CHUNK_HEIGHT_SPAN,
+    simulation::blocks::{
+        chunk::{Column, START_Y},
+        loader::parse::{ColumnData, section::Section},
+    },
+};
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::NEG_Y,
+    IVec3::Y,
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// The loaded columns by position.
+pub type Columns = IndexMap<IVec2, Column, FxBuildHasher>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    Block,
+    Sky,
+}
+
+/// Whether replacing `before` with `after` can change the light around it.
+#[must_use]
+pub const fn affects_light(before: BlockState, after: BlockState) -> bool {
+    before.is_opaque() != after.is_opaque() || before.luminance() != after.luminance()
+}
+
+/// Relights the blocks at `positions`, which changed in a way that [`affects_light`], and the
+/// blocks around them in all loaded columns. Returns the indices in `columns` of the columns whose
+/// light changed.
+pub fn update(columns: &mut Columns, positions: &[IVec3]) -> RoaringBitmap {
+    let mut region = Region::new(columns);
+
+    for &position in positions {
+        region.relight_block(LightKind::Block, position);
+        region.relight_block(LightKind::Sky, position);
+    }
+
+    region.touched
+}
+
+/// Lights `data`, the column at `position`, from scratch. Light from the columns around it is not
+/// taken into account. The light of its sections is not marked changed.
+#[must_use]
+pub fn relit(data: ColumnData, position: IVec2) -> ColumnData {
+    let mut columns = Columns::default();
+    columns.insert(position, Column::new(Bytes::new(), data, position));
+
+    Region::new(&mut columns).relight_column(position);
+
+    let (_, mut column) = columns.pop().unwrap();
+
+    for section in &mut column.data.sections {
+        section.light_changed = false;
+    }
+
+    column.data
+}
+
+/// The light `level` of a block has in its neighbour by `direction`, which is `state`.
+fn spread(kind: LightKind, level: u8, direction: IVec3, state: BlockState) -> u8 {
+    if state.is_opaque() {
+        return 0;
+    }
+
+    if kind == LightKind::Sky && level == 15 && direction == IVec3::NEG_Y {
+        return 15;
+    }
+
+    level.saturating_sub(1)
+}
+
+/// The light `state` gives off by itself.
+const fn emission(kind: LightKind, state: BlockState) -> u8 {
+    match kind {
+        LightKind::Block => state.luminance(),
+        LightKind::Sky => 0,
+    }
+}
+
+/// The section of the column a block is in and its index in that section.
+#[derive(Copy, Clone)]
+struct Location {
+    column: IVec2,
+    section: usize,
+    idx: u16,
+}
+
+struct Region<'a> {
+    columns: &'a mut Columns,
+    /// The indices of the columns whose light changed.
+    touched: RoaringBitmap,
+}
+
+impl<'a> Region<'a> {
+    fn new(columns: &'a mut Columns) -> Self {
+        Self {
+            columns,
+            touched: RoaringBitmap::new(),
+        }
+    }
+
+    /// `None` for blocks outside the world.
+    fn locate(position: IVec3) -> Option<Location> {
+        let y = u32::try_from(position.y - i32::from(START_Y)).ok()?;
+        if y >= CHUNK_HEIGHT_SPAN {
+            return None;
+        }
+
+        let x = (position.x & 15).unsigned_abs();
+        let z = (position.z & 15).unsigned_abs();
+        let idx = ((y & 15) << 8) | (z << 4) | x;
+
+        Some(Location {
+            column: IVec2::new(position.x >> 4, position.z >> 4),
+            section: usize::try_from(y >> 4).ok()?,
+            idx: u16::try_from(idx).ok()?,
+        })
+    }
+
+    fn section(&self, location: Location) -> Option<&Section> {
+        self.columns
+            .get(&location.column)?
+            .data
+            .sections
+            .get(location.section)
+    }
+
+    fn is_above_world(position: IVec3) -> bool {
+        i64::from(position.y) >= i64::from(START_Y) + i64::from(CHUNK_HEIGHT_SPAN)
+    }
+
+    /// `None` if the block is outside the world or not loaded.
+    fn block(&self, position: IVec3) -> Option<BlockState> {
+        let location = Self::locate(position)?;
+        let state = self
+            .section(location)?
+            .block_states
+            .get(usize::from(location.idx));
+
+        BlockState::from_raw(state)
+    }
+
+    /// `None` if the block is below the world or not loaded. Above the world, the sky is fully lit.
+    fn light(&self, kind: LightKind, position: IVec3) -> Option<u8> {
+        if Self::is_above_world(position) {
+            return Some(if kind == LightKind::Sky { 15 } else { 0 });
+        }
+
+        let location = Self::locate(position)?;
+        let section = self.section(location)?;
+
+        Some(match kind {
+            LightKind::Block => section.get_block_light(location.idx),
+            LightKind::Sky => section.get_sky_light(location.idx),
+        })
+    }
+
+    fn set_light(&mut self, kind: LightKind, position: IVec3, level: u8) {
+        let Some(location) = Self::locate(position) else {
+            return;
+        };
+
+        let Some((column_idx, _, column)) = self.columns.get_full_mut(&location.column) else {
+            return;
+        };
+
+        let Some(section) = column.data.sections.get_mut(location.section) else {
+            return;
+        };
+
+        match kind {
+            LightKind::Block => section.set_block_light(location.idx, level),
+            LightKind::Sky => section.set_sky_light(location.idx, level),
+        }
+
+        if section.light_changed {
+            self.touched.insert(u32::try_from(column_idx).unwrap());
+        }
+    }
+
+    /// Relights the block at `position` after it changed.
+    fn relight_block(&mut self, kind: LightKind, position: IVec3) {
+        let Some(state) = self.block(position) else {
+            return;
+        };
+
+        let level = self.light(kind, position).unwrap_or(0);
+        self.set_light(kind, position, 0);
+
+        let mut relight = self.remove(kind, VecDeque::from([(position, level)]));
+
+        let emitted = emission(kind, state);
+        if emitted > 0 {
+            self.set_light(kind, position, emitted);
+            relight.push_back(position);
+        }
+
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+            if self.light(kind, neighbour).is_some_and(|level| level > 0) {
+                relight.push_back(neighbour);
+            }
+        }
+
+        self.spread_from(kind, relight);
+    }
+
+    /// Lights the column at `position` from scratch.
+    fn relight_column(&mut self, position: IVec2) {
+        let Some(column) = self.columns.get_mut(&position) else {
+            return;
+        };
+
+        for section in &mut column.data.sections {
+            section.sky_light = Some([0; 2048]);
+            section.block_light = None;
+        }
+
+        let origin = IVec3::new(position.x << 4, i32::from(START_Y), position.y << 4);
+        let top = i32::try_from(CHUNK_HEIGHT_SPAN).unwrap() - 1;
+
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+
+        for x in 0..16 {
+            for z in 0..16 {
+                // sky light comes straight down until the first opaque block
+                for y in (0..=top).rev() {
+                    let position = origin + IVec3::new(x, y, z);
+                    if self.block(position).is_none_or(BlockState::is_opaque) {
+                        break;
+                    }
+
+                    self.set_light(LightKind::Sky, position, 15);
+                    sky.push_back(position);
+                }
+
+                for y in 0..=top {
+                    let position = origin + IVec3::new(x, y, z);
+                    let Some(state) = self.block(position) else {
+                        continue;
+                    };
+
+                    let emitted = emission(LightKind::Block, state);
+                    if emitted > 0 {
+                        self.set_light(LightKind::Block, position, emitted);
+                        block.push_back(position);
+                    }
+                }
+            }
+        }
+
+        self.spread_from(LightKind::Sky, sky);
+        self.spread_from(LightKind::Block, block);
+    }
+
+    /// Removes the light that came through the blocks in `queue`, whose light was set to 0 and
+    /// which had the given levels before. Returns the lit blocks around the removed light, from
+    /// which light spreads back.
+    fn remove(&mut self, kind: LightKind, mut queue: VecDeque<(IVec3, u8)>) -> VecDeque<IVec3> {
+        let mut relight = VecDeque::new();
+
+        while let Some((from, level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let to = from + direction;
+
+                let Some(current) = self.light(kind, to) else {
+                    continue;
+                };
+
+                if current == 0 {
+                    continue;
+                }
+
+                let straight_down = kind == LightKind::Sky && direction == IVec3::NEG_Y;
+                let came_from_here = current < level || (straight_down && level == 15);
+
+                // brighter light, or the sky above the world, comes from elsewhere and spreads back
+                let Some(state) = self.block(to).filter(|_| came_from_here) else {
+                    relight.push_back(to);
+                    continue;
+                };
+
+                let emitted = emission(kind, state);
+                self.set_light(kind, to, emitted);
+                queue.push_back((to, current));
+
+                if emitted > 0 {
+                    relight.push_back(to);
+                }
+            }
+        }
+
+        relight
+    }
+
+    /// Spreads light from the blocks in `queue` to the blocks around them that are darker.
+    fn spread_from(&mut self, kind: LightKind, mut queue: VecDeque<IVec3>) {
+        while let Some(from) = queue.pop_front() {
+            let Some(level) = self.light(kind, from) else {
+                continue;
+            };
+
+            for direction in DIRECTIONS {
+                let to = from + direction;
+
+                let Some(state) = self.block(to) else {
+                    continue;
+                };
+
+                let Some(current) = self.light(kind, to) else {
+                    continue;
+                };
+
+                let spread = spread(kind, level, direction, state);
+                if spread > current {
+                    self.set_light(kind, to, spread);
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{IVec2, IVec3};
+    use valence_generated::block::BlockState;
+
+    use super::{Columns, LightKind, Region, affects_light, relit, update};
+    use crate::{
+        CHUNK_HEIGHT_SPAN,
+        simulation::blocks::{
+            chunk::Column,
+            loader::parse::{ColumnData, section::Section},
+        },
+    };
+
+    const GROUND: i32 = 0;
+
+    /// A 2x2 region of columns that are stone up to and including y = `GROUND`, with air above.
+    fn flat_region() -> Columns {
+        let mut columns = Columns::default();
+
+        for x in -1..=0 {
+            for z in -1..=0 {
+                let position = IVec2::new(x, z);
+                let mut data = ColumnData::new_with(CHUNK_HEIGHT_SPAN, Section::default);
+
+                // y = -64 to 0 are the first four sections and one layer of the fifth
+                for section in &mut data.sections[..4] {
+                    section.fill(BlockState::STONE);
+                }
+                data.sections[4].fill_range(0, 256, BlockState::STONE);
+
+                let data = relit(data, position);
+                columns.insert(position, Column::new(bytes::Bytes::new(), data, position));
+            }
+        }
+
+        columns
+    }
+
+    fn set(columns: &mut Columns, position: IVec3, state: BlockState) {
+        let before = Region::new(columns).block(position).unwrap();
+        assert!(affects_light(before, state));
+
+        let location = Region::locate(position).unwrap();
+        let section = &mut columns[&location.column].data.sections[location.section];
+        section.set_delta(location.idx, state);
+
+        update(columns, &[position]);
+    }
+
+    fn light(columns: &mut Columns, kind: LightKind, position: IVec3) -> u8 {
+        Region::new(columns).light(kind, position).unwrap()
+    }
+
+    #[test]
+    fn loaded_columns_are_lit_by_the_sky() {
+        let mut columns = flat_region();
+
+        assert_eq!(
+            light(&mut columns, LightKind::Sky, IVec3::new(3, GROUND + 1, -7)),
+            15
+        );
+        assert_eq!(
+            light(&mut columns, LightKind::Sky, IVec3::new(3, GROUND, -7)),
+            0
+        );
+        assert_eq!(
+            light(&mut columns, LightKind::Sky, IVec3::new(3, 300, -7)),
+            15
+        );
+        assert_eq!(
+            light(
+                &mut columns,
+                LightKind::Block,
+                IVec3::new(3, GROUND + 1, -7)
+            ),
+            0
+        );
+
+        let mut sections = columns.values().flat_map(|column| &column.data.sections);
+        assert!(sections.all(|section| !section.light_changed));
+    }
+
+    #[test]
+    fn torches_light_up_around_them() {
+        let mut columns = flat_region();
+        let torch = IVec3::new(0, GROUND + 1, 0);
+
+        set(&mut columns, torch, BlockState::TORCH);
+
+        assert_eq!(light(&mut columns, LightKind::Block, torch), 14);
+        assert_eq!(
+            light(&mut columns, LightKind::Block, torch + IVec3::new(0, 0, -1)),
+            13
+        );
+        assert_eq!(
+            light(&mut columns, LightKind::Block, torch + IVec3::new(-3, 2, 0)),
+            9
+        );
+        assert_eq!(
+            light(&mut columns, LightKind::Block, torch + IVec3::new(0, 14, 0)),
+            0
+        );
+        assert_eq!(light(&mut columns, LightKind::Block, torch - IVec3::Y), 0);
+
+        set(&mut columns, torch, BlockState::AIR);
+
+        assert_eq!(light(&mut columns, LightKind::Block, torch), 0);
+        assert_eq!(
+            light(&mut columns, LightKind::Block, torch + IVec3::new(-3, 2, 0)),
+            0
+        );
+    }
+
+    #[test]
+    fn blocks_under_the_sky_cast_shadows() {
+        let mut columns = flat_region();
+        let block = IVec3::new(-2, GROUND + 3, 5);
+        let below = block - IVec3::Y;
+
+        set(&mut columns, block, BlockState::STONE);
+
+        assert_eq!(light(&mut columns, LightKind::Sky, block), 0);
+        assert_eq!(light(&mut columns, LightKind::Sky, below), 14);
+        assert_eq!(light(&mut columns, LightKind::Sky, below - IVec3::Y), 14);
+        assert_eq!(light(&mut columns, LightKind::Sky, block + IVec3::Y), 15);
+        assert_eq!(light(&mut columns, LightKind::Sky, below + IVec3::X), 15);
+    }
+
+    #[test]
+    fn removing_a_ceiling_lets_the_sky_in() {
+        let mut columns = flat_region();
+
+        // a 3x3 roof two blocks above the ground, across the corner of four columns
+        for x in -1..=1 {
+            for z in -1..=1 {
+                set(
+                    &mut columns,
+                    IVec3::new(x, GROUND + 3, z),
+                    BlockState::STONE,
+                );
+            }
+        }
+
+        let under = IVec3::new(0, GROUND + 1, 0);
+        assert_eq!(light(&mut columns, LightKind::Sky, under), 13);
+
+        set(&mut columns, IVec3::new(0, GROUND + 3, 0), BlockState::AIR);
+
+        assert_eq!(light(&mut columns, LightKind::Sky, under), 15);
+        assert_eq!(light(&mut columns, LightKind::Sky, under + IVec3::X), 14);
+        assert_eq!(light(&mut columns, LightKind::Sky, under - IVec3::Y), 0);
+    }
+
+    #[test]
+    fn light_changes_mark_their_columns() {
+        let mut columns = flat_region();
+
+        let location = Region::locate(IVec3::new(-1, GROUND + 1, -1)).unwrap();
+        let section = &mut columns[&location.column].data.sections[location.section];
+        section.set_delta(location.idx, BlockState::GLOWSTONE);
+
+        let touched = update(&mut columns, &[IVec3::new(-1, GROUND + 1, -1)]);
+
+        // the light reaches all four columns
+        assert_eq!(touched.len(), 4);
+        assert!(
+            columns
+                .values()
+                .all(|column| column.data.sections[4].light_changed)
+        );
+    }
+}
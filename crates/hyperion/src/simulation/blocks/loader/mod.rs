@@ -1,23 +1,29 @@
-use std::{borrow::Cow, cell::RefCell, io::Write, sync::Arc};
+use std::{borrow::Cow, cell::RefCell, sync::Arc};
 
 use anyhow::{Context, bail};
 use bytes::BytesMut;
-use glam::IVec2;
+use glam::{IVec2, UVec3};
 use hyperion_nerd_font::NERD_ROCKET;
 use itertools::Itertools;
 use libdeflater::{CompressionLvl, Compressor};
 use parse::ColumnData;
 use rustc_hash::FxHashSet;
 use tracing::{debug, warn};
-use valence_generated::block::BlockState;
-use valence_nbt::{List, compound};
-use valence_protocol::{ChunkPos, CompressionThreshold, FixedArray, packets::play};
-use valence_registry::RegistryIdx;
-use valence_server::layer::chunk::{BiomeContainer, Chunk, bit_width};
+use valence_generated::block::BlockEntityKind;
+use valence_nbt::{Compound, List, compound};
+use valence_protocol::{
+    ChunkPos, CompressionThreshold, FixedArray, VarInt,
+    packets::play::{self, chunk_data_s2c::ChunkDataBlockEntity},
+};
+use valence_server::layer::chunk::Chunk;
 
 pub mod parse;
 
-use super::{chunk::Column, shared::WorldShared};
+use super::{
+    chunk::{Column, START_Y},
+    light,
+    shared::WorldShared,
+};
 use crate::{
     CHUNK_HEIGHT_SPAN, Scratch,
     net::encoder::PacketEncoder,
@@ -192,6 +198,8 @@ async fn load_chunk(position: IVec2, shared: &WorldShared) -> anyhow::Result<Col
         }
     };
 
+    let chunk = light::relit(chunk, position);
+
     STATE.with_borrow_mut(|state| {
         let Ok(Some(bytes)) = encode_chunk_packet(&chunk, position, state) else {
             bail!("failed to encode chunk {position:?}");
@@ -203,6 +211,21 @@ async fn load_chunk(position: IVec2, shared: &WorldShared) -> anyhow::Result<Col
     })
 }
 
+/// One above the highest motion blocking block of the column at `x` and `z`, counted from the
+/// bottom of the chunk, or 0 if there is none.
+fn column_height(sections: &[Section], x: u8, z: u8) -> u32 {
+    sections
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, section)| {
+            let height = section.heightmap_column(x, z);
+            let bottom = u32::try_from(i * 16).ok()?;
+            (height > 0).then_some(bottom + u32::from(height))
+        })
+        .unwrap_or(0)
+}
+
 // #[instrument(skip_all, level = "trace", fields(location = ?location))]
 fn encode_chunk_packet(
     chunk: &ColumnData,
@@ -211,10 +234,29 @@ fn encode_chunk_packet(
 ) -> anyhow::Result<Option<BytesMut>> {
     let encoder = PacketEncoder::new(CompressionThreshold::from(6));
 
+    let buf = &mut state.bytes;
+    let scratch = &mut state.scratch;
+    let compressor = &mut state.compressor;
+
+    let result = with_chunk_data_packet(chunk, location, |pkt| {
+        encoder.append_packet(pkt, buf, scratch, compressor)
+    })??;
+
+    Ok(Some(result))
+}
+
+/// Builds the packet that sends all of `chunk`, the column at `location`, and hands it to `f`.
+pub fn with_chunk_data_packet<R>(
+    chunk: &ColumnData,
+    location: IVec2,
+    f: impl FnOnce(&play::ChunkDataS2c<'_>) -> R,
+) -> anyhow::Result<R> {
     let section_count = CHUNK_HEIGHT_SPAN as usize / 16_usize;
     let dimension_height = CHUNK_HEIGHT_SPAN;
 
-    let map = heightmap(dimension_height, dimension_height - 3);
+    let map = heightmap(dimension_height, |x, z| {
+        column_height(&chunk.sections, x, z)
+    });
     let map = map.into_iter().map(i64::try_from).try_collect()?;
 
     // convert section_count + 2 0b1s into `u64` array
@@ -235,9 +277,7 @@ fn encode_chunk_packet(
     block_light_mask.set(0, 0);
 
     for (i, section) in chunk.sections.iter().enumerate() {
-        use valence_protocol::Encode;
-        let non_air_blocks: u16 = 42;
-        non_air_blocks.encode(&mut section_bytes).unwrap();
+        section.encode_to_packet(&mut section_bytes)?;
 
         // todo: how do sky light and block light work differently?
 
@@ -255,9 +295,6 @@ fn encode_chunk_packet(
             block_light_arrays.push(block_light);
             block_light_mask.set(i + 1, 1);
         }
-
-        write_block_states(&section.block_states, &mut section_bytes).unwrap();
-        write_biomes(&section.biomes, &mut section_bytes).unwrap();
     }
 
     // todo: is this right?
@@ -276,6 +313,11 @@ fn encode_chunk_packet(
     let sky_light_data = sky_light_mask.into_data();
     let block_light_data = block_light_mask.into_data();
 
+    let block_entities: Vec<_> = chunk
+        .iter_block_entities()
+        .map(|(position, kind, data)| block_entity_entry(position, kind, data))
+        .try_collect()?;
+
     let pkt = play::ChunkDataS2c {
         pos: ChunkPos::new(location.x, location.y),
 
@@ -284,7 +326,7 @@ fn encode_chunk_packet(
             "MOTION_BLOCKING" => List::Long(map),
         }),
         blocks_and_biomes: &section_bytes,
-        block_entities: Cow::Borrowed(&[]),
+        block_entities: Cow::Owned(block_entities),
 
         sky_light_mask: Cow::Borrowed(&sky_light_data),
         block_light_mask: Cow::Borrowed(&block_light_data),
@@ -296,36 +338,77 @@ fn encode_chunk_packet(
         block_light_arrays: Cow::Owned(block_light_arrays),
     };
 
-    let buf = &mut state.bytes;
-    let scratch = &mut state.scratch;
-    let compressor = &mut state.compressor;
-
-    let result = encoder.append_packet(&pkt, buf, scratch, compressor)?;
-
-    Ok(Some(result))
+    Ok(f(&pkt))
 }
 
-fn write_block_states(
-    states: &hyperion_palette::PalettedContainer,
-    writer: &mut impl Write,
-) -> anyhow::Result<()> {
-    states.encode_mc_format(
-        writer,
-        derive_more::Into::into,
-        4,
-        8,
-        bit_width(BlockState::max_raw().into()),
-    )?;
-    Ok(())
+/// The block entity at `position` in a column as it is sent with the column.
+fn block_entity_entry(
+    position: UVec3,
+    kind: BlockEntityKind,
+    data: &Compound,
+) -> anyhow::Result<ChunkDataBlockEntity<'_>> {
+    let packed_xz = u8::try_from((position.x << 4) | position.z)?;
+
+    Ok(ChunkDataBlockEntity {
+        packed_xz: i8::from_ne_bytes([packed_xz]),
+        y: i16::try_from(position.y)? + START_Y,
+        kind: VarInt(i32::try_from(kind.id())?),
+        data: Cow::Borrowed(data),
+    })
 }
 
-fn write_biomes(biomes: &BiomeContainer, writer: &mut impl Write) -> anyhow::Result<()> {
-    biomes.encode_mc_format(
-        writer,
-        |b| b.to_index() as u64,
-        0,
-        3,
-        6, // bit_width(info.biome_registry_len - 1),
-    )?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use glam::IVec2;
+    use valence_generated::block::{BlockEntityKind, BlockState};
+    use valence_nbt::{List, compound};
+    use valence_protocol::{Decode, Encode, packets::play};
+    use valence_server::layer::chunk::Chunk;
+
+    use super::with_chunk_data_packet;
+    use crate::{
+        CHUNK_HEIGHT_SPAN,
+        simulation::blocks::loader::parse::{ColumnData, section::Section},
+    };
+
+    #[test]
+    fn chest_contents_are_sent_with_the_column() {
+        let mut data = ColumnData::new_with(CHUNK_HEIGHT_SPAN, Section::default);
+
+        // y = 70 in the world
+        data.set_delta(3, 134, 9, BlockState::CHEST);
+
+        let items = compound! {
+            "Items" => List::Compound(vec![compound! {
+                "Slot" => 0_i8,
+                "id" => "minecraft:diamond",
+                "Count" => 5_i8,
+            }]),
+        };
+        data.set_block_entity(3, 134, 9, Some(items.clone()));
+
+        let mut bytes = Vec::new();
+        with_chunk_data_packet(&data, IVec2::new(-2, 7), |pkt| pkt.encode(&mut bytes))
+            .unwrap()
+            .unwrap();
+
+        let mut r = bytes.as_slice();
+        let pkt = play::ChunkDataS2c::decode(&mut r).unwrap();
+
+        let [chest] = &*pkt.block_entities else {
+            panic!("expected one block entity, got {:?}", pkt.block_entities);
+        };
+
+        assert_eq!(chest.packed_xz, (3 << 4) | 9);
+        assert_eq!(chest.y, 70);
+        assert_eq!(
+            chest.kind.0,
+            i32::try_from(BlockEntityKind::Chest.id()).unwrap()
+        );
+        assert_eq!(*chest.data, items);
+
+        // the chest goes with its block
+        data.set_delta(3, 134, 9, BlockState::STONE);
+        assert!(data.block_entity(3, 134, 9).is_none());
+    }
 }
@@ -1,9 +1,10 @@
 use std::{borrow::Cow, collections::BTreeMap};
 
+use glam::UVec3;
 use thiserror::Error;
 use tracing::warn;
 use valence_anvil::RegionError;
-use valence_generated::block::{BlockKind, BlockState, PropName, PropValue};
+use valence_generated::block::{BlockEntityKind, BlockKind, BlockState, PropName, PropValue};
 use valence_nbt::{Compound, List, Value};
 use valence_protocol::Ident;
 use valence_registry::biome::BiomeId;
@@ -99,6 +100,8 @@ impl ColumnData {
         }
     }
 
+    /// Sets the block at `x`, `y`, `z`, returning the one it replaces. A block entity there is
+    /// removed if the block becomes a different kind of block.
     pub fn set_delta(&mut self, x: u32, y: u32, z: u32, block: BlockState) -> BlockState {
         check_block_oob(self, x, y, z);
 
@@ -109,7 +112,35 @@ impl ColumnData {
         // todo: remove try_unwrap when we show this is safe
         let idx = u16::try_from(idx).unwrap();
 
-        self.sections[y as usize / 16].set_delta(idx, block)
+        let before = self.sections[y as usize / 16].set_delta(idx, block);
+
+        if before.to_kind() != block.to_kind() {
+            self.block_entities.remove(&(x + z * 16 + y * 16 * 16));
+        }
+
+        before
+    }
+
+    /// The block entity stored under `idx`, with its position in the column and the kind of the
+    /// block it belongs to. `None` if there is none or the block there has no block entity.
+    #[must_use]
+    pub fn block_entity_at(&self, idx: u32) -> Option<(UVec3, BlockEntityKind, &Compound)> {
+        let data = self.block_entities.get(&idx)?;
+
+        let position = UVec3::new(idx % 16, idx / (16 * 16), idx / 16 % 16);
+        let block = self.block_state(position.x, position.y, position.z);
+        let kind = block.to_kind().block_entity_kind()?;
+
+        Some((position, kind, data))
+    }
+
+    /// Every block entity, as returned by [`ColumnData::block_entity_at`].
+    pub fn iter_block_entities(
+        &self,
+    ) -> impl Iterator<Item = (UVec3, BlockEntityKind, &Compound)> + '_ {
+        self.block_entities
+            .keys()
+            .filter_map(|&idx| self.block_entity_at(idx))
     }
 }
 
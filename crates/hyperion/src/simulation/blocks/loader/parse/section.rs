@@ -1,8 +1,13 @@
+use std::io::Write;
+
 use glam::IVec3;
-use more_asserts::debug_assert_lt;
+use more_asserts::{debug_assert_le, debug_assert_lt};
 use roaring::RoaringBitmap;
+use thiserror::Error;
 use valence_generated::block::BlockState;
-use valence_server::layer::chunk::BiomeContainer;
+use valence_protocol::Encode;
+use valence_registry::{RegistryIdx, biome::BiomeId};
+use valence_server::layer::chunk::{BiomeContainer, bit_width};
 
 #[derive(Clone, Debug)]
 pub struct Section {
@@ -15,6 +20,13 @@ pub struct Section {
 
     pub changed: RoaringBitmap,
     pub changed_since_last_tick: RoaringBitmap,
+
+    /// Whether a biome changed since the section was last sent. Whoever resends it clears this.
+    pub biomes_changed: bool,
+
+    /// Whether a light level changed since the light of the section was last sent. Whoever resends
+    /// it clears this.
+    pub light_changed: bool,
 }
 
 impl Default for Section {
@@ -26,10 +38,24 @@ impl Default for Section {
             sky_light: None,
             changed: RoaringBitmap::new(),
             changed_since_last_tick: RoaringBitmap::new(),
+            biomes_changed: false,
+            light_changed: false,
         }
     }
 }
 
+/// A block index past the 4096 blocks of a section.
+#[derive(Copy, Clone, Debug, Error, PartialEq, Eq)]
+#[error("block index {0} is outside of the section")]
+pub struct InvalidBlockIndex(pub u16);
+
+/// Whether `state` stops movement or holds a fluid. The `MOTION_BLOCKING` heightmap and sky light
+/// both stop at such blocks.
+#[must_use]
+pub const fn is_motion_blocking(state: BlockState) -> bool {
+    state.blocks_motion() || state.is_liquid()
+}
+
 impl Section {
     pub fn empty_sky() -> Self {
         Self {
@@ -45,14 +71,53 @@ impl Section {
         let y = idx >> 8;
         IVec3::new(x, y, z)
     }
+
+    /// The local x, y and z of the block at `idx`. Blocks are ordered by y, then z, then x like in
+    /// packets.
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "each coordinate is masked to 4 bits"
+    )]
+    pub const fn index_to_coords(idx: u16) -> (u8, u8, u8) {
+        debug_assert_lt!(idx, 4096);
+
+        let x = (idx & 0xF) as u8;
+        let z = (idx >> 4 & 0xF) as u8;
+        let y = (idx >> 8 & 0xF) as u8;
+        (x, y, z)
+    }
+
+    /// The index of the block at the local `x`, `y` and `z`, which must each be below 16.
+    #[must_use]
+    #[expect(clippy::cast_lossless, reason = "`u16::from` is not const")]
+    pub const fn coords_to_index(x: u8, y: u8, z: u8) -> u16 {
+        debug_assert_lt!(x, 16);
+        debug_assert_lt!(y, 16);
+        debug_assert_lt!(z, 16);
+
+        (y as u16) << 8 | (z as u16) << 4 | x as u16
+    }
 }
 
 impl Section {
     pub fn set(&mut self, idx: u16, new: BlockState) -> BlockState {
+        debug_assert_lt!(idx, 4096);
+
         let prev = unsafe { self.block_states.set_unchecked(idx as usize, new.to_raw()) };
         unsafe { BlockState::from_raw(prev).unwrap_unchecked() }
     }
 
+    /// Like [`Section::set`], but `idx` is checked instead of trusted, for indices that come from
+    /// players.
+    pub fn try_set(&mut self, idx: u16, new: BlockState) -> Result<BlockState, InvalidBlockIndex> {
+        if idx >= 4096 {
+            return Err(InvalidBlockIndex(idx));
+        }
+
+        Ok(self.set(idx, new))
+    }
+
     pub fn blocks_states(&self) -> impl Iterator<Item = (glam::U16Vec3, BlockState)> + '_ {
         self.block_states.iter().enumerate().map(|(idx, data)| {
             let idx = unsafe { u16::try_from(idx).unwrap_unchecked() };
@@ -81,9 +146,245 @@ impl Section {
         unsafe { BlockState::from_raw(before).unwrap_unchecked() }
     }
 
+    /// Sets every block to `state`, which leaves a single value in the container. Like
+    /// [`Section::set_delta`], only the blocks that were something else are marked changed.
+    pub fn fill(&mut self, state: BlockState) {
+        let new = state.to_raw();
+
+        if let hyperion_palette::PalettedContainer::Single(before) = self.block_states {
+            if before == new {
+                return;
+            }
+        }
+
+        let changed = (0_u32..)
+            .zip(&self.block_states)
+            .filter(|&(_, before)| before != new)
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        self.block_states.fill(new);
+        self.mark_all_changed(changed);
+    }
+
+    /// Sets the blocks from `start` up to but not including `end` to `state`. Like
+    /// [`Section::set_delta`], only the blocks that were something else are marked changed.
+    pub fn fill_range(&mut self, start: u16, end: u16, state: BlockState) {
+        debug_assert_le!(start, end);
+        debug_assert_le!(end, 4096);
+
+        if start == 0 && end == 4096 {
+            self.fill(state);
+            return;
+        }
+
+        let new = state.to_raw();
+        let mut changed = Vec::new();
+
+        for idx in start..end {
+            let before = unsafe { self.block_states.set_unchecked(usize::from(idx), new) };
+
+            if before != new {
+                changed.push(u32::from(idx));
+            }
+        }
+
+        self.mark_all_changed(changed);
+    }
+
+    /// The blocks that changed since the last tick, with their current states, by index.
+    pub fn iter_changed_since_last_tick(&self) -> impl Iterator<Item = (u16, BlockState)> + '_ {
+        self.states_of(&self.changed_since_last_tick)
+    }
+
+    /// Like [`Section::iter_changed_since_last_tick`], but by local x, y and z.
+    pub fn iter_changed_coords_since_last_tick(
+        &self,
+    ) -> impl Iterator<Item = ((u8, u8, u8), BlockState)> + '_ {
+        self.iter_changed_since_last_tick()
+            .map(|(idx, state)| (Self::index_to_coords(idx), state))
+    }
+
+    /// The blocks that changed since the section was loaded, with their current states, by index.
+    pub fn iter_changed(&self) -> impl Iterator<Item = (u16, BlockState)> + '_ {
+        self.states_of(&self.changed)
+    }
+
+    fn states_of<'a>(
+        &'a self,
+        indices: &'a RoaringBitmap,
+    ) -> impl Iterator<Item = (u16, BlockState)> + 'a {
+        indices.iter().map(|idx| {
+            debug_assert_lt!(idx, 4096);
+
+            let idx = unsafe { u16::try_from(idx).unwrap_unchecked() };
+            let state = unsafe { self.block_states.get_unchecked(usize::from(idx)) };
+
+            (idx, unsafe {
+                BlockState::from_raw(state).unwrap_unchecked()
+            })
+        })
+    }
+
     pub fn reset_tick_deltas(&mut self) {
         self.changed_since_last_tick.clear();
     }
+
+    /// How many blocks are not air. Clients skip sections without any.
+    #[must_use]
+    pub fn non_air_count(&self) -> u16 {
+        let is_air = |raw| unsafe { BlockState::from_raw(raw).unwrap_unchecked() }.is_air();
+
+        if let hyperion_palette::PalettedContainer::Single(raw) = self.block_states {
+            return if is_air(raw) { 0 } else { 4096 };
+        }
+
+        let count = self.block_states.iter().filter(|&raw| !is_air(raw)).count();
+        u16::try_from(count).unwrap()
+    }
+
+    /// Writes the section as it is sent in a chunk data packet: the number of blocks that are not
+    /// air, then the block states and biomes. Light is not part of it, as the packet sends the
+    /// light of every section after all of them.
+    pub fn encode_to_packet(&self, buf: &mut impl Write) -> anyhow::Result<()> {
+        self.non_air_count().encode(&mut *buf)?;
+        write_block_states(&self.block_states, buf)?;
+        write_biomes(&self.biomes, buf)
+    }
+
+    /// The highest local Y of the column at `x` and `z` that is not air.
+    #[must_use]
+    pub fn highest_non_air(&self, x: u8, z: u8) -> Option<u8> {
+        self.highest_where(x, z, |state| !state.is_air())
+    }
+
+    /// One above the highest local Y of the column at `x` and `z` that is motion blocking, or 0
+    /// if there is none, as stored in heightmaps.
+    #[must_use]
+    pub fn heightmap_column(&self, x: u8, z: u8) -> u8 {
+        self.highest_where(x, z, is_motion_blocking)
+            .map_or(0, |y| y + 1)
+    }
+
+    fn highest_where(&self, x: u8, z: u8, predicate: impl Fn(BlockState) -> bool) -> Option<u8> {
+        debug_assert_lt!(x, 16);
+        debug_assert_lt!(z, 16);
+
+        (0..16_u8).rev().find(|&y| {
+            let idx = usize::from(x) + usize::from(z) * 16 + usize::from(y) * 256;
+            let state = self.block_states.get(idx);
+            predicate(unsafe { BlockState::from_raw(state).unwrap_unchecked() })
+        })
+    }
+
+    /// The biome at `idx` of the 4x4x4 biome grid, which is ordered like blocks.
+    #[must_use]
+    pub fn get_biome(&self, idx: u16) -> BiomeId {
+        debug_assert_lt!(idx, 64);
+        self.biomes.get(usize::from(idx))
+    }
+
+    /// Sets the biome at `idx` of the 4x4x4 biome grid, marking the biomes changed if it differs.
+    pub fn set_biome(&mut self, idx: u16, biome: BiomeId) {
+        debug_assert_lt!(idx, 64);
+
+        if self.biomes.set(usize::from(idx), biome) != biome {
+            self.biomes_changed = true;
+        }
+    }
+
+    /// The block light level at `idx`, from 0 to 15. Sections without block light are dark.
+    #[must_use]
+    pub fn get_block_light(&self, idx: u16) -> u8 {
+        self.block_light
+            .as_ref()
+            .map_or(0, |light| get_nibble(light, idx))
+    }
+
+    /// Sets the block light level at `idx`, clamped to 15, marking the light changed if it differs.
+    pub fn set_block_light(&mut self, idx: u16, level: u8) {
+        if self.block_light.is_none() && level == 0 {
+            return;
+        }
+
+        let light = self.block_light.get_or_insert([0; 2048]);
+        if set_nibble(light, idx, level) {
+            self.light_changed = true;
+        }
+    }
+
+    /// The sky light level at `idx`, from 0 to 15. Sections without sky light are fully lit, as
+    /// that is what is sent to clients for them.
+    #[must_use]
+    pub fn get_sky_light(&self, idx: u16) -> u8 {
+        self.sky_light
+            .as_ref()
+            .map_or(15, |light| get_nibble(light, idx))
+    }
+
+    /// Sets the sky light level at `idx`, clamped to 15, marking the light changed if it differs.
+    pub fn set_sky_light(&mut self, idx: u16, level: u8) {
+        let light = self.sky_light.get_or_insert([0xff; 2048]);
+        if set_nibble(light, idx, level) {
+            self.light_changed = true;
+        }
+    }
+
+    /// Marks the sorted `indices` changed.
+    fn mark_all_changed(&mut self, indices: Vec<u32>) {
+        self.changed_since_last_tick.extend(indices.iter().copied());
+        self.changed.extend(indices);
+    }
+}
+
+fn write_block_states(
+    states: &hyperion_palette::PalettedContainer,
+    writer: &mut impl Write,
+) -> anyhow::Result<()> {
+    states.encode_mc_format(
+        writer,
+        derive_more::Into::into,
+        4,
+        8,
+        bit_width(BlockState::max_raw().into()),
+    )?;
+    Ok(())
+}
+
+fn write_biomes(biomes: &BiomeContainer, writer: &mut impl Write) -> anyhow::Result<()> {
+    biomes.encode_mc_format(
+        writer,
+        |b| b.to_index() as u64,
+        0,
+        3,
+        6, // bit_width(info.biome_registry_len - 1),
+    )?;
+    Ok(())
+}
+
+/// Light is stored as half bytes, with even indices in the low half and odd ones in the high half.
+fn get_nibble(light: &[u8; 2048], idx: u16) -> u8 {
+    debug_assert_lt!(idx, 4096);
+
+    let byte = light[usize::from(idx / 2)];
+    if idx % 2 == 0 { byte & 0xF } else { byte >> 4 }
+}
+
+/// Returns whether the level at `idx` changed.
+fn set_nibble(light: &mut [u8; 2048], idx: u16, level: u8) -> bool {
+    debug_assert_lt!(idx, 4096);
+
+    let level = level.min(15);
+    let before = get_nibble(light, idx);
+
+    let byte = &mut light[usize::from(idx / 2)];
+    *byte = if idx % 2 == 0 {
+        (*byte & 0xF0) | level
+    } else {
+        (*byte & 0x0F) | (level << 4)
+    };
+
+    before != level
 }
 
 #[cfg(test)]
@@ -98,6 +399,8 @@ mod tests {
             sky_light: None,
             changed: RoaringBitmap::default(),
             changed_since_last_tick: RoaringBitmap::default(),
+            biomes_changed: false,
+            light_changed: false,
         }
     }
 
@@ -167,6 +470,26 @@ mod tests {
         assert_eq!(section.block_states.get(4095), state.to_raw());
     }
 
+    #[test]
+    fn test_try_set_checks_the_index() {
+        let mut section = create_test_section();
+
+        assert_eq!(
+            section.try_set(4095, BlockState::STONE),
+            Ok(BlockState::AIR)
+        );
+        assert_eq!(section.block_states.get(4095), BlockState::STONE.to_raw());
+
+        assert_eq!(
+            section.try_set(4096, BlockState::STONE),
+            Err(InvalidBlockIndex(4096))
+        );
+        assert_eq!(
+            section.try_set(u16::MAX, BlockState::STONE),
+            Err(InvalidBlockIndex(u16::MAX))
+        );
+    }
+
     #[test]
     fn test_reset_tick_deltas() {
         let mut section = create_test_section();
@@ -194,4 +517,229 @@ mod tests {
             BlockState::GRASS_BLOCK.to_raw()
         );
     }
+
+    #[test]
+    fn test_block_light_even_and_odd_indices() {
+        let mut section = create_test_section();
+        assert_eq!(section.get_block_light(0), 0);
+
+        section.set_block_light(0, 3);
+        section.set_block_light(1, 12);
+
+        assert_eq!(section.get_block_light(0), 3);
+        assert_eq!(section.get_block_light(1), 12);
+        assert_eq!(section.block_light.unwrap()[0], 0xC3);
+        assert!(section.light_changed);
+    }
+
+    #[test]
+    fn test_sky_light_boundary_index() {
+        let mut section = create_test_section();
+        assert_eq!(section.get_sky_light(4095), 15);
+
+        section.set_sky_light(4095, 4);
+
+        assert_eq!(section.get_sky_light(4095), 4);
+        assert_eq!(section.get_sky_light(4094), 15);
+        assert!(section.light_changed);
+    }
+
+    #[test]
+    fn test_light_is_clamped() {
+        let mut section = create_test_section();
+
+        section.set_block_light(2, 200);
+        section.set_sky_light(2, 16);
+
+        assert_eq!(section.get_block_light(2), 15);
+        assert_eq!(section.get_block_light(3), 0);
+        assert_eq!(section.get_sky_light(2), 15);
+
+        // light is not a block change
+        assert!(section.light_changed);
+        assert!(section.changed.is_empty());
+    }
+
+    #[test]
+    fn test_fill_only_marks_changed_blocks() {
+        let mut section = create_test_section();
+        section.set_delta(5, BlockState::STONE);
+        section.reset_tick_deltas();
+
+        section.fill(BlockState::STONE);
+
+        assert_eq!(section.changed.len(), 4096);
+        assert_eq!(section.changed_since_last_tick.len(), 4095);
+        assert!(!section.changed_since_last_tick.contains(5));
+        assert!(matches!(
+            section.block_states,
+            hyperion_palette::PalettedContainer::Single(_)
+        ));
+        assert_eq!(section.block_states.get(0), BlockState::STONE.to_raw());
+    }
+
+    #[test]
+    fn test_fill_uniform_section_is_noop() {
+        let mut section = create_test_section();
+
+        section.fill(BlockState::AIR);
+
+        assert!(section.changed.is_empty());
+        assert!(section.changed_since_last_tick.is_empty());
+    }
+
+    #[test]
+    fn test_fill_range() {
+        let mut section = create_test_section();
+        section.set_delta(10, BlockState::DIRT);
+
+        section.fill_range(8, 16, BlockState::DIRT);
+
+        assert_eq!(section.changed.len(), 8);
+        assert_eq!(section.block_states.get(7), BlockState::AIR.to_raw());
+        assert_eq!(section.block_states.get(15), BlockState::DIRT.to_raw());
+        assert_eq!(section.block_states.get(16), BlockState::AIR.to_raw());
+
+        section.reset_tick_deltas();
+        section.fill_range(8, 16, BlockState::DIRT);
+        assert!(section.changed_since_last_tick.is_empty());
+
+        section.fill_range(4095, 4096, BlockState::STONE);
+        assert!(section.changed.contains(4095));
+    }
+
+    #[test]
+    fn test_iter_changed() {
+        let mut section = create_test_section();
+
+        section.set_delta(3, BlockState::STONE);
+        section.set_delta(4095, BlockState::DIRT);
+        section.reset_tick_deltas();
+
+        section.set_delta(3, BlockState::GRASS_BLOCK);
+        section.set_delta(7, BlockState::AIR);
+        section.set_delta(100, BlockState::DIRT);
+
+        assert_eq!(
+            section.iter_changed_since_last_tick().collect::<Vec<_>>(),
+            vec![(3, BlockState::GRASS_BLOCK), (100, BlockState::DIRT),]
+        );
+        assert_eq!(section.iter_changed().collect::<Vec<_>>(), vec![
+            (3, BlockState::GRASS_BLOCK),
+            (100, BlockState::DIRT),
+            (4095, BlockState::DIRT),
+        ]);
+    }
+
+    #[test]
+    fn test_coords_round_trip() {
+        for idx in [0, 1, 15, 16, 255, 256, 1234, 4095] {
+            let (x, y, z) = Section::index_to_coords(idx);
+            assert_eq!(Section::coords_to_index(x, y, z), idx);
+        }
+
+        assert_eq!(Section::index_to_coords(0x3A5), (5, 3, 10));
+        assert_eq!(Section::coords_to_index(15, 15, 15), 4095);
+    }
+
+    #[test]
+    fn test_iter_changed_coords() {
+        let mut section = create_test_section();
+
+        section.set_delta(Section::coords_to_index(1, 2, 3), BlockState::STONE);
+        section.set_delta(4095, BlockState::DIRT);
+
+        assert_eq!(
+            section
+                .iter_changed_coords_since_last_tick()
+                .collect::<Vec<_>>(),
+            vec![
+                ((1, 2, 3), BlockState::STONE),
+                ((15, 15, 15), BlockState::DIRT)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encoded_sections_start_with_the_non_air_count() {
+        let mut section = create_test_section();
+
+        let mut air = Vec::new();
+        section.encode_to_packet(&mut air).unwrap();
+        assert_eq!(air[..2], 0_u16.to_be_bytes());
+
+        section.fill(BlockState::STONE);
+        let mut stone = Vec::new();
+        section.encode_to_packet(&mut stone).unwrap();
+        assert_eq!(stone[..2], 4096_u16.to_be_bytes());
+
+        // only the count differs, as both are a single block state and biome
+        assert_eq!(air.len(), stone.len());
+
+        section.set_delta(7, BlockState::AIR);
+        assert_eq!(section.non_air_count(), 4095);
+    }
+
+    #[test]
+    fn test_set_biome() {
+        let mut section = create_test_section();
+        let plains = BiomeId::from_index(1);
+        let desert = BiomeId::from_index(2);
+
+        section.set_biome(0, BiomeId::default());
+        assert!(!section.biomes_changed);
+
+        section.set_biome(0, plains);
+        section.set_biome(63, desert);
+
+        assert!(section.biomes_changed);
+        assert_eq!(section.get_biome(0), plains);
+        assert_eq!(section.get_biome(1), BiomeId::default());
+        assert_eq!(section.get_biome(63), desert);
+        assert!(section.changed.is_empty());
+    }
+
+    fn column_idx(y: u16) -> u16 {
+        // x = 3, z = 5
+        3 + 5 * 16 + y * 256
+    }
+
+    #[test]
+    fn test_air_column_has_no_height() {
+        let section = create_test_section();
+
+        assert_eq!(section.highest_non_air(3, 5), None);
+        assert_eq!(section.heightmap_column(3, 5), 0);
+    }
+
+    #[test]
+    fn test_single_block_column() {
+        let mut section = create_test_section();
+        section.set_delta(column_idx(6), BlockState::STONE);
+
+        assert_eq!(section.highest_non_air(3, 5), Some(6));
+        assert_eq!(section.heightmap_column(3, 5), 7);
+        assert_eq!(section.highest_non_air(5, 3), None);
+    }
+
+    #[test]
+    fn test_full_column() {
+        let mut section = create_test_section();
+        for y in 0..16 {
+            section.set_delta(column_idx(y), BlockState::STONE);
+        }
+
+        assert_eq!(section.highest_non_air(3, 5), Some(15));
+        assert_eq!(section.heightmap_column(3, 5), 16);
+    }
+
+    #[test]
+    fn test_heightmap_skips_blocks_that_do_not_block_motion() {
+        let mut section = create_test_section();
+        section.set_delta(column_idx(2), BlockState::WATER);
+        section.set_delta(column_idx(3), BlockState::TORCH);
+
+        assert_eq!(section.highest_non_air(3, 5), Some(3));
+        assert_eq!(section.heightmap_column(3, 5), 3);
+    }
 }
@@ -18,6 +18,7 @@ use rustc_hash::FxBuildHasher;
 use shared::WorldShared;
 use tracing::error;
 use valence_generated::block::BlockState;
+use valence_nbt::Compound;
 use valence_server::layer::chunk::Chunk;
 
 use crate::{
@@ -28,8 +29,10 @@ use crate::{
 
 pub mod chunk;
 
+pub mod light;
 mod loader;
 mod manager;
+pub mod raycast;
 
 pub mod frame;
 mod region;
@@ -49,6 +52,8 @@ pub struct EntityAndSequence {
 pub enum TrySetBlockDeltaError {
     OutOfBounds,
     ChunkNotLoaded,
+    /// The block has no block entity, so none can be set for it.
+    NoBlockEntity,
 }
 
 /// Accessor of blocks.
@@ -190,6 +195,41 @@ impl Blocks {
         self.chunk_cache.get_mut(&chunk_position)
     }
 
+    /// The number of chunks currently loaded.
+    #[must_use]
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.chunk_cache.len()
+    }
+
+    /// The position of a random loaded chunk, or `None` if no chunk is loaded.
+    #[must_use]
+    pub fn random_loaded_chunk(&self) -> Option<IVec2> {
+        if self.chunk_cache.is_empty() {
+            return None;
+        }
+
+        let idx = fastrand::usize(..self.chunk_cache.len());
+        let (&position, _) = self.chunk_cache.get_index(idx)?;
+
+        Some(position)
+    }
+
+    /// The y coordinate of the highest block at `x`, `z` that is not air. Returns `None` if the
+    /// chunk is not loaded or there is only air.
+    #[must_use]
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        const START_Y: i32 = -64;
+
+        let end_y = START_Y + i32::try_from(CHUNK_HEIGHT_SPAN).unwrap();
+
+        self.get_loaded_chunk(IVec2::new(x, z) >> 4)?;
+
+        (START_Y..end_y).rev().find(|&y| {
+            self.get_block(IVec3::new(x, y, z))
+                .is_some_and(|block| !block.is_air())
+        })
+    }
+
     /// Returns all loaded blocks within the range from `start` to `end` (inclusive).
     #[expect(clippy::excessive_nesting)]
     pub fn get_blocks<F, R>(&self, start: IVec3, end: IVec3, mut f: F) -> R
@@ -339,9 +379,100 @@ impl Blocks {
             self.should_update.insert(u32::try_from(chunk_idx).unwrap());
         }
 
+        if light::affects_light(old_state, state) {
+            chunk.light_queue.push(position);
+        }
+
         Ok(old_state)
     }
 
+    /// Relights around the blocks set since the last call that affect light, marking every column
+    /// whose light changed for an update.
+    pub fn update_light(&mut self) {
+        let chunk_cache = &mut self.chunk_cache;
+
+        let queued = self
+            .should_update
+            .iter()
+            .flat_map(|idx| {
+                let (_, column) = chunk_cache.get_index_mut(idx as usize).unwrap();
+                std::mem::take(&mut column.light_queue)
+            })
+            .collect::<Vec<_>>();
+
+        if queued.is_empty() {
+            return;
+        }
+
+        self.should_update |= light::update(chunk_cache, &queued);
+    }
+
+    /// The block entity at `position`, such as the items in a chest or the text of a sign. `None`
+    /// if there is none or the chunk is not loaded.
+    #[must_use]
+    pub fn get_block_entity(&self, position: IVec3) -> Option<&Compound> {
+        const START_Y: i32 = -64;
+
+        let chunk_pos: IVec2 = IVec2::new(position.x, position.z) >> 4;
+        let chunk_start_block: IVec2 = chunk_pos << 4;
+
+        let chunk = self.get_loaded_chunk(chunk_pos)?;
+
+        let x = u32::try_from(position.x - chunk_start_block[0]).unwrap();
+        let y = u32::try_from(position.y - START_Y).ok()?;
+        let z = u32::try_from(position.z - chunk_start_block[1]).unwrap();
+
+        if y >= CHUNK_HEIGHT_SPAN {
+            return None;
+        }
+
+        chunk.data.block_entity(x, y, z)
+    }
+
+    /// Sets the block entity at `position`, returning the one it replaces. The block there must
+    /// have a block entity, which is removed once the block becomes a different kind of block.
+    /// Players who have the chunk are sent the new block entity.
+    pub fn set_block_entity(
+        &mut self,
+        position: IVec3,
+        data: Compound,
+    ) -> Result<Option<Compound>, TrySetBlockDeltaError> {
+        const START_Y: i32 = -64;
+
+        let chunk_pos: IVec2 = IVec2::new(position.x, position.z) >> 4;
+        let chunk_start_block: IVec2 = chunk_pos << 4;
+
+        let Ok(y) = u32::try_from(position.y - START_Y) else {
+            return Err(TrySetBlockDeltaError::OutOfBounds);
+        };
+
+        if y >= CHUNK_HEIGHT_SPAN {
+            return Err(TrySetBlockDeltaError::OutOfBounds);
+        }
+
+        let Some((chunk_idx, _, chunk)) = self.chunk_cache.get_full_mut(&chunk_pos) else {
+            return Err(TrySetBlockDeltaError::ChunkNotLoaded);
+        };
+
+        let x = u32::try_from(position.x - chunk_start_block[0]).unwrap();
+        let z = u32::try_from(position.z - chunk_start_block[1]).unwrap();
+
+        let block = chunk.data.block_state(x, y, z);
+        if block.to_kind().block_entity_kind().is_none() {
+            return Err(TrySetBlockDeltaError::NoBlockEntity);
+        }
+
+        let before = chunk.data.set_block_entity(x, y, z, Some(data));
+
+        let idx = x + z * 16 + y * 16 * 16;
+        chunk.block_entities_changed.insert(idx);
+        chunk.block_entities_changed_since_last_tick.insert(idx);
+
+        self.should_update.insert(u32::try_from(chunk_idx).unwrap());
+
+        Ok(before)
+    }
+
     // todo: allow modifying the chunk. we will need to implement resending
     // So,
     // for instance, if a player modifies a chunk, we're going to need to rebroadcast it to all the players in that region.
@@ -0,0 +1,474 @@
+//! Finding the block or entity a ray hits first, such as what a player is looking at.
+//!
+//! Blocks are hit by their collision shapes, so a ray passes over the bottom half of a slab and
+//! through blocks without one, such as flowers. Fluids have no collision shape and are only hit
+//! when asked for, at the height of their surface.
+
+use bvh_region::aabb::Aabb;
+use flecs_ecs::core::Entity;
+use glam::{IVec2, IVec3, Vec3};
+use valence_generated::block::{BlockState, PropName, PropValue};
+use valence_protocol::Direction;
+
+use crate::{
+    CHUNK_HEIGHT_SPAN,
+    simulation::blocks::{Blocks, chunk::START_Y},
+};
+
+/// How far an entity's hitbox may reach out of the chunk it is in, in blocks.
+const HITBOX_MARGIN: f32 = 2.0;
+
+/// The first block a ray hits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BlockHit {
+    pub position: IVec3,
+    pub state: BlockState,
+    /// The face of the block the ray enters through.
+    pub face: Direction,
+    /// How far from its origin the ray hits the block, in blocks.
+    pub distance: f32,
+}
+
+/// The first entity a ray hits.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EntityHit {
+    pub entity: Entity,
+    /// How far from its origin the ray hits the hitbox of the entity, in blocks.
+    pub distance: f32,
+}
+
+/// The face a ray going along `axis` in the direction of `d` enters a box through.
+const fn entry_face(axis: usize, d: f32) -> Direction {
+    match (axis, d > 0.0) {
+        (0, true) => Direction::West,
+        (0, false) => Direction::East,
+        (1, true) => Direction::Down,
+        (1, false) => Direction::Up,
+        (_, true) => Direction::North,
+        (_, false) => Direction::South,
+    }
+}
+
+/// How far along the ray from `origin` in the normalized `direction` it enters `aabb`, and through
+/// which face. `None` if it misses the box or starts inside of it.
+fn enter(origin: Vec3, direction: Vec3, aabb: &Aabb) -> Option<(f32, Direction)> {
+    let mut near = f32::NEG_INFINITY;
+    let mut far = f32::INFINITY;
+    let mut face = Direction::Up;
+
+    for axis in 0..3 {
+        let (min, max) = (aabb.min[axis], aabb.max[axis]);
+        let (o, d) = (origin[axis], direction[axis]);
+
+        if d == 0.0 {
+            if o < min || o > max {
+                return None;
+            }
+            continue;
+        }
+
+        let (to_min, to_max) = ((min - o) / d, (max - o) / d);
+        let (entry, exit) = if d > 0.0 {
+            (to_min, to_max)
+        } else {
+            (to_max, to_min)
+        };
+
+        if entry > near {
+            near = entry;
+            face = entry_face(axis, d);
+        }
+
+        far = far.min(exit);
+    }
+
+    (near >= 0.0 && near <= far).then_some((near, face))
+}
+
+/// The surface of a fluid within its block. The fluid above is not taken into account, so a
+/// fluid that is covered by more of itself is still a bit lower than the block.
+fn fluid_shape(state: BlockState) -> Option<Aabb> {
+    if !state.is_liquid() {
+        return None;
+    }
+
+    let level = state
+        .get(PropName::Level)
+        .and_then(PropValue::to_u16)
+        .unwrap_or(0);
+
+    // levels from 8 on are falling, which is as high as a source
+    let amount = if level >= 8 { 8 } else { 8 - level };
+
+    Some(Aabb::new(
+        Vec3::ZERO,
+        Vec3::new(1.0, f32::from(amount) / 9.0, 1.0),
+    ))
+}
+
+/// The boxes a ray can hit in a block, relative to the block.
+fn shapes(state: BlockState, include_fluids: bool) -> impl Iterator<Item = Aabb> {
+    let fluid = include_fluids.then(|| fluid_shape(state)).flatten();
+
+    state
+        .collision_shapes()
+        .map(|shape| Aabb::new(shape.min().as_vec3(), shape.max().as_vec3()))
+        .chain(fluid)
+}
+
+/// The first block hit by the ray from `origin` in `direction` within `max_distance` blocks, with
+/// the blocks looked up by `get_block`. The ray ends at the first block `get_block` has none for,
+/// such as one in a chunk that is not loaded.
+pub fn raycast_block_with(
+    get_block: impl Fn(IVec3) -> Option<BlockState>,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    include_fluids: bool,
+) -> Option<BlockHit> {
+    let direction = direction.try_normalize()?;
+
+    let mut block = origin.floor().as_ivec3();
+    let offset = origin - block.as_vec3();
+
+    let mut step = [0; 3];
+    // how far along the ray the next block boundary is crossed on each axis
+    let mut next = [f32::INFINITY; 3];
+    // how far along the ray a whole block is crossed on each axis
+    let mut across = [f32::INFINITY; 3];
+
+    for axis in 0..3 {
+        let d = direction[axis];
+
+        if d > 0.0 {
+            step[axis] = 1;
+            next[axis] = (1.0 - offset[axis]) / d;
+            across[axis] = 1.0 / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            next[axis] = offset[axis] / -d;
+            across[axis] = 1.0 / -d;
+        }
+    }
+
+    loop {
+        let state = get_block(block)?;
+        let corner = block.as_vec3();
+
+        let hit = shapes(state, include_fluids)
+            .filter_map(|shape| enter(origin, direction, &shape.move_by(corner)))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        if let Some((distance, face)) = hit
+            && distance <= max_distance
+        {
+            return Some(BlockHit {
+                position: block,
+                state,
+                face,
+                distance,
+            });
+        }
+
+        let axis = (0..3)
+            .min_by(|&a, &b| next[a].total_cmp(&next[b]))
+            .unwrap_or_default();
+
+        if next[axis] > max_distance {
+            return None;
+        }
+
+        block[axis] += step[axis];
+        next[axis] += across[axis];
+    }
+}
+
+/// The first of `entities` whose hitbox is hit by the ray from `origin` in `direction` within
+/// `max_distance` blocks. Hitboxes the ray starts in, such as the one of whoever is looking, are
+/// not hit.
+pub fn raycast_entities(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    entities: impl IntoIterator<Item = (Entity, Aabb)>,
+) -> Option<EntityHit> {
+    let direction = direction.try_normalize()?;
+
+    entities
+        .into_iter()
+        .filter_map(|(entity, hitbox)| {
+            let (distance, _) = enter(origin, direction, &hitbox)?;
+            (distance <= max_distance).then_some(EntityHit { entity, distance })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+/// The chunks the entities that the ray from `origin` in `direction` can hit within
+/// `max_distance` blocks are in, for picking what to pass to [`raycast_entities`].
+pub fn ray_chunks(origin: Vec3, direction: Vec3, max_distance: f32) -> impl Iterator<Item = IVec2> {
+    let end = origin + direction.normalize_or_zero() * max_distance;
+
+    let min = ((origin.min(end) - HITBOX_MARGIN) / 16.0)
+        .floor()
+        .as_ivec3();
+    let max = ((origin.max(end) + HITBOX_MARGIN) / 16.0)
+        .floor()
+        .as_ivec3();
+
+    (min.x..=max.x).flat_map(move |x| (min.z..=max.z).map(move |z| IVec2::new(x, z)))
+}
+
+impl Blocks {
+    /// The first block hit by the ray from `origin` in `direction` within `max_distance` blocks.
+    /// Fluids are only hit if `include_fluids` is set. The ray ends at chunks that are not loaded.
+    #[must_use]
+    pub fn raycast_block(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        include_fluids: bool,
+    ) -> Option<BlockHit> {
+        let top = i32::from(START_Y) + i32::try_from(CHUNK_HEIGHT_SPAN).unwrap();
+
+        let get_block = |position: IVec3| {
+            if position.y >= top {
+                return Some(BlockState::AIR);
+            }
+
+            self.get_block(position)
+        };
+
+        raycast_block_with(get_block, origin, direction, max_distance, include_fluids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bvh_region::aabb::Aabb;
+    use flecs_ecs::core::Entity;
+    use glam::{IVec2, IVec3, Vec3};
+    use valence_generated::block::BlockState;
+    use valence_protocol::Direction;
+
+    use super::{BlockHit, ray_chunks, raycast_block_with, raycast_entities};
+
+    /// A world of the given blocks and air everywhere else. Chunks from `x = 16` on are not loaded.
+    struct TestWorld {
+        blocks: HashMap<IVec3, BlockState>,
+    }
+
+    impl TestWorld {
+        fn new(blocks: impl IntoIterator<Item = ([i32; 3], BlockState)>) -> Self {
+            let blocks = blocks
+                .into_iter()
+                .map(|(position, state)| (IVec3::from_array(position), state))
+                .collect();
+
+            Self { blocks }
+        }
+
+        fn get(&self, position: IVec3) -> Option<BlockState> {
+            if position.x >= 16 {
+                return None;
+            }
+
+            let state = self.blocks.get(&position).copied();
+            Some(state.unwrap_or(BlockState::AIR))
+        }
+
+        fn raycast(&self, origin: Vec3, direction: Vec3, fluids: bool) -> Option<BlockHit> {
+            raycast_block_with(
+                |position| self.get(position),
+                origin,
+                direction,
+                5.0,
+                fluids,
+            )
+        }
+    }
+
+    #[track_caller]
+    fn assert_hit(hit: Option<BlockHit>, position: [i32; 3], face: Direction, distance: f32) {
+        let hit = hit.expect("the ray should hit a block");
+
+        assert_eq!(hit.position, IVec3::from_array(position));
+        assert_eq!(hit.face, face);
+        assert!(
+            (hit.distance - distance).abs() < 1e-5,
+            "hit at {} instead of {distance}",
+            hit.distance
+        );
+    }
+
+    #[test]
+    fn rays_hit_the_face_they_enter_through() {
+        let world = TestWorld::new([
+            ([3, 64, 0], BlockState::STONE),
+            ([-3, 64, 0], BlockState::STONE),
+            ([0, 64, -2], BlockState::STONE),
+            ([0, 62, 0], BlockState::STONE),
+        ]);
+        let origin = Vec3::new(0.5, 64.5, 0.5);
+
+        assert_hit(
+            world.raycast(origin, Vec3::X, false),
+            [3, 64, 0],
+            Direction::West,
+            2.5,
+        );
+        assert_hit(
+            world.raycast(origin, Vec3::NEG_X, false),
+            [-3, 64, 0],
+            Direction::East,
+            2.5,
+        );
+        assert_hit(
+            world.raycast(origin, Vec3::NEG_Z, false),
+            [0, 64, -2],
+            Direction::South,
+            1.5,
+        );
+        assert_hit(
+            world.raycast(origin, Vec3::NEG_Y, false),
+            [0, 62, 0],
+            Direction::Up,
+            1.5,
+        );
+        assert_eq!(world.raycast(origin, Vec3::Z, false), None);
+        assert_eq!(world.raycast(origin, Vec3::Y, false), None);
+    }
+
+    #[test]
+    fn diagonal_rays_hit_the_first_block_on_their_way() {
+        let floor = (0..4).map(|x| ([x, 64, 0], BlockState::STONE));
+        let world = TestWorld::new(floor.chain([([3, 65, 0], BlockState::STONE)]));
+
+        // the ray reaches the top of the floor at x = 1.5
+        let direction = Vec3::new(1.0, -1.0, 0.0);
+        let hit = world.raycast(Vec3::new(0.5, 66.0, 0.5), direction, false);
+        assert_hit(hit, [1, 64, 0], Direction::Up, 2.0_f32.sqrt());
+
+        // a flatter one reaches the block sticking out of the floor before the floor itself
+        let hit = world.raycast(Vec3::new(1.0, 66.0, 0.5), Vec3::new(4.0, -1.0, 0.0), false);
+        assert_hit(
+            hit,
+            [3, 65, 0],
+            Direction::West,
+            Vec3::new(2.0, -0.5, 0.0).length(),
+        );
+    }
+
+    #[test]
+    fn rays_go_over_slabs_and_hit_stairs_by_their_shape() {
+        let world = TestWorld::new([
+            ([1, 64, 0], BlockState::STONE_SLAB),
+            ([3, 64, 0], BlockState::STONE),
+            // facing north, so the upper step is at the north, low z side
+            ([0, 64, 2], BlockState::OAK_STAIRS),
+        ]);
+
+        // a bottom slab is half a block high
+        let hit = world.raycast(Vec3::new(1.5, 66.5, 0.5), Vec3::NEG_Y, false);
+        assert_hit(hit, [1, 64, 0], Direction::Up, 2.0);
+
+        let hit = world.raycast(Vec3::new(0.5, 64.75, 0.5), Vec3::X, false);
+        assert_hit(hit, [3, 64, 0], Direction::West, 2.5);
+
+        let hit = world.raycast(Vec3::new(0.5, 66.5, 2.25), Vec3::NEG_Y, false);
+        assert_hit(hit, [0, 64, 2], Direction::Up, 1.5);
+
+        let hit = world.raycast(Vec3::new(0.5, 66.5, 2.75), Vec3::NEG_Y, false);
+        assert_hit(hit, [0, 64, 2], Direction::Up, 2.0);
+
+        // the upper step is entered through its south side from above the lower one
+        let hit = world.raycast(Vec3::new(0.5, 64.75, 4.5), Vec3::NEG_Z, false);
+        assert_hit(hit, [0, 64, 2], Direction::South, 2.0);
+    }
+
+    #[test]
+    fn fluids_are_only_hit_when_asked_for() {
+        let world = TestWorld::new([
+            ([0, 64, 0], BlockState::WATER),
+            ([0, 63, 0], BlockState::STONE),
+        ]);
+        let origin = Vec3::new(0.5, 66.5, 0.5);
+
+        assert_hit(
+            world.raycast(origin, Vec3::NEG_Y, false),
+            [0, 63, 0],
+            Direction::Up,
+            2.5,
+        );
+
+        // a source is 8/9 of a block high
+        let surface = 64.0 + 8.0 / 9.0;
+        let hit = world.raycast(origin, Vec3::NEG_Y, true);
+        assert_hit(hit, [0, 64, 0], Direction::Up, 66.5 - surface);
+    }
+
+    #[test]
+    fn rays_end_at_their_distance_and_at_unloaded_chunks() {
+        let world = TestWorld::new([
+            ([5, 64, 0], BlockState::STONE),
+            ([17, 64, 0], BlockState::STONE),
+        ]);
+
+        // the face is 4.5 blocks away
+        let hit = world.raycast(Vec3::new(0.5, 64.5, 0.5), Vec3::X, false);
+        assert_hit(hit, [5, 64, 0], Direction::West, 4.5);
+        assert_eq!(
+            world.raycast(Vec3::new(-0.5, 64.5, 0.5), Vec3::X, false),
+            None
+        );
+
+        // nothing is known about the blocks past x = 16
+        assert_eq!(
+            world.raycast(Vec3::new(13.5, 64.5, 0.5), Vec3::X, false),
+            None
+        );
+
+        assert_eq!(
+            world.raycast(Vec3::new(0.5, 64.5, 0.5), Vec3::ZERO, false),
+            None
+        );
+    }
+
+    #[test]
+    fn rays_hit_the_nearest_entity_they_do_not_start_in() {
+        let [looking, near, far] = [1, 2, 3].map(Entity::new);
+        let hitbox =
+            |x: f32| Aabb::new(Vec3::new(x - 0.3, 64.0, 0.2), Vec3::new(x + 0.3, 65.8, 0.8));
+
+        let entities = [
+            (far, hitbox(4.5)),
+            (looking, hitbox(0.5)),
+            (near, hitbox(2.5)),
+        ];
+        let eyes = Vec3::new(0.5, 65.62, 0.5);
+
+        let hit = raycast_entities(eyes, Vec3::X, 3.0, entities).unwrap();
+        assert_eq!(hit.entity, near);
+        assert!((hit.distance - 1.7).abs() < 1e-5);
+
+        assert_eq!(raycast_entities(eyes, Vec3::X, 1.5, entities), None);
+        assert_eq!(raycast_entities(eyes, Vec3::NEG_X, 3.0, entities), None);
+
+        // over their heads
+        assert_eq!(
+            raycast_entities(eyes + Vec3::Y, Vec3::X, 5.0, entities),
+            None
+        );
+    }
+
+    #[test]
+    fn ray_chunks_cover_hitboxes_reaching_over_chunk_borders() {
+        let chunks = ray_chunks(Vec3::new(8.0, 64.0, 15.0), Vec3::X, 4.0).collect::<Vec<_>>();
+        assert_eq!(chunks, [IVec2::new(0, 0), IVec2::new(0, 1)]);
+
+        let chunks = ray_chunks(Vec3::new(1.0, 64.0, 8.0), Vec3::NEG_X, 4.0).collect::<Vec<_>>();
+        assert_eq!(chunks, [IVec2::new(-1, 0), IVec2::new(0, 0)]);
+    }
+}
@@ -0,0 +1,460 @@
+//! Boss bars at the top of the screen, such as a countdown everyone sees or a bar that only shows
+//! one player their own progress.
+//!
+//! Each bar is an entity with a [`BossBar`], created with [`spawn_boss_bar`]. The bar can be
+//! changed through the component every tick: players who start seeing it are sent the whole bar,
+//! and players who already see it are only sent what changed. The bar is removed for its viewers
+//! when its entity is deleted, and [`spawn_owned_boss_bar`] deletes it along with its owner.
+//!
+//! ```ignore
+//! let bar = spawn_boss_bar(world, BossBar::new("Overtime").color(BossBarColor::Red));
+//!
+//! bar.entity_view(world).get::<&mut BossBar>(|bar| {
+//!     bar.show_all();
+//!     bar.set_progress(0.5);
+//! });
+//! ```
+
+use std::borrow::Cow;
+
+use flecs_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::packets::{
+    play,
+    play::boss_bar_s2c::{BossBarAction, BossBarColor, BossBarDivision, BossBarFlags},
+};
+use valence_text::{IntoText, Text};
+
+use crate::{
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::PacketState,
+    system_registry::BOSS_BARS,
+};
+
+/// Who a [`BossBar`] is shown to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Audience {
+    /// Everyone who is playing, including players who join later.
+    Everyone,
+    Players(Vec<NetworkStreamRef>),
+}
+
+/// What a [`BossBar`] looks like.
+#[derive(Clone, Debug)]
+struct Look {
+    title: Text,
+    progress: f32,
+    color: BossBarColor,
+    division: BossBarDivision,
+    flags: BossBarFlags,
+}
+
+/// A boss bar, which is hidden until it is shown with [`BossBar::show_all`] or
+/// [`BossBar::show_to`].
+#[derive(Component, Clone, Debug)]
+pub struct BossBar {
+    id: uuid::Uuid,
+    look: Look,
+    audience: Audience,
+    /// The players who were sent the bar.
+    viewers: Vec<NetworkStreamRef>,
+    /// What the viewers were last sent.
+    sent: Look,
+}
+
+/// What a [`BossBar`] sends in a tick.
+#[derive(Debug, Default)]
+struct BossBarChanges {
+    /// Players who are sent the whole bar.
+    added: Vec<NetworkStreamRef>,
+    /// Players the bar is removed for.
+    removed: Vec<NetworkStreamRef>,
+    /// What changed, for the players who already saw the bar.
+    updates: Vec<BossBarAction<'static>>,
+}
+
+impl BossBar {
+    /// A full, pink bar titled `title`.
+    #[must_use]
+    pub fn new<'a>(title: impl IntoText<'a>) -> Self {
+        let look = Look {
+            title: title.into_text(),
+            progress: 1.0,
+            color: BossBarColor::Pink,
+            division: BossBarDivision::NoDivision,
+            flags: BossBarFlags::default(),
+        };
+
+        Self {
+            id: uuid::Uuid::from_u128(fastrand::u128(..)),
+            sent: look.clone(),
+            look,
+            audience: Audience::Players(Vec::new()),
+            viewers: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn color(mut self, color: BossBarColor) -> Self {
+        self.look.color = color;
+        self
+    }
+
+    /// The notches the bar is divided into.
+    #[must_use]
+    pub const fn division(mut self, division: BossBarDivision) -> Self {
+        self.look.division = division;
+        self
+    }
+
+    /// Whether the bar darkens the sky, plays the boss music or adds fog.
+    #[must_use]
+    pub const fn flags(mut self, flags: BossBarFlags) -> Self {
+        self.look.flags = flags;
+        self
+    }
+
+    #[must_use]
+    pub fn progress(mut self, progress: f32) -> Self {
+        self.set_progress(progress);
+        self
+    }
+
+    /// Shows the bar to everyone who is playing, including players who join later.
+    pub fn show_all(&mut self) {
+        self.audience = Audience::Everyone;
+    }
+
+    /// Shows the bar to `stream` as well. A bar already shown to everyone stays that way.
+    pub fn show_to(&mut self, stream: NetworkStreamRef) {
+        if let Audience::Players(players) = &mut self.audience
+            && !players.contains(&stream)
+        {
+            players.push(stream);
+        }
+    }
+
+    /// Stops showing the bar to `stream`. This does nothing for a bar shown to everyone.
+    pub fn hide_from(&mut self, stream: NetworkStreamRef) {
+        if let Audience::Players(players) = &mut self.audience {
+            players.retain(|&player| player != stream);
+        }
+    }
+
+    /// Stops showing the bar to anyone.
+    pub fn hide(&mut self) {
+        self.audience = Audience::Players(Vec::new());
+    }
+
+    #[must_use]
+    pub fn is_shown_to_everyone(&self) -> bool {
+        self.audience == Audience::Everyone
+    }
+
+    #[must_use]
+    pub const fn title(&self) -> &Text {
+        &self.look.title
+    }
+
+    pub fn set_title<'a>(&mut self, title: impl IntoText<'a>) {
+        self.look.title = title.into_text();
+    }
+
+    /// From `0.0` (empty) to `1.0` (full).
+    #[must_use]
+    pub const fn get_progress(&self) -> f32 {
+        self.look.progress
+    }
+
+    /// Sets how full the bar is, from `0.0` (empty) to `1.0` (full).
+    pub fn set_progress(&mut self, progress: f32) {
+        self.look.progress = progress.clamp(0.0, 1.0);
+    }
+
+    pub const fn set_color(&mut self, color: BossBarColor) {
+        self.look.color = color;
+    }
+
+    pub const fn set_division(&mut self, division: BossBarDivision) {
+        self.look.division = division;
+    }
+
+    pub const fn set_flags(&mut self, flags: BossBarFlags) {
+        self.look.flags = flags;
+    }
+
+    fn add_packet(&self) -> play::BossBarS2c<'_> {
+        play::BossBarS2c {
+            id: self.id,
+            action: BossBarAction::Add {
+                title: Cow::Borrowed(&self.look.title),
+                health: self.look.progress,
+                color: self.look.color,
+                division: self.look.division,
+                flags: self.look.flags,
+            },
+        }
+    }
+
+    const fn remove_packet(&self) -> play::BossBarS2c<'static> {
+        play::BossBarS2c {
+            id: self.id,
+            action: BossBarAction::Remove,
+        }
+    }
+
+    /// The changes to send since this was last called, given the players who are `playing`.
+    /// Players who are no longer playing are dropped without being told.
+    fn take_changes(&mut self, playing: &[NetworkStreamRef]) -> BossBarChanges {
+        let shown_to = match &mut self.audience {
+            Audience::Everyone => playing.to_vec(),
+            Audience::Players(players) => {
+                players.retain(|stream| playing.contains(stream));
+                players.clone()
+            }
+        };
+
+        let mut changes = BossBarChanges::default();
+
+        for &stream in &shown_to {
+            if !self.viewers.contains(&stream) {
+                changes.added.push(stream);
+            }
+        }
+
+        for &stream in &self.viewers {
+            if !shown_to.contains(&stream) && playing.contains(&stream) {
+                changes.removed.push(stream);
+            }
+        }
+
+        let (sent, look) = (&self.sent, &self.look);
+
+        if sent.title != look.title {
+            let title = Cow::Owned(look.title.clone());
+            changes.updates.push(BossBarAction::UpdateTitle(title));
+        }
+
+        if sent.progress.to_bits() != look.progress.to_bits() {
+            changes
+                .updates
+                .push(BossBarAction::UpdateHealth(look.progress));
+        }
+
+        if sent.color != look.color || sent.division != look.division {
+            changes
+                .updates
+                .push(BossBarAction::UpdateStyle(look.color, look.division));
+        }
+
+        if sent.flags != look.flags {
+            changes.updates.push(BossBarAction::UpdateFlags(look.flags));
+        }
+
+        self.sent.clone_from(&self.look);
+        self.viewers = shown_to;
+
+        changes
+    }
+}
+
+/// Spawns `bar`. It is removed for everyone once the returned entity is deleted.
+pub fn spawn_boss_bar(world: &World, bar: BossBar) -> Entity {
+    world.entity().set(bar).id()
+}
+
+/// Like [`spawn_boss_bar`], but the bar is also deleted along with `owner`, such as the player it
+/// is shown to.
+pub fn spawn_owned_boss_bar(world: &World, owner: Entity, bar: BossBar) -> Entity {
+    world.entity().set(bar).child_of_id(owner).id()
+}
+
+#[derive(Component)]
+pub struct BossBarModule;
+
+impl Module for BossBarModule {
+    fn module(world: &World) {
+        world.component::<BossBar>();
+
+        let players = world
+            .query::<&NetworkStreamRef>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!("send_boss_bars", world, &Compose($), &mut BossBar)
+            .kind::<flecs::pipeline::OnStore>()
+            .each_entity(move |entity, (compose, bar)| {
+                let world = entity.world();
+
+                let mut playing = Vec::new();
+                players.each(|&stream| playing.push(stream));
+
+                let changes = bar.take_changes(&playing);
+
+                for &stream in &changes.added {
+                    if let Err(e) = compose.unicast(&bar.add_packet(), stream, BOSS_BARS, &world) {
+                        warn!("failed to send boss bar: {e}");
+                    }
+                }
+
+                let remove = bar.remove_packet();
+
+                for &stream in &changes.removed {
+                    if let Err(e) = compose.unicast(&remove, stream, BOSS_BARS, &world) {
+                        warn!("failed to remove boss bar: {e}");
+                    }
+                }
+
+                if changes.updates.is_empty() {
+                    return;
+                }
+
+                // everyone a broadcast reaches already has the bar, unless someone just got it
+                if bar.is_shown_to_everyone() && changes.added.is_empty() {
+                    for action in changes.updates {
+                        let pkt = play::BossBarS2c { id: bar.id, action };
+
+                        if let Err(e) = compose.broadcast(&pkt, BOSS_BARS).send(&world) {
+                            warn!("failed to update boss bar: {e}");
+                        }
+                    }
+
+                    return;
+                }
+
+                for &stream in &bar.viewers {
+                    // players who just got the bar were sent it as it is now
+                    if changes.added.contains(&stream) {
+                        continue;
+                    }
+
+                    let mut bundle = DataBundle::new(compose);
+
+                    for action in &changes.updates {
+                        let pkt = play::BossBarS2c {
+                            id: bar.id,
+                            action: action.clone(),
+                        };
+
+                        if let Err(e) = bundle.add_packet(&pkt, &world) {
+                            warn!("failed to encode boss bar update: {e}");
+                        }
+                    }
+
+                    if let Err(e) = bundle.send(&world, stream, BOSS_BARS) {
+                        warn!("failed to update boss bar: {e}");
+                    }
+                }
+            });
+
+        observer!(world, flecs::OnRemove, &BossBar, &Compose($)).each_entity(
+            |entity, (bar, compose)| {
+                let world = entity.world();
+                let remove = bar.remove_packet();
+
+                for &stream in &bar.viewers {
+                    if let Err(e) = compose.unicast(&remove, stream, BOSS_BARS, &world) {
+                        warn!("failed to remove boss bar: {e}");
+                    }
+                }
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::play::boss_bar_s2c::{BossBarAction, BossBarColor};
+
+    use super::BossBar;
+    use crate::net::NetworkStreamRef;
+
+    #[test]
+    fn new_viewers_get_the_whole_bar_and_others_only_changes() {
+        let alice = NetworkStreamRef::new(1);
+        let bob = NetworkStreamRef::new(2);
+        let playing = [alice, bob];
+
+        let mut bar = BossBar::new("Infection").progress(0.0);
+
+        // a bar is hidden until it is shown
+        let changes = bar.take_changes(&playing);
+        assert!(changes.added.is_empty());
+        assert!(changes.updates.is_empty());
+
+        bar.show_to(alice);
+        bar.set_progress(0.25);
+
+        let changes = bar.take_changes(&playing);
+        assert_eq!(changes.added, [alice]);
+        assert!(changes.removed.is_empty());
+
+        bar.show_to(bob);
+        bar.set_progress(0.5);
+
+        let changes = bar.take_changes(&playing);
+        assert_eq!(changes.added, [bob]);
+        assert!(
+            matches!(changes.updates[..], [BossBarAction::UpdateHealth(progress)]
+            if (progress - 0.5).abs() < f32::EPSILON)
+        );
+
+        // setting the same look again sends nothing
+        bar.set_progress(0.5);
+        assert!(bar.take_changes(&playing).updates.is_empty());
+
+        bar.set_title("Infected!");
+        bar.set_color(BossBarColor::Green);
+
+        let changes = bar.take_changes(&playing);
+        assert!(changes.added.is_empty());
+        assert!(matches!(changes.updates[..], [
+            BossBarAction::UpdateTitle(_),
+            BossBarAction::UpdateStyle(BossBarColor::Green, _)
+        ]));
+    }
+
+    #[test]
+    fn bars_are_removed_for_hidden_viewers_but_not_for_disconnected_ones() {
+        let alice = NetworkStreamRef::new(1);
+        let bob = NetworkStreamRef::new(2);
+
+        let mut bar = BossBar::new("Grace period");
+        bar.show_to(alice);
+        bar.show_to(bob);
+        bar.take_changes(&[alice, bob]);
+
+        bar.hide_from(alice);
+        let changes = bar.take_changes(&[alice, bob]);
+        assert_eq!(changes.removed, [alice]);
+
+        // bob left, so there is no one to send the removal to
+        let changes = bar.take_changes(&[alice]);
+        assert!(changes.removed.is_empty());
+
+        // and he is not shown the bar again when he rejoins
+        let changes = bar.take_changes(&[alice, bob]);
+        assert!(changes.added.is_empty());
+    }
+
+    #[test]
+    fn bars_shown_to_everyone_include_late_joiners() {
+        let alice = NetworkStreamRef::new(1);
+        let bob = NetworkStreamRef::new(2);
+
+        let mut bar = BossBar::new("Overtime");
+        bar.show_all();
+        bar.show_to(alice);
+        assert!(bar.is_shown_to_everyone());
+
+        let changes = bar.take_changes(&[alice]);
+        assert_eq!(changes.added, [alice]);
+
+        let changes = bar.take_changes(&[alice, bob]);
+        assert_eq!(changes.added, [bob]);
+
+        bar.hide();
+        let changes = bar.take_changes(&[alice, bob]);
+        assert_eq!(changes.removed, [alice, bob]);
+    }
+}
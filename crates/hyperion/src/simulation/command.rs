@@ -1,7 +1,4 @@
-use flecs_ecs::{
-    core::{Entity, EntityViewGet, IdOperations, World},
-    macros::Component,
-};
+use flecs_ecs::prelude::*;
 use tracing::warn;
 pub use valence_protocol::packets::play::command_tree_s2c::Parser;
 use valence_protocol::{
@@ -9,11 +6,68 @@ use valence_protocol::{
     packets::play::command_tree_s2c::{Node, NodeData, Suggestion},
 };
 
+use crate::{
+    net::{Compose, NetworkStreamRef},
+    simulation::PacketState,
+    system_registry::COMMAND_TREE,
+};
+
 #[derive(Component)]
 pub struct Command {
     data: NodeData,
 }
 
+/// A permission level, from [`Permissions::PLAYER`] to [`Permissions::OWNER`], as in vanilla.
+///
+/// On a player, it is what they may do. On a [`Command`] node, it is what a player needs for the
+/// node to be in their command tree. Setting it on a player resends their command tree, so a
+/// change takes effect right away.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Permissions(pub u8);
+
+impl Permissions {
+    /// Changing the world, such as with `/time` or `/weather`.
+    pub const GAME_MASTER: Self = Self(2);
+    /// Managing other players, such as with `/kick` or `/op`.
+    pub const MANAGE_PLAYERS: Self = Self(3);
+    /// Everything.
+    pub const OWNER: Self = Self(4);
+    /// What everyone may do.
+    pub const PLAYER: Self = Self(0);
+
+    /// Whether these permissions include `required`.
+    #[must_use]
+    pub const fn allows(self, required: Self) -> bool {
+        self.0 >= required.0
+    }
+}
+
+/// The terminal the server runs in. Commands typed there run as [`Console::entity`], which has
+/// [`Permissions::OWNER`], during the next tick.
+///
+/// The entity has no connection, so the replies to it are logged instead.
+#[derive(Component)]
+pub struct Console {
+    /// What the commands typed into the terminal run as.
+    pub entity: Entity,
+    commands: kanal::Receiver<String>,
+}
+
+impl Console {
+    /// Creates the entity the commands received from `commands` run as.
+    #[must_use]
+    pub fn new(world: &World, commands: kanal::Receiver<String>) -> Self {
+        let entity = world.entity_named("console").set(Permissions::OWNER).id();
+
+        Self { entity, commands }
+    }
+
+    /// The commands typed since the last call, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = String> + '_ {
+        std::iter::from_fn(|| self.commands.try_recv().ok().flatten())
+    }
+}
+
 pub(crate) static ROOT_COMMAND: once_cell::sync::OnceCell<Entity> =
     once_cell::sync::OnceCell::new();
 
@@ -54,6 +108,15 @@ const MAX_DEPTH: usize = 64;
 pub fn get_command_packet(
     world: &World,
     root: Entity,
+) -> valence_protocol::packets::play::CommandTreeS2c {
+    get_command_packet_for(world, root, Permissions::OWNER)
+}
+
+/// The command tree without the nodes that need more than `permissions`.
+pub fn get_command_packet_for(
+    world: &World,
+    root: Entity,
+    permissions: Permissions,
 ) -> valence_protocol::packets::play::CommandTreeS2c {
     struct StackElement {
         depth: usize,
@@ -88,6 +151,14 @@ pub fn get_command_packet(
         }
 
         world.entity_from_id(entity).each_child(|child| {
+            let required = child
+                .try_get::<&Permissions>(|required| *required)
+                .unwrap_or_default();
+
+            if !permissions.allows(required) {
+                return;
+            }
+
             child.get::<&Command>(|command| {
                 let ptr = commands.len();
 
@@ -115,6 +186,46 @@ pub fn get_command_packet(
         root_index: VarInt(0),
     }
 }
+
+/// Sends `player` the command tree their [`Permissions`] let them see, such as after commands were
+/// added while they were online.
+pub fn resend_command_tree(player: EntityView<'_>, compose: &Compose) -> anyhow::Result<()> {
+    let Some(stream) = player.try_get::<&NetworkStreamRef>(|stream| *stream) else {
+        return Ok(());
+    };
+
+    let Some(&root) = ROOT_COMMAND.get() else {
+        return Ok(());
+    };
+
+    let permissions = player
+        .try_get::<&Permissions>(|permissions| *permissions)
+        .unwrap_or_default();
+
+    let world = player.world();
+    let packet = get_command_packet_for(&world, root, permissions);
+
+    compose.unicast(&packet, stream, COMMAND_TREE, &world)?;
+
+    Ok(())
+}
+
+/// Resends the command tree of players whose [`Permissions`] changed.
+pub(crate) fn resend_on_permissions_change(world: &World) {
+    observer!(world, flecs::OnSet, &Permissions, &Compose($)).each_entity(
+        |player, (_, compose)| {
+            // players who are still joining get their tree with the other join packets
+            if !player.has_enum(PacketState::Play) {
+                return;
+            }
+
+            if let Err(e) = resend_command_tree(player, compose) {
+                warn!("failed to resend the command tree: {e}");
+            }
+        },
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use flecs_ecs::prelude::*;
@@ -224,4 +335,63 @@ mod tests {
 
         assert_eq!(packet.commands.len(), MAX_DEPTH + 1);
     }
+
+    #[test]
+    fn nodes_need_their_permissions() {
+        let world = World::new();
+        world.component::<Command>();
+        world.component::<Permissions>();
+
+        let root = world.entity();
+
+        world
+            .entity()
+            .set(Command::literal("help"))
+            .child_of_id(root);
+
+        world
+            .entity()
+            .set(Command::literal("op"))
+            .set(Permissions::MANAGE_PLAYERS)
+            .child_of_id(root);
+
+        let names = |permissions| {
+            get_command_packet_for(&world, root.id(), permissions)
+                .commands
+                .into_iter()
+                .filter_map(|node| match node.data {
+                    NodeData::Literal { name } => Some(name),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(Permissions::PLAYER), ["help"]);
+        assert_eq!(names(Permissions(2)), ["help"]);
+        assert_eq!(names(Permissions::MANAGE_PLAYERS).len(), 2);
+        assert_eq!(names(Permissions::OWNER).len(), 2);
+    }
+
+    #[test]
+    fn console_commands_run_as_an_owner_in_order() {
+        let world = World::new();
+        world.component::<Permissions>();
+
+        let (commands, rx) = kanal::unbounded();
+        let console = Console::new(&world, rx);
+
+        commands.send("op Alex".to_owned()).unwrap();
+        commands.send("time set day".to_owned()).unwrap();
+
+        assert_eq!(console.drain().collect::<Vec<_>>(), [
+            "op Alex",
+            "time set day"
+        ]);
+        assert_eq!(console.drain().count(), 0);
+
+        let permissions = world
+            .entity_from_id(console.entity)
+            .get::<&Permissions>(|permissions| *permissions);
+        assert_eq!(permissions, Permissions::OWNER);
+    }
 }
@@ -0,0 +1,468 @@
+//! Containers, such as chests, whose items live on an entity of their own.
+//!
+//! Any number of players can have the same container open. Their clicks are queued as
+//! [`event::ContainerClick`]s and applied by `apply_container_clicks`, as the packet handlers of
+//! different players run in parallel. Every slot of the container that changed is then sent to
+//! everyone viewing it.
+//!
+//! Block entities are not read from the world yet, so a chest starts out empty the first time it
+//! is opened. Its items are kept for as long as the server runs, even if the block is broken.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use flecs_ecs::prelude::*;
+use glam::IVec3;
+use hyperion_inventory::{ChestInventory, PlayerInventory};
+use parking_lot::Mutex;
+use tracing::warn;
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::{
+        play,
+        play::{click_slot_c2s::ClickMode, open_screen_s2c::WindowType},
+    },
+    text::IntoText,
+};
+
+use crate::{
+    ingress::PendingRemove,
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::{
+        Position, crafting_table, event,
+        handlers::PacketSwitchQuery,
+        menu::{self, OpenMenu},
+    },
+    storage::{EventQueue, Events},
+    system_registry::CONTAINER,
+};
+
+/// The chest's own slots come first in its window.
+const CHEST_SLOTS: u16 = 27;
+
+/// The container entity of every chest block that has been opened, by position.
+#[derive(Component, Debug, Default)]
+pub struct Chests {
+    // packet handlers run in parallel, so they share the chests through a lock
+    chests: Mutex<HashMap<IVec3, Entity>>,
+}
+
+impl Chests {
+    /// The container of the chest at `position`, spawning an empty one if there is none yet.
+    fn get_or_spawn(&self, world: &World, position: IVec3) -> Entity {
+        *self
+            .chests
+            .lock()
+            .entry(position)
+            .or_insert_with(|| world.entity().set(ChestInventory::default()).id())
+    }
+}
+
+/// The container window a player has open.
+#[derive(Component, Clone, Debug)]
+pub struct OpenInventory {
+    /// The entity with the container's [`ChestInventory`].
+    pub container: Entity,
+    pub window_id: u8,
+    /// The stack held on the cursor.
+    pub carried: ItemStack,
+}
+
+impl OpenInventory {
+    /// Applies a click on window slot `slot_idx`. Only clicks and shift-clicks are supported; the
+    /// window is re-sent after any click anyway, undoing whatever else the client predicted.
+    fn click(
+        &mut self,
+        chest: &mut ChestInventory,
+        inventory: &mut PlayerInventory,
+        slot_idx: i16,
+        button: i8,
+        mode: ClickMode,
+    ) {
+        let player_slot = menu::player_slot(slot_idx, CHEST_SLOTS);
+
+        let chest_slot = u16::try_from(slot_idx)
+            .ok()
+            .filter(|&slot| slot < CHEST_SLOTS);
+
+        match (mode, player_slot, chest_slot) {
+            (ClickMode::Click, Some(slot), _) => {
+                if let Ok(mut stack) = inventory.get_mut(slot) {
+                    crafting_table::click_stack(&mut stack, &mut self.carried, button == 1);
+                }
+            }
+            (ClickMode::Click, None, Some(slot)) => {
+                if let Ok(mut stack) = chest.get_mut(slot) {
+                    crafting_table::click_stack(&mut stack, &mut self.carried, button == 1);
+                }
+            }
+            (ClickMode::ShiftClick, Some(slot), _) => {
+                let Ok(stack) = inventory.get(slot).cloned() else {
+                    return;
+                };
+
+                if stack.is_empty() {
+                    return;
+                }
+
+                let rest = chest.try_add_item_to(stack, 0..CHEST_SLOTS).remaining;
+                inventory
+                    .set(slot, rest.unwrap_or(ItemStack::EMPTY))
+                    .unwrap();
+            }
+            (ClickMode::ShiftClick, None, Some(slot)) => {
+                let Ok(stack) = chest.get(slot).cloned() else {
+                    return;
+                };
+
+                if stack.is_empty() {
+                    return;
+                }
+
+                let rest = inventory.try_add_item(stack).remaining;
+                chest.set(slot, rest.unwrap_or(ItemStack::EMPTY)).unwrap();
+            }
+            _ => {}
+        }
+    }
+
+    /// Gives the cursor back to the player. Returns whatever does not fit.
+    fn close(self, inventory: &mut PlayerInventory) -> Option<ItemStack> {
+        if self.carried.is_empty() {
+            return None;
+        }
+
+        inventory.try_add_item(self.carried).remaining
+    }
+
+    /// The whole window and the cursor, undoing anything the client predicted.
+    fn add_contents(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        chest: &ChestInventory,
+        inventory: &PlayerInventory,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        bundle.add_packet(
+            &menu::window_contents(self.window_id, chest.slots(), inventory),
+            world,
+        )?;
+
+        let cursor = play::ScreenHandlerSlotUpdateS2c {
+            window_id: -1,
+            state_id: VarInt::default(),
+            slot_idx: -1,
+            slot_data: Cow::Borrowed(&self.carried),
+        };
+
+        bundle.add_packet(&cursor, world)
+    }
+}
+
+/// Opens the chest at `position` for the player who right-clicked it.
+pub fn open_chest(query: &mut PacketSwitchQuery<'_>, position: IVec3) -> anyhow::Result<()> {
+    let container = query
+        .world
+        .get::<&Chests>(|chests| chests.get_or_spawn(query.world, position));
+
+    open_container(query, container, "Chest")
+}
+
+/// Opens the [`ChestInventory`] of `container` for a player, replacing any container they already
+/// have open.
+pub fn open_container(
+    query: &mut PacketSwitchQuery<'_>,
+    container: Entity,
+    title: &str,
+) -> anyhow::Result<()> {
+    // a stack on the cursor of a window that never closed still belongs to the player
+    close_container(query);
+
+    // a container spawned this tick has no inventory until the end of the tick, but is empty
+    let chest = container
+        .entity_view(query.world)
+        .try_get::<&ChestInventory>(Clone::clone)
+        .unwrap_or_default();
+
+    let open = OpenInventory {
+        container,
+        window_id: menu::next_window_id(),
+        carried: ItemStack::EMPTY,
+    };
+
+    let mut bundle = DataBundle::new(query.compose);
+
+    bundle.add_packet(
+        &play::OpenScreenS2c {
+            window_id: VarInt(i32::from(open.window_id)),
+            window_type: WindowType::Generic9x3,
+            window_title: title.into_cow_text(),
+        },
+        query.world,
+    )?;
+
+    open.add_contents(&mut bundle, &chest, query.inventory, query.world)?;
+    bundle.send(query.world, query.io_ref, CONTAINER)?;
+
+    // the container window replaces any menu on the client
+    query.view.remove::<OpenMenu>();
+    query.view.set(open);
+
+    Ok(())
+}
+
+/// Queues a click in an open container window.
+pub fn click_container(query: &PacketSwitchQuery<'_>, pkt: &play::ClickSlotC2s) {
+    let click = event::ContainerClick {
+        player: query.id,
+        window_id: pkt.window_id,
+        slot_idx: pkt.slot_idx,
+        button: pkt.button,
+        mode: pkt.mode,
+    };
+
+    query.events.push(click, query.world);
+}
+
+/// Closes the container window, giving the cursor back to the player. Whatever does not fit is
+/// dropped at their feet.
+pub fn close_container(query: &mut PacketSwitchQuery<'_>) {
+    let Some(open) = query.view.try_get::<&OpenInventory>(Clone::clone) else {
+        return;
+    };
+
+    query.view.remove::<OpenInventory>();
+
+    if let Some(item) = open.close(&mut *query.inventory) {
+        let location = **query.position;
+        query
+            .events
+            .push(event::ItemDropEvent::new(item, location), query.world);
+    }
+}
+
+/// Applies `click` to the container the player has open and re-sends them the window.
+fn apply_click(
+    world: &World,
+    compose: &Compose,
+    click: &event::ContainerClick,
+) -> anyhow::Result<()> {
+    let player = click.player.entity_view(world);
+
+    if !player.is_alive() {
+        return Ok(());
+    }
+
+    let applied = player.try_get::<(&mut OpenInventory, &mut PlayerInventory, &NetworkStreamRef)>(
+        |(open, inventory, &io)| {
+            // the window was closed or replaced since
+            if open.window_id != click.window_id {
+                return Ok(());
+            }
+
+            let container = open.container.entity_view(world);
+
+            if !container.is_alive() {
+                return Ok(());
+            }
+
+            container
+                .try_get::<&mut ChestInventory>(|chest| {
+                    open.click(chest, inventory, click.slot_idx, click.button, click.mode);
+
+                    let mut bundle = DataBundle::new(compose);
+                    open.add_contents(&mut bundle, chest, inventory, world)?;
+                    bundle.send(world, io, CONTAINER)
+                })
+                .unwrap_or(Ok(()))
+        },
+    );
+
+    applied.unwrap_or(Ok(()))
+}
+
+/// Sends the changed slots of a chest to the players viewing it.
+fn sync_viewers(
+    container: EntityView<'_>,
+    changed: &[(u16, ItemStack)],
+    viewers: &Query<(&OpenInventory, &NetworkStreamRef)>,
+    compose: &Compose,
+) {
+    let world = container.world();
+
+    viewers.each(|(open, &io)| {
+        if open.container != container.id() {
+            return;
+        }
+
+        let mut bundle = DataBundle::new(compose);
+
+        for (slot, stack) in changed {
+            let pkt = play::ScreenHandlerSlotUpdateS2c {
+                window_id: i8::try_from(open.window_id).unwrap(),
+                state_id: VarInt::default(),
+                slot_idx: i16::try_from(*slot).unwrap(),
+                slot_data: Cow::Borrowed(stack),
+            };
+
+            if let Err(e) = bundle.add_packet(&pkt, &world) {
+                warn!("failed to sync container slot {slot}: {e}");
+            }
+        }
+
+        if let Err(e) = bundle.send(&world, io, CONTAINER) {
+            warn!("failed to sync container: {e}");
+        }
+    });
+}
+
+#[derive(Component)]
+pub struct ContainerModule;
+
+impl Module for ContainerModule {
+    fn module(world: &World) {
+        world.component::<Chests>();
+        world.component::<ChestInventory>();
+        world.component::<OpenInventory>();
+
+        world.set(Chests::default());
+
+        system!(
+            "apply_container_clicks",
+            world,
+            &Compose($),
+            &mut EventQueue<event::ContainerClick>($),
+        )
+        .kind::<flecs::pipeline::PostUpdate>()
+        .each_iter(|it, _, (compose, queue)| {
+            let world = it.world();
+
+            for click in queue.drain() {
+                if let Err(e) = apply_click(&world, compose, &click) {
+                    warn!("failed to apply container click: {e}");
+                }
+            }
+        });
+
+        let viewers = world.query::<(&OpenInventory, &NetworkStreamRef)>().build();
+
+        system!(
+            "sync_open_containers",
+            world,
+            &Compose($),
+            &mut ChestInventory,
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_entity(move |container, (compose, chest)| {
+            let changed = chest.drain_updates().slots;
+
+            if changed.is_empty() {
+                return;
+            }
+
+            sync_viewers(container, &changed, &viewers, compose);
+        });
+
+        // players are saved once they leave, so the cursor must be back in their inventory by then
+        system!(
+            "close_containers_of_leaving_players",
+            world,
+            &Events($),
+            &Position,
+            &mut PlayerInventory,
+            &OpenInventory,
+        )
+        .kind::<flecs::pipeline::PostLoad>()
+        .with::<&PendingRemove>()
+        .each_entity(|player, (events, position, inventory, open)| {
+            let world = player.world();
+
+            if let Some(item) = open.clone().close(inventory) {
+                let location = **position;
+                events.push(event::ItemDropEvent::new(item, location), &world);
+            }
+
+            player.remove::<OpenInventory>();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::Entity;
+    use hyperion_inventory::{ChestInventory, PlayerInventory};
+    use hyperion_utils::EntityExt;
+    use valence_protocol::{ItemKind, ItemStack, packets::play::click_slot_c2s::ClickMode};
+
+    use super::OpenInventory;
+
+    const LEFT: i8 = 0;
+
+    /// The window slot of the first slot of the main inventory.
+    const MAIN: i16 = 27;
+
+    fn open() -> OpenInventory {
+        OpenInventory {
+            container: Entity::from_minecraft_id(1),
+            window_id: 1,
+            carried: ItemStack::EMPTY,
+        }
+    }
+
+    #[test]
+    fn stacks_are_moved_with_the_cursor() {
+        let mut open = open();
+        let mut chest = ChestInventory::default();
+        let mut inventory = PlayerInventory::default();
+
+        let stone = ItemStack::new(ItemKind::Stone, 16, None);
+        chest.set(4, stone.clone()).unwrap();
+
+        open.click(&mut chest, &mut inventory, 4, LEFT, ClickMode::Click);
+        assert_eq!(open.carried, stone);
+        assert!(chest.get(4).unwrap().is_empty());
+
+        open.click(&mut chest, &mut inventory, MAIN, LEFT, ClickMode::Click);
+        assert!(open.carried.is_empty());
+        assert_eq!(*inventory.get(9).unwrap(), stone);
+    }
+
+    #[test]
+    fn shift_clicks_move_between_the_chest_and_the_inventory() {
+        let mut open = open();
+        let mut chest = ChestInventory::default();
+        let mut inventory = PlayerInventory::default();
+
+        let stone = ItemStack::new(ItemKind::Stone, 16, None);
+        chest
+            .set(3, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+        inventory.set(9, stone).unwrap();
+
+        // tops up the stack in the chest, then starts a new one
+        open.click(
+            &mut chest,
+            &mut inventory,
+            MAIN,
+            LEFT,
+            ClickMode::ShiftClick,
+        );
+        assert!(inventory.get(9).unwrap().is_empty());
+        assert_eq!(chest.get(3).unwrap().count, 64);
+        assert_eq!(chest.get(0).unwrap().count, 12);
+
+        open.click(&mut chest, &mut inventory, 0, LEFT, ClickMode::ShiftClick);
+        assert!(chest.get(0).unwrap().is_empty());
+        assert_eq!(inventory.get(36).unwrap().count, 12);
+    }
+
+    #[test]
+    fn closing_returns_the_cursor() {
+        let mut open = open();
+        let mut inventory = PlayerInventory::default();
+
+        open.carried = ItemStack::new(ItemKind::Diamond, 3, None);
+
+        assert_eq!(open.close(&mut inventory), None);
+        assert_eq!(inventory.get(36).unwrap().count, 3);
+    }
+}
@@ -0,0 +1,435 @@
+//! The crafting table window. Its 3x3 grid is a [`CraftingGrid`] on the player rather than on the
+//! table, so players crafting at the same table never share items, and whatever is left in the
+//! grid goes back to the player when the window closes.
+//!
+//! Unlike the other windows, items are moved with the cursor like in vanilla, since a recipe
+//! needs each ingredient in a particular cell.
+
+use std::borrow::Cow;
+
+use flecs_ecs::prelude::*;
+use hyperion_crafting::CraftingRegistry;
+use hyperion_inventory::{CraftingGrid, PlayerInventory};
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::{
+        play,
+        play::{click_slot_c2s::ClickMode, open_screen_s2c::WindowType},
+    },
+    text::IntoText,
+};
+
+use crate::{
+    ingress::PendingRemove,
+    net::DataBundle,
+    simulation::{
+        Position, event,
+        handlers::PacketSwitchQuery,
+        menu::{self, OpenMenu},
+    },
+    storage::Events,
+    system_registry::CRAFTING_TABLE,
+};
+
+/// The result and the nine cells come before the player's inventory.
+const TABLE_SLOTS: u16 = 10;
+const RESULT_SLOT: i16 = 0;
+
+/// The crafting table window a player has open. The grid itself is the player's
+/// `CraftingGrid<3>`.
+#[derive(Component, Clone, Debug)]
+pub struct OpenCraftingTable {
+    pub window_id: u8,
+    /// The stack held on the cursor.
+    pub carried: ItemStack,
+}
+
+impl OpenCraftingTable {
+    /// Applies a click on window slot `slot_idx`. Returns whatever fits nowhere and has to be
+    /// dropped.
+    fn click(
+        &mut self,
+        grid: &mut CraftingGrid<3>,
+        inventory: &mut PlayerInventory,
+        registry: &CraftingRegistry,
+        slot_idx: i16,
+        button: i8,
+        mode: ClickMode,
+    ) -> Vec<ItemStack> {
+        let dropped = match (mode, slot_idx) {
+            (ClickMode::Click, RESULT_SLOT) => self.craft(grid, inventory, registry),
+            (ClickMode::ShiftClick, RESULT_SLOT) => {
+                grid.take_result_bulk(registry, inventory).dropped
+            }
+            (ClickMode::Click, _) => {
+                with_slot_mut(grid, inventory, slot_idx, |slot| {
+                    click_stack(slot, &mut self.carried, button == 1);
+                });
+
+                Vec::new()
+            }
+            (ClickMode::ShiftClick, 1..=9) => {
+                // moves the whole stack out of the grid, leaving whatever does not fit
+                let cell = grid.get_mut(slot_idx.unsigned_abs() - 1).unwrap();
+                let stack = std::mem::replace(cell, ItemStack::EMPTY);
+
+                *cell = inventory
+                    .try_add_item(stack)
+                    .remaining
+                    .unwrap_or(ItemStack::EMPTY);
+
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+
+        grid.update(registry);
+
+        dropped
+    }
+
+    /// Crafts once onto the cursor, if the result fits there.
+    fn craft(
+        &mut self,
+        grid: &mut CraftingGrid<3>,
+        inventory: &mut PlayerInventory,
+        registry: &CraftingRegistry,
+    ) -> Vec<ItemStack> {
+        let result = grid.result();
+
+        let fits = self.carried.is_empty()
+            || (self.carried.item == result.item
+                && self.carried.nbt == result.nbt
+                && self.carried.count + result.count <= result.item.max_stack());
+
+        if result.is_empty() || !fits {
+            return Vec::new();
+        }
+
+        let crafted = grid.take_result(registry, inventory);
+
+        if self.carried.is_empty() {
+            self.carried = crafted.result;
+        } else {
+            self.carried.count += crafted.result.count;
+        }
+
+        crafted.dropped
+    }
+
+    /// Gives the cursor and the grid back to the player. Returns whatever does not fit.
+    fn close(self, grid: &mut CraftingGrid<3>, inventory: &mut PlayerInventory) -> Vec<ItemStack> {
+        let mut dropped = grid.return_to(inventory);
+
+        if !self.carried.is_empty() {
+            dropped.extend(inventory.try_add_item(self.carried).remaining);
+        }
+
+        dropped
+    }
+
+    /// Adds the whole window and the cursor to `bundle`, undoing anything the client predicted.
+    fn add_contents(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        slots: &[ItemStack],
+        query: &PacketSwitchQuery<'_>,
+    ) -> anyhow::Result<()> {
+        bundle.add_packet(
+            &menu::window_contents(self.window_id, slots, query.inventory),
+            query.world,
+        )?;
+
+        let cursor = play::ScreenHandlerSlotUpdateS2c {
+            window_id: -1,
+            state_id: VarInt::default(),
+            slot_idx: -1,
+            slot_data: Cow::Borrowed(&self.carried),
+        };
+
+        bundle.add_packet(&cursor, query.world)
+    }
+}
+
+/// The result followed by the cells, as laid out in the window.
+fn table_slots(grid: &CraftingGrid<3>) -> Vec<ItemStack> {
+    std::iter::once(grid.result())
+        .chain(grid.stacks())
+        .cloned()
+        .collect()
+}
+
+/// Runs `f` on the stack in window slot `slot_idx`, which is either a cell of the grid or a slot
+/// of the player's inventory.
+fn with_slot_mut(
+    grid: &mut CraftingGrid<3>,
+    inventory: &mut PlayerInventory,
+    slot_idx: i16,
+    f: impl FnOnce(&mut ItemStack),
+) {
+    if let Some(slot) = menu::player_slot(slot_idx, TABLE_SLOTS) {
+        if let Ok(mut stack) = inventory.get_mut(slot) {
+            f(&mut stack);
+        }
+
+        return;
+    }
+
+    let cell = slot_idx
+        .checked_sub(1)
+        .and_then(|cell| u16::try_from(cell).ok());
+
+    if let Some(stack) = cell.and_then(|cell| grid.get_mut(cell)) {
+        f(stack);
+    }
+}
+
+/// Clicks `slot` while holding `carried`, like vanilla does. The left button picks up, puts
+/// down, merges or swaps whole stacks; the right button picks up half a stack or puts down one
+/// item.
+pub(crate) fn click_stack(slot: &mut ItemStack, carried: &mut ItemStack, right: bool) {
+    if carried.is_empty() && slot.is_empty() {
+        return;
+    }
+
+    if carried.is_empty() {
+        let take = if right {
+            (slot.count + 1) / 2
+        } else {
+            slot.count
+        };
+
+        *carried = slot.clone().with_count(take);
+        *slot = shrink(slot, take);
+    } else if slot.is_empty() {
+        let put = if right { 1 } else { carried.count };
+
+        *slot = carried.clone().with_count(put);
+        *carried = shrink(carried, put);
+    } else if slot.item == carried.item && slot.nbt == carried.nbt {
+        let room = slot.item.max_stack() - slot.count;
+        let put = room.min(if right { 1 } else { carried.count });
+
+        slot.count += put;
+        *carried = shrink(carried, put);
+    } else {
+        std::mem::swap(slot, carried);
+    }
+}
+
+/// `stack` with `count` fewer items, or nothing if none are left.
+fn shrink(stack: &ItemStack, count: i8) -> ItemStack {
+    if stack.count > count {
+        stack.clone().with_count(stack.count - count)
+    } else {
+        ItemStack::EMPTY
+    }
+}
+
+/// Opens a crafting table window with an empty grid.
+pub fn open_crafting_table(query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    // a grid left over from a window that never closed still belongs to the player
+    close_crafting_table(query);
+
+    let open = OpenCraftingTable {
+        window_id: menu::next_window_id(),
+        carried: ItemStack::EMPTY,
+    };
+    let grid = CraftingGrid::<3>::default();
+
+    let mut bundle = DataBundle::new(query.compose);
+
+    bundle.add_packet(
+        &play::OpenScreenS2c {
+            window_id: VarInt(i32::from(open.window_id)),
+            window_type: WindowType::Crafting,
+            window_title: "Crafting".into_cow_text(),
+        },
+        query.world,
+    )?;
+
+    open.add_contents(&mut bundle, &table_slots(&grid), query)?;
+    bundle.send(query.world, query.io_ref, CRAFTING_TABLE)?;
+
+    // the crafting table window replaces any menu on the client
+    query.view.remove::<OpenMenu>();
+    query.view.set(open);
+    query.view.set(grid);
+
+    Ok(())
+}
+
+/// Handles a click in an open crafting table window.
+pub fn click_crafting_table(
+    query: &mut PacketSwitchQuery<'_>,
+    pkt: &play::ClickSlotC2s,
+    open: &OpenCraftingTable,
+) -> anyhow::Result<()> {
+    let mut open = open.clone();
+
+    let inventory = &mut *query.inventory;
+    let registry = query.crafting_registry;
+
+    let clicked = query.view.try_get::<&mut CraftingGrid<3>>(|grid| {
+        let dropped = open.click(
+            grid,
+            inventory,
+            registry,
+            pkt.slot_idx,
+            pkt.button,
+            pkt.mode,
+        );
+
+        (dropped, table_slots(grid))
+    });
+
+    let Some((dropped, slots)) = clicked else {
+        return Ok(());
+    };
+
+    let mut bundle = DataBundle::new(query.compose);
+    open.add_contents(&mut bundle, &slots, query)?;
+    bundle.send(query.world, query.io_ref, CRAFTING_TABLE)?;
+
+    drop_items(query, dropped);
+    query.view.set(open);
+
+    Ok(())
+}
+
+/// Closes the crafting table window, giving the grid and the cursor back to the player. Whatever
+/// does not fit is dropped at their feet.
+pub fn close_crafting_table(query: &mut PacketSwitchQuery<'_>) {
+    let Some(open) = query.view.try_get::<&OpenCraftingTable>(Clone::clone) else {
+        return;
+    };
+
+    let inventory = &mut *query.inventory;
+
+    let dropped = query
+        .view
+        .try_get::<&mut CraftingGrid<3>>(|grid| open.close(grid, inventory))
+        .unwrap_or_default();
+
+    query.view.remove::<OpenCraftingTable>();
+    query.view.remove::<CraftingGrid<3>>();
+
+    drop_items(query, dropped);
+}
+
+/// Gives the grid and the cursor of an open crafting table back to players who leave, before they
+/// are saved.
+pub(crate) fn close_on_leave(world: &World) {
+    system!(
+        "close_crafting_tables_of_leaving_players",
+        world,
+        &Events($),
+        &Position,
+        &mut PlayerInventory,
+        &OpenCraftingTable,
+        &mut CraftingGrid<3>,
+    )
+    .kind::<flecs::pipeline::PostLoad>()
+    .with::<&PendingRemove>()
+    .each_entity(|player, (events, position, inventory, open, grid)| {
+        let world = player.world();
+
+        for item in open.clone().close(grid, inventory) {
+            let location = **position;
+            events.push(event::ItemDropEvent::new(item, location), &world);
+        }
+
+        player.remove::<OpenCraftingTable>();
+        player.remove::<CraftingGrid<3>>();
+    });
+}
+
+fn drop_items(query: &PacketSwitchQuery<'_>, items: Vec<ItemStack>) {
+    for item in items {
+        let location = **query.position;
+        query
+            .events
+            .push(event::ItemDropEvent::new(item, location), query.world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_crafting::CraftingRegistry;
+    use hyperion_inventory::{CraftingGrid, PlayerInventory};
+    use valence_protocol::{ItemKind, ItemStack, packets::play::click_slot_c2s::ClickMode};
+
+    use super::OpenCraftingTable;
+
+    const LEFT: i8 = 0;
+    const RIGHT: i8 = 1;
+
+    /// The window slot of the first hotbar slot.
+    const HOTBAR: i16 = 37;
+
+    #[test]
+    fn crafting_sticks_at_a_table() {
+        let mut registry = CraftingRegistry::default();
+        registry
+            .register_shaped(
+                "minecraft:stick".to_owned(),
+                &["#", "#"],
+                [('#', ItemKind::OakPlanks)],
+                ItemStack::new(ItemKind::Stick, 4, None),
+            )
+            .unwrap();
+
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(36, ItemStack::new(ItemKind::OakPlanks, 5, None))
+            .unwrap();
+
+        let mut open = OpenCraftingTable {
+            window_id: 1,
+            carried: ItemStack::EMPTY,
+        };
+        let mut grid = CraftingGrid::<3>::default();
+
+        let mut click = |open: &mut OpenCraftingTable, slot, button| {
+            let dropped = open.click(
+                &mut grid,
+                &mut inventory,
+                &registry,
+                slot,
+                button,
+                ClickMode::Click,
+            );
+            assert!(dropped.is_empty());
+        };
+
+        // pick up the planks and put one in the top left cell and one below it
+        click(&mut open, HOTBAR, LEFT);
+        click(&mut open, 1, RIGHT);
+        click(&mut open, 4, RIGHT);
+        assert_eq!(open.carried, ItemStack::new(ItemKind::OakPlanks, 3, None));
+
+        // the sticks do not fit on a cursor full of planks
+        click(&mut open, 0, LEFT);
+        assert_eq!(open.carried.item, ItemKind::OakPlanks);
+
+        click(&mut open, HOTBAR, LEFT);
+        click(&mut open, 0, LEFT);
+        assert_eq!(open.carried, ItemStack::new(ItemKind::Stick, 4, None));
+
+        click(&mut open, HOTBAR + 1, LEFT);
+
+        // leave two planks in the middle of the grid
+        click(&mut open, HOTBAR, RIGHT);
+        click(&mut open, 5, LEFT);
+        assert!(open.carried.is_empty());
+
+        assert!(open.close(&mut grid, &mut inventory).is_empty());
+        assert!(grid.stacks().iter().all(ItemStack::is_empty));
+
+        let items: Vec<_> = inventory.items().collect();
+        assert_eq!(items, [
+            (36, &ItemStack::new(ItemKind::OakPlanks, 3, None)),
+            (37, &ItemStack::new(ItemKind::Stick, 4, None)),
+        ]);
+    }
+}
@@ -0,0 +1,553 @@
+//! Items lying in the world, such as those thrown by players or dropped when a container closes.
+//!
+//! Every [`event::ItemDropEvent`] becomes an [`ItemEntity`], which falls until it lands on a
+//! block, merges with identical stacks next to it and is picked up by the nearest player once its
+//! pickup delay is over. Items nobody picks up despawn after a while. How close players have to be
+//! and how long all of this takes is up to the [`DroppedItemConfig`].
+
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::warn;
+use valence_protocol::{ItemStack, VarInt, packets::play};
+
+use crate::{
+    Prev,
+    net::Compose,
+    simulation::{
+        Health, PacketState, Player, Position,
+        blocks::Blocks,
+        entity::{EntityKind, EntityMetadata, NetworkEntity, Viewers, spawn_moving_entity},
+        event,
+        metadata::DroppedStack,
+        mob::Terrain,
+    },
+    storage::{EventQueue, Events},
+    system_registry::DROPPED_ITEMS,
+};
+
+/// How close, in blocks, a player has to be to pick up an item.
+pub const PICKUP_RADIUS: f32 = 1.5;
+
+/// Ticks after being dropped before an item can be picked up, like items dropped by vanilla
+/// blocks. This keeps players from picking up what they just threw.
+pub const PICKUP_DELAY: u32 = 10;
+
+/// How close, in blocks, identical items have to be to merge.
+pub const MERGE_RADIUS: f32 = 0.5;
+
+/// Ticks after being dropped before an item despawns, which is 5 minutes.
+pub const DESPAWN_AFTER: u32 = 20 * 60 * 5;
+
+/// Items merge at most once every this many ticks, as looking for neighbours is quadratic.
+const MERGE_INTERVAL: i64 = 10;
+
+/// Blocks per tick an item falls faster each tick.
+const GRAVITY: f32 = 0.04;
+
+/// The share of its velocity an item keeps each tick.
+const DRAG: f32 = 0.98;
+
+/// How high above the feet of a player items they throw appear, which is just below their eyes.
+const THROW_HEIGHT: f32 = 1.32;
+
+/// How fast items are thrown, in blocks per tick.
+const THROW_SPEED: f32 = 0.3;
+
+/// How players pick up and leave behind dropped items. Change it with `world.get::<&mut
+/// DroppedItemConfig>`.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct DroppedItemConfig {
+    pub pickup_radius: f32,
+    /// Ticks after being dropped before an item can be picked up.
+    pub pickup_delay: u32,
+    pub merge_radius: f32,
+    /// Ticks after being dropped before an item despawns.
+    pub despawn_after: u32,
+    /// Whether players drop their whole inventory when they die.
+    pub drop_on_death: bool,
+}
+
+impl Default for DroppedItemConfig {
+    fn default() -> Self {
+        Self {
+            pickup_radius: PICKUP_RADIUS,
+            pickup_delay: PICKUP_DELAY,
+            merge_radius: MERGE_RADIUS,
+            despawn_after: DESPAWN_AFTER,
+            drop_on_death: true,
+        }
+    }
+}
+
+/// A stack of items lying in the world. Spawn one with [`drop_item`].
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct ItemEntity {
+    pub stack: ItemStack,
+    /// Ticks until the item can be picked up.
+    pub pickup_delay: u32,
+    /// Ticks since the item was dropped.
+    pub age: u32,
+    /// How fast the item moves, in blocks per tick.
+    pub velocity: Vec3,
+}
+
+impl ItemEntity {
+    /// Where the item at `position` ends up after falling for a tick. It stops once it lands on a
+    /// block and when it flies into one.
+    fn step(&mut self, position: Vec3, terrain: &impl Terrain) -> Vec3 {
+        self.velocity.y -= GRAVITY;
+
+        let next = position + self.velocity;
+        let block = next.floor().as_ivec3();
+
+        let next = if !terrain.is_solid(block) {
+            next
+        } else if self.velocity.y < 0.0 && !terrain.is_solid(position.floor().as_ivec3()) {
+            self.velocity = Vec3::ZERO;
+            next.with_y(block.y as f32 + 1.0)
+        } else {
+            self.velocity = self.velocity.with_x(0.0).with_z(0.0);
+            position
+        };
+
+        self.velocity *= DRAG;
+        next
+    }
+
+    /// Whether `other` can be added to this stack without it going over the maximum stack size.
+    fn can_merge(&self, other: &ItemStack) -> bool {
+        let stack = &self.stack;
+
+        !stack.is_empty()
+            && stack.item == other.item
+            && stack.nbt == other.nbt
+            && i16::from(stack.count) + i16::from(other.count) <= i16::from(stack.item.max_stack())
+    }
+}
+
+/// Drops `stack` at `position`, moving at `velocity` blocks per tick. It can be picked up after
+/// `pickup_delay` ticks.
+pub fn drop_item(
+    world: &World,
+    stack: ItemStack,
+    position: Vec3,
+    velocity: Vec3,
+    pickup_delay: u32,
+) -> Entity {
+    let metadata = EntityMetadata::default().with(DroppedStack(stack.clone()));
+    let item = spawn_moving_entity(world, EntityKind::ITEM, position, velocity, metadata);
+
+    world.entity_from_id(item).set(ItemEntity {
+        stack,
+        pickup_delay,
+        age: 0,
+        velocity,
+    });
+
+    item
+}
+
+/// `item` thrown by a player standing at `position` and looking at `yaw` and `pitch`.
+#[must_use]
+pub fn throw(item: ItemStack, position: Vec3, yaw: f32, pitch: f32) -> event::ItemDropEvent {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+
+    let direction = Vec3::new(
+        -yaw.sin() * pitch.cos(),
+        -pitch.sin(),
+        yaw.cos() * pitch.cos(),
+    );
+    let velocity = direction * THROW_SPEED + Vec3::Y * 0.1;
+
+    event::ItemDropEvent {
+        item,
+        location: position + Vec3::Y * THROW_HEIGHT,
+        velocity: Some(velocity),
+    }
+}
+
+/// A small hop in a random direction, for items that are not thrown.
+fn scatter() -> Vec3 {
+    let sideways = || (fastrand::f32() - 0.5) * 0.2;
+    Vec3::new(sideways(), 0.2, sideways())
+}
+
+/// Adds each stack to the earliest stack before it that is within `radius` and has room for it.
+/// Returns the indices of the stacks that were added to another one and are left over.
+fn merge_stacks(items: &mut [(Vec3, ItemEntity)], radius: f32) -> Vec<usize> {
+    let mut merged = Vec::new();
+
+    for i in 1..items.len() {
+        let (before, rest) = items.split_at_mut(i);
+        let (position, item) = &rest[0];
+
+        let into = before
+            .iter_mut()
+            .enumerate()
+            .filter(|(j, _)| !merged.contains(j))
+            .map(|(_, into)| into)
+            .find(|(into_position, into)| {
+                into_position.distance(*position) <= radius && into.can_merge(&item.stack)
+            });
+
+        if let Some((_, into)) = into {
+            into.stack.count += item.stack.count;
+            merged.push(i);
+        }
+    }
+
+    merged
+}
+
+/// The player in `players` nearest to `position`, if any of them is within `radius`.
+fn nearest_player(position: Vec3, players: &[(Entity, Vec3)], radius: f32) -> Option<Entity> {
+    let distance = |player: &(Entity, Vec3)| player.1.distance_squared(position);
+
+    players
+        .iter()
+        .filter(|player| distance(player) <= radius.powi(2))
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .map(|(player, _)| *player)
+}
+
+/// Shows the `count` items of `item` flying into `collector` to everyone who can see the item.
+fn send_pickup(
+    compose: &Compose,
+    item: Entity,
+    collector: Entity,
+    count: i8,
+    viewers: &Viewers,
+    world: &World,
+) {
+    let pkt = play::ItemPickupAnimationS2c {
+        collected_entity_id: VarInt(item.minecraft_id()),
+        collector_entity_id: VarInt(collector.minecraft_id()),
+        pickup_item_count: VarInt(i32::from(count)),
+    };
+
+    for stream in viewers.streams() {
+        if let Err(e) = compose.unicast(&pkt, stream, DROPPED_ITEMS, world) {
+            warn!("failed to send item pickup packet: {e}");
+        }
+    }
+}
+
+/// The slot of a player inventory that shows what the crafting grid makes. It is not a real item,
+/// so it is not dropped.
+const CRAFTING_RESULT: u16 = 0;
+
+#[derive(Component)]
+pub struct DroppedItemModule;
+
+impl Module for DroppedItemModule {
+    fn module(world: &World) {
+        world.component::<DroppedItemConfig>();
+        world.component::<ItemEntity>();
+
+        world.set(DroppedItemConfig::default());
+
+        system!(
+            "dropped_item_physics",
+            world,
+            &Blocks($),
+            &DroppedItemConfig($),
+            &mut ItemEntity,
+            &mut Position,
+            &mut NetworkEntity,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_entity(|entity, (blocks, config, item, position, network)| {
+            item.age += 1;
+            item.pickup_delay = item.pickup_delay.saturating_sub(1);
+
+            if item.age >= config.despawn_after {
+                entity.destruct();
+                return;
+            }
+
+            // items lying on a block stay there until the block is gone
+            let below = (**position - Vec3::Y).floor().as_ivec3();
+            if item.velocity == Vec3::ZERO && blocks.is_solid(below) {
+                return;
+            }
+
+            **position = item.step(**position, blocks);
+            network.set_velocity(item.velocity);
+        });
+
+        let items = world.query::<(&ItemEntity, &Position)>().build();
+
+        system!(
+            "merge_dropped_items",
+            world,
+            &Compose($),
+            &DroppedItemConfig($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(move |it, _, (compose, config)| {
+            if compose.global().tick % MERGE_INTERVAL != 0 {
+                return;
+            }
+
+            let world = it.world();
+
+            let mut entities = Vec::new();
+            let mut stacks = Vec::new();
+
+            items.each_entity(|entity, (item, position)| {
+                entities.push(entity.id());
+                stacks.push((**position, item.clone()));
+            });
+
+            let merged = merge_stacks(&mut stacks, config.merge_radius);
+
+            for (i, (entity, (_, merged_item))) in entities.into_iter().zip(stacks).enumerate() {
+                let entity = world.entity_from_id(entity);
+
+                if merged.contains(&i) {
+                    entity.destruct();
+                    continue;
+                }
+
+                entity.get::<(&mut ItemEntity, &mut EntityMetadata)>(|(item, metadata)| {
+                    if item.stack != merged_item.stack {
+                        metadata.set(DroppedStack(merged_item.stack.clone()));
+                        item.stack = merged_item.stack;
+                    }
+                });
+            }
+        });
+
+        let players = world
+            .query::<(&Position, &Health)>()
+            .with::<Player>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!(
+            "pick_up_dropped_items",
+            world,
+            &Compose($),
+            &DroppedItemConfig($),
+            &mut ItemEntity,
+            &Position,
+            &mut EntityMetadata,
+            &Viewers,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_entity(
+            move |entity, (compose, config, item, position, metadata, viewers)| {
+                if item.pickup_delay > 0 {
+                    return;
+                }
+
+                let world = entity.world();
+
+                let mut candidates = Vec::new();
+                players.each_entity(|player, (position, health)| {
+                    if !health.is_dead() {
+                        candidates.push((player.id(), **position));
+                    }
+                });
+
+                let radius = config.pickup_radius;
+                let Some(collector) = nearest_player(**position, &candidates, radius) else {
+                    return;
+                };
+
+                let remaining = world
+                    .entity_from_id(collector)
+                    .try_get::<&mut PlayerInventory>(|inventory| {
+                        inventory.try_add_item(item.stack.clone()).remaining
+                    });
+
+                let Some(remaining) = remaining else {
+                    return;
+                };
+
+                let remaining = remaining.unwrap_or(ItemStack::EMPTY);
+                let taken = item.stack.count - remaining.count;
+
+                if taken > 0 {
+                    send_pickup(compose, entity.id(), collector, taken, viewers, &world);
+                }
+
+                if remaining.is_empty() {
+                    entity.destruct();
+                    return;
+                }
+
+                metadata.set(DroppedStack(remaining.clone()));
+                item.stack = remaining;
+            },
+        );
+
+        // the health of players who died is reset when it is sent, so they are only dead for a tick
+        system!(
+            "drop_items_on_death",
+            world,
+            &Events($),
+            &DroppedItemConfig($),
+            &mut PlayerInventory,
+            &Health,
+            &Prev<Health>,
+            &Position,
+        )
+        .kind::<flecs::pipeline::PostUpdate>()
+        .with::<Player>()
+        .each_entity(
+            |player, (events, config, inventory, health, previous, position)| {
+                if !config.drop_on_death || !health.is_dead() || previous.is_dead() {
+                    return;
+                }
+
+                let world = player.world();
+
+                let items = inventory
+                    .items()
+                    .filter(|&(slot, _)| slot != CRAFTING_RESULT)
+                    .map(|(_, item)| item.clone())
+                    .collect::<Vec<_>>();
+
+                inventory.clear();
+
+                for item in items {
+                    events.push(event::ItemDropEvent::new(item, **position), &world);
+                }
+            },
+        );
+
+        system!(
+            "spawn_dropped_items",
+            world,
+            &mut EventQueue<event::ItemDropEvent>($),
+            &DroppedItemConfig($),
+        )
+        .kind::<flecs::pipeline::PostUpdate>()
+        .each_iter(|it, _, (queue, config)| {
+            let world = it.world();
+
+            for drop in queue.drain() {
+                if drop.item.is_empty() {
+                    continue;
+                }
+
+                let velocity = drop.velocity.unwrap_or_else(scatter);
+                drop_item(
+                    &world,
+                    drop.item,
+                    drop.location,
+                    velocity,
+                    config.pickup_delay,
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::Entity;
+    use glam::{IVec3, Vec3};
+    use hyperion_utils::EntityExt;
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{DRAG, GRAVITY, ItemEntity, merge_stacks, nearest_player, throw};
+    use crate::simulation::mob::Terrain;
+
+    /// Flat ground with its top at `y = 64`.
+    struct Flat;
+
+    impl Terrain for Flat {
+        fn is_solid(&self, position: IVec3) -> bool {
+            position.y < 64
+        }
+    }
+
+    fn item(kind: ItemKind, count: i8) -> ItemEntity {
+        ItemEntity {
+            stack: ItemStack::new(kind, count, None),
+            pickup_delay: 0,
+            age: 0,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn items_fall_and_land_on_the_ground() {
+        let mut dropped = item(ItemKind::Stone, 1);
+        dropped.velocity = Vec3::new(0.1, 0.2, 0.0);
+
+        let mut position = Vec3::new(0.5, 66.0, 0.5);
+        let next = dropped.step(position, &Flat);
+        assert!(next.y > position.y);
+        assert!(next.x > position.x);
+        assert!((dropped.velocity.y - (0.2 - GRAVITY) * DRAG).abs() < f32::EPSILON);
+
+        for _ in 0..100 {
+            position = dropped.step(position, &Flat);
+        }
+
+        assert!((position.y - 64.0).abs() < f32::EPSILON);
+        assert_eq!(dropped.velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn identical_items_next_to_each_other_merge() {
+        let here = Vec3::new(0.5, 64.0, 0.5);
+        let mut items = vec![
+            (here, item(ItemKind::Stone, 10)),
+            (here + Vec3::X * 0.25, item(ItemKind::Stone, 20)),
+            (here, item(ItemKind::Dirt, 5)),
+            (here + Vec3::X * 3.0, item(ItemKind::Stone, 1)),
+            // would go over the maximum stack size
+            (here, item(ItemKind::Stone, 40)),
+        ];
+
+        let merged = merge_stacks(&mut items, 0.5);
+
+        assert_eq!(merged, [1]);
+        assert_eq!(items[0].1.stack.count, 30);
+        assert_eq!(items[2].1.stack.count, 5);
+        assert_eq!(items[4].1.stack.count, 40);
+    }
+
+    #[test]
+    fn nearest_player_in_range_picks_up() {
+        let alex = Entity::from_minecraft_id(1);
+        let steve = Entity::from_minecraft_id(2);
+        let players = [
+            (alex, Vec3::new(1.0, 64.0, 0.0)),
+            (steve, Vec3::new(0.5, 64.0, 0.0)),
+        ];
+
+        assert_eq!(
+            nearest_player(Vec3::new(0.0, 64.0, 0.0), &players, 1.5),
+            Some(steve)
+        );
+        assert_eq!(
+            nearest_player(Vec3::new(3.0, 64.0, 0.0), &players, 1.5),
+            None
+        );
+        assert_eq!(nearest_player(Vec3::ZERO, &[], 1.5), None);
+    }
+
+    #[test]
+    fn items_are_thrown_where_the_player_looks() {
+        let stone = ItemStack::new(ItemKind::Stone, 1, None);
+
+        // facing south, towards +z
+        let drop = throw(stone.clone(), Vec3::ZERO, 0.0, 0.0);
+        let velocity = drop.velocity.unwrap();
+        assert!(velocity.z > 0.0);
+        assert!(velocity.x.abs() < 1e-6);
+        assert!(drop.location.y > 1.0);
+
+        // facing west, towards -x, and looking up
+        let velocity = throw(stone, Vec3::ZERO, 90.0, -45.0).velocity.unwrap();
+        assert!(velocity.x < 0.0);
+        assert!(velocity.y > 0.1);
+    }
+}
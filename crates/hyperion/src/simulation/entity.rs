@@ -0,0 +1,890 @@
+//! Entities that are not players, such as holograms.
+//!
+//! Players are spawned for everyone, but these entities are only spawned for the players close
+//! enough to see them. Each entity remembers who it was spawned for in [`Viewers`], spawns itself
+//! for players who come into range and is destroyed for players who leave the range or when the
+//! ECS entity is deleted.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use flecs_ecs::prelude::*;
+use glam::{IVec2, Vec3};
+use hyperion_utils::EntityExt;
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::warn;
+use valence_protocol::{ByteAngle, RawBytes, VarInt, Velocity, packets::play};
+pub use valence_server::entity::EntityKind;
+use valence_text::IntoText;
+
+use crate::{
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::{
+        ChunkPosition, PacketState, Pitch, Position, Yaw,
+        metadata::{
+            ArmorStandFlags, CustomName, CustomNameVisible, EntityFlags, Metadata, MetadataBuilder,
+            NoGravity, encode_value,
+        },
+    },
+    system_registry::NETWORK_ENTITIES,
+};
+
+/// How many chunks away a player can be from a [`NetworkEntity`] and still see it. This is the
+/// tracking range vanilla uses for most entities that are not players.
+pub const TRACKING_RADIUS: i32 = 10;
+
+/// An entity that is spawned for the players near it. Create one with [`spawn_entity`].
+///
+/// Its position and rotation are the [`Position`], [`Yaw`] and [`Pitch`] components, which are
+/// sent to its viewers when they change, at most once every [`NetworkEntity::update_interval`]
+/// ticks. Players the entity is spawned for are also sent its [`NetworkEntity::velocity`], which
+/// their client applies until it is told where the entity moved.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct NetworkEntity {
+    kind: EntityKind,
+    uuid: uuid::Uuid,
+    /// The position and rotation the viewers were last sent.
+    sent: Transform,
+    /// The tick `sent` was sent on.
+    sent_at: i64,
+    update_interval: i64,
+    velocity: Vec3,
+}
+
+impl NetworkEntity {
+    #[must_use]
+    pub const fn kind(&self) -> EntityKind {
+        self.kind
+    }
+
+    #[must_use]
+    pub const fn uuid(&self) -> uuid::Uuid {
+        self.uuid
+    }
+
+    /// The minimum number of ticks between two moves sent to the viewers. Moves in between are
+    /// combined.
+    #[must_use]
+    pub const fn update_interval(&self) -> i64 {
+        self.update_interval
+    }
+
+    pub const fn set_update_interval(&mut self, ticks: i64) {
+        self.update_interval = ticks;
+    }
+
+    /// How fast the entity moves, in blocks per tick.
+    #[must_use]
+    pub const fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    pub const fn set_velocity(&mut self, velocity: Vec3) {
+        self.velocity = velocity;
+    }
+
+    /// Where the viewers last saw the entity and where it is now, if it moved and the viewers may
+    /// be told on `tick`.
+    fn take_move(&mut self, transform: Transform, tick: i64) -> Option<(Transform, Transform)> {
+        if self.sent == transform || tick - self.sent_at < self.update_interval {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut self.sent, transform);
+        self.sent_at = tick;
+
+        Some((from, transform))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Transform {
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Transform {
+    fn of(position: Position, yaw: Yaw, pitch: Pitch) -> Self {
+        Self {
+            position: *position,
+            yaw: *yaw,
+            pitch: *pitch,
+        }
+    }
+}
+
+/// The metadata of a [`NetworkEntity`], such as its flags or its custom name. Changes are sent to
+/// the viewers of the entity at the end of the tick.
+///
+/// Players can have it as well, for entries other than their flags, pose and health, which are
+/// kept in their own components. Their changes are sent in the same packet as those.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntityMetadata {
+    /// The encoded type and value of each entry, by index.
+    values: BTreeMap<u8, Vec<u8>>,
+    /// The indices of the entries changed since the viewers were last sent the metadata.
+    changed: BTreeSet<u8>,
+}
+
+impl EntityMetadata {
+    #[must_use]
+    pub fn with<M: Metadata>(mut self, metadata: M) -> Self {
+        self.set(metadata);
+        self
+    }
+
+    /// Sets an entry. It is only sent to the viewers if its value changed.
+    pub fn set<M: Metadata>(&mut self, metadata: M) {
+        let value = encode_value(metadata);
+
+        if self.values.get(&M::INDEX) == Some(&value) {
+            return;
+        }
+
+        self.values.insert(M::INDEX, value);
+        self.changed.insert(M::INDEX);
+    }
+
+    /// Every entry, as sent to players the entity is spawned for.
+    fn encode_all(&self) -> Option<Vec<u8>> {
+        encode_entries(&self.values)
+    }
+
+    /// The entries that changed since this was last called, if any.
+    fn take_changes(&mut self) -> Option<Vec<u8>> {
+        let changed = std::mem::take(&mut self.changed);
+
+        encode_entries(
+            changed
+                .iter()
+                .filter_map(|index| self.values.get_key_value(index)),
+        )
+    }
+
+    /// Adds every entry to `builder`, for players the entity is spawned for.
+    pub(crate) fn add_all_to(&self, builder: &mut MetadataBuilder) {
+        for (&index, value) in &self.values {
+            builder.encode_raw(index, value);
+        }
+    }
+
+    /// Adds the entries that changed since they were last taken to `builder`.
+    pub(crate) fn add_changes_to(&mut self, builder: &mut MetadataBuilder) {
+        for index in std::mem::take(&mut self.changed) {
+            if let Some(value) = self.values.get(&index) {
+                builder.encode_raw(index, value);
+            }
+        }
+    }
+}
+
+fn encode_entries<'a>(entries: impl IntoIterator<Item = (&'a u8, &'a Vec<u8>)>) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    for (&index, value) in entries {
+        bytes.push(index);
+        bytes.extend_from_slice(value);
+    }
+
+    if bytes.is_empty() {
+        return None;
+    }
+
+    // denote end of metadata
+    bytes.push(0xff);
+
+    Some(bytes)
+}
+
+/// The players a [`NetworkEntity`] is currently spawned for.
+#[derive(Component, Debug, Default)]
+pub struct Viewers {
+    streams: FxHashMap<Entity, NetworkStreamRef>,
+    /// The viewers before the last update, kept so that updates do not allocate.
+    previous: FxHashMap<Entity, NetworkStreamRef>,
+}
+
+/// The viewers a [`NetworkEntity`] gained and lost in a tick.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ViewerChanges {
+    entered: Vec<NetworkStreamRef>,
+    left: Vec<NetworkStreamRef>,
+}
+
+impl Viewers {
+    #[must_use]
+    pub fn contains(&self, player: Entity) -> bool {
+        self.streams.contains_key(&player)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    pub fn streams(&self) -> impl Iterator<Item = NetworkStreamRef> + '_ {
+        self.streams.values().copied()
+    }
+
+    /// Makes the `players` within [`TRACKING_RADIUS`] of `chunk` the viewers. Viewers missing
+    /// from `players` are no longer playing, so they are dropped without being told.
+    fn update(&mut self, chunk: IVec2, players: &PlayerChunks) -> ViewerChanges {
+        let mut changes = ViewerChanges::default();
+
+        std::mem::swap(&mut self.streams, &mut self.previous);
+
+        players.for_each_near(chunk, |player, stream| {
+            if self.previous.remove(&player).is_none() {
+                changes.entered.push(stream);
+            }
+
+            self.streams.insert(player, stream);
+        });
+
+        for (player, stream) in self.previous.drain() {
+            if players.contains(player) {
+                changes.left.push(stream);
+            }
+        }
+
+        changes
+    }
+}
+
+fn in_range(chunk: IVec2, player_chunk: IVec2) -> bool {
+    (chunk - player_chunk).abs().max_element() <= TRACKING_RADIUS
+}
+
+/// How many chunks are within [`TRACKING_RADIUS`] of a chunk.
+const CHUNKS_IN_RANGE: usize = (2 * TRACKING_RADIUS.unsigned_abs() as usize + 1).pow(2);
+
+/// The playing players by chunk, gathered once a tick so that each [`NetworkEntity`] only looks
+/// at the players near it.
+#[derive(Component, Debug, Default)]
+struct PlayerChunks {
+    by_chunk: FxHashMap<IVec2, Vec<(Entity, NetworkStreamRef)>>,
+    players: FxHashSet<Entity>,
+}
+
+impl PlayerChunks {
+    /// Forgets every player, keeping the memory for the next tick.
+    fn clear(&mut self) {
+        for players in self.by_chunk.values_mut() {
+            players.clear();
+        }
+
+        self.players.clear();
+    }
+
+    fn insert(&mut self, player: Entity, stream: NetworkStreamRef, chunk: IVec2) {
+        self.by_chunk
+            .entry(chunk)
+            .or_default()
+            .push((player, stream));
+
+        self.players.insert(player);
+    }
+
+    /// Drops the chunks no player is in anymore.
+    fn retain_occupied(&mut self) {
+        self.by_chunk.retain(|_, players| !players.is_empty());
+    }
+
+    fn contains(&self, player: Entity) -> bool {
+        self.players.contains(&player)
+    }
+
+    /// Calls `f` with every player within [`TRACKING_RADIUS`] of `chunk`.
+    fn for_each_near(&self, chunk: IVec2, mut f: impl FnMut(Entity, NetworkStreamRef)) {
+        let mut visit = |players: &[(Entity, NetworkStreamRef)]| {
+            for &(player, stream) in players {
+                f(player, stream);
+            }
+        };
+
+        // players are usually in fewer chunks than are in range, so those are checked instead
+        if self.by_chunk.len() <= CHUNKS_IN_RANGE {
+            for (&player_chunk, players) in &self.by_chunk {
+                if in_range(chunk, player_chunk) {
+                    visit(players);
+                }
+            }
+
+            return;
+        }
+
+        for x in -TRACKING_RADIUS..=TRACKING_RADIUS {
+            for z in -TRACKING_RADIUS..=TRACKING_RADIUS {
+                if let Some(players) = self.by_chunk.get(&(chunk + IVec2::new(x, z))) {
+                    visit(players);
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a `kind` entity at `position` for the players near it. It is destroyed for everyone
+/// once the returned entity is deleted.
+pub fn spawn_entity(
+    world: &World,
+    kind: EntityKind,
+    position: Vec3,
+    metadata: EntityMetadata,
+) -> Entity {
+    spawn_moving_entity(world, kind, position, Vec3::ZERO, metadata)
+}
+
+/// Like [`spawn_entity`], but the entity is spawned with `velocity`, in blocks per tick.
+pub fn spawn_moving_entity(
+    world: &World,
+    kind: EntityKind,
+    position: Vec3,
+    velocity: Vec3,
+    metadata: EntityMetadata,
+) -> Entity {
+    let yaw = Yaw::default();
+    let pitch = Pitch::default();
+    let position = Position::from(position);
+
+    let network = NetworkEntity {
+        kind,
+        uuid: uuid::Uuid::from_u128(fastrand::u128(..)),
+        sent: Transform::of(position, yaw, pitch),
+        sent_at: 0,
+        update_interval: 1,
+        velocity,
+    };
+
+    world
+        .entity()
+        .set(network)
+        .set(metadata)
+        .set(Viewers::default())
+        .set(position)
+        .set(yaw)
+        .set(pitch)
+        .id()
+}
+
+/// Spawns floating `text` at `position`. The text is the name of an invisible armor stand that
+/// has no hitbox, so players cannot hit it or see it anywhere but in the text.
+pub fn spawn_hologram<'a>(world: &World, position: Vec3, text: impl IntoText<'a>) -> Entity {
+    let metadata = EntityMetadata::default()
+        .with(EntityFlags::INVISIBLE)
+        .with(CustomName(Some(text.into_text())))
+        .with(CustomNameVisible(true))
+        .with(NoGravity(true))
+        .with(ArmorStandFlags::MARKER);
+
+    spawn_entity(world, EntityKind::ARMOR_STAND, position, metadata)
+}
+
+/// Changes the text of a hologram spawned with [`spawn_hologram`].
+pub fn set_hologram_text<'a>(hologram: EntityView<'_>, text: impl IntoText<'a>) {
+    let name = CustomName(Some(text.into_text()));
+
+    hologram.get::<&mut EntityMetadata>(|metadata| metadata.set(name));
+}
+
+/// Spawns the entity where its other viewers last saw it, so that later moves relative to that
+/// position are right for everyone.
+fn spawn_packet(entity_id: i32, network: &NetworkEntity) -> play::EntitySpawnS2c {
+    let transform = network.sent;
+
+    play::EntitySpawnS2c {
+        entity_id: VarInt(entity_id),
+        object_uuid: network.uuid,
+        kind: VarInt(network.kind.get()),
+        position: transform.position.as_dvec3(),
+        yaw: ByteAngle::from_degrees(transform.yaw),
+        pitch: ByteAngle::from_degrees(transform.pitch),
+        head_yaw: ByteAngle::from_degrees(transform.yaw),
+        data: VarInt::default(),
+        velocity: encode_velocity(network.velocity),
+    }
+}
+
+/// `velocity` in blocks per tick as clients expect it, in 1/8000ths of a block per tick.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "the velocity is clamped to fit in an i16 first"
+)]
+fn encode_velocity(velocity: Vec3) -> Velocity {
+    let max = Vec3::splat(f32::from(i16::MAX));
+    let velocity = (velocity * 8000.0).clamp(-max, max);
+
+    Velocity(velocity.to_array().map(|component| component as i16))
+}
+
+fn spawn_bundle<'a>(
+    compose: &'a Compose,
+    entity_id: i32,
+    network: &NetworkEntity,
+    metadata: &EntityMetadata,
+    world: &World,
+) -> anyhow::Result<DataBundle<'a>> {
+    let mut bundle = DataBundle::new(compose);
+
+    bundle.add_packet(&spawn_packet(entity_id, network), world)?;
+
+    if let Some(tracked_values) = metadata.encode_all() {
+        let pkt = play::EntityTrackerUpdateS2c {
+            entity_id: VarInt(entity_id),
+            tracked_values: RawBytes(&tracked_values),
+        };
+        bundle.add_packet(&pkt, world)?;
+    }
+
+    Ok(bundle)
+}
+
+/// Sends the changed position, rotation and metadata of an entity to a viewer.
+fn update_bundle<'a>(
+    compose: &'a Compose,
+    entity_id: i32,
+    moved: Option<(Transform, Transform)>,
+    metadata: Option<&[u8]>,
+    world: &World,
+) -> anyhow::Result<DataBundle<'a>> {
+    let mut bundle = DataBundle::new(compose);
+    let entity_id = VarInt(entity_id);
+
+    if let Some((from, to)) = moved {
+        let yaw = ByteAngle::from_degrees(to.yaw);
+        let pitch = ByteAngle::from_degrees(to.pitch);
+
+        if let Some(delta) = relative_delta(from.position, to.position) {
+            let pkt = play::RotateAndMoveRelativeS2c {
+                entity_id,
+                delta,
+                yaw,
+                pitch,
+                on_ground: false,
+            };
+            bundle.add_packet(&pkt, world)?;
+        } else {
+            let pkt = play::EntityPositionS2c {
+                entity_id,
+                position: to.position.as_dvec3(),
+                yaw,
+                pitch,
+                on_ground: false,
+            };
+            bundle.add_packet(&pkt, world)?;
+        }
+
+        let pkt = play::EntitySetHeadYawS2c {
+            entity_id,
+            head_yaw: yaw,
+        };
+        bundle.add_packet(&pkt, world)?;
+    }
+
+    if let Some(tracked_values) = metadata {
+        let pkt = play::EntityTrackerUpdateS2c {
+            entity_id,
+            tracked_values: RawBytes(tracked_values),
+        };
+        bundle.add_packet(&pkt, world)?;
+    }
+
+    Ok(bundle)
+}
+
+/// The move from `from` to `to` in 1/4096ths of a block, or `None` if it is too far for a relative
+/// move. Both positions are rounded the way the client rounds them, so that the client does not
+/// drift away from the server over many moves.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "positions times 4096 are nowhere near i64::MAX"
+)]
+fn relative_delta(from: Vec3, to: Vec3) -> Option<[i16; 3]> {
+    let delta = |from: f32, to: f32| {
+        let quantize = |position: f32| (f64::from(position) * 4096.0).round() as i64;
+        i16::try_from(quantize(to) - quantize(from)).ok()
+    };
+
+    Some([
+        delta(from.x, to.x)?,
+        delta(from.y, to.y)?,
+        delta(from.z, to.z)?,
+    ])
+}
+
+fn send_destroy(
+    compose: &Compose,
+    entity_id: i32,
+    streams: impl IntoIterator<Item = NetworkStreamRef>,
+    world: &World,
+) {
+    let entity_ids = [VarInt(entity_id)];
+    let pkt = play::EntitiesDestroyS2c {
+        entity_ids: Cow::Borrowed(&entity_ids),
+    };
+
+    for stream in streams {
+        if let Err(e) = compose.unicast(&pkt, stream, NETWORK_ENTITIES, world) {
+            warn!("failed to send entity despawn packet: {e}");
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct NetworkEntityModule;
+
+impl Module for NetworkEntityModule {
+    fn module(world: &World) {
+        world.component::<NetworkEntity>();
+        world.component::<EntityMetadata>();
+        world.component::<Viewers>();
+        world.component::<PlayerChunks>();
+
+        world.set(PlayerChunks::default());
+
+        let players = world
+            .query::<(&NetworkStreamRef, &ChunkPosition)>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!("index_player_chunks", world, &mut PlayerChunks($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(move |_, _, index| {
+                index.clear();
+
+                players.each_entity(|player, (&stream, chunk)| {
+                    index.insert(player.id(), stream, chunk.position);
+                });
+
+                index.retain_occupied();
+            });
+
+        system!(
+            "sync_network_entities",
+            world,
+            &Compose($),
+            &PlayerChunks($),
+            &mut NetworkEntity,
+            &mut Viewers,
+            &mut EntityMetadata,
+            &Position,
+            &Yaw,
+            &Pitch,
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_entity(
+            |entity, (compose, players, network, viewers, metadata, position, yaw, pitch)| {
+                let world = entity.world();
+                let entity_id = entity.minecraft_id();
+
+                let changes = viewers.update(position.to_chunk(), players);
+
+                for &stream in &changes.entered {
+                    let result = spawn_bundle(compose, entity_id, network, metadata, &world)
+                        .and_then(|bundle| bundle.send(&world, stream, NETWORK_ENTITIES));
+
+                    if let Err(e) = result {
+                        warn!("failed to send entity spawn packets: {e}");
+                    }
+                }
+
+                send_destroy(compose, entity_id, changes.left, &world);
+
+                let transform = Transform::of(*position, *yaw, *pitch);
+                let moved = network.take_move(transform, compose.global().tick);
+
+                let changed_metadata = metadata.take_changes();
+
+                if moved.is_none() && changed_metadata.is_none() {
+                    return;
+                }
+
+                for stream in viewers.streams() {
+                    // viewers who just entered were sent all of the metadata with the spawn packet
+                    let metadata = changed_metadata
+                        .as_deref()
+                        .filter(|_| !changes.entered.contains(&stream));
+
+                    let result = update_bundle(compose, entity_id, moved, metadata, &world)
+                        .and_then(|bundle| bundle.send(&world, stream, NETWORK_ENTITIES));
+
+                    if let Err(e) = result {
+                        warn!("failed to send entity update packets: {e}");
+                    }
+                }
+            },
+        );
+
+        observer!(world, flecs::OnRemove, &Viewers, &Compose($)).each_entity(
+            |entity, (viewers, compose)| {
+                let world = entity.world();
+                send_destroy(compose, entity.minecraft_id(), viewers.streams(), &world);
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use glam::{IVec2, Vec3};
+    use hyperion_utils::EntityExt;
+    use valence_protocol::VarInt;
+
+    use super::{
+        EntityKind, EntityMetadata, NetworkEntity, NetworkEntityModule, PlayerChunks,
+        TRACKING_RADIUS, Transform, ViewerChanges, Viewers, relative_delta, spawn_hologram,
+        spawn_packet,
+    };
+    use crate::{
+        net::{Compose, NetworkStreamRef},
+        simulation::{
+            ChunkPosition, PacketState,
+            metadata::{CustomNameVisible, EntityFlags, MetadataBuilder, NoGravity},
+        },
+    };
+
+    fn players(players: &[(Entity, NetworkStreamRef, IVec2)]) -> PlayerChunks {
+        let mut index = PlayerChunks::default();
+
+        for &(player, stream, chunk) in players {
+            index.insert(player, stream, chunk);
+        }
+
+        index
+    }
+
+    #[test]
+    fn viewer_is_spawned_and_despawned_as_it_moves() {
+        let mut viewers = Viewers::default();
+        let hologram = IVec2::new(0, 0);
+
+        let player = Entity::from_minecraft_id(1);
+        let stream = NetworkStreamRef::new(1);
+
+        let far_away = IVec2::new(TRACKING_RADIUS + 1, 0);
+        let changes = viewers.update(hologram, &players(&[(player, stream, far_away)]));
+        assert_eq!(changes, ViewerChanges::default());
+        assert!(viewers.is_empty());
+
+        // walking into range spawns the entity once
+        let edge = IVec2::new(TRACKING_RADIUS, -TRACKING_RADIUS);
+        let changes = viewers.update(hologram, &players(&[(player, stream, edge)]));
+        assert_eq!(changes.entered, [stream]);
+        assert!(changes.left.is_empty());
+        assert!(viewers.contains(player));
+
+        let changes = viewers.update(hologram, &players(&[(player, stream, IVec2::ZERO)]));
+        assert_eq!(changes, ViewerChanges::default());
+
+        // walking out of range destroys it
+        let changes = viewers.update(hologram, &players(&[(player, stream, far_away)]));
+        assert!(changes.entered.is_empty());
+        assert_eq!(changes.left, [stream]);
+        assert!(viewers.is_empty());
+
+        // a viewer that disconnected is forgotten without a despawn packet
+        viewers.update(hologram, &players(&[(player, stream, IVec2::ZERO)]));
+        let changes = viewers.update(hologram, &players(&[]));
+        assert_eq!(changes, ViewerChanges::default());
+        assert!(viewers.is_empty());
+    }
+
+    #[test]
+    fn players_spread_over_many_chunks_are_found_near_the_entity() {
+        let spread = (0..1000)
+            .map(|i| {
+                let chunk = IVec2::new(i % 40 - 20, i / 40 - 12) * 3;
+                (
+                    Entity::from_minecraft_id(i),
+                    NetworkStreamRef::new(i.unsigned_abs().into()),
+                    chunk,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let index = players(&spread);
+        let chunk = IVec2::new(4, -2);
+
+        let mut near = Vec::new();
+        index.for_each_near(chunk, |player, _| near.push(player));
+        near.sort_unstable();
+
+        let mut expected = spread
+            .iter()
+            .filter(|(_, _, player_chunk)| super::in_range(chunk, *player_chunk))
+            .map(|&(player, ..)| player)
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+
+        assert!(!expected.is_empty());
+        assert_eq!(near, expected);
+    }
+
+    /// How many unicasts were queued for the proxy so far.
+    fn unicasts(world: &World) -> u64 {
+        world.get::<&Compose>(|compose| compose.io_buf().stats().unicast.frames)
+    }
+
+    #[test]
+    fn entities_are_spawned_and_destroyed_for_nearby_players() {
+        let world = World::new();
+        world.set(Compose::for_tests());
+        world.import::<NetworkEntityModule>();
+
+        let player = world
+            .entity()
+            .set(NetworkStreamRef::new(1))
+            .set(ChunkPosition {
+                position: IVec2::ZERO,
+            })
+            .add_enum(PacketState::Play);
+
+        let hologram = spawn_hologram(&world, Vec3::new(0.5, 64.0, 0.5), "hello");
+        let viewed = || {
+            world
+                .entity_from_id(hologram)
+                .get::<&Viewers>(|viewers| viewers.contains(player.id()))
+        };
+
+        // the spawn and metadata packets are sent in one bundle
+        assert!(world.progress());
+        assert_eq!(unicasts(&world), 1);
+        assert!(viewed());
+
+        assert!(world.progress());
+        assert_eq!(unicasts(&world), 1);
+
+        // walking out of range destroys it
+        player.set(ChunkPosition {
+            position: IVec2::new(TRACKING_RADIUS + 1, 0),
+        });
+        assert!(world.progress());
+        assert_eq!(unicasts(&world), 2);
+        assert!(!viewed());
+
+        // coming back spawns it again
+        player.set(ChunkPosition {
+            position: IVec2::ZERO,
+        });
+        assert!(world.progress());
+        assert_eq!(unicasts(&world), 3);
+
+        // deleting it destroys it for its viewers
+        world.entity_from_id(hologram).destruct();
+        assert_eq!(unicasts(&world), 4);
+    }
+
+    fn at(x: f32) -> Transform {
+        Transform {
+            position: Vec3::new(x, 64.0, -2.5),
+            yaw: 90.0,
+            pitch: 0.0,
+        }
+    }
+
+    fn armor_stand(sent: Transform) -> NetworkEntity {
+        NetworkEntity {
+            kind: EntityKind::ARMOR_STAND,
+            uuid: uuid::Uuid::from_u128(42),
+            sent,
+            sent_at: 0,
+            update_interval: 1,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn spawn_packet_describes_the_entity() {
+        let network = armor_stand(at(1.5));
+
+        let pkt = spawn_packet(7, &network);
+        assert_eq!(pkt.entity_id, VarInt(7));
+        assert_eq!(pkt.kind, VarInt(EntityKind::ARMOR_STAND.get()));
+        assert_eq!(pkt.object_uuid, network.uuid);
+        assert_eq!(pkt.position, at(1.5).position.as_dvec3());
+        assert_eq!(pkt.velocity.0, [0; 3]);
+
+        let mut network = network;
+        network.set_velocity(Vec3::new(0.5, -0.25, 10.0));
+        assert_eq!(spawn_packet(7, &network).velocity.0, [
+            4000,
+            -2000,
+            i16::MAX
+        ]);
+    }
+
+    #[test]
+    fn moves_are_combined_within_the_update_interval() {
+        let mut network = armor_stand(at(0.0));
+        network.set_update_interval(3);
+
+        assert_eq!(network.take_move(at(0.0), 3), None);
+        assert_eq!(network.take_move(at(1.0), 3), Some((at(0.0), at(1.0))));
+
+        // too soon, so both moves are sent together
+        assert_eq!(network.take_move(at(2.0), 4), None);
+        assert_eq!(network.take_move(at(3.0), 5), None);
+        assert_eq!(network.take_move(at(3.0), 6), Some((at(1.0), at(3.0))));
+    }
+
+    #[test]
+    fn far_moves_are_not_relative() {
+        let from = Vec3::new(0.0, 64.0, 0.0);
+
+        assert_eq!(
+            relative_delta(from, Vec3::new(1.0, 64.5, -0.25)),
+            Some([4096, 2048, -1024])
+        );
+        assert_eq!(relative_delta(from, Vec3::new(8.0, 64.0, 0.0)), None);
+    }
+
+    #[test]
+    fn only_changed_metadata_is_resent() {
+        let mut metadata = EntityMetadata::default()
+            .with(EntityFlags::INVISIBLE)
+            .with(NoGravity(true));
+
+        let all = metadata.encode_all().unwrap();
+        assert_eq!(all.first(), Some(&0));
+        assert_eq!(all.last(), Some(&0xff));
+
+        // the initial values are sent with the spawn packet
+        assert!(metadata.take_changes().is_some());
+        assert!(metadata.take_changes().is_none());
+
+        // setting the same value again is not a change
+        metadata.set(NoGravity(true));
+        assert!(metadata.take_changes().is_none());
+
+        metadata.set(CustomNameVisible(true));
+        // index 3, type 8 (boolean), true, end
+        assert_eq!(metadata.take_changes().unwrap(), [3, 8, 1, 0xff]);
+        assert!(metadata.encode_all().unwrap().len() > all.len());
+    }
+
+    #[test]
+    fn player_metadata_changes_join_their_flags() {
+        let mut metadata = EntityMetadata::default().with(NoGravity(true));
+        let mut builder = MetadataBuilder::default();
+
+        builder.encode(EntityFlags::GLOWING);
+        metadata.add_changes_to(&mut builder);
+
+        // flags at index 0, then no gravity at index 5, in one list
+        assert_eq!(&*builder.get_and_clear().unwrap(), [
+            0, 0, 0x40, 5, 8, 1, 0xff
+        ]);
+
+        metadata.set(NoGravity(true));
+        metadata.add_changes_to(&mut builder);
+        assert!(builder.get_and_clear().is_none());
+    }
+}
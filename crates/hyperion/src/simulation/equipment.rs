@@ -0,0 +1,191 @@
+//! Lets others see what entities wear and hold.
+//!
+//! Whenever an equipment slot of a [`PlayerInventory`] changes, an [`EquipmentChange`] is pushed,
+//! which is then broadcast to everyone nearby. Changes are found with
+//! [`PlayerInventory::is_updated`], so this runs before the inventory sync in egress drains them.
+
+use flecs_ecs::prelude::*;
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use tracing::warn;
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::play::{self, entity_equipment_update_s2c::EquipmentEntry},
+};
+
+use crate::{
+    net::{Compose, NetworkStreamRef},
+    simulation::{
+        Position,
+        event::{EquipmentChange, EquipmentSlot},
+        visibility::HiddenFrom,
+    },
+    storage::{EventQueue, Events},
+    system_registry::EQUIPMENT,
+};
+
+/// The equipment of `entity` that changed since the last tick.
+fn changes(entity: Entity, inventory: &PlayerInventory) -> Vec<EquipmentChange> {
+    EquipmentSlot::ALL
+        .into_iter()
+        .filter(|&slot| {
+            let index = slot.inventory_slot(inventory);
+
+            // selecting another hotbar slot changes the held item too
+            (slot == EquipmentSlot::MainHand && inventory.is_hand_slot_updated())
+                || inventory.is_updated(index)
+        })
+        .map(|slot| EquipmentChange {
+            entity,
+            slot,
+            item: inventory
+                .get(slot.inventory_slot(inventory))
+                .cloned()
+                .unwrap_or(ItemStack::EMPTY),
+        })
+        .collect()
+}
+
+fn broadcast(world: &World, compose: &Compose, change: EquipmentChange) -> anyhow::Result<()> {
+    let EquipmentChange { entity, slot, item } = change;
+    let entity = entity.entity_view(world);
+
+    if !entity.is_alive() {
+        return Ok(());
+    }
+
+    let Some(chunk_pos) = entity.try_get::<&Position>(|position| position.to_chunk()) else {
+        return Ok(());
+    };
+
+    let hidden_from = entity
+        .try_get::<&HiddenFrom>(|hidden_from| hidden_from.streams().to_vec())
+        .unwrap_or_default();
+
+    let pkt = play::EntityEquipmentUpdateS2c {
+        entity_id: VarInt(entity.minecraft_id()),
+        equipment: vec![EquipmentEntry {
+            slot: slot as i8,
+            item,
+        }],
+    };
+
+    let mut broadcast = compose
+        .broadcast_local(&pkt, chunk_pos, EQUIPMENT)
+        .exclude_many(&hidden_from);
+
+    // players see their own equipment in their inventory
+    if let Some(stream) = entity.try_get::<&NetworkStreamRef>(|stream| *stream) {
+        broadcast = broadcast.exclude(stream);
+    }
+
+    broadcast.send(world)?;
+
+    Ok(())
+}
+
+#[derive(Component)]
+pub struct EquipmentModule;
+
+impl Module for EquipmentModule {
+    fn module(world: &World) {
+        system!(
+            "detect_equipment_changes",
+            world,
+            &Events($),
+            &PlayerInventory,
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_entity(|entity, (events, inventory)| {
+            let world = entity.world();
+
+            for change in changes(entity.id(), inventory) {
+                events.push(change, &world);
+            }
+        });
+
+        system!(
+            "broadcast_equipment",
+            world,
+            &Compose($),
+            &mut EventQueue<EquipmentChange>($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, queue)| {
+            let world = it.world();
+
+            for change in queue.drain() {
+                if let Err(e) = broadcast(&world, compose, change) {
+                    warn!("failed to broadcast equipment: {e}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::Entity;
+    use hyperion_inventory::PlayerInventory;
+    use hyperion_utils::EntityExt;
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::changes;
+    use crate::simulation::event::EquipmentSlot;
+
+    fn slots(inventory: &PlayerInventory) -> Vec<(EquipmentSlot, ItemKind)> {
+        changes(Entity::from_minecraft_id(1), inventory)
+            .into_iter()
+            .map(|change| (change.slot, change.item.item))
+            .collect()
+    }
+
+    #[test]
+    fn only_equipment_slots_are_changes() {
+        let mut inventory = PlayerInventory::default();
+
+        inventory.set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None));
+        inventory.set_offhand(ItemStack::new(ItemKind::Shield, 1, None));
+        inventory
+            .set(9, ItemStack::new(ItemKind::Stone, 1, None))
+            .unwrap();
+
+        assert_eq!(slots(&inventory), vec![
+            (EquipmentSlot::OffHand, ItemKind::Shield),
+            (EquipmentSlot::Helmet, ItemKind::IronHelmet),
+        ]);
+    }
+
+    #[test]
+    fn taking_off_armor_is_a_change() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_boots(ItemStack::new(ItemKind::IronBoots, 1, None));
+        inventory.drain_updates();
+
+        inventory
+            .set(PlayerInventory::BOOTS_SLOT, ItemStack::EMPTY)
+            .unwrap();
+
+        assert_eq!(slots(&inventory), vec![(
+            EquipmentSlot::Boots,
+            ItemKind::Air
+        )]);
+    }
+
+    #[test]
+    fn selecting_a_hotbar_slot_changes_the_main_hand() {
+        let mut inventory = PlayerInventory::default();
+        inventory
+            .set(37, ItemStack::new(ItemKind::IronSword, 1, None))
+            .unwrap();
+        inventory.drain_updates();
+
+        assert!(slots(&inventory).is_empty());
+
+        inventory.set_cursor(1);
+        assert_eq!(slots(&inventory), vec![(
+            EquipmentSlot::MainHand,
+            ItemKind::IronSword
+        )]);
+    }
+}
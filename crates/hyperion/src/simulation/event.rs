@@ -1,10 +1,13 @@
 //! Flecs components which are used for events.
 
+use std::borrow::Cow;
+
 use derive_more::Constructor;
 use flecs_ecs::{core::Entity, macros::Component};
 use glam::{IVec3, Vec3};
+pub use hyperion_inventory::EquipmentSlot;
 use valence_generated::block::BlockState;
-use valence_protocol::Hand;
+use valence_protocol::{Hand, packets::play::click_slot_c2s::ClickMode};
 use valence_server::entity::item_frame::ItemStack;
 
 use crate::simulation::skin::PlayerSkin;
@@ -13,6 +16,21 @@ use crate::simulation::skin::PlayerSkin;
 pub struct ItemDropEvent {
     pub item: ItemStack,
     pub location: Vec3,
+    /// How fast the item is thrown, in blocks per tick. Without one, it pops out in a random
+    /// direction like an item dropped by a block.
+    pub velocity: Option<Vec3>,
+}
+
+impl ItemDropEvent {
+    /// Drops `item` at `location`, popping out in a random direction.
+    #[must_use]
+    pub const fn new(item: ItemStack, location: Vec3) -> Self {
+        Self {
+            item,
+            location,
+            velocity: None,
+        }
+    }
 }
 
 #[derive(Component, Default, Debug)]
@@ -22,9 +40,11 @@ pub struct ItemInteract {
     pub sequence: i32,
 }
 
+/// A chat message, as left by the [`crate::storage::GlobalEventHandlers::chat_message`] handlers.
 #[derive(Debug)]
 pub struct ChatMessage<'a> {
-    pub msg: &'a str,
+    /// Borrowed from the packet, unless a handler rewrote it.
+    pub msg: Cow<'a, str>,
     pub by: Entity,
 }
 
@@ -35,13 +55,84 @@ pub struct SetSkin {
 }
 
 /// Represents an attack action by an entity in the game.
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AttackEntity {
     /// The entity that is performing the attack.
     pub origin: Entity,
     pub target: Entity,
     /// The damage dealt by the attack. This corresponds to the same unit as [`crate::simulation::Health`].
     pub damage: f32,
+    /// The item the attacker was holding when the attack packet arrived.
+    ///
+    /// This is cloned at attack time so handlers running later in the tick see the weapon
+    /// that was actually used, even if the attacker's inventory has changed since.
+    pub weapon: ItemStack,
+    /// The hand the attack was performed with.
+    pub hand: Hand,
+    /// The horizontal direction the target is knocked in, as a unit vector pointing away from the
+    /// attacker along the way it was facing.
+    pub knockback: Vec3,
+    pub flags: AttackFlags,
+}
+
+/// What kind of hit an [`AttackEntity`] was.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AttackFlags {
+    value: u8,
+}
+
+impl AttackFlags {
+    /// A falling hit. The server does not track whether players are on the ground, so this is
+    /// never set when the event is created.
+    pub const CRITICAL: Self = Self { value: 0x01 };
+    pub const SPRINT: Self = Self { value: 0x02 };
+    /// A hit with a sword that is not a sprint hit, which also sweeps entities next to the target.
+    pub const SWEEPING: Self = Self { value: 0x04 };
+
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self { value: 0 }
+    }
+
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.value & other.value == other.value
+    }
+}
+
+impl std::ops::BitOr for AttackFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            value: self.value | rhs.value,
+        }
+    }
+}
+
+impl std::ops::BitOrAssign for AttackFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.value |= rhs.value;
+    }
+}
+
+/// Damage dealt to an entity by something other than a player's attack, such as a mob or the
+/// world border.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Damage {
+    /// The entity that dealt the damage. Damage dealt by the world, such as by the world border,
+    /// has the target as its origin.
+    pub origin: Entity,
+    pub target: Entity,
+    /// This corresponds to the same unit as [`crate::simulation::Health`].
+    pub amount: f32,
+}
+
+/// A player landed after falling `distance` blocks. See [`crate::simulation::fall`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fall {
+    pub entity: Entity,
+    pub distance: f32,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Constructor)]
@@ -121,6 +212,26 @@ pub struct PostureUpdate {
     pub state: Posture,
 }
 
+/// The item in an equipment slot of an entity changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EquipmentChange {
+    pub entity: Entity,
+    pub slot: EquipmentSlot,
+    /// The item now in the slot, which is empty if it was taken out.
+    pub item: ItemStack,
+}
+
+/// A click in the window of a container a player has open. These are applied after the packet
+/// handlers, which run in parallel, so players viewing the same container never change it at once.
+#[derive(Copy, Clone, Debug)]
+pub struct ContainerClick {
+    pub player: Entity,
+    pub window_id: u8,
+    pub slot_idx: i16,
+    pub button: i8,
+    pub mode: ClickMode,
+}
+
 #[derive(Debug)]
 pub struct Command<'a> {
     pub raw: &'a str,
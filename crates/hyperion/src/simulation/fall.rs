@@ -0,0 +1,78 @@
+//! How far players fall, measured from the movement they send like in vanilla.
+//!
+//! Landing fires an [`event::Fall`](crate::simulation::event::Fall), so games decide whether a
+//! fall hurts, e.g. with [`fall_damage`].
+
+use flecs_ecs::prelude::*;
+
+/// How many blocks players can fall without being hurt.
+pub const SAFE_FALL_DISTANCE: f32 = 3.0;
+
+/// How far a player has fallen since they last stood on the ground.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq)]
+pub struct FallDistance(f32);
+
+impl FallDistance {
+    /// Records a move of `dy` blocks up. Returns how far the player fell if this move landed them.
+    ///
+    /// Like in vanilla, only moving down counts, so a player jumping up and falling back down falls
+    /// from the top of the jump.
+    pub fn update(&mut self, dy: f32, on_ground: bool) -> Option<f32> {
+        if on_ground {
+            let distance = std::mem::take(&mut self.0);
+            return (distance > 0.0).then_some(distance);
+        }
+
+        if dy < 0.0 {
+            self.0 -= dy;
+        }
+
+        None
+    }
+
+    /// Forgets how far the player has fallen, e.g. after they are teleported.
+    pub const fn reset(&mut self) {
+        self.0 = 0.0;
+    }
+
+    #[must_use]
+    pub const fn get(self) -> f32 {
+        self.0
+    }
+}
+
+/// The damage a fall of `distance` blocks deals in vanilla.
+#[must_use]
+pub fn fall_damage(distance: f32) -> f32 {
+    (distance - SAFE_FALL_DISTANCE).ceil().max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FallDistance, fall_damage};
+
+    #[test]
+    fn falls_are_measured_from_the_highest_point() {
+        let mut fall = FallDistance::default();
+
+        // jumping up first does not make the fall any longer
+        assert_eq!(fall.update(1.0, false), None);
+        assert_eq!(fall.update(-2.5, false), None);
+        assert_eq!(fall.update(-4.0, false), None);
+        assert_eq!(fall.get(), 6.5);
+
+        assert_eq!(fall.update(-0.5, true), Some(6.5));
+        assert_eq!(fall.get(), 0.0);
+
+        // walking around on the ground is not a fall
+        assert_eq!(fall.update(0.0, true), None);
+    }
+
+    #[test]
+    fn short_falls_do_not_hurt() {
+        assert_eq!(fall_damage(0.5), 0.0);
+        assert_eq!(fall_damage(3.0), 0.0);
+        assert_eq!(fall_damage(3.2), 1.0);
+        assert_eq!(fall_damage(6.5), 4.0);
+    }
+}
@@ -0,0 +1,201 @@
+//! Locking players in place, e.g. during a countdown.
+
+use flecs_ecs::prelude::*;
+use hyperion_utils::EntityExt;
+use tracing::warn;
+use valence_ident::ident;
+use valence_protocol::{
+    VarInt,
+    packets::{play, play::entity_attributes_s2c::AttributeProperty},
+};
+
+use crate::{
+    net::{Compose, NetworkStreamRef},
+    simulation::{Pitch, Position, Yaw, teleport::PendingTeleport},
+    system_registry::{SystemId, TELEPORT},
+};
+
+/// While present, movement from the client is discarded and the player is snapped back to their
+/// position. Attacks and block interactions are ignored as well.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct Frozen;
+
+/// The vanilla base movement speed of a player.
+const BASE_MOVEMENT_SPEED: f64 = 0.1;
+
+/// Freezes a player. Calling this on a player that is already frozen does nothing.
+pub fn freeze(entity: EntityView<'_>) {
+    if entity.has::<Frozen>() {
+        return;
+    }
+
+    entity.add::<Frozen>();
+
+    // without this the client would predict movement and then be snapped back every tick
+    send_movement_speed(entity, 0.0);
+}
+
+/// Unfreezes a player. Calling this on a player that is not frozen does nothing.
+///
+/// Their movement speed is reset to the vanilla base, so games that change it must send it again.
+pub fn unfreeze(entity: EntityView<'_>) {
+    if !entity.has::<Frozen>() {
+        return;
+    }
+
+    entity.remove::<Frozen>();
+
+    send_movement_speed(entity, BASE_MOVEMENT_SPEED);
+}
+
+fn send_movement_speed(entity: EntityView<'_>, speed: f64) {
+    let world = entity.world();
+
+    let pkt = play::EntityAttributesS2c {
+        entity_id: VarInt(entity.minecraft_id()),
+        properties: vec![AttributeProperty {
+            key: ident!("minecraft:generic.movement_speed").into(),
+            value: speed,
+            modifiers: vec![],
+        }],
+    };
+
+    world.get::<&Compose>(|compose| {
+        entity.get::<&NetworkStreamRef>(|&io| {
+            if let Err(e) = compose.unicast(&pkt, io, TELEPORT, &world) {
+                warn!("failed to send movement speed: {e}");
+            }
+        });
+    });
+}
+
+/// The teleport used to put a frozen player back where they are supposed to be.
+#[must_use]
+pub fn snap_back(position: &Position, yaw: &Yaw, pitch: &Pitch, tick: i64) -> PendingTeleport {
+    PendingTeleport::new(**position, **yaw, **pitch, tick)
+}
+
+/// Called when a player sends movement. If they are frozen, they are teleported back to where
+/// they are instead.
+///
+/// Returns `true` if the player is frozen, in which case the movement must be discarded.
+pub fn hold_in_place(
+    entity: EntityView<'_>,
+    compose: &Compose,
+    io: NetworkStreamRef,
+    position: &Position,
+    yaw: &Yaw,
+    pitch: &Pitch,
+    system_id: SystemId,
+) -> bool {
+    if !entity.has::<Frozen>() {
+        return false;
+    }
+
+    let correction = snap_back(position, yaw, pitch, compose.global().tick);
+
+    if let Err(e) = compose.unicast(&correction.packet(), io, system_id, &entity.world()) {
+        warn!("failed to snap back frozen player: {e}");
+    }
+
+    entity.set(correction);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use glam::Vec3;
+
+    use super::{Frozen, freeze, hold_in_place, snap_back, unfreeze};
+    use crate::{
+        net::{Compose, NetworkStreamRef},
+        simulation::{Pitch, Position, Yaw, teleport::PendingTeleport},
+        system_registry::SystemId,
+    };
+
+    const SYSTEM_ID: SystemId = SystemId(0);
+
+    fn world() -> (World, Entity) {
+        let world = World::new();
+        world.set(Compose::for_tests());
+
+        let player = world
+            .entity()
+            .set(Position::from(Vec3::new(1.0, 64.0, -3.0)))
+            .set(Yaw::default())
+            .set(Pitch::default())
+            .set(NetworkStreamRef::new(1))
+            .id();
+
+        (world, player)
+    }
+
+    fn unicasts(world: &World) -> u64 {
+        world.get::<&Compose>(|compose| compose.io_buf().stats().unicast.frames)
+    }
+
+    /// What a movement packet from `player` does, as `Some(correction)` if it was discarded.
+    fn move_player(world: &World, player: Entity) -> Option<PendingTeleport> {
+        let player = world.entity_from_id(player);
+
+        let (io, position, yaw, pitch) = player
+            .get::<(&NetworkStreamRef, &Position, &Yaw, &Pitch)>(
+                |(&io, &position, &yaw, &pitch)| (io, position, yaw, pitch),
+            );
+
+        let held = world.get::<&Compose>(|compose| {
+            hold_in_place(player, compose, io, &position, &yaw, &pitch, SYSTEM_ID)
+        });
+
+        held.then(|| player.get::<&PendingTeleport>(|pending| *pending))
+    }
+
+    #[test]
+    fn snap_back_returns_to_current_position() {
+        let position = Position::from(Vec3::new(1.0, 64.0, -3.0));
+        let yaw = Yaw::default();
+        let pitch = Pitch::default();
+
+        let correction = snap_back(&position, &yaw, &pitch, 7);
+
+        assert_eq!(correction.destination, *position);
+        assert_eq!(correction.sent_tick, 7);
+        assert_eq!(correction.packet().position, position.as_dvec3());
+    }
+
+    #[test]
+    fn moving_while_frozen_is_corrected_with_a_teleport() {
+        let (world, player) = world();
+
+        assert_eq!(move_player(&world, player), None);
+        assert_eq!(unicasts(&world), 0);
+
+        freeze(world.entity_from_id(player));
+        let sent = unicasts(&world);
+
+        let correction = move_player(&world, player).unwrap();
+
+        assert_eq!(correction.destination, Vec3::new(1.0, 64.0, -3.0));
+        assert_eq!(unicasts(&world), sent + 1);
+
+        unfreeze(world.entity_from_id(player));
+        world.entity_from_id(player).remove::<PendingTeleport>();
+
+        assert_eq!(move_player(&world, player), None);
+    }
+
+    #[test]
+    fn freezing_twice_is_the_same_as_once() {
+        let (world, player) = world();
+
+        freeze(world.entity_from_id(player));
+        freeze(world.entity_from_id(player));
+        assert_eq!(unicasts(&world), 1);
+
+        unfreeze(world.entity_from_id(player));
+        unfreeze(world.entity_from_id(player));
+        assert_eq!(unicasts(&world), 2);
+        assert!(!world.entity_from_id(player).has::<Frozen>());
+    }
+}
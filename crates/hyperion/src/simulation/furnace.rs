@@ -0,0 +1,450 @@
+//! Furnace blocks, which smelt with the recipes in [`SmeltingRegistry`], and the furnace window.
+//!
+//! Block entities are not read from the world yet, so a furnace starts out empty the first time it
+//! is opened and is forgotten once its block is gone. Hoppers are not supported; items only go in
+//! and out through the window, where clicking a stack moves it between the furnace and the
+//! player's inventory.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use flecs_ecs::prelude::*;
+use glam::IVec3;
+use hyperion_crafting::{SmeltingRegistry, burn_ticks};
+use hyperion_inventory::{Furnace, PlayerInventory};
+use parking_lot::Mutex;
+use tracing::warn;
+use valence_generated::block::{BlockKind, PropName, PropValue};
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::{
+        play,
+        play::{click_slot_c2s::ClickMode, open_screen_s2c::WindowType},
+    },
+    text::IntoText,
+};
+
+use crate::{
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::{
+        Xp,
+        blocks::Blocks,
+        handlers::PacketSwitchQuery,
+        menu::{self, OpenMenu},
+    },
+    system_registry::FURNACE,
+};
+
+/// The furnace's own slots come first in its window.
+const FURNACE_SLOTS: u16 = 3;
+
+/// Every furnace in use, by position.
+#[derive(Component, Debug, Default)]
+pub struct Furnaces {
+    // packet handlers run in parallel, so they share the furnaces through a lock
+    furnaces: Mutex<HashMap<IVec3, Furnace>>,
+}
+
+impl Furnaces {
+    /// Runs `f` on the furnace at `position`, creating an empty one if there is none yet.
+    pub fn with<R>(&self, position: IVec3, f: impl FnOnce(&mut Furnace) -> R) -> R {
+        let mut furnaces = self.furnaces.lock();
+        f(furnaces.entry(position).or_default())
+    }
+
+    /// Runs `f` on the furnace at `position` if there is one.
+    pub fn with_existing<R>(
+        &self,
+        position: IVec3,
+        f: impl FnOnce(&mut Furnace) -> R,
+    ) -> Option<R> {
+        let mut furnaces = self.furnaces.lock();
+        furnaces.get_mut(&position).map(f)
+    }
+
+    /// Ticks every furnace, lighting up the blocks of those that are burning. Furnaces whose block
+    /// has been replaced are dropped along with their contents.
+    fn tick(&self, blocks: &mut Blocks, registry: &SmeltingRegistry) {
+        self.furnaces.lock().retain(|&position, furnace| {
+            let Some(block) = blocks.get_block(position) else {
+                // the chunk is not loaded
+                return true;
+            };
+
+            if block.to_kind() != BlockKind::Furnace {
+                return false;
+            }
+
+            furnace.tick(registry);
+
+            let lit = if furnace.is_burning() {
+                PropValue::True
+            } else {
+                PropValue::False
+            };
+
+            if block.get(PropName::Lit) != Some(lit) {
+                if let Err(e) = blocks.set_block(position, block.set(PropName::Lit, lit)) {
+                    warn!("failed to light furnace at {position}: {e:?}");
+                }
+            }
+
+            true
+        });
+    }
+}
+
+/// The furnace window a player has open.
+#[derive(Component, Clone, Debug)]
+pub struct OpenFurnace {
+    pub window_id: u8,
+    pub position: IVec3,
+    /// What the client was last sent, so only changes are sent each tick.
+    sent_properties: [i16; 4],
+    sent_slots: [ItemStack; 3],
+}
+
+impl OpenFurnace {
+    fn contents_packet(&self, inventory: &PlayerInventory) -> play::InventoryS2c<'static> {
+        menu::window_contents(self.window_id, &self.sent_slots, inventory)
+    }
+
+    fn property_packet(&self, property: usize) -> play::ScreenHandlerPropertyUpdateS2c {
+        play::ScreenHandlerPropertyUpdateS2c {
+            window_id: self.window_id,
+            property: i16::try_from(property).unwrap(),
+            value: self.sent_properties[property],
+        }
+    }
+
+    /// Brings the client up to date with `furnace`, sending only what changed.
+    fn sync(
+        &mut self,
+        furnace: &Furnace,
+        io: NetworkStreamRef,
+        compose: &Compose,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        let mut bundle = DataBundle::new(compose);
+
+        for (property, value) in furnace.properties().into_iter().enumerate() {
+            if self.sent_properties[property] != value {
+                self.sent_properties[property] = value;
+                bundle.add_packet(&self.property_packet(property), world)?;
+            }
+        }
+
+        for (slot, stack) in furnace.inventory.slots().iter().enumerate() {
+            if self.sent_slots[slot] != *stack {
+                self.sent_slots[slot] = stack.clone();
+
+                let pkt = play::ScreenHandlerSlotUpdateS2c {
+                    window_id: i8::try_from(self.window_id).unwrap(),
+                    state_id: VarInt::default(),
+                    slot_idx: i16::try_from(slot).unwrap(),
+                    slot_data: Cow::Borrowed(stack),
+                };
+
+                bundle.add_packet(&pkt, world)?;
+            }
+        }
+
+        bundle.send(world, io, FURNACE)
+    }
+}
+
+/// Opens the furnace at `position` for the player who right-clicked it.
+pub fn open_furnace(query: &mut PacketSwitchQuery<'_>, position: IVec3) -> anyhow::Result<()> {
+    let (sent_slots, sent_properties) = query.world.get::<&Furnaces>(|furnaces| {
+        furnaces.with(position, |furnace| {
+            (furnace.inventory.slots().clone(), furnace.properties())
+        })
+    });
+
+    let open = OpenFurnace {
+        window_id: menu::next_window_id(),
+        position,
+        sent_properties,
+        sent_slots,
+    };
+
+    let mut bundle = DataBundle::new(query.compose);
+
+    bundle.add_packet(
+        &play::OpenScreenS2c {
+            window_id: VarInt(i32::from(open.window_id)),
+            window_type: WindowType::Furnace,
+            window_title: "Furnace".into_cow_text(),
+        },
+        query.world,
+    )?;
+
+    bundle.add_packet(&open.contents_packet(query.inventory), query.world)?;
+
+    for property in 0..sent_properties.len() {
+        bundle.add_packet(&open.property_packet(property), query.world)?;
+    }
+
+    bundle.send(query.world, query.io_ref, FURNACE)?;
+
+    // the furnace window replaces any menu on the client
+    query.view.remove::<OpenMenu>();
+    query.view.set(open);
+
+    Ok(())
+}
+
+/// Handles a click in an open furnace window.
+pub fn click_furnace(
+    query: &mut PacketSwitchQuery<'_>,
+    pkt: &play::ClickSlotC2s,
+    open: &OpenFurnace,
+) -> anyhow::Result<()> {
+    let clicked = matches!(pkt.mode, ClickMode::Click | ClickMode::ShiftClick);
+    let inventory = &mut *query.inventory;
+
+    let (experience, slots) = query.world.get::<&SmeltingRegistry>(|registry| {
+        query.world.get::<&Furnaces>(|furnaces| {
+            furnaces.with(open.position, |furnace| {
+                let experience = if clicked {
+                    move_stack(furnace, inventory, pkt.slot_idx, registry)
+                } else {
+                    0
+                };
+
+                (experience, furnace.inventory.slots().clone())
+            })
+        })
+    });
+
+    if experience > 0 {
+        query.view.try_get::<&mut Xp>(|xp| {
+            xp.amount = xp.amount.saturating_add(experience);
+        });
+    }
+
+    // the window is re-sent whatever happened, undoing what the client predicted
+    let refreshed = OpenFurnace {
+        sent_slots: slots,
+        ..open.clone()
+    };
+
+    let mut bundle = DataBundle::new(query.compose);
+    bundle.add_packet(&refreshed.contents_packet(query.inventory), query.world)?;
+    bundle.add_packet(&menu::reset_cursor(), query.world)?;
+    bundle.send(query.world, query.io_ref, FURNACE)?;
+
+    query.view.set(refreshed);
+
+    Ok(())
+}
+
+/// Moves the stack in the clicked window slot between the furnace and the player's inventory.
+/// Items from the inventory go into the input slot if they can be smelted, or else the fuel slot
+/// if they burn.
+///
+/// Returns the experience earned by taking smelted items out.
+fn move_stack(
+    furnace: &mut Furnace,
+    inventory: &mut PlayerInventory,
+    slot_idx: i16,
+    registry: &SmeltingRegistry,
+) -> u16 {
+    if let Some(slot) = menu::player_slot(slot_idx, FURNACE_SLOTS) {
+        let stack = inventory.get(slot).unwrap().clone();
+
+        let target = if registry.get(&stack).is_some() {
+            Furnace::INPUT_SLOT
+        } else if !stack.is_empty() && burn_ticks(stack.item).is_some() {
+            Furnace::FUEL_SLOT
+        } else {
+            return 0;
+        };
+
+        let rest = merge(&mut furnace.inventory.get_mut(target).unwrap(), stack);
+        inventory.set(slot, rest).unwrap();
+
+        return 0;
+    }
+
+    let Ok(slot) = u16::try_from(slot_idx) else {
+        return 0;
+    };
+
+    let Ok(stack) = furnace.inventory.get(slot).cloned() else {
+        return 0;
+    };
+
+    if stack.is_empty() {
+        return 0;
+    }
+
+    let rest = inventory.try_add_item(stack).remaining;
+    furnace
+        .inventory
+        .set(slot, rest.unwrap_or(ItemStack::EMPTY))
+        .unwrap();
+
+    if slot == Furnace::OUTPUT_SLOT {
+        furnace.take_experience()
+    } else {
+        0
+    }
+}
+
+/// Puts as much of `stack` into `slot` as fits, returning the rest.
+fn merge(slot: &mut ItemStack, stack: ItemStack) -> ItemStack {
+    if slot.is_empty() {
+        *slot = stack;
+        return ItemStack::EMPTY;
+    }
+
+    if slot.item != stack.item || slot.nbt != stack.nbt {
+        return stack;
+    }
+
+    let moved = (slot.item.max_stack() - slot.count).min(stack.count);
+    slot.count += moved;
+
+    if moved == stack.count {
+        ItemStack::EMPTY
+    } else {
+        stack.with_count(stack.count - moved)
+    }
+}
+
+#[derive(Component)]
+pub struct FurnaceModule;
+
+impl Module for FurnaceModule {
+    fn module(world: &World) {
+        world.component::<Furnaces>();
+        world.component::<OpenFurnace>();
+
+        world.set(Furnaces::default());
+
+        system!(
+            "tick_furnaces",
+            world,
+            &mut Blocks($),
+            &Furnaces($),
+            &SmeltingRegistry($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each(|(blocks, furnaces, registry)| {
+            furnaces.tick(blocks, registry);
+        });
+
+        system!(
+            "sync_open_furnaces",
+            world,
+            &Compose($),
+            &Furnaces($),
+            &NetworkStreamRef,
+            &mut OpenFurnace,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, row, (compose, furnaces, &io, open)| {
+            let world = it.world();
+
+            let result = furnaces.with_existing(open.position, |furnace| {
+                open.sync(furnace, io, compose, &world)
+            });
+
+            match result {
+                Some(Ok(())) => {}
+                Some(Err(e)) => warn!("failed to sync furnace window: {e}"),
+                None => {
+                    // the furnace was broken
+                    let pkt = play::CloseScreenS2c {
+                        window_id: open.window_id,
+                    };
+
+                    if let Err(e) = compose.unicast(&pkt, io, FURNACE, &world) {
+                        warn!("failed to close furnace window: {e}");
+                    }
+
+                    it.entity(row).remove::<OpenFurnace>();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_crafting::{SmeltingRecipe, SmeltingRegistry};
+    use hyperion_inventory::{Furnace, PlayerInventory};
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::move_stack;
+
+    fn registry() -> SmeltingRegistry {
+        let mut registry = SmeltingRegistry::default();
+
+        registry.register(
+            "minecraft:iron_ingot_from_smelting_iron_ore".to_owned(),
+            SmeltingRecipe::new(
+                ItemKind::IronOre,
+                ItemStack::new(ItemKind::IronIngot, 1, None),
+            ),
+        );
+
+        registry
+    }
+
+    #[test]
+    fn clicked_stacks_go_to_the_right_furnace_slot() {
+        let registry = registry();
+        let mut furnace = Furnace::default();
+        let mut inventory = PlayerInventory::default();
+
+        inventory
+            .set(9, ItemStack::new(ItemKind::IronOre, 8, None))
+            .unwrap();
+        inventory
+            .set(36, ItemStack::new(ItemKind::Coal, 1, None))
+            .unwrap();
+        inventory
+            .set(10, ItemStack::new(ItemKind::Dirt, 1, None))
+            .unwrap();
+
+        // window slot 3 is the first slot of the main inventory, 30 the first of the hotbar
+        move_stack(&mut furnace, &mut inventory, 3, &registry);
+        move_stack(&mut furnace, &mut inventory, 30, &registry);
+        move_stack(&mut furnace, &mut inventory, 4, &registry);
+
+        let slots = furnace.inventory.slots();
+        assert_eq!(slots[0], ItemStack::new(ItemKind::IronOre, 8, None));
+        assert_eq!(slots[1], ItemStack::new(ItemKind::Coal, 1, None));
+        assert!(slots[2].is_empty());
+
+        // dirt neither smelts nor burns
+        assert_eq!(inventory.get(10).unwrap().item, ItemKind::Dirt);
+        assert!(inventory.get(9).unwrap().is_empty());
+    }
+
+    #[test]
+    fn output_is_taken_into_the_inventory() {
+        let registry = registry();
+        let mut furnace = Furnace::default();
+        let mut inventory = PlayerInventory::default();
+
+        let ingots = ItemStack::new(ItemKind::IronIngot, 4, None);
+        furnace
+            .inventory
+            .set(Furnace::OUTPUT_SLOT, ingots.clone())
+            .unwrap();
+
+        move_stack(&mut furnace, &mut inventory, 2, &registry);
+
+        assert!(
+            furnace
+                .inventory
+                .get(Furnace::OUTPUT_SLOT)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(*inventory.get(36).unwrap(), ingots);
+    }
+}
@@ -0,0 +1,155 @@
+//! The game mode of each player and switching between them.
+//!
+//! The client applies most game mode rules itself, such as not being able to break blocks in
+//! adventure mode, but nothing stops a modified client from sending the packets anyway. Systems
+//! that act on those packets should check [`GameMode`] themselves.
+
+use std::borrow::Cow;
+
+use derive_more::{Deref, From};
+use flecs_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::packets::{
+    play,
+    play::{game_state_change_s2c::GameEventKind, player_abilities_s2c::PlayerAbilitiesFlags},
+};
+
+use crate::{
+    egress::player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::Uuid,
+    system_registry::GAME_MODE,
+};
+
+/// The game mode of a player. Players join in survival.
+#[derive(Component, Copy, Clone, Debug, Default, Deref, From, PartialEq, Eq)]
+pub struct GameMode(pub valence_protocol::GameMode);
+
+impl GameMode {
+    /// Whether the player can break blocks, which they cannot in adventure and spectator mode.
+    #[must_use]
+    pub const fn can_break_blocks(self) -> bool {
+        matches!(
+            self.0,
+            valence_protocol::GameMode::Survival | valence_protocol::GameMode::Creative
+        )
+    }
+
+    /// Whether the player can attack other entities, which spectators cannot.
+    #[must_use]
+    pub const fn can_attack(self) -> bool {
+        !matches!(self.0, valence_protocol::GameMode::Spectator)
+    }
+
+    /// Whether the player can fly, which they can in creative and spectator mode.
+    #[must_use]
+    pub const fn can_fly(self) -> bool {
+        matches!(
+            self.0,
+            valence_protocol::GameMode::Creative | valence_protocol::GameMode::Spectator
+        )
+    }
+
+    /// The abilities vanilla gives players in this game mode, at the default flying speed.
+    #[must_use]
+    pub const fn abilities(self) -> play::PlayerAbilitiesS2c {
+        let creative = matches!(self.0, valence_protocol::GameMode::Creative);
+        let spectator = matches!(self.0, valence_protocol::GameMode::Spectator);
+
+        play::PlayerAbilitiesS2c {
+            flags: PlayerAbilitiesFlags::default()
+                .with_invulnerable(creative || spectator)
+                // spectators cannot stop flying
+                .with_flying(spectator)
+                .with_allow_flying(self.can_fly())
+                .with_instant_break(creative),
+            flying_speed: 0.05,
+            fov_modifier: 0.1,
+        }
+    }
+
+    /// The packet that switches the client to this game mode.
+    #[must_use]
+    pub fn change_packet(self) -> play::GameStateChangeS2c {
+        // the value is the id of the new game mode
+        play::GameStateChangeS2c {
+            kind: GameEventKind::ChangeGameMode,
+            value: f32::from(self.0 as u8),
+        }
+    }
+}
+
+/// Switches a player to `mode`, updating their abilities and how other players see them in the
+/// tab list.
+pub fn set_game_mode(entity: EntityView<'_>, mode: impl Into<GameMode>) {
+    let mode = mode.into();
+    let world = entity.world();
+
+    world.get::<&Compose>(|compose| {
+        entity.get::<(&NetworkStreamRef, &Uuid)>(|(&io, uuid)| {
+            if let Err(e) = send_game_mode(compose, io, mode, &world) {
+                warn!("failed to send game mode: {e}");
+            }
+
+            let entry = PlayerListEntry {
+                player_uuid: uuid.0,
+                game_mode: mode.0,
+                ..Default::default()
+            };
+
+            let pkt = PlayerListS2c {
+                actions: PlayerListActions::default().with_update_game_mode(true),
+                entries: Cow::Owned(vec![entry]),
+            };
+
+            if let Err(e) = compose.broadcast(&pkt, GAME_MODE).send(&world) {
+                warn!("failed to send game mode to the tab list: {e}");
+            }
+        });
+    });
+
+    entity.set(mode);
+}
+
+fn send_game_mode(
+    compose: &Compose,
+    io: NetworkStreamRef,
+    mode: GameMode,
+    world: &World,
+) -> anyhow::Result<()> {
+    let mut bundle = DataBundle::new(compose);
+    bundle.add_packet(&mode.change_packet(), world)?;
+    bundle.add_packet(&mode.abilities(), world)?;
+    bundle.send(world, io, GAME_MODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::GameMode::{Adventure, Creative, Spectator, Survival};
+
+    use super::GameMode;
+
+    #[test]
+    fn modes_allow_what_vanilla_allows() {
+        assert!(GameMode(Survival).can_break_blocks());
+        assert!(!GameMode(Adventure).can_break_blocks());
+        assert!(!GameMode(Spectator).can_break_blocks());
+
+        assert!(GameMode(Adventure).can_attack());
+        assert!(!GameMode(Spectator).can_attack());
+
+        assert!(!GameMode(Survival).can_fly());
+        assert!(GameMode(Creative).can_fly());
+    }
+
+    #[test]
+    fn spectators_are_always_flying() {
+        let abilities = GameMode(Spectator).abilities();
+
+        assert!(abilities.flags.flying());
+        assert!(abilities.flags.allow_flying());
+        assert!(!abilities.flags.instant_break());
+
+        assert!(!GameMode(Survival).abilities().flags.allow_flying());
+    }
+}
@@ -4,16 +4,17 @@ use std::borrow::Cow;
 
 use anyhow::{Context, bail};
 use bvh_region::aabb::Aabb;
-use flecs_ecs::core::{Entity, EntityView, World};
+use flecs_ecs::core::{Entity, EntityView, EntityViewGet, World};
 use glam::{IVec3, Vec3};
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
 use tracing::{info, instrument, trace, warn};
 use valence_generated::block::{BlockKind, BlockState, PropName};
 use valence_protocol::{
     Decode, Hand, ItemStack, Packet, VarInt,
     packets::play::{
-        self, client_command_c2s::ClientCommand, player_action_c2s::PlayerAction,
-        player_interact_entity_c2s::EntityInteraction,
+        self, click_slot_c2s::ClickMode, client_command_c2s::ClientCommand,
+        player_action_c2s::PlayerAction, player_interact_entity_c2s::EntityInteraction,
         player_position_look_s2c::PlayerPositionLookFlags,
     },
 };
@@ -22,14 +23,26 @@ use valence_text::IntoText;
 use super::{
     ConfirmBlockSequences, EntitySize, Position,
     animation::{self, ActiveAnimation},
+    anvil::{self, OpenAnvil},
     block_bounds,
     blocks::Blocks,
-    metadata::Pose,
+    container::{self, OpenInventory},
+    crafting_table::{self, OpenCraftingTable},
+    dropped_item,
+    fall::FallDistance,
+    frozen::{self, Frozen},
+    furnace::{self, OpenFurnace},
+    keep_alive,
+    menu::{self, OpenMenu},
+    metadata::{EntityFlags, Pose},
+    recipe_book,
+    teleport::{self, PendingTeleport},
 };
 use crate::{
+    l10n,
     net::{Compose, NetworkStreamRef, decoder::BorrowedPacketFrame},
     simulation::{Pitch, Yaw, aabb, event, event::PluginMessage},
-    storage::{CommandCompletionRequest, Events, GlobalEventHandlers},
+    storage::{ChatEvent, CommandCompletionRequest, Events, GlobalEventHandlers},
     system_registry::SystemId,
 };
 
@@ -40,14 +53,16 @@ fn full(query: &mut PacketSwitchQuery<'_>, mut data: &[u8]) -> anyhow::Result<()
         position,
         yaw,
         pitch,
-        ..
+        on_ground,
     } = pkt;
 
     // check to see if the player is moving too fast
     // if they are, ignore the packet
 
     let position = position.as_vec3();
+    let from = query.position.y;
     change_position_or_correct_client(query, position);
+    track_fall(query, query.position.y - from, on_ground);
 
     query.yaw.yaw = yaw;
     query.pitch.pitch = pitch;
@@ -55,6 +70,24 @@ fn full(query: &mut PacketSwitchQuery<'_>, mut data: &[u8]) -> anyhow::Result<()
     Ok(())
 }
 
+/// Adds a move of `dy` blocks up to how far the player has fallen, firing an [`event::Fall`] if
+/// they landed.
+fn track_fall(query: &PacketSwitchQuery<'_>, dy: f32, on_ground: bool) {
+    let landed = query
+        .view
+        .try_get::<&mut FallDistance>(|fall| fall.update(dy, on_ground))
+        .flatten();
+
+    if let Some(distance) = landed {
+        let event = event::Fall {
+            entity: query.id,
+            distance,
+        };
+
+        query.events.push(event, query.world);
+    }
+}
+
 // #[instrument(skip_all)]
 fn change_position_or_correct_client(query: &mut PacketSwitchQuery<'_>, proposed: Vec3) {
     let pose = &mut *query.position;
@@ -165,11 +198,17 @@ fn has_block_collision(position: &Vec3, size: EntitySize, blocks: &Blocks) -> bo
 fn look_and_on_ground(mut data: &[u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
     let pkt = play::LookAndOnGroundC2s::decode(&mut data)?;
 
-    let play::LookAndOnGroundC2s { yaw, pitch, .. } = pkt;
+    let play::LookAndOnGroundC2s {
+        yaw,
+        pitch,
+        on_ground,
+    } = pkt;
 
     **query.yaw = yaw;
     **query.pitch = pitch;
 
+    track_fall(query, 0.0, on_ground);
+
     Ok(())
 }
 
@@ -179,9 +218,14 @@ fn position_and_on_ground(
 ) -> anyhow::Result<()> {
     let pkt = play::PositionAndOnGroundC2s::decode(&mut data)?;
 
-    let play::PositionAndOnGroundC2s { position, .. } = pkt;
+    let play::PositionAndOnGroundC2s {
+        position,
+        on_ground,
+    } = pkt;
 
+    let from = query.position.y;
     change_position_or_correct_client(query, position.as_vec3());
+    track_fall(query, query.position.y - from, on_ground);
 
     Ok(())
 }
@@ -205,6 +249,9 @@ fn chat_command(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyho
 
     let command = pkt.command.0;
 
+    #[cfg(feature = "metrics")]
+    crate::metrics::METRICS.record_command();
+
     query.events.push(
         event::Command {
             raw: command,
@@ -240,17 +287,86 @@ fn player_interact_entity(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> any
         return Ok(());
     }
 
+    if query.view.has::<Frozen>() {
+        return Ok(());
+    }
+
     let target = packet.entity_id.0;
     let target = Entity::from_minecraft_id(target);
 
-    query.events.push(
-        event::AttackEntity {
-            origin: query.id,
-            target,
-            damage: 1.0,
-        },
-        query.world,
-    );
+    let sprinting = *query.flags & EntityFlags::SPRINTING == EntityFlags::SPRINTING;
+    let event = attack_event(query.id, target, query.inventory, **query.yaw, sprinting);
+
+    query.events.push(event, query.world);
+
+    Ok(())
+}
+
+/// Builds an [`event::AttackEntity`] from the attacker's state at the instant the attack packet
+/// is processed.
+///
+/// Attacks are always performed with the main hand, so the weapon is the currently held hotbar
+/// slot. Like in vanilla, the target is knocked back in the direction the attacker is facing.
+fn attack_event(
+    origin: Entity,
+    target: Entity,
+    inventory: &PlayerInventory,
+    yaw: f32,
+    sprinting: bool,
+) -> event::AttackEntity {
+    let weapon = inventory.get_cursor().clone();
+
+    let mut flags = event::AttackFlags::empty();
+    if sprinting {
+        flags |= event::AttackFlags::SPRINT;
+    } else if weapon.item.to_str().ends_with("_sword") {
+        flags |= event::AttackFlags::SWEEPING;
+    }
+
+    let yaw = yaw.to_radians();
+    let knockback = Vec3::new(-yaw.sin(), 0.0, yaw.cos());
+
+    event::AttackEntity {
+        origin,
+        target,
+        damage: 1.0,
+        weapon,
+        hand: Hand::Main,
+        knockback,
+        flags,
+    }
+}
+
+/// Whether a movement packet from the client should be applied.
+fn accept_movement(query: &PacketSwitchQuery<'_>) -> bool {
+    if query.view.has::<PendingTeleport>() {
+        // movement sent before the client confirmed a teleport is relative to where it was
+        // teleported from, so applying it would rubber-band the player
+        trace!("ignoring movement while a teleport is pending");
+        return false;
+    }
+
+    if frozen::hold_in_place(
+        query.view,
+        query.compose,
+        query.io_ref,
+        query.position,
+        query.yaw,
+        query.pitch,
+        query.system_id,
+    ) {
+        return false;
+    }
+
+    true
+}
+
+fn teleport_confirm(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let packet = play::TeleportConfirmC2s::decode(&mut data)?;
+
+    if !teleport::confirm(query.view, packet.teleport_id.0) {
+        trace!("ignoring a confirm for a teleport that is not pending");
+    }
 
     Ok(())
 }
@@ -269,20 +385,27 @@ pub struct PacketSwitchQuery<'a> {
     pub world: &'a World,
     pub blocks: &'a Blocks,
     pub pose: &'a mut Pose,
+    pub flags: &'a mut EntityFlags,
     pub confirm_block_sequences: &'a mut ConfirmBlockSequences,
     pub system_id: SystemId,
-    pub inventory: &'a mut hyperion_inventory::PlayerInventory,
+    pub inventory: &'a mut PlayerInventory,
     pub animation: &'a mut ActiveAnimation,
     pub crafting_registry: &'a hyperion_crafting::CraftingRegistry,
 }
 
 // i.e., shooting a bow, digging a block, etc
-fn player_action(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+fn player_action(mut data: &[u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
     let packet = play::PlayerActionC2s::decode(&mut data)?;
 
     let sequence = packet.sequence.0;
     let position = IVec3::new(packet.position.x, packet.position.y, packet.position.z);
 
+    if query.view.has::<Frozen>() {
+        // acknowledging without applying makes the client roll back its prediction
+        query.confirm_block_sequences.push(sequence);
+        return Ok(());
+    }
+
     match packet.action {
         PlayerAction::StopDestroyBlock => {
             let event = event::DestroyBlock {
@@ -293,6 +416,14 @@ fn player_action(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Resu
 
             query.events.push(event, query.world);
         }
+        PlayerAction::DropItem => {
+            let item = query.inventory.take_one_held();
+            throw_held(query, item);
+        }
+        PlayerAction::DropAllItems => {
+            let item = query.inventory.take_held();
+            throw_held(query, item);
+        }
         action => bail!("unimplemented {action:?}"),
     }
 
@@ -301,7 +432,17 @@ fn player_action(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Resu
     Ok(())
 }
 
-// for sneaking
+/// Throws `item`, which was taken from the hand of the player, the way they are looking.
+fn throw_held(query: &PacketSwitchQuery<'_>, item: ItemStack) {
+    if item.is_empty() {
+        return;
+    }
+
+    let drop = dropped_item::throw(item, **query.position, **query.yaw, **query.pitch);
+    query.events.push(drop, query.world);
+}
+
+// for sneaking and sprinting
 fn client_command(mut data: &[u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
     let packet = play::ClientCommandC2s::decode(&mut data)?;
 
@@ -312,9 +453,13 @@ fn client_command(mut data: &[u8], query: &mut PacketSwitchQuery<'_>) -> anyhow:
         ClientCommand::StopSneaking | ClientCommand::LeaveBed => {
             *query.pose = Pose::Standing;
         }
-        ClientCommand::StartSprinting
-        | ClientCommand::StopSprinting
-        | ClientCommand::StartJumpWithHorse
+        ClientCommand::StartSprinting => {
+            *query.flags |= EntityFlags::SPRINTING;
+        }
+        ClientCommand::StopSprinting => {
+            *query.flags &= !EntityFlags::SPRINTING;
+        }
+        ClientCommand::StartJumpWithHorse
         | ClientCommand::StopJumpWithHorse
         | ClientCommand::OpenHorseInventory
         | ClientCommand::StartFlyingWithElytra => {}
@@ -358,6 +503,10 @@ pub fn player_interact_block(
 
     query.confirm_block_sequences.push(packet.sequence.0);
 
+    if query.view.has::<Frozen>() {
+        return Ok(());
+    }
+
     let interacted_block_pos = packet.position;
     let interacted_block_pos_vec = IVec3::new(
         interacted_block_pos.x,
@@ -369,7 +518,22 @@ pub fn player_interact_block(
         return Ok(());
     };
 
-    if interacted_block.get(PropName::Open).is_some() {
+    let kind = interacted_block.to_kind();
+    let sneaking = *query.pose == Pose::Sneaking;
+
+    if kind == BlockKind::Furnace && !sneaking {
+        furnace::open_furnace(query, interacted_block_pos_vec)?;
+    } else if matches!(
+        kind,
+        BlockKind::Anvil | BlockKind::ChippedAnvil | BlockKind::DamagedAnvil
+    ) && !sneaking
+    {
+        anvil::open_anvil(query)?;
+    } else if kind == BlockKind::CraftingTable && !sneaking {
+        crafting_table::open_crafting_table(query)?;
+    } else if kind == BlockKind::Chest && !sneaking {
+        container::open_chest(query, interacted_block_pos_vec)?;
+    } else if interacted_block.get(PropName::Open).is_some() {
         // Toggle the open state of a door
         // todo: place block instead of toggling door if the player is crouching and holding a
         // block
@@ -517,9 +681,82 @@ pub fn custom_payload(
 }
 
 // keywords: inventory
-fn click_slot(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+fn click_slot(mut data: &'static [u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
     let pkt = play::ClickSlotC2s::decode(&mut data)?;
 
+    let clicked_menu = query
+        .view
+        .try_get::<&OpenMenu>(|menu| {
+            (menu.window_id == pkt.window_id)
+                .then(|| (menu.clone(), menu.clicked_slot(pkt.slot_idx)))
+        })
+        .flatten();
+
+    let clicked_furnace = query
+        .view
+        .try_get::<&OpenFurnace>(|open| (open.window_id == pkt.window_id).then(|| open.clone()))
+        .flatten();
+
+    if let Some(open) = clicked_furnace {
+        return furnace::click_furnace(query, &pkt, &open);
+    }
+
+    let clicked_anvil = query
+        .view
+        .try_get::<&OpenAnvil>(|open| (open.window_id == pkt.window_id).then(|| open.clone()))
+        .flatten();
+
+    if let Some(open) = clicked_anvil {
+        return anvil::click_anvil(query, &pkt, &open);
+    }
+
+    let clicked_crafting_table = query
+        .view
+        .try_get::<&OpenCraftingTable>(|open| {
+            (open.window_id == pkt.window_id).then(|| open.clone())
+        })
+        .flatten();
+
+    if let Some(open) = clicked_crafting_table {
+        return crafting_table::click_crafting_table(query, &pkt, &open);
+    }
+
+    let clicked_container = query
+        .view
+        .try_get::<&OpenInventory>(|open| open.window_id == pkt.window_id)
+        .unwrap_or(false);
+
+    if clicked_container {
+        container::click_container(query, &pkt);
+        return Ok(());
+    }
+
+    if let Some((menu, click)) = clicked_menu {
+        // menu items are buttons; nothing is ever picked up or moved
+        menu.refresh(query.io_ref, query.compose, query.world);
+
+        let reset_cursor = play::ScreenHandlerSlotUpdateS2c {
+            window_id: -1,
+            state_id: VarInt::default(),
+            slot_idx: -1,
+            slot_data: Cow::Borrowed(&ItemStack::EMPTY),
+        };
+
+        query
+            .compose
+            .unicast(&reset_cursor, query.io_ref, query.system_id, query.world)?;
+
+        if let Some(click) = click {
+            (menu.on_click)(query, &click);
+        }
+
+        return Ok(());
+    }
+
+    if !query.inventory.validate_state(menu::client_state_id(&pkt)) {
+        return resync_inventory(query);
+    }
+
     let to_send_pkt = play::ScreenHandlerSlotUpdateS2c {
         window_id: -1,
         state_id: VarInt::default(),
@@ -532,13 +769,36 @@ fn click_slot(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyhow:
         .compose
         .unicast(&to_send_pkt, query.io_ref, query.system_id, query.world)?;
 
+    let takes_result = pkt.window_id == 0 && pkt.slot_idx == 0;
+
+    let dropped = match pkt.mode {
+        ClickMode::ShiftClick if takes_result => {
+            query
+                .inventory
+                .take_crafting_result_bulk(query.crafting_registry)
+                .dropped
+        }
+        ClickMode::Click if takes_result => take_crafting_result(query)?,
+        _ => Vec::new(),
+    };
+
+    for item in dropped {
+        let location = **query.position;
+        query
+            .events
+            .push(event::ItemDropEvent::new(item, location), query.world);
+    }
+
+    // taken after crafting, which changes it
+    let state_id = menu::state_id(query.inventory);
+
     let slot_idx = u16::try_from(pkt.slot_idx).context("slot index is negative")?;
 
     let item_in_slot = query.inventory.get(slot_idx)?;
 
     let to_send_pkt = play::ScreenHandlerSlotUpdateS2c {
         window_id: 0,
-        state_id: VarInt::default(),
+        state_id,
         slot_idx: pkt.slot_idx,
         slot_data: Cow::Borrowed(item_in_slot),
     };
@@ -560,7 +820,7 @@ fn click_slot(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyhow:
 
     let set_item_pkt = play::ScreenHandlerSlotUpdateS2c {
         window_id: 0,
-        state_id: VarInt(0),
+        state_id,
         slot_idx: 0, // crafting result
         slot_data: Cow::Owned(item),
     };
@@ -572,10 +832,133 @@ fn click_slot(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyhow:
     Ok(())
 }
 
-fn chat_message(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
-    // todo: we could technically remove allocations &[u8] exists until end of tick
+/// Crafts the recipe in the inventory's own grid once, consuming the ingredients. The server does
+/// not track what is held on the cursor of the player's own inventory, so the result goes straight
+/// into the inventory instead of onto the cursor where the client put it. Returns whatever fits
+/// nowhere and has to be dropped.
+fn take_crafting_result(query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<Vec<ItemStack>> {
+    let crafted = query
+        .inventory
+        .take_crafting_result(query.crafting_registry);
+
+    let mut dropped = crafted.dropped;
+
+    if !crafted.result.is_empty() {
+        dropped.extend(query.inventory.try_add_item(crafted.result).remaining);
+    }
+
+    let reset_cursor = play::ScreenHandlerSlotUpdateS2c {
+        window_id: -1,
+        state_id: VarInt::default(),
+        slot_idx: -1,
+        slot_data: Cow::Borrowed(&ItemStack::EMPTY),
+    };
+
+    query
+        .compose
+        .unicast(&reset_cursor, query.io_ref, query.system_id, query.world)?;
+
+    Ok(dropped)
+}
+
+/// Sends the player their whole inventory, for when they clicked on an inventory that changed
+/// since the client last heard of it.
+///
+/// Clicks in the player's own inventory are not applied on the server, except for taking the
+/// crafting result, which goes into the inventory and clears the cursor. Nothing is ever carried
+/// on its cursor on the server, so the cursor is sent empty, dropping whatever the client
+/// predicted it holds.
+fn resync_inventory(query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let mut slots = query.inventory.slots().to_vec();
+    slots[0] = query.inventory.crafting_result(query.crafting_registry);
+
+    let pkt = play::InventoryS2c {
+        window_id: 0,
+        state_id: menu::state_id(query.inventory),
+        slots: Cow::Owned(slots),
+        carried_item: Cow::Borrowed(&ItemStack::EMPTY),
+    };
+
+    query
+        .compose
+        .unicast(&pkt, query.io_ref, query.system_id, query.world)?;
+
+    Ok(())
+}
+
+fn close_handled_screen(mut data: &[u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let pkt = play::CloseHandledScreenC2s::decode(&mut data)?;
+
+    let is_menu = query
+        .view
+        .try_get::<&OpenMenu>(|menu| i16::from(menu.window_id) == i16::from(pkt.window_id))
+        .unwrap_or(false);
+
+    if is_menu {
+        query.view.remove::<OpenMenu>();
+    }
+
+    let is_furnace = query
+        .view
+        .try_get::<&OpenFurnace>(|open| i16::from(open.window_id) == i16::from(pkt.window_id))
+        .unwrap_or(false);
+
+    if is_furnace {
+        query.view.remove::<OpenFurnace>();
+    }
+
+    let is_anvil = query
+        .view
+        .try_get::<&OpenAnvil>(|open| i16::from(open.window_id) == i16::from(pkt.window_id))
+        .unwrap_or(false);
+
+    if is_anvil {
+        anvil::close_anvil(query);
+    }
+
+    let is_crafting_table = query
+        .view
+        .try_get::<&OpenCraftingTable>(|open| i16::from(open.window_id) == i16::from(pkt.window_id))
+        .unwrap_or(false);
+
+    if is_crafting_table {
+        crafting_table::close_crafting_table(query);
+    }
+
+    let is_container = query
+        .view
+        .try_get::<&OpenInventory>(|open| i16::from(open.window_id) == i16::from(pkt.window_id))
+        .unwrap_or(false);
+
+    if is_container {
+        container::close_container(query);
+    }
+
+    Ok(())
+}
+
+fn chat_message(mut data: &'static [u8], query: &mut PacketSwitchQuery<'_>) -> anyhow::Result<()> {
     let pkt = play::ChatMessageC2s::decode(&mut data)?;
-    let msg = pkt.message.0;
+    let original = pkt.message.0;
+
+    let mut chat = ChatEvent {
+        message: original.to_owned(),
+        cancelled: false,
+    };
+
+    // handlers run before anything is sent, so they can keep the message from being broadcast
+    query.handlers.chat_message.trigger_all(query, &mut chat);
+
+    if chat.cancelled {
+        return Ok(());
+    }
+
+    // the packet outlives the event, so the message is only copied when a handler changed it
+    let msg = if chat.message == original {
+        Cow::Borrowed(original)
+    } else {
+        Cow::Owned(chat.message)
+    };
 
     query
         .events
@@ -584,6 +967,38 @@ fn chat_message(mut data: &'static [u8], query: &PacketSwitchQuery<'_>) -> anyho
     Ok(())
 }
 
+fn keep_alive(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let pkt = play::KeepAliveC2s::decode(&mut data)?;
+
+    keep_alive::answer(query.view, pkt.id, query.compose);
+
+    Ok(())
+}
+
+fn rename_item(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let pkt = play::RenameItemC2s::decode(&mut data)?;
+
+    anvil::rename_item(query, &pkt);
+
+    Ok(())
+}
+
+fn client_settings(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let pkt = play::ClientSettingsC2s::decode(&mut data)?;
+
+    l10n::update_locale(query.view, pkt.locale);
+
+    Ok(())
+}
+
+fn recipe_category_options(mut data: &[u8], query: &PacketSwitchQuery<'_>) -> anyhow::Result<()> {
+    let pkt = play::RecipeCategoryOptionsC2s::decode(&mut data)?;
+
+    recipe_book::recipe_category_options(query.view, &pkt);
+
+    Ok(())
+}
+
 pub fn request_command_completions(
     mut data: &'static [u8],
     query: &mut PacketSwitchQuery<'_>,
@@ -613,29 +1028,111 @@ pub fn packet_switch(
     let packet_id = raw.id;
     let data = raw.body;
 
+    #[cfg(feature = "metrics")]
+    crate::metrics::METRICS.record_packet(packet_id);
+
     // ideally we wouldn't have to do this. The lifetime is the same as the entire tick.
     // as the data is bump-allocated and reset occurs at the end of the tick
     let data: &'static [u8] = unsafe { core::mem::transmute(data) };
 
     match packet_id {
+        play::FullC2s::ID | play::PositionAndOnGroundC2s::ID | play::LookAndOnGroundC2s::ID
+            if !accept_movement(query) => {}
         play::ChatMessageC2s::ID => chat_message(data, query)?,
         play::ClickSlotC2s::ID => click_slot(data, query)?,
         play::ClientCommandC2s::ID => client_command(data, query)?,
+        play::ClientSettingsC2s::ID => client_settings(data, query)?,
+        play::CloseHandledScreenC2s::ID => close_handled_screen(data, query)?,
         play::CommandExecutionC2s::ID => chat_command(data, query)?,
         play::CreativeInventoryActionC2s::ID => creative_inventory_action(data, query)?,
         play::CustomPayloadC2s::ID => custom_payload(data, query)?,
         play::FullC2s::ID => full(query, data)?,
         play::HandSwingC2s::ID => hand_swing(data, query)?,
+        play::KeepAliveC2s::ID => keep_alive(data, query)?,
         play::LookAndOnGroundC2s::ID => look_and_on_ground(data, query)?,
         play::PlayerActionC2s::ID => player_action(data, query)?,
         play::PlayerInteractBlockC2s::ID => player_interact_block(data, query)?,
         play::PlayerInteractEntityC2s::ID => player_interact_entity(data, query)?,
         play::PlayerInteractItemC2s::ID => player_interact_item(data, query)?,
         play::PositionAndOnGroundC2s::ID => position_and_on_ground(query, data)?,
+        play::RecipeCategoryOptionsC2s::ID => recipe_category_options(data, query)?,
+        play::RenameItemC2s::ID => rename_item(data, query)?,
         play::RequestCommandCompletionsC2s::ID => request_command_completions(data, query)?,
+        play::TeleportConfirmC2s::ID => teleport_confirm(data, query)?,
         play::UpdateSelectedSlotC2s::ID => update_selected_slot(data, query)?,
         _ => trace!("unknown packet id: 0x{:02X}", packet_id),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::Entity;
+    use glam::Vec3;
+    use hyperion_inventory::PlayerInventory;
+    use hyperion_utils::EntityExt;
+    use valence_protocol::{Hand, ItemKind, ItemStack};
+
+    use super::attack_event;
+    use crate::simulation::event::AttackFlags;
+
+    #[test]
+    fn attack_weapon_is_captured_at_hit_time() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::GoldenSword, 1, None));
+
+        let origin = Entity::from_minecraft_id(1);
+        let target = Entity::from_minecraft_id(2);
+
+        let event = attack_event(origin, target, &inventory, 0.0, true);
+
+        // the slot changes on the next tick
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::Stick, 1, None));
+
+        assert_eq!(event.weapon.item, ItemKind::GoldenSword);
+        assert_eq!(event.hand, Hand::Main);
+        assert_eq!(inventory.get_cursor().item, ItemKind::Stick);
+    }
+
+    #[test]
+    fn sprint_hits_set_the_sprint_flag() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(0, ItemStack::new(ItemKind::IronSword, 1, None));
+
+        let origin = Entity::from_minecraft_id(1);
+        let target = Entity::from_minecraft_id(2);
+
+        let sprint = attack_event(origin, target, &inventory, 0.0, true);
+        assert!(sprint.flags.contains(AttackFlags::SPRINT));
+        assert!(!sprint.flags.contains(AttackFlags::SWEEPING));
+        assert!(!sprint.flags.contains(AttackFlags::CRITICAL));
+
+        // a sword sweeps when not sprinting
+        let sweep = attack_event(origin, target, &inventory, 0.0, false);
+        assert_eq!(sweep.flags, AttackFlags::SWEEPING);
+
+        let inventory = PlayerInventory::default();
+        let punch = attack_event(origin, target, &inventory, 0.0, false);
+        assert_eq!(punch.flags, AttackFlags::empty());
+    }
+
+    #[test]
+    fn knockback_points_away_from_the_attacker() {
+        let inventory = PlayerInventory::default();
+        let origin = Entity::from_minecraft_id(1);
+        let target = Entity::from_minecraft_id(2);
+
+        // yaw 0 faces south (+z), 90 faces west (-x)
+        for (yaw, facing) in [(0.0, Vec3::Z), (90.0, Vec3::NEG_X), (-90.0, Vec3::X)] {
+            let event = attack_event(origin, target, &inventory, yaw, false);
+
+            assert!(
+                event.knockback.abs_diff_eq(facing, 1e-5),
+                "{yaw}: {}",
+                event.knockback
+            );
+            assert_eq!(event.knockback.y, 0.0);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Keep-alives and player latency.
+//!
+//! Every [`KeepAlive::INTERVAL`] each player is sent a [`play::KeepAliveS2c`] with a random id,
+//! which the client echoes back in a [`play::KeepAliveC2s`]. The round trip is averaged into the
+//! player's [`Ping`], which is also what other players see in the tab list. Players that leave
+//! [`KeepAlive::MAX_MISSED`] keep-alives in a row unanswered are disconnected.
+
+use std::{
+    borrow::Cow,
+    time::{Duration, Instant},
+};
+
+use flecs_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::packets::play;
+
+use crate::{
+    egress::player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
+    ingress::PendingRemove,
+    net::{Compose, NetworkStreamRef},
+    simulation::Uuid,
+    system_registry::KEEP_ALIVE,
+};
+
+/// The keep-alive state of a player.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// The id of the keep-alive waiting for an answer and when it was sent.
+    pending: Option<(u64, Instant)>,
+    last_sent: Instant,
+    /// How many keep-alives in a row went unanswered.
+    missed: u32,
+}
+
+impl KeepAlive {
+    /// How often a keep-alive is sent.
+    pub const INTERVAL: Duration = Duration::from_secs(10);
+    /// How many keep-alives in a row can go unanswered before the player is disconnected.
+    pub const MAX_MISSED: u32 = 2;
+
+    /// Keep-alive tracking for a player who joined at `now`. The first keep-alive is sent one
+    /// [`Self::INTERVAL`] later.
+    #[must_use]
+    pub const fn new(now: Instant) -> Self {
+        Self {
+            pending: None,
+            last_sent: now,
+            missed: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn should_send(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_sent) >= Self::INTERVAL
+    }
+
+    /// Starts waiting for an answer to the keep-alive `id`. A keep-alive that is still waiting for
+    /// one is counted as missed, and a late answer to it is ignored.
+    pub const fn send(&mut self, id: u64, now: Instant) {
+        if self.pending.is_some() {
+            self.missed += 1;
+        }
+
+        self.pending = Some((id, now));
+        self.last_sent = now;
+    }
+
+    #[must_use]
+    pub const fn timed_out(&self) -> bool {
+        self.missed >= Self::MAX_MISSED
+    }
+
+    /// Answers the pending keep-alive, returning how long the round trip took.
+    ///
+    /// Answers that do not match the pending keep-alive, such as duplicates or ones that were
+    /// never sent, return `None` and change nothing.
+    pub fn answer(&mut self, id: u64, now: Instant) -> Option<Duration> {
+        let (pending, sent) = self.pending?;
+
+        if pending != id {
+            return None;
+        }
+
+        self.pending = None;
+        self.missed = 0;
+
+        Some(now.saturating_duration_since(sent))
+    }
+}
+
+/// A player's latency in milliseconds, averaged over recent keep-alives.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ping {
+    millis: i32,
+    measured: bool,
+}
+
+impl Ping {
+    #[must_use]
+    pub const fn millis(&self) -> i32 {
+        self.millis
+    }
+
+    /// Adds a round trip to the average. Like vanilla, the newest round trip makes up a quarter of
+    /// it, so a single slow keep-alive does not make the latency jump.
+    pub fn record(&mut self, round_trip: Duration) {
+        let sample = i64::try_from(round_trip.as_millis()).unwrap_or(i64::MAX);
+
+        let millis = if self.measured {
+            (i64::from(self.millis) * 3).saturating_add(sample) / 4
+        } else {
+            sample
+        };
+
+        self.millis = i32::try_from(millis).unwrap_or(i32::MAX);
+        self.measured = true;
+    }
+
+    /// The tab list entry that shows this latency for the player with `uuid`.
+    #[must_use]
+    pub fn list_entry(&self, uuid: uuid::Uuid) -> PlayerListEntry<'static> {
+        PlayerListEntry {
+            player_uuid: uuid,
+            ping: self.millis,
+            ..Default::default()
+        }
+    }
+}
+
+/// Handles a [`play::KeepAliveC2s`] from `player`, updating their [`Ping`] and the tab list.
+pub(crate) fn answer(player: EntityView<'_>, id: u64, compose: &Compose) {
+    let now = Instant::now();
+
+    let entry = player
+        .try_get::<(&mut KeepAlive, &mut Ping, &Uuid)>(|(keep_alive, ping, uuid)| {
+            let round_trip = keep_alive.answer(id, now)?;
+            ping.record(round_trip);
+            Some(ping.list_entry(uuid.0))
+        })
+        .flatten();
+
+    // duplicate and unsolicited answers are not a measurement
+    let Some(entry) = entry else {
+        return;
+    };
+
+    let pkt = PlayerListS2c {
+        actions: PlayerListActions::default().with_update_latency(true),
+        entries: Cow::Owned(vec![entry]),
+    };
+
+    let world = player.world();
+
+    if let Err(e) = compose.broadcast(&pkt, KEEP_ALIVE).send(&world) {
+        warn!("failed to send player latency: {e}");
+    }
+}
+
+#[derive(Component)]
+pub struct KeepAliveModule;
+
+impl Module for KeepAliveModule {
+    fn module(world: &World) {
+        world.component::<KeepAlive>();
+        world.component::<Ping>();
+
+        system!(
+            "send_keep_alives",
+            world,
+            &Compose($),
+            &NetworkStreamRef,
+            &mut KeepAlive,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_entity(|entity, (compose, &io, keep_alive)| {
+            let now = Instant::now();
+
+            if !keep_alive.should_send(now) {
+                return;
+            }
+
+            let id = fastrand::u64(..);
+            keep_alive.send(id, now);
+
+            if keep_alive.timed_out() {
+                entity.set(PendingRemove::new("timed out"));
+                return;
+            }
+
+            let world = entity.world();
+            let pkt = play::KeepAliveS2c { id };
+
+            if let Err(e) = compose.unicast(&pkt, io, KEEP_ALIVE, &world) {
+                warn!("failed to send keep-alive: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{KeepAlive, Ping};
+
+    #[test]
+    fn only_the_pending_keep_alive_is_answered() {
+        let start = Instant::now();
+        let mut keep_alive = KeepAlive::new(start);
+
+        // nothing has been sent yet
+        assert_eq!(keep_alive.answer(1, start), None);
+
+        let sent = start + KeepAlive::INTERVAL;
+        assert!(keep_alive.should_send(sent));
+        keep_alive.send(1, sent);
+        assert!(!keep_alive.should_send(sent));
+
+        let answered = sent + Duration::from_millis(50);
+        assert_eq!(keep_alive.answer(2, answered), None);
+        assert_eq!(
+            keep_alive.answer(1, answered),
+            Some(Duration::from_millis(50))
+        );
+
+        // a duplicate of an answer that was already counted
+        assert_eq!(keep_alive.answer(1, answered), None);
+    }
+
+    #[test]
+    fn times_out_after_missed_keep_alives() {
+        let start = Instant::now();
+        let mut keep_alive = KeepAlive::new(start);
+
+        for i in 0..=KeepAlive::MAX_MISSED {
+            assert!(!keep_alive.timed_out());
+            keep_alive.send(u64::from(i), start + KeepAlive::INTERVAL * (i + 1));
+        }
+
+        assert!(keep_alive.timed_out());
+
+        // answering the latest keep-alive forgives the earlier ones
+        let latest = u64::from(KeepAlive::MAX_MISSED);
+        assert!(keep_alive.answer(latest, Instant::now()).is_some());
+        assert!(!keep_alive.timed_out());
+    }
+
+    #[test]
+    fn ping_is_a_rolling_average() {
+        let mut ping = Ping::default();
+
+        ping.record(Duration::from_millis(100));
+        assert_eq!(ping.millis(), 100);
+
+        ping.record(Duration::from_millis(200));
+        assert_eq!(ping.millis(), 125);
+    }
+}
@@ -0,0 +1,249 @@
+//! Chest-style menus whose slots act as buttons.
+//!
+//! While a [`OpenMenu`] is present, clicks in its window are never applied to any inventory.
+//! Instead the menu is re-sent to undo the client's prediction and the menu's handler is called
+//! with the clicked slot.
+
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use flecs_ecs::prelude::*;
+use hyperion_inventory::PlayerInventory;
+use tracing::warn;
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::{play, play::open_screen_s2c::WindowType},
+    text::IntoText,
+};
+
+use crate::{
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::handlers::PacketSwitchQuery,
+    system_registry::MENU,
+};
+
+/// Window id `0` is the player inventory.
+static NEXT_WINDOW_ID: AtomicU8 = AtomicU8::new(1);
+
+/// A fresh id for a window other than the player inventory.
+pub(crate) fn next_window_id() -> u8 {
+    // the id wraps within 1..=100 like vanilla so it never collides with the player inventory
+    NEXT_WINDOW_ID.fetch_add(1, Ordering::Relaxed) % 100 + 1
+}
+
+/// The contents of a container window: the container's own `slots`, followed by the player's main
+/// inventory and hotbar like in every vanilla container.
+pub(crate) fn window_contents(
+    window_id: u8,
+    slots: &[ItemStack],
+    inventory: &PlayerInventory,
+) -> play::InventoryS2c<'static> {
+    let player_slots = &inventory.slots()[9..45];
+
+    play::InventoryS2c {
+        window_id,
+        state_id: VarInt::default(),
+        slots: Cow::Owned(slots.iter().chain(player_slots).cloned().collect()),
+        carried_item: Cow::Owned(ItemStack::EMPTY),
+    }
+}
+
+/// The state id of `inventory` as it is sent to the client, which the client sends back with
+/// every click so it can be checked with [`PlayerInventory::validate_state`].
+pub(crate) fn state_id(inventory: &PlayerInventory) -> VarInt {
+    #[expect(
+        clippy::cast_possible_wrap,
+        reason = "the client wraps it around the same way"
+    )]
+    let state_id = inventory.state_id() as i32;
+    VarInt(state_id)
+}
+
+/// The state id the client tagged `pkt` with, to compare with [`PlayerInventory::state_id`].
+pub(crate) const fn client_state_id(pkt: &play::ClickSlotC2s) -> u32 {
+    #[expect(
+        clippy::cast_sign_loss,
+        reason = "the client wraps it around the same way"
+    )]
+    let state_id = pkt.state_id.0 as u32;
+    state_id
+}
+
+/// The player inventory slot shown at `window_slot` of a container with `container_slots` slots
+/// of its own, or `None` if the slot belongs to the container or is outside the window.
+pub(crate) fn player_slot(window_slot: i16, container_slots: u16) -> Option<u16> {
+    let slot = u16::try_from(window_slot)
+        .ok()?
+        .checked_sub(container_slots)?;
+    (slot < 36).then_some(slot + 9)
+}
+
+/// Empties the client's cursor, which it fills when it predicts picking up a stack.
+pub(crate) fn reset_cursor() -> play::ScreenHandlerSlotUpdateS2c<'static> {
+    play::ScreenHandlerSlotUpdateS2c {
+        window_id: -1,
+        state_id: VarInt::default(),
+        slot_idx: -1,
+        slot_data: Cow::Owned(ItemStack::EMPTY),
+    }
+}
+
+/// A click on one of a menu's slots.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MenuClick {
+    pub slot: usize,
+}
+
+pub type MenuHandler = fn(&mut PacketSwitchQuery<'_>, &MenuClick);
+
+/// A menu that is currently open for a player.
+#[derive(Component, Clone, Debug)]
+pub struct OpenMenu {
+    pub window_id: u8,
+    /// One item per slot, in whole rows of nine.
+    pub items: Vec<ItemStack>,
+    pub on_click: MenuHandler,
+}
+
+impl OpenMenu {
+    /// Creates a menu with a fresh window id. The items are padded with empty slots to fill whole
+    /// rows; anything past the sixth row is dropped.
+    #[must_use]
+    pub fn new(mut items: Vec<ItemStack>, on_click: MenuHandler) -> Self {
+        let rows = items.len().div_ceil(9).clamp(1, 6);
+        items.resize(rows * 9, ItemStack::EMPTY);
+
+        let window_id = next_window_id();
+
+        Self {
+            window_id,
+            items,
+            on_click,
+        }
+    }
+
+    /// The clicked menu slot, or `None` if the click was outside the menu, e.g. in the player's
+    /// own inventory below it.
+    #[must_use]
+    pub fn clicked_slot(&self, slot_idx: i16) -> Option<MenuClick> {
+        let slot = usize::try_from(slot_idx).ok()?;
+        (slot < self.items.len()).then_some(MenuClick { slot })
+    }
+
+    fn window_type(&self) -> WindowType {
+        match self.items.len() / 9 {
+            0 | 1 => WindowType::Generic9x1,
+            2 => WindowType::Generic9x2,
+            3 => WindowType::Generic9x3,
+            4 => WindowType::Generic9x4,
+            5 => WindowType::Generic9x5,
+            _ => WindowType::Generic9x6,
+        }
+    }
+
+    fn contents_packet(&self) -> play::InventoryS2c<'_> {
+        play::InventoryS2c {
+            window_id: self.window_id,
+            state_id: VarInt::default(),
+            slots: Cow::Borrowed(&self.items),
+            carried_item: Cow::Borrowed(&ItemStack::EMPTY),
+        }
+    }
+
+    /// Re-sends the menu, undoing whatever the client predicted a click would do.
+    pub fn refresh(&self, io: NetworkStreamRef, compose: &Compose, world: &World) {
+        if let Err(e) = compose.unicast(&self.contents_packet(), io, MENU, world) {
+            warn!("failed to refresh menu: {e}");
+        }
+    }
+}
+
+/// Opens `menu` for a player, replacing any menu they already have open.
+pub fn open_menu(entity: EntityView<'_>, title: &str, menu: OpenMenu) {
+    let world = entity.world();
+
+    let result = world.get::<&Compose>(|compose| {
+        entity.get::<&NetworkStreamRef>(|&io| {
+            let mut bundle = DataBundle::new(compose);
+
+            bundle.add_packet(
+                &play::OpenScreenS2c {
+                    window_id: VarInt(i32::from(menu.window_id)),
+                    window_type: menu.window_type(),
+                    window_title: title.into_cow_text(),
+                },
+                &world,
+            )?;
+
+            bundle.add_packet(&menu.contents_packet(), &world)?;
+
+            bundle.send(&world, io, MENU)
+        })
+    });
+
+    if let Err(e) = result {
+        warn!("failed to open menu: {e}");
+        return;
+    }
+
+    entity.set(menu);
+}
+
+/// Closes the menu a player has open. Does nothing if they have none.
+pub fn close_menu(entity: EntityView<'_>) {
+    let world = entity.world();
+
+    let window_id = entity.try_get::<&OpenMenu>(|menu| menu.window_id);
+
+    let Some(window_id) = window_id else {
+        return;
+    };
+
+    entity.remove::<OpenMenu>();
+
+    world.get::<&Compose>(|compose| {
+        entity.get::<&NetworkStreamRef>(|&io| {
+            let pkt = play::CloseScreenS2c { window_id };
+
+            if let Err(e) = compose.unicast(&pkt, io, MENU, &world) {
+                warn!("failed to close menu: {e}");
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{MenuClick, OpenMenu, player_slot};
+
+    #[test]
+    fn clicks_outside_the_menu_are_ignored() {
+        let items = vec![ItemStack::new(ItemKind::Bow, 1, None); 3];
+        let menu = OpenMenu::new(items, |_, _| {});
+
+        // padded to a full row
+        assert_eq!(menu.items.len(), 9);
+        assert_eq!(menu.items[8], ItemStack::EMPTY);
+
+        assert_eq!(menu.clicked_slot(4), Some(MenuClick { slot: 4 }));
+        // slot 9 is the first slot of the player's inventory
+        assert_eq!(menu.clicked_slot(9), None);
+        // clicking outside the window
+        assert_eq!(menu.clicked_slot(-999), None);
+        assert_ne!(menu.window_id, 0);
+    }
+
+    #[test]
+    fn container_slots_map_to_the_player_inventory() {
+        // a furnace has three slots of its own
+        assert_eq!(player_slot(2, 3), None);
+        assert_eq!(player_slot(3, 3), Some(9));
+        assert_eq!(player_slot(38, 3), Some(44));
+        assert_eq!(player_slot(39, 3), None);
+        assert_eq!(player_slot(-999, 3), None);
+    }
+}
@@ -1,8 +1,9 @@
 use derive_more::Deref;
 use flecs_ecs::macros::Component;
-use valence_protocol::{Encode, VarInt};
+use valence_protocol::{Encode, ItemStack, VarInt};
+use valence_text::Text;
 
-use crate::simulation::metadata::r#type::MetadataType;
+use crate::simulation::{event::Posture, metadata::r#type::MetadataType};
 
 #[derive(Debug, Default)]
 // index (u8), type (varint), value (varies)
@@ -141,6 +142,28 @@ pub enum Pose {
     Digging,
 }
 
+impl From<Posture> for Pose {
+    fn from(posture: Posture) -> Self {
+        match posture {
+            Posture::Standing => Self::Standing,
+            Posture::FallFlying => Self::FallFlying,
+            Posture::Sleeping => Self::Sleeping,
+            Posture::Swimming => Self::Swimming,
+            Posture::SpinAttack => Self::SpinAttack,
+            Posture::Sneaking => Self::Sneaking,
+            Posture::LongJumping => Self::LongJumping,
+            Posture::Dying => Self::Dying,
+            Posture::Croaking => Self::Croaking,
+            Posture::UsingTongue => Self::UsingTongue,
+            Posture::Sitting => Self::Sitting,
+            Posture::Roaring => Self::Roaring,
+            Posture::Sniffing => Self::Sniffing,
+            Posture::Emerging => Self::Emerging,
+            Posture::Digging => Self::Digging,
+        }
+    }
+}
+
 impl Metadata for Pose {
     type Type = Self;
 
@@ -151,6 +174,106 @@ impl Metadata for Pose {
     }
 }
 
+/// The name shown above an entity. Players only see it when looking at the entity unless it is
+/// [`CustomNameVisible`].
+#[derive(Clone, Debug, Default)]
+pub struct CustomName(pub Option<Text>);
+
+impl Metadata for CustomName {
+    type Type = Option<Text>;
+
+    const INDEX: u8 = 2;
+
+    fn to_type(self) -> Self::Type {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomNameVisible(pub bool);
+
+impl Metadata for CustomNameVisible {
+    type Type = bool;
+
+    const INDEX: u8 = 3;
+
+    fn to_type(self) -> Self::Type {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoGravity(pub bool);
+
+impl Metadata for NoGravity {
+    type Type = bool;
+
+    const INDEX: u8 = 5;
+
+    fn to_type(self) -> Self::Type {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArmorStandFlags {
+    value: u8,
+}
+
+impl ArmorStandFlags {
+    pub const HAS_ARMS: Self = Self { value: 0x04 };
+    /// A marker has no hitbox, so it cannot be hit and does not block anything.
+    pub const MARKER: Self = Self { value: 0x10 };
+    pub const NO_BASEPLATE: Self = Self { value: 0x08 };
+    pub const SMALL: Self = Self { value: 0x01 };
+}
+
+impl std::ops::BitOr for ArmorStandFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            value: self.value | rhs.value,
+        }
+    }
+}
+
+impl Metadata for ArmorStandFlags {
+    type Type = u8;
+
+    const INDEX: u8 = 15;
+
+    fn to_type(self) -> Self::Type {
+        self.value
+    }
+}
+
+/// The stack an item entity shows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DroppedStack(pub ItemStack);
+
+impl Metadata for DroppedStack {
+    type Type = ItemStack;
+
+    const INDEX: u8 = 8;
+
+    fn to_type(self) -> Self::Type {
+        self.0
+    }
+}
+
+/// The type and value of `metadata` as they are encoded after its index.
+#[must_use]
+pub(crate) fn encode_value<M: Metadata>(metadata: M) -> Vec<u8> {
+    let mut value = Vec::new();
+
+    let type_index = VarInt(<M as Metadata>::Type::INDEX);
+    type_index.encode(&mut value).unwrap();
+    metadata.to_type().encode(&mut value).unwrap();
+
+    value
+}
+
 impl MetadataBuilder {
     #[must_use]
     pub fn is_empty(&self) -> bool {
@@ -168,6 +291,12 @@ impl MetadataBuilder {
         r#type.encode(&mut self.0).unwrap();
     }
 
+    /// Adds an entry whose type and value were already encoded, such as by [`encode_value`].
+    pub(crate) fn encode_raw(&mut self, index: u8, value: &[u8]) {
+        self.0.push(index);
+        self.0.extend_from_slice(value);
+    }
+
     pub fn get_and_clear(&mut self) -> Option<MetadataView<'_>> {
         if self.is_empty() {
             return None;
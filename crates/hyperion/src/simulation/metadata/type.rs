@@ -1,4 +1,5 @@
-use valence_protocol::VarInt;
+use valence_protocol::{ItemStack, VarInt};
+use valence_text::Text;
 
 use crate::simulation::metadata::Pose;
 
@@ -22,3 +23,15 @@ impl MetadataType for Pose {
 impl MetadataType for VarInt {
     const INDEX: i32 = 1;
 }
+
+impl MetadataType for Option<Text> {
+    const INDEX: i32 = 6;
+}
+
+impl MetadataType for bool {
+    const INDEX: i32 = 8;
+}
+
+impl MetadataType for ItemStack {
+    const INDEX: i32 = 7;
+}
@@ -0,0 +1,458 @@
+//! Hostile mobs that chase and attack the nearest player they can see.
+//!
+//! The AI is deliberately simple. Mobs walk straight at their target, step up single blocks, refuse
+//! to walk off anything taller than [`MAX_DROP`] blocks and wander around when there is no one to
+//! chase. Hits are pushed as [`event::Damage`] for the game to apply.
+
+use flecs_ecs::prelude::*;
+use glam::{IVec3, Vec3};
+
+use crate::{
+    simulation::{
+        PacketState, Player, Position, Yaw,
+        blocks::Blocks,
+        entity::{EntityKind, EntityMetadata, NetworkEntity, spawn_entity},
+        event,
+    },
+    storage::Events,
+};
+
+/// The highest drop, in blocks, a mob walks off.
+pub const MAX_DROP: i32 = 3;
+
+/// Mobs are sent to clients at most once every this many ticks, like vanilla zombies.
+const UPDATE_INTERVAL: i64 = 3;
+
+const MOB_EYE_HEIGHT: f32 = 1.74;
+const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// How far from where it stands an idle mob wanders to, in blocks.
+const WANDER_RADIUS: f32 = 8.0;
+
+/// On average, an idle mob starts wandering once every this many ticks.
+const WANDER_CHANCE: u32 = 120;
+
+/// The blocks a mob moves through and looks across.
+pub trait Terrain {
+    /// Whether the block at `position` can neither be walked nor seen through.
+    fn is_solid(&self, position: IVec3) -> bool;
+
+    /// The first solid block on the line from `from` to `to`, if any.
+    fn raycast(&self, from: Vec3, to: Vec3) -> Option<IVec3> {
+        let delta = to - from;
+        let length = delta.length();
+        let direction = delta.normalize_or_zero();
+
+        let mut block = from.floor().as_ivec3();
+        let end = to.floor().as_ivec3();
+
+        let mut step = [0; 3];
+        // how far along the ray the next block boundary is crossed on each axis
+        let mut next = [f32::INFINITY; 3];
+        // how far along the ray a whole block is crossed on each axis
+        let mut across = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            let d = direction[axis];
+            let offset = from[axis] - block[axis] as f32;
+
+            if d > 0.0 {
+                step[axis] = 1;
+                next[axis] = (1.0 - offset) / d;
+                across[axis] = 1.0 / d;
+            } else if d < 0.0 {
+                step[axis] = -1;
+                next[axis] = offset / -d;
+                across[axis] = 1.0 / -d;
+            }
+        }
+
+        loop {
+            if self.is_solid(block) {
+                return Some(block);
+            }
+
+            if block == end {
+                return None;
+            }
+
+            let axis = (0..3)
+                .min_by(|&a, &b| next[a].total_cmp(&next[b]))
+                .unwrap_or_default();
+
+            if next[axis] > length {
+                return None;
+            }
+
+            block[axis] += step[axis];
+            next[axis] += across[axis];
+        }
+    }
+}
+
+impl Terrain for Blocks {
+    /// Blocks in chunks that are not loaded count as solid, so mobs do not walk into them.
+    fn is_solid(&self, position: IVec3) -> bool {
+        self.get_block(position)
+            .is_none_or(|block| block.collision_shapes().next().is_some())
+    }
+}
+
+/// The brain of a hostile mob. Spawn a mob with [`spawn_mob`].
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct MobAi {
+    /// How far away, in blocks, the mob notices players.
+    pub follow_range: f32,
+    /// How far the mob walks in a tick while chasing, in blocks. It wanders at half the speed.
+    pub speed: f32,
+    /// How close the mob has to be to hit its target, in blocks.
+    pub reach: f32,
+    pub attack_damage: f32,
+    /// Ticks between two hits.
+    pub attack_cooldown: u32,
+    target: Option<Entity>,
+    /// Ticks until the mob can hit again.
+    cooldown: u32,
+    wander: Option<Vec3>,
+}
+
+impl Default for MobAi {
+    /// A vanilla zombie.
+    fn default() -> Self {
+        Self {
+            follow_range: 35.0,
+            speed: 0.15,
+            reach: 2.0,
+            attack_damage: 3.0,
+            attack_cooldown: 20,
+            target: None,
+            cooldown: 0,
+            wander: None,
+        }
+    }
+}
+
+/// What a mob does in a tick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MobStep {
+    pub position: Vec3,
+    pub yaw: f32,
+    /// The player the mob hits.
+    pub attack: Option<Entity>,
+}
+
+impl MobStep {
+    const fn new(position: Vec3, yaw: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            attack: None,
+        }
+    }
+}
+
+impl MobAi {
+    /// The player the mob is chasing.
+    #[must_use]
+    pub const fn target(&self) -> Option<Entity> {
+        self.target
+    }
+
+    /// Decides what a mob at `position` facing `yaw` does this tick, given where the `players`
+    /// are.
+    pub fn tick(
+        &mut self,
+        position: Vec3,
+        yaw: f32,
+        players: &[(Entity, Vec3)],
+        terrain: &impl Terrain,
+    ) -> MobStep {
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        let target = self.pick_target(position, players, terrain);
+        self.target = target.map(|(target, _)| target);
+
+        if let Some((target, target_position)) = target {
+            self.wander = None;
+
+            let yaw = yaw_towards(position, target_position).unwrap_or(yaw);
+
+            if position.distance(target_position) > self.reach {
+                let next = step(position, target_position, self.speed, terrain);
+                return MobStep::new(next.unwrap_or(position), yaw);
+            }
+
+            let attack = (self.cooldown == 0).then(|| {
+                self.cooldown = self.attack_cooldown;
+                target
+            });
+
+            return MobStep {
+                position,
+                yaw,
+                attack,
+            };
+        }
+
+        if self.wander.is_none() && fastrand::u32(..WANDER_CHANCE) == 0 {
+            let offset = Vec3::new(fastrand::f32() - 0.5, 0.0, fastrand::f32() - 0.5);
+            self.wander = Some(position + offset * 2.0 * WANDER_RADIUS);
+        }
+
+        let Some(goal) = self.wander else {
+            return MobStep::new(position, yaw);
+        };
+
+        // give up on goals that are reached or cannot be reached in a straight line
+        let Some(next) = step(position, goal, self.speed / 2.0, terrain) else {
+            self.wander = None;
+            return MobStep::new(position, yaw);
+        };
+
+        MobStep::new(next, yaw_towards(position, goal).unwrap_or(yaw))
+    }
+
+    /// The nearest player within the follow range that the mob can see.
+    fn pick_target(
+        &self,
+        position: Vec3,
+        players: &[(Entity, Vec3)],
+        terrain: &impl Terrain,
+    ) -> Option<(Entity, Vec3)> {
+        let eye = position + Vec3::Y * MOB_EYE_HEIGHT;
+        let distance = |player: &(Entity, Vec3)| player.1.distance_squared(position);
+
+        players
+            .iter()
+            .filter(|player| distance(player) <= self.follow_range.powi(2))
+            .filter(|(_, player)| {
+                terrain
+                    .raycast(eye, *player + Vec3::Y * PLAYER_EYE_HEIGHT)
+                    .is_none()
+            })
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .copied()
+    }
+}
+
+/// The yaw of something at `from` looking at `to`, or `None` if `to` is straight above or below.
+fn yaw_towards(from: Vec3, to: Vec3) -> Option<f32> {
+    let delta = (to - from).with_y(0.0);
+
+    if delta == Vec3::ZERO {
+        return None;
+    }
+
+    Some((-delta.x).atan2(delta.z).to_degrees())
+}
+
+/// Where a mob at `position` ends up after walking up to `speed` blocks towards `goal`. Returns
+/// `None` if the mob is at the goal, would walk into a wall or off a cliff.
+fn step(position: Vec3, goal: Vec3, speed: f32, terrain: &impl Terrain) -> Option<Vec3> {
+    let delta = (goal - position).with_y(0.0);
+    let distance = delta.length();
+
+    if distance < f32::EPSILON {
+        return None;
+    }
+
+    let next = position + delta / distance * speed.min(distance);
+    let feet = next.floor().as_ivec3();
+
+    let y = if terrain.is_solid(feet) {
+        // step up a single block if there is room to stand on it
+        let above = feet + IVec3::Y;
+
+        if terrain.is_solid(above) || terrain.is_solid(above + IVec3::Y) {
+            return None;
+        }
+
+        feet.y + 1
+    } else {
+        if terrain.is_solid(feet + IVec3::Y) {
+            return None;
+        }
+
+        // fall onto the ground below unless it is too far down
+        let drop = (1..=MAX_DROP + 1).find(|&drop| terrain.is_solid(feet - IVec3::Y * drop))?;
+
+        feet.y - drop + 1
+    };
+
+    Some(next.with_y(y as f32))
+}
+
+/// Spawns a `kind` mob at `position` that is driven by `ai`.
+pub fn spawn_mob(world: &World, kind: EntityKind, position: Vec3, ai: MobAi) -> Entity {
+    let mob = spawn_entity(world, kind, position, EntityMetadata::default());
+
+    world
+        .entity_from_id(mob)
+        .set(ai)
+        .get::<&mut NetworkEntity>(|network| network.set_update_interval(UPDATE_INTERVAL));
+
+    mob
+}
+
+#[derive(Component)]
+pub struct MobModule;
+
+impl Module for MobModule {
+    fn module(world: &World) {
+        world.component::<MobAi>();
+
+        let players = world
+            .query::<&Position>()
+            .with::<Player>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!(
+            "mob_ai",
+            world,
+            &Blocks($),
+            &Events($),
+            &mut MobAi,
+            &mut Position,
+            &mut Yaw,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_entity(move |entity, (blocks, events, ai, position, yaw)| {
+            let world = entity.world();
+
+            let mut targets = Vec::new();
+            players.each_entity(|player, position| targets.push((player.id(), **position)));
+
+            let step = ai.tick(**position, **yaw, &targets, blocks);
+
+            **position = step.position;
+            **yaw = step.yaw;
+
+            if let Some(target) = step.attack {
+                let damage = event::Damage {
+                    origin: entity.id(),
+                    target,
+                    amount: ai.attack_damage,
+                };
+
+                events.push(damage, &world);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::Entity;
+    use glam::{IVec3, Vec3};
+    use hyperion_utils::EntityExt;
+
+    use super::{MAX_DROP, MobAi, Terrain, step};
+
+    /// Flat ground with its top at `y = 64`, and some extra blocks.
+    struct Flat {
+        walls: Vec<IVec3>,
+        /// Everything from this `x` on is a pit this deep.
+        pit: Option<(i32, i32)>,
+    }
+
+    impl Flat {
+        const fn new() -> Self {
+            Self {
+                walls: Vec::new(),
+                pit: None,
+            }
+        }
+    }
+
+    impl Terrain for Flat {
+        fn is_solid(&self, position: IVec3) -> bool {
+            let ground = match self.pit {
+                Some((x, depth)) if position.x >= x => 64 - depth,
+                _ => 64,
+            };
+
+            position.y < ground || self.walls.contains(&position)
+        }
+    }
+
+    #[test]
+    fn mob_chases_and_hits_a_player() {
+        let terrain = Flat::new();
+        let player = Entity::from_minecraft_id(1);
+        let players = [(player, Vec3::new(10.5, 64.0, 0.5))];
+
+        let mut ai = MobAi::default();
+        let mut position = Vec3::new(0.5, 64.0, 0.5);
+        let mut attacks = Vec::new();
+
+        for _ in 0..200 {
+            let distance = position.distance(players[0].1);
+            let step = ai.tick(position, 0.0, &players, &terrain);
+
+            assert!(step.position.distance(players[0].1) <= distance);
+            assert_eq!(ai.target(), Some(player));
+
+            position = step.position;
+            attacks.extend(step.attack);
+        }
+
+        assert!(position.distance(players[0].1) <= ai.reach);
+
+        // hits are spaced out by the cooldown
+        assert!(!attacks.is_empty());
+        assert!(attacks.len() <= 200 / ai.attack_cooldown as usize + 1);
+        assert!(attacks.iter().all(|&target| target == player));
+    }
+
+    #[test]
+    fn mob_ignores_players_behind_walls() {
+        let mut terrain = Flat::new();
+        terrain.walls = (64..70).map(|y| IVec3::new(5, y, 0)).collect();
+
+        let players = [(Entity::from_minecraft_id(1), Vec3::new(10.5, 64.0, 0.5))];
+
+        let mut ai = MobAi::default();
+        ai.tick(Vec3::new(0.5, 64.0, 0.5), 0.0, &players, &terrain);
+
+        assert_eq!(ai.target(), None);
+    }
+
+    #[test]
+    fn mob_steps_up_single_blocks() {
+        let mut terrain = Flat::new();
+        terrain.walls.push(IVec3::new(1, 64, 0));
+
+        let next = step(
+            Vec3::new(0.9, 64.0, 0.5),
+            Vec3::new(5.5, 64.0, 0.5),
+            0.2,
+            &terrain,
+        );
+        assert_eq!(next.map(|next| next.y), Some(65.0));
+
+        // a wall two blocks high blocks the way
+        terrain.walls.push(IVec3::new(1, 65, 0));
+        let next = step(
+            Vec3::new(0.9, 64.0, 0.5),
+            Vec3::new(5.5, 64.0, 0.5),
+            0.2,
+            &terrain,
+        );
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn mob_does_not_walk_off_cliffs() {
+        let mut terrain = Flat::new();
+        let from = Vec3::new(0.9, 64.0, 0.5);
+        let goal = Vec3::new(5.5, 64.0, 0.5);
+
+        terrain.pit = Some((1, MAX_DROP));
+        let next = step(from, goal, 0.2, &terrain);
+        assert_eq!(next.map(|next| next.y), Some(64.0 - MAX_DROP as f32));
+
+        terrain.pit = Some((1, MAX_DROP + 1));
+        assert_eq!(step(from, goal, 0.2, &terrain), None);
+    }
+}
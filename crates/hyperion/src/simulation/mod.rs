@@ -11,18 +11,46 @@ use uuid;
 
 use crate::{
     Global, Prev,
-    simulation::{command::Command, metadata::Metadata},
+    simulation::{
+        command::{Command, Console, Permissions},
+        metadata::Metadata,
+    },
     storage::ThreadLocalVec,
 };
 
 pub mod animation;
+pub mod anvil;
 pub mod blocks;
+pub mod boss_bar;
 pub mod command;
+pub mod container;
+pub mod crafting_table;
+pub mod dropped_item;
+pub mod entity;
+pub mod equipment;
 pub mod event;
+pub mod fall;
+pub mod frozen;
+pub mod furnace;
+pub mod game_mode;
 pub mod handlers;
+pub mod keep_alive;
+pub mod menu;
 pub mod metadata;
+pub mod mob;
+pub mod persistence;
+pub mod recipe_book;
+pub mod roster;
+pub mod scheduled_blocks;
+pub mod scoreboard;
 pub mod skin;
+pub mod spawn;
+pub mod teleport;
+pub mod time;
 pub mod util;
+pub mod visibility;
+pub mod weather;
+pub mod world_border;
 
 #[derive(Component, Default, Debug, Deref, DerefMut)]
 pub struct StreamLookup {
@@ -232,7 +260,17 @@ impl Xp {
             _ => 63,
         };
 
-        let (level_start, next_level_start) = match level {
+        let (level_start, next_level_start) = Self::level_bounds(level);
+
+        let prop = f32::from(self.amount - level_start) / f32::from(next_level_start - level_start);
+
+        XpVisual { level, prop }
+    }
+
+    /// The XP at which `level` starts and the XP at which the next level starts.
+    #[must_use]
+    pub const fn level_bounds(level: u8) -> (u16, u16) {
+        match level {
             0 => (0, 7),
             1 => (7, 16),
             2 => (16, 27),
@@ -297,11 +335,33 @@ impl Xp {
             61 => (9052, 9443),
             62 => (9443, 9843),
             _ => (9843, 10242), // Extrapolated next value
+        }
+    }
+
+    /// Takes away whole levels, keeping the progress towards the next level.
+    ///
+    /// Returns `false` and leaves the XP unchanged if there are fewer than `levels` levels.
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "the progress is a non-negative fraction of a level"
+    )]
+    pub fn try_spend_levels(&mut self, levels: u8) -> bool {
+        let visual = self.get_visual();
+
+        let Some(level) = visual.level.checked_sub(levels) else {
+            return false;
         };
 
-        let prop = f32::from(self.amount - level_start) / f32::from(next_level_start - level_start);
+        let (level_start, next_level_start) = Self::level_bounds(level);
+        let width = next_level_start - level_start;
 
-        XpVisual { level, prop }
+        // the last level has no upper bound, so its progress can go past a whole level
+        let progress = (f32::from(width) * visual.prop) as u16;
+
+        self.amount = level_start + progress.min(width - 1);
+
+        true
     }
 }
 
@@ -592,6 +652,9 @@ impl Module for SimModule {
 
         world.component::<PlayerSkin>();
         world.component::<Command>();
+        world.component::<Permissions>();
+        world.component::<Console>();
+        command::resend_on_permissions_change(world);
 
         component!(world, EntitySize).opaque_func(meta_ser_stringify_type_display::<EntitySize>);
         component!(world, IVec3 {
@@ -621,7 +684,40 @@ impl Module for SimModule {
         world.component::<EntityReaction>().meta();
         world.component::<ConfirmBlockSequences>();
         world.component::<animation::ActiveAnimation>();
+        world.component::<fall::FallDistance>();
+        world.component::<frozen::Frozen>();
+        world.component::<game_mode::GameMode>();
+        world.component::<menu::OpenMenu>();
+        world.component::<anvil::OpenAnvil>();
+        world.component::<crafting_table::OpenCraftingTable>();
+        world.component::<hyperion_inventory::CraftingGrid<3>>();
+        world.component::<visibility::HiddenEntities>();
+        world.component::<visibility::HiddenFrom>();
+        world.component::<hyperion_crafting::RecipeBook>();
 
         world.component::<hyperion_inventory::PlayerInventory>();
+
+        // before persistence is imported, so leaving players get their items back before they
+        // are saved
+        anvil::close_on_leave(world);
+        crafting_table::close_on_leave(world);
+
+        world.import::<teleport::TeleportModule>();
+        world.import::<furnace::FurnaceModule>();
+        // closes the containers of leaving players before they are saved
+        world.import::<container::ContainerModule>();
+        world.import::<persistence::PersistenceModule>();
+        world.import::<time::TimeModule>();
+        world.import::<weather::WeatherModule>();
+        world.import::<world_border::WorldBorderModule>();
+        world.import::<scheduled_blocks::ScheduledBlocksModule>();
+        world.import::<scoreboard::ScoreboardModule>();
+        world.import::<boss_bar::BossBarModule>();
+        world.import::<spawn::WorldSpawnModule>();
+        world.import::<entity::NetworkEntityModule>();
+        world.import::<mob::MobModule>();
+        world.import::<dropped_item::DroppedItemModule>();
+        world.import::<equipment::EquipmentModule>();
+        world.import::<keep_alive::KeepAliveModule>();
     }
 }
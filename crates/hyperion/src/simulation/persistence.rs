@@ -0,0 +1,408 @@
+//! Saving players when they leave, and periodically while they play, so they come back where
+//! they left with the same inventory, health and experience.
+//!
+//! Records live in the [`PlayerDataHandler`] database keyed by UUID. Each one is a schema version
+//! byte followed by an uncompressed NBT compound using the names vanilla uses in player data
+//! files. The server does not track game modes or hunger yet, so neither is saved; they can be
+//! added in a new schema version once it does.
+
+use anyhow::{Context, bail};
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use hyperion_inventory::PlayerInventory;
+use tracing::warn;
+use valence_protocol::nbt::{Compound, List, Value};
+
+use crate::{
+    ingress::PendingRemove,
+    net::Compose,
+    simulation::{Health, Pitch, Position, Uuid, Xp, Yaw},
+    storage::PlayerDataHandler,
+};
+
+/// Bumped whenever the layout of a record changes.
+const SCHEMA_VERSION: u8 = 1;
+
+/// Five minutes at 20 ticks per second.
+const AUTOSAVE_TICKS: i64 = 20 * 60 * 5;
+
+/// A player whose saved data, if any, has been loaded. Only these players are saved, so a
+/// record that could not be read is never overwritten with the defaults a player joined with.
+#[derive(Component, Debug)]
+pub struct PlayerDataLoaded;
+
+/// Everything saved about a player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerRecord {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub health: f32,
+    /// The total experience, from which the level and progress are derived.
+    pub xp: u16,
+    /// The selected hotbar slot, from 0 to 8.
+    pub hotbar_slot: u16,
+    /// The inventory as written by [`PlayerInventory::to_nbt`].
+    pub inventory: List,
+}
+
+impl PlayerRecord {
+    /// Captures the current state of a player.
+    #[must_use]
+    pub fn capture(
+        position: &Position,
+        yaw: &Yaw,
+        pitch: &Pitch,
+        health: &Health,
+        xp: &Xp,
+        inventory: &PlayerInventory,
+    ) -> Self {
+        Self {
+            position: **position,
+            yaw: **yaw,
+            pitch: **pitch,
+            health: **health,
+            xp: xp.amount,
+            hotbar_slot: inventory.get_cursor_hand_slot(),
+            inventory: inventory.to_nbt(),
+        }
+    }
+
+    /// Restores a player to the captured state.
+    pub fn apply(
+        &self,
+        position: &mut Position,
+        yaw: &mut Yaw,
+        pitch: &mut Pitch,
+        health: &mut Health,
+        xp: &mut Xp,
+        inventory: &mut PlayerInventory,
+    ) {
+        **position = self.position;
+        **yaw = self.yaw;
+        **pitch = self.pitch;
+        **health = self.health;
+        xp.amount = self.xp;
+
+        inventory.clear();
+        inventory.load_nbt(&self.inventory);
+        inventory.set_cursor(self.hotbar_slot);
+    }
+
+    /// Encodes the record as it is stored.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut nbt = Compound::new();
+
+        nbt.insert(
+            "Pos",
+            List::Double(self.position.to_array().map(f64::from).to_vec()),
+        );
+        nbt.insert("Rotation", List::Float(vec![self.yaw, self.pitch]));
+        nbt.insert("Health", self.health);
+        nbt.insert("XpTotal", i32::from(self.xp));
+        nbt.insert("SelectedItemSlot", i32::from(self.hotbar_slot));
+        nbt.insert("Inventory", self.inventory.clone());
+
+        let mut bytes = vec![SCHEMA_VERSION];
+
+        // writing to a vec cannot fail
+        valence_nbt::to_binary(&nbt, &mut bytes, "").unwrap();
+
+        bytes
+    }
+
+    /// Decodes a record written by [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let Some((&version, mut nbt)) = bytes.split_first() else {
+            bail!("the record is empty");
+        };
+
+        if version != SCHEMA_VERSION {
+            bail!("unknown schema version {version}");
+        }
+
+        let (nbt, _): (Compound, String) =
+            valence_nbt::from_binary(&mut nbt).context("invalid nbt")?;
+
+        let Some(Value::List(List::Double(pos))) = nbt.get("Pos") else {
+            bail!("missing `Pos`");
+        };
+
+        let &[x, y, z] = pos.as_slice() else {
+            bail!("`Pos` does not have three coordinates");
+        };
+
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "positions are saved from f32s, so they convert back exactly"
+        )]
+        let position = Vec3::new(x as f32, y as f32, z as f32);
+
+        let Some(Value::List(List::Float(rotation))) = nbt.get("Rotation") else {
+            bail!("missing `Rotation`");
+        };
+
+        let &[yaw, pitch] = rotation.as_slice() else {
+            bail!("`Rotation` does not have a yaw and a pitch");
+        };
+
+        let Some(&Value::Float(health)) = nbt.get("Health") else {
+            bail!("missing `Health`");
+        };
+
+        let Some(&Value::Int(xp)) = nbt.get("XpTotal") else {
+            bail!("missing `XpTotal`");
+        };
+
+        let Some(&Value::Int(hotbar_slot)) = nbt.get("SelectedItemSlot") else {
+            bail!("missing `SelectedItemSlot`");
+        };
+
+        let Some(Value::List(inventory)) = nbt.get("Inventory") else {
+            bail!("missing `Inventory`");
+        };
+
+        let hotbar_slot = u16::try_from(hotbar_slot)
+            .ok()
+            .filter(|&slot| slot < 9)
+            .context("`SelectedItemSlot` is not a hotbar slot")?;
+
+        Ok(Self {
+            position,
+            yaw,
+            pitch,
+            health,
+            xp: u16::try_from(xp).context("`XpTotal` is out of range")?,
+            hotbar_slot,
+            inventory: inventory.clone(),
+        })
+    }
+}
+
+/// Loads the saved data of a joining player over the defaults they were given at login. A
+/// corrupt record is ignored with a warning, so the player starts over.
+pub fn load(player: &EntityView<'_>, players: &PlayerDataHandler) {
+    let Some(uuid) = player.try_get::<&Uuid>(|uuid| uuid.0) else {
+        return;
+    };
+
+    let record = match players.find(uuid) {
+        Ok(record) => record,
+        Err(e) => {
+            // not marked as loaded, so the saved data survives until it can be read
+            warn!("failed to load the player data of {uuid}: {e}");
+            return;
+        }
+    };
+
+    let record = record.and_then(|bytes| {
+        PlayerRecord::from_bytes(&bytes)
+            .inspect_err(|e| warn!("the player data of {uuid} is corrupt, using defaults: {e}"))
+            .ok()
+    });
+
+    if let Some(record) = record {
+        player.try_get::<(
+            &mut Position,
+            &mut Yaw,
+            &mut Pitch,
+            &mut Health,
+            &mut Xp,
+            &mut PlayerInventory,
+        )>(|(position, yaw, pitch, health, xp, inventory)| {
+            record.apply(position, yaw, pitch, health, xp, inventory);
+        });
+    }
+
+    player.add::<PlayerDataLoaded>();
+}
+
+fn save(players: &PlayerDataHandler, uuid: &Uuid, record: &PlayerRecord) {
+    if let Err(e) = players.insert(uuid.0, &record.to_bytes()) {
+        warn!("failed to save the player data of {}: {e}", uuid.0);
+    }
+}
+
+#[derive(Component)]
+pub struct PersistenceModule;
+
+impl Module for PersistenceModule {
+    fn module(world: &World) {
+        world.component::<PlayerDataLoaded>();
+
+        system!(
+            "save_leaving_players",
+            world,
+            &PlayerDataHandler($),
+            &Uuid,
+            &Position,
+            &Yaw,
+            &Pitch,
+            &Health,
+            &Xp,
+            &PlayerInventory,
+        )
+        .kind::<flecs::pipeline::PostLoad>()
+        .with::<&PendingRemove>()
+        .with::<PlayerDataLoaded>()
+        .each(
+            |(players, uuid, position, yaw, pitch, health, xp, inventory)| {
+                let record = PlayerRecord::capture(position, yaw, pitch, health, xp, inventory);
+                save(players, uuid, &record);
+            },
+        );
+
+        let online = world
+            .query::<(
+                &Uuid,
+                &Position,
+                &Yaw,
+                &Pitch,
+                &Health,
+                &Xp,
+                &PlayerInventory,
+            )>()
+            .with::<PlayerDataLoaded>()
+            .build();
+
+        // everyone is saved in one transaction, since committing one per player stalls the tick
+        // on a busy server
+        system!(
+            "autosave_players",
+            world,
+            &Compose($),
+            &PlayerDataHandler($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(move |_, _, (compose, players)| {
+            if compose.global().tick % AUTOSAVE_TICKS != 0 {
+                return;
+            }
+
+            let mut records = Vec::new();
+
+            online.each(|(uuid, position, yaw, pitch, health, xp, inventory)| {
+                let record = PlayerRecord::capture(position, yaw, pitch, health, xp, inventory);
+                records.push((uuid.0, record.to_bytes()));
+            });
+
+            let records = records
+                .iter()
+                .map(|(uuid, bytes)| (*uuid, bytes.as_slice()));
+
+            if let Err(e) = players.insert_all(records) {
+                warn!("failed to autosave players: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use hyperion_inventory::PlayerInventory;
+    use valence_protocol::{ItemKind, ItemStack, nbt::Compound};
+
+    use super::{PlayerRecord, SCHEMA_VERSION};
+    use crate::{
+        simulation::{Health, Pitch, Position, Xp, Yaw},
+        storage::{LocalDb, PlayerDataHandler},
+    };
+
+    #[test]
+    fn records_round_trip() {
+        let mut inventory = PlayerInventory::default();
+
+        let mut nbt = Compound::new();
+        nbt.insert("Damage", 12);
+
+        inventory
+            .set(36, ItemStack::new(ItemKind::IronPickaxe, 1, Some(nbt)))
+            .unwrap();
+        inventory
+            .set(9, ItemStack::new(ItemKind::Cobblestone, 64, None))
+            .unwrap();
+        inventory
+            .set(45, ItemStack::new(ItemKind::Torch, 7, None))
+            .unwrap();
+        inventory.set_cursor(4);
+
+        let mut health = Health::default();
+        *health = 13.5;
+
+        let record = PlayerRecord::capture(
+            &Position::from(Vec3::new(12.5, 70.0, -300.25)),
+            &Yaw::default(),
+            &Pitch::default(),
+            &health,
+            &Xp { amount: 345 },
+            &inventory,
+        );
+
+        let decoded = PlayerRecord::from_bytes(&record.to_bytes()).unwrap();
+        assert_eq!(decoded, record);
+
+        let mut position = Position::from(Vec3::ZERO);
+        let mut yaw = Yaw::default();
+        let mut pitch = Pitch::default();
+        let mut health = Health::default();
+        let mut xp = Xp::default();
+        let mut restored = PlayerInventory::default();
+
+        decoded.apply(
+            &mut position,
+            &mut yaw,
+            &mut pitch,
+            &mut health,
+            &mut xp,
+            &mut restored,
+        );
+
+        assert_eq!(*position, Vec3::new(12.5, 70.0, -300.25));
+        assert!((*health - 13.5).abs() < f32::EPSILON);
+        assert_eq!(xp.amount, 345);
+        assert_eq!(restored.get_cursor_index(), 40);
+        assert_eq!(
+            restored.items().collect::<Vec<_>>(),
+            inventory.items().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn unreadable_records_are_rejected() {
+        assert!(PlayerRecord::from_bytes(&[]).is_err());
+        assert!(PlayerRecord::from_bytes(&[SCHEMA_VERSION, 1, 2, 3]).is_err());
+
+        let record = PlayerRecord {
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            health: 20.0,
+            xp: 0,
+            hotbar_slot: 0,
+            inventory: PlayerInventory::default().to_nbt(),
+        };
+
+        let mut bytes = record.to_bytes();
+        bytes[0] = SCHEMA_VERSION + 1;
+
+        assert!(PlayerRecord::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn autosaves_write_every_player() {
+        let dir = tempfile::tempdir().unwrap();
+        let players = PlayerDataHandler::new(&LocalDb::open(dir.path()).unwrap()).unwrap();
+
+        let alex = uuid::Uuid::from_u128(1);
+        let steve = uuid::Uuid::from_u128(2);
+
+        players
+            .insert_all([(alex, [1, 2].as_slice()), (steve, [3].as_slice())])
+            .unwrap();
+
+        assert_eq!(players.find(alex).unwrap(), Some(vec![1, 2]));
+        assert_eq!(players.find(steve).unwrap(), Some(vec![3]));
+    }
+}
@@ -0,0 +1,51 @@
+//! Unlocking recipes for players during the game.
+
+use flecs_ecs::prelude::*;
+use hyperion_crafting::{BookKind, RecipeBook, RecipeBookState};
+use valence_protocol::packets::play::{self, recipe_category_options_c2s::RecipeBookId};
+
+use crate::{
+    net::{Compose, NetworkStreamRef},
+    system_registry::RECIPE_BOOK,
+};
+
+/// Unlocks the recipe `id` for `player`, adding it to their recipe book with a toast. `id` is the
+/// id the recipe was registered under, e.g. `minecraft:stone_pickaxe` for a vanilla recipe.
+///
+/// Returns `false` if the player already had the recipe.
+pub fn unlock_recipe(player: EntityView<'_>, id: &str) -> anyhow::Result<bool> {
+    let world = player.world();
+
+    let pkt = player.get::<&mut RecipeBook>(|book| {
+        book.unlock(id)
+            .then(|| book.add_packet(vec![id.to_owned()]))
+    });
+
+    let Some(pkt) = pkt else {
+        return Ok(false);
+    };
+
+    player.get::<&NetworkStreamRef>(|&io| {
+        world.get::<&Compose>(|compose| compose.unicast(&pkt, io, RECIPE_BOOK, &world))
+    })?;
+
+    Ok(true)
+}
+
+/// Remembers how the client left one of its recipe books for as long as the player is online.
+/// It is not saved with the player, so every join starts with the books closed and unfiltered.
+pub fn recipe_category_options(player: EntityView<'_>, pkt: &play::RecipeCategoryOptionsC2s) {
+    let book = match pkt.book_id {
+        RecipeBookId::Crafting => BookKind::Crafting,
+        RecipeBookId::Furnace => BookKind::Furnace,
+        RecipeBookId::BlastFurnace => BookKind::BlastFurnace,
+        RecipeBookId::Smoker => BookKind::Smoker,
+    };
+
+    let state = RecipeBookState {
+        open: pkt.book_open,
+        filter_active: pkt.filter_active,
+    };
+
+    player.get::<&mut RecipeBook>(|recipe_book| recipe_book.set_state(book, state));
+}
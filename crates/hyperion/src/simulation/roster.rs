@@ -0,0 +1,213 @@
+//! The set of players that are currently online.
+
+use std::sync::Arc;
+
+use flecs_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use tracing::info_span;
+
+use crate::{net::NetworkStreamRef, storage::ThreadLocalVec};
+
+/// A player that is currently online.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerEntry {
+    pub entity: Entity,
+    pub uuid: uuid::Uuid,
+    pub name: Arc<str>,
+    pub stream: NetworkStreamRef,
+}
+
+/// Every player that has logged in and has not yet been removed.
+///
+/// Players are added when their login completes, which happens before any join handler runs, and
+/// removed only once the leave handlers for a [`crate::ingress::PendingRemove`] have finished.
+/// Additions made from multithreaded systems are deferred and applied by [`PlayerRoster::update`].
+#[derive(Component, Debug, Default)]
+pub struct PlayerRoster {
+    to_add: ThreadLocalVec<PlayerEntry>,
+    entries: FxHashMap<Entity, PlayerEntry>,
+    by_name: FxHashMap<Arc<str>, Entity>,
+    by_uuid: FxHashMap<uuid::Uuid, Entity>,
+}
+
+impl PlayerRoster {
+    /// The number of players online.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerEntry> + '_ {
+        self.entries.values()
+    }
+
+    #[must_use]
+    pub fn get(&self, entity: Entity) -> Option<&PlayerEntry> {
+        self.entries.get(&entity)
+    }
+
+    #[must_use]
+    pub fn by_name(&self, name: &str) -> Option<&PlayerEntry> {
+        let entity = self.by_name.get(name)?;
+        self.entries.get(entity)
+    }
+
+    #[must_use]
+    pub fn by_uuid(&self, uuid: uuid::Uuid) -> Option<&PlayerEntry> {
+        let entity = self.by_uuid.get(&uuid)?;
+        self.entries.get(entity)
+    }
+
+    /// Queues a player to be added. This is safe to call from multithreaded systems.
+    pub fn insert(&self, entry: PlayerEntry, world: &World) {
+        self.to_add.push(entry, world);
+    }
+
+    /// Applies all queued additions.
+    pub fn update(&mut self) {
+        let to_add: Vec<_> = self.to_add.drain().collect();
+
+        for entry in to_add {
+            self.insert_now(entry);
+        }
+    }
+
+    fn insert_now(&mut self, entry: PlayerEntry) {
+        // a player logging in with a name or uuid that is already online replaces the old entry
+        for previous in [
+            self.by_name.get(&entry.name).copied(),
+            self.by_uuid.get(&entry.uuid).copied(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            self.remove(previous);
+        }
+
+        self.by_name.insert(entry.name.clone(), entry.entity);
+        self.by_uuid.insert(entry.uuid, entry.entity);
+        self.entries.insert(entry.entity, entry);
+    }
+
+    /// Removes a player immediately, returning their entry if they were online.
+    pub fn remove(&mut self, entity: Entity) -> Option<PlayerEntry> {
+        let entry = self.entries.remove(&entity)?;
+
+        if self.by_name.get(&entry.name) == Some(&entity) {
+            self.by_name.remove(&entry.name);
+        }
+
+        if self.by_uuid.get(&entry.uuid) == Some(&entity) {
+            self.by_uuid.remove(&entry.uuid);
+        }
+
+        Some(entry)
+    }
+}
+
+/// Applies the players queued with [`PlayerRoster::insert`] at the start of every tick.
+#[derive(Component)]
+pub struct PlayerRosterModule;
+
+impl Module for PlayerRosterModule {
+    fn module(world: &World) {
+        system!(
+            "update_player_roster",
+            world,
+            &mut PlayerRoster($),
+        )
+        .kind::<flecs::pipeline::PostLoad>()
+        .each_iter(|_, _, roster| {
+            let span = info_span!("update_player_roster");
+            let _enter = span.enter();
+            roster.update();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use hyperion_utils::EntityExt;
+
+    use super::{PlayerEntry, PlayerRoster, PlayerRosterModule};
+    use crate::net::NetworkStreamRef;
+
+    fn entry(id: i32, name: &str) -> PlayerEntry {
+        PlayerEntry {
+            entity: Entity::from_minecraft_id(id),
+            uuid: uuid::Uuid::from_u128(u128::from(id.unsigned_abs())),
+            name: name.into(),
+            stream: NetworkStreamRef::new(u64::from(id.unsigned_abs())),
+        }
+    }
+
+    #[test]
+    fn lookup_after_join() {
+        let mut roster = PlayerRoster::default();
+        let alice = entry(1, "alice");
+
+        roster.insert_now(alice.clone());
+
+        assert_eq!(roster.count(), 1);
+        assert_eq!(roster.by_name("alice"), Some(&alice));
+        assert_eq!(roster.by_uuid(alice.uuid), Some(&alice));
+        assert_eq!(roster.get(alice.entity), Some(&alice));
+    }
+
+    #[test]
+    fn absent_after_leave() {
+        let mut roster = PlayerRoster::default();
+        let alice = entry(1, "alice");
+        let bob = entry(2, "bob");
+
+        roster.insert_now(alice.clone());
+        roster.insert_now(bob.clone());
+
+        assert_eq!(roster.remove(alice.entity), Some(alice.clone()));
+
+        assert_eq!(roster.count(), 1);
+        assert_eq!(roster.by_name("alice"), None);
+        assert_eq!(roster.by_uuid(alice.uuid), None);
+        assert_eq!(roster.iter().collect::<Vec<_>>(), vec![&bob]);
+        assert_eq!(roster.remove(alice.entity), None);
+    }
+
+    #[test]
+    fn rejoin_with_same_name_replaces_entry() {
+        let mut roster = PlayerRoster::default();
+        let old = entry(1, "alice");
+        let new = entry(2, "alice");
+
+        roster.insert_now(old.clone());
+        roster.insert_now(new.clone());
+
+        assert_eq!(roster.count(), 1);
+        assert_eq!(roster.by_name("alice"), Some(&new));
+        assert_eq!(roster.get(old.entity), None);
+    }
+
+    #[test]
+    fn logins_are_looked_up_from_the_next_tick_on() {
+        let world = World::new();
+        world.set(PlayerRoster::default());
+        world.import::<PlayerRosterModule>();
+
+        let alice = entry(1, "alice");
+
+        // logins are queued from multithreaded systems
+        world.get::<&PlayerRoster>(|roster| roster.insert(alice.clone(), &world));
+        assert_eq!(world.get::<&PlayerRoster>(PlayerRoster::count), 0);
+
+        assert!(world.progress());
+
+        world.get::<&PlayerRoster>(|roster| {
+            assert_eq!(roster.by_name("alice"), Some(&alice));
+            assert_eq!(roster.by_uuid(alice.uuid), Some(&alice));
+        });
+
+        // nothing is queued, so the next tick changes nothing
+        assert!(world.progress());
+        assert_eq!(world.get::<&PlayerRoster>(PlayerRoster::count), 1);
+    }
+}
@@ -0,0 +1,206 @@
+//! Blocks that are set some ticks from now, such as a barrier that turns back into air or ore that
+//! grows back after it was mined.
+//!
+//! ```ignore
+//! world.get::<&mut ScheduledBlockUpdates>(|scheduled| {
+//!     scheduled.schedule(position, BlockState::AIR, 60);
+//! });
+//! ```
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use flecs_ecs::prelude::*;
+use glam::IVec3;
+use rustc_hash::FxHashMap;
+use tracing::{info_span, warn};
+use valence_generated::block::BlockState;
+
+use crate::{
+    profiler::PROFILER,
+    simulation::blocks::{Blocks, TrySetBlockDeltaError},
+};
+
+#[derive(Copy, Clone, Debug)]
+struct Pending {
+    id: u64,
+    state: BlockState,
+}
+
+/// Blocks to set once a number of ticks has passed. Only the latest update scheduled for a position
+/// is kept.
+#[derive(Component, Debug, Default)]
+pub struct ScheduledBlockUpdates {
+    /// How many ticks have passed.
+    tick: u64,
+    next_id: u64,
+    /// The tick each update is due and its id, earliest first. Entries whose id is no longer in
+    /// `pending` were replaced or cancelled and are skipped.
+    queue: BinaryHeap<Reverse<(u64, u64, [i32; 3])>>,
+    pending: FxHashMap<IVec3, Pending>,
+}
+
+impl ScheduledBlockUpdates {
+    /// Sets the block at `position` to `state` in `delay_ticks` ticks, replacing any update already
+    /// scheduled for it. A delay of `0` sets it on the next tick, like a delay of `1`.
+    pub fn schedule(&mut self, position: IVec3, state: BlockState, delay_ticks: u32) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let due = self.tick + u64::from(delay_ticks);
+
+        self.pending.insert(position, Pending { id, state });
+        self.queue.push(Reverse((due, id, position.to_array())));
+    }
+
+    /// Cancels the update scheduled for `position`, returning the state it would have set.
+    pub fn cancel(&mut self, position: IVec3) -> Option<BlockState> {
+        // the entry in `queue` is skipped once it is due
+        self.pending.remove(&position).map(|pending| pending.state)
+    }
+
+    /// The state scheduled for `position`, if any.
+    #[must_use]
+    pub fn get(&self, position: IVec3) -> Option<BlockState> {
+        self.pending.get(&position).map(|pending| pending.state)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Advances by one tick, removing the updates that are now due. They are returned in the order
+    /// they are due, and updates due on the same tick in the order they were scheduled.
+    pub fn advance(&mut self) -> Vec<(IVec3, BlockState)> {
+        self.tick += 1;
+
+        let mut due = Vec::new();
+
+        while let Some(&Reverse((tick, id, position))) = self.queue.peek()
+            && tick <= self.tick
+        {
+            self.queue.pop();
+
+            let position = IVec3::from_array(position);
+
+            if let Some(pending) = self.pending.get(&position)
+                && pending.id == id
+            {
+                due.push((position, pending.state));
+                self.pending.remove(&position);
+            }
+        }
+
+        due
+    }
+
+    /// Sets the blocks that are due this tick. Blocks in chunks that are no longer loaded are
+    /// dropped.
+    pub fn apply(&mut self, blocks: &mut Blocks) {
+        for (position, state) in self.advance() {
+            match blocks.set_block(position, state) {
+                Ok(_) | Err(TrySetBlockDeltaError::ChunkNotLoaded) => {}
+                Err(e) => warn!("failed to set scheduled block at {position}: {e:?}"),
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ScheduledBlocksModule;
+
+impl Module for ScheduledBlocksModule {
+    fn module(world: &World) {
+        world.component::<ScheduledBlockUpdates>();
+        world.set(ScheduledBlockUpdates::default());
+
+        system!(
+            "apply_scheduled_blocks",
+            world,
+            &mut ScheduledBlockUpdates($),
+            &mut Blocks($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each(|(scheduled, blocks)| {
+            let span = info_span!("apply_scheduled_blocks");
+            let _enter = span.enter();
+            let _timer = PROFILER.time("apply_scheduled_blocks");
+
+            scheduled.apply(blocks);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::IVec3;
+    use valence_generated::block::BlockState;
+
+    use super::ScheduledBlockUpdates;
+
+    #[test]
+    fn updates_are_due_after_their_delay() {
+        let mut scheduled = ScheduledBlockUpdates::default();
+        let barrier = IVec3::new(3, 70, -8);
+        let ore = IVec3::new(-20, 12, 5);
+
+        scheduled.schedule(barrier, BlockState::AIR, 3);
+        scheduled.schedule(ore, BlockState::IRON_ORE, 1);
+        assert_eq!(scheduled.len(), 2);
+
+        assert_eq!(scheduled.advance(), [(ore, BlockState::IRON_ORE)]);
+        assert!(scheduled.advance().is_empty());
+        assert_eq!(scheduled.advance(), [(barrier, BlockState::AIR)]);
+        assert!(scheduled.advance().is_empty());
+        assert!(scheduled.is_empty());
+
+        // a delay of 0 is due on the next tick
+        scheduled.schedule(barrier, BlockState::STONE, 0);
+        assert_eq!(scheduled.advance(), [(barrier, BlockState::STONE)]);
+    }
+
+    #[test]
+    fn the_latest_update_for_a_position_wins() {
+        let mut scheduled = ScheduledBlockUpdates::default();
+        let position = IVec3::new(0, 64, 0);
+
+        scheduled.schedule(position, BlockState::STONE, 1);
+        scheduled.schedule(position, BlockState::AIR, 2);
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled.get(position), Some(BlockState::AIR));
+
+        assert!(scheduled.advance().is_empty());
+        assert_eq!(scheduled.advance(), [(position, BlockState::AIR)]);
+
+        // an earlier delay replaces a later one as well
+        scheduled.schedule(position, BlockState::STONE, 5);
+        scheduled.schedule(position, BlockState::DIRT, 1);
+        assert_eq!(scheduled.advance(), [(position, BlockState::DIRT)]);
+        for _ in 0..5 {
+            assert!(scheduled.advance().is_empty());
+        }
+    }
+
+    #[test]
+    fn cancelled_updates_are_never_due() {
+        let mut scheduled = ScheduledBlockUpdates::default();
+        let position = IVec3::new(1, 2, 3);
+
+        scheduled.schedule(position, BlockState::AIR, 2);
+        assert_eq!(scheduled.cancel(position), Some(BlockState::AIR));
+        assert_eq!(scheduled.cancel(position), None);
+
+        for _ in 0..3 {
+            assert!(scheduled.advance().is_empty());
+        }
+
+        // scheduling again after cancelling still works
+        scheduled.schedule(position, BlockState::STONE, 1);
+        assert_eq!(scheduled.advance(), [(position, BlockState::STONE)]);
+    }
+}
@@ -0,0 +1,844 @@
+//! Scoreboard objectives, such as a sidebar, and scoreboard teams, which color nametags.
+//!
+//! The [`Scoreboard`] singleton is shown to everyone, while a [`Scoreboard`] added to a player is
+//! only shown to them, so each player can see their own numbers. Give the two different objective
+//! names if both are used. Lines can be set every tick; only lines that changed since the last tick
+//! are sent.
+//!
+//! ```ignore
+//! world.get::<&mut Scoreboard>(|scoreboard| {
+//!     scoreboard.show(Objective::new("round").title("§lInfection"));
+//!     scoreboard.set_lines(["Humans: 4", "Zombies: 2"]);
+//! });
+//! ```
+
+use std::io::Write;
+
+use flecs_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::{
+    VarInt,
+    packets::{
+        play,
+        play::{
+            scoreboard_display_s2c::ScoreboardPosition,
+            scoreboard_objective_update_s2c::{ObjectiveMode, ObjectiveRenderType},
+            scoreboard_player_update_s2c::ScoreboardPlayerUpdateAction,
+            team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
+        },
+    },
+};
+use valence_text::IntoText;
+
+use crate::{
+    PacketBundle,
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::PacketState,
+    system_registry::SCOREBOARD,
+};
+
+/// An objective shown by a [`Scoreboard`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Objective {
+    name: String,
+    title: String,
+    position: ScoreboardPosition,
+}
+
+impl Objective {
+    /// An objective shown in the sidebar, titled with its name until [`Self::title`] is set.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self {
+            title: name.clone(),
+            name,
+            position: ScoreboardPosition::Sidebar,
+        }
+    }
+
+    #[must_use]
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    #[must_use]
+    pub const fn position(mut self, position: ScoreboardPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A change to a scoreboard as clients see it. Each change is sent as a single packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScoreboardChange {
+    CreateObjective {
+        name: String,
+        title: String,
+    },
+    UpdateTitle {
+        name: String,
+        title: String,
+    },
+    RemoveObjective {
+        name: String,
+    },
+    /// Shows the objective `name` at `position`, or nothing if `name` is empty.
+    Display {
+        position: ScoreboardPosition,
+        name: String,
+    },
+    SetScore {
+        objective: String,
+        line: String,
+        score: i32,
+    },
+    RemoveScore {
+        objective: String,
+        line: String,
+    },
+}
+
+impl PacketBundle for &ScoreboardChange {
+    fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
+        match self {
+            ScoreboardChange::CreateObjective { name, title } => {
+                let pkt = play::ScoreboardObjectiveUpdateS2c {
+                    objective_name: name,
+                    mode: ObjectiveMode::Create {
+                        objective_display_name: title.as_str().into_cow_text(),
+                        render_type: ObjectiveRenderType::Integer,
+                    },
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+            ScoreboardChange::UpdateTitle { name, title } => {
+                let pkt = play::ScoreboardObjectiveUpdateS2c {
+                    objective_name: name,
+                    mode: ObjectiveMode::Update {
+                        objective_display_name: title.as_str().into_cow_text(),
+                        render_type: ObjectiveRenderType::Integer,
+                    },
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+            ScoreboardChange::RemoveObjective { name } => {
+                let pkt = play::ScoreboardObjectiveUpdateS2c {
+                    objective_name: name,
+                    mode: ObjectiveMode::Remove,
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+            ScoreboardChange::Display { position, name } => {
+                let pkt = play::ScoreboardDisplayS2c {
+                    position: *position,
+                    score_name: name,
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+            ScoreboardChange::SetScore {
+                objective,
+                line,
+                score,
+            } => {
+                let pkt = play::ScoreboardPlayerUpdateS2c {
+                    entity_name: line,
+                    action: ScoreboardPlayerUpdateAction::Update {
+                        objective_name: objective,
+                        objective_score: VarInt(*score),
+                    },
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+            ScoreboardChange::RemoveScore { objective, line } => {
+                let pkt = play::ScoreboardPlayerUpdateS2c {
+                    entity_name: line,
+                    action: ScoreboardPlayerUpdateAction::Remove {
+                        objective_name: objective,
+                    },
+                };
+                pkt.encode_including_ids(&mut w)
+            }
+        }
+    }
+}
+
+/// An objective and its lines. Changes are only sent once a tick, as the difference from what
+/// clients were last sent.
+#[derive(Component, Clone, Debug, Default)]
+pub struct Scoreboard {
+    objective: Option<Objective>,
+    /// Each line and its score. Lines are sorted by descending score on the client.
+    lines: Vec<(String, i32)>,
+    sent_objective: Option<Objective>,
+    sent_lines: Vec<(String, i32)>,
+}
+
+impl Scoreboard {
+    /// Shows `objective`, keeping the lines already set.
+    pub fn show(&mut self, objective: Objective) {
+        self.objective = Some(objective);
+    }
+
+    /// Removes the objective along with its lines.
+    pub fn hide(&mut self) {
+        self.objective = None;
+        self.lines.clear();
+    }
+
+    #[must_use]
+    pub const fn objective(&self) -> Option<&Objective> {
+        self.objective.as_ref()
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> &[(String, i32)] {
+        &self.lines
+    }
+
+    /// Shows `line` with `score`, moving it if it is already shown.
+    pub fn set_line(&mut self, line: impl Into<String>, score: i32) {
+        let line = line.into();
+
+        match self
+            .lines
+            .iter_mut()
+            .find(|(existing, _)| *existing == line)
+        {
+            Some((_, existing)) => *existing = score,
+            None => self.lines.push((line, score)),
+        }
+    }
+
+    pub fn remove_line(&mut self, line: &str) {
+        self.lines.retain(|(existing, _)| existing != line);
+    }
+
+    /// Replaces every line with `lines`, top to bottom. Lines are keyed by their text, so each has
+    /// to be different; an invisible color code such as `§0` keeps identical lines apart.
+    pub fn set_lines<S: Into<String>>(&mut self, lines: impl IntoIterator<Item = S>) {
+        let lines: Vec<String> = lines.into_iter().map(Into::into).collect();
+        let len = lines.len();
+
+        self.lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| (line, i32::try_from(len - i).unwrap_or(i32::MAX)))
+            .collect();
+    }
+
+    /// The changes to send since this was last called.
+    pub fn take_changes(&mut self) -> Vec<ScoreboardChange> {
+        let mut changes = Vec::new();
+
+        match (&self.sent_objective, &self.objective) {
+            (Some(sent), Some(objective)) if sent.name == objective.name => {
+                if sent.title != objective.title {
+                    changes.push(ScoreboardChange::UpdateTitle {
+                        name: objective.name.clone(),
+                        title: objective.title.clone(),
+                    });
+                }
+
+                if sent.position != objective.position {
+                    changes.push(ScoreboardChange::Display {
+                        position: sent.position,
+                        name: String::new(),
+                    });
+                    changes.push(ScoreboardChange::Display {
+                        position: objective.position,
+                        name: objective.name.clone(),
+                    });
+                }
+            }
+            (sent, objective) => {
+                // removing an objective removes its scores too
+                if let Some(sent) = sent {
+                    changes.push(ScoreboardChange::RemoveObjective {
+                        name: sent.name.clone(),
+                    });
+                    self.sent_lines.clear();
+                }
+
+                if let Some(objective) = objective {
+                    changes.extend(create(objective));
+                }
+            }
+        }
+
+        if let Some(objective) = &self.objective {
+            for (line, _) in &self.sent_lines {
+                if !self.lines.iter().any(|(new, _)| new == line) {
+                    changes.push(ScoreboardChange::RemoveScore {
+                        objective: objective.name.clone(),
+                        line: line.clone(),
+                    });
+                }
+            }
+
+            // a line that only moved is updated in place by sending it with its new score
+            for entry in &self.lines {
+                if !self.sent_lines.contains(entry) {
+                    changes.push(ScoreboardChange::SetScore {
+                        objective: objective.name.clone(),
+                        line: entry.0.clone(),
+                        score: entry.1,
+                    });
+                }
+            }
+        }
+
+        self.sent_objective.clone_from(&self.objective);
+        self.sent_lines.clone_from(&self.lines);
+
+        changes
+    }
+
+    /// The changes that show a player who just joined what everyone else was last sent.
+    #[must_use]
+    pub fn join_changes(&self) -> Vec<ScoreboardChange> {
+        let Some(objective) = &self.sent_objective else {
+            return Vec::new();
+        };
+
+        let mut changes = create(objective);
+
+        changes.extend(
+            self.sent_lines
+                .iter()
+                .map(|(line, score)| ScoreboardChange::SetScore {
+                    objective: objective.name.clone(),
+                    line: line.clone(),
+                    score: *score,
+                }),
+        );
+
+        changes
+    }
+
+    /// Adds the packets that show a player who just joined the scoreboard to `bundle`.
+    pub fn add_join_packets(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        for change in self.join_changes() {
+            bundle.add_packet(&change, world)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn create(objective: &Objective) -> Vec<ScoreboardChange> {
+    vec![
+        ScoreboardChange::CreateObjective {
+            name: objective.name.clone(),
+            title: objective.title.clone(),
+        },
+        ScoreboardChange::Display {
+            position: objective.position,
+            name: objective.name.clone(),
+        },
+    ]
+}
+
+/// A scoreboard team, which colors the names of its members and decides whether their nametags
+/// are shown.
+#[derive(Clone, Debug)]
+pub struct ScoreboardTeam {
+    name: String,
+    display_name: String,
+    color: TeamColor,
+    prefix: String,
+    suffix: String,
+    name_tags: NameTagVisibility,
+    collision: CollisionRule,
+    flags: TeamFlags,
+}
+
+impl ScoreboardTeam {
+    /// A white team with nametags and collisions like having no team.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            display_name: String::new(),
+            color: TeamColor::White,
+            prefix: String::new(),
+            suffix: String::new(),
+            name_tags: NameTagVisibility::Always,
+            collision: CollisionRule::Always,
+            flags: TeamFlags::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = display_name.into();
+        self
+    }
+
+    #[must_use]
+    pub const fn color(mut self, color: TeamColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Text shown before the names of members.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Text shown after the names of members.
+    #[must_use]
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    #[must_use]
+    pub const fn name_tags(mut self, visibility: NameTagVisibility) -> Self {
+        self.name_tags = visibility;
+        self
+    }
+
+    #[must_use]
+    pub const fn collision(mut self, rule: CollisionRule) -> Self {
+        self.collision = rule;
+        self
+    }
+
+    /// Whether members can hurt each other and see invisible teammates.
+    #[must_use]
+    pub const fn flags(mut self, flags: TeamFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A change to the scoreboard teams as clients see it. Each change is sent as a single packet.
+#[derive(Clone, Debug)]
+pub enum TeamChange {
+    Create {
+        team: ScoreboardTeam,
+        members: Vec<String>,
+    },
+    Remove {
+        team: String,
+    },
+    AddMembers {
+        team: String,
+        members: Vec<String>,
+    },
+    RemoveMembers {
+        team: String,
+        members: Vec<String>,
+    },
+}
+
+impl PacketBundle for &TeamChange {
+    fn encode_including_ids(self, mut w: impl Write) -> anyhow::Result<()> {
+        let names = |members: &[String]| members.iter().map(String::as_str).collect();
+
+        let (team_name, mode) = match self {
+            TeamChange::Create { team, members } => {
+                let mode = Mode::CreateTeam {
+                    team_display_name: team.display_name.as_str().into_cow_text(),
+                    friendly_flags: team.flags,
+                    name_tag_visibility: team.name_tags,
+                    collision_rule: team.collision,
+                    team_color: team.color,
+                    team_prefix: team.prefix.as_str().into_cow_text(),
+                    team_suffix: team.suffix.as_str().into_cow_text(),
+                    entities: names(members),
+                };
+
+                (team.name.as_str(), mode)
+            }
+            TeamChange::Remove { team } => (team.as_str(), Mode::RemoveTeam),
+            TeamChange::AddMembers { team, members } => {
+                let mode = Mode::AddEntities {
+                    entities: names(members),
+                };
+
+                (team.as_str(), mode)
+            }
+            TeamChange::RemoveMembers { team, members } => {
+                let mode = Mode::RemoveEntities {
+                    entities: names(members),
+                };
+
+                (team.as_str(), mode)
+            }
+        };
+
+        play::TeamS2c { team_name, mode }.encode_including_ids(&mut w)
+    }
+}
+
+/// The scoreboard teams everyone sees. Members are players' names.
+#[derive(Component, Debug, Default)]
+pub struct ScoreboardTeams {
+    teams: Vec<(ScoreboardTeam, Vec<String>)>,
+    changes: Vec<TeamChange>,
+    sent: Vec<(ScoreboardTeam, Vec<String>)>,
+}
+
+impl ScoreboardTeams {
+    /// Creates `team`, replacing a team with the same name.
+    pub fn create(&mut self, team: ScoreboardTeam) {
+        self.remove(&team.name);
+
+        self.changes.push(TeamChange::Create {
+            team: team.clone(),
+            members: Vec::new(),
+        });
+        self.teams.push((team, Vec::new()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let before = self.teams.len();
+        self.teams.retain(|(team, _)| team.name != name);
+
+        if self.teams.len() != before {
+            self.changes.push(TeamChange::Remove {
+                team: name.to_owned(),
+            });
+        }
+    }
+
+    /// The team `member` is on.
+    #[must_use]
+    pub fn team_of(&self, member: &str) -> Option<&ScoreboardTeam> {
+        self.teams
+            .iter()
+            .find(|(_, members)| members.iter().any(|existing| existing == member))
+            .map(|(team, _)| team)
+    }
+
+    /// Moves `member` to the team `name`, taking them off any other team. Returns `false` if
+    /// there is no such team.
+    pub fn add_member(&mut self, name: &str, member: impl Into<String>) -> bool {
+        let member = member.into();
+
+        if !self.teams.iter().any(|(team, _)| team.name == name) {
+            return false;
+        }
+
+        if self.team_of(&member).is_some_and(|team| team.name == name) {
+            return true;
+        }
+
+        self.remove_member(&member);
+
+        let Some((_, members)) = self.teams.iter_mut().find(|(team, _)| team.name == name) else {
+            return false;
+        };
+
+        members.push(member.clone());
+        self.changes.push(TeamChange::AddMembers {
+            team: name.to_owned(),
+            members: vec![member],
+        });
+
+        true
+    }
+
+    /// Takes `member` off their team, if they are on one.
+    pub fn remove_member(&mut self, member: &str) {
+        for (team, members) in &mut self.teams {
+            let before = members.len();
+            members.retain(|existing| existing != member);
+
+            if members.len() != before {
+                self.changes.push(TeamChange::RemoveMembers {
+                    team: team.name.clone(),
+                    members: vec![member.to_owned()],
+                });
+            }
+        }
+    }
+
+    /// The changes to send to everyone since this was last called.
+    pub fn take_changes(&mut self) -> Vec<TeamChange> {
+        self.sent.clone_from(&self.teams);
+        std::mem::take(&mut self.changes)
+    }
+
+    /// The changes that show a player who just joined every team as everyone else was last sent
+    /// it. Changes that were not sent yet reach them along with everyone else, so no team is
+    /// created twice.
+    #[must_use]
+    pub fn join_changes(&self) -> Vec<TeamChange> {
+        self.sent
+            .iter()
+            .map(|(team, members)| TeamChange::Create {
+                team: team.clone(),
+                members: members.clone(),
+            })
+            .collect()
+    }
+
+    /// Adds the packets that show a player who just joined every team to `bundle`.
+    pub fn add_join_packets(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        for change in self.join_changes() {
+            bundle.add_packet(&change, world)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+pub struct ScoreboardModule;
+
+impl Module for ScoreboardModule {
+    fn module(world: &World) {
+        world.component::<Scoreboard>();
+        world.component::<ScoreboardTeams>();
+        world.set(Scoreboard::default());
+        world.set(ScoreboardTeams::default());
+
+        system!(
+            "broadcast_scoreboard",
+            world,
+            &Compose($),
+            &mut Scoreboard($),
+            &mut ScoreboardTeams($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, scoreboard, teams)| {
+            let world = it.world();
+
+            for change in teams.take_changes() {
+                if let Err(e) = compose.broadcast(&change, SCOREBOARD).send(&world) {
+                    warn!("failed to send team change: {e}");
+                }
+            }
+
+            for change in scoreboard.take_changes() {
+                if let Err(e) = compose.broadcast(&change, SCOREBOARD).send(&world) {
+                    warn!("failed to send scoreboard change: {e}");
+                }
+            }
+        });
+
+        system!(
+            "send_player_scoreboards",
+            world,
+            &Compose($),
+            &NetworkStreamRef,
+            &mut Scoreboard,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, &io, scoreboard)| {
+            let world = it.world();
+
+            let mut bundle = DataBundle::new(compose);
+
+            for change in scoreboard.take_changes() {
+                if let Err(e) = bundle.add_packet(&change, &world) {
+                    warn!("failed to encode scoreboard change: {e}");
+                }
+            }
+
+            if let Err(e) = bundle.send(&world, io, SCOREBOARD) {
+                warn!("failed to send scoreboard: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::play::scoreboard_display_s2c::ScoreboardPosition;
+
+    use super::{
+        Objective, Scoreboard, ScoreboardChange, ScoreboardTeam, ScoreboardTeams, TeamChange,
+    };
+
+    fn set(line: &str, score: i32) -> ScoreboardChange {
+        ScoreboardChange::SetScore {
+            objective: "round".to_owned(),
+            line: line.to_owned(),
+            score,
+        }
+    }
+
+    #[test]
+    fn only_changed_lines_are_sent() {
+        let mut scoreboard = Scoreboard::default();
+        assert!(scoreboard.take_changes().is_empty());
+
+        scoreboard.show(Objective::new("round").title("Infection"));
+        scoreboard.set_lines(["Time: 1:35", "Humans: 4", "Zombies: 2"]);
+
+        assert_eq!(scoreboard.take_changes(), [
+            ScoreboardChange::CreateObjective {
+                name: "round".to_owned(),
+                title: "Infection".to_owned(),
+            },
+            ScoreboardChange::Display {
+                position: ScoreboardPosition::Sidebar,
+                name: "round".to_owned(),
+            },
+            set("Time: 1:35", 3),
+            set("Humans: 4", 2),
+            set("Zombies: 2", 1),
+        ]);
+
+        // drawing the same lines again sends nothing
+        scoreboard.set_lines(["Time: 1:35", "Humans: 4", "Zombies: 2"]);
+        assert!(scoreboard.take_changes().is_empty());
+
+        scoreboard.set_lines(["Time: 1:34", "Humans: 4", "Zombies: 2"]);
+        assert_eq!(scoreboard.take_changes(), [
+            ScoreboardChange::RemoveScore {
+                objective: "round".to_owned(),
+                line: "Time: 1:35".to_owned(),
+            },
+            set("Time: 1:34", 3),
+        ]);
+
+        // a line that moves is only resent with its new score
+        scoreboard.set_lines(["Humans: 4", "Zombies: 2"]);
+        assert_eq!(scoreboard.take_changes(), [ScoreboardChange::RemoveScore {
+            objective: "round".to_owned(),
+            line: "Time: 1:34".to_owned(),
+        },]);
+        scoreboard.set_line("Kills: 3", 10);
+        scoreboard.set_line("Humans: 4", 5);
+        assert_eq!(scoreboard.take_changes(), [
+            set("Humans: 4", 5),
+            set("Kills: 3", 10)
+        ]);
+    }
+
+    #[test]
+    fn replacing_the_objective_starts_over() {
+        let mut scoreboard = Scoreboard::default();
+        scoreboard.show(Objective::new("lobby"));
+        scoreboard.set_line("Players: 1", 1);
+        scoreboard.take_changes();
+
+        scoreboard.show(Objective::new("lobby").title("Lobby"));
+        assert_eq!(scoreboard.take_changes(), [ScoreboardChange::UpdateTitle {
+            name: "lobby".to_owned(),
+            title: "Lobby".to_owned(),
+        }]);
+
+        // the scores of a removed objective are gone on the client, so every line is resent
+        scoreboard.show(Objective::new("round"));
+        let changes = scoreboard.take_changes();
+        assert_eq!(changes[0], ScoreboardChange::RemoveObjective {
+            name: "lobby".to_owned(),
+        });
+        assert_eq!(changes.last(), Some(&set("Players: 1", 1)));
+
+        scoreboard.hide();
+        assert_eq!(scoreboard.take_changes(), [
+            ScoreboardChange::RemoveObjective {
+                name: "round".to_owned(),
+            }
+        ]);
+        assert!(scoreboard.join_changes().is_empty());
+    }
+
+    #[test]
+    fn joining_players_see_what_was_last_sent() {
+        let mut scoreboard = Scoreboard::default();
+        scoreboard.show(Objective::new("round"));
+        scoreboard.set_line("Humans: 4", 1);
+        scoreboard.take_changes();
+
+        // not sent yet, so everyone is told about it along with the player who joined
+        scoreboard.set_line("Zombies: 2", 0);
+
+        let changes = scoreboard.join_changes();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2], set("Humans: 4", 1));
+    }
+
+    #[test]
+    fn players_are_on_at_most_one_team() {
+        let mut teams = ScoreboardTeams::default();
+        teams.create(ScoreboardTeam::new("zombies"));
+        teams.create(ScoreboardTeam::new("humans"));
+
+        assert!(teams.add_member("humans", "Steve"));
+        assert!(teams.add_member("zombies", "Steve"));
+        assert!(!teams.add_member("spectators", "Steve"));
+        assert_eq!(
+            teams.team_of("Steve").map(ScoreboardTeam::name),
+            Some("zombies")
+        );
+
+        let changes = teams.take_changes();
+        assert!(matches!(
+            &changes[..],
+            [
+                TeamChange::Create { .. },
+                TeamChange::Create { .. },
+                TeamChange::AddMembers { team: humans, .. },
+                TeamChange::RemoveMembers { team: left, .. },
+                TeamChange::AddMembers { team: zombies, .. },
+            ] if humans == "humans" && left == "humans" && zombies == "zombies"
+        ));
+
+        // moving to the same team again changes nothing
+        assert!(teams.add_member("zombies", "Steve"));
+        assert!(teams.take_changes().is_empty());
+
+        teams.remove("zombies");
+        assert!(teams.team_of("Steve").is_none());
+    }
+
+    #[test]
+    fn joining_players_get_every_team_that_was_sent() {
+        let mut teams = ScoreboardTeams::default();
+        teams.create(ScoreboardTeam::new("zombies"));
+        teams.add_member("zombies", "Steve");
+
+        // the team is created for everyone in play on the next broadcast, so those joining now
+        // must not get it twice
+        assert!(teams.join_changes().is_empty());
+
+        teams.take_changes();
+        teams.add_member("zombies", "Alex");
+
+        let changes = teams.join_changes();
+        assert!(matches!(
+            &changes[..],
+            [TeamChange::Create { team, members }]
+                if team.name() == "zombies" && members == &["Steve"]
+        ));
+
+        // players joining rounds later still see the team with everyone infected so far
+        teams.take_changes();
+        assert!(matches!(
+            &teams.join_changes()[..],
+            [TeamChange::Create { members, .. }] if members == &["Steve", "Alex"]
+        ));
+    }
+}
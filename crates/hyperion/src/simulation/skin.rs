@@ -1,9 +1,8 @@
 //! Constructs for obtaining a player's skin.
-use anyhow::Context;
-use base64::{Engine as _, engine::general_purpose};
 use flecs_ecs::macros::Component;
 use rkyv::Archive;
 use tracing::info;
+use valence_protocol::profile::Property;
 
 use crate::{storage::SkinHandler, util::mojang::MojangClient};
 
@@ -59,39 +58,34 @@ impl PlayerSkin {
 
         info!("player skin cache miss for {uuid}");
 
-        let json_object = mojang.data_from_uuid(&uuid).await?;
-        let properties_array = json_object["properties"]
-            .as_array()
-            .with_context(|| format!("no properties on {json_object:?}"))?;
-        for property_object in properties_array {
-            let name = property_object["name"]
-                .as_str()
-                .with_context(|| format!("no name on {property_object:?}"))?;
-            if name != "textures" {
-                continue;
-            }
-            let textures = property_object["value"]
-                .as_str()
-                .with_context(|| format!("no value on {property_object:?}"))?;
-            let signature = property_object["signature"]
-                .as_str()
-                .with_context(|| format!("no signature on {property_object:?}"))?;
+        let Some(skin) = mojang.get_skin(uuid).await? else {
+            return Ok(None);
+        };
 
-            // Validate base64 encoding
-            general_purpose::STANDARD
-                .decode(textures)
-                .context("invalid texture value")?;
-            general_purpose::STANDARD
-                .decode(signature)
-                .context("invalid signature value")?;
+        skins.insert(uuid, &skin)?;
+        Ok(Some(skin))
+    }
+
+    /// Whether this is [`Self::EMPTY`], which clients show as a default skin.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
 
-            let res = Self {
-                textures: textures.to_string(),
-                signature: signature.to_string(),
-            };
-            skins.insert(uuid, &res)?;
-            return Ok(Some(res));
+    /// The profile properties clients read this skin from, such as in [`PlayerListS2c`]. An empty
+    /// skin has none.
+    ///
+    /// [`PlayerListS2c`]: crate::egress::player_join::PlayerListS2c
+    #[must_use]
+    pub fn properties(&self) -> Vec<Property> {
+        if self.is_empty() {
+            return Vec::new();
         }
-        Ok(None)
+
+        vec![Property {
+            name: "textures".to_owned(),
+            value: self.textures.clone(),
+            signature: Some(self.signature.clone()),
+        }]
     }
 }
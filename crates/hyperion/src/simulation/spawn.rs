@@ -0,0 +1,165 @@
+//! The world spawn and per-player respawn anchors.
+//!
+//! Players join at the world spawn and respawn there unless a game gave them a [`RespawnAnchor`],
+//! such as the spawn of their team. Compasses point to the world spawn.
+
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use tracing::warn;
+use valence_protocol::packets::play;
+
+use crate::{
+    config::{ServerConfig, Spawn},
+    net::Compose,
+    simulation::{
+        Position,
+        blocks::{Blocks, GetChunk},
+    },
+    system_registry::SPAWN,
+};
+
+/// Where players join for the first time and respawn unless they have a [`RespawnAnchor`].
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub position: Vec3,
+    /// The direction players face when they spawn.
+    pub yaw: f32,
+}
+
+impl SpawnPoint {
+    #[must_use]
+    pub const fn new(position: Vec3) -> Self {
+        Self { position, yaw: 0.0 }
+    }
+
+    /// The center of the block at `spawn`.
+    #[must_use]
+    pub fn from_config(spawn: &Spawn) -> Self {
+        let position = Vec3::new(spawn.x as f32 + 0.5, spawn.y as f32, spawn.z as f32 + 0.5);
+
+        Self::new(position)
+    }
+
+    /// The packet that makes compasses point here.
+    #[must_use]
+    pub fn packet(&self) -> play::PlayerSpawnPositionS2c {
+        play::PlayerSpawnPositionS2c {
+            position: self.position.as_dvec3().into(),
+            angle: self.yaw,
+        }
+    }
+}
+
+/// Where a player respawns instead of the [`SpawnPoint`].
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct RespawnAnchor(pub Vec3);
+
+/// Makes `player` respawn at `position` from now on.
+pub fn set_respawn(player: EntityView<'_>, position: Vec3) {
+    player.set(RespawnAnchor(position));
+}
+
+/// Makes `player` respawn at the world spawn again.
+pub fn clear_respawn(player: EntityView<'_>) {
+    player.remove::<RespawnAnchor>();
+}
+
+/// Where `player` respawns: their [`RespawnAnchor`] if its chunk is loaded, otherwise the world
+/// spawn. An anchor in a chunk that is not loaded yet starts loading it, so it can be used next
+/// time.
+#[must_use]
+pub fn respawn_position(player: EntityView<'_>) -> Vec3 {
+    let world = player.world();
+
+    let spawn = world.get::<&SpawnPoint>(|spawn| spawn.position);
+    let anchor = player.try_get::<&RespawnAnchor>(|anchor| anchor.0);
+
+    resolve_respawn(anchor, spawn, |anchor| {
+        let chunk = Position::from(anchor).to_chunk();
+
+        world
+            .try_get::<&Blocks>(|blocks| {
+                matches!(blocks.get_cached_or_load(chunk), GetChunk::Loaded(_))
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// Falls back from `anchor` to `spawn` if there is no anchor or `is_loaded` says the chunk of the
+/// anchor is not loaded.
+fn resolve_respawn(
+    anchor: Option<Vec3>,
+    spawn: Vec3,
+    is_loaded: impl FnOnce(Vec3) -> bool,
+) -> Vec3 {
+    match anchor {
+        Some(anchor) if is_loaded(anchor) => anchor,
+        Some(anchor) => {
+            warn!("the chunk of the respawn anchor at {anchor} is not loaded, using world spawn");
+            spawn
+        }
+        None => spawn,
+    }
+}
+
+/// Moves the world spawn and points the compasses of everyone online to it.
+pub fn set_world_spawn(world: &World, spawn: SpawnPoint) {
+    world.set(spawn);
+
+    world.get::<&Compose>(|compose| {
+        if let Err(e) = compose.broadcast(&spawn.packet(), SPAWN).send(world) {
+            warn!("failed to send the world spawn: {e}");
+        }
+    });
+}
+
+#[derive(Component)]
+pub struct WorldSpawnModule;
+
+impl Module for WorldSpawnModule {
+    fn module(world: &World) {
+        world.component::<SpawnPoint>();
+        world.component::<RespawnAnchor>();
+
+        let spawn = world.get::<&ServerConfig>(|config| SpawnPoint::from_config(&config.spawn));
+        world.set(spawn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use valence_protocol::BlockPos;
+
+    use super::{SpawnPoint, resolve_respawn};
+    use crate::config::Spawn;
+
+    #[test]
+    fn respawn_falls_back_to_world_spawn() {
+        let spawn = Vec3::new(0.5, 64.0, 0.5);
+        let anchor = Vec3::new(100.5, 70.0, -20.5);
+
+        assert_eq!(resolve_respawn(None, spawn, |_| true), spawn);
+        assert_eq!(resolve_respawn(Some(anchor), spawn, |_| true), anchor);
+
+        // the anchor is somewhere that could not be loaded
+        assert_eq!(resolve_respawn(Some(anchor), spawn, |_| false), spawn);
+    }
+
+    #[test]
+    fn joining_players_are_told_the_world_spawn() {
+        let config = Spawn {
+            x: 10,
+            y: 70,
+            z: -3,
+            ..Spawn::default()
+        };
+
+        let spawn = SpawnPoint::from_config(&config);
+        assert_eq!(spawn.position, Vec3::new(10.5, 70.0, -2.5));
+
+        let pkt = spawn.packet();
+        assert_eq!(pkt.position, BlockPos::new(10, 70, -3));
+        assert!(pkt.angle.abs() < f32::EPSILON);
+    }
+}
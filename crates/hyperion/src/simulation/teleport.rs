@@ -0,0 +1,402 @@
+//! Server-initiated teleports.
+//!
+//! The client must acknowledge every [`play::PlayerPositionLookS2c`] with a
+//! [`play::TeleportConfirmC2s`] carrying the same teleport id. Until it does, any movement packets
+//! it sends are relative to its old position, so they are ignored while a [`PendingTeleport`] is
+//! present.
+
+use std::{
+    borrow::Cow,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use tracing::warn;
+use valence_protocol::{
+    Ident, VarInt,
+    game_mode::OptGameMode,
+    packets::{play, play::player_position_look_s2c::PlayerPositionLookFlags},
+};
+
+use crate::{
+    egress::sync_chunks::ChunkSendQueue,
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::{ChunkPosition, Pitch, Position, Yaw, fall::FallDistance, game_mode::GameMode},
+    system_registry::TELEPORT,
+};
+
+/// Teleport id `1` is used by the join sequence.
+static NEXT_TELEPORT_ID: AtomicI32 = AtomicI32::new(2);
+
+/// A teleport that has been sent to the client but not yet confirmed.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct PendingTeleport {
+    pub teleport_id: i32,
+    pub destination: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// The tick the teleport packet was last sent on.
+    pub sent_tick: i64,
+}
+
+impl PendingTeleport {
+    /// How long to wait for a confirmation before sending the teleport again.
+    pub const RESEND_TICKS: i64 = 20;
+
+    /// Creates a teleport with a fresh teleport id.
+    #[must_use]
+    pub fn new(destination: Vec3, yaw: f32, pitch: f32, tick: i64) -> Self {
+        let teleport_id = NEXT_TELEPORT_ID.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            teleport_id,
+            destination,
+            yaw,
+            pitch,
+            sent_tick: tick,
+        }
+    }
+
+    #[must_use]
+    pub fn packet(&self) -> play::PlayerPositionLookS2c {
+        play::PlayerPositionLookS2c {
+            position: self.destination.as_dvec3(),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            flags: PlayerPositionLookFlags::default(),
+            teleport_id: VarInt(self.teleport_id),
+        }
+    }
+
+    /// Whether a [`play::TeleportConfirmC2s`] with the given id confirms this teleport. Confirms
+    /// for older teleports are stale and do not.
+    #[must_use]
+    pub const fn is_confirmed_by(&self, teleport_id: i32) -> bool {
+        self.teleport_id == teleport_id
+    }
+
+    #[must_use]
+    pub const fn should_resend(&self, tick: i64) -> bool {
+        tick - self.sent_tick >= Self::RESEND_TICKS
+    }
+}
+
+/// Teleports a player to `destination`, optionally changing where they are looking.
+///
+/// The new position is applied on the server immediately and movement packets from the client are
+/// ignored until it confirms the teleport.
+pub fn teleport(entity: EntityView<'_>, destination: Vec3, yaw_pitch: Option<(f32, f32)>) {
+    send_teleport(entity, destination, yaw_pitch, None);
+}
+
+/// Like [`teleport`], but into `dimension`, such as another world. The dimension must be one the
+/// client was told about when it joined.
+///
+/// The client is respawned into the dimension first, which unloads every chunk it has, so the
+/// chunks around the destination are sent again. It forgets its position when it respawns, so the
+/// teleport is sent right after the respawn packet.
+pub fn teleport_to_dimension(
+    entity: EntityView<'_>,
+    dimension: Ident<Cow<'_, str>>,
+    destination: Vec3,
+    yaw_pitch: Option<(f32, f32)>,
+) {
+    // the client takes on the game mode in the respawn packet
+    let game_mode = entity
+        .try_get::<&GameMode>(|mode| mode.0)
+        .unwrap_or_default();
+
+    let respawn = play::PlayerRespawnS2c {
+        dimension_type_name: dimension.clone(),
+        dimension_name: dimension,
+        hashed_seed: 0,
+        game_mode,
+        previous_game_mode: OptGameMode::default(),
+        is_debug: false,
+        is_flat: false,
+        copy_metadata: true,
+        last_death_location: None,
+        portal_cooldown: VarInt::default(),
+    };
+
+    entity.get::<(&mut ChunkPosition, &mut ChunkSendQueue)>(|(chunk_position, queue)| {
+        // forget what was sent so every chunk around the destination is sent again
+        *chunk_position = ChunkPosition::null();
+        queue.clear();
+    });
+
+    send_teleport(entity, destination, yaw_pitch, Some(&respawn));
+}
+
+/// Moves the player, sending `respawn` ahead of the teleport in the same bundle if there is one.
+fn send_teleport(
+    entity: EntityView<'_>,
+    destination: Vec3,
+    yaw_pitch: Option<(f32, f32)>,
+    respawn: Option<&play::PlayerRespawnS2c<'_>>,
+) {
+    let world = entity.world();
+
+    let pending = world.get::<&Compose>(|compose| {
+        let tick = compose.global().tick;
+
+        entity.get::<(&mut Position, &mut Yaw, &mut Pitch, &NetworkStreamRef)>(
+            |(position, yaw, pitch, &io)| {
+                if let Some((new_yaw, new_pitch)) = yaw_pitch {
+                    **yaw = new_yaw;
+                    **pitch = new_pitch;
+                }
+
+                **position = destination;
+
+                // a player teleported mid-fall starts falling again from where they arrive
+                entity.try_get::<&mut FallDistance>(FallDistance::reset);
+
+                let pending = PendingTeleport::new(destination, **yaw, **pitch, tick);
+
+                if let Err(e) = send_packets(compose, io, respawn, &pending, &world) {
+                    warn!("failed to send teleport packet: {e}");
+                }
+
+                pending
+            },
+        )
+    });
+
+    entity.set(pending);
+}
+
+fn send_packets(
+    compose: &Compose,
+    io: NetworkStreamRef,
+    respawn: Option<&play::PlayerRespawnS2c<'_>>,
+    pending: &PendingTeleport,
+    world: &World,
+) -> anyhow::Result<()> {
+    let mut bundle = DataBundle::new(compose);
+
+    if let Some(respawn) = respawn {
+        bundle.add_packet(respawn, world)?;
+    }
+
+    bundle.add_packet(&pending.packet(), world)?;
+    bundle.send(world, io, TELEPORT)
+}
+
+/// Handles a [`play::TeleportConfirmC2s`] for `teleport_id`, returning whether it confirmed the
+/// pending teleport of `entity`. Movement packets are applied again once it did.
+#[must_use]
+pub fn confirm(entity: EntityView<'_>, teleport_id: i32) -> bool {
+    let confirmed = entity
+        .try_get::<&PendingTeleport>(|pending| pending.is_confirmed_by(teleport_id))
+        .unwrap_or_default();
+
+    // a stale confirm is for a teleport that has since been superseded; keep waiting
+    if confirmed {
+        entity.remove::<PendingTeleport>();
+    }
+
+    confirmed
+}
+
+#[derive(Component)]
+pub struct TeleportModule;
+
+impl Module for TeleportModule {
+    fn module(world: &World) {
+        world.component::<PendingTeleport>();
+
+        system!(
+            "resend_pending_teleports",
+            world,
+            &Compose($),
+            &NetworkStreamRef,
+            &mut PendingTeleport,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(|it, _, (compose, &io, pending)| {
+            let tick = compose.global().tick;
+
+            if !pending.should_resend(tick) {
+                return;
+            }
+
+            let world = it.world();
+
+            // the id stays the same so a late confirm for the original packet is still accepted
+            pending.sent_tick = tick;
+
+            if let Err(e) = compose.unicast(&pending.packet(), io, TELEPORT, &world) {
+                warn!("failed to resend teleport packet: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use glam::{IVec2, Vec3};
+    use valence_ident::ident;
+
+    use super::{PendingTeleport, TeleportModule, confirm, teleport, teleport_to_dimension};
+    use crate::{
+        egress::sync_chunks::ChunkSendQueue,
+        net::{Compose, NetworkStreamRef},
+        simulation::{ChunkPosition, Pitch, Position, Yaw},
+    };
+
+    /// A world running the teleport systems with a player at the origin.
+    fn world() -> (World, Entity) {
+        let world = World::new();
+        world.set(Compose::for_tests());
+        world.import::<TeleportModule>();
+
+        let player = world
+            .entity()
+            .set(Position::from(Vec3::ZERO))
+            .set(Yaw::default())
+            .set(Pitch::default())
+            .set(NetworkStreamRef::new(1))
+            .set(ChunkPosition {
+                position: IVec2::ZERO,
+            })
+            .set(ChunkSendQueue::default())
+            .id();
+
+        (world, player)
+    }
+
+    /// How many unicasts were queued for the proxy so far.
+    fn unicasts(world: &World) -> u64 {
+        world.get::<&Compose>(|compose| compose.io_buf().stats().unicast.frames)
+    }
+
+    /// Runs a tick `ticks` ticks after the last one.
+    fn advance(world: &World, ticks: i64) {
+        world.get::<&mut Compose>(|compose| compose.global_mut().tick += ticks);
+        assert!(world.progress());
+    }
+
+    fn pending(world: &World, player: Entity) -> Option<PendingTeleport> {
+        world
+            .entity_from_id(player)
+            .try_get::<&PendingTeleport>(|pending| *pending)
+    }
+
+    #[test]
+    fn teleports_are_resent_until_confirmed() {
+        let (world, player) = world();
+        let destination = Vec3::new(10.0, 64.0, -3.0);
+
+        teleport(
+            world.entity_from_id(player),
+            destination,
+            Some((90.0, 10.0)),
+        );
+
+        world
+            .entity_from_id(player)
+            .get::<(&Position, &Yaw)>(|(position, yaw)| {
+                assert_eq!(**position, destination);
+                assert_eq!(**yaw, 90.0);
+            });
+        assert_eq!(unicasts(&world), 1);
+
+        let sent = pending(&world, player).unwrap();
+
+        advance(&world, PendingTeleport::RESEND_TICKS - 1);
+        assert_eq!(unicasts(&world), 1);
+
+        advance(&world, 1);
+        assert_eq!(unicasts(&world), 2);
+
+        // the resent packet keeps the id, so the confirm for the first one still counts
+        assert_eq!(
+            pending(&world, player).unwrap().teleport_id,
+            sent.teleport_id
+        );
+        assert!(confirm(world.entity_from_id(player), sent.teleport_id));
+        assert_eq!(pending(&world, player), None);
+
+        advance(&world, PendingTeleport::RESEND_TICKS);
+        assert_eq!(unicasts(&world), 2);
+    }
+
+    #[test]
+    fn stale_confirms_keep_the_newer_teleport_pending() {
+        let (world, player) = world();
+
+        teleport(world.entity_from_id(player), Vec3::X, None);
+        let stale = pending(&world, player).unwrap();
+
+        teleport(world.entity_from_id(player), Vec3::Y, None);
+        let current = pending(&world, player).unwrap();
+
+        assert!(!confirm(world.entity_from_id(player), stale.teleport_id));
+        assert_eq!(pending(&world, player), Some(current));
+
+        // still waiting, so the newer teleport is resent
+        advance(&world, PendingTeleport::RESEND_TICKS);
+        assert_eq!(unicasts(&world), 3);
+
+        assert!(confirm(world.entity_from_id(player), current.teleport_id));
+        assert_eq!(pending(&world, player), None);
+    }
+
+    #[test]
+    fn teleports_into_another_dimension_resend_chunks() {
+        let (world, player) = world();
+        let destination = Vec3::new(0.0, 80.0, 0.0);
+
+        teleport_to_dimension(
+            world.entity_from_id(player),
+            ident!("minecraft:the_nether").into(),
+            destination,
+            None,
+        );
+
+        // the respawn and the teleport go out together
+        assert_eq!(unicasts(&world), 1);
+        assert_eq!(pending(&world, player).unwrap().destination, destination);
+
+        world
+            .entity_from_id(player)
+            .get::<&ChunkPosition>(|chunk_position| {
+                assert_eq!(chunk_position.position, ChunkPosition::null().position);
+            });
+
+        advance(&world, PendingTeleport::RESEND_TICKS);
+        assert_eq!(unicasts(&world), 2);
+    }
+
+    #[test]
+    fn teleport_ids_are_fresh() {
+        let a = PendingTeleport::new(Vec3::ZERO, 0.0, 0.0, 0);
+        let b = PendingTeleport::new(Vec3::ZERO, 0.0, 0.0, 0);
+
+        assert_ne!(a.teleport_id, b.teleport_id);
+        assert_eq!(a.packet().teleport_id.0, a.teleport_id);
+    }
+
+    #[test]
+    fn only_matching_confirm_completes_handshake() {
+        let stale = PendingTeleport::new(Vec3::ZERO, 0.0, 0.0, 0);
+        let pending = PendingTeleport::new(Vec3::new(1.0, 2.0, 3.0), 90.0, 0.0, 0);
+
+        // the client confirms a teleport that has since been superseded
+        assert!(!pending.is_confirmed_by(stale.teleport_id));
+        assert!(pending.is_confirmed_by(pending.teleport_id));
+    }
+
+    #[test]
+    fn resends_after_timeout() {
+        let pending = PendingTeleport::new(Vec3::ZERO, 0.0, 0.0, 100);
+
+        assert!(!pending.should_resend(100));
+        assert!(!pending.should_resend(100 + PendingTeleport::RESEND_TICKS - 1));
+        assert!(pending.should_resend(100 + PendingTeleport::RESEND_TICKS));
+    }
+}
@@ -0,0 +1,212 @@
+//! The time of day and the day/night cycle.
+//!
+//! The client advances the time of day on its own, so [`play::WorldTimeUpdateS2c`] only needs to
+//! be sent every so often to correct drift, and whenever the time is changed.
+
+use flecs_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::packets::play;
+
+use crate::{net::Compose, system_registry::WORLD_TIME};
+
+/// How long a full day lasts.
+pub const TICKS_PER_DAY: i64 = 24_000;
+
+/// The time of day at which the sun has risen.
+pub const DAY: i64 = 1_000;
+pub const NOON: i64 = 6_000;
+/// The time of day at which the sun has set.
+pub const NIGHT: i64 = 13_000;
+pub const MIDNIGHT: i64 = 18_000;
+
+/// The time of day at which the sun rises again.
+const SUNRISE: i64 = 23_000;
+
+/// How often the time is sent to clients even if it has not been changed, which is once a second.
+const SYNC_INTERVAL: i64 = 20;
+
+/// The age of the world and the time of day. Both are in ticks.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WorldTime {
+    world_age: i64,
+    /// Always in `0..TICKS_PER_DAY`.
+    time_of_day: i64,
+    /// Whether the time of day advances, like the `doDaylightCycle` game rule.
+    daylight_cycle: bool,
+    /// Whether the time was changed since it was last sent.
+    changed: bool,
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        Self {
+            world_age: 0,
+            time_of_day: DAY,
+            daylight_cycle: true,
+            changed: true,
+        }
+    }
+}
+
+impl WorldTime {
+    /// How many ticks the world has existed for. This advances even while the daylight cycle is
+    /// paused.
+    #[must_use]
+    pub const fn world_age(&self) -> i64 {
+        self.world_age
+    }
+
+    /// The time of day, from `0` to `23_999`.
+    #[must_use]
+    pub const fn time_of_day(&self) -> i64 {
+        self.time_of_day
+    }
+
+    /// Whether the sun is down. This is the stretch of the day in which vanilla monsters spawn.
+    #[must_use]
+    pub const fn is_night(&self) -> bool {
+        self.time_of_day >= NIGHT && self.time_of_day < SUNRISE
+    }
+
+    #[must_use]
+    pub const fn daylight_cycle(&self) -> bool {
+        self.daylight_cycle
+    }
+
+    /// Pauses or resumes the daylight cycle.
+    pub const fn set_daylight_cycle(&mut self, daylight_cycle: bool) {
+        self.daylight_cycle = daylight_cycle;
+        self.changed = true;
+    }
+
+    /// Sets the time of day. Times outside of a single day wrap around, so `24_000` is `0` and
+    /// `-1000` is `23_000`.
+    pub const fn set_time(&mut self, time_of_day: i64) {
+        self.time_of_day = time_of_day.rem_euclid(TICKS_PER_DAY);
+        self.changed = true;
+    }
+
+    /// Moves the time of day forward by `ticks`, or backward if `ticks` is negative.
+    pub const fn add_time(&mut self, ticks: i64) {
+        self.set_time(self.time_of_day + ticks);
+    }
+
+    /// Advances the world by one tick.
+    pub const fn tick(&mut self) {
+        self.world_age += 1;
+
+        if self.daylight_cycle {
+            self.time_of_day = (self.time_of_day + 1) % TICKS_PER_DAY;
+        }
+    }
+
+    /// The packet that tells a client the current time.
+    ///
+    /// A negative time of day tells the client not to advance the time itself. As `-0` is not
+    /// negative, a paused time of `0` is sent as `-1`, like vanilla does.
+    #[must_use]
+    pub const fn packet(&self) -> play::WorldTimeUpdateS2c {
+        let time_of_day = match (self.daylight_cycle, self.time_of_day) {
+            (true, time) => time,
+            (false, 0) => -1,
+            (false, time) => -time,
+        };
+
+        play::WorldTimeUpdateS2c {
+            world_age: self.world_age,
+            time_of_day,
+        }
+    }
+
+    /// Whether the time should be sent to clients this tick. Clears the changed flag.
+    const fn take_sync(&mut self) -> bool {
+        let sync = self.changed || self.world_age % SYNC_INTERVAL == 0;
+        self.changed = false;
+        sync
+    }
+}
+
+#[derive(Component)]
+pub struct TimeModule;
+
+impl Module for TimeModule {
+    fn module(world: &World) {
+        world.component::<WorldTime>();
+        world.set(WorldTime::default());
+
+        system!("advance_world_time", world, &mut WorldTime($), &Compose($))
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each_iter(|it, _, (time, compose)| {
+                let world = it.world();
+
+                time.tick();
+
+                if !time.take_sync() {
+                    return;
+                }
+
+                if let Err(e) = compose.broadcast(&time.packet(), WORLD_TIME).send(&world) {
+                    warn!("failed to send world time: {e}");
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DAY, MIDNIGHT, NIGHT, TICKS_PER_DAY, WorldTime};
+
+    #[test]
+    fn time_wraps_around_at_the_end_of_the_day() {
+        let mut time = WorldTime::default();
+
+        time.set_time(TICKS_PER_DAY - 1);
+        time.tick();
+        assert_eq!(time.time_of_day(), 0);
+        assert_eq!(time.world_age(), 1);
+
+        time.set_time(TICKS_PER_DAY + DAY);
+        assert_eq!(time.time_of_day(), DAY);
+
+        time.set_time(-DAY);
+        assert_eq!(time.time_of_day(), TICKS_PER_DAY - DAY);
+
+        time.set_time(MIDNIGHT);
+        time.add_time(TICKS_PER_DAY / 2);
+        assert_eq!(time.time_of_day(), MIDNIGHT - TICKS_PER_DAY / 2);
+    }
+
+    #[test]
+    fn paused_time_is_sent_negative() {
+        let mut time = WorldTime::default();
+        time.set_time(NIGHT);
+
+        assert_eq!(time.packet().time_of_day, NIGHT);
+
+        time.set_daylight_cycle(false);
+        time.tick();
+
+        let packet = time.packet();
+        assert_eq!(packet.time_of_day, -NIGHT);
+        assert_eq!(packet.world_age, 1);
+
+        // -0 would not be negative
+        time.set_time(0);
+        assert_eq!(time.packet().time_of_day, -1);
+    }
+
+    #[test]
+    fn night_lasts_from_sunset_to_sunrise() {
+        let mut time = WorldTime::default();
+        assert!(!time.is_night());
+
+        time.set_time(NIGHT);
+        assert!(time.is_night());
+
+        time.set_time(MIDNIGHT);
+        assert!(time.is_night());
+
+        time.set_time(0);
+        assert!(!time.is_night());
+    }
+}
@@ -116,16 +116,17 @@ pub const fn ceil_log2(x: u32) -> u32 {
     u32::BITS - x.leading_zeros()
 }
 
-/// Create a heightmap for the highest solid block at each position in the chunk.
+/// Create a heightmap from the height of each column of the chunk, which is one above its highest
+/// block counted from the bottom of the chunk, or 0 if the column is empty.
 #[must_use]
-pub fn heightmap(max_height: u32, current_height: u32) -> Vec<u64> {
+pub fn heightmap(max_height: u32, column_height: impl Fn(u8, u8) -> u32) -> Vec<u64> {
     let bits = ceil_log2(max_height + 1);
     let mut data = BitStorage::new(bits as usize, 16 * 16, None).unwrap();
 
-    for x in 0_usize..16 {
-        for z in 0_usize..16 {
-            let index = x + z * 16;
-            data.set(index, u64::from(current_height) + 1);
+    for x in 0_u8..16 {
+        for z in 0_u8..16 {
+            let index = usize::from(x) + usize::from(z) * 16;
+            data.set(index, u64::from(column_height(x, z)));
         }
     }
 
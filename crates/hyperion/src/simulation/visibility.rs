@@ -0,0 +1,331 @@
+//! Hiding entities from specific players, e.g. spectators or vanished moderators.
+
+use std::borrow::Cow;
+
+use flecs_ecs::prelude::*;
+use hyperion_utils::EntityExt;
+use roaring::RoaringBitmap;
+use tracing::warn;
+use valence_protocol::{ByteAngle, RawBytes, VarInt, packets::play};
+
+use crate::{
+    egress::metadata::show_all,
+    net::{Compose, DataBundle, NetworkStreamRef},
+    simulation::{
+        Pitch, Position, Uuid, Yaw,
+        entity::EntityMetadata,
+        metadata::{EntityFlags, MetadataBuilder},
+    },
+    system_registry::VISIBILITY,
+};
+
+/// The entities a viewer cannot see, by minecraft entity id.
+#[derive(Component, Debug, Default)]
+pub struct HiddenEntities {
+    hidden: RoaringBitmap,
+}
+
+impl HiddenEntities {
+    /// Returns `true` if the target was not already hidden.
+    pub fn hide(&mut self, target: Entity) -> bool {
+        self.hidden.insert(entity_key(target))
+    }
+
+    /// Returns `true` if the target was hidden.
+    pub fn show(&mut self, target: Entity) -> bool {
+        self.hidden.remove(entity_key(target))
+    }
+}
+
+/// The streams of every viewer an entity is hidden from. This is the inverse of
+/// [`HiddenEntities`] and is used to exclude those viewers from broadcasts about the entity.
+#[derive(Component, Debug, Default)]
+pub struct HiddenFrom {
+    streams: Vec<u64>,
+}
+
+impl HiddenFrom {
+    #[must_use]
+    pub fn streams(&self) -> &[u64] {
+        &self.streams
+    }
+
+    fn insert(&mut self, viewer: NetworkStreamRef) {
+        let stream = viewer.id();
+        if !self.streams.contains(&stream) {
+            self.streams.push(stream);
+        }
+    }
+
+    fn remove(&mut self, viewer: NetworkStreamRef) {
+        let stream = viewer.id();
+        self.streams.retain(|&s| s != stream);
+    }
+}
+
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "minecraft ids are only used as bitmap keys; the sign does not matter"
+)]
+fn entity_key(entity: Entity) -> u32 {
+    entity.minecraft_id() as u32
+}
+
+/// Hides `target` from `viewer`. The viewer receives a despawn packet and no further updates
+/// about the target until [`show_to`] is called.
+pub fn hide_from(viewer: EntityView<'_>, target: EntityView<'_>) {
+    let world = viewer.world();
+
+    viewer.get::<(&mut HiddenEntities, &NetworkStreamRef)>(|(hidden, &io)| {
+        if !hidden.hide(target.id()) {
+            return;
+        }
+
+        target.get::<&mut HiddenFrom>(|hidden_from| hidden_from.insert(io));
+
+        let entity_ids = [VarInt(target.minecraft_id())];
+        let pkt = play::EntitiesDestroyS2c {
+            entity_ids: Cow::Borrowed(&entity_ids),
+        };
+
+        world.get::<&Compose>(|compose| {
+            if let Err(e) = compose.unicast(&pkt, io, VISIBILITY, &world) {
+                warn!("failed to send despawn packet: {e}");
+            }
+        });
+    });
+}
+
+/// Shows a `target` previously hidden with [`hide_from`] to `viewer` again, re-sending everything
+/// needed to spawn it.
+pub fn show_to(viewer: EntityView<'_>, target: EntityView<'_>) {
+    let world = viewer.world();
+
+    viewer.get::<(&mut HiddenEntities, &NetworkStreamRef)>(|(hidden, &io)| {
+        if !hidden.show(target.id()) {
+            return;
+        }
+
+        target.get::<&mut HiddenFrom>(|hidden_from| hidden_from.remove(io));
+
+        let metadata = target.try_get::<&EntityMetadata>(|metadata| metadata.clone());
+
+        let spawned = target.try_get::<(&Uuid, &Position, &Yaw, &Pitch, &EntityFlags)>(
+            |(uuid, position, yaw, pitch, flags)| {
+                world.get::<&Compose>(|compose| {
+                    let result = spawn_bundle(
+                        compose,
+                        target.minecraft_id(),
+                        uuid,
+                        position,
+                        yaw,
+                        pitch,
+                        *flags,
+                        metadata.as_ref(),
+                        &world,
+                    )
+                    .and_then(|bundle| bundle.send(&world, io, VISIBILITY));
+
+                    if let Err(e) = result {
+                        warn!("failed to send respawn packets: {e}");
+                    }
+                });
+            },
+        );
+
+        if spawned.is_none() {
+            warn!("cannot show {:?}: it is not a player", target.id());
+        }
+    });
+}
+
+/// Forgets everything `player` was hidden from and everything hidden from them, e.g. when they
+/// leave. Minecraft ids are reused, so a player joining later with the same id would otherwise
+/// start out hidden.
+pub fn forget_player(player: EntityView<'_>) {
+    let Some(io) = player.try_get::<&NetworkStreamRef>(|&io| io) else {
+        return;
+    };
+
+    player
+        .world()
+        .new_query::<(&mut HiddenEntities, &mut HiddenFrom)>()
+        .each(|(hidden, hidden_from)| {
+            hidden.show(player.id());
+            hidden_from.remove(io);
+        });
+}
+
+#[expect(
+    clippy::too_many_arguments,
+    reason = "all of these are needed to spawn a player"
+)]
+fn spawn_bundle<'a>(
+    compose: &'a Compose,
+    entity_id: i32,
+    uuid: &Uuid,
+    position: &Position,
+    yaw: &Yaw,
+    pitch: &Pitch,
+    flags: EntityFlags,
+    entity_metadata: Option<&EntityMetadata>,
+    world: &World,
+) -> anyhow::Result<DataBundle<'a>> {
+    let mut bundle = DataBundle::new(compose);
+
+    let pkt = play::PlayerSpawnS2c {
+        entity_id: VarInt(entity_id),
+        player_uuid: uuid.0,
+        position: position.as_dvec3(),
+        yaw: ByteAngle::from_degrees(**yaw),
+        pitch: ByteAngle::from_degrees(**pitch),
+    };
+    bundle.add_packet(&pkt, world)?;
+
+    let show_all = show_all(entity_id);
+    bundle.add_packet(show_all.borrow_packet(), world)?;
+
+    let mut metadata = MetadataBuilder::default();
+    metadata.encode(flags);
+
+    if let Some(entity_metadata) = entity_metadata {
+        entity_metadata.add_all_to(&mut metadata);
+    }
+
+    if let Some(view) = metadata.get_and_clear() {
+        let pkt = play::EntityTrackerUpdateS2c {
+            entity_id: VarInt(entity_id),
+            tracked_values: RawBytes(&view),
+        };
+        bundle.add_packet(&pkt, world)?;
+    }
+
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use glam::Vec3;
+    use hyperion_utils::EntityExt;
+
+    use super::{HiddenEntities, HiddenFrom, entity_key, forget_player, hide_from, show_to};
+    use crate::{
+        net::{Compose, NetworkStreamRef},
+        simulation::{Pitch, Position, Uuid, Yaw, metadata::EntityFlags},
+    };
+
+    fn is_hidden(viewer: EntityView<'_>, target: EntityView<'_>) -> bool {
+        viewer.get::<&HiddenEntities>(|hidden| hidden.hidden.contains(entity_key(target.id())))
+    }
+
+    fn unicasts(world: &World) -> u64 {
+        world.get::<&Compose>(|compose| compose.io_buf().stats().unicast.frames)
+    }
+
+    #[test]
+    fn hide_then_show_toggles_exactly_once() {
+        let mut hidden = HiddenEntities::default();
+        let target = Entity::from_minecraft_id(7);
+        let other = Entity::from_minecraft_id(8);
+
+        // despawn is only sent the first time
+        assert!(hidden.hide(target));
+        assert!(!hidden.hide(target));
+        assert!(hidden.hidden.contains(entity_key(target)));
+        assert!(!hidden.hidden.contains(entity_key(other)));
+
+        // respawn is only sent if the target was hidden
+        assert!(hidden.show(target));
+        assert!(!hidden.show(target));
+        assert!(!hidden.hidden.contains(entity_key(target)));
+    }
+
+    #[test]
+    fn hidden_from_tracks_each_viewer_once() {
+        let mut hidden_from = HiddenFrom::default();
+        let a = NetworkStreamRef::new(1);
+        let b = NetworkStreamRef::new(2);
+
+        hidden_from.insert(a);
+        hidden_from.insert(a);
+        hidden_from.insert(b);
+        assert_eq!(hidden_from.streams(), &[1, 2]);
+
+        hidden_from.remove(a);
+        assert_eq!(hidden_from.streams(), &[2]);
+    }
+
+    #[test]
+    fn hidden_players_are_despawned_and_respawned_for_the_viewer() {
+        let world = World::new();
+        world.set(Compose::for_tests());
+
+        let viewer = world
+            .entity()
+            .set(HiddenEntities::default())
+            .set(NetworkStreamRef::new(1));
+
+        let target = world
+            .entity()
+            .set(HiddenFrom::default())
+            .set(Uuid(uuid::Uuid::from_u128(2)))
+            .set(Position::from(Vec3::ZERO))
+            .set(Yaw::default())
+            .set(Pitch::default())
+            .set(EntityFlags::default());
+
+        let hidden_from =
+            || target.get::<&HiddenFrom>(|hidden_from| hidden_from.streams().to_vec());
+
+        hide_from(viewer, target);
+        hide_from(viewer, target);
+
+        // one despawn packet, and broadcasts about the target skip the viewer
+        assert_eq!(unicasts(&world), 1);
+        assert_eq!(hidden_from(), [1]);
+        assert!(is_hidden(viewer, target));
+
+        show_to(viewer, target);
+        show_to(viewer, target);
+
+        // one bundle that spawns the target again
+        assert_eq!(unicasts(&world), 2);
+        assert!(hidden_from().is_empty());
+        assert!(!is_hidden(viewer, target));
+    }
+
+    #[test]
+    fn leaving_players_are_forgotten_on_both_sides() {
+        let world = World::new();
+        world.set(Compose::for_tests());
+
+        let player = |stream| {
+            world
+                .entity()
+                .set(HiddenEntities::default())
+                .set(HiddenFrom::default())
+                .set(NetworkStreamRef::new(stream))
+        };
+
+        let viewer = player(1);
+        let target = player(2);
+
+        let hidden_from =
+            || target.get::<&HiddenFrom>(|hidden_from| hidden_from.streams().to_vec());
+
+        hide_from(viewer, target);
+
+        // whoever joins with the id of the target next is not hidden, so they are despawned again
+        forget_player(target);
+        assert!(!is_hidden(viewer, target));
+
+        hide_from(viewer, target);
+        assert_eq!(unicasts(&world), 2);
+        assert_eq!(hidden_from(), [1]);
+
+        // broadcasts about the target no longer skip the stream of the viewer who left
+        forget_player(viewer);
+        assert!(hidden_from().is_empty());
+    }
+}
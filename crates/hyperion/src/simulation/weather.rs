@@ -0,0 +1,364 @@
+//! Rain, thunderstorms and lightning.
+//!
+//! Weather is purely visual: the client is told whether it is raining and how strong the rain and
+//! thunder are with [`play::GameStateChangeS2c`], and lightning bolts are spawned as entities that
+//! the client removes on its own.
+
+use std::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use flecs_ecs::prelude::*;
+use glam::{DVec3, IVec2};
+use tracing::warn;
+use valence_protocol::{
+    ByteAngle, VarInt, Velocity,
+    packets::{play, play::game_state_change_s2c::GameEventKind},
+};
+use valence_server::entity::EntityKind;
+
+use crate::{
+    config::ServerConfig,
+    net::{Compose, DataBundle},
+    simulation::blocks::Blocks,
+    system_registry::WEATHER,
+};
+
+/// On average, a lightning bolt strikes once every this many ticks during a thunderstorm.
+const LIGHTNING_CHANCE: u32 = 100;
+
+/// Lightning bolts only exist on the client, so they get ids that flecs entities never have.
+static NEXT_LIGHTNING_ID: AtomicI32 = AtomicI32::new(-1);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl WeatherKind {
+    /// How long this weather lasts when it starts on its own, in ticks. These are the vanilla
+    /// durations.
+    #[must_use]
+    pub const fn natural_duration(self) -> RangeInclusive<i64> {
+        match self {
+            Self::Clear => 12_000..=180_000,
+            Self::Rain => 12_000..=24_000,
+            Self::Thunder => 3_600..=15_600,
+        }
+    }
+
+    /// The weather that follows this one when it ends on its own.
+    fn natural_successor(self) -> Self {
+        match self {
+            Self::Clear if fastrand::u8(..3) == 0 => Self::Thunder,
+            Self::Clear => Self::Rain,
+            Self::Rain | Self::Thunder => Self::Clear,
+        }
+    }
+
+    const fn is_raining(self) -> bool {
+        !matches!(self, Self::Clear)
+    }
+
+    const fn rain_level(self) -> f32 {
+        if self.is_raining() { 1.0 } else { 0.0 }
+    }
+
+    const fn thunder_level(self) -> f32 {
+        if matches!(self, Self::Thunder) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A change of weather as the client sees it. Each event is sent as a
+/// [`play::GameStateChangeS2c`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WeatherEvent {
+    BeginRain,
+    EndRain,
+    /// From `0.0` (none) to `1.0`.
+    RainLevel(f32),
+    /// From `0.0` (none) to `1.0`.
+    ThunderLevel(f32),
+}
+
+impl WeatherEvent {
+    #[must_use]
+    pub const fn packet(self) -> play::GameStateChangeS2c {
+        // vanilla names these the other way around, but these are the names the protocol uses
+        let (kind, value) = match self {
+            Self::BeginRain => (GameEventKind::BeginRaining, 0.0),
+            Self::EndRain => (GameEventKind::EndRaining, 0.0),
+            Self::RainLevel(level) => (GameEventKind::RainLevelChange, level),
+            Self::ThunderLevel(level) => (GameEventKind::ThunderLevelChange, level),
+        };
+
+        play::GameStateChangeS2c { kind, value }
+    }
+
+    /// The events that move a client from `from` to `to`.
+    #[must_use]
+    pub fn transition(from: WeatherKind, to: WeatherKind) -> Vec<Self> {
+        let mut events = Vec::new();
+
+        match (from.is_raining(), to.is_raining()) {
+            (false, true) => events.push(Self::BeginRain),
+            (true, false) => events.push(Self::EndRain),
+            _ => {}
+        }
+
+        events.push(Self::RainLevel(to.rain_level()));
+        events.push(Self::ThunderLevel(to.thunder_level()));
+
+        events
+    }
+
+    /// The events that show a player who just joined the current weather. A client joins
+    /// thinking it is clear, so nothing needs to be sent for clear weather.
+    #[must_use]
+    pub fn join(weather: WeatherKind) -> Vec<Self> {
+        if weather.is_raining() {
+            Self::transition(WeatherKind::Clear, weather)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// The current weather and how long it lasts.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Weather {
+    kind: WeatherKind,
+    /// Ticks until the weather changes on its own.
+    remaining: i64,
+    /// Whether the weather changes on its own once `remaining` runs out.
+    cycle: bool,
+    /// The weather clients were last told about, if it has changed since.
+    previous: Option<WeatherKind>,
+}
+
+impl Weather {
+    /// Clear weather, which ends after a natural duration if `cycle` is set.
+    #[must_use]
+    pub fn new(cycle: bool) -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            remaining: fastrand::i64(WeatherKind::Clear.natural_duration()),
+            cycle,
+            previous: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> WeatherKind {
+        self.kind
+    }
+
+    /// Ticks until the weather changes on its own. This counts down even if it does not change on
+    /// its own.
+    #[must_use]
+    pub const fn remaining(&self) -> i64 {
+        self.remaining
+    }
+
+    #[must_use]
+    pub const fn cycle(&self) -> bool {
+        self.cycle
+    }
+
+    pub const fn set_cycle(&mut self, cycle: bool) {
+        self.cycle = cycle;
+    }
+
+    /// Changes the weather for `duration` ticks, or for a natural duration if `None`.
+    pub fn set(&mut self, kind: WeatherKind, duration: Option<i64>) {
+        if kind != self.kind && self.previous.is_none() {
+            self.previous = Some(self.kind);
+        }
+
+        self.kind = kind;
+        self.remaining = duration.unwrap_or_else(|| fastrand::i64(kind.natural_duration()));
+    }
+
+    /// Advances the weather by a tick, changing it if its time is up.
+    pub fn tick(&mut self) {
+        self.remaining = self.remaining.saturating_sub(1);
+
+        if self.cycle && self.remaining <= 0 {
+            self.set(self.kind.natural_successor(), None);
+        }
+    }
+
+    /// The events to send to everyone if the weather changed since this was last called.
+    pub fn take_transition(&mut self) -> Vec<WeatherEvent> {
+        match self.previous.take() {
+            Some(previous) if previous != self.kind => {
+                WeatherEvent::transition(previous, self.kind)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Adds the packets that show a player who just joined the current weather to `bundle`.
+    pub fn add_join_packets(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        for event in WeatherEvent::join(self.kind) {
+            bundle.add_packet(&event.packet(), world)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A lightning bolt striking the ground at a random loaded position.
+fn lightning_packet(blocks: &Blocks) -> Option<play::EntitySpawnS2c> {
+    let chunk = blocks.random_loaded_chunk()?;
+    let IVec2 { x, y: z } = (chunk << 4) + IVec2::new(fastrand::i32(..16), fastrand::i32(..16));
+    let y = blocks.surface_height(x, z)? + 1;
+
+    Some(play::EntitySpawnS2c {
+        entity_id: VarInt(NEXT_LIGHTNING_ID.fetch_sub(1, Ordering::Relaxed)),
+        object_uuid: uuid::Uuid::from_u128(fastrand::u128(..)),
+        kind: VarInt(EntityKind::LIGHTNING_BOLT.get()),
+        position: DVec3::new(f64::from(x) + 0.5, f64::from(y), f64::from(z) + 0.5),
+        yaw: ByteAngle::default(),
+        pitch: ByteAngle::default(),
+        head_yaw: ByteAngle::default(),
+        data: VarInt::default(),
+        velocity: Velocity([0; 3]),
+    })
+}
+
+#[derive(Component)]
+pub struct WeatherModule;
+
+impl Module for WeatherModule {
+    fn module(world: &World) {
+        let cycle = world.get::<&ServerConfig>(|config| config.weather_cycle);
+
+        world.component::<Weather>();
+        world.set(Weather::new(cycle));
+
+        system!("advance_weather", world, &mut Weather($), &Compose($))
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each_iter(|it, _, (weather, compose)| {
+                let world = it.world();
+
+                weather.tick();
+
+                for event in weather.take_transition() {
+                    if let Err(e) = compose.broadcast(&event.packet(), WEATHER).send(&world) {
+                        warn!("failed to send weather change: {e}");
+                    }
+                }
+            });
+
+        system!(
+            "strike_lightning",
+            world,
+            &Weather($),
+            &Blocks($),
+            &Compose($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(|it, _, (weather, blocks, compose)| {
+            if weather.kind() != WeatherKind::Thunder || fastrand::u32(..LIGHTNING_CHANCE) != 0 {
+                return;
+            }
+
+            let Some(pkt) = lightning_packet(blocks) else {
+                return;
+            };
+
+            let world = it.world();
+
+            if let Err(e) = compose.broadcast(&pkt, WEATHER).send(&world) {
+                warn!("failed to send lightning bolt: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Weather, WeatherEvent, WeatherKind};
+
+    #[test]
+    fn rain_then_thunder_then_clear() {
+        let mut weather = Weather::new(false);
+        assert!(weather.take_transition().is_empty());
+
+        weather.set(WeatherKind::Rain, Some(100));
+        assert_eq!(weather.take_transition(), [
+            WeatherEvent::BeginRain,
+            WeatherEvent::RainLevel(1.0),
+            WeatherEvent::ThunderLevel(0.0),
+        ]);
+
+        // it is already raining, so only the thunder changes
+        weather.set(WeatherKind::Thunder, Some(100));
+        assert_eq!(weather.take_transition(), [
+            WeatherEvent::RainLevel(1.0),
+            WeatherEvent::ThunderLevel(1.0),
+        ]);
+
+        weather.set(WeatherKind::Clear, None);
+        assert_eq!(weather.take_transition(), [
+            WeatherEvent::EndRain,
+            WeatherEvent::RainLevel(0.0),
+            WeatherEvent::ThunderLevel(0.0),
+        ]);
+
+        assert!(weather.take_transition().is_empty());
+    }
+
+    #[test]
+    fn changing_back_within_a_tick_sends_nothing() {
+        let mut weather = Weather::new(false);
+
+        weather.set(WeatherKind::Rain, None);
+        weather.set(WeatherKind::Clear, None);
+
+        assert!(weather.take_transition().is_empty());
+    }
+
+    #[test]
+    fn weather_only_cycles_when_enabled() {
+        let mut weather = Weather::new(false);
+        weather.set(WeatherKind::Rain, Some(1));
+        weather.take_transition();
+
+        weather.tick();
+        assert_eq!(weather.kind(), WeatherKind::Rain);
+
+        weather.set_cycle(true);
+        weather.tick();
+        assert_eq!(weather.kind(), WeatherKind::Clear);
+        assert!(
+            WeatherKind::Clear
+                .natural_duration()
+                .contains(&weather.remaining())
+        );
+    }
+
+    #[test]
+    fn joining_players_see_the_current_weather() {
+        assert!(WeatherEvent::join(WeatherKind::Clear).is_empty());
+
+        assert_eq!(WeatherEvent::join(WeatherKind::Thunder), [
+            WeatherEvent::BeginRain,
+            WeatherEvent::RainLevel(1.0),
+            WeatherEvent::ThunderLevel(1.0),
+        ]);
+    }
+}
@@ -0,0 +1,429 @@
+//! The world border, which can move and resize over time and hurts players left outside of it.
+//!
+//! The client interpolates the border on its own, so packets only need to be sent when the border
+//! is changed. The server interpolates it as well to know who is outside.
+//!
+//! ```ignore
+//! world.get::<&mut WorldBorder>(|border| border.shrink_to(100.0, 20 * 60));
+//! ```
+
+use flecs_ecs::prelude::*;
+use glam::{DVec2, Vec3};
+use tracing::warn;
+use valence_protocol::{VarInt, packets::play};
+
+use crate::{
+    net::{Compose, DataBundle},
+    simulation::{PacketState, Player, Position, event},
+    storage::Events,
+    system_registry::WORLD_BORDER,
+};
+
+/// The largest the border can be, which is also where a client puts it before it is told
+/// otherwise.
+pub const MAX_DIAMETER: f64 = 59_999_968.0;
+
+/// How far players can be teleported through nether portals. This is the vanilla value.
+const PORTAL_TELEPORT_BOUNDARY: i32 = 29_999_984;
+
+/// How long before the border reaches a player the warning is shown, in seconds.
+const WARNING_TIME: i32 = 15;
+
+/// How often players outside the border are hurt, which is once a second.
+const DAMAGE_INTERVAL: i64 = 20;
+
+/// A change to the border that clients have to be told about.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BorderChange {
+    Center(DVec2),
+    Diameter(f64),
+    /// The border resizes from `from` to `to` over `ticks` ticks.
+    Interpolate {
+        from: f64,
+        to: f64,
+        ticks: i64,
+    },
+    WarningBlocks(i32),
+}
+
+impl BorderChange {
+    pub fn broadcast(self, compose: &Compose, world: &World) -> anyhow::Result<()> {
+        match self {
+            Self::Center(center) => {
+                let pkt = play::WorldBorderCenterChangedS2c {
+                    x_pos: center.x,
+                    z_pos: center.y,
+                };
+                compose.broadcast(&pkt, WORLD_BORDER).send(world)?;
+            }
+            Self::Diameter(diameter) => {
+                let pkt = play::WorldBorderSizeChangedS2c { diameter };
+                compose.broadcast(&pkt, WORLD_BORDER).send(world)?;
+            }
+            Self::Interpolate { from, to, ticks } => {
+                let pkt = play::WorldBorderInterpolateSizeS2c {
+                    old_diameter: from,
+                    new_diameter: to,
+                    duration_millis: (ticks * 50).into(),
+                };
+                compose.broadcast(&pkt, WORLD_BORDER).send(world)?;
+            }
+            Self::WarningBlocks(blocks) => {
+                let pkt = play::WorldBorderWarningBlocksChangedS2c {
+                    warning_blocks: VarInt(blocks),
+                };
+                compose.broadcast(&pkt, WORLD_BORDER).send(world)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A square border around `center`. Players further outside of it than `buffer` take
+/// `damage_per_block` damage a second for each block past the buffer.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct WorldBorder {
+    center: DVec2,
+    diameter: f64,
+    /// The diameter the border is resizing from and to.
+    from: f64,
+    target: f64,
+    /// How many ticks the resize takes, and how many of them have passed.
+    duration: i64,
+    elapsed: i64,
+    damage_per_block: f32,
+    buffer: f64,
+    /// How far from the border players start seeing the red warning tint.
+    warning_blocks: i32,
+    /// Changes made since they were last sent.
+    changes: Vec<BorderChange>,
+}
+
+impl Default for WorldBorder {
+    /// The vanilla border.
+    fn default() -> Self {
+        Self::new(DVec2::ZERO, MAX_DIAMETER)
+    }
+}
+
+impl WorldBorder {
+    /// A border `diameter` blocks wide with the vanilla damage and warning distance.
+    #[must_use]
+    pub const fn new(center: DVec2, diameter: f64) -> Self {
+        Self {
+            center,
+            diameter,
+            from: diameter,
+            target: diameter,
+            duration: 0,
+            elapsed: 0,
+            damage_per_block: 0.2,
+            buffer: 5.0,
+            warning_blocks: 5,
+            changes: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn center(&self) -> DVec2 {
+        self.center
+    }
+
+    /// The diameter this tick, partway through a resize if one is happening.
+    #[must_use]
+    pub const fn diameter(&self) -> f64 {
+        self.diameter
+    }
+
+    /// The diameter the border is resizing to, which is the current diameter if it is not.
+    #[must_use]
+    pub const fn target_diameter(&self) -> f64 {
+        self.target
+    }
+
+    /// Ticks until the border stops resizing.
+    #[must_use]
+    pub const fn remaining_ticks(&self) -> i64 {
+        self.duration - self.elapsed
+    }
+
+    #[must_use]
+    pub const fn damage_per_block(&self) -> f32 {
+        self.damage_per_block
+    }
+
+    #[must_use]
+    pub const fn buffer(&self) -> f64 {
+        self.buffer
+    }
+
+    #[must_use]
+    pub const fn warning_blocks(&self) -> i32 {
+        self.warning_blocks
+    }
+
+    pub fn set_center(&mut self, center: DVec2) {
+        self.center = center;
+        self.changes.push(BorderChange::Center(center));
+    }
+
+    /// Resizes the border to `diameter` at once.
+    pub fn set_diameter(&mut self, diameter: f64) {
+        self.shrink_to(diameter, 0);
+    }
+
+    /// Resizes the border from its current diameter to `diameter` over `ticks` ticks. The border
+    /// grows instead if `diameter` is larger.
+    pub fn shrink_to(&mut self, diameter: f64, ticks: i64) {
+        let diameter = diameter.clamp(0.0, MAX_DIAMETER);
+
+        self.from = self.diameter;
+        self.target = diameter;
+        self.duration = ticks.max(0);
+        self.elapsed = 0;
+
+        if self.duration == 0 {
+            self.diameter = diameter;
+            self.changes.push(BorderChange::Diameter(diameter));
+        } else {
+            self.changes.push(BorderChange::Interpolate {
+                from: self.from,
+                to: diameter,
+                ticks: self.duration,
+            });
+        }
+    }
+
+    pub const fn set_damage_per_block(&mut self, damage: f32) {
+        self.damage_per_block = damage;
+    }
+
+    /// How far outside the border players can be before they take damage.
+    pub const fn set_buffer(&mut self, buffer: f64) {
+        self.buffer = buffer;
+    }
+
+    pub fn set_warning_blocks(&mut self, blocks: i32) {
+        self.warning_blocks = blocks;
+        self.changes.push(BorderChange::WarningBlocks(blocks));
+    }
+
+    /// Advances a resize by a tick.
+    pub fn tick(&mut self) {
+        if self.elapsed >= self.duration {
+            return;
+        }
+
+        self.elapsed += 1;
+
+        let progress = self.elapsed as f64 / self.duration as f64;
+        self.diameter = (self.target - self.from).mul_add(progress, self.from);
+    }
+
+    /// How many blocks `position` is outside the border. This is negative inside of it.
+    #[must_use]
+    pub fn distance_outside(&self, position: Vec3) -> f64 {
+        let dx = (f64::from(position.x) - self.center.x).abs();
+        let dz = (f64::from(position.z) - self.center.y).abs();
+
+        dx.max(dz) - self.diameter / 2.0
+    }
+
+    /// The damage a player at `position` takes each second. Like in vanilla, a player past the
+    /// buffer always takes at least one point of damage.
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the distance outside the border is at most a few million blocks"
+    )]
+    pub fn damage(&self, position: Vec3) -> f32 {
+        let past_buffer = self.distance_outside(position) - self.buffer;
+
+        if past_buffer <= 0.0 || self.damage_per_block <= 0.0 {
+            return 0.0;
+        }
+
+        (past_buffer as f32 * self.damage_per_block)
+            .floor()
+            .max(1.0)
+    }
+
+    /// The changes to send to everyone since this was last called.
+    pub fn take_changes(&mut self) -> Vec<BorderChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// The whole border as it is this tick, including a resize that is still happening.
+    #[must_use]
+    pub fn initialize_packet(&self) -> play::WorldBorderInitializeS2c {
+        play::WorldBorderInitializeS2c {
+            x: self.center.x,
+            z: self.center.y,
+            old_diameter: self.diameter,
+            new_diameter: self.target,
+            duration_millis: (self.remaining_ticks() * 50).into(),
+            portal_teleport_boundary: PORTAL_TELEPORT_BOUNDARY.into(),
+            warning_blocks: self.warning_blocks.into(),
+            warning_time: WARNING_TIME.into(),
+        }
+    }
+
+    /// Adds the packets that show a player who just joined the border to `bundle`. A client joins
+    /// with the vanilla border, so nothing needs to be sent until it is changed.
+    pub fn add_join_packets(
+        &self,
+        bundle: &mut DataBundle<'_>,
+        world: &World,
+    ) -> anyhow::Result<()> {
+        let vanilla = Self::default();
+
+        let unchanged = self.center == vanilla.center
+            && self.diameter == vanilla.diameter
+            && self.target == vanilla.target
+            && self.warning_blocks == vanilla.warning_blocks;
+
+        if !unchanged {
+            bundle.add_packet(&self.initialize_packet(), world)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+pub struct WorldBorderModule;
+
+impl Module for WorldBorderModule {
+    fn module(world: &World) {
+        world.component::<WorldBorder>();
+        world.set(WorldBorder::default());
+
+        let players = world
+            .query::<&Position>()
+            .with::<Player>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!("advance_world_border", world, &mut WorldBorder($), &Compose($))
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each_iter(|it, _, (border, compose)| {
+                let world = it.world();
+
+                border.tick();
+
+                for change in border.take_changes() {
+                    if let Err(e) = change.broadcast(compose, &world) {
+                        warn!("failed to send world border change: {e}");
+                    }
+                }
+            });
+
+        // players who joined outside the border are hurt like everyone else, so there is nowhere
+        // to wait out a shrinking border
+        system!(
+            "world_border_damage",
+            world,
+            &WorldBorder($),
+            &Compose($),
+            &Events($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(move |it, _, (border, compose, events)| {
+            if compose.global().tick % DAMAGE_INTERVAL != 0 {
+                return;
+            }
+
+            let world = it.world();
+
+            players.each_entity(|player, position| {
+                let amount = border.damage(**position);
+
+                if amount <= 0.0 {
+                    return;
+                }
+
+                let damage = event::Damage {
+                    origin: player.id(),
+                    target: player.id(),
+                    amount,
+                };
+
+                events.push(damage, &world);
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec2, Vec3};
+
+    use super::{BorderChange, WorldBorder};
+
+    #[test]
+    fn shrinking_interpolates_each_tick() {
+        let mut border = WorldBorder::new(DVec2::ZERO, 200.0);
+        border.take_changes();
+
+        border.shrink_to(100.0, 20);
+        assert_eq!(border.take_changes(), [BorderChange::Interpolate {
+            from: 200.0,
+            to: 100.0,
+            ticks: 20,
+        }]);
+
+        for _ in 0..10 {
+            border.tick();
+        }
+        assert!((border.diameter() - 150.0).abs() < 1e-9);
+        assert_eq!(border.remaining_ticks(), 10);
+
+        for _ in 0..15 {
+            border.tick();
+        }
+        assert!((border.diameter() - 100.0).abs() < 1e-9);
+        assert_eq!(border.remaining_ticks(), 0);
+
+        // nothing is sent while the border moves, since clients move it on their own
+        assert!(border.take_changes().is_empty());
+
+        border.set_diameter(300.0);
+        assert!((border.diameter() - 300.0).abs() < 1e-9);
+        assert_eq!(border.take_changes(), [BorderChange::Diameter(300.0)]);
+    }
+
+    #[test]
+    fn only_players_past_the_buffer_are_hurt() {
+        let mut border = WorldBorder::new(DVec2::new(100.0, -50.0), 20.0);
+        border.set_damage_per_block(1.0);
+        border.set_buffer(2.0);
+
+        // the border runs from x 90 to 110
+        assert_eq!(border.damage(Vec3::new(100.0, 64.0, -50.0)), 0.0);
+        assert_eq!(border.damage(Vec3::new(111.5, 64.0, -50.0)), 0.0);
+        assert_eq!(border.damage(Vec3::new(115.0, 64.0, -50.0)), 3.0);
+        assert_eq!(border.damage(Vec3::new(100.0, 64.0, -65.0)), 3.0);
+
+        // a sliver past the buffer still hurts
+        assert_eq!(border.damage(Vec3::new(112.1, 64.0, -50.0)), 1.0);
+
+        border.set_damage_per_block(0.0);
+        assert_eq!(border.damage(Vec3::new(115.0, 64.0, -50.0)), 0.0);
+    }
+
+    #[test]
+    fn joining_players_see_a_border_mid_shrink() {
+        let mut border = WorldBorder::new(DVec2::ZERO, 100.0);
+        border.shrink_to(50.0, 40);
+
+        for _ in 0..20 {
+            border.tick();
+        }
+
+        let pkt = border.initialize_packet();
+        assert!((pkt.old_diameter - 75.0).abs() < 1e-9);
+        assert!((pkt.new_diameter - 50.0).abs() < 1e-9);
+        assert_eq!(pkt.duration_millis.0, 1000);
+    }
+}
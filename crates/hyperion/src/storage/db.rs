@@ -1,6 +1,6 @@
 //! Constructs for connecting and working with a `Heed` database.
 
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use byteorder::NativeEndian;
 use derive_more::Deref;
@@ -10,34 +10,39 @@ use uuid::Uuid;
 
 use crate::simulation::skin::{ArchivedPlayerSkin, PlayerSkin};
 
-/// A wrapper around a `Heed` database
+/// A wrapper around a `Heed` database. Clones share the same database, so every handler keeps
+/// its own clone instead of reopening it.
 #[derive(Component, Debug, Clone, Deref)]
+#[deref(forward)]
 pub struct LocalDb {
-    env: Env,
+    env: Arc<Env>,
 }
 
 impl LocalDb {
     /// Creates a new [`LocalDb`]
     pub fn new() -> anyhow::Result<Self> {
-        let path = Path::new("db").join("heed.mdb");
+        Self::open(&Path::new("db").join("heed.mdb"))
+    }
 
-        std::fs::create_dir_all(&path)?;
+    /// Opens the [`LocalDb`] in the directory at `path`, creating it if it does not exist.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(path)?;
 
         let env = unsafe {
             EnvOpenOptions::new()
                 .map_size(10 * 1024 * 1024) // 10MB
                 .max_dbs(8) // todo: why is this needed/configurable? ideally would be infinite...
-                .open(&path)?
+                .open(path)?
         };
 
-        Ok(Self { env })
+        Ok(Self { env: Arc::new(env) })
     }
 }
 
 /// A handler for player skin operations
 #[derive(Component, Debug, Clone)]
 pub struct SkinHandler {
-    env: Env,
+    db: LocalDb,
     skins: Database<types::U128<NativeEndian>, types::Bytes>,
 }
 
@@ -53,7 +58,7 @@ impl SkinHandler {
         };
 
         Ok(Self {
-            env: db.env.clone(),
+            db: db.clone(),
             skins,
         })
     }
@@ -64,7 +69,7 @@ impl SkinHandler {
 
         let uuid = uuid.as_u128();
 
-        let rtxn = self.env.read_txn()?;
+        let rtxn = self.db.read_txn()?;
         let skin = self.skins.get(&rtxn, &uuid);
 
         let Some(skin) = skin? else {
@@ -80,7 +85,7 @@ impl SkinHandler {
     pub fn insert(&self, uuid: Uuid, skin: &PlayerSkin) -> anyhow::Result<()> {
         let uuid = uuid.as_u128();
 
-        let mut wtxn = self.env.write_txn()?;
+        let mut wtxn = self.db.write_txn()?;
 
         let skin = rkyv::to_bytes::<rkyv::rancor::Error>(skin).unwrap();
 
@@ -90,3 +95,65 @@ impl SkinHandler {
         Ok(())
     }
 }
+
+/// A handler for saved player data, such as their inventory and where they logged out
+#[derive(Component, Debug, Clone)]
+pub struct PlayerDataHandler {
+    db: LocalDb,
+    players: Database<types::U128<NativeEndian>, types::Bytes>,
+}
+
+impl PlayerDataHandler {
+    /// Creates a new [`PlayerDataHandler`] from a given [`LocalDb`].
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let players = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some("uuid-to-player-data"))?;
+            wtxn.commit()?;
+            db
+        };
+
+        Ok(Self {
+            db: db.clone(),
+            players,
+        })
+    }
+
+    /// Finds the saved data of a player by their UUID.
+    pub fn find(&self, uuid: Uuid) -> anyhow::Result<Option<Vec<u8>>> {
+        let uuid = uuid.as_u128();
+
+        let rtxn = self.db.read_txn()?;
+        let data = self.players.get(&rtxn, &uuid)?;
+
+        Ok(data.map(<[u8]>::to_vec))
+    }
+
+    /// Saves the data of a player, replacing whatever was saved before.
+    pub fn insert(&self, uuid: Uuid, data: &[u8]) -> anyhow::Result<()> {
+        let uuid = uuid.as_u128();
+
+        let mut wtxn = self.db.write_txn()?;
+        self.players.put(&mut wtxn, &uuid, data)?;
+        wtxn.commit()?;
+
+        Ok(())
+    }
+
+    /// Saves the data of many players at once in a single transaction, which is far cheaper than
+    /// calling [`Self::insert`] for each of them. Either every record is saved or none are.
+    pub fn insert_all<'a>(
+        &self,
+        records: impl IntoIterator<Item = (Uuid, &'a [u8])>,
+    ) -> anyhow::Result<()> {
+        let mut wtxn = self.db.write_txn()?;
+
+        for (uuid, data) in records {
+            self.players.put(&mut wtxn, &uuid.as_u128(), data)?;
+        }
+
+        wtxn.commit()?;
+
+        Ok(())
+    }
+}
@@ -50,7 +50,11 @@ define_events! {
     event::AttackEntity,
     event::ChatMessage<'static>,
     event::Command<'static>,
+    event::ContainerClick,
+    event::Damage,
     event::DestroyBlock,
+    event::EquipmentChange,
+    event::Fall,
     event::ItemDropEvent,
     event::PlaceBlock,
     event::PluginMessage<'static>,
@@ -1,10 +1,21 @@
-use flecs_ecs::{core::Entity, macros::Component};
+use flecs_ecs::{
+    core::{Entity, World},
+    macros::Component,
+};
 use valence_protocol::Hand;
 
 use crate::simulation::handlers::PacketSwitchQuery;
 
 pub type EventFn<T> = fn(&mut PacketSwitchQuery<'_>, &T);
 
+/// A handler that may change the event it is given, for the handlers after it and for whatever
+/// happens with the event afterwards.
+pub type EventFnMut<T> = fn(&mut PacketSwitchQuery<'_>, &mut T);
+
+/// A handler for an event that does not come from a packet. Singletons such as
+/// [`crate::net::Compose`] can be read from the world.
+pub type WorldEventFn<T> = fn(&World, &T);
+
 pub struct CommandCompletionRequest<'a> {
     pub query: &'a str,
     pub id: i32,
@@ -16,6 +27,24 @@ pub struct GlobalEventHandlers {
 
     // todo: this should be a lifetime for<'a>
     pub completion: EventHandlers<CommandCompletionRequest<'static>>,
+
+    /// Runs when a player sends a chat message, before it is pushed as an
+    /// [`crate::simulation::event::ChatMessage`]. The sender is [`PacketSwitchQuery::id`].
+    pub chat_message: EventHandlersMut<ChatEvent>,
+
+    /// Runs once for each player who leaves, for any reason, before their entity is removed. The
+    /// player is still in the [`crate::simulation::roster::PlayerRoster`] and still has all their
+    /// components, and others have not been told yet.
+    pub leave_server: WorldEventHandlers<PlayerLeaveServer>,
+}
+
+/// A chat message a player sent, as given to [`GlobalEventHandlers::chat_message`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChatEvent {
+    /// What will be sent. Handlers can rewrite it, such as to add a tag.
+    pub message: String,
+    /// Set to drop the message, such as when the sender is muted or a handler sent it on its own.
+    pub cancelled: bool,
 }
 
 pub struct EventHandlers<T> {
@@ -42,7 +71,96 @@ impl<T> EventHandlers<T> {
     }
 }
 
+/// Handlers that may change the event, run in the order they were registered. A handler still runs
+/// when one before it cancelled the event, so it can check for that itself.
+pub struct EventHandlersMut<T> {
+    handlers: Vec<EventFnMut<T>>,
+}
+
+impl<T> Default for EventHandlersMut<T> {
+    fn default() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+}
+
+impl<T> EventHandlersMut<T> {
+    pub fn trigger_all(&self, world: &mut PacketSwitchQuery<'_>, event: &mut T) {
+        for handler in &self.handlers {
+            handler(world, event);
+        }
+    }
+
+    pub fn register(&mut self, handler: EventFnMut<T>) {
+        self.handlers.push(handler);
+    }
+}
+
+/// Handlers for an event that does not come from a packet, run in the order they were registered.
+pub struct WorldEventHandlers<T> {
+    handlers: Vec<WorldEventFn<T>>,
+}
+
+impl<T> Default for WorldEventHandlers<T> {
+    fn default() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+}
+
+impl<T> WorldEventHandlers<T> {
+    pub fn trigger_all(&self, world: &World, event: &T) {
+        for handler in &self.handlers {
+            handler(world, event);
+        }
+    }
+
+    pub fn register(&mut self, handler: WorldEventFn<T>) {
+        self.handlers.push(handler);
+    }
+}
+
 pub struct PlayerJoinServer {
     pub username: String,
     pub entity: Entity,
 }
+
+/// A player leaving, as given to [`GlobalEventHandlers::leave_server`].
+pub struct PlayerLeaveServer {
+    pub entity: Entity,
+    /// Why they left, such as `disconnected`, or the message they were kicked with.
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::{Entity, World};
+    use parking_lot::Mutex;
+
+    use super::{PlayerLeaveServer, WorldEventHandlers};
+
+    static CALLS: Mutex<Vec<(&str, Entity)>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn handlers_run_once_in_order() {
+        let world = World::new();
+        let mut handlers = WorldEventHandlers::default();
+
+        handlers.register(|_, leave: &PlayerLeaveServer| {
+            CALLS.lock().push(("teams", leave.entity));
+        });
+        handlers.register(|_, leave: &PlayerLeaveServer| {
+            CALLS.lock().push(("stats", leave.entity));
+        });
+
+        let player = world.entity().id();
+        handlers.trigger_all(&world, &PlayerLeaveServer {
+            entity: player,
+            reason: "disconnected".to_owned(),
+        });
+
+        assert_eq!(*CALLS.lock(), [("teams", player), ("stats", player)]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Scrapes the metrics endpoint after a world has run a few ticks.
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    metrics::{self, MetricsModule},
+    profiler::ProfilerModule,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const METRIC_NAMES: [&str; 10] = [
+    "hyperion_tick_duration_seconds",
+    "hyperion_entities",
+    "hyperion_players",
+    "hyperion_loaded_chunks",
+    "hyperion_sent_bytes_total",
+    "hyperion_received_packets_total",
+    "hyperion_system_average_seconds",
+    "hyperion_system_max_seconds",
+    "hyperion_commands_total",
+    "hyperion_mojang_cache_hit_rate",
+];
+
+#[test]
+fn scraping_exposes_every_metric() {
+    let world = World::new();
+    world.import::<ProfilerModule>();
+    world.import::<MetricsModule>();
+
+    for _ in 0..3 {
+        assert!(world.progress());
+    }
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let response = runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(metrics::serve(listener));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    });
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    for name in METRIC_NAMES {
+        assert!(
+            response.contains(&format!("# TYPE {name} ")),
+            "{name} is missing from:\n{response}"
+        );
+    }
+
+    assert!(response.contains("\nhyperion_tick_duration_seconds_count 3\n"));
+}
@@ -0,0 +1,84 @@
+//! Shuts a world down through its handle, the way signals and `/stop` do.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    config::ServerConfig,
+    ingress::PendingRemove,
+    net::NetworkStreamRef,
+    shutdown::{ShutdownHandle, ShutdownModule, on_shutdown},
+    simulation::{PacketState, Player},
+};
+
+/// More than enough ticks for a shutdown to finish.
+const MAX_TICKS: usize = 10;
+
+#[test]
+fn shutdown_kicks_players_and_saves_before_stopping() {
+    let world = World::new();
+    world.component::<PacketState>();
+    world.set(ServerConfig::default());
+    world.import::<ShutdownModule>();
+
+    let player = world
+        .entity()
+        .add::<Player>()
+        .add_enum(PacketState::Play)
+        .id();
+
+    let joining = world
+        .entity()
+        .add::<Player>()
+        .set(NetworkStreamRef::new(2))
+        .add_enum(PacketState::Login)
+        .id();
+
+    let saved = Arc::new(AtomicBool::new(false));
+
+    on_shutdown(&world, {
+        let saved = saved.clone();
+        move |world| {
+            // players were kicked, and with that saved, before the hooks run
+            assert!(world.entity_from_id(player).has::<PendingRemove>());
+            saved.store(true, Ordering::Relaxed);
+        }
+    });
+
+    for _ in 0..3 {
+        assert!(world.progress());
+    }
+
+    assert!(!saved.load(Ordering::Relaxed));
+    assert!(!world.entity_from_id(player).has::<PendingRemove>());
+    assert!(!world.entity_from_id(joining).has::<PendingRemove>());
+
+    let handle = world.get::<&ShutdownHandle>(ShutdownHandle::clone);
+    handle.request();
+
+    let mut ticks = 0;
+    while world.progress() {
+        ticks += 1;
+        assert!(
+            ticks < MAX_TICKS,
+            "the world is still running {ticks} ticks after shutdown"
+        );
+    }
+
+    assert!(saved.load(Ordering::Relaxed));
+    assert!(handle.is_finished());
+
+    world
+        .entity_from_id(player)
+        .get::<&PendingRemove>(|kicked| {
+            assert_eq!(kicked.reason, ServerConfig::default().shutdown_message);
+        });
+
+    // they were sent a login disconnect instead of a play one
+    world
+        .entity_from_id(joining)
+        .get::<&PendingRemove>(|kicked| assert!(kicked.reason.is_empty()));
+}
@@ -1,21 +1,107 @@
-use flecs_ecs::core::World;
-use hyperion_clap::{MinecraftCommand, hyperion_command::CommandRegistry};
+use flecs_ecs::core::{Entity, EntityViewGet, World, WorldGet};
+use hyperion::{
+    net::{Compose, NetworkStreamRef, agnostic},
+    system_registry::SystemId,
+};
+use hyperion_clap::{
+    MinecraftCommand,
+    hyperion_command::{CommandRegisterError, CommandRegistry},
+};
+use tracing::{info, warn};
 
 use crate::command::{
-    fly::FlyCommand, rank::ClassCommand, replace::ReplaceCommand, speed::SpeedCommand,
+    ban::{BanCommand, BanListCommand, UnbanCommand},
+    fly::FlyCommand,
+    global_chat::GlobalChatCommand,
+    kick::KickCommand,
+    leaderboard::LeaderboardCommand,
+    op::{DeopCommand, OpCommand},
+    rank::ClassCommand,
+    replace::ReplaceCommand,
+    round::StartRoundCommand,
+    shop::ShopCommand,
+    spawn::SetWorldSpawnCommand,
+    spectate::SpectateCommand,
+    speed::SpeedCommand,
+    stats::StatsCommand,
+    stop::StopCommand,
+    time::TimeCommand,
+    tp::TpCommand,
+    tps::TpsCommand,
+    weather::WeatherCommand,
+    whitelist::WhitelistCommand,
     xp::XpCommand,
 };
 
+mod ban;
 mod fly;
+mod global_chat;
+mod kick;
+mod leaderboard;
+mod op;
 mod rank;
 mod replace;
+mod round;
+mod shop;
+mod spawn;
+mod spectate;
 mod speed;
+mod stats;
+mod stop;
+mod time;
+mod tp;
+mod tps;
+mod weather;
+mod whitelist;
 mod xp;
 
-pub fn register(registry: &mut CommandRegistry, world: &World) {
-    SpeedCommand::register(registry, world);
-    FlyCommand::register(registry, world);
-    ClassCommand::register(registry, world);
-    XpCommand::register(registry, world);
-    ReplaceCommand::register(registry, world);
+const SYSTEM_ID: SystemId = SystemId(31);
+
+pub fn register(registry: &mut CommandRegistry, world: &World) -> Result<(), CommandRegisterError> {
+    SpeedCommand::register(registry, world)?;
+    FlyCommand::register(registry, world)?;
+    ClassCommand::register(registry, world)?;
+    XpCommand::register(registry, world)?;
+    ReplaceCommand::register(registry, world)?;
+    TpCommand::register(registry, world)?;
+    StartRoundCommand::register(registry, world)?;
+    StatsCommand::register(registry, world)?;
+    ShopCommand::register(registry, world)?;
+    GlobalChatCommand::register(registry, world)?;
+    LeaderboardCommand::register(registry, world)?;
+    SpectateCommand::register(registry, world)?;
+    WhitelistCommand::register(registry, world)?;
+    KickCommand::register(registry, world)?;
+    BanCommand::register(registry, world)?;
+    UnbanCommand::register(registry, world)?;
+    BanListCommand::register(registry, world)?;
+    TpsCommand::register(registry, world)?;
+    TimeCommand::register(registry, world)?;
+    WeatherCommand::register(registry, world)?;
+    SetWorldSpawnCommand::register(registry, world)?;
+    StopCommand::register(registry, world)?;
+    OpCommand::register(registry, world)?;
+    DeopCommand::register(registry, world)?;
+
+    Ok(())
+}
+
+/// Replies to whoever ran a command. Callers without a connection, like the console, get the reply
+/// in the log instead.
+pub fn send_message(world: &World, caller: Entity, msg: &str) {
+    let Some(io) = caller
+        .entity_view(world)
+        .try_get::<&NetworkStreamRef>(|&io| io)
+    else {
+        info!("{msg}");
+        return;
+    };
+
+    let chat = agnostic::chat(msg);
+
+    world.get::<&Compose>(|compose| {
+        if let Err(e) = compose.unicast(&chat, io, SYSTEM_ID, world) {
+            warn!("failed to reply to a command: {e}");
+        }
+    });
 }
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityViewGet, World, WorldGet};
+use hyperion::{
+    ban::{Ban, BanList, format_duration, parse_duration, unix_now},
+    ingress::PendingRemove,
+    l10n::with_locale,
+    msg,
+    runtime::AsyncRuntime,
+    simulation::{Name, command::Permissions, roster::PlayerRoster},
+    util::mojang::MojangClient,
+    uuid::Uuid,
+};
+use hyperion_clap::MinecraftCommand;
+use tracing::warn;
+
+use crate::command::send_message;
+
+/// How many bans `/banlist` shows per page.
+const PAGE_SIZE: usize = 8;
+
+#[derive(Parser, Debug)]
+#[command(name = "ban")]
+pub struct BanCommand {
+    /// The name or UUID of the player to ban.
+    player: String,
+    /// How long the ban lasts, like `1d12h`, followed by the reason. Without a duration, the ban
+    /// is permanent.
+    args: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "unban")]
+pub struct UnbanCommand {
+    /// The name or UUID of the player to unban.
+    player: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "banlist")]
+pub struct BanListCommand {
+    #[arg(default_value_t = 1)]
+    page: usize,
+}
+
+impl MinecraftCommand for BanCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let Self { player, mut args } = self;
+        let now = unix_now();
+
+        let duration = args.first().and_then(|arg| parse_duration(arg));
+
+        if duration.is_some() {
+            args.remove(0);
+        }
+
+        let reason = if args.is_empty() {
+            "Banned by a moderator".to_owned()
+        } else {
+            args.join(" ")
+        };
+
+        let source = caller
+            .entity_view(world)
+            .try_get::<&Name>(ToString::to_string)
+            .unwrap_or_else(|| "Server".to_owned());
+
+        let mut ban = Ban {
+            uuid: Uuid::nil(),
+            name: player.clone(),
+            reason,
+            source,
+            created_at: now,
+            expires_at: duration.map(|duration| now.saturating_add(duration.as_secs())),
+        };
+
+        let online = world.get::<&PlayerRoster>(|roster| {
+            let entry = match Uuid::parse_str(&player) {
+                Ok(uuid) => roster.by_uuid(uuid),
+                Err(_) => roster.by_name(&player),
+            };
+
+            entry.map(|entry| (entry.uuid, entry.name.to_string()))
+        });
+
+        if let Some((uuid, name)) = online {
+            ban.uuid = uuid;
+            ban.name = name;
+            apply_ban(world, caller, &ban);
+            return;
+        }
+
+        if let Ok(uuid) = Uuid::parse_str(&player) {
+            ban.uuid = uuid;
+            apply_ban(world, caller, &ban);
+            return;
+        }
+
+        // offline players are banned once their UUID is known
+        world.get::<&MojangClient>(|mojang| {
+            world.get::<&AsyncRuntime>(|runtime| {
+                let mojang = mojang.clone();
+                let lookup = async move {
+                    let uuid = mojang.get_uuid(&player).await;
+                    (caller, ban, uuid)
+                };
+
+                runtime.schedule(lookup, finish_ban);
+            });
+        });
+    }
+}
+
+fn finish_ban((caller, mut ban, uuid): (Entity, Ban, anyhow::Result<Uuid>), world: &World) {
+    match uuid {
+        Ok(uuid) => {
+            ban.uuid = uuid;
+            apply_ban(world, caller, &ban);
+        }
+        Err(e) => {
+            warn!("failed to look up {} to ban them: {e}", ban.name);
+            let msg = msg!(
+                caller.entity_view(world),
+                "ban.not_found",
+                player = ban.name
+            );
+            send_message(world, caller, &msg);
+        }
+    }
+}
+
+/// Saves `ban` and kicks the player if they are online.
+fn apply_ban(world: &World, caller: Entity, ban: &Ban) {
+    let saved = world.get::<&BanList>(|bans| bans.ban(ban));
+
+    if let Err(e) = saved {
+        warn!("failed to save the ban of {}: {e}", ban.name);
+        send_message(
+            world,
+            caller,
+            &msg!(caller.entity_view(world), "ban.save_failed"),
+        );
+        return;
+    }
+
+    let online = world.get::<&PlayerRoster>(|roster| roster.by_uuid(ban.uuid).map(|p| p.entity));
+
+    if let Some(banned) = online {
+        let banned = world.entity_from_id(banned);
+        let msg = with_locale(banned, |l10n, locale| {
+            ban.disconnect_message(l10n, locale, unix_now())
+        });
+        banned.set(PendingRemove::new(msg));
+    }
+
+    if !world.is_alive(caller) {
+        return;
+    }
+
+    let caller_view = caller.entity_view(world);
+
+    let msg = match ban.expires_at {
+        Some(expires_at) => {
+            let length = expires_at.saturating_sub(ban.created_at);
+
+            msg!(
+                caller_view,
+                "ban.banned_for",
+                player = ban.name,
+                length = format_duration(Duration::from_secs(length)),
+                reason = ban.reason
+            )
+        }
+        None => msg!(
+            caller_view,
+            "ban.banned_permanently",
+            player = ban.name,
+            reason = ban.reason
+        ),
+    };
+
+    send_message(world, caller, &msg);
+}
+
+impl MinecraftCommand for UnbanCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let Self { player } = self;
+
+        let result = world.get::<&BanList>(|bans| {
+            let uuid = match Uuid::parse_str(&player) {
+                Ok(uuid) => Some(uuid),
+                Err(_) => bans.find_by_name(&player, unix_now())?.map(|ban| ban.uuid),
+            };
+
+            match uuid {
+                Some(uuid) => bans.unban(uuid),
+                None => Ok(false),
+            }
+        });
+
+        let caller_view = caller.entity_view(world);
+
+        let msg = match result {
+            Ok(true) => msg!(caller_view, "ban.unbanned", player = player),
+            Ok(false) => msg!(caller_view, "ban.not_banned", player = player),
+            Err(e) => {
+                warn!("failed to unban {player}: {e}");
+                msg!(caller_view, "ban.unban_failed")
+            }
+        };
+
+        send_message(world, caller, &msg);
+    }
+}
+
+impl MinecraftCommand for BanListCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+        let now = unix_now();
+
+        let bans = match world.get::<&BanList>(|bans| bans.list(now)) {
+            Ok(bans) => bans,
+            Err(e) => {
+                warn!("failed to list bans: {e}");
+                send_message(world, caller, &msg!(caller_view, "ban.list_failed"));
+                return;
+            }
+        };
+
+        let pages = bans.len().div_ceil(PAGE_SIZE).max(1);
+        let page = self.page.clamp(1, pages);
+
+        let mut msg = msg!(
+            caller_view,
+            "ban.list",
+            count = bans.len(),
+            page = page,
+            pages = pages
+        );
+
+        for ban in bans.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE) {
+            let ends = match ban.expires_at {
+                Some(expires_at) => {
+                    let left = Duration::from_secs(expires_at.saturating_sub(now));
+                    msg!(caller_view, "ban.ends_in", duration = format_duration(left))
+                }
+                None => msg!(caller_view, "ban.permanent"),
+            };
+
+            msg.push_str(&msg!(
+                caller_view,
+                "ban.list_entry",
+                player = ban.name,
+                source = ban.source,
+                ends = ends,
+                reason = ban.reason
+            ));
+        }
+
+        send_message(world, caller, &msg);
+    }
+}
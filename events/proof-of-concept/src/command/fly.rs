@@ -1,7 +1,9 @@
 use clap::Parser;
 use flecs_ecs::core::{Entity, EntityViewGet, World, WorldGet};
 use hyperion::{
+    msg,
     net::{Compose, DataBundle, NetworkStreamRef, agnostic},
+    simulation::command::Permissions,
     system_registry::SystemId,
     valence_protocol::packets::play::{
         PlayerAbilitiesS2c, player_abilities_s2c::PlayerAbilitiesFlags,
@@ -14,13 +16,15 @@ use hyperion_clap::MinecraftCommand;
 pub struct FlyCommand;
 
 impl MinecraftCommand for FlyCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
     fn execute(self, world: &World, caller: Entity) {
-        let chat = agnostic::chat("§aFlying enabled");
+        let chat = agnostic::chat(msg!(caller.entity_view(world), "fly.enabled"));
 
         world.get::<&Compose>(|compose| {
             caller
                 .entity_view(world)
-                .get::<&NetworkStreamRef>(|stream| {
+                .try_get::<&NetworkStreamRef>(|stream| {
                     let packet = fly_packet();
 
                     let mut bundle = DataBundle::new(compose);
@@ -0,0 +1,30 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion::net::Compose;
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::chat::{Channel, send_chat};
+
+/// Sends a message to global chat, for clients that do not let messages start with `!`.
+#[derive(Parser, Debug)]
+#[command(name = "g")]
+pub struct GlobalChatCommand {
+    #[arg(required = true)]
+    message: Vec<String>,
+}
+
+impl MinecraftCommand for GlobalChatCommand {
+    fn execute(self, world: &World, caller: Entity) {
+        let msg = self.message.join(" ");
+
+        world.get::<&Compose>(|compose| {
+            send_chat(
+                world,
+                compose,
+                caller.entity_view(world),
+                &msg,
+                Channel::Global,
+            );
+        });
+    }
+}
@@ -0,0 +1,53 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World, WorldGet};
+use hyperion::{
+    ingress::PendingRemove,
+    msg,
+    simulation::{command::Permissions, roster::PlayerRoster},
+};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "kick")]
+pub struct KickCommand {
+    /// The player to kick.
+    player: String,
+    /// Why the player is kicked.
+    reason: Vec<String>,
+}
+
+impl MinecraftCommand for KickCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        let Self { player, reason } = self;
+
+        let kicked =
+            world.get::<&PlayerRoster>(|roster| roster.by_name(&player).map(|entry| entry.entity));
+
+        let Some(kicked) = kicked else {
+            let msg = msg!(caller_view, "kick.not_online", player = player);
+            send_message(world, caller, &msg);
+            return;
+        };
+
+        let kicked = world.entity_from_id(kicked);
+
+        // the kicked player reads the reason in their own language
+        let reason = if reason.is_empty() {
+            msg!(kicked, "kick.default_reason")
+        } else {
+            reason.join(" ")
+        };
+
+        let disconnect = msg!(kicked, "kick.disconnect", reason = reason);
+        kicked.set(PendingRemove::new(disconnect));
+
+        let msg = msg!(caller_view, "kick.kicked", player = player, reason = reason);
+        send_message(world, caller, &msg);
+    }
+}
@@ -0,0 +1,22 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::leaderboard::{Metric, show_leaderboard};
+
+#[derive(Parser, Debug)]
+#[command(name = "leaderboard")]
+pub struct LeaderboardCommand {
+    /// What to rank players by.
+    #[arg(value_enum, default_value_t = Metric::Infections)]
+    metric: Metric,
+
+    #[arg(default_value_t = 1)]
+    page: usize,
+}
+
+impl MinecraftCommand for LeaderboardCommand {
+    fn execute(self, world: &World, caller: Entity) {
+        show_leaderboard(world, caller, self.metric, self.page);
+    }
+}
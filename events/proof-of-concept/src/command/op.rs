@@ -0,0 +1,110 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityView, EntityViewGet, World, WorldGet};
+use hyperion::{
+    msg,
+    simulation::{command::Permissions, roster::PlayerRoster},
+};
+use hyperion_clap::MinecraftCommand;
+use hyperion_permission::set_permissions;
+use tracing::warn;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "op")]
+pub struct OpCommand {
+    /// The player to make an operator.
+    player: String,
+    /// Their permission level, from 1 to 4.
+    #[arg(value_parser = clap::value_parser!(u8).range(1..=4), default_value_t = 4)]
+    level: u8,
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "deop")]
+pub struct DeopCommand {
+    /// The operator to make a regular player again.
+    player: String,
+}
+
+impl MinecraftCommand for OpCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+        let own = permissions_of(caller_view);
+
+        // nobody can hand out more than they have
+        if !own.allows(Permissions(self.level)) {
+            let msg = msg!(caller_view, "op.too_high", level = own.0);
+            send_message(world, caller, &msg);
+            return;
+        }
+
+        change(world, caller, &self.player, Permissions(self.level));
+    }
+}
+
+impl MinecraftCommand for DeopCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        change(world, caller, &self.player, Permissions::PLAYER);
+    }
+}
+
+fn permissions_of(player: EntityView<'_>) -> Permissions {
+    player
+        .try_get::<&Permissions>(|permissions| *permissions)
+        .unwrap_or_default()
+}
+
+/// Gives the online `player` new permissions and tells both them and the caller. Operators can
+/// only change the permissions of players whose level is not above their own.
+fn change(world: &World, caller: Entity, player: &str, permissions: Permissions) {
+    let caller_view = caller.entity_view(world);
+
+    let target =
+        world.get::<&PlayerRoster>(|roster| roster.by_name(player).map(|entry| entry.entity));
+
+    let Some(target) = target else {
+        let msg = msg!(caller_view, "op.not_online", player = player);
+        send_message(world, caller, &msg);
+        return;
+    };
+
+    let target = world.entity_from_id(target);
+    let own = permissions_of(caller_view);
+
+    if !own.allows(permissions_of(target)) {
+        let msg = msg!(caller_view, "op.outranked", player = player);
+        send_message(world, caller, &msg);
+        return;
+    }
+
+    if let Err(e) = set_permissions(target, permissions) {
+        warn!("failed to change the permissions of {player}: {e}");
+        let msg = msg!(caller_view, "op.failed", player = player);
+        send_message(world, caller, &msg);
+        return;
+    }
+
+    let (to_caller, to_target) = if permissions == Permissions::PLAYER {
+        (
+            msg!(caller_view, "op.deopped", player = player),
+            msg!(target, "op.revoked"),
+        )
+    } else {
+        let level = permissions.0;
+        (
+            msg!(caller_view, "op.opped", player = player, level = level),
+            msg!(target, "op.granted", level = level),
+        )
+    };
+
+    send_message(world, caller, &to_caller);
+
+    if target.id() != caller {
+        send_message(world, target.id(), &to_target);
+    }
+}
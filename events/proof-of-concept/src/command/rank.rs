@@ -7,11 +7,13 @@ use hyperion::{
         metadata::show_all,
         player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
     },
+    msg,
     net::{Compose, DataBundle, NetworkStreamRef, agnostic},
+    simulation::{command::Permissions, game_mode::GameMode},
     system_registry::SystemId,
     valence_ident::ident,
     valence_protocol::{
-        GameMode, VarInt,
+        VarInt,
         game_mode::OptGameMode,
         packets::{play, play::PlayerRespawnS2c},
         profile::Property,
@@ -28,14 +30,23 @@ pub struct ClassCommand {
     team: hyperion_rank_tree::Team,
 }
 impl MinecraftCommand for ClassCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
     fn execute(self, world: &World, caller: Entity) {
         let rank = self.rank;
         let team = self.team;
-        let msg = format!("Setting rank to {rank:?}");
+        let rank_name = format!("{rank:?}");
+        let msg = msg!(caller.entity_view(world), "class.set", rank = rank_name);
         let chat = agnostic::chat(msg);
 
+        // the player keeps their game mode through the respawn
+        let game_mode = caller
+            .entity_view(world)
+            .try_get::<&GameMode>(|mode| mode.0)
+            .unwrap_or_default();
+
         world.get::<&Compose>(|compose| {
-            caller.entity_view(world).get::<(
+            caller.entity_view(world).try_get::<(
                 &NetworkStreamRef,
                 &hyperion::simulation::Uuid,
                 &mut PlayerInventory,
@@ -88,7 +99,7 @@ impl MinecraftCommand for ClassCommand {
                                 chat_data: None,
                                 listed: true,
                                 ping: 20,
-                                game_mode: GameMode::Survival,
+                                game_mode,
                                 display_name: None,
                             }]),
                         },
@@ -103,7 +114,7 @@ impl MinecraftCommand for ClassCommand {
                             dimension_type_name: ident!("minecraft:overworld").into(),
                             dimension_name: ident!("minecraft:overworld").into(),
                             hashed_seed: 0,
-                            game_mode: GameMode::Survival,
+                            game_mode,
                             previous_game_mode: OptGameMode::default(),
                             is_debug: false,
                             is_flat: false,
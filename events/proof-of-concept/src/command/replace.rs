@@ -2,7 +2,11 @@ use std::collections::{HashSet, VecDeque};
 
 use flecs_ecs::core::{Entity, EntityViewGet, World, WorldGet};
 use gxhash::GxBuildHasher;
-use hyperion::{BlockState, glam::IVec3, simulation::blocks::Blocks};
+use hyperion::{
+    BlockState,
+    glam::IVec3,
+    simulation::{blocks::Blocks, command::Permissions},
+};
 use rayon::iter::ParallelIterator;
 
 use crate::OreVeins;
@@ -105,6 +109,8 @@ fn group(positions: &HashSet<IVec3, GxBuildHasher>) -> Vec<Vec<IVec3>> {
 }
 
 impl hyperion_clap::MinecraftCommand for ReplaceCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
     fn execute(self, world: &World, caller: Entity) {
         world.get::<&mut Blocks>(|blocks| {
             let started_time = std::time::Instant::now();
@@ -140,7 +146,7 @@ impl hyperion_clap::MinecraftCommand for ReplaceCommand {
             world.get::<&hyperion::net::Compose>(|compose| {
                 caller
                     .entity_view(world)
-                    .get::<&hyperion::net::NetworkStreamRef>(|stream| {
+                    .try_get::<&hyperion::net::NetworkStreamRef>(|stream| {
                         let mut bundle = hyperion::net::DataBundle::new(compose);
                         bundle.add_packet(&msg, world).unwrap();
                         bundle
@@ -0,0 +1,18 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion::simulation::command::Permissions;
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::round::start_round;
+
+#[derive(Parser, Debug)]
+#[command(name = "startround")]
+pub struct StartRoundCommand;
+
+impl MinecraftCommand for StartRoundCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, _caller: Entity) {
+        start_round(world);
+    }
+}
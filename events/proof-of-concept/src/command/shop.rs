@@ -0,0 +1,15 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::shop::open_shop;
+
+#[derive(Parser, Debug)]
+#[command(name = "shop")]
+pub struct ShopCommand;
+
+impl MinecraftCommand for ShopCommand {
+    fn execute(self, world: &World, caller: Entity) {
+        open_shop(caller.entity_view(world));
+    }
+}
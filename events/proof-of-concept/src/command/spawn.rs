@@ -0,0 +1,65 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityViewGet, World};
+use hyperion::{
+    msg,
+    simulation::{
+        Position, Yaw,
+        command::Permissions,
+        spawn::{SpawnPoint, set_world_spawn},
+    },
+    valence_protocol::math::Vec3,
+};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "setworldspawn")]
+pub struct SetWorldSpawnCommand {
+    /// Defaults to where you are standing.
+    #[arg(requires_all = ["y", "z"], allow_negative_numbers = true)]
+    x: Option<i32>,
+    #[arg(allow_negative_numbers = true)]
+    y: Option<i32>,
+    #[arg(allow_negative_numbers = true)]
+    z: Option<i32>,
+}
+
+impl MinecraftCommand for SetWorldSpawnCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        // the console has no position of its own
+        let here =
+            caller_view.try_get::<(&Position, &Yaw)>(|(position, yaw)| (position.floor(), **yaw));
+
+        let (position, yaw) = match (self.x, self.y, self.z, here) {
+            (Some(x), Some(y), Some(z), here) => {
+                let yaw = here.map_or(0.0, |(_, yaw)| yaw);
+                (Vec3::new(x as f32, y as f32, z as f32), yaw)
+            }
+            (.., Some(here)) => here,
+            (.., None) => {
+                send_message(world, caller, &msg!(caller_view, "spawn.no_position"));
+                return;
+            }
+        };
+
+        // players spawn in the middle of the block
+        let position = position + Vec3::new(0.5, 0.0, 0.5);
+
+        set_world_spawn(world, SpawnPoint { position, yaw });
+
+        let msg = msg!(
+            caller_view,
+            "spawn.set",
+            x = position.x.floor(),
+            y = position.y,
+            z = position.z.floor()
+        );
+
+        send_message(world, caller, &msg);
+    }
+}
@@ -0,0 +1,25 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion::simulation::{Position, command::Permissions};
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::spectator::toggle_spectating;
+
+#[derive(Parser, Debug)]
+#[command(name = "spectate")]
+pub struct SpectateCommand;
+
+impl MinecraftCommand for SpectateCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller = caller.entity_view(world);
+
+        // the console cannot be a spectator
+        if !caller.has::<Position>() {
+            return;
+        }
+
+        toggle_spectating(world, caller);
+    }
+}
@@ -1,7 +1,9 @@
 use clap::Parser;
 use flecs_ecs::core::{Entity, EntityViewGet, World, WorldGet};
 use hyperion::{
+    msg,
     net::{Compose, DataBundle, NetworkStreamRef, agnostic},
+    simulation::command::Permissions,
     system_registry::SystemId,
     valence_protocol::packets::play::{
         PlayerAbilitiesS2c, player_abilities_s2c::PlayerAbilitiesFlags,
@@ -16,14 +18,16 @@ pub struct SpeedCommand {
 }
 
 impl MinecraftCommand for SpeedCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
     fn execute(self, world: &World, caller: Entity) {
-        let msg = format!("Setting speed to {}", self.amount);
+        let msg = msg!(caller.entity_view(world), "speed.set", speed = self.amount);
         let chat = agnostic::chat(msg);
 
         world.get::<&Compose>(|compose| {
             caller
                 .entity_view(world)
-                .get::<&NetworkStreamRef>(|stream| {
+                .try_get::<&NetworkStreamRef>(|stream| {
                     let packet = speed_packet(self.amount);
 
                     let mut bundle = DataBundle::new(compose);
@@ -0,0 +1,18 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion_clap::MinecraftCommand;
+
+use crate::module::player_stats::show_stats;
+
+#[derive(Parser, Debug)]
+#[command(name = "stats")]
+pub struct StatsCommand {
+    /// The player to show the stats of. Defaults to yourself.
+    player: Option<String>,
+}
+
+impl MinecraftCommand for StatsCommand {
+    fn execute(self, world: &World, caller: Entity) {
+        show_stats(world, caller, self.player.as_deref());
+    }
+}
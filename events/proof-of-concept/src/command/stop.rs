@@ -0,0 +1,16 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World, WorldGet};
+use hyperion::{shutdown::ShutdownHandle, simulation::command::Permissions};
+use hyperion_clap::MinecraftCommand;
+
+#[derive(Parser, Debug)]
+#[command(name = "stop")]
+pub struct StopCommand;
+
+impl MinecraftCommand for StopCommand {
+    const PERMISSIONS: Permissions = Permissions::OWNER;
+
+    fn execute(self, world: &World, _caller: Entity) {
+        world.get::<&ShutdownHandle>(|shutdown| shutdown.request_or_exit("/stop"));
+    }
+}
@@ -0,0 +1,73 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World, WorldGet};
+use hyperion::{
+    msg,
+    simulation::{
+        command::Permissions,
+        time::{DAY, MIDNIGHT, NIGHT, NOON, WorldTime},
+    },
+};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "time")]
+pub enum TimeCommand {
+    /// Sets the time of day to `day`, `noon`, `night`, `midnight` or a number of ticks.
+    Set {
+        #[arg(value_parser = parse_time)]
+        time: i64,
+    },
+    /// Moves the time of day forward, or backward if negative.
+    Add {
+        #[arg(allow_negative_numbers = true)]
+        ticks: i64,
+    },
+    /// Shows the time of day.
+    Query,
+    /// Stops the time of day from advancing.
+    Pause,
+    /// Lets the time of day advance again.
+    Resume,
+}
+
+fn parse_time(time: &str) -> Result<i64, String> {
+    match time {
+        "day" => Ok(DAY),
+        "noon" => Ok(NOON),
+        "night" => Ok(NIGHT),
+        "midnight" => Ok(MIDNIGHT),
+        ticks => ticks
+            .parse()
+            .map_err(|_| format!("{ticks} is neither a time of day nor a number of ticks")),
+    }
+}
+
+impl MinecraftCommand for TimeCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        let msg = world.get::<&mut WorldTime>(|time| {
+            match self {
+                Self::Set { time: ticks } => time.set_time(ticks),
+                Self::Add { ticks } => time.add_time(ticks),
+                Self::Query => {}
+                Self::Pause => time.set_daylight_cycle(false),
+                Self::Resume => time.set_daylight_cycle(true),
+            }
+
+            let key = if time.daylight_cycle() {
+                "time.current"
+            } else {
+                "time.current_paused"
+            };
+
+            msg!(caller_view, key, time = time.time_of_day())
+        });
+
+        send_message(world, caller, &msg);
+    }
+}
@@ -0,0 +1,31 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, World};
+use hyperion::{
+    simulation::{Position, command::Permissions, teleport::teleport},
+    valence_protocol::math::Vec3,
+};
+use hyperion_clap::MinecraftCommand;
+
+#[derive(Parser, Debug)]
+#[command(name = "tp", visible_alias = "teleport")]
+pub struct TpCommand {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl MinecraftCommand for TpCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let Self { x, y, z } = self;
+        let caller = caller.entity_view(world);
+
+        // the console has nowhere to be teleported
+        if !caller.has::<Position>() {
+            return;
+        }
+
+        teleport(caller, Vec3::new(x, y, z), None);
+    }
+}
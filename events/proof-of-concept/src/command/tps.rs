@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use flecs_ecs::core::{Entity, EntityView, World};
+use hyperion::{msg, profiler::PROFILER, simulation::command::Permissions};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::send_message;
+
+/// How many of the slowest systems `/tps` lists.
+const SHOWN_SYSTEMS: usize = 5;
+
+#[derive(Parser, Debug)]
+#[command(name = "tps")]
+pub struct TpsCommand {
+    /// Turns timing every system on or off.
+    #[arg(value_enum)]
+    profiling: Option<Profiling>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum Profiling {
+    On,
+    Off,
+}
+
+impl MinecraftCommand for TpsCommand {
+    const PERMISSIONS: Permissions = Permissions::OWNER;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        let Some(profiling) = self.profiling else {
+            send_message(world, caller, &report(caller_view));
+            return;
+        };
+
+        PROFILER.set_enabled(profiling == Profiling::On);
+
+        let key = match profiling {
+            Profiling::On => "tps.profiling_on",
+            Profiling::Off => "tps.profiling_off",
+        };
+
+        send_message(world, caller, &msg!(caller_view, key));
+    }
+}
+
+/// The tick rate and the slowest systems, in the locale of `player`.
+fn report(player: EntityView<'_>) -> String {
+    let report = PROFILER.report();
+
+    let mspt = millis(report.average);
+
+    // the server never runs more than 20 ticks per second
+    let tps = if mspt > 50.0 { 1000.0 / mspt } else { 20.0 };
+
+    let mut msg = msg!(
+        player,
+        "tps.report",
+        tps = format!("{tps:.1}"),
+        mspt = format!("{mspt:.1}"),
+        max = format!("{:.1}", millis(report.max)),
+        ticks = report.ticks,
+    );
+
+    if report.systems.is_empty() {
+        msg.push('\n');
+        msg.push_str(&msg!(player, "tps.timing_off"));
+        return msg;
+    }
+
+    for system in report.systems.iter().take(SHOWN_SYSTEMS) {
+        msg.push('\n');
+        msg.push_str(&msg!(
+            player,
+            "tps.system",
+            name = system.name,
+            average = format!("{:.2}", millis(system.average)),
+            max = format!("{:.2}", millis(system.max)),
+        ));
+    }
+
+    msg
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,61 @@
+use clap::{Parser, ValueEnum};
+use flecs_ecs::core::{Entity, World, WorldGet};
+use hyperion::{
+    msg,
+    simulation::{
+        command::Permissions,
+        weather::{Weather, WeatherKind},
+    },
+};
+use hyperion_clap::MinecraftCommand;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "weather")]
+pub struct WeatherCommand {
+    #[arg(value_enum)]
+    kind: Kind,
+    /// How many seconds the weather lasts. Defaults to a random vanilla duration.
+    #[arg(value_parser = clap::value_parser!(u32).range(1..=1_000_000))]
+    duration: Option<u32>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl From<Kind> for WeatherKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Clear => Self::Clear,
+            Kind::Rain => Self::Rain,
+            Kind::Thunder => Self::Thunder,
+        }
+    }
+}
+
+impl MinecraftCommand for WeatherCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        let duration = self.duration.map(|seconds| i64::from(seconds) * 20);
+
+        world.get::<&mut Weather>(|weather| {
+            weather.set(self.kind.into(), duration);
+        });
+
+        let key = match self.kind {
+            Kind::Clear => "weather.clear",
+            Kind::Rain => "weather.rain",
+            Kind::Thunder => "weather.thunder",
+        };
+
+        send_message(world, caller, &msg!(caller_view, key));
+    }
+}
@@ -0,0 +1,208 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityView, QueryAPI, World, WorldGet};
+use hyperion::{
+    msg,
+    runtime::AsyncRuntime,
+    simulation::{Name, command::Permissions},
+    util::mojang::MojangClient,
+    uuid::Uuid,
+    whitelist::{Whitelist, WhitelistEntry, resolve_entry, resolve_uuid_entry},
+};
+use hyperion_clap::MinecraftCommand;
+use tracing::warn;
+
+use crate::command::send_message;
+
+#[derive(Parser, Debug)]
+#[command(name = "whitelist")]
+pub enum WhitelistCommand {
+    /// Lets a player join, by name or UUID.
+    Add { player: String },
+    /// Stops a player from joining, by name or UUID.
+    Remove { player: String },
+    /// Lists the whitelisted players.
+    List,
+    /// Only lets whitelisted players join.
+    On,
+    /// Lets anyone join.
+    Off,
+}
+
+impl MinecraftCommand for WhitelistCommand {
+    const PERMISSIONS: Permissions = Permissions::MANAGE_PLAYERS;
+
+    fn execute(self, world: &World, caller: Entity) {
+        let caller_view = caller.entity_view(world);
+
+        let result = world.get::<&Whitelist>(|whitelist| match self {
+            Self::Add { player } => add(world, whitelist, caller, player),
+            Self::Remove { player } => whitelist.remove(&player).map(|removed| {
+                if removed {
+                    msg!(caller_view, "whitelist.removed", player = player)
+                } else {
+                    msg!(caller_view, "whitelist.not_whitelisted", player = player)
+                }
+            }),
+            Self::List => Ok(list(caller_view, whitelist)),
+            Self::On => whitelist
+                .set_enabled(true)
+                .map(|_| msg!(caller_view, "whitelist.on")),
+            Self::Off => whitelist
+                .set_enabled(false)
+                .map(|_| msg!(caller_view, "whitelist.off")),
+        });
+
+        let msg = result.unwrap_or_else(|e| {
+            warn!("failed to save the whitelist: {e}");
+            msg!(caller_view, "whitelist.save_failed")
+        });
+
+        send_message(world, caller, &msg);
+    }
+}
+
+fn add(
+    world: &World,
+    whitelist: &Whitelist,
+    caller: Entity,
+    player: String,
+) -> anyhow::Result<String> {
+    let caller_view = caller.entity_view(world);
+
+    let uuid = Uuid::parse_str(&player).ok();
+
+    if let Some(entry) = online_entry(world, &player, uuid) {
+        let WhitelistEntry::Player { name, .. } = &entry else {
+            unreachable!("online players are always added with their UUID");
+        };
+        let name = name.clone();
+
+        return whitelist
+            .add(entry)
+            .map(|added| added_message(caller_view, &name, added));
+    }
+
+    // looking the player up takes a while, so they are added once the lookup is done
+    world.get::<&MojangClient>(|mojang| {
+        world.get::<&AsyncRuntime>(|runtime| {
+            let mojang = mojang.clone();
+
+            let lookup = async move {
+                let entry = match uuid {
+                    Some(uuid) => resolve_uuid_entry(&mojang, uuid)
+                        .await
+                        .map_err(|e| (player, e)),
+                    None => Ok(resolve_entry(&mojang, &player).await),
+                };
+
+                (caller, entry)
+            };
+
+            runtime.schedule(lookup, finish_add);
+        });
+    });
+
+    Ok(msg!(caller_view, "whitelist.looking_up"))
+}
+
+/// The entry of an online player called `player`, or with `uuid` if `player` is one. Their name
+/// and UUID are already known, so they do not need to be looked up.
+fn online_entry(world: &World, player: &str, uuid: Option<Uuid>) -> Option<WhitelistEntry> {
+    let mut entry = None;
+
+    world
+        .new_query::<(&hyperion::simulation::Uuid, &Name)>()
+        .each(|(id, name)| {
+            if Some(id.0) == uuid || name.eq_ignore_ascii_case(player) {
+                entry = Some(WhitelistEntry::Player {
+                    uuid: id.0,
+                    name: name.to_string(),
+                });
+            }
+        });
+
+    entry
+}
+
+type Lookup = Result<WhitelistEntry, (String, anyhow::Error)>;
+
+fn finish_add((caller, entry): (Entity, Lookup), world: &World) {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err((player, e)) => {
+            warn!("failed to look up the name of {player}: {e}");
+
+            if world.is_alive(caller) {
+                let msg = msg!(
+                    caller.entity_view(world),
+                    "whitelist.unknown_uuid",
+                    player = player
+                );
+                send_message(world, caller, &msg);
+            }
+
+            return;
+        }
+    };
+
+    let result = world.get::<&Whitelist>(|whitelist| whitelist.add(entry.clone()));
+
+    if !world.is_alive(caller) {
+        return;
+    }
+
+    let caller_view = caller.entity_view(world);
+
+    let msg = match result {
+        Ok(added) => {
+            let player = match entry {
+                WhitelistEntry::Player { name, .. } => name,
+                WhitelistEntry::Name(name) => msg!(caller_view, "whitelist.by_name", player = name),
+            };
+
+            added_message(caller_view, &player, added)
+        }
+        Err(e) => {
+            warn!("failed to save the whitelist: {e}");
+            msg!(caller_view, "whitelist.save_failed")
+        }
+    };
+
+    send_message(world, caller, &msg);
+}
+
+fn added_message(caller: EntityView<'_>, player: &str, added: bool) -> String {
+    if added {
+        msg!(caller, "whitelist.added", player = player)
+    } else {
+        msg!(caller, "whitelist.already_added", player = player)
+    }
+}
+
+fn list(caller: EntityView<'_>, whitelist: &Whitelist) -> String {
+    let entries = whitelist.entries();
+
+    let key = if whitelist.is_enabled() {
+        "whitelist.list_on"
+    } else {
+        "whitelist.list_off"
+    };
+
+    let mut msg = msg!(caller, key, count = entries.len());
+
+    for (name, uuid) in entries {
+        let uuid = uuid.map_or_else(
+            || msg!(caller, "whitelist.not_resolved"),
+            |uuid| uuid.to_string(),
+        );
+
+        msg.push_str(&msg!(
+            caller,
+            "whitelist.list_entry",
+            player = name,
+            uuid = uuid
+        ));
+    }
+
+    msg
+}
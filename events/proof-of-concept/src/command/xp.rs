@@ -1,6 +1,6 @@
 use clap::Parser;
 use flecs_ecs::core::{Entity, EntityViewGet, World};
-use hyperion::simulation::Xp;
+use hyperion::simulation::{Xp, command::Permissions};
 use hyperion_clap::MinecraftCommand;
 
 #[derive(Parser, Debug)]
@@ -10,10 +10,12 @@ pub struct XpCommand {
 }
 
 impl MinecraftCommand for XpCommand {
+    const PERMISSIONS: Permissions = Permissions::GAME_MASTER;
+
     fn execute(self, world: &World, caller: Entity) {
         let Self { amount } = self;
 
-        caller.entity_view(world).get::<&mut Xp>(|xp| {
+        caller.entity_view(world).try_get::<&mut Xp>(|xp| {
             xp.amount = amount;
         });
     }
@@ -1,13 +1,49 @@
 use std::fmt::Display;
 
-use flecs_ecs::macros::Component;
+use flecs_ecs::{
+    core::{QueryAPI, World},
+    macros::Component,
+};
+use hyperion::{
+    PacketBundle,
+    net::{Compose, NetworkStreamRef, SendReport},
+    system_registry::SystemId,
+};
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
 #[repr(C)]
 pub enum Team {
     #[default]
-    Player,
+    Human,
     Zombie,
+    /// Watching without playing; see [`crate::module::spectator`].
+    Spectator,
+}
+
+impl Team {
+    /// Sends `packet` to every player on this team.
+    pub fn multicast<P>(
+        self,
+        packet: P,
+        compose: &Compose,
+        system_id: SystemId,
+        world: &World,
+    ) -> anyhow::Result<SendReport>
+    where
+        P: PacketBundle,
+    {
+        let mut streams = Vec::new();
+
+        world
+            .new_query::<(&NetworkStreamRef, &Self)>()
+            .each(|(&stream, &team)| {
+                if team == self {
+                    streams.push(stream);
+                }
+            });
+
+        compose.multicast(packet, &streams, system_id, world)
+    }
 }
 
 impl Display for Team {
@@ -15,7 +51,8 @@ impl Display for Team {
         // https://modrinth.com/resourcepack/565+-minecraft-emoji
         match self {
             Self::Zombie => write!(f, "\u{E050}"),
-            Self::Player => write!(f, "\u{E252}"),
+            Self::Human => write!(f, "\u{E252}"),
+            Self::Spectator => write!(f, "\u{1F441}"),
         }
     }
 }
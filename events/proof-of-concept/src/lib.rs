@@ -6,16 +6,12 @@
 #![feature(iter_from_coroutine)]
 #![feature(exact_size_is_empty)]
 
-use std::net::ToSocketAddrs;
-
+use anyhow::Context;
 use flecs_ecs::prelude::*;
-use hyperion::{
-    Hyperion,
-    runtime::AsyncRuntime,
-    simulation::{Player, blocks::Blocks},
-};
+use hyperion::{Hyperion, config::ServerConfig, simulation::Player};
 use hyperion_clap::hyperion_command::CommandRegistry;
 use module::block::BlockModule;
+use tracing::error;
 
 mod component;
 mod module;
@@ -25,7 +21,14 @@ use hyperion::glam::IVec3;
 use module::{attack::AttackModule, level::LevelModule, regeneration::RegenerationModule};
 
 use crate::{
-    module::{chat::ChatModule, spawn::SpawnModule, stats::StatsModule},
+    module::{
+        chat::ChatModule, class::ClassModule, death::DeathModule, grace::GraceModule,
+        infection::InfectionModule, leaderboard::LeaderboardModule, leap::LeapModule,
+        locales::LocalesModule, map::MapModule, messages::MessagesModule, overtime::OvertimeModule,
+        player_stats::PlayerStatsModule, round::RoundModule, shop::ShopModule,
+        sidebar::SidebarModule, spawn::SpawnModule, spectator::SpectatorModule, stats::StatsModule,
+        tracker::TrackerModule,
+    },
     skin::SkinModule,
 };
 
@@ -53,10 +56,27 @@ impl Module for ProofOfConceptModule {
             .add_trait::<(flecs::With, component::team::Team)>();
 
         world.import::<SpawnModule>();
+        world.import::<MapModule>();
+        world.import::<LocalesModule>();
+        world.import::<MessagesModule>();
         world.import::<ChatModule>();
+        world.import::<LeapModule>();
+        // reads infection and round events before the modules below drain them
+        world.import::<PlayerStatsModule>();
+        world.import::<LeaderboardModule>();
+        world.import::<InfectionModule>();
+        world.import::<SpectatorModule>();
+        world.import::<RoundModule>();
+        world.import::<GraceModule>();
+        world.import::<TrackerModule>();
+        world.import::<OvertimeModule>();
+        world.import::<ClassModule>();
+        world.import::<ShopModule>();
+        world.import::<SidebarModule>();
         world.import::<StatsModule>();
         world.import::<BlockModule>();
         world.import::<AttackModule>();
+        world.import::<DeathModule>();
         world.import::<LevelModule>();
         world.import::<RegenerationModule>();
         world.import::<hyperion_permission::PermissionModule>();
@@ -65,7 +85,11 @@ impl Module for ProofOfConceptModule {
         world.import::<SkinModule>();
 
         world.get::<&mut CommandRegistry>(|registry| {
-            command::register(registry, world);
+            if let Err(e) =
+                command::register(registry, world).context("failed to register commands")
+            {
+                error!("{e:#}");
+            }
         });
 
         world.set(hyperion_utils::AppId {
@@ -74,22 +98,12 @@ impl Module for ProofOfConceptModule {
             application: "hyperion-poc".to_string(),
         });
 
-        world.get::<&AsyncRuntime>(|runtime| {
-            let f = hyperion_utils::cached_save(
-                world,
-                "https://github.com/andrewgazelka/maps/raw/main/GenMap.tar.gz",
-            );
-
-            runtime.schedule(f, |result, world| {
-                let save = result.unwrap();
-                world.set(Blocks::new(world, &save).unwrap());
-            });
-        });
+        module::map::load_current_map(world);
     }
 }
 
-pub fn init_game(address: impl ToSocketAddrs + Send + Sync + 'static) -> anyhow::Result<()> {
-    Hyperion::init_with(address, |world| {
+pub fn init_game(config: ServerConfig) -> anyhow::Result<()> {
+    Hyperion::init_with(config, |world| {
         world.import::<ProofOfConceptModule>();
     })?;
 
@@ -1,4 +1,6 @@
+use anyhow::Context;
 use clap::Parser;
+use hyperion::config::{ServerConfig, split_host_port};
 use proof_of_concept::init_game;
 use tracing_subscriber::{EnvFilter, Registry, layer::SubscriberExt};
 use tracing_tracy::TracyLayer;
@@ -10,12 +12,12 @@ static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 /// The arguments to run the server
 #[derive(Parser)]
 struct Args {
-    /// The IP address the server should listen on. Defaults to 0.0.0.0
-    #[clap(short, long, default_value = "0.0.0.0")]
-    ip: String,
-    /// The port the server should listen on. Defaults to 25565
-    #[clap(short, long, default_value = "35565")]
-    port: u16,
+    /// The IP address the server should listen on. Overrides `proxy_address` in the config
+    #[clap(short, long)]
+    ip: Option<String>,
+    /// The port the server should listen on. Overrides `proxy_address` in the config
+    #[clap(short, long)]
+    port: Option<u16>,
 }
 
 fn setup_logging() {
@@ -34,14 +36,27 @@ fn setup_logging() {
     .expect("setup tracing subscribers");
 }
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
     setup_logging();
 
     let Args { ip, port } = Args::parse();
 
-    let address = format!("{ip}:{port}");
+    let mut config = ServerConfig::load_default().context("failed to load the configuration")?;
 
-    init_game(address).unwrap();
+    if ip.is_some() || port.is_some() {
+        let (host, default_port) = split_host_port(&config.proxy_address).with_context(|| {
+            format!(
+                "`proxy_address` in the configuration must be a host and port, but is \"{}\"",
+                config.proxy_address
+            )
+        })?;
+        let ip = ip.unwrap_or_else(|| host.to_owned());
+        let port = port.unwrap_or(default_port);
+
+        config.proxy_address = format!("{ip}:{port}");
+    }
+
+    init_game(config)
 }
@@ -1,7 +1,23 @@
 pub mod attack;
 pub mod block;
 pub mod chat;
+pub mod class;
+pub mod death;
+pub mod grace;
+pub mod infection;
+pub mod leaderboard;
+pub mod leap;
 pub mod level;
+pub mod locales;
+pub mod map;
+pub mod messages;
+pub mod overtime;
+pub mod player_stats;
 pub mod regeneration;
+pub mod round;
+pub mod shop;
+pub mod sidebar;
 pub mod spawn;
+pub mod spectator;
 pub mod stats;
+pub mod tracker;
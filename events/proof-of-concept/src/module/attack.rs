@@ -1,34 +1,31 @@
-use std::borrow::Cow;
-
 use compact_str::format_compact;
 use flecs_ecs::{
     core::{
-        EntityViewGet, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl, World,
-        WorldProvider, flecs,
+        Entity, EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl,
+        World, WorldProvider, flecs,
     },
     macros::{Component, system},
     prelude::Module,
 };
 use hyperion::{
-    net::{
-        Compose, NetworkStreamRef, agnostic,
-        packets::{BossBarAction, BossBarS2c},
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{
+        EntityReaction, Health, PacketState, Player, Position,
+        boss_bar::{BossBar, spawn_owned_boss_bar},
+        event::{self, AttackFlags},
+        fall::fall_damage,
+        game_mode::GameMode,
     },
-    simulation::{EntityReaction, Health, PacketState, Player, Position, event},
     storage::EventQueue,
     system_registry::SystemId,
     util::TracingExt,
-    uuid::Uuid,
     valence_protocol::{
         ItemKind, ItemStack, Particle, VarInt, ident,
-        math::{DVec3, Vec3},
+        math::Vec3,
         nbt,
         packets::{
             play,
-            play::{
-                boss_bar_s2c::{BossBarColor, BossBarDivision, BossBarFlags},
-                entity_attributes_s2c::AttributeProperty,
-            },
+            play::{boss_bar_s2c::BossBarColor, entity_attributes_s2c::AttributeProperty},
         },
     },
 };
@@ -36,9 +33,21 @@ use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
 use tracing::info_span;
 
+use crate::module::{
+    grace::cancel_grace_attack,
+    infection::{InfectedEvents, damage_taken_multiplier, try_infect},
+    leap::NoFallDamage,
+    round::{GameState, check_win_condition},
+    shop::Strength,
+    spectator::is_spectating,
+};
+
 #[derive(Component)]
 pub struct AttackModule;
 
+/// How long a player cannot be hurt again after being hurt, in ticks.
+const IMMUNE_TICK_DURATION: i64 = 10;
+
 #[derive(Component, Default, Copy, Clone, Debug)]
 #[meta]
 pub struct ImmuneUntil {
@@ -51,6 +60,34 @@ pub struct Armor {
     pub armor: f32,
 }
 
+/// How long a player's death is still credited to whoever last hurt them, in ticks.
+const ATTACKER_MEMORY_TICKS: i64 = 20 * 5;
+
+/// Who last hurt a player, so that their death can be credited to them.
+#[derive(Component, Default, Copy, Clone, Debug)]
+pub struct LastAttacker {
+    attacker: Option<Entity>,
+    tick: i64,
+}
+
+impl LastAttacker {
+    pub const fn record(&mut self, attacker: Entity, tick: i64) {
+        self.attacker = Some(attacker);
+        self.tick = tick;
+    }
+
+    /// The attacker at `tick`, if they hurt the player recently enough to be credited.
+    #[must_use]
+    pub fn get(self, tick: i64) -> Option<Entity> {
+        self.attacker
+            .filter(|_| tick - self.tick <= ATTACKER_MEMORY_TICKS)
+    }
+
+    pub const fn clear(&mut self) {
+        self.attacker = None;
+    }
+}
+
 // Used as a component only for commands, does not include armor or weapons
 #[derive(Component, Default, Copy, Clone, Debug)]
 #[meta]
@@ -67,6 +104,70 @@ pub struct KillCount {
     pub kill_count: u32,
 }
 
+/// The boss bar showing a player their kills, which is deleted along with the player.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct KillCountBar(Entity);
+
+/// How hard hits knock players back, in blocks per tick. Game modes can set their own.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct KnockbackConfig {
+    /// How far a plain hit knocks players away.
+    pub base: f32,
+    /// Added for a hit while sprinting.
+    pub sprint: f32,
+    /// Added for each level of Knockback on the weapon.
+    pub per_level: f32,
+    /// How far up hits knock players, which is also the highest upward velocity a hit leaves.
+    pub vertical: f32,
+}
+
+impl Default for KnockbackConfig {
+    /// Vanilla knockback.
+    fn default() -> Self {
+        Self {
+            base: 0.4,
+            sprint: 0.4,
+            per_level: 0.5,
+            vertical: 0.4,
+        }
+    }
+}
+
+impl KnockbackConfig {
+    /// How hard a hit knocks away, before knockback resistance.
+    #[must_use]
+    pub fn strength(&self, sprinting: bool, knockback_level: u16) -> f32 {
+        let sprint = if sprinting { self.sprint } else { 0.0 };
+
+        self.base + sprint + self.per_level * f32::from(knockback_level)
+    }
+
+    /// The velocity of a player moving at `velocity` after being hit from `origin` while at
+    /// `target`, with `resistance` from `0` for none to `1` for full knockback resistance.
+    #[must_use]
+    pub fn knockback(
+        &self,
+        velocity: Vec3,
+        origin: Vec3,
+        target: Vec3,
+        strength: f32,
+        resistance: f32,
+    ) -> Vec3 {
+        let scale = 1.0 - resistance.clamp(0.0, 1.0);
+        let away = Vec3::new(target.x - origin.x, 0.0, target.z - origin.z);
+
+        // a hit from straight above or below has no direction to knock in
+        if scale <= 0.0 || away.length() < 0.01 {
+            return velocity;
+        }
+
+        let mut velocity = velocity / 2.0 + away.normalize() * strength * scale;
+        velocity.y = (velocity.y + self.vertical * scale).min(self.vertical);
+
+        velocity
+    }
+}
+
 impl Module for AttackModule {
     #[allow(clippy::excessive_nesting)]
     fn module(world: &World) {
@@ -74,215 +175,372 @@ impl Module for AttackModule {
         world.component::<Armor>().meta();
         world.component::<CombatStats>().meta();
         world.component::<KillCount>().meta();
+        world.component::<KillCountBar>();
+        world.component::<LastAttacker>();
+        world.component::<KnockbackConfig>();
+
+        world.set(KnockbackConfig::default());
 
         world
             .component::<Player>()
             .add_trait::<(flecs::With, ImmuneUntil)>()
+            .add_trait::<(flecs::With, LastAttacker)>()
             .add_trait::<(flecs::With, CombatStats)>()
             .add_trait::<(flecs::With, KillCount)>()
             .add_trait::<(flecs::With, Armor)>();
 
-        let kill_count_uuid = Uuid::new_v4();
-
         system!(
             "kill_counts",
             world,
-            &Compose($),
             &KillCount,
             &NetworkStreamRef,
+            ?&KillCountBar,
         )
         .with_enum(PacketState::Play)
-        .multi_threaded()
         .kind::<flecs::pipeline::OnUpdate>()
         .tracing_each_entity(
             info_span!("kill_counts"),
-            move |entity, (compose, kill_count, stream)| {
+            |entity, (kill_count, &stream, kill_count_bar)| {
                 const MAX_KILLS: usize = 10;
 
                 let world = entity.world();
 
                 let kills = kill_count.kill_count;
                 let title = format_compact!("{kills} kills");
-                let title = hyperion_text::Text::new(&title);
-                let health = (kill_count.kill_count as f32 / MAX_KILLS as f32).min(1.0);
-
-                let pkt = BossBarS2c {
-                    id: kill_count_uuid,
-                    action: BossBarAction::Add {
-                        title,
-                        health,
-                        color: BossBarColor::Red,
-                        division: BossBarDivision::NoDivision,
-                        flags: BossBarFlags::default(),
-                    },
+                let progress = (kill_count.kill_count as f32 / MAX_KILLS as f32).min(1.0);
+
+                let Some(&KillCountBar(bar)) = kill_count_bar else {
+                    let mut bar = BossBar::new(title.as_str())
+                        .color(BossBarColor::Red)
+                        .progress(progress);
+                    bar.show_to(stream);
+
+                    let bar = spawn_owned_boss_bar(&world, entity.id(), bar);
+                    entity.set(KillCountBar(bar));
+                    return;
                 };
 
-                compose
-                    .unicast(&pkt, *stream, SystemId(99), &world)
-                    .unwrap();
+                bar.entity_view(world).get::<&mut BossBar>(|bar| {
+                    bar.set_title(title.as_str());
+                    bar.set_progress(progress);
+                });
             },
         );
 
-        system!("handle_attacks", world, &mut EventQueue<event::AttackEntity>($), &Compose($))
-            .multi_threaded()
-            .each_iter(
-                move |it: TableIter<'_, false>,
-                      _,
-                      (event_queue, compose): (
-                          &mut EventQueue<event::AttackEntity>,
-                          &Compose,
-                      )| {
-                    const IMMUNE_TICK_DURATION: i64 = 10;
-
-                    let span = info_span!("handle_attacks");
-                    let _enter = span.enter();
-
-                    let current_tick = compose.global().tick;
-
-                    let world = it.world();
-
-                    for event in event_queue.drain() {
-                        let target = world.entity_from_id(event.target);
-                        let origin = world.entity_from_id(event.origin);
-                        origin.get::<(&Position, &mut KillCount, &mut PlayerInventory, &mut Armor, &CombatStats, &PlayerInventory)>(|(origin_pos, kill_count, inventory, origin_armor, from_stats, from_inventory)| {
-                            let damage = from_stats.damage + calculate_stats(from_inventory).damage;
+        system!(
+            "handle_attacks",
+            world,
+            &mut EventQueue<event::AttackEntity>($),
+            &Compose($),
+            &KnockbackConfig($),
+            &mut InfectedEvents($),
+            &mut GameState($),
+        )
+        .multi_threaded()
+        .each_iter(
+            move |it: TableIter<'_, false>,
+                  _,
+                  (event_queue, compose, knockback, infected, state): (
+                &mut EventQueue<event::AttackEntity>,
+                &Compose,
+                &KnockbackConfig,
+                &mut InfectedEvents,
+                &mut GameState,
+            )| {
+                let span = info_span!("handle_attacks");
+                let _enter = span.enter();
+
+                let current_tick = compose.global().tick;
+
+                let world = it.world();
+
+                // combat is frozen while the result of a round is shown
+                if !state.combat_enabled() {
+                    event_queue.drain().for_each(drop);
+                    return;
+                }
+
+                let mut any_infected = false;
+
+                for event in event_queue.drain() {
+                    let target = world.entity_from_id(event.target);
+                    let origin = world.entity_from_id(event.origin);
+
+                    // spectators can neither hit nor be hit
+                    if is_spectating(origin) || is_spectating(target) {
+                        continue;
+                    }
+
+                    if !can_attack(origin) || !can_attack(target) {
+                        continue;
+                    }
+
+                    if cancel_grace_attack(&world, compose, state, origin, target) {
+                        continue;
+                    }
+
+                    // a zombie hitting a human converts them instead of dealing damage
+                    if try_infect(&world, compose, origin, target, infected) {
+                        any_infected = true;
+                        continue;
+                    }
+
+                    let strength = origin
+                        .try_get::<&Strength>(|strength| strength.bonus(current_tick))
+                        .unwrap_or_default();
+
+                    let damage_taken = damage_taken_multiplier(&world, target);
+
+                    origin.get::<(
+                        &Position,
+                        &mut KillCount,
+                        &mut PlayerInventory,
+                        &mut Armor,
+                        &CombatStats,
+                    )>(
+                        |(origin_pos, kill_count, inventory, origin_armor, from_stats)| {
+                            // use the weapon captured when the attack happened rather than
+                            // whatever the attacker is holding now
+                            let damage =
+                                from_stats.damage + calculate_damage(&event.weapon) + strength;
                             target.get::<(
                                 &mut ImmuneUntil,
+                                &mut LastAttacker,
                                 &mut Health,
                                 &mut Position,
                                 &mut EntityReaction,
                                 &CombatStats,
-                                &PlayerInventory
+                                &PlayerInventory,
                             )>(
-                                |(immune_until, health, target_position, reaction, stats, target_inventory)| {
+                                |(
+                                    immune_until,
+                                    last_attacker,
+                                    health,
+                                    target_position,
+                                    reaction,
+                                    stats,
+                                    target_inventory,
+                                )| {
                                     if immune_until.tick > current_tick {
                                         return;
                                     }
 
                                     immune_until.tick = current_tick + IMMUNE_TICK_DURATION;
+                                    last_attacker.record(origin.id(), current_tick);
 
                                     let calculated_stats = calculate_stats(target_inventory);
                                     let armor = stats.armor + calculated_stats.armor;
-                                    let toughness = stats.armor_toughness + calculated_stats.armor_toughness;
+                                    let toughness =
+                                        stats.armor_toughness + calculated_stats.armor_toughness;
                                     let protection = stats.protection + calculated_stats.protection;
 
-                                    let damage_after_armor = get_damage_left(damage, armor, toughness);
-                                    let damage_after_protection = get_inflicted_damage(damage_after_armor, protection);
+                                    let damage_after_armor =
+                                        get_damage_left(damage, armor, toughness);
+                                    let damage_after_protection =
+                                        get_inflicted_damage(damage_after_armor, protection);
 
-                                    health.damage(damage_after_protection);
+                                    health.damage(damage_after_protection * damage_taken);
                                     if health.is_dead() {
                                         let sound = agnostic::sound(
                                             ident!("minecraft:entity.player.attack.knockback"),
                                             **target_position,
-                                        ).volume(1.5)
-                                            .pitch(0.8)
-                                            .seed(fastrand::i64(..))
-                                            .build();
+                                        )
+                                        .volume(1.5)
+                                        .pitch(0.8)
+                                        .seed(fastrand::i64(..))
+                                        .build();
 
-                                        compose.broadcast(&sound, SystemId(999)).send(&world).unwrap();
+                                        sound
+                                            .broadcast_local(compose, SystemId(999))
+                                            .send(&world)
+                                            .unwrap();
 
                                         // Create particle effect at the attacker's position
-                                        let particle_pkt = play::ParticleS2c {
-                                            particle: Cow::Owned(Particle::Explosion),
-                                            long_distance: true,
-                                            position: target_position.as_dvec3() + DVec3::new(0.0, 1.0, 0.0),
-                                            max_speed: 0.5,
-                                            count: 100,
-                                            offset: Vec3::new(0.5, 0.5, 0.5),
-                                        };
+                                        let particles = agnostic::particles(
+                                            Particle::Explosion,
+                                            **target_position + Vec3::new(0.0, 1.0, 0.0),
+                                        )
+                                        .offset(Vec3::splat(0.5))
+                                        .speed(0.5)
+                                        .count(100)
+                                        .long_distance(true)
+                                        .build();
 
                                         // Add a second particle effect for more visual impact
-                                        let particle_pkt2 = play::ParticleS2c {
-                                            particle: Cow::Owned(Particle::DragonBreath),
-                                            long_distance: true,
-                                            position: target_position.as_dvec3() + DVec3::new(0.0, 1.5, 0.0),
-                                            max_speed: 0.2,
-                                            count: 75,
-                                            offset: Vec3::new(0.3, 0.3, 0.3),
-                                        };
+                                        let particles2 = agnostic::particles(
+                                            Particle::DragonBreath,
+                                            **target_position + Vec3::new(0.0, 1.5, 0.0),
+                                        )
+                                        .offset(Vec3::splat(0.3))
+                                        .speed(0.2)
+                                        .count(75)
+                                        .long_distance(true)
+                                        .build();
                                         let origin_entity_id = origin.minecraft_id();
 
                                         origin_armor.armor += 1.0;
                                         let pkt = play::EntityAttributesS2c {
                                             entity_id: VarInt(origin_entity_id),
-                                            properties: vec![
-                                                AttributeProperty {
-                                                    key: ident!("minecraft:generic.armor").into(),
-                                                    value: origin_armor.armor.into(),
-                                                    modifiers: vec![],
-                                                }
-                                            ],
+                                            properties: vec![AttributeProperty {
+                                                key: ident!("minecraft:generic.armor").into(),
+                                                value: origin_armor.armor.into(),
+                                                modifiers: vec![],
+                                            }],
                                         };
 
-                                        compose.broadcast(&pkt, SystemId(999)).send(&world).unwrap();
-                                        compose.broadcast(&particle_pkt, SystemId(999)).send(&world).unwrap();
-                                        compose.broadcast(&particle_pkt2, SystemId(999)).send(&world).unwrap();
+                                        compose
+                                            .broadcast(&pkt, SystemId(999))
+                                            .send(&world)
+                                            .unwrap();
+                                        particles
+                                            .broadcast_local(compose, SystemId(999))
+                                            .send(&world)
+                                            .unwrap();
+                                        particles2
+                                            .broadcast_local(compose, SystemId(999))
+                                            .send(&world)
+                                            .unwrap();
 
                                         // Create NBT for enchantment protection level 1
                                         let mut protection_nbt = nbt::Compound::new();
                                         let mut enchantments = vec![];
 
                                         let mut protection_enchantment = nbt::Compound::new();
-                                        protection_enchantment.insert("id", nbt::Value::String("minecraft:protection".into()));
+                                        protection_enchantment.insert(
+                                            "id",
+                                            nbt::Value::String("minecraft:protection".into()),
+                                        );
                                         protection_enchantment.insert("lvl", nbt::Value::Short(1));
                                         enchantments.push(protection_enchantment);
                                         protection_nbt.insert(
                                             "Enchantments",
-                                            nbt::Value::List(nbt::list::List::Compound(enchantments)),
+                                            nbt::Value::List(nbt::list::List::Compound(
+                                                enchantments,
+                                            )),
                                         );
                                         // Apply upgrades based on the level
                                         match kill_count.kill_count {
                                             0 => {}
-                                            1 => inventory
-                                                .set_hotbar(0, ItemStack::new(ItemKind::WoodenSword, 1, None)),
-                                            2 => inventory
-                                                .set_boots(ItemStack::new(ItemKind::LeatherBoots, 1, None)),
-                                            3 => inventory
-                                                .set_leggings(ItemStack::new(ItemKind::LeatherLeggings, 1, None)),
-                                            4 => inventory
-                                                .set_chestplate(ItemStack::new(ItemKind::LeatherChestplate, 1, None)),
-                                            5 => inventory
-                                                .set_helmet(ItemStack::new(ItemKind::LeatherHelmet, 1, None)),
-                                            6 => inventory
-                                                .set_hotbar(0, ItemStack::new(ItemKind::StoneSword, 1, None)),
-                                            7 => inventory
-                                                .set_boots(ItemStack::new(ItemKind::ChainmailBoots, 1, None)),
-                                            8 => inventory
-                                                .set_leggings(ItemStack::new(ItemKind::ChainmailLeggings, 1, None)),
-                                            9 => inventory
-                                                .set_chestplate(ItemStack::new(ItemKind::ChainmailChestplate, 1, None)),
-                                            10 => inventory
-                                                .set_helmet(ItemStack::new(ItemKind::ChainmailHelmet, 1, None)),
-                                            11 => inventory
-                                                .set_hotbar(0, ItemStack::new(ItemKind::IronSword, 1, None)),
-                                            12 => inventory
-                                                .set_boots(ItemStack::new(ItemKind::IronBoots, 1, None)),
-                                            13 => inventory
-                                                .set_leggings(ItemStack::new(ItemKind::IronLeggings, 1, None)),
-                                            14 => inventory
-                                                .set_chestplate(ItemStack::new(ItemKind::IronChestplate, 1, None)),
-                                            15 => inventory
-                                                .set_helmet(ItemStack::new(ItemKind::IronHelmet, 1, None)),
-                                            16 => inventory
-                                                .set_hotbar(0, ItemStack::new(ItemKind::DiamondSword, 1, None)),
-                                            17 => inventory
-                                                .set_boots(ItemStack::new(ItemKind::DiamondBoots, 1, None)),
-                                            18 => inventory
-                                                .set_leggings(ItemStack::new(ItemKind::DiamondLeggings, 1, None)),
-                                            19 => inventory
-                                                .set_chestplate(ItemStack::new(ItemKind::DiamondChestplate, 1, None)),
-                                            20 => inventory
-                                                .set_helmet(ItemStack::new(ItemKind::DiamondHelmet, 1, None)),
-                                            21 => inventory
-                                                .set_hotbar(0, ItemStack::new(ItemKind::NetheriteSword, 1, None)),
-                                            22 => inventory
-                                                .set_boots(ItemStack::new(ItemKind::NetheriteBoots, 1, None)),
-                                            23 => inventory
-                                                .set_leggings(ItemStack::new(ItemKind::NetheriteLeggings, 1, None)),
-                                            24 => inventory
-                                                .set_chestplate(ItemStack::new(ItemKind::NetheriteChestplate, 1, None)),
-                                            25 => inventory
-                                                .set_helmet(ItemStack::new(ItemKind::NetheriteHelmet, 1, None)),
+                                            1 => inventory.set_hotbar(
+                                                0,
+                                                ItemStack::new(ItemKind::WoodenSword, 1, None),
+                                            ),
+                                            2 => inventory.set_boots(ItemStack::new(
+                                                ItemKind::LeatherBoots,
+                                                1,
+                                                None,
+                                            )),
+                                            3 => inventory.set_leggings(ItemStack::new(
+                                                ItemKind::LeatherLeggings,
+                                                1,
+                                                None,
+                                            )),
+                                            4 => inventory.set_chestplate(ItemStack::new(
+                                                ItemKind::LeatherChestplate,
+                                                1,
+                                                None,
+                                            )),
+                                            5 => inventory.set_helmet(ItemStack::new(
+                                                ItemKind::LeatherHelmet,
+                                                1,
+                                                None,
+                                            )),
+                                            6 => inventory.set_hotbar(
+                                                0,
+                                                ItemStack::new(ItemKind::StoneSword, 1, None),
+                                            ),
+                                            7 => inventory.set_boots(ItemStack::new(
+                                                ItemKind::ChainmailBoots,
+                                                1,
+                                                None,
+                                            )),
+                                            8 => inventory.set_leggings(ItemStack::new(
+                                                ItemKind::ChainmailLeggings,
+                                                1,
+                                                None,
+                                            )),
+                                            9 => inventory.set_chestplate(ItemStack::new(
+                                                ItemKind::ChainmailChestplate,
+                                                1,
+                                                None,
+                                            )),
+                                            10 => inventory.set_helmet(ItemStack::new(
+                                                ItemKind::ChainmailHelmet,
+                                                1,
+                                                None,
+                                            )),
+                                            11 => inventory.set_hotbar(
+                                                0,
+                                                ItemStack::new(ItemKind::IronSword, 1, None),
+                                            ),
+                                            12 => inventory.set_boots(ItemStack::new(
+                                                ItemKind::IronBoots,
+                                                1,
+                                                None,
+                                            )),
+                                            13 => inventory.set_leggings(ItemStack::new(
+                                                ItemKind::IronLeggings,
+                                                1,
+                                                None,
+                                            )),
+                                            14 => inventory.set_chestplate(ItemStack::new(
+                                                ItemKind::IronChestplate,
+                                                1,
+                                                None,
+                                            )),
+                                            15 => inventory.set_helmet(ItemStack::new(
+                                                ItemKind::IronHelmet,
+                                                1,
+                                                None,
+                                            )),
+                                            16 => inventory.set_hotbar(
+                                                0,
+                                                ItemStack::new(ItemKind::DiamondSword, 1, None),
+                                            ),
+                                            17 => inventory.set_boots(ItemStack::new(
+                                                ItemKind::DiamondBoots,
+                                                1,
+                                                None,
+                                            )),
+                                            18 => inventory.set_leggings(ItemStack::new(
+                                                ItemKind::DiamondLeggings,
+                                                1,
+                                                None,
+                                            )),
+                                            19 => inventory.set_chestplate(ItemStack::new(
+                                                ItemKind::DiamondChestplate,
+                                                1,
+                                                None,
+                                            )),
+                                            20 => inventory.set_helmet(ItemStack::new(
+                                                ItemKind::DiamondHelmet,
+                                                1,
+                                                None,
+                                            )),
+                                            21 => inventory.set_hotbar(
+                                                0,
+                                                ItemStack::new(ItemKind::NetheriteSword, 1, None),
+                                            ),
+                                            22 => inventory.set_boots(ItemStack::new(
+                                                ItemKind::NetheriteBoots,
+                                                1,
+                                                None,
+                                            )),
+                                            23 => inventory.set_leggings(ItemStack::new(
+                                                ItemKind::NetheriteLeggings,
+                                                1,
+                                                None,
+                                            )),
+                                            24 => inventory.set_chestplate(ItemStack::new(
+                                                ItemKind::NetheriteChestplate,
+                                                1,
+                                                None,
+                                            )),
+                                            25 => inventory.set_helmet(ItemStack::new(
+                                                ItemKind::NetheriteHelmet,
+                                                1,
+                                                None,
+                                            )),
                                             26 => {
                                                 // Reset armor and start again with Protection I
                                                 inventory.set_boots(ItemStack::new(
@@ -399,33 +657,113 @@ impl Module for AttackModule {
                                         return;
                                     }
 
-                                    // Calculate velocity change based on attack direction
-                                    let this = **target_position;
-                                    let other = **origin_pos;
+                                    // hits within the immunity window returned above, so knockback
+                                    // never stacks
+                                    let sprinting = event.flags.contains(AttackFlags::SPRINT);
+                                    let level = enchantment_level(&event.weapon, "knockback");
+                                    let strength = knockback.strength(sprinting, level);
+                                    let resistance = knockback_resistance(target_inventory);
 
-                                    let delta_x = other.x - this.x;
-                                    let delta_z = other.z - this.z;
+                                    reaction.velocity = knockback.knockback(
+                                        reaction.velocity,
+                                        **origin_pos,
+                                        **target_position,
+                                        strength,
+                                        resistance,
+                                    );
+                                },
+                            );
+                        },
+                    );
+                }
 
-                                    if delta_x.abs() >= 0.01 || delta_z.abs() >= 0.01 {
-                                        let dist_xz = delta_x.hypot(delta_z);
-                                        let multiplier = 0.4;
+                // the last human may have been infected by one of this tick's hits
+                if any_infected {
+                    check_win_condition(&world, compose, state, None);
+                }
+            },
+        );
 
-                                        reaction.velocity /= 2.0;
-                                        reaction.velocity.x -= delta_x / dist_xz * multiplier;
-                                        reaction.velocity.y += multiplier;
-                                        reaction.velocity.z -= delta_z / dist_xz * multiplier;
+        system!(
+            "handle_mob_damage",
+            world,
+            &mut EventQueue<event::Damage>($),
+            &Compose($),
+            &GameState($),
+        )
+        .each_iter(|it, _, (event_queue, compose, state)| {
+            let world = it.world();
+            let current_tick = compose.global().tick;
 
-                                        reaction.velocity.y = reaction.velocity.y.min(0.4);
-                                    }
-                                },
-                            );
-                        });
-                    }
-                },
-            );
+            for event in event_queue.drain() {
+                // combat is frozen while the result of a round is shown
+                if !state.combat_enabled() {
+                    continue;
+                }
+
+                let target = world.entity_from_id(event.target);
+
+                if is_spectating(target) {
+                    continue;
+                }
+
+                let damage = event.amount * damage_taken_multiplier(&world, target);
+
+                target.try_get::<(&mut ImmuneUntil, &mut LastAttacker, &mut Health)>(
+                    |(immune_until, last_attacker, health)| {
+                        if immune_until.tick > current_tick {
+                            return;
+                        }
+
+                        immune_until.tick = current_tick + IMMUNE_TICK_DURATION;
+                        health.damage(damage);
+
+                        // damage dealt by the world has the target as its origin
+                        if event.origin != event.target {
+                            last_attacker.record(event.origin, current_tick);
+                        }
+                    },
+                );
+            }
+        });
+
+        system!(
+            "handle_fall_damage",
+            world,
+            &mut EventQueue<event::Fall>($),
+        )
+        .each_iter(|it, _, event_queue| {
+            let world = it.world();
+
+            for event in event_queue.drain() {
+                take_fall_damage(world.entity_from_id(event.entity), event.distance);
+            }
+        });
+    }
+}
+
+/// Hurts a player who landed after falling `distance` blocks.
+fn take_fall_damage(target: EntityView<'_>, distance: f32) {
+    // zombies are protected from landing after a leap
+    if target.has::<NoFallDamage>() || is_spectating(target) {
+        return;
+    }
+
+    let damage = fall_damage(distance);
+
+    if damage > 0.0 {
+        target.try_get::<&mut Health>(|health| health.damage(damage));
     }
 }
 
+/// Whether the game mode of `entity` lets them attack. Like vanilla, players in spectator mode,
+/// such as zombies waiting to respawn, cannot be attacked either.
+fn can_attack(entity: EntityView<'_>) -> bool {
+    entity
+        .try_get::<&GameMode>(|mode| mode.can_attack())
+        .unwrap_or(true)
+}
+
 // From minecraft source
 fn get_damage_left(damage: f32, armor: f32, armor_toughness: f32) -> f32 {
     let f: f32 = 2.0 + armor_toughness / 4.0;
@@ -489,6 +827,88 @@ const fn calculate_toughness(item: &ItemStack) -> f32 {
     }
 }
 
+/// Whether an NBT string is `name`, with or without the `minecraft:` namespace.
+fn is_named(value: Option<&nbt::Value>, name: &str) -> bool {
+    let Some(nbt::Value::String(value)) = value else {
+        return false;
+    };
+
+    value.strip_prefix("minecraft:").unwrap_or(value) == name
+}
+
+/// The level of the enchantment `id` on `item`, or `0` if it does not have it.
+fn enchantment_level(item: &ItemStack, id: &str) -> u16 {
+    let Some(nbt::Value::List(nbt::List::Compound(enchantments))) =
+        item.nbt.as_ref().and_then(|nbt| nbt.get("Enchantments"))
+    else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .filter(|enchantment| is_named(enchantment.get("id"), id))
+        .find_map(|enchantment| match enchantment.get("lvl") {
+            Some(&nbt::Value::Short(level)) => u16::try_from(level).ok(),
+            Some(&nbt::Value::Int(level)) => u16::try_from(level).ok(),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// The knockback resistance a piece of armor gives. Netherite armor gives `0.1`, unless the item
+/// has its own attribute modifiers, which replace the ones of its kind like in vanilla.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "knockback resistance is between 0 and 1"
+)]
+fn armor_knockback_resistance(item: &ItemStack) -> f32 {
+    let modifiers = item
+        .nbt
+        .as_ref()
+        .and_then(|nbt| nbt.get("AttributeModifiers"));
+
+    let Some(nbt::Value::List(nbt::List::Compound(modifiers))) = modifiers else {
+        return match item.item {
+            ItemKind::NetheriteHelmet
+            | ItemKind::NetheriteChestplate
+            | ItemKind::NetheriteLeggings
+            | ItemKind::NetheriteBoots => 0.1,
+            _ => 0.0,
+        };
+    };
+
+    modifiers
+        .iter()
+        .filter(|modifier| {
+            is_named(
+                modifier.get("AttributeName"),
+                "generic.knockback_resistance",
+            )
+        })
+        .filter_map(|modifier| match modifier.get("Amount") {
+            Some(&nbt::Value::Double(amount)) => Some(amount as f32),
+            Some(&nbt::Value::Float(amount)) => Some(amount),
+            _ => None,
+        })
+        .sum()
+}
+
+/// The knockback resistance of the armor in `inventory`, from `0` to `1`.
+fn knockback_resistance(inventory: &PlayerInventory) -> f32 {
+    let armor = [
+        inventory.get_helmet(),
+        inventory.get_chestplate(),
+        inventory.get_leggings(),
+        inventory.get_boots(),
+    ];
+
+    armor
+        .into_iter()
+        .map(armor_knockback_resistance)
+        .sum::<f32>()
+        .clamp(0.0, 1.0)
+}
+
 fn calculate_stats(inventory: &PlayerInventory) -> CombatStats {
     let hand = inventory.get_hand_slot(0).unwrap();
     let damage = calculate_damage(hand);
@@ -510,3 +930,140 @@ fn calculate_stats(inventory: &PlayerInventory) -> CombatStats {
         protection: 0.0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::{Entity, EntityViewGet, World};
+    use hyperion::{
+        simulation::Health,
+        valence_protocol::{ItemKind, ItemStack, math::Vec3, nbt},
+    };
+    use hyperion_inventory::PlayerInventory;
+
+    use super::{
+        ATTACKER_MEMORY_TICKS, KnockbackConfig, LastAttacker, enchantment_level,
+        knockback_resistance, take_fall_damage,
+    };
+    use crate::module::leap::NoFallDamage;
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn leaping_zombies_take_no_fall_damage() {
+        let world = World::new();
+        let health = |entity| {
+            world
+                .entity_from_id(entity)
+                .get::<&Health>(|health| **health)
+        };
+
+        let falling = world.entity().set(Health::default()).id();
+        let leaping = world
+            .entity()
+            .set(Health::default())
+            .set(NoFallDamage { until: 100 })
+            .id();
+
+        for player in [falling, leaping] {
+            take_fall_damage(world.entity_from_id(player), 6.5);
+        }
+
+        assert_eq!(health(falling), 16.0);
+        assert_eq!(health(leaping), 20.0);
+    }
+
+    #[test]
+    fn deaths_are_credited_to_recent_attackers() {
+        let mut last = LastAttacker::default();
+        assert_eq!(last.get(0), None);
+
+        last.record(Entity::new(1), 10);
+        last.record(Entity::new(2), 20);
+        assert_eq!(last.get(20 + ATTACKER_MEMORY_TICKS), Some(Entity::new(2)));
+        assert_eq!(last.get(21 + ATTACKER_MEMORY_TICKS), None);
+
+        last.clear();
+        assert_eq!(last.get(20), None);
+    }
+
+    #[test]
+    fn hits_knock_away_from_the_attacker() {
+        let config = KnockbackConfig::default();
+        let origin = Vec3::new(0.0, 64.0, 0.0);
+        let target = Vec3::new(3.0, 64.0, 4.0);
+
+        let strength = config.strength(false, 0);
+        let velocity = config.knockback(Vec3::ZERO, origin, target, strength, 0.0);
+        assert_near(velocity, Vec3::new(0.24, 0.4, 0.32));
+
+        // sprinting and Knockback II add up, but the upward velocity stays the same
+        let strength = config.strength(true, 2);
+        assert!((strength - 1.8).abs() < 1e-6);
+        let velocity = config.knockback(Vec3::ZERO, origin, target, strength, 0.0);
+        assert_near(velocity, Vec3::new(1.08, 0.4, 1.44));
+
+        // half of the velocity the player already had is kept
+        let velocity = config.knockback(Vec3::new(1.0, -0.2, 0.0), origin, target, 0.4, 0.0);
+        assert_near(velocity, Vec3::new(0.74, 0.3, 0.32));
+
+        // a hit from straight above has no direction
+        let above = target + Vec3::Y * 2.0;
+        let velocity = config.knockback(Vec3::X, above, target, strength, 0.0);
+        assert_eq!(velocity, Vec3::X);
+    }
+
+    #[test]
+    fn resistance_scales_knockback_down() {
+        let config = KnockbackConfig::default();
+        let origin = Vec3::ZERO;
+        let target = Vec3::X;
+
+        let velocity = config.knockback(Vec3::ZERO, origin, target, 0.4, 0.5);
+        assert_near(velocity, Vec3::new(0.2, 0.2, 0.0));
+
+        let velocity = config.knockback(Vec3::Z, origin, target, 0.4, 1.0);
+        assert_eq!(velocity, Vec3::Z);
+    }
+
+    #[test]
+    fn armor_and_weapons_are_read_from_their_nbt() {
+        let mut inventory = PlayerInventory::default();
+        assert!(knockback_resistance(&inventory).abs() < f32::EPSILON);
+
+        inventory.set_helmet(ItemStack::new(ItemKind::NetheriteHelmet, 1, None));
+        inventory.set_boots(ItemStack::new(ItemKind::NetheriteBoots, 1, None));
+        assert!((knockback_resistance(&inventory) - 0.2).abs() < 1e-6);
+
+        // modifiers replace the resistance of netherite
+        let mut modifier = nbt::Compound::new();
+        modifier.insert("AttributeName", "minecraft:generic.knockback_resistance");
+        modifier.insert("Amount", 0.75_f64);
+
+        let mut tag = nbt::Compound::new();
+        tag.insert("AttributeModifiers", nbt::List::Compound(vec![modifier]));
+        inventory.set_boots(ItemStack::new(ItemKind::NetheriteBoots, 1, Some(tag)));
+        assert!((knockback_resistance(&inventory) - 0.85).abs() < 1e-6);
+
+        inventory.set_chestplate(ItemStack::new(ItemKind::NetheriteChestplate, 1, None));
+        inventory.set_leggings(ItemStack::new(ItemKind::NetheriteLeggings, 1, None));
+        assert!((knockback_resistance(&inventory) - 1.0).abs() < f32::EPSILON);
+
+        let mut enchantment = nbt::Compound::new();
+        enchantment.insert("id", "minecraft:knockback");
+        enchantment.insert("lvl", 2_i16);
+
+        let mut tag = nbt::Compound::new();
+        tag.insert("Enchantments", nbt::List::Compound(vec![enchantment]));
+        let sword = ItemStack::new(ItemKind::IronSword, 1, Some(tag));
+
+        assert_eq!(enchantment_level(&sword, "knockback"), 2);
+        assert_eq!(enchantment_level(&sword, "sharpness"), 0);
+        let plain = ItemStack::new(ItemKind::IronSword, 1, None);
+        assert_eq!(enchantment_level(&plain, "knockback"), 0);
+    }
+}
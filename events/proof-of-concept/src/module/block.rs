@@ -1,7 +1,4 @@
-use std::{
-    borrow::Cow,
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 use flecs_ecs::{
     core::{Entity, EntityViewGet, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl, World},
@@ -15,6 +12,7 @@ use hyperion::{
         Xp,
         blocks::{Blocks, EntityAndSequence},
         event,
+        game_mode::GameMode,
     },
     storage::EventQueue,
     system_registry::SystemId,
@@ -22,7 +20,7 @@ use hyperion::{
         BlockPos, BlockState, Particle, VarInt,
         block::{PropName, PropValue},
         ident,
-        math::{DVec3, IVec3, Vec3},
+        math::{IVec3, Vec3},
         packets::play,
         text::IntoText,
     },
@@ -83,51 +81,46 @@ impl Module for BlockModule {
                             .send(&world)
                             .unwrap();
 
-                        let center_block = position.as_dvec3() + DVec3::splat(0.5);
+                        let center_block = position.as_vec3() + Vec3::splat(0.5);
                         let sound = agnostic::sound(
                             ident!("minecraft:block.stone.break"),
-                            center_block.as_vec3(),
+                            center_block,
                         ).volume(0.35)
                             .pitch(f32::from(stage).mul_add(0.1, 1.0))
                             .build();
 
-                        compose.broadcast(&sound, SystemId(999))
+                        sound.broadcast_local(compose, SystemId(999))
                             .send(&world)
                             .unwrap();
                     }
                     for destroy in pending_air.destroy_at.pop_until(&now) {
                         // Play particle effect for block destruction
-                        let center_block = destroy.position.as_dvec3() + DVec3::splat(0.5);
-
-                        let particle_packet = play::ParticleS2c {
-                            particle: Cow::Owned(Particle::Explosion),
-                            long_distance: false,
-                            position: center_block,
-                            offset: Vec3::default(),
-                            max_speed: 0.0,
-                            count: 0,
-                        };
+                        let center_block = destroy.position.as_vec3() + Vec3::splat(0.5);
 
-                        compose.broadcast(&particle_packet, SystemId(999))
+                        let particles = agnostic::particles(Particle::Explosion, center_block)
+                            .count(0)
+                            .build();
+
+                        particles.broadcast_local(compose, SystemId(999))
                             .send(&world)
                             .unwrap();
 
                         let sound = agnostic::sound(
                             ident!("minecraft:entity.zombie.break_wooden_door"),
-                            center_block.as_vec3(),
+                            center_block,
                         ).volume(1.0)
                             .pitch(0.8)
                             .seed(fastrand::i64(..))
                             .build();
 
-                        compose.broadcast(&sound, SystemId(999))
+                        sound.broadcast_local(compose, SystemId(999))
                             .send(&world)
                             .unwrap();
 
                         destroy.from
                             .entity_view(world)
                             .get::<&mut PlayerInventory>(|inventory| {
-                                let stack = inventory
+                                let mut stack = inventory
                                     .get_hand_slot_mut(inventory::BLOCK_SLOT)
                                     .unwrap();
 
@@ -157,7 +150,14 @@ impl Module for BlockModule {
                         sequence: event.sequence,
                     });
 
-                    if !ore_veins.ores.contains(&event.position) {
+                    let can_break = event
+                        .from
+                        .entity_view(world)
+                        .try_get::<&GameMode>(|mode| mode.can_break_blocks())
+                        .unwrap_or(true);
+
+                    // only ores can be broken, and only in game modes that break blocks
+                    if !can_break || !ore_veins.ores.contains(&event.position) {
                         let current = blocks.get_block(event.position).unwrap();
 
                         // make sure the player knows the block was placed back
@@ -1,16 +1,28 @@
 use flecs_ecs::{
-    core::{EntityViewGet, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl, World, flecs},
+    core::{
+        EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TableIter,
+        TermBuilderImpl, World, flecs,
+    },
     macros::{Component, system},
     prelude::Module,
 };
 use hyperion::{
-    net::NetworkStreamRef,
-    simulation::{Name, Player, Position, event},
+    l10n::with_locale,
+    msg,
+    net::{Compose, NetworkStreamRef},
+    simulation::{Name, Player, event},
     storage::EventQueue,
     system_registry::SystemId,
     valence_protocol::{packets::play, text::IntoText},
 };
-use tracing::info_span;
+use tracing::{info_span, warn};
+
+use crate::{component::team::Team, module::death::Respawning};
+
+const SYSTEM_ID: SystemId = SystemId(8);
+
+/// Starting a message with this sends it to global chat instead of the sender's team.
+pub const GLOBAL_PREFIX: char = '!';
 
 const CHAT_COOLDOWN_SECONDS: i64 = 15; // 15 seconds
 const CHAT_COOLDOWN_TICKS: i64 = CHAT_COOLDOWN_SECONDS * 20; // Convert seconds to ticks
@@ -26,23 +38,19 @@ pub struct ChatModule;
 
 impl Module for ChatModule {
     fn module(world: &World) {
-        let system_id = SystemId(8);
-
         world.component::<ChatCooldown>().meta();
 
         world
             .component::<Player>()
             .add_trait::<(flecs::With, ChatCooldown)>();
 
-        system!("handle_chat_messages", world, &mut EventQueue<event::ChatMessage<'static>>($), &hyperion::net::Compose($))
-            .multi_threaded()
-            .each_iter(move |it: TableIter<'_, false>, _: usize, (event_queue, compose): (&mut EventQueue<event::ChatMessage<'static>>, &hyperion::net::Compose)| {
+        // not multi-threaded as routing a team message queries the teams of every player
+        system!("handle_chat_messages", world, &mut EventQueue<event::ChatMessage<'static>>($), &Compose($))
+            .each_iter(|it: TableIter<'_, false>, _: usize, (event_queue, compose): (&mut EventQueue<event::ChatMessage<'static>>, &Compose)| {
                 let world = it.world();
                 let span = info_span!("handle_chat_messages");
                 let _enter = span.enter();
 
-                let current_tick = compose.global().tick;
-
                 for event::ChatMessage { msg, by } in event_queue.drain() {
                     let by = world.entity_from_id(by);
 
@@ -51,40 +59,213 @@ impl Module for ChatModule {
                         continue;
                     }
 
-                    // Check cooldown
-                    // todo: try_get if entity is dead/not found what will happen?
-                    by.get::<(&Name, &Position, &mut ChatCooldown, &NetworkStreamRef)>(|(name, position, cooldown, io)| {
-                        // Check if player is still on cooldown
-                        if cooldown.expires > current_tick {
-                            let remaining_ticks = cooldown.expires - current_tick;
-                            let remaining_secs = remaining_ticks as f32 / 20.0;
+                    let (channel, msg) = parse_channel(&msg);
+
+                    if msg.is_empty() {
+                        continue;
+                    }
+
+                    send_chat(&world, compose, by, msg, channel);
+                }
+            });
+    }
+}
+
+/// Where a chat message is sent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Everyone on the server.
+    Global,
+    /// Only the sender's team, so zombies can coordinate without giving away hiding spots.
+    Team,
+}
+
+/// A player who may receive a chat message.
+#[derive(Copy, Clone, Debug)]
+pub struct Listener<S> {
+    pub stream: S,
+    pub team: Team,
+    /// Spectators see every channel.
+    pub spectating: bool,
+}
+
+/// Messages starting with [`GLOBAL_PREFIX`] go to global chat, without the prefix. Everything
+/// else goes to the sender's team.
+#[must_use]
+pub fn parse_channel(msg: &str) -> (Channel, &str) {
+    match msg.strip_prefix(GLOBAL_PREFIX) {
+        Some(msg) => (Channel::Global, msg.trim_start()),
+        None => (Channel::Team, msg),
+    }
+}
+
+/// The streams a message from a player on `sender` sent to `channel` is delivered to.
+pub fn recipients<S>(
+    channel: Channel,
+    sender: Team,
+    listeners: impl IntoIterator<Item = Listener<S>>,
+) -> Vec<S> {
+    listeners
+        .into_iter()
+        .filter(|listener| {
+            channel == Channel::Global || listener.spectating || listener.team == sender
+        })
+        .map(|listener| listener.stream)
+        .collect()
+}
 
-                            let cooldown_msg = format!("§cPlease wait {remaining_secs:.2} seconds before sending another message").into_cow_text();
+/// Sends a chat message from `by`, unless they are still on cooldown.
+///
+/// Teams are read when the message is sent, so a player who was just infected already talks to
+/// and hears the zombies.
+pub fn send_chat(
+    world: &World,
+    compose: &Compose,
+    by: EntityView<'_>,
+    msg: &str,
+    channel: Channel,
+) {
+    let current_tick = compose.global().tick;
 
-                            let packet = play::GameMessageS2c {
-                                chat: cooldown_msg,
-                                overlay: false,
-                            };
+    // todo: try_get if entity is dead/not found what will happen?
+    let sent = by.get::<(&Name, &Team, &mut ChatCooldown, &NetworkStreamRef)>(
+        |(name, team, cooldown, io)| {
+            // Check if player is still on cooldown
+            if cooldown.expires > current_tick {
+                let remaining_ticks = cooldown.expires - current_tick;
+                let remaining_secs = remaining_ticks as f32 / 20.0;
 
-                            compose.unicast(&packet, *io, system_id, &world).unwrap();
-                            return;
-                        }
+                let cooldown_msg = msg!(
+                    by,
+                    "chat.cooldown",
+                    seconds = format!("{remaining_secs:.2}")
+                )
+                .into_cow_text();
 
-                        cooldown.expires = current_tick + CHAT_COOLDOWN_TICKS;
+                let packet = play::GameMessageS2c {
+                    chat: cooldown_msg,
+                    overlay: false,
+                };
 
-                        let chat = format!("§8<§b{name}§8>§r {msg}").into_cow_text();
-                        let packet = play::GameMessageS2c {
-                            chat,
-                            overlay: false,
-                        };
+                compose.unicast(&packet, *io, SYSTEM_ID, world).unwrap();
+                return None;
+            }
 
-                        let center = position.to_chunk();
+            cooldown.expires = current_tick + CHAT_COOLDOWN_TICKS;
 
-                        compose.broadcast_local(&packet, center, system_id)
-                            .send(&world)
-                            .unwrap();
-                    });
-                }
+            Some((name.to_string(), *team))
+        },
+    );
+
+    let Some((name, team)) = sent else {
+        return;
+    };
+
+    let mut listeners = Vec::new();
+
+    world
+        .new_query::<(&NetworkStreamRef, &Team)>()
+        .each_entity(|entity, (_, &listener_team)| {
+            listeners.push(Listener {
+                stream: entity.id(),
+                team: listener_team,
+                spectating: listener_team == Team::Spectator || entity.has::<Respawning>(),
             });
+        });
+
+    // each player reads the message with the team names of their locale
+    for recipient in recipients(channel, team, listeners) {
+        let recipient = world.entity_from_id(recipient);
+
+        let chat = with_locale(recipient, |l10n, locale| match channel {
+            Channel::Global => {
+                let prefix = l10n.translate(locale, prefix_key(team), &[]);
+                l10n.translate(locale, "chat.global", &[
+                    ("team", &prefix),
+                    ("player", &name),
+                    ("message", &msg),
+                ])
+            }
+            Channel::Team => {
+                l10n.translate(locale, "chat.team", &[("player", &name), ("message", &msg)])
+            }
+        });
+
+        let packet = play::GameMessageS2c {
+            chat: chat.into_cow_text(),
+            overlay: false,
+        };
+
+        let io = recipient.get::<&NetworkStreamRef>(|&io| io);
+
+        if let Err(e) = compose.unicast(&packet, io, SYSTEM_ID, world) {
+            warn!("failed to send chat message: {e}");
+        }
+    }
+}
+
+const fn prefix_key(team: Team) -> &'static str {
+    match team {
+        Team::Human => "chat.prefix.human",
+        Team::Zombie => "chat.prefix.zombie",
+        Team::Spectator => "chat.prefix.spectator",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Channel, Listener, parse_channel, recipients};
+    use crate::component::team::Team;
+
+    const HUMAN_A: u64 = 1;
+    const HUMAN_B: u64 = 2;
+    const ZOMBIE_A: u64 = 3;
+    const ZOMBIE_B: u64 = 4;
+    const SPECTATOR: u64 = 5;
+
+    fn listeners() -> Vec<Listener<u64>> {
+        let listener = |stream, team, spectating| Listener {
+            stream,
+            team,
+            spectating,
+        };
+
+        vec![
+            listener(HUMAN_A, Team::Human, false),
+            listener(HUMAN_B, Team::Human, false),
+            listener(ZOMBIE_A, Team::Zombie, false),
+            listener(ZOMBIE_B, Team::Zombie, false),
+            listener(SPECTATOR, Team::Zombie, true),
+        ]
+    }
+
+    #[test]
+    fn team_messages_stay_within_the_team() {
+        let from_zombie = recipients(Channel::Team, Team::Zombie, listeners());
+        assert_eq!(from_zombie, [ZOMBIE_A, ZOMBIE_B, SPECTATOR]);
+
+        let from_human = recipients(Channel::Team, Team::Human, listeners());
+        assert_eq!(from_human, [HUMAN_A, HUMAN_B, SPECTATOR]);
+    }
+
+    #[test]
+    fn global_messages_reach_everyone() {
+        let everyone = [HUMAN_A, HUMAN_B, ZOMBIE_A, ZOMBIE_B, SPECTATOR];
+
+        assert_eq!(
+            recipients(Channel::Global, Team::Zombie, listeners()),
+            everyone
+        );
+        assert_eq!(
+            recipients(Channel::Global, Team::Human, listeners()),
+            everyone
+        );
+    }
+
+    #[test]
+    fn prefix_selects_the_channel() {
+        assert_eq!(parse_channel("!hello"), (Channel::Global, "hello"));
+        assert_eq!(parse_channel("! hello"), (Channel::Global, "hello"));
+        assert_eq!(parse_channel("hello"), (Channel::Team, "hello"));
     }
 }
@@ -0,0 +1,405 @@
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, TermBuilderImpl, World,
+        flecs,
+    },
+    macros::{Component, observer},
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{
+        Player, Uuid,
+        handlers::PacketSwitchQuery,
+        menu::{MenuClick, OpenMenu, close_menu, open_menu},
+    },
+    system_registry::SystemId,
+    valence_protocol::{
+        Hand, ItemKind, VarInt, ident,
+        packets::{play, play::entity_attributes_s2c::AttributeProperty},
+    },
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_item::builder::{Color, ItemBuilder};
+use hyperion_utils::EntityExt;
+use tracing::warn;
+
+use crate::module::round::GameState;
+
+const SYSTEM_ID: SystemId = SystemId(13);
+
+/// The hotbar slot holding the class selector while in the lobby.
+pub const SELECTOR_SLOT: u16 = 8;
+
+/// The class a human plays as. Players who never choose play as the default class.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub enum Class {
+    Archer,
+    Tank,
+    #[default]
+    Scout,
+}
+
+/// A passive effect a class has for the whole round.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PassiveEffect {
+    MovementSpeed(f64),
+    MaxHealth(f64),
+    KnockbackResistance(f64),
+}
+
+impl PassiveEffect {
+    /// Every effect a class can have, at the value players have without one.
+    pub const DEFAULTS: [Self; 3] = [
+        Self::MovementSpeed(0.1),
+        Self::MaxHealth(20.0),
+        Self::KnockbackResistance(0.0),
+    ];
+
+    /// Whether both effects change the same attribute.
+    #[must_use]
+    pub fn same_attribute(self, other: Self) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+
+    #[must_use]
+    pub fn property(self) -> AttributeProperty<'static> {
+        let (key, value) = match self {
+            Self::MovementSpeed(value) => (ident!("minecraft:generic.movement_speed"), value),
+            Self::MaxHealth(value) => (ident!("minecraft:generic.max_health"), value),
+            Self::KnockbackResistance(value) => {
+                (ident!("minecraft:generic.knockback_resistance"), value)
+            }
+        };
+
+        AttributeProperty {
+            key: key.into(),
+            value,
+            modifiers: vec![],
+        }
+    }
+}
+
+impl Class {
+    pub const ALL: [Self; 3] = [Self::Archer, Self::Tank, Self::Scout];
+
+    /// The key of the class's name in the locale files.
+    #[must_use]
+    pub const fn name_key(self) -> &'static str {
+        match self {
+            Self::Archer => "class.name.archer",
+            Self::Tank => "class.name.tank",
+            Self::Scout => "class.name.scout",
+        }
+    }
+
+    #[must_use]
+    pub const fn icon(self) -> ItemKind {
+        match self {
+            Self::Archer => ItemKind::Bow,
+            Self::Tank => ItemKind::Shield,
+            Self::Scout => ItemKind::Feather,
+        }
+    }
+
+    #[must_use]
+    pub const fn effects(self) -> &'static [PassiveEffect] {
+        match self {
+            Self::Archer => &[],
+            Self::Tank => &[
+                PassiveEffect::MaxHealth(30.0),
+                PassiveEffect::KnockbackResistance(0.5),
+            ],
+            Self::Scout => &[PassiveEffect::MovementSpeed(0.13)],
+        }
+    }
+
+    /// Every attribute a class can change, at the value it has for this class. Sending all of them
+    /// undoes the effects of whatever class was played before.
+    #[must_use]
+    pub fn attributes(self) -> [PassiveEffect; 3] {
+        PassiveEffect::DEFAULTS.map(|default| {
+            self.effects()
+                .iter()
+                .copied()
+                .find(|effect| effect.same_attribute(default))
+                .unwrap_or(default)
+        })
+    }
+
+    /// Replaces the contents of `inventory` with this class's kit.
+    pub fn apply_kit(self, inventory: &mut PlayerInventory) {
+        inventory.clear();
+
+        match self {
+            Self::Archer => {
+                inventory.set_hotbar(0, ItemBuilder::new(ItemKind::Bow).build());
+                inventory.set_hotbar(1, ItemBuilder::new(ItemKind::WoodenSword).build());
+                inventory.set_hotbar(2, ItemBuilder::new(ItemKind::Arrow).count(32).build());
+                inventory.set_helmet(
+                    ItemBuilder::new(ItemKind::LeatherHelmet)
+                        .color(Color(0, 170, 0))
+                        .build(),
+                );
+            }
+            Self::Tank => {
+                inventory.set_hotbar(0, ItemBuilder::new(ItemKind::StoneSword).build());
+                inventory.set_offhand(ItemBuilder::new(ItemKind::Shield).build());
+                inventory.set_chestplate(ItemBuilder::new(ItemKind::IronChestplate).build());
+                inventory.set_leggings(ItemBuilder::new(ItemKind::IronLeggings).build());
+            }
+            Self::Scout => {
+                inventory.set_hotbar(0, ItemBuilder::new(ItemKind::IronSword).build());
+                inventory.set_boots(
+                    ItemBuilder::new(ItemKind::LeatherBoots)
+                        .color(Color(85, 255, 255))
+                        .build(),
+                );
+            }
+        }
+    }
+}
+
+/// The item handlers used by the class selection.
+#[derive(Component)]
+pub struct ClassHandles {
+    pub selector: Entity,
+}
+
+#[derive(Component)]
+pub struct ClassModule;
+
+impl Module for ClassModule {
+    fn module(world: &World) {
+        world.import::<hyperion_item::ItemModule>();
+
+        world.component::<Class>();
+        world.component::<ClassHandles>();
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Class)>();
+
+        let selector = world
+            .entity()
+            .set(hyperion_item::Handler::new(open_class_menu));
+
+        world.set(ClassHandles {
+            selector: selector.id(),
+        });
+
+        // players joining the lobby can pick a class right away
+        observer!(
+            world,
+            flecs::OnSet,
+            &Uuid,
+            &mut PlayerInventory,
+            &GameState($),
+            &ClassHandles($),
+        )
+        .each_entity(|player, (_, inventory, state, handles)| {
+            if state.is_active() {
+                return;
+            }
+
+            give_selector(player, inventory, handles);
+        });
+    }
+}
+
+/// Puts the class selector in its locked hotbar slot. Clicks in the inventory are never applied,
+/// so it cannot be moved out of it.
+pub fn give_selector(
+    player: EntityView<'_>,
+    inventory: &mut PlayerInventory,
+    handles: &ClassHandles,
+) {
+    let selector = ItemBuilder::new(ItemKind::NetherStar)
+        .name(msg!(player, "class.selector"))
+        .handler(handles.selector)
+        .build();
+
+    inventory.set_hotbar(SELECTOR_SLOT, selector);
+}
+
+/// Gives every player the class selector, e.g. when the lobby opens again.
+pub fn give_selectors(world: &World) {
+    world.get::<&ClassHandles>(|handles| {
+        world
+            .new_query::<&mut PlayerInventory>()
+            .each_entity(|player, inventory| give_selector(player, inventory, handles));
+    });
+}
+
+fn open_class_menu(query: &mut PacketSwitchQuery<'_>, _: &Hand) {
+    let current = query.view.get::<&Class>(|class| *class);
+
+    let items = Class::ALL
+        .into_iter()
+        .map(|class| {
+            let name = msg!(query.view, class.name_key());
+            let builder = ItemBuilder::new(class.icon()).name(format!("§f{name}"));
+
+            if class == current {
+                builder.glowing().build()
+            } else {
+                builder.build()
+            }
+        })
+        .collect();
+
+    open_menu(
+        query.view,
+        &msg!(query.view, "class.menu"),
+        OpenMenu::new(items, on_class_click),
+    );
+}
+
+fn on_class_click(query: &mut PacketSwitchQuery<'_>, click: &MenuClick) {
+    let Some(&class) = Class::ALL.get(click.slot) else {
+        return;
+    };
+
+    let active = query.world.get::<&GameState>(GameState::is_active);
+
+    let msg = if active {
+        msg!(query.view, "class.locked")
+    } else {
+        query.view.set(class);
+        let name = msg!(query.view, class.name_key());
+        msg!(query.view, "class.chosen", class = name)
+    };
+
+    close_menu(query.view);
+
+    let chat = agnostic::chat(msg);
+    if let Err(e) = query
+        .compose
+        .unicast(&chat, query.io_ref, query.system_id, query.world)
+    {
+        warn!("failed to send class selection message: {e}");
+    }
+}
+
+/// Gives a human the kit and passive effects of their class.
+pub fn apply_class(entity: EntityView<'_>, compose: &Compose, world: &World) {
+    let class = entity.get::<(&Class, &mut PlayerInventory)>(|(class, inventory)| {
+        class.apply_kit(inventory);
+        *class
+    });
+
+    send_effects(entity, &class.attributes(), compose, world);
+}
+
+/// Takes away the passive effects of the player's class, e.g. when they stop being a human.
+pub fn clear_class_effects(entity: EntityView<'_>, compose: &Compose, world: &World) {
+    send_effects(entity, &PassiveEffect::DEFAULTS, compose, world);
+}
+
+fn send_effects(
+    entity: EntityView<'_>,
+    effects: &[PassiveEffect],
+    compose: &Compose,
+    world: &World,
+) {
+    let pkt = play::EntityAttributesS2c {
+        entity_id: VarInt(entity.minecraft_id()),
+        properties: effects.iter().map(|effect| effect.property()).collect(),
+    };
+
+    entity.get::<&NetworkStreamRef>(|&io| {
+        if let Err(e) = compose.unicast(&pkt, io, SYSTEM_ID, world) {
+            warn!("failed to send class effects: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::valence_protocol::ItemKind;
+    use hyperion_inventory::PlayerInventory;
+
+    use super::{Class, PassiveEffect};
+
+    fn kit(class: Class) -> PlayerInventory {
+        let mut inventory = PlayerInventory::default();
+        inventory.set_hotbar(
+            5,
+            hyperion_item::builder::ItemBuilder::new(ItemKind::Dirt).build(),
+        );
+        class.apply_kit(&mut inventory);
+        inventory
+    }
+
+    fn hotbar(inventory: &PlayerInventory, slot: u16) -> ItemKind {
+        inventory.get_hand_slot(slot).unwrap().item
+    }
+
+    #[test]
+    fn archer_kit() {
+        let inventory = kit(Class::Archer);
+
+        assert_eq!(hotbar(&inventory, 0), ItemKind::Bow);
+        assert_eq!(hotbar(&inventory, 1), ItemKind::WoodenSword);
+        assert_eq!(hotbar(&inventory, 2), ItemKind::Arrow);
+        assert_eq!(inventory.get_hand_slot(2).unwrap().count, 32);
+        assert_eq!(inventory.get_helmet().item, ItemKind::LeatherHelmet);
+        assert_eq!(hotbar(&inventory, 5), ItemKind::Air);
+        assert!(Class::Archer.effects().is_empty());
+    }
+
+    #[test]
+    fn tank_kit() {
+        let inventory = kit(Class::Tank);
+
+        assert_eq!(hotbar(&inventory, 0), ItemKind::StoneSword);
+        assert_eq!(
+            inventory.get(PlayerInventory::OFFHAND_SLOT).unwrap().item,
+            ItemKind::Shield
+        );
+        assert_eq!(inventory.get_chestplate().item, ItemKind::IronChestplate);
+        assert_eq!(inventory.get_leggings().item, ItemKind::IronLeggings);
+        assert_eq!(hotbar(&inventory, 5), ItemKind::Air);
+        assert_eq!(Class::Tank.effects(), [
+            PassiveEffect::MaxHealth(30.0),
+            PassiveEffect::KnockbackResistance(0.5),
+        ]);
+    }
+
+    #[test]
+    fn scout_kit() {
+        let inventory = kit(Class::Scout);
+
+        assert_eq!(hotbar(&inventory, 0), ItemKind::IronSword);
+        assert_eq!(inventory.get_boots().item, ItemKind::LeatherBoots);
+        assert_eq!(hotbar(&inventory, 5), ItemKind::Air);
+        assert_eq!(Class::Scout.effects(), [PassiveEffect::MovementSpeed(0.13)]);
+
+        let property = PassiveEffect::MovementSpeed(0.13).property();
+        assert_eq!(property.key.as_str(), "minecraft:generic.movement_speed");
+    }
+
+    #[test]
+    fn switching_class_resets_the_effects_of_the_last_one() {
+        assert_eq!(Class::Tank.attributes(), [
+            PassiveEffect::MovementSpeed(0.1),
+            PassiveEffect::MaxHealth(30.0),
+            PassiveEffect::KnockbackResistance(0.5),
+        ]);
+
+        // a tank switching to scout goes back to the normal health and knockback
+        assert_eq!(Class::Scout.attributes(), [
+            PassiveEffect::MovementSpeed(0.13),
+            PassiveEffect::MaxHealth(20.0),
+            PassiveEffect::KnockbackResistance(0.0),
+        ]);
+        assert_eq!(Class::Archer.attributes(), PassiveEffect::DEFAULTS);
+    }
+
+    #[test]
+    fn default_class_is_scout() {
+        assert_eq!(Class::default(), Class::Scout);
+    }
+}
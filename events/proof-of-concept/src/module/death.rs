@@ -0,0 +1,358 @@
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+        WorldProvider,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{
+        FULL_HEALTH, Health, PacketState, Position,
+        game_mode::set_game_mode,
+        spawn::respawn_position,
+        teleport::{teleport, teleport_to_dimension},
+    },
+    system_registry::SystemId,
+    valence_protocol::{GameMode, ident, math::Vec3},
+};
+use tracing::{info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        attack::LastAttacker,
+        infection::{InfectedEvents, infect_by_environment},
+        round::{GameState, Phase, check_win_condition},
+        spawn::SpawnPoints,
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(14);
+
+#[derive(Component, Copy, Clone, Debug)]
+pub struct DeathConfig {
+    /// Players below this height have fallen out of the world.
+    pub void_y: f32,
+    /// How long a zombie spectates before respawning.
+    pub zombie_respawn_ticks: i64,
+}
+
+impl Default for DeathConfig {
+    fn default() -> Self {
+        Self {
+            void_y: -128.0,
+            zombie_respawn_ticks: 20 * 5,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    Void,
+    /// Any damage that is not a zombie hit. Zombie hits infect instead of dealing damage.
+    Damage,
+}
+
+impl DeathCause {
+    /// The message announcing that a human died this way and rose as a zombie.
+    const fn infection_key(self) -> &'static str {
+        match self {
+            Self::Void => "infection.void",
+            Self::Damage => "infection.died",
+        }
+    }
+}
+
+/// Fired when a player dies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub cause: DeathCause,
+    /// Whoever last hurt the player, if they did so shortly before the death.
+    pub attacker: Option<Entity>,
+}
+
+/// Deaths that happened this tick.
+#[derive(Component, Default, Debug)]
+pub struct DeathEvents {
+    events: Vec<DeathEvent>,
+}
+
+/// What happens to a player after they die.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Respawn {
+    /// A human who died during a round comes back as a zombie at the zombie spawn.
+    AsZombie,
+    /// A zombie comes back at the zombie spawn once the respawn delay is over.
+    Delayed,
+    /// Outside of a round, players come back at the lobby spawn on the same team.
+    Lobby,
+}
+
+impl Respawn {
+    #[must_use]
+    pub const fn for_death(phase: Phase, team: Team) -> Self {
+        match (phase, team) {
+            (Phase::Active { .. }, Team::Human) => Self::AsZombie,
+            (Phase::Active { .. }, Team::Zombie) => Self::Delayed,
+            _ => Self::Lobby,
+        }
+    }
+
+    /// The team a player on `team` respawns on.
+    #[must_use]
+    pub const fn team(self, team: Team) -> Team {
+        match self {
+            Self::AsZombie => Team::Zombie,
+            Self::Delayed | Self::Lobby => team,
+        }
+    }
+}
+
+/// Present while a dead zombie spectates, until they respawn at `at`.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct Respawning {
+    pub at: i64,
+}
+
+#[derive(Component)]
+pub struct DeathModule;
+
+impl Module for DeathModule {
+    fn module(world: &World) {
+        world.component::<DeathConfig>();
+        world.component::<DeathEvents>();
+        world.component::<Respawning>();
+
+        world.set(DeathConfig::default());
+        world.set(DeathEvents::default());
+
+        system!(
+            "detect_deaths",
+            world,
+            &Compose($),
+            &DeathConfig($),
+            &mut DeathEvents($),
+            &Health,
+            &Position,
+            &Team,
+            &LastAttacker,
+        )
+        .with_enum(PacketState::Play)
+        .without::<Respawning>()
+        .each_entity(
+            |entity, (compose, config, deaths, health, position, team, last_attacker)| {
+                // spectators fly through the void and cannot be hurt
+                if *team == Team::Spectator {
+                    return;
+                }
+
+                if let Some(cause) = death_cause(health, **position, config.void_y) {
+                    deaths.events.push(DeathEvent {
+                        entity: entity.id(),
+                        cause,
+                        attacker: last_attacker.get(compose.global().tick),
+                    });
+                }
+            },
+        );
+
+        system!(
+            "handle_deaths",
+            world,
+            &Compose($),
+            &DeathConfig($),
+            &SpawnPoints($),
+            &mut DeathEvents($),
+            &mut GameState($),
+            &mut InfectedEvents($),
+        )
+        .each_iter(
+            |it, _, (compose, config, spawns, deaths, state, infected)| {
+                let span = info_span!("handle_deaths");
+                let _enter = span.enter();
+
+                let world = it.world();
+                let tick = compose.global().tick;
+
+                let mut any_infected = false;
+
+                for DeathEvent { entity, cause, .. } in deaths.events.drain(..) {
+                    let entity = world.entity_from_id(entity);
+
+                    if !entity.is_alive() {
+                        continue;
+                    }
+
+                    // players are brought back before the client ever sees them die
+                    entity.get::<&mut Health>(|health| **health = FULL_HEALTH);
+                    entity.get::<&mut LastAttacker>(LastAttacker::clear);
+
+                    let team = entity.get::<&Team>(|team| *team);
+
+                    match Respawn::for_death(state.phase, team) {
+                        Respawn::AsZombie => {
+                            any_infected |= infect_by_environment(
+                                &world,
+                                compose,
+                                entity,
+                                infected,
+                                cause.infection_key(),
+                            );
+                            // infecting sets the respawn point deferred, so it cannot be read back yet
+                            teleport(entity, spawns.zombies, None);
+                        }
+                        Respawn::Delayed => {
+                            entity.set(Respawning {
+                                at: tick + config.zombie_respawn_ticks,
+                            });
+                            teleport(entity, respawn_position(entity), None);
+
+                            set_game_mode(entity, GameMode::Spectator);
+
+                            let io = entity.get::<&NetworkStreamRef>(|&io| io);
+
+                            let seconds = config.zombie_respawn_ticks / 20;
+                            if let Err(e) = show_countdown(&world, compose, entity, io, seconds) {
+                                warn!("failed to show respawn countdown: {e}");
+                            }
+                        }
+                        Respawn::Lobby => teleport(entity, spawns.lobby, None),
+                    }
+                }
+
+                // the last human may have died
+                if any_infected {
+                    check_win_condition(&world, compose, state, None);
+                }
+            },
+        );
+
+        system!(
+            "respawn_countdown",
+            world,
+            &Compose($),
+            &Respawning,
+            &NetworkStreamRef,
+        )
+        .each_entity(|entity, (compose, respawning, &io)| {
+            let world = entity.world();
+            let tick = compose.global().tick;
+
+            if tick >= respawning.at {
+                entity.remove::<Respawning>();
+                entity.get::<&mut Health>(|health| **health = FULL_HEALTH);
+
+                // the respawn packet still says spectator, the game mode is switched right after
+                teleport_to_dimension(
+                    entity,
+                    ident!("minecraft:overworld").into(),
+                    respawn_position(entity),
+                    None,
+                );
+                set_game_mode(entity, GameMode::Survival);
+                return;
+            }
+
+            let remaining = respawning.at - tick;
+
+            // the countdown is only redrawn when the number of seconds changes
+            if remaining % 20 != 0 {
+                return;
+            }
+
+            let seconds = remaining / 20;
+
+            if let Err(e) = show_countdown(&world, compose, entity, io, seconds) {
+                warn!("failed to show respawn countdown: {e}");
+            }
+        });
+    }
+}
+
+/// Why a player has died, or `None` if they are still alive.
+#[must_use]
+pub fn death_cause(health: &Health, position: Vec3, void_y: f32) -> Option<DeathCause> {
+    if position.y < void_y {
+        Some(DeathCause::Void)
+    } else if health.is_dead() {
+        Some(DeathCause::Damage)
+    } else {
+        None
+    }
+}
+
+fn show_countdown(
+    world: &World,
+    compose: &Compose,
+    player: EntityView<'_>,
+    io: NetworkStreamRef,
+    seconds: i64,
+) -> anyhow::Result<()> {
+    agnostic::title(msg!(player, "death.title"))
+        .subtitle(msg!(player, "death.respawning", seconds = seconds))
+        .fade_in(0)
+        .stay(25)
+        .fade_out(5)
+        .unicast(compose, io, SYSTEM_ID, world)
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::Entity;
+    use hyperion::{simulation::Health, valence_protocol::math::Vec3};
+
+    use super::{DeathCause, Respawn, death_cause};
+    use crate::{
+        component::team::Team,
+        module::round::{Phase, Winner, humans_remaining, round_outcome},
+    };
+
+    const VOID_Y: f32 = -128.0;
+
+    #[test]
+    fn last_human_falling_into_the_void_is_a_zombie_win() {
+        let human = Entity::new(1);
+        let zombie = Entity::new(2);
+        let phase = Phase::Active { ends_at: 100 };
+
+        let cause = death_cause(&Health::default(), Vec3::new(0.0, -200.0, 0.0), VOID_Y);
+        assert_eq!(cause, Some(DeathCause::Void));
+
+        let respawn = Respawn::for_death(phase, Team::Human);
+        assert_eq!(respawn, Respawn::AsZombie);
+
+        let teams = [(human, respawn.team(Team::Human)), (zombie, Team::Zombie)];
+        let humans = humans_remaining(teams, None);
+
+        assert_eq!(humans, 0);
+        assert_eq!(round_outcome(phase, humans, 50), Some(Winner::Zombies));
+    }
+
+    #[test]
+    fn zombies_and_lobby_deaths_keep_their_team() {
+        let active = Phase::Active { ends_at: 100 };
+
+        assert_eq!(Respawn::for_death(active, Team::Zombie), Respawn::Delayed);
+        assert_eq!(Respawn::Delayed.team(Team::Zombie), Team::Zombie);
+
+        for phase in [Phase::Lobby, Phase::Ending { until: 100 }] {
+            for team in [Team::Human, Team::Zombie] {
+                let respawn = Respawn::for_death(phase, team);
+
+                assert_eq!(respawn, Respawn::Lobby);
+                assert_eq!(respawn.team(team), team);
+            }
+        }
+    }
+
+    #[test]
+    fn standing_above_the_void_is_alive() {
+        let position = Vec3::new(0.0, 64.0, 0.0);
+
+        assert_eq!(death_cause(&Health::default(), position, VOID_Y), None);
+    }
+}
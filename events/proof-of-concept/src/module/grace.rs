@@ -0,0 +1,274 @@
+//! The grace period at the start of a round, during which zombies cannot attack humans so the
+//! humans have time to spread out.
+//!
+//! Only attacks are held back; falling into the void or other environmental damage still applies.
+
+use flecs_ecs::{
+    core::{
+        Builder, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl,
+        World,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{
+        Compose, NetworkStreamRef, agnostic,
+        packets::{BossBarAction, BossBarS2c},
+    },
+    simulation::Position,
+    system_registry::SystemId,
+    uuid::Uuid,
+    valence_protocol::{
+        ident,
+        packets::play::boss_bar_s2c::{BossBarColor, BossBarDivision, BossBarFlags},
+    },
+};
+use tracing::{info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        messages::announce,
+        round::{GameState, RoundConfig},
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(18);
+
+/// Present while a zombie is shown the grace period boss bar.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct GraceBar;
+
+#[derive(Component)]
+pub struct GraceModule;
+
+impl Module for GraceModule {
+    fn module(world: &World) {
+        world.component::<GraceBar>();
+
+        let bar_id = Uuid::new_v4();
+
+        // zombies who join or are infected during the grace period are picked up here as well
+        let without_bar = world
+            .query::<(&Team, &NetworkStreamRef)>()
+            .without::<GraceBar>()
+            .build();
+
+        let with_bar = world
+            .query::<&NetworkStreamRef>()
+            .with::<GraceBar>()
+            .build();
+
+        system!(
+            "grace_period",
+            world,
+            &Compose($),
+            &GameState($),
+            &RoundConfig($),
+        )
+        .each_iter(move |it, _, (compose, state, config)| {
+            let span = info_span!("grace_period");
+            let _enter = span.enter();
+
+            let world = it.world();
+            let tick = compose.global().tick;
+
+            if !state.in_grace(tick) {
+                with_bar.each_entity(|entity, &io| {
+                    entity.remove::<GraceBar>();
+                    send_bar(&world, compose, io, bar_id, BossBarAction::Remove);
+                });
+
+                if state.is_active() && tick == state.grace_until {
+                    announce_grace_end(&world, compose);
+                }
+
+                return;
+            }
+
+            let progress = grace_progress(state.grace_until - tick, config.grace_ticks);
+
+            without_bar.each_entity(|entity, (team, &io)| {
+                if *team != Team::Zombie {
+                    return;
+                }
+
+                entity.add::<GraceBar>();
+
+                let title = msg!(entity, "grace.bar");
+                let action = BossBarAction::Add {
+                    title: hyperion_text::Text::new(&title),
+                    health: progress,
+                    color: BossBarColor::Yellow,
+                    division: BossBarDivision::NoDivision,
+                    flags: BossBarFlags::default(),
+                };
+
+                send_bar(&world, compose, io, bar_id, action);
+            });
+
+            if tick % 20 != 0 {
+                return;
+            }
+
+            with_bar.each(|&io| {
+                send_bar(
+                    &world,
+                    compose,
+                    io,
+                    bar_id,
+                    BossBarAction::UpdateHealth(progress),
+                );
+            });
+        });
+    }
+}
+
+/// Whether an attack should be cancelled because the grace period is still running.
+#[must_use]
+pub fn grace_blocks_attack(state: &GameState, tick: i64, attacker: Team, victim: Team) -> bool {
+    state.in_grace(tick) && attacker == Team::Zombie && victim == Team::Human
+}
+
+/// Cancels a zombie's attack on a human during the grace period, telling the zombie why.
+///
+/// Returns `true` if the attack was cancelled.
+pub fn cancel_grace_attack(
+    world: &World,
+    compose: &Compose,
+    state: &GameState,
+    attacker: EntityView<'_>,
+    victim: EntityView<'_>,
+) -> bool {
+    let tick = compose.global().tick;
+
+    let attacker_team = attacker.get::<&Team>(|team| *team);
+    let victim_team = victim.get::<&Team>(|team| *team);
+
+    if !grace_blocks_attack(state, tick, attacker_team, victim_team) {
+        return false;
+    }
+
+    let seconds = (state.grace_until - tick).div_ceil(20);
+
+    let pkt = agnostic::action_bar(msg!(attacker, "grace.attack_in", seconds = seconds));
+
+    attacker.get::<&NetworkStreamRef>(|&io| {
+        if let Err(e) = compose.unicast(&pkt, io, SYSTEM_ID, world) {
+            warn!("failed to send grace period message: {e}");
+        }
+    });
+
+    true
+}
+
+/// How full the boss bar is with `remaining` ticks of the grace period left.
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "grace periods are far shorter than where f32 loses precision"
+)]
+fn grace_progress(remaining: i64, total: i64) -> f32 {
+    if total <= 0 {
+        return 0.0;
+    }
+
+    (remaining as f32 / total as f32).clamp(0.0, 1.0)
+}
+
+fn send_bar(
+    world: &World,
+    compose: &Compose,
+    io: NetworkStreamRef,
+    id: Uuid,
+    action: BossBarAction<'_>,
+) {
+    let pkt = BossBarS2c { id, action };
+
+    if let Err(e) = compose.unicast(&pkt, io, SYSTEM_ID, world) {
+        warn!("failed to send grace period bar: {e}");
+    }
+}
+
+fn announce_grace_end(world: &World, compose: &Compose) {
+    announce(world, compose, "grace.over", &[], SYSTEM_ID);
+
+    world
+        .new_query::<(&Position, &NetworkStreamRef)>()
+        .each(|(position, &io)| {
+            let sound = agnostic::sound(ident!("minecraft:entity.wither.spawn"), **position)
+                .volume(0.5)
+                .seed(fastrand::i64(..))
+                .build();
+
+            if let Err(e) = compose.unicast(&sound, io, SYSTEM_ID, world) {
+                warn!("failed to play grace period sound: {e}");
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grace_blocks_attack, grace_progress};
+    use crate::{
+        component::team::Team,
+        module::round::{GameState, Phase},
+    };
+
+    fn state() -> GameState {
+        GameState {
+            round: 1,
+            phase: Phase::Active { ends_at: 6000 },
+            countdown_until: 0,
+            grace_until: 300,
+            overtime_from: None,
+            humans: 0,
+        }
+    }
+
+    #[test]
+    fn attacks_during_grace_are_cancelled() {
+        assert!(grace_blocks_attack(&state(), 10, Team::Zombie, Team::Human));
+    }
+
+    #[test]
+    fn attacks_after_grace_land() {
+        assert!(!grace_blocks_attack(
+            &state(),
+            300,
+            Team::Zombie,
+            Team::Human
+        ));
+        assert!(!grace_blocks_attack(
+            &state(),
+            301,
+            Team::Zombie,
+            Team::Human
+        ));
+    }
+
+    #[test]
+    fn only_zombies_are_held_back() {
+        assert!(!grace_blocks_attack(
+            &state(),
+            10,
+            Team::Human,
+            Team::Zombie
+        ));
+        assert!(!grace_blocks_attack(&state(), 10, Team::Human, Team::Human));
+
+        let lobby = GameState {
+            phase: Phase::Lobby,
+            ..state()
+        };
+        assert!(!grace_blocks_attack(&lobby, 10, Team::Zombie, Team::Human));
+    }
+
+    #[test]
+    fn bar_drains_over_the_grace_period() {
+        assert!((grace_progress(300, 300) - 1.0).abs() < f32::EPSILON);
+        assert!((grace_progress(150, 300) - 0.5).abs() < f32::EPSILON);
+        assert!(grace_progress(0, 300).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,322 @@
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+        flecs,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    l10n::Arg,
+    net::{Compose, agnostic},
+    simulation::{
+        Name, Player, Position,
+        metadata::EntityFlags,
+        scoreboard::{ScoreboardTeam, ScoreboardTeams},
+        spawn::{clear_respawn, set_respawn},
+        time::WorldTime,
+    },
+    system_registry::SystemId,
+    valence_protocol::{
+        ItemKind, ItemStack, ident,
+        packets::play::team_s2c::{CollisionRule, NameTagVisibility, TeamColor},
+    },
+};
+use hyperion_inventory::PlayerInventory;
+use tracing::{debug, info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        attack::KillCount,
+        class::clear_class_effects,
+        leap::{LEAP_SLOT, LeapHandles, leap_item},
+        messages::{FeedEntry, KillFeed, announce},
+        round::humans_left,
+        spawn::SpawnPoints,
+        tracker::{TRACKER_SLOT, tracker_item},
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(10);
+
+const ZOMBIE_TEAM: &str = "zombies";
+
+/// The share of damage zombies take at night, when they are stronger.
+const NIGHT_DAMAGE_TAKEN: f32 = 0.75;
+
+/// What turned a human into a zombie.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InfectedBy {
+    /// Hit by this zombie.
+    Zombie(Entity),
+    /// Died to something other than a zombie, such as the void.
+    Environment,
+}
+
+/// Fired when a human is turned into a zombie.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InfectedEvent {
+    pub victim: Entity,
+    pub by: InfectedBy,
+}
+
+/// How many humans a player has infected in the current round.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct Infections {
+    pub this_round: u32,
+}
+
+/// Infections that happened this tick.
+#[derive(Component, Default, Debug)]
+pub struct InfectedEvents {
+    events: Vec<InfectedEvent>,
+}
+
+impl InfectedEvents {
+    pub fn push(&mut self, event: InfectedEvent) {
+        self.events.push(event);
+    }
+
+    /// The events without consuming them, for systems that run before they are drained.
+    pub fn iter(&self) -> impl Iterator<Item = &InfectedEvent> + '_ {
+        self.events.iter()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = InfectedEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+#[derive(Component)]
+pub struct InfectionModule;
+
+impl Module for InfectionModule {
+    fn module(world: &World) {
+        world.component::<Infections>();
+        world.component::<InfectedEvents>();
+        world.set(InfectedEvents::default());
+
+        // players join and leave this team as they are infected or cured
+        let zombies = ScoreboardTeam::new(ZOMBIE_TEAM)
+            .color(TeamColor::DarkGreen)
+            .name_tags(NameTagVisibility::Never)
+            .collision(CollisionRule::Always);
+
+        world.get::<&mut ScoreboardTeams>(|teams| teams.create(zombies));
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Infections)>();
+
+        // an infection counts as a kill for the zombie
+        system!("count_infections", world, &mut InfectedEvents($)).each_iter(|it, _, infected| {
+            let span = info_span!("count_infections");
+            let _enter = span.enter();
+
+            let world = it.world();
+
+            for InfectedEvent { victim, by } in infected.drain() {
+                debug!("{victim:?} was infected by {by:?}");
+
+                // nobody is credited for environmental deaths
+                let InfectedBy::Zombie(by) = by else {
+                    continue;
+                };
+
+                let by = world.entity_from_id(by);
+
+                if !by.is_alive() {
+                    continue;
+                }
+
+                by.get::<(&mut KillCount, &mut Infections)>(|(kill_count, infections)| {
+                    kill_count.kill_count += 1;
+                    infections.this_round += 1;
+                });
+            }
+        });
+    }
+}
+
+/// How much of the damage dealt to `target` it takes. Zombies shrug off part of it at night.
+pub fn damage_taken_multiplier(world: &World, target: EntityView<'_>) -> f32 {
+    let is_zombie = target
+        .try_get::<&Team>(|team| *team == Team::Zombie)
+        .unwrap_or_default();
+
+    if is_zombie && world.get::<&WorldTime>(WorldTime::is_night) {
+        NIGHT_DAMAGE_TAKEN
+    } else {
+        1.0
+    }
+}
+
+/// Converts `victim` into a zombie if `attacker` is a zombie and `victim` is still human.
+///
+/// Returns `true` if the victim was infected. A victim hit by several zombies in the same tick is
+/// only infected by the first hit, as the later hits already see them as a zombie.
+pub fn try_infect(
+    world: &World,
+    compose: &Compose,
+    attacker: EntityView<'_>,
+    victim: EntityView<'_>,
+    infected: &mut InfectedEvents,
+) -> bool {
+    let attacker_team = attacker.get::<&Team>(|team| *team);
+
+    if attacker_team != Team::Zombie {
+        return false;
+    }
+
+    let Some(victim_name) = make_zombie(world, victim) else {
+        return false;
+    };
+
+    let attacker_name = attacker.get::<&Name>(ToString::to_string);
+
+    infected.push(InfectedEvent {
+        victim: victim.id(),
+        by: InfectedBy::Zombie(attacker.id()),
+    });
+
+    let feed = FeedEntry {
+        key: "infection.feed.by_zombie",
+        victim: victim_name,
+        attacker: attacker_name,
+    };
+
+    if let Err(e) = announce_infection(world, compose, "infection.by_zombie", feed, victim) {
+        warn!("failed to announce infection: {e}");
+    }
+
+    true
+}
+
+/// Converts a human who died to something other than a zombie, e.g. the void. `key` is the
+/// announcement, such as `infection.void` for "Steve fell out of the world and rose as a zombie".
+///
+/// Returns `true` if the victim was a human and has been converted.
+pub fn infect_by_environment(
+    world: &World,
+    compose: &Compose,
+    victim: EntityView<'_>,
+    infected: &mut InfectedEvents,
+    key: &str,
+) -> bool {
+    let Some(victim_name) = make_zombie(world, victim) else {
+        return false;
+    };
+
+    infected.push(InfectedEvent {
+        victim: victim.id(),
+        by: InfectedBy::Environment,
+    });
+
+    let feed = FeedEntry {
+        key: "infection.feed.environment",
+        victim: victim_name,
+        attacker: String::new(),
+    };
+
+    if let Err(e) = announce_infection(world, compose, key, feed, victim) {
+        warn!("failed to announce infection: {e}");
+    }
+
+    true
+}
+
+/// Moves a human to the zombie team and gives them the zombie kit.
+///
+/// Zombies respawn at the zombie spawn from then on. Returns the player's name, or `None` if they
+/// already were a zombie.
+pub fn make_zombie(world: &World, entity: EntityView<'_>) -> Option<String> {
+    let leap = world.get::<&LeapHandles>(|handles| leap_item(entity, handles));
+    let tracker = tracker_item(entity);
+
+    let name = entity.get::<(&mut Team, &mut PlayerInventory, &mut EntityFlags, &Name)>(
+        |(team, inventory, flags, name)| {
+            if *team != Team::Human {
+                return None;
+            }
+
+            *team = Team::Zombie;
+            give_zombie_kit(inventory, leap, tracker);
+
+            // zombies glow in the color of their team
+            *flags |= EntityFlags::GLOWING;
+
+            Some(name.to_string())
+        },
+    )?;
+
+    set_respawn(entity, world.get::<&SpawnPoints>(|spawns| spawns.zombies));
+
+    world.get::<&Compose>(|compose| clear_class_effects(entity, compose, world));
+
+    world.get::<&mut ScoreboardTeams>(|teams| teams.add_member(ZOMBIE_TEAM, name.as_str()));
+
+    Some(name)
+}
+
+/// Moves a zombie back to the human team, taking away the zombie kit.
+pub fn make_human(world: &World, entity: EntityView<'_>) {
+    let name = entity.get::<(&mut Team, &mut PlayerInventory, &mut EntityFlags, &Name)>(
+        |(team, inventory, flags, name)| {
+            if *team != Team::Zombie {
+                return None;
+            }
+
+            *team = Team::Human;
+            inventory.clear();
+            *flags &= !EntityFlags::GLOWING;
+
+            Some(name.to_string())
+        },
+    );
+
+    let Some(name) = name else {
+        return;
+    };
+
+    clear_respawn(entity);
+
+    world.get::<&mut ScoreboardTeams>(|teams| teams.remove_member(&name));
+}
+
+pub fn give_zombie_kit(inventory: &mut PlayerInventory, leap: ItemStack, tracker: ItemStack) {
+    inventory.clear();
+    inventory.set_helmet(ItemStack::new(ItemKind::ZombieHead, 1, None));
+    inventory.set_hotbar(0, ItemStack::new(ItemKind::StoneSword, 1, None));
+    inventory.set_hotbar(LEAP_SLOT, leap);
+    inventory.set_hotbar(TRACKER_SLOT, tracker);
+}
+
+/// Announces an infection with the message for `key`, which can use `{victim}`, `{attacker}` and
+/// `{humans_left}`.
+fn announce_infection(
+    world: &World,
+    compose: &Compose,
+    key: &str,
+    feed: FeedEntry,
+    victim: EntityView<'_>,
+) -> anyhow::Result<()> {
+    let humans_left = humans_left(world);
+    let args: [Arg<'_>; 3] = [
+        ("victim", &feed.victim),
+        ("attacker", &feed.attacker),
+        ("humans_left", &humans_left),
+    ];
+
+    announce(world, compose, key, &args, SYSTEM_ID);
+
+    world.get::<&mut KillFeed>(|kill_feed| kill_feed.push(feed));
+
+    let position = victim.get::<&Position>(|position| **position);
+    let sound = agnostic::sound(ident!("minecraft:entity.zombie.infect"), position)
+        .seed(fastrand::i64(..))
+        .build();
+    compose.broadcast(&sound, SYSTEM_ID).send(world)?;
+
+    Ok(())
+}
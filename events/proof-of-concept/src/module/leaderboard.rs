@@ -0,0 +1,336 @@
+//! The top players by one of their lifetime [`PlayerStats`], read from the [`StatsStore`].
+//!
+//! Reading every stored player can hit the disk, so the store is read on the async runtime and
+//! the sorted result is kept for a while, so that paging through it does not read it again.
+
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use flecs_ecs::{
+    core::{Entity, EntityView, EntityViewGet, World},
+    macros::Component,
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{Compose, NetworkStreamRef},
+    runtime::AsyncRuntime,
+    system_registry::SystemId,
+    valence_protocol::{packets::play, text::IntoText},
+};
+use rustc_hash::FxHashMap;
+use tracing::warn;
+
+use crate::module::player_stats::{PlayerStats, StatsStore, duration};
+
+const SYSTEM_ID: SystemId = SystemId(21);
+
+pub const PAGE_SIZE: usize = 10;
+
+/// How long a sorted leaderboard is shown before the store is read again.
+const CACHE_DURATION: Duration = Duration::from_secs(30);
+
+/// What players are ranked by.
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    Infections,
+    /// Wins as either team.
+    Wins,
+    /// Time spent as a human.
+    Survival,
+}
+
+impl Metric {
+    /// The message naming the metric.
+    const fn title_key(self) -> &'static str {
+        match self {
+            Self::Infections => "leaderboard.metric.infections",
+            Self::Wins => "leaderboard.metric.wins",
+            Self::Survival => "leaderboard.metric.survival",
+        }
+    }
+
+    fn value(self, stats: &PlayerStats) -> u64 {
+        match self {
+            Self::Infections => u64::from(stats.infections),
+            Self::Wins => u64::from(stats.human_wins) + u64::from(stats.zombie_wins),
+            Self::Survival => stats.survival_secs,
+        }
+    }
+
+    fn format(self, value: u64, viewer: EntityView<'_>) -> String {
+        match self {
+            Self::Infections | Self::Wins => value.to_string(),
+            Self::Survival => duration(viewer, value),
+        }
+    }
+}
+
+/// A player's position on a leaderboard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Players with the same value share a rank.
+    pub rank: usize,
+    pub value: u64,
+    pub stats: PlayerStats,
+}
+
+/// One page of a leaderboard.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Page<'a> {
+    /// Starting at 1.
+    pub number: usize,
+    pub total: usize,
+    pub entries: &'a [Entry],
+}
+
+/// Ranks `stats` by `metric`, highest first. Ties are ordered by name so the order does not depend
+/// on how the store happens to be laid out.
+#[must_use]
+pub fn rank(stats: Vec<PlayerStats>, metric: Metric) -> Vec<Entry> {
+    let mut entries: Vec<_> = stats
+        .into_iter()
+        .map(|stats| Entry {
+            rank: 0,
+            value: metric.value(&stats),
+            stats,
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.value
+            .cmp(&a.value)
+            .then_with(|| a.stats.name.cmp(&b.stats.name))
+    });
+
+    let mut previous = None;
+
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.rank = match previous {
+            Some((value, rank)) if value == entry.value => rank,
+            _ => i + 1,
+        };
+        previous = Some((entry.value, entry.rank));
+    }
+
+    entries
+}
+
+/// The page `number` of `entries`, counting from 1. Pages past the last one show the last page.
+#[must_use]
+pub fn page(entries: &[Entry], number: usize) -> Page<'_> {
+    let total = entries.len().div_ceil(PAGE_SIZE).max(1);
+    let number = number.clamp(1, total);
+
+    let start = (number - 1) * PAGE_SIZE;
+    let end = (start + PAGE_SIZE).min(entries.len());
+
+    Page {
+        number,
+        total,
+        entries: &entries[start..end],
+    }
+}
+
+struct CachedBoard {
+    sorted_at: Instant,
+    entries: Vec<Entry>,
+}
+
+/// The most recently sorted leaderboard for each metric.
+#[derive(Component, Default)]
+pub struct LeaderboardCache {
+    boards: FxHashMap<Metric, CachedBoard>,
+}
+
+impl LeaderboardCache {
+    fn fresh(&self, metric: Metric) -> Option<&[Entry]> {
+        self.boards
+            .get(&metric)
+            .filter(|board| board.sorted_at.elapsed() < CACHE_DURATION)
+            .map(|board| board.entries.as_slice())
+    }
+}
+
+#[derive(Component)]
+pub struct LeaderboardModule;
+
+impl Module for LeaderboardModule {
+    fn module(world: &World) {
+        world.component::<LeaderboardCache>();
+        world.set(LeaderboardCache::default());
+    }
+}
+
+/// Sends page `number` of the `metric` leaderboard to `caller`, reading the store first if the
+/// cached leaderboard is too old.
+pub fn show_leaderboard(world: &World, caller: Entity, metric: Metric, number: usize) {
+    let shown = world.get::<&LeaderboardCache>(|cache| {
+        let entries = cache.fresh(metric)?;
+        send_page(world, caller, metric, &page(entries, number));
+        Some(())
+    });
+
+    if shown.is_some() {
+        return;
+    }
+
+    let Some(store) = world.try_get::<&StatsStore>(Clone::clone) else {
+        warn!("cannot show the leaderboard, the player stats could not be opened");
+        return;
+    };
+
+    world.get::<&AsyncRuntime>(|runtime| {
+        let read = async move { (caller, metric, number, store.all()) };
+        runtime.schedule(read, on_stats_read);
+    });
+}
+
+fn on_stats_read(
+    (caller, metric, number, stats): (Entity, Metric, usize, anyhow::Result<Vec<PlayerStats>>),
+    world: &World,
+) {
+    let stats = match stats {
+        Ok(stats) => stats,
+        Err(e) => {
+            warn!("failed to read stats for the leaderboard: {e}");
+            return;
+        }
+    };
+
+    let entries = rank(stats, metric);
+
+    world.get::<&mut LeaderboardCache>(|cache| {
+        let board = CachedBoard {
+            sorted_at: Instant::now(),
+            entries,
+        };
+
+        cache.boards.insert(metric, board);
+
+        let entries = &cache.boards[&metric].entries;
+        send_page(world, caller, metric, &page(entries, number));
+    });
+}
+
+fn send_page(world: &World, caller: Entity, metric: Metric, page: &Page<'_>) {
+    let caller = world.entity_from_id(caller);
+
+    // the caller may have left while the store was being read
+    if !caller.is_alive() {
+        return;
+    }
+
+    let title = msg!(
+        caller,
+        "leaderboard.title",
+        metric = msg!(caller, metric.title_key()),
+        page = page.number,
+        pages = page.total
+    );
+
+    let mut lines = vec![title.into_text()];
+
+    if page.entries.is_empty() {
+        lines.push(msg!(caller, "leaderboard.empty").into_text());
+    }
+
+    for entry in page.entries {
+        let line = msg!(
+            caller,
+            "leaderboard.entry",
+            rank = entry.rank,
+            player = entry.stats.name,
+            value = metric.format(entry.value, caller)
+        );
+
+        lines.push(line.on_hover_show_text(entry.stats.lines(caller).join("\n")));
+    }
+
+    world.get::<&Compose>(|compose| {
+        caller.try_get::<&NetworkStreamRef>(|&io| {
+            for line in lines {
+                let pkt = play::GameMessageS2c {
+                    chat: line.into_cow_text(),
+                    overlay: false,
+                };
+
+                if let Err(e) = compose.unicast(&pkt, io, SYSTEM_ID, world) {
+                    warn!("failed to send leaderboard: {e}");
+                }
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metric, PAGE_SIZE, page, rank};
+    use crate::module::player_stats::PlayerStats;
+
+    fn player(name: &str, infections: u32) -> PlayerStats {
+        PlayerStats {
+            name: name.to_owned(),
+            infections,
+            ..PlayerStats::default()
+        }
+    }
+
+    fn names(entries: &[super::Entry]) -> Vec<(usize, &str)> {
+        entries
+            .iter()
+            .map(|entry| (entry.rank, entry.stats.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn ties_share_a_rank_and_keep_their_order() {
+        let stats = vec![
+            player("Steve", 3),
+            player("Notch", 5),
+            player("Alex", 3),
+            player("Herobrine", 1),
+        ];
+
+        let ranked = rank(stats.clone(), Metric::Infections);
+
+        assert_eq!(names(&ranked), [
+            (1, "Notch"),
+            (2, "Alex"),
+            (2, "Steve"),
+            (4, "Herobrine")
+        ]);
+
+        // the order of the store does not change the order of ties
+        let reversed = rank(stats.into_iter().rev().collect(), Metric::Infections);
+        assert_eq!(ranked, reversed);
+    }
+
+    #[test]
+    fn pages_past_the_end_show_the_last_page() {
+        let stats = (0..25)
+            .map(|i| player(&format!("player{i:02}"), i))
+            .collect();
+        let ranked = rank(stats, Metric::Infections);
+
+        let first = page(&ranked, 1);
+        assert_eq!((first.number, first.total), (1, 3));
+        assert_eq!(first.entries.len(), PAGE_SIZE);
+        assert_eq!(first.entries[0].stats.name, "player24");
+
+        let last = page(&ranked, 99);
+        assert_eq!((last.number, last.total), (3, 3));
+        assert_eq!(last.entries.len(), 5);
+        assert_eq!(last.entries[4].stats.name, "player00");
+
+        assert_eq!(page(&ranked, 0).number, 1);
+    }
+
+    #[test]
+    fn empty_leaderboard_has_one_page() {
+        let empty = page(&[], 3);
+
+        assert_eq!((empty.number, empty.total), (1, 1));
+        assert!(empty.entries.is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+        flecs,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{Compose, agnostic},
+    simulation::{EntityReaction, Player, handlers::PacketSwitchQuery},
+    valence_protocol::{Hand, ItemKind, ItemStack, VarInt, ident, math::Vec3, packets::play},
+};
+use hyperion_item::builder::ItemBuilder;
+use tracing::warn;
+
+use crate::component::team::Team;
+
+/// The hotbar slot holding the leap ability while a zombie.
+pub const LEAP_SLOT: u16 = 1;
+
+/// How long a leaping zombie is protected from fall damage.
+const FALL_IMMUNITY_TICKS: i64 = 60;
+
+#[derive(Component, Copy, Clone, Debug)]
+pub struct LeapConfig {
+    /// The speed, in blocks per tick, a zombie is launched at in their look direction.
+    pub strength: f32,
+    /// Added to the vertical velocity so a leap on flat ground still leaves the ground.
+    pub upward_bias: f32,
+    pub cooldown_ticks: i64,
+}
+
+impl Default for LeapConfig {
+    fn default() -> Self {
+        Self {
+            strength: 1.2,
+            upward_bias: 0.4,
+            cooldown_ticks: 20 * 5,
+        }
+    }
+}
+
+/// The tick a player can next leap on.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct LeapCooldown {
+    ready_at: i64,
+    /// Extra leaps that can be used while on cooldown.
+    charges: u8,
+}
+
+impl LeapCooldown {
+    #[must_use]
+    pub const fn is_ready(&self, tick: i64) -> bool {
+        tick >= self.ready_at
+    }
+
+    pub const fn add_charge(&mut self) {
+        self.charges = self.charges.saturating_add(1);
+    }
+
+    /// Uses up the cooldown, or an extra charge if still on cooldown. Returns `false` if neither
+    /// is available.
+    pub const fn try_leap(&mut self, tick: i64, cooldown_ticks: i64) -> bool {
+        if self.is_ready(tick) {
+            self.ready_at = tick + cooldown_ticks;
+            return true;
+        }
+
+        if self.charges > 0 {
+            self.charges -= 1;
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Present while a player should not take fall damage, e.g. after a leap.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct NoFallDamage {
+    pub until: i64,
+}
+
+/// The item handler for the leap ability.
+#[derive(Component)]
+pub struct LeapHandles {
+    pub leap: Entity,
+}
+
+#[derive(Component)]
+pub struct LeapModule;
+
+impl Module for LeapModule {
+    fn module(world: &World) {
+        world.import::<hyperion_item::ItemModule>();
+
+        world.component::<LeapConfig>();
+        world.component::<LeapCooldown>();
+        world.component::<NoFallDamage>();
+        world.component::<LeapHandles>();
+
+        world.set(LeapConfig::default());
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, LeapCooldown)>();
+
+        let leap = world.entity().set(hyperion_item::Handler::new(on_leap));
+
+        world.set(LeapHandles { leap: leap.id() });
+
+        system!(
+            "expire_no_fall_damage",
+            world,
+            &Compose($),
+            &NoFallDamage,
+        )
+        .multi_threaded()
+        .each_entity(|entity, (compose, no_fall_damage)| {
+            if compose.global().tick >= no_fall_damage.until {
+                entity.remove::<NoFallDamage>();
+            }
+        });
+    }
+}
+
+/// The item that triggers a leap when used.
+pub fn leap_item(player: EntityView<'_>, handles: &LeapHandles) -> ItemStack {
+    ItemBuilder::new(ItemKind::Feather)
+        .name(msg!(player, "leap.item"))
+        .handler(handles.leap)
+        .build()
+}
+
+/// The velocity a leap launches a player with, given where they are looking.
+#[must_use]
+pub fn leap_velocity(yaw: f32, pitch: f32, config: &LeapConfig) -> Vec3 {
+    let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+
+    let direction = Vec3::new(
+        -yaw.sin() * pitch.cos(),
+        -pitch.sin(),
+        yaw.cos() * pitch.cos(),
+    );
+
+    direction * config.strength + Vec3::new(0.0, config.upward_bias, 0.0)
+}
+
+fn on_leap(query: &mut PacketSwitchQuery<'_>, _: &Hand) {
+    let is_zombie = query.view.get::<&Team>(|team| *team == Team::Zombie);
+
+    if !is_zombie {
+        return;
+    }
+
+    let tick = query.compose.global().tick;
+    let config = query.world.get::<&LeapConfig>(|config| *config);
+
+    let ready = query
+        .view
+        .get::<&mut LeapCooldown>(|cooldown| cooldown.try_leap(tick, config.cooldown_ticks));
+
+    if !ready {
+        return;
+    }
+
+    let velocity = leap_velocity(**query.yaw, **query.pitch, &config);
+
+    query
+        .view
+        .get::<&mut EntityReaction>(|reaction| reaction.velocity = velocity);
+
+    query.view.set(NoFallDamage {
+        until: tick + FALL_IMMUNITY_TICKS,
+    });
+
+    if let Err(e) = send_leap_feedback(query, config.cooldown_ticks) {
+        warn!("failed to send leap feedback: {e}");
+    }
+}
+
+fn send_leap_feedback(query: &PacketSwitchQuery<'_>, cooldown_ticks: i64) -> anyhow::Result<()> {
+    let pkt = play::CooldownUpdateS2c {
+        item_id: VarInt(i32::from(ItemKind::Feather.to_raw())),
+        cooldown_ticks: VarInt(i32::try_from(cooldown_ticks)?),
+    };
+
+    query
+        .compose
+        .unicast(&pkt, query.io_ref, query.system_id, query.world)?;
+
+    let sound = agnostic::sound(
+        ident!("minecraft:entity.ender_dragon.flap"),
+        **query.position,
+    )
+    .pitch(1.5)
+    .seed(fastrand::i64(..))
+    .build();
+
+    query
+        .compose
+        .unicast(&sound, query.io_ref, query.system_id, query.world)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::valence_protocol::math::Vec3;
+
+    use super::{LeapConfig, LeapCooldown, leap_velocity};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn config() -> LeapConfig {
+        LeapConfig {
+            strength: 2.0,
+            upward_bias: 0.5,
+            cooldown_ticks: 100,
+        }
+    }
+
+    #[test]
+    fn leap_follows_look_direction() {
+        // yaw 0 is looking towards +z
+        let forward = leap_velocity(0.0, 0.0, &config());
+        assert!(forward.abs_diff_eq(Vec3::new(0.0, 0.5, 2.0), EPSILON));
+
+        // yaw 90 is looking towards -x
+        let west = leap_velocity(90.0, 0.0, &config());
+        assert!(west.abs_diff_eq(Vec3::new(-2.0, 0.5, 0.0), EPSILON));
+
+        // pitch -90 is looking straight up
+        let up = leap_velocity(0.0, -90.0, &config());
+        assert!(up.abs_diff_eq(Vec3::new(0.0, 2.5, 0.0), EPSILON));
+    }
+
+    #[test]
+    fn cooldown_gates_leaps() {
+        let cooldown = LeapCooldown {
+            ready_at: 100,
+            charges: 0,
+        };
+
+        assert!(!cooldown.is_ready(99));
+        assert!(cooldown.is_ready(100));
+        assert!(LeapCooldown::default().is_ready(0));
+    }
+
+    #[test]
+    fn charges_are_used_while_on_cooldown() {
+        let mut cooldown = LeapCooldown::default();
+        cooldown.add_charge();
+
+        assert!(cooldown.try_leap(0, 100));
+        assert!(cooldown.try_leap(10, 100));
+        assert!(!cooldown.try_leap(20, 100));
+        assert!(cooldown.try_leap(100, 100));
+    }
+}
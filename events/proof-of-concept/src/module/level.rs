@@ -3,7 +3,7 @@ use flecs_ecs::{
     macros::Component,
     prelude::Module,
 };
-use hyperion::simulation::Player;
+use hyperion::simulation::{Player, Xp};
 
 #[derive(Component)]
 pub struct LevelModule;
@@ -23,3 +23,8 @@ impl Module for LevelModule {
             .add_trait::<(flecs::With, Level)>(); // todo: how does this even call Default? (IndraDb)
     }
 }
+
+/// Gives a player XP, saturating at the maximum amount.
+pub fn award_xp(xp: &mut Xp, amount: u16) {
+    xp.amount = xp.amount.saturating_add(amount);
+}
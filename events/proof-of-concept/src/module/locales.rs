@@ -0,0 +1,95 @@
+//! The messages players see, in every language the game has been translated to.
+//!
+//! English and German are built in. Operators can add translations or reword messages with files
+//! in `run/locales`, which replace the built-in messages with the same key. The game's messages
+//! are added to those of the server rather than replacing them.
+
+use flecs_ecs::{core::World, macros::Component, prelude::Module};
+use hyperion::l10n::{DEFAULT_LOCALE, Localization};
+use tracing::warn;
+
+/// The built-in catalogs, by locale.
+const BUILT_IN: [(&str, &str); 2] = [
+    ("en_us", include_str!("../../locales/en_us.toml")),
+    ("de_de", include_str!("../../locales/de_de.toml")),
+];
+
+pub(crate) fn built_in() -> anyhow::Result<Localization> {
+    let mut l10n = Localization::new(DEFAULT_LOCALE);
+
+    for (locale, source) in BUILT_IN {
+        l10n.add_toml(locale, source)?;
+    }
+
+    Ok(l10n)
+}
+
+#[derive(Component)]
+pub struct LocalesModule;
+
+impl Module for LocalesModule {
+    fn module(world: &World) {
+        // the built-in messages are checked by the tests below
+        let mut l10n = built_in().unwrap();
+
+        if let Err(e) = l10n.load_dir("run/locales") {
+            warn!("failed to load translations, using the built-in messages: {e:#}");
+            l10n = built_in().unwrap();
+        }
+
+        world.get::<&mut Localization>(|messages| messages.merge(l10n));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::built_in;
+
+    #[test]
+    fn built_in_messages_load() {
+        let l10n = built_in().unwrap();
+
+        assert_eq!(
+            l10n.translate("de_de", "kick.not_online", &[("player", &"Notch")]),
+            "§cNotch ist nicht online"
+        );
+
+        // not translated, so in english
+        assert_eq!(
+            l10n.translate("de_de", "ban.unbanned", &[("player", &"Notch")]),
+            "§7Unbanned Notch"
+        );
+    }
+
+    #[test]
+    fn every_translation_has_an_english_message() {
+        let l10n = built_in().unwrap();
+        let german: toml::Table = toml::from_str(include_str!("../../locales/de_de.toml")).unwrap();
+
+        let mut keys = Vec::new();
+        collect_keys("", &german, &mut keys);
+
+        for key in keys {
+            assert_ne!(
+                l10n.message("en_us", &key),
+                key,
+                "`{key}` is not in en_us.toml"
+            );
+        }
+    }
+
+    fn collect_keys(prefix: &str, table: &toml::Table, keys: &mut Vec<String>) {
+        for (name, value) in table {
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+
+            match value {
+                toml::Value::Table(table) => collect_keys(&key, table, keys),
+                _ => keys.push(key),
+            }
+        }
+    }
+}
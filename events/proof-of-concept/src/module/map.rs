@@ -0,0 +1,667 @@
+//! Map rotation. While a round's result is shown, players vote on the map the next round is played
+//! on, and the winning map is loaded before the lobby opens again.
+
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, TermBuilderImpl, World,
+        WorldProvider, flecs,
+    },
+    macros::{Component, observer},
+    prelude::Module,
+};
+use hyperion::{
+    glam::DVec2,
+    l10n::Arg,
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    runtime::AsyncRuntime,
+    simulation::{
+        Uuid,
+        blocks::Blocks,
+        handlers::PacketSwitchQuery,
+        menu::{MenuClick, OpenMenu, close_menu, open_menu},
+        spawn::{SpawnPoint, set_world_spawn},
+        teleport::{teleport, teleport_to_dimension},
+    },
+    system_registry::SystemId,
+    valence_protocol::{ItemKind, ident, math::Vec3, packets::play},
+};
+use hyperion_item::builder::ItemBuilder;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::module::{messages::announce, round::GameState, spawn::SpawnPoints};
+
+const SYSTEM_ID: SystemId = SystemId(15);
+
+/// How many maps are offered in a vote.
+const CANDIDATES: usize = 3;
+
+/// A map that rounds can be played on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapDefinition {
+    pub name: String,
+    /// Where to download the map's save from, as accepted by [`hyperion_utils::cached_save`].
+    pub save_url: String,
+    pub spawns: SpawnPoints,
+    /// The diameter of the world border, centered on the lobby spawn.
+    pub border_diameter: f64,
+    #[serde(default)]
+    pub overtime: OvertimeBorder,
+}
+
+/// How the world border closes in during overtime.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct OvertimeBorder {
+    /// The point the border shrinks towards.
+    pub center_x: f64,
+    pub center_z: f64,
+    pub final_diameter: f64,
+    pub duration_secs: u32,
+    /// Damage per second for every block a player is outside the border.
+    pub damage_per_block: f32,
+}
+
+impl Default for OvertimeBorder {
+    fn default() -> Self {
+        Self {
+            center_x: 0.0,
+            center_z: 0.0,
+            final_diameter: 32.0,
+            duration_secs: 60,
+            damage_per_block: 1.0,
+        }
+    }
+}
+
+impl OvertimeBorder {
+    #[must_use]
+    pub fn duration_ticks(&self) -> i64 {
+        i64::from(self.duration_secs) * 20
+    }
+
+    /// How far into overtime `elapsed` ticks are, from `0.0` at the start to `1.0` once the
+    /// border stops moving.
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "overtime lasts far fewer ticks than where f64 loses precision"
+    )]
+    fn progress(&self, elapsed: i64) -> f64 {
+        let duration = self.duration_ticks();
+
+        if duration <= 0 {
+            return 1.0;
+        }
+
+        (elapsed as f64 / duration as f64).clamp(0.0, 1.0)
+    }
+
+    #[must_use]
+    pub const fn center(&self) -> DVec2 {
+        DVec2::new(self.center_x, self.center_z)
+    }
+
+    /// The diameter of the border `elapsed` ticks into overtime, shrinking from `start`.
+    #[must_use]
+    pub fn diameter_at(&self, start: f64, elapsed: i64) -> f64 {
+        (self.final_diameter - start).mul_add(self.progress(elapsed), start)
+    }
+
+    /// The center of the border `elapsed` ticks into overtime. It moves from `start` to the
+    /// overtime center while the border shrinks, so the border never jumps.
+    #[must_use]
+    pub fn center_at(&self, start: DVec2, elapsed: i64) -> DVec2 {
+        start.lerp(self.center(), self.progress(elapsed))
+    }
+
+    /// The damage a player at `position` takes per second while the border is `diameter` wide
+    /// around `center`.
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the distance outside the border is at most a few thousand blocks"
+    )]
+    pub fn damage(&self, position: Vec3, center: DVec2, diameter: f64) -> f32 {
+        let dx = (f64::from(position.x) - center.x).abs();
+        let dz = (f64::from(position.z) - center.y).abs();
+
+        // the border is a square
+        let outside = dx.max(dz) - diameter / 2.0;
+
+        if outside <= 0.0 {
+            return 0.0;
+        }
+
+        outside as f32 * self.damage_per_block
+    }
+}
+
+/// The maps in the rotation, read from a `toml` file at startup.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapConfig {
+    pub maps: Vec<MapDefinition>,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            maps: vec![MapDefinition {
+                name: "GenMap".to_owned(),
+                save_url: "https://github.com/andrewgazelka/maps/raw/main/GenMap.tar.gz".to_owned(),
+                spawns: SpawnPoints::default(),
+                border_diameter: 256.0,
+                overtime: OvertimeBorder::default(),
+            }],
+        }
+    }
+}
+
+impl MapConfig {
+    /// Reads the config at `path`, writing the default config there if it does not exist yet.
+    pub fn load<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        if path.as_ref().exists() {
+            let mut contents = String::new();
+            File::open(path)?.read_to_string(&mut contents)?;
+
+            let config = toml::from_str::<Self>(&contents)?;
+            anyhow::ensure!(!config.maps.is_empty(), "the map config has no maps");
+
+            return Ok(config);
+        }
+
+        info!("map config not found, using defaults");
+
+        let config = Self::default();
+
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, toml::to_string(&config)?)?;
+
+        Ok(config)
+    }
+}
+
+/// The maps in the rotation and which of them is being played.
+#[derive(Component, Debug)]
+pub struct MapRegistry {
+    maps: Vec<MapDefinition>,
+    current: usize,
+}
+
+impl MapRegistry {
+    /// # Panics
+    /// If `maps` is empty.
+    #[must_use]
+    pub fn new(maps: Vec<MapDefinition>) -> Self {
+        assert!(!maps.is_empty(), "the map registry needs at least one map");
+        Self { maps, current: 0 }
+    }
+
+    #[must_use]
+    pub fn current(&self) -> &MapDefinition {
+        &self.maps[self.current]
+    }
+
+    /// The map after the current one, used when nobody votes.
+    #[must_use]
+    pub fn next_in_rotation(&self) -> usize {
+        (self.current + 1) % self.maps.len()
+    }
+
+    /// Makes `index` the current map, returning its spawn points.
+    pub fn select(&mut self, index: usize) -> SpawnPoints {
+        self.current = index;
+        self.current().spawns
+    }
+}
+
+/// The vote for the next map. Only running while a round's result is shown.
+#[derive(Component, Debug, Default)]
+pub struct MapVote {
+    /// Indices into the [`MapRegistry`], in the order they are shown in the menu.
+    candidates: Vec<usize>,
+    /// Each player's chosen position in `candidates`.
+    votes: FxHashMap<Entity, usize>,
+}
+
+impl MapVote {
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        !self.candidates.is_empty()
+    }
+
+    /// Records a vote, replacing any earlier vote by the same player.
+    ///
+    /// Returns `false` if there is no such candidate.
+    pub fn cast(&mut self, player: Entity, choice: usize) -> bool {
+        if choice >= self.candidates.len() {
+            return false;
+        }
+
+        self.votes.insert(player, choice);
+        true
+    }
+}
+
+#[derive(Component)]
+pub struct MapModule;
+
+impl Module for MapModule {
+    fn module(world: &World) {
+        world.component::<MapRegistry>();
+        world.component::<MapVote>();
+
+        let config = MapConfig::load("run/maps.toml").unwrap_or_else(|e| {
+            warn!("failed to load map config, using defaults: {e}");
+            MapConfig::default()
+        });
+
+        let registry = MapRegistry::new(config.maps);
+
+        // players join and respawn outside of rounds at the lobby
+        let spawns = registry.current().spawns;
+        world.set(spawns);
+        set_world_spawn(world, SpawnPoint::new(spawns.lobby));
+        world.set(registry);
+        world.set(MapVote::default());
+
+        // players joining see the border of the map being played
+        observer!(world, flecs::OnSet, &Uuid, &NetworkStreamRef, &MapRegistry($)).each_entity(
+            |entity, (_, &io, registry)| {
+                let world = entity.world();
+
+                world.get::<&Compose>(|compose| {
+                    if let Err(e) = send_border(&world, compose, io, registry.current()) {
+                        warn!("failed to send world border: {e}");
+                    }
+                });
+            },
+        );
+    }
+}
+
+/// Chooses up to [`CANDIDATES`] maps to vote on. The current map is only offered if it is the
+/// only one.
+pub fn pick_candidates(maps: usize, current: usize, rng: &mut fastrand::Rng) -> Vec<usize> {
+    let mut candidates: Vec<usize> = (0..maps).filter(|&map| map != current).collect();
+
+    if candidates.is_empty() {
+        return vec![current];
+    }
+
+    rng.shuffle(&mut candidates);
+    candidates.truncate(CANDIDATES);
+    candidates
+}
+
+/// Picks the winning map from the votes, given as positions in `candidates`. Ties are broken
+/// randomly and `fallback` is chosen if nobody voted.
+pub fn tally_votes(
+    candidates: &[usize],
+    votes: impl IntoIterator<Item = usize>,
+    fallback: usize,
+    rng: &mut fastrand::Rng,
+) -> usize {
+    let mut counts = vec![0_usize; candidates.len()];
+
+    for choice in votes {
+        if let Some(count) = counts.get_mut(choice) {
+            *count += 1;
+        }
+    }
+
+    let most = counts.iter().copied().max().unwrap_or(0);
+
+    if most == 0 {
+        return fallback;
+    }
+
+    let tied: Vec<usize> = (0..candidates.len())
+        .filter(|&choice| counts[choice] == most)
+        .collect();
+
+    candidates[tied[rng.usize(..tied.len())]]
+}
+
+/// Starts the vote for the next map and shows it to every player.
+pub fn open_map_vote(world: &World) {
+    let mut rng = fastrand::Rng::new();
+
+    let items = world.get::<&MapRegistry>(|registry| {
+        let candidates = pick_candidates(registry.maps.len(), registry.current, &mut rng);
+
+        let items = candidates
+            .iter()
+            .map(|&map| {
+                ItemBuilder::new(ItemKind::FilledMap)
+                    .name(format!("§f{}", registry.maps[map].name))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        world.get::<&mut MapVote>(|vote| {
+            vote.candidates = candidates;
+            vote.votes.clear();
+        });
+
+        items
+    });
+
+    for player in players(world) {
+        let player = player.entity_view(world);
+
+        open_menu(
+            player,
+            &msg!(player, "map.vote_menu"),
+            OpenMenu::new(items.clone(), on_vote),
+        );
+    }
+}
+
+fn on_vote(query: &mut PacketSwitchQuery<'_>, click: &MenuClick) {
+    let player = query.view.id();
+
+    let voted = query
+        .world
+        .get::<&mut MapVote>(|vote| vote.cast(player, click.slot));
+
+    if !voted {
+        return;
+    }
+
+    close_menu(query.view);
+
+    let chat = agnostic::chat(msg!(query.view, "map.voted"));
+    if let Err(e) = query
+        .compose
+        .unicast(&chat, query.io_ref, query.system_id, query.world)
+    {
+        warn!("failed to confirm map vote: {e}");
+    }
+}
+
+/// Ends the map vote, moving everyone to the winning map. Does nothing if no vote is running.
+pub fn finish_map_vote(world: &World) {
+    let mut rng = fastrand::Rng::new();
+
+    let winner = world.get::<&mut MapVote>(|vote| {
+        if !vote.is_open() {
+            return None;
+        }
+
+        let fallback = world.get::<&MapRegistry>(MapRegistry::next_in_rotation);
+        let votes = vote.votes.drain().map(|(_, choice)| choice);
+        let winner = tally_votes(&vote.candidates, votes, fallback, &mut rng);
+
+        vote.candidates.clear();
+
+        Some(winner)
+    });
+
+    let Some(winner) = winner else {
+        return;
+    };
+
+    for player in players(world) {
+        close_menu(player.entity_view(world));
+    }
+
+    change_map(world, winner);
+}
+
+fn change_map(world: &World, index: usize) {
+    let (changed, name, spawns) = world.get::<&mut MapRegistry>(|registry| {
+        let changed = registry.current != index;
+        let spawns = registry.select(index);
+        (changed, registry.current().name.clone(), spawns)
+    });
+
+    world.set(spawns);
+    set_world_spawn(world, SpawnPoint::new(spawns.lobby));
+
+    world.get::<&Compose>(|compose| {
+        let args: [Arg<'_>; 1] = [("map", &name)];
+        announce(world, compose, "map.next", &args, SYSTEM_ID);
+    });
+
+    if changed {
+        load_current_map(world);
+    } else {
+        for player in players(world) {
+            teleport(player.entity_view(world), spawns.lobby, None);
+        }
+    }
+}
+
+/// Downloads the current map if needed and replaces the world with it. Players are moved to its
+/// lobby once it has loaded.
+pub fn load_current_map(world: &World) {
+    let url = world.get::<&MapRegistry>(|registry| registry.current().save_url.clone());
+
+    world.get::<&AsyncRuntime>(|runtime| {
+        let save = hyperion_utils::cached_save(world, url);
+
+        runtime.schedule(save, on_map_downloaded);
+    });
+}
+
+fn on_map_downloaded(save: anyhow::Result<PathBuf>, world: &World) {
+    let blocks = save.and_then(|save| Blocks::new(world, &save));
+
+    let blocks = match blocks {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            warn!("failed to load map: {e}");
+            return;
+        }
+    };
+
+    world.set(blocks);
+
+    for player in players(world) {
+        if let Err(e) = move_to_new_map(player.entity_view(world)) {
+            warn!("failed to move player to the new map: {e}");
+        }
+    }
+}
+
+/// Makes a player's client drop the old map and receive the new one, and moves them to the lobby.
+fn move_to_new_map(entity: EntityView<'_>) -> anyhow::Result<()> {
+    let world = entity.world();
+
+    let lobby = world.get::<&SpawnPoints>(|spawns| spawns.lobby);
+
+    // respawning unloads every chunk the client has, so the new map is sent around the lobby
+    teleport_to_dimension(entity, ident!("minecraft:overworld").into(), lobby, None);
+
+    world.get::<&Compose>(|compose| {
+        world.get::<&MapRegistry>(|registry| {
+            entity.get::<&NetworkStreamRef>(|&io| {
+                send_border(&world, compose, io, registry.current())
+            })
+        })
+    })
+}
+
+fn send_border(
+    world: &World,
+    compose: &Compose,
+    io: NetworkStreamRef,
+    map: &MapDefinition,
+) -> anyhow::Result<()> {
+    // players joining during overtime see the border where it has closed in to
+    let overtime_from = world.get::<&GameState>(|state| state.overtime_from);
+
+    let border = match overtime_from {
+        Some(from) => overtime_border(map, compose.global().tick - from),
+        None => map_border(map),
+    };
+
+    compose.unicast(&border, io, SYSTEM_ID, world)?;
+
+    Ok(())
+}
+
+/// Where the border of `map` is centered outside of overtime.
+#[must_use]
+pub fn map_center(map: &MapDefinition) -> DVec2 {
+    let center = map.spawns.lobby;
+    DVec2::new(f64::from(center.x), f64::from(center.z))
+}
+
+/// The border of `map` outside of overtime.
+#[must_use]
+pub fn map_border(map: &MapDefinition) -> play::WorldBorderInitializeS2c {
+    let center = map_center(map);
+
+    border(center, map.border_diameter, map.border_diameter, 0)
+}
+
+/// The border of `map` `elapsed` ticks into overtime, still closing in on the overtime center.
+#[must_use]
+pub fn overtime_border(map: &MapDefinition, elapsed: i64) -> play::WorldBorderInitializeS2c {
+    let overtime = &map.overtime;
+    let remaining = (overtime.duration_ticks() - elapsed).max(0);
+
+    border(
+        overtime.center_at(map_center(map), elapsed),
+        overtime.diameter_at(map.border_diameter, elapsed),
+        overtime.final_diameter,
+        remaining * 50,
+    )
+}
+
+fn border(
+    center: DVec2,
+    old_diameter: f64,
+    new_diameter: f64,
+    duration_millis: i64,
+) -> play::WorldBorderInitializeS2c {
+    play::WorldBorderInitializeS2c {
+        x: center.x,
+        z: center.y,
+        old_diameter,
+        new_diameter,
+        duration_millis: duration_millis.into(),
+        portal_teleport_boundary: 29_999_984.into(),
+        warning_blocks: 5.into(),
+        warning_time: 15.into(),
+    }
+}
+
+fn players(world: &World) -> Vec<Entity> {
+    let mut players = Vec::new();
+
+    world
+        .new_query::<&NetworkStreamRef>()
+        .each_entity(|entity, _| players.push(entity.id()));
+
+    players
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use flecs_ecs::core::Entity;
+    use hyperion::valence_protocol::math::Vec3;
+
+    use super::{
+        MapDefinition, MapRegistry, MapVote, OvertimeBorder, pick_candidates, tally_votes,
+    };
+    use crate::module::spawn::SpawnPoints;
+
+    fn map(name: &str, x: f32) -> MapDefinition {
+        MapDefinition {
+            name: name.to_owned(),
+            save_url: format!("https://example.com/{name}.tar.gz"),
+            spawns: SpawnPoints {
+                lobby: Vec3::new(x, 64.0, 0.0),
+                zombies: Vec3::new(x, 64.0, 50.0),
+            },
+            border_diameter: 200.0,
+            overtime: OvertimeBorder::default(),
+        }
+    }
+
+    #[test]
+    fn most_votes_wins() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let candidates = [4, 2, 7];
+
+        assert_eq!(tally_votes(&candidates, [1, 1, 0, 2, 1], 0, &mut rng), 2);
+        // votes for missing candidates are ignored
+        assert_eq!(tally_votes(&candidates, [0, 9, 9], 0, &mut rng), 4);
+    }
+
+    #[test]
+    fn ties_break_randomly() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let candidates = [4, 2, 7];
+
+        let winners: HashSet<_> = (0..100)
+            .map(|_| tally_votes(&candidates, [0, 2], 0, &mut rng))
+            .collect();
+
+        assert_eq!(winners, HashSet::from([4, 7]));
+    }
+
+    #[test]
+    fn no_votes_follows_the_rotation() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut registry = MapRegistry::new(vec![map("a", 0.0), map("b", 1.0), map("c", 2.0)]);
+
+        assert_eq!(registry.next_in_rotation(), 1);
+        registry.select(2);
+        assert_eq!(registry.next_in_rotation(), 0);
+
+        let fallback = registry.next_in_rotation();
+        assert_eq!(tally_votes(&[1, 0], [], fallback, &mut rng), 0);
+    }
+
+    #[test]
+    fn chosen_map_provides_the_spawn_points() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut registry = MapRegistry::new(vec![map("a", 0.0), map("b", 100.0)]);
+
+        let candidates = pick_candidates(2, 0, &mut rng);
+        assert_eq!(candidates, [1]);
+
+        let mut vote = MapVote {
+            candidates,
+            ..MapVote::default()
+        };
+        assert!(vote.cast(Entity::new(1), 0));
+        assert!(!vote.cast(Entity::new(2), 3));
+
+        let winner = tally_votes(&vote.candidates, vote.votes.values().copied(), 0, &mut rng);
+        let spawns = registry.select(winner);
+
+        assert_eq!(registry.current().name, "b");
+        assert_eq!(spawns.lobby, Vec3::new(100.0, 64.0, 0.0));
+        assert_eq!(spawns.zombies, Vec3::new(100.0, 64.0, 50.0));
+    }
+
+    #[test]
+    fn a_single_map_is_always_offered() {
+        let mut rng = fastrand::Rng::with_seed(1);
+
+        assert_eq!(pick_candidates(1, 0, &mut rng), [0]);
+        assert_eq!(pick_candidates(10, 3, &mut rng).len(), 3);
+        assert!(!pick_candidates(10, 3, &mut rng).contains(&3));
+    }
+}
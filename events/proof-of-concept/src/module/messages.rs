@@ -0,0 +1,151 @@
+//! Announcements to every player and the kill feed, each read in the locale of the player.
+//!
+//! The messages are in the locale files, see [`super::locales`], where operators can reword them.
+
+use flecs_ecs::{
+    core::{EntityView, QueryBuilderImpl, TermBuilderImpl, World},
+    macros::Component,
+    prelude::Module,
+};
+use hyperion::{
+    l10n::{Arg, Localization},
+    net::{Compose, NetworkStreamRef, agnostic::Title},
+    simulation::PacketState,
+    system_registry::SystemId,
+};
+use tracing::warn;
+
+/// How many events the kill feed shows.
+pub const KILL_FEED_LEN: usize = 5;
+
+/// Runs `f` for every player who is playing.
+fn each_player(world: &World, mut f: impl FnMut(EntityView<'_>, NetworkStreamRef)) {
+    world
+        .query::<&NetworkStreamRef>()
+        .with_enum(PacketState::Play)
+        .build()
+        .each_entity(|player, &io| f(player, io));
+}
+
+/// Sends every player the message for `key` in their locale.
+pub fn announce(
+    world: &World,
+    compose: &Compose,
+    key: &str,
+    args: &[Arg<'_>],
+    system_id: SystemId,
+) {
+    each_player(world, |player, _| {
+        if let Err(e) = compose.chat_l10n(player, key, args, system_id) {
+            warn!("failed to announce `{key}`: {e}");
+        }
+    });
+}
+
+/// Shows every player the title `title` makes for them, such as with text in their locale.
+pub fn announce_title(
+    world: &World,
+    compose: &Compose,
+    system_id: SystemId,
+    title: impl Fn(EntityView<'_>) -> Title,
+) {
+    each_player(world, |player, io| {
+        if let Err(e) = title(player).unicast(compose, io, system_id, world) {
+            warn!("failed to show a title: {e}");
+        }
+    });
+}
+
+/// An event in the kill feed. It is kept untranslated, as each player reads it in their locale.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// The message, such as `infection.feed.by_zombie`.
+    pub key: &'static str,
+    pub victim: String,
+    /// The zombie who infected the victim, or empty if they were not infected by a zombie.
+    pub attacker: String,
+}
+
+impl FeedEntry {
+    #[must_use]
+    pub fn render(&self, l10n: &Localization, locale: &str) -> String {
+        l10n.translate(locale, self.key, &[
+            ("victim", &self.victim),
+            ("attacker", &self.attacker),
+        ])
+    }
+}
+
+/// The most recent infections, newest first.
+#[derive(Component, Debug, Default)]
+pub struct KillFeed {
+    entries: Vec<FeedEntry>,
+}
+
+impl KillFeed {
+    pub fn push(&mut self, entry: FeedEntry) {
+        self.entries.insert(0, entry);
+        self.entries.truncate(KILL_FEED_LEN);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[FeedEntry] {
+        &self.entries
+    }
+}
+
+#[derive(Component)]
+pub struct MessagesModule;
+
+impl Module for MessagesModule {
+    fn module(world: &World) {
+        world.component::<KillFeed>();
+        world.set(KillFeed::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedEntry, KILL_FEED_LEN, KillFeed};
+    use crate::module::locales::built_in;
+
+    fn entry(victim: &str) -> FeedEntry {
+        FeedEntry {
+            key: "infection.feed.by_zombie",
+            victim: victim.to_owned(),
+            attacker: "Steve".to_owned(),
+        }
+    }
+
+    #[test]
+    fn feed_entries_are_read_in_each_locale() {
+        let l10n = built_in().unwrap();
+
+        assert_eq!(entry("Alex").render(&l10n, "en_us"), "§2Steve §7» §aAlex");
+
+        let environment = FeedEntry {
+            key: "infection.feed.environment",
+            victim: "Alex".to_owned(),
+            attacker: String::new(),
+        };
+        assert_eq!(environment.render(&l10n, "de_de"), "§8☠ §aAlex");
+    }
+
+    #[test]
+    fn kill_feed_keeps_the_latest_events() {
+        let mut feed = KillFeed::default();
+
+        for i in 0..7 {
+            feed.push(entry(&format!("player{i}")));
+        }
+
+        let entries = feed.entries();
+        assert_eq!(entries.len(), KILL_FEED_LEN);
+        assert_eq!(entries[0].victim, "player6");
+        assert_eq!(entries[4].victim, "player2");
+    }
+}
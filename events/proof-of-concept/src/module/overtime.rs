@@ -0,0 +1,302 @@
+//! Overtime at the end of a round the humans are about to survive. The world border closes in on
+//! the map's overtime center, and anyone left outside of it takes damage, so humans cannot stay
+//! hidden until the timer runs out.
+//!
+//! Players who join during overtime are sent the border as it is at that point by the map module,
+//! and see the boss bar since it is shown to everyone.
+
+use flecs_ecs::{
+    core::{Entity, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World},
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    l10n::translate_default,
+    msg,
+    net::{Compose, agnostic},
+    simulation::{
+        Health, Position,
+        boss_bar::{BossBar, spawn_boss_bar},
+    },
+    system_registry::SystemId,
+    valence_protocol::packets::play::{self, boss_bar_s2c::BossBarColor},
+};
+use tracing::{info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        map::{MapDefinition, MapRegistry, map_border, map_center, overtime_border},
+        messages::announce_title,
+        round::{GameState, Phase},
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(20);
+
+/// The boss bar counting down overtime, which is hidden outside of overtime.
+#[derive(Component, Copy, Clone, Debug)]
+struct OvertimeBar(Entity);
+
+#[derive(Component)]
+pub struct OvertimeModule;
+
+impl Module for OvertimeModule {
+    fn module(world: &World) {
+        world.component::<OvertimeBar>();
+
+        // titled when overtime starts, once the messages are loaded
+        let bar = spawn_boss_bar(world, BossBar::new("").color(BossBarColor::Red));
+        world.set(OvertimeBar(bar));
+
+        let players = world.new_query::<(&Team, &Position, &mut Health)>();
+
+        system!(
+            "overtime",
+            world,
+            &Compose($),
+            &mut GameState($),
+            &MapRegistry($),
+        )
+        .each_iter(move |it, _, (compose, state, registry)| {
+            let span = info_span!("overtime");
+            let _enter = span.enter();
+
+            let world = it.world();
+            let tick = compose.global().tick;
+
+            let Phase::Active { ends_at } = state.phase else {
+                return;
+            };
+
+            let map = registry.current();
+            let overtime = &map.overtime;
+
+            let Some(from) = state.overtime_from else {
+                if overtime_starts(ends_at, overtime.duration_ticks(), tick) {
+                    state.overtime_from = Some(tick);
+
+                    if let Err(e) = start_overtime(&world, compose, map) {
+                        warn!("failed to start overtime: {e}");
+                    }
+                }
+                return;
+            };
+
+            let elapsed = tick - from;
+            let center = overtime.center_at(map_center(map), elapsed);
+
+            // clients only interpolate the diameter, so the center is moved a little every tick
+            if elapsed <= overtime.duration_ticks() {
+                let pkt = play::WorldBorderCenterChangedS2c {
+                    x_pos: center.x,
+                    z_pos: center.y,
+                };
+
+                if let Err(e) = compose.broadcast(&pkt, SYSTEM_ID).send(&world) {
+                    warn!("failed to move world border: {e}");
+                }
+            }
+
+            // border damage is dealt once a second, like vanilla
+            if elapsed % 20 != 0 {
+                return;
+            }
+
+            let diameter = overtime.diameter_at(map.border_diameter, elapsed);
+
+            players.each(|(team, position, health)| {
+                if *team != Team::Human {
+                    return;
+                }
+
+                let damage = overtime.damage(**position, center, diameter);
+
+                if damage > 0.0 {
+                    health.damage(damage);
+                }
+            });
+
+            let progress = 1.0 - overtime_progress(elapsed, overtime.duration_ticks());
+            overtime_bar(&world, |bar| bar.set_progress(progress));
+        });
+    }
+}
+
+/// Whether a round ending at `ends_at` goes into overtime on `tick`. Overtime takes up the last
+/// `duration` ticks of the round, so the border is fully closed when the timer runs out.
+#[must_use]
+pub const fn overtime_starts(ends_at: i64, duration: i64, tick: i64) -> bool {
+    ends_at - tick <= duration
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "overtime lasts far fewer ticks than where f32 loses precision"
+)]
+fn overtime_progress(elapsed: i64, duration: i64) -> f32 {
+    if duration <= 0 {
+        return 1.0;
+    }
+
+    (elapsed as f32 / duration as f32).clamp(0.0, 1.0)
+}
+
+fn start_overtime(world: &World, compose: &Compose, map: &MapDefinition) -> anyhow::Result<()> {
+    compose
+        .broadcast(&overtime_border(map, 0), SYSTEM_ID)
+        .send(world)?;
+
+    announce_title(world, compose, SYSTEM_ID, |player| {
+        agnostic::title(msg!(player, "overtime.title"))
+            .subtitle(msg!(player, "overtime.subtitle"))
+            .fade_in(10)
+            .stay(50)
+            .fade_out(10)
+    });
+
+    // everyone shares the bar, so it is in the default locale
+    let title = translate_default(world, "overtime.boss_bar", &[]);
+
+    overtime_bar(world, |bar| {
+        bar.set_title(title);
+        bar.set_progress(1.0);
+        bar.show_all();
+    });
+
+    Ok(())
+}
+
+/// Stops overtime, putting the border back to the size of the map. Called when a round that went
+/// into overtime ends, including when the last human is infected while the border is shrinking.
+pub fn end_overtime(world: &World, compose: &Compose) {
+    let border = world.get::<&MapRegistry>(|registry| map_border(registry.current()));
+
+    if let Err(e) = compose.broadcast(&border, SYSTEM_ID).send(world) {
+        warn!("failed to reset world border: {e}");
+    }
+
+    overtime_bar(world, BossBar::hide);
+}
+
+fn overtime_bar(world: &World, f: impl FnOnce(&mut BossBar)) {
+    let bar = world.get::<&OvertimeBar>(|bar| bar.0);
+    bar.entity_view(world).get::<&mut BossBar>(f);
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::{glam::DVec2, valence_protocol::math::Vec3};
+
+    use super::overtime_starts;
+    use crate::module::{
+        map::{MapDefinition, OvertimeBorder, map_border, map_center, overtime_border},
+        spawn::SpawnPoints,
+    };
+
+    const ROUND_ENDS_AT: i64 = 6000;
+
+    fn map() -> MapDefinition {
+        MapDefinition {
+            name: "Test".to_owned(),
+            save_url: "https://example.com/Test.tar.gz".to_owned(),
+            spawns: SpawnPoints {
+                lobby: Vec3::new(0.0, 64.0, 0.0),
+                zombies: Vec3::new(50.0, 64.0, 50.0),
+            },
+            border_diameter: 200.0,
+            overtime: OvertimeBorder {
+                center_x: 10.0,
+                center_z: -10.0,
+                final_diameter: 32.0,
+                duration_secs: 60,
+                damage_per_block: 0.5,
+            },
+        }
+    }
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn timer_runs_into_overtime_and_resets_at_round_end() {
+        let map = map();
+        let duration = map.overtime.duration_ticks();
+
+        assert_eq!(duration, 1200);
+        assert!(!overtime_starts(ROUND_ENDS_AT, duration, 4799));
+        assert!(overtime_starts(ROUND_ENDS_AT, duration, 4800));
+
+        // the border starts where it was outside of overtime
+        let shrink = overtime_border(&map, 0);
+        assert!(approx(shrink.x, 0.0));
+        assert!(approx(shrink.z, 0.0));
+        assert!(approx(shrink.old_diameter, 200.0));
+        assert!(approx(shrink.new_diameter, 32.0));
+        assert_eq!(shrink.duration_millis.0, 60_000);
+
+        let reset = map_border(&map);
+        assert!(approx(reset.x, 0.0));
+        assert!(approx(reset.z, 0.0));
+        assert!(approx(reset.old_diameter, 200.0));
+        assert!(approx(reset.new_diameter, 200.0));
+        assert_eq!(reset.duration_millis.0, 0);
+    }
+
+    #[test]
+    fn players_joining_during_overtime_see_the_border_partway_in() {
+        let map = map();
+
+        let joined = overtime_border(&map, 600);
+        assert!(approx(joined.x, 5.0));
+        assert!(approx(joined.z, -5.0));
+        assert!(approx(joined.old_diameter, 116.0));
+        assert!(approx(joined.new_diameter, 32.0));
+        assert_eq!(joined.duration_millis.0, 30_000);
+
+        let late = overtime_border(&map, 5000);
+        assert!(approx(late.x, 10.0));
+        assert!(approx(late.old_diameter, 32.0));
+        assert_eq!(late.duration_millis.0, 0);
+    }
+
+    #[test]
+    fn center_moves_to_the_overtime_center() {
+        let map = map();
+        let overtime = map.overtime;
+        let start = map_center(&map);
+
+        assert_eq!(overtime.center_at(start, 0), start);
+        assert_eq!(overtime.center_at(start, 300), DVec2::new(2.5, -2.5));
+        assert_eq!(overtime.center_at(start, 1200), DVec2::new(10.0, -10.0));
+        assert_eq!(overtime.center_at(start, 5000), DVec2::new(10.0, -10.0));
+    }
+
+    #[test]
+    fn border_closes_in_over_the_duration() {
+        let overtime = map().overtime;
+
+        assert!(approx(overtime.diameter_at(200.0, 0), 200.0));
+        assert!(approx(overtime.diameter_at(200.0, 600), 116.0));
+        assert!(approx(overtime.diameter_at(200.0, 1200), 32.0));
+        assert!(approx(overtime.diameter_at(200.0, 5000), 32.0));
+    }
+
+    #[test]
+    fn only_players_outside_the_border_take_damage() {
+        let overtime = map().overtime;
+
+        let center = overtime.center();
+
+        let inside = Vec3::new(20.0, 64.0, -10.0);
+        assert!(overtime.damage(inside, center, 32.0).abs() < f32::EPSILON);
+
+        // 36 blocks from the center along x is 20 blocks outside a border 32 wide
+        let outside = Vec3::new(46.0, 64.0, -10.0);
+        assert!((overtime.damage(outside, center, 32.0) - 10.0).abs() < f32::EPSILON);
+
+        // while the center is still moving, damage is measured from where it is
+        assert!(overtime.damage(inside, DVec2::ZERO, 32.0).abs() > f32::EPSILON);
+    }
+}
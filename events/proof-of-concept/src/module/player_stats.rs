@@ -0,0 +1,460 @@
+//! Lifetime statistics for each player, stored in the [`LocalDb`] so they survive restarts.
+//!
+//! The stats of online players live on their [`PlayerStats`] component and always hold the full
+//! totals, which are written back when they leave and whenever a round ends. Writes replace the
+//! stored record rather than adding to it, so a player leaving and rejoining mid-round is never
+//! counted twice.
+
+use anyhow::Context;
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl,
+        World, flecs,
+    },
+    macros::{Component, observer, system},
+    prelude::Module,
+};
+use heed::{Database, byteorder::NativeEndian, types};
+use hyperion::{
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{IgnMap, Name, Uuid},
+    storage::LocalDb,
+    system_registry::SystemId,
+    uuid,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        infection::{InfectedBy, InfectedEvent, InfectedEvents},
+        round::{GameState, RoundEndEvents, Winner},
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(16);
+
+#[derive(
+    Component,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq
+)]
+pub struct PlayerStats {
+    /// The name the player last played as, so stats can be looked up while they are offline.
+    pub name: String,
+    pub rounds_played: u32,
+    /// Humans this player infected as a zombie.
+    pub infections: u32,
+    pub times_infected: u32,
+    pub human_wins: u32,
+    pub zombie_wins: u32,
+    /// Seconds spent as a human during rounds.
+    pub survival_secs: u64,
+}
+
+impl PlayerStats {
+    /// Counts a finished round for a player who was on `team` when it ended.
+    pub fn record_round_end(&mut self, team: Team, winner: Winner) {
+        // spectators watched the round rather than playing it
+        if team == Team::Spectator {
+            return;
+        }
+
+        self.rounds_played += 1;
+
+        match (team, winner) {
+            (Team::Human, Winner::Humans) => self.human_wins += 1,
+            (Team::Zombie, Winner::Zombies) => self.zombie_wins += 1,
+            _ => {}
+        }
+    }
+
+    /// The stats as `viewer` reads them, one line each.
+    #[must_use]
+    pub fn lines(&self, viewer: EntityView<'_>) -> Vec<String> {
+        let time = duration(viewer, self.survival_secs);
+
+        vec![
+            msg!(viewer, "stats.title", player = self.name),
+            msg!(viewer, "stats.rounds_played", count = self.rounds_played),
+            msg!(viewer, "stats.infections", count = self.infections),
+            msg!(viewer, "stats.times_infected", count = self.times_infected),
+            msg!(viewer, "stats.human_wins", count = self.human_wins),
+            msg!(viewer, "stats.zombie_wins", count = self.zombie_wins),
+            msg!(viewer, "stats.time_survived", time = time),
+        ]
+    }
+}
+
+/// `secs` as `viewer` reads a length of time, such as "2m 5s".
+#[must_use]
+pub fn duration(viewer: EntityView<'_>, secs: u64) -> String {
+    msg!(
+        viewer,
+        "stats.duration",
+        minutes = secs / 60,
+        seconds = secs % 60
+    )
+}
+
+/// The persisted [`PlayerStats`] of every player who has ever joined.
+#[derive(Component, Clone)]
+pub struct StatsStore {
+    db: LocalDb,
+    stats: Database<types::U128<NativeEndian>, types::SerdeJson<PlayerStats>>,
+}
+
+impl StatsStore {
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let stats = {
+            let mut wtxn = db.write_txn()?;
+            let db = db.create_database(&mut wtxn, Some("uuid-to-stats"))?;
+            wtxn.commit()?;
+            db
+        };
+
+        Ok(Self {
+            db: db.clone(),
+            stats,
+        })
+    }
+
+    pub fn get(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<PlayerStats>> {
+        let rtxn = self.db.read_txn()?;
+        Ok(self.stats.get(&rtxn, &uuid.as_u128())?)
+    }
+
+    pub fn set(&self, uuid: uuid::Uuid, stats: &PlayerStats) -> anyhow::Result<()> {
+        let mut wtxn = self.db.write_txn()?;
+        self.stats.put(&mut wtxn, &uuid.as_u128(), stats)?;
+        wtxn.commit()?;
+        Ok(())
+    }
+
+    /// The stats of every player, online or not. Players that are online may have progress that
+    /// has not been written yet.
+    pub fn all(&self) -> anyhow::Result<Vec<PlayerStats>> {
+        let rtxn = self.db.read_txn()?;
+
+        let mut all = Vec::new();
+
+        for entry in self.stats.iter(&rtxn)? {
+            let (_, stats) = entry?;
+            all.push(stats);
+        }
+
+        Ok(all)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> anyhow::Result<Option<PlayerStats>> {
+        let all = self.all()?;
+        Ok(all
+            .into_iter()
+            .find(|stats| stats.name.eq_ignore_ascii_case(name)))
+    }
+}
+
+#[derive(Component)]
+pub struct PlayerStatsModule;
+
+impl Module for PlayerStatsModule {
+    fn module(world: &World) {
+        world.component::<PlayerStats>();
+        world.component::<StatsStore>();
+
+        world.get::<&LocalDb>(|db| {
+            match StatsStore::new(db).context("failed to open the player stats") {
+                Ok(store) => {
+                    world.set(store);
+                }
+                Err(e) => error!("{e:#}"),
+            }
+        });
+
+        observer!(world, flecs::OnSet, &Uuid, &Name, &StatsStore($)).each_entity(
+            |entity, (uuid, name, store)| {
+                let mut stats = store
+                    .get(**uuid)
+                    .unwrap_or_else(|e| {
+                        warn!("failed to load stats: {e}");
+                        None
+                    })
+                    .unwrap_or_default();
+
+                stats.name = name.to_string();
+
+                entity.set(stats);
+            },
+        );
+
+        observer!(world, flecs::OnRemove, &Uuid, &PlayerStats, &StatsStore($)).each(
+            |(uuid, stats, store)| {
+                if let Err(e) = store.set(**uuid, stats) {
+                    warn!("failed to save stats: {e}");
+                }
+            },
+        );
+
+        let players = world.new_query::<(&Uuid, &Team, &mut PlayerStats)>();
+
+        // runs after every system that infects players or ends rounds. Infections are drained on
+        // the next tick and round ends at the end of this one
+        system!(
+            "track_player_stats",
+            world,
+            &InfectedEvents($),
+            &RoundEndEvents($),
+            &StatsStore($),
+        )
+        .kind::<flecs::pipeline::PostUpdate>()
+        .each_iter(move |it, _, (infected, round_ends, store)| {
+            let span = info_span!("track_player_stats");
+            let _enter = span.enter();
+
+            let world = it.world();
+
+            for event in infected.iter() {
+                record_infection(&world, event);
+            }
+
+            for event in round_ends.iter() {
+                players.each(|(uuid, team, stats)| {
+                    stats.record_round_end(*team, event.winner);
+
+                    if let Err(e) = store.set(**uuid, stats) {
+                        warn!("failed to save stats: {e}");
+                    }
+                });
+            }
+        });
+
+        system!(
+            "track_survival",
+            world,
+            &Compose($),
+            &GameState($),
+            &Team,
+            &mut PlayerStats,
+        )
+        .multi_threaded()
+        .each(|(compose, state, team, stats)| {
+            if compose.global().tick % 20 != 0 {
+                return;
+            }
+
+            if state.is_active() && *team == Team::Human {
+                stats.survival_secs += 1;
+            }
+        });
+    }
+}
+
+fn record_infection(world: &World, event: &InfectedEvent) {
+    let victim = world.entity_from_id(event.victim);
+
+    if victim.is_alive() {
+        victim.get::<&mut PlayerStats>(|stats| stats.times_infected += 1);
+    }
+
+    let InfectedBy::Zombie(by) = event.by else {
+        return;
+    };
+
+    let by = world.entity_from_id(by);
+
+    if by.is_alive() {
+        by.get::<&mut PlayerStats>(|stats| stats.infections += 1);
+    }
+}
+
+/// Sends the stats of `target`, or of the caller if there is no target, to the caller.
+pub fn show_stats(world: &World, caller: Entity, target: Option<&str>) {
+    let stats = match target {
+        None => Some(caller.entity_view(world).get::<&PlayerStats>(Clone::clone)),
+        Some(name) => find_stats(world, name),
+    };
+
+    let viewer = caller.entity_view(world);
+
+    let lines = match stats {
+        Some(stats) => stats.lines(viewer),
+        None => vec![msg!(
+            viewer,
+            "stats.not_found",
+            player = target.unwrap_or_default()
+        )],
+    };
+
+    world.get::<&Compose>(|compose| {
+        caller.entity_view(world).get::<&NetworkStreamRef>(|&io| {
+            for line in lines {
+                let chat = agnostic::chat(line);
+
+                if let Err(e) = compose.unicast(&chat, io, SYSTEM_ID, world) {
+                    warn!("failed to send stats: {e}");
+                }
+            }
+        });
+    });
+}
+
+/// Online players are read from their component as it is more up to date than the store.
+fn find_stats(world: &World, name: &str) -> Option<PlayerStats> {
+    let online = world.get::<&IgnMap>(|ign_map| ign_map.get(name).copied());
+
+    if let Some(entity) = online {
+        return entity
+            .entity_view(world)
+            .try_get::<&PlayerStats>(Clone::clone);
+    }
+
+    world
+        .try_get::<&StatsStore>(|store| {
+            store.find_by_name(name).unwrap_or_else(|e| {
+                warn!("failed to look up stats: {e}");
+                None
+            })
+        })
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use flecs_ecs::{
+        core::{
+            EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet, flecs,
+        },
+        macros::system,
+    };
+    use hyperion::{
+        simulation::{Name, Uuid},
+        storage::LocalDb,
+    };
+
+    use super::{PlayerStats, PlayerStatsModule, StatsStore};
+    use crate::{
+        component::team::Team,
+        module::{
+            infection::InfectedEvents,
+            round::{RoundEndEvent, RoundEndEvents, Winner},
+        },
+    };
+
+    #[test]
+    fn stats_accumulate_over_a_round() {
+        let mut patient_zero = PlayerStats::default();
+        let mut survivor = PlayerStats::default();
+        let mut victim = PlayerStats::default();
+
+        // patient zero infects the victim, who survived for a minute
+        patient_zero.infections += 1;
+        victim.times_infected += 1;
+        victim.survival_secs += 60;
+        survivor.survival_secs += 300;
+
+        patient_zero.record_round_end(Team::Zombie, Winner::Humans);
+        victim.record_round_end(Team::Zombie, Winner::Humans);
+        survivor.record_round_end(Team::Human, Winner::Humans);
+
+        assert_eq!(patient_zero, PlayerStats {
+            rounds_played: 1,
+            infections: 1,
+            ..PlayerStats::default()
+        });
+        assert_eq!(victim, PlayerStats {
+            rounds_played: 1,
+            times_infected: 1,
+            survival_secs: 60,
+            ..PlayerStats::default()
+        });
+        assert_eq!(survivor, PlayerStats {
+            rounds_played: 1,
+            human_wins: 1,
+            survival_secs: 300,
+            ..PlayerStats::default()
+        });
+
+        victim.record_round_end(Team::Zombie, Winner::Zombies);
+        assert_eq!(victim.rounds_played, 2);
+        assert_eq!(victim.zombie_wins, 1);
+        assert_eq!(victim.human_wins, 0);
+    }
+
+    #[test]
+    fn rounds_ending_on_the_timer_are_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let world = World::new();
+        world.set(LocalDb::open(dir.path()).unwrap());
+        world.set(InfectedEvents::default());
+        world.set(RoundEndEvents::default());
+        world.import::<PlayerStatsModule>();
+
+        // like the round timer, this is registered after the stats and ends the round on the
+        // first tick
+        let mut ended = false;
+        system!("round_timer", world, &mut RoundEndEvents($)).each(move |events| {
+            if !ended {
+                ended = true;
+                events.push(RoundEndEvent {
+                    round: 1,
+                    winner: Winner::Humans,
+                });
+            }
+        });
+
+        system!("clear_round_end_events", world, &mut RoundEndEvents($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each(|events| events.clear());
+
+        let uuid = hyperion::uuid::Uuid::from_u128(1);
+        let survivor = world
+            .entity()
+            .set(Uuid(uuid))
+            .set(Name::from(Arc::<str>::from("Steve")))
+            .set(Team::Human)
+            .id();
+
+        world.progress();
+        world.progress();
+
+        let stats = world
+            .entity_from_id(survivor)
+            .get::<&PlayerStats>(Clone::clone);
+
+        assert_eq!(stats.rounds_played, 1);
+        assert_eq!(stats.human_wins, 1);
+
+        let stored = world.get::<&StatsStore>(|store| store.get(uuid).unwrap());
+        assert_eq!(stored, Some(stats));
+    }
+
+    #[test]
+    fn relogging_mid_round_does_not_double_count() {
+        let mut stats = PlayerStats {
+            name: "Steve".to_owned(),
+            infections: 2,
+            ..PlayerStats::default()
+        };
+
+        // leaving writes the totals; rejoining reads them back
+        let stored = serde_json::to_vec(&stats).unwrap();
+        stats = serde_json::from_slice(&stored).unwrap();
+
+        stats.infections += 1;
+        stats.record_round_end(Team::Zombie, Winner::Zombies);
+
+        assert_eq!(stats.infections, 3);
+        assert_eq!(stats.rounds_played, 1);
+        assert_eq!(stats.zombie_wins, 1);
+    }
+}
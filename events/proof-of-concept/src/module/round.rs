@@ -0,0 +1,918 @@
+use std::collections::HashMap;
+
+use flecs_ecs::{
+    core::{
+        Entity, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+        WorldProvider, flecs,
+    },
+    macros::{Component, observer, system},
+    prelude::Module,
+};
+use hyperion::{
+    l10n::{Arg, translate},
+    msg,
+    net::{Compose, NetworkStreamRef, agnostic},
+    simulation::{
+        Name, PacketState, Player, Uuid, Xp,
+        frozen::{Frozen, freeze, unfreeze},
+    },
+    storage::{GlobalEventHandlers, PlayerLeaveServer},
+    system_registry::SystemId,
+};
+use tracing::{info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{
+        class::{apply_class, give_selectors},
+        infection::{Infections, make_human, make_zombie},
+        level::award_xp,
+        map::{finish_map_vote, open_map_vote},
+        messages::{KillFeed, announce, announce_title},
+        overtime::end_overtime,
+        shop::resend_movement_speed,
+        spectator::{SpectatorConfig, spectates_on_join, start_spectating, stop_spectating},
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(11);
+
+/// XP given to every human still alive when the humans win.
+const SURVIVOR_XP: u16 = 50;
+
+/// XP given to a zombie for each human they infected during the round.
+const XP_PER_INFECTION: u16 = 10;
+
+/// Where the round state machine is.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for a round to be started.
+    #[default]
+    Lobby,
+    /// A round is being played and ends with a human win at `ends_at`.
+    Active { ends_at: i64 },
+    /// The round is over. Combat is disabled until `until`, after which the lobby opens again.
+    Ending { until: i64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Winner {
+    Humans,
+    Zombies,
+}
+
+/// The state of the current round.
+#[derive(Component, Debug, Default)]
+pub struct GameState {
+    /// Incremented every time a round starts. `0` means no round has been played yet.
+    pub round: u32,
+    pub phase: Phase,
+    /// The tick the countdown of the current round ends on. Players are frozen in place until then.
+    pub countdown_until: i64,
+    /// The tick the grace period of the current round ends on.
+    pub grace_until: i64,
+    /// The tick overtime started on, if the current round is in overtime.
+    pub overtime_from: Option<i64>,
+    /// How many humans were left when the win condition was last checked.
+    pub humans: usize,
+}
+
+impl GameState {
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        matches!(self.phase, Phase::Active { .. })
+    }
+
+    /// Whether attacks should be processed. Combat is frozen while a round is ending.
+    #[must_use]
+    pub const fn combat_enabled(&self) -> bool {
+        !matches!(self.phase, Phase::Ending { .. })
+    }
+
+    /// Whether players are still frozen in place before the round starts.
+    #[must_use]
+    pub const fn in_countdown(&self, tick: i64) -> bool {
+        self.is_active() && tick < self.countdown_until
+    }
+
+    /// Whether zombies are still held back at the start of the round.
+    #[must_use]
+    pub const fn in_grace(&self, tick: i64) -> bool {
+        self.is_active() && tick < self.grace_until
+    }
+
+    /// Records that `humans` humans are left. Returns whether this is the check that left a
+    /// single human, so the last human is announced once rather than on every check.
+    pub const fn record_humans(&mut self, humans: usize) -> bool {
+        let last_human = humans == 1 && self.humans != 1;
+        self.humans = humans;
+        last_human
+    }
+}
+
+#[derive(Component, Copy, Clone, Debug)]
+pub struct RoundConfig {
+    /// The fraction of players chosen as patient-zero zombies when a round starts.
+    pub zombie_ratio: f32,
+    /// How long players are frozen in place before the round starts.
+    pub countdown_ticks: i64,
+    /// How long the humans have to survive.
+    pub round_ticks: i64,
+    /// How long zombies cannot attack for after the round starts, so humans can spread out.
+    pub grace_ticks: i64,
+    /// How long the result is shown before the lobby opens again.
+    pub ending_ticks: i64,
+    /// How many players are needed before a round can start.
+    pub min_players: usize,
+}
+
+impl Default for RoundConfig {
+    fn default() -> Self {
+        Self {
+            zombie_ratio: 0.2,
+            countdown_ticks: 20 * 5,
+            round_ticks: 20 * 60 * 5,
+            grace_ticks: 20 * 15,
+            ending_ticks: 20 * 10,
+            min_players: 2,
+        }
+    }
+}
+
+/// The last round a player was chosen as a patient-zero zombie in.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct LastPicked {
+    round: Option<u32>,
+}
+
+/// Fired when a round ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RoundEndEvent {
+    pub round: u32,
+    pub winner: Winner,
+}
+
+/// Rounds that ended this tick. Rounds end during [`flecs::pipeline::OnUpdate`] at the latest, so
+/// systems reading these run in [`flecs::pipeline::PostUpdate`]. They are cleared in
+/// [`flecs::pipeline::OnStore`].
+#[derive(Component, Default, Debug)]
+pub struct RoundEndEvents {
+    events: Vec<RoundEndEvent>,
+}
+
+impl RoundEndEvents {
+    pub fn iter(&self) -> impl Iterator<Item = &RoundEndEvent> + '_ {
+        self.events.iter()
+    }
+
+    pub fn push(&mut self, event: RoundEndEvent) {
+        self.events.push(event);
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// The teams of the players who left during the current round, so they go back to the same team if
+/// they rejoin before it ends.
+#[derive(Component, Debug, Default)]
+pub struct DepartedTeams {
+    teams: HashMap<Uuid, Team>,
+}
+
+impl DepartedTeams {
+    pub fn leave(&mut self, uuid: Uuid, team: Team) {
+        self.teams.insert(uuid, team);
+    }
+
+    /// The team a player had when they left, which is then forgotten.
+    pub fn rejoin(&mut self, uuid: Uuid) -> Option<Team> {
+        self.teams.remove(&uuid)
+    }
+
+    pub fn clear(&mut self) {
+        self.teams.clear();
+    }
+}
+
+/// The team a player joining on `tick` plays on, given the team they had if they left during the
+/// round. `None` outside of rounds, where everyone waits in the lobby as a human.
+#[must_use]
+pub const fn team_on_join(
+    phase: Phase,
+    tick: i64,
+    late_join_ticks: i64,
+    previous: Option<Team>,
+) -> Option<Team> {
+    if !matches!(phase, Phase::Active { .. }) {
+        return None;
+    }
+
+    if let Some(team) = previous {
+        return Some(team);
+    }
+
+    if spectates_on_join(phase, tick, late_join_ticks) {
+        Some(Team::Spectator)
+    } else {
+        Some(Team::Zombie)
+    }
+}
+
+#[derive(Component)]
+pub struct RoundModule;
+
+impl Module for RoundModule {
+    fn module(world: &World) {
+        world.component::<GameState>();
+        world.component::<RoundConfig>();
+        world.component::<LastPicked>();
+        world.component::<RoundEndEvents>();
+        world.component::<DepartedTeams>();
+
+        world.set(GameState::default());
+        world.set(RoundConfig::default());
+        world.set(RoundEndEvents::default());
+        world.set(DepartedTeams::default());
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, LastPicked)>();
+
+        // players joining mid-round start out as zombies, or spectate if the round is nearly over.
+        // Players who left during the round get their team back.
+        observer!(
+            world,
+            flecs::OnSet,
+            &Uuid,
+            &Compose($),
+            &GameState($),
+            &SpectatorConfig($),
+            &mut DepartedTeams($),
+        )
+        .with::<Team>()
+        .each_entity(
+            |entity, (uuid, compose, state, spectator_config, departed)| {
+                let world = entity.world();
+                let tick = compose.global().tick;
+
+                let previous = departed.rejoin(*uuid);
+                let late_join_ticks = spectator_config.late_join_ticks;
+
+                match team_on_join(state.phase, tick, late_join_ticks, previous) {
+                    None => {}
+                    Some(Team::Human) => apply_class(entity, compose, &world),
+                    Some(Team::Zombie) => {
+                        make_zombie(&world, entity);
+                    }
+                    Some(Team::Spectator) => start_spectating(&world, entity),
+                }
+            },
+        );
+
+        world.get::<&mut GlobalEventHandlers>(|handlers| {
+            handlers.leave_server.register(leave_team);
+        });
+
+        // the last human leaving ends the round right away
+        world
+            .observer::<flecs::OnRemove, &Team>()
+            .with::<Player>()
+            .each_entity(|entity, team| {
+                if *team != Team::Human {
+                    return;
+                }
+
+                let world = entity.world();
+
+                world.get::<&Compose>(|compose| {
+                    world.get::<&mut GameState>(|state| {
+                        check_win_condition(&world, compose, state, Some(entity.id()));
+                    });
+                });
+            });
+
+        // players who join during the countdown are frozen as well
+        let unfrozen = world
+            .query::<&Team>()
+            .with_enum(PacketState::Play)
+            .without::<Frozen>()
+            .build();
+
+        system!("clear_round_end_events", world, &mut RoundEndEvents($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each(|events| events.clear());
+
+        // only the timers are checked every tick; humans running out is checked when it happens
+        system!("round_timer", world, &Compose($), &mut GameState($)).each_iter(
+            move |it, _, (compose, state)| {
+                let span = info_span!("round_timer");
+                let _enter = span.enter();
+
+                let world = it.world();
+                let tick = compose.global().tick;
+
+                match state.phase {
+                    Phase::Lobby => {}
+                    Phase::Active { ends_at } => {
+                        if state.in_countdown(tick) {
+                            unfrozen.each_entity(|player, team| {
+                                if *team != Team::Spectator {
+                                    freeze(player);
+                                }
+                            });
+
+                            let remaining = state.countdown_until - tick;
+
+                            if remaining % 20 == 0 {
+                                show_countdown(&world, compose, remaining / 20);
+                            }
+
+                            return;
+                        }
+
+                        if tick == state.countdown_until {
+                            release_players(&world, compose);
+                        }
+
+                        if tick < ends_at {
+                            return;
+                        }
+
+                        let humans = count_humans(&world, None);
+
+                        if let Some(winner) = round_outcome(state.phase, humans, tick) {
+                            end_round(&world, compose, state, winner, humans);
+                        }
+                    }
+                    Phase::Ending { until } => {
+                        if tick >= until {
+                            state.phase = Phase::Lobby;
+                            finish_map_vote(&world);
+                            give_selectors(&world);
+                        }
+                    }
+                }
+            },
+        );
+    }
+}
+
+/// How many patient-zero zombies to choose for a round with `players` players.
+///
+/// There is always at least one zombie, and at least one human if there are two or more players.
+#[must_use]
+#[expect(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "player counts are small and the ratio is non-negative"
+)]
+pub fn zombie_count(players: usize, ratio: f32) -> usize {
+    if players == 0 {
+        return 0;
+    }
+
+    let wanted = (players as f32 * ratio).round() as usize;
+    let max = players.saturating_sub(1).max(1);
+
+    wanted.clamp(1, max)
+}
+
+/// Randomly picks `count` players from `candidates`, given as each player's last picked round.
+///
+/// Players are weighted by how many rounds ago they were last picked, so players who have never
+/// been picked are the most likely and the same players are not picked round after round.
+pub fn pick_zombies(
+    candidates: &[(Entity, Option<u32>)],
+    count: usize,
+    round: u32,
+    rng: &mut fastrand::Rng,
+) -> Vec<Entity> {
+    let mut pool: Vec<(Entity, u64)> = candidates
+        .iter()
+        .map(|&(entity, last_picked)| {
+            let rounds_since = match last_picked {
+                Some(last) => round.saturating_sub(last),
+                None => round.saturating_add(1),
+            };
+
+            (entity, u64::from(rounds_since.max(1)))
+        })
+        .collect();
+
+    let mut picked = Vec::with_capacity(count.min(pool.len()));
+
+    while picked.len() < count && !pool.is_empty() {
+        let total: u64 = pool.iter().map(|&(_, weight)| weight).sum();
+        let mut roll = rng.u64(0..total);
+
+        let idx = pool
+            .iter()
+            .position(|&(_, weight)| {
+                if roll < weight {
+                    return true;
+                }
+                roll -= weight;
+                false
+            })
+            .unwrap_or(pool.len() - 1);
+
+        picked.push(pool.swap_remove(idx).0);
+    }
+
+    picked
+}
+
+/// Starts a new round: every player becomes human and some of them are chosen as patient-zero
+/// zombies. Does nothing if a round is already being played.
+pub fn start_round(world: &World) {
+    let config = world.get::<&RoundConfig>(|config| *config);
+
+    world.get::<&Compose>(|compose| {
+        world.get::<&mut GameState>(|state| {
+            if state.is_active() {
+                return;
+            }
+
+            let tick = compose.global().tick;
+            let starts_at = tick + config.countdown_ticks;
+
+            state.round += 1;
+            state.phase = Phase::Active {
+                ends_at: starts_at + config.round_ticks,
+            };
+            state.countdown_until = starts_at;
+            state.grace_until = starts_at + config.grace_ticks;
+            state.humans = 0;
+
+            world.get::<&mut KillFeed>(KillFeed::clear);
+            world.get::<&mut DepartedTeams>(DepartedTeams::clear);
+
+            let mut candidates = Vec::new();
+            let mut spectators = Vec::new();
+
+            world
+                .new_query::<(&Team, &LastPicked, &mut Infections)>()
+                .each_entity(|entity, (team, last_picked, infections)| {
+                    infections.this_round = 0;
+                    candidates.push((entity.id(), last_picked.round));
+
+                    if *team == Team::Spectator {
+                        spectators.push(entity.id());
+                    }
+                });
+
+            // everyone plays, including those who spectated the last round
+            for &entity in &spectators {
+                stop_spectating(world, entity.entity_view(world));
+            }
+
+            for &(entity, _) in &candidates {
+                make_human(world, entity.entity_view(world));
+            }
+
+            let count = zombie_count(candidates.len(), config.zombie_ratio);
+            let mut rng = fastrand::Rng::new();
+            let chosen = pick_zombies(&candidates, count, state.round, &mut rng);
+
+            let mut names = Vec::with_capacity(chosen.len());
+
+            for &entity in &chosen {
+                let entity = entity.entity_view(world);
+
+                entity.get::<&mut LastPicked>(|last_picked| {
+                    last_picked.round = Some(state.round);
+                });
+
+                make_zombie(world, entity);
+                names.push(entity.get::<&Name>(ToString::to_string));
+            }
+
+            for &(entity, _) in &candidates {
+                let entity = entity.entity_view(world);
+
+                if !chosen.contains(&entity.id()) {
+                    apply_class(entity, compose, world);
+                }
+
+                freeze(entity);
+            }
+
+            announce_round_start(world, compose, state.round, &names);
+        });
+    });
+}
+
+fn show_countdown(world: &World, compose: &Compose, seconds: i64) {
+    world
+        .query::<&NetworkStreamRef>()
+        .with_enum(PacketState::Play)
+        .build()
+        .each_entity(|player, &io| {
+            let args: [Arg<'_>; 1] = [("seconds", &seconds)];
+            let text = translate(player, "round.countdown", &args);
+
+            if let Err(e) = compose.unicast(&agnostic::action_bar(text), io, SYSTEM_ID, world) {
+                warn!("failed to show round countdown: {e}");
+            }
+        });
+}
+
+/// Unfreezes everyone frozen for the countdown.
+fn release_players(world: &World, compose: &Compose) {
+    let mut frozen = Vec::new();
+
+    world
+        .query::<()>()
+        .with::<Frozen>()
+        .build()
+        .each_entity(|player, ()| frozen.push(player.id()));
+
+    for player in frozen {
+        let player = player.entity_view(world);
+        unfreeze(player);
+
+        // unfreezing resets the movement speed, which classes and speed boosts change
+        resend_movement_speed(player, compose, world);
+    }
+}
+
+fn announce_round_start(world: &World, compose: &Compose, number: u32, zombies: &[String]) {
+    let zombies = zombies.join("§7, §2");
+    let args: [Arg<'_>; 2] = [("number", &number), ("zombies", &zombies)];
+
+    // everyone in the game reads it in their own language
+    world
+        .query::<&Team>()
+        .with_enum(PacketState::Play)
+        .build()
+        .each_entity(|player, _| {
+            if let Err(e) = compose.chat_l10n(player, "round.started", &args, SYSTEM_ID) {
+                warn!("failed to announce round start: {e}");
+            }
+        });
+}
+
+/// Decides whether an active round is over. Zombies win as soon as no humans remain; humans win if
+/// any of them are still alive when the timer runs out.
+#[must_use]
+pub const fn round_outcome(phase: Phase, humans: usize, tick: i64) -> Option<Winner> {
+    let Phase::Active { ends_at } = phase else {
+        return None;
+    };
+
+    if humans == 0 {
+        Some(Winner::Zombies)
+    } else if tick >= ends_at {
+        Some(Winner::Humans)
+    } else {
+        None
+    }
+}
+
+/// Counts the humans among `teams`, not counting `leaving`.
+pub fn humans_remaining(
+    teams: impl IntoIterator<Item = (Entity, Team)>,
+    leaving: Option<Entity>,
+) -> usize {
+    teams
+        .into_iter()
+        .filter(|&(entity, team)| team == Team::Human && Some(entity) != leaving)
+        .count()
+}
+
+/// How many humans are still alive in the current round.
+pub fn humans_left(world: &World) -> usize {
+    count_humans(world, None)
+}
+
+fn count_humans(world: &World, leaving: Option<Entity>) -> usize {
+    let mut teams = Vec::new();
+
+    world.new_query::<&Team>().each_entity(|entity, team| {
+        teams.push((entity.id(), *team));
+    });
+
+    humans_remaining(teams, leaving)
+}
+
+/// Ends the round with a zombie victory if no humans are left, or announces the last human if only
+/// one is. `leaving` is a player that is about to be removed and should not be counted.
+///
+/// Returns whether the round ended.
+pub fn check_win_condition(
+    world: &World,
+    compose: &Compose,
+    state: &mut GameState,
+    leaving: Option<Entity>,
+) -> bool {
+    if !state.is_active() {
+        return false;
+    }
+
+    let humans = count_humans(world, leaving);
+    let tick = compose.global().tick;
+
+    let last_human = state.record_humans(humans);
+
+    let Some(winner) = round_outcome(state.phase, humans, tick) else {
+        if last_human {
+            announce_last_human(world, compose, leaving);
+        }
+        return false;
+    };
+
+    end_round(world, compose, state, winner, humans);
+
+    true
+}
+
+fn announce_last_human(world: &World, compose: &Compose, leaving: Option<Entity>) {
+    let mut last = None;
+
+    world
+        .new_query::<(&Team, &Name)>()
+        .each_entity(|entity, (team, name)| {
+            if *team == Team::Human && Some(entity.id()) != leaving {
+                last = Some(name.to_string());
+            }
+        });
+
+    let Some(player) = last else {
+        return;
+    };
+
+    let args: [Arg<'_>; 1] = [("player", &player)];
+    announce(world, compose, "infection.last_human", &args, SYSTEM_ID);
+}
+
+fn end_round(
+    world: &World,
+    compose: &Compose,
+    state: &mut GameState,
+    winner: Winner,
+    humans: usize,
+) {
+    let config = world.get::<&RoundConfig>(|config| *config);
+    let tick = compose.global().tick;
+
+    state.phase = Phase::Ending {
+        until: tick + config.ending_ticks,
+    };
+
+    // the round can end during the countdown if everyone leaves
+    release_players(world, compose);
+
+    if state.overtime_from.take().is_some() {
+        end_overtime(world, compose);
+    }
+
+    world
+        .new_query::<(&Team, &Infections, &mut Xp)>()
+        .each(|(team, infections, xp)| {
+            let reward = round_reward(*team, infections.this_round, winner);
+            award_xp(xp, reward);
+        });
+
+    world.get::<&mut RoundEndEvents>(|events| {
+        events.push(RoundEndEvent {
+            round: state.round,
+            winner,
+        });
+    });
+
+    show_result(world, compose, winner, humans);
+
+    open_map_vote(world);
+}
+
+/// The XP a player earns at the end of a round.
+#[must_use]
+pub fn round_reward(team: Team, infections: u32, winner: Winner) -> u16 {
+    match team {
+        Team::Human if winner == Winner::Humans => SURVIVOR_XP,
+        Team::Human | Team::Spectator => 0,
+        Team::Zombie => {
+            let infections = u16::try_from(infections).unwrap_or(u16::MAX);
+            infections.saturating_mul(XP_PER_INFECTION)
+        }
+    }
+}
+
+fn show_result(world: &World, compose: &Compose, winner: Winner, humans: usize) {
+    let (title, subtitle) = match winner {
+        Winner::Humans => (
+            "round.result.humans_win",
+            "round.result.humans_win_subtitle",
+        ),
+        Winner::Zombies => (
+            "round.result.zombies_win",
+            "round.result.zombies_win_subtitle",
+        ),
+    };
+
+    announce_title(world, compose, SYSTEM_ID, |player| {
+        agnostic::title(msg!(player, title, humans_left = humans)).subtitle(msg!(
+            player,
+            subtitle,
+            humans_left = humans
+        ))
+    });
+}
+
+/// Takes a player who is leaving off their team, so they no longer count towards it while their
+/// entity is being removed. During a round, their team is kept in [`DepartedTeams`].
+fn leave_team(world: &World, leave: &PlayerLeaveServer) {
+    let entity = world.entity_from_id(leave.entity);
+
+    let active = world
+        .try_get::<&GameState>(GameState::is_active)
+        .unwrap_or(false);
+
+    if active {
+        entity.try_get::<(&Uuid, &Team)>(|(&uuid, &team)| {
+            world.get::<&mut DepartedTeams>(|departed| departed.leave(uuid, team));
+        });
+    }
+
+    entity.remove::<Team>();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use flecs_ecs::core::{Entity, World};
+    use hyperion::{
+        simulation::Uuid,
+        storage::{PlayerLeaveServer, WorldEventHandlers},
+    };
+
+    use super::{
+        DepartedTeams, GameState, Phase, SURVIVOR_XP, Winner, humans_remaining, leave_team,
+        pick_zombies, round_outcome, round_reward, team_on_join, zombie_count,
+    };
+    use crate::component::team::Team;
+
+    const RATIO: f32 = 0.2;
+
+    fn players(count: usize) -> Vec<(Entity, Option<u32>)> {
+        (1..)
+            .take(count)
+            .map(|id| (Entity::new(id), None))
+            .collect()
+    }
+
+    #[test]
+    fn last_human_is_announced_once() {
+        let mut state = GameState::default();
+
+        assert!(!state.record_humans(3));
+        assert!(state.record_humans(1));
+
+        // later checks with the same human left, such as a zombie leaving, stay quiet
+        assert!(!state.record_humans(1));
+        assert!(!state.record_humans(1));
+
+        // a human coming back and being infected again is announced again
+        assert!(!state.record_humans(2));
+        assert!(state.record_humans(1));
+    }
+
+    #[test]
+    fn selection_ratio() {
+        assert_eq!(zombie_count(2, RATIO), 1);
+        assert_eq!(zombie_count(7, RATIO), 1);
+        assert_eq!(zombie_count(50, RATIO), 10);
+    }
+
+    #[test]
+    fn never_zero_zombies() {
+        let mut rng = fastrand::Rng::with_seed(7);
+
+        for count in [2, 7, 50] {
+            for ratio in [0.0, 0.01, RATIO, 1.0] {
+                let zombies = zombie_count(count, ratio);
+
+                assert!(zombies >= 1, "{count} players at {ratio} gave no zombies");
+                assert!(zombies < count, "{count} players at {ratio} gave no humans");
+
+                let picked = pick_zombies(&players(count), zombies, 1, &mut rng);
+                let unique: HashSet<_> = picked.iter().copied().collect();
+
+                assert_eq!(picked.len(), zombies);
+                assert_eq!(unique.len(), zombies);
+            }
+        }
+    }
+
+    #[test]
+    fn recently_picked_players_are_less_likely() {
+        let recent = Entity::new(1);
+        let fresh = Entity::new(2);
+        let candidates = [(recent, Some(9)), (fresh, None)];
+
+        let mut rng = fastrand::Rng::with_seed(42);
+        let recent_picks = (0..1000)
+            .filter(|_| pick_zombies(&candidates, 1, 10, &mut rng) == [recent])
+            .count();
+
+        // the fresh player has eleven times the weight of the one picked last round
+        assert!(
+            recent_picks < 200,
+            "picked the recent player {recent_picks} times"
+        );
+    }
+
+    #[test]
+    fn zombies_win_when_no_humans_remain() {
+        let phase = Phase::Active { ends_at: 100 };
+
+        assert_eq!(round_outcome(phase, 1, 50), None);
+        assert_eq!(round_outcome(phase, 0, 50), Some(Winner::Zombies));
+        assert_eq!(round_outcome(Phase::Lobby, 0, 50), None);
+    }
+
+    #[test]
+    fn humans_win_when_timer_expires() {
+        let phase = Phase::Active { ends_at: 100 };
+
+        assert_eq!(round_outcome(phase, 3, 99), None);
+        assert_eq!(round_outcome(phase, 3, 100), Some(Winner::Humans));
+        assert_eq!(round_reward(Team::Human, 0, Winner::Humans), SURVIVOR_XP);
+        assert_eq!(round_reward(Team::Human, 0, Winner::Zombies), 0);
+    }
+
+    #[test]
+    fn last_human_disconnecting_is_a_zombie_win() {
+        let human = Entity::new(1);
+        let teams = [(human, Team::Human), (Entity::new(2), Team::Zombie)];
+
+        assert_eq!(humans_remaining(teams, None), 1);
+
+        let humans = humans_remaining(teams, Some(human));
+        let phase = Phase::Active { ends_at: 100 };
+
+        assert_eq!(round_outcome(phase, humans, 50), Some(Winner::Zombies));
+    }
+
+    #[test]
+    fn leaving_players_are_taken_off_their_team() {
+        let world = World::new();
+        let mut handlers = WorldEventHandlers::default();
+        handlers.register(leave_team);
+
+        let leaving = world.entity().set(Team::Zombie).id();
+        let staying = world.entity().set(Team::Zombie).id();
+
+        handlers.trigger_all(&world, &PlayerLeaveServer {
+            entity: leaving,
+            reason: "disconnected".to_owned(),
+        });
+
+        assert!(!world.entity_from_id(leaving).has::<Team>());
+        assert!(world.entity_from_id(staying).has::<Team>());
+    }
+
+    #[test]
+    fn players_who_rejoin_keep_their_team() {
+        const ACTIVE: Phase = Phase::Active { ends_at: 6000 };
+        const LATE: i64 = 600;
+
+        let mut departed = DepartedTeams::default();
+        let human = Uuid(hyperion::uuid::Uuid::from_u128(1));
+        let zombie = Uuid(hyperion::uuid::Uuid::from_u128(2));
+
+        departed.leave(human, Team::Human);
+        departed.leave(zombie, Team::Zombie);
+
+        let previous = departed.rejoin(human);
+        assert_eq!(team_on_join(ACTIVE, 100, LATE, previous), Some(Team::Human));
+
+        // even when it is too late for new players to join
+        let previous = departed.rejoin(zombie);
+        assert_eq!(
+            team_on_join(ACTIVE, 5900, LATE, previous),
+            Some(Team::Zombie)
+        );
+
+        // the team is only kept for one rejoin
+        assert_eq!(departed.rejoin(human), None);
+        assert_eq!(team_on_join(ACTIVE, 100, LATE, None), Some(Team::Zombie));
+        assert_eq!(
+            team_on_join(ACTIVE, 5900, LATE, None),
+            Some(Team::Spectator)
+        );
+
+        departed.leave(human, Team::Human);
+        departed.clear();
+        assert_eq!(departed.rejoin(human), None);
+
+        assert_eq!(
+            team_on_join(Phase::Lobby, 100, LATE, Some(Team::Zombie)),
+            None
+        );
+    }
+}
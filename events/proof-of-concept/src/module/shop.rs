@@ -0,0 +1,515 @@
+//! A shop where players spend their levels on upgrades for the current round.
+//!
+//! Levels are taken with [`Xp::try_spend_levels`] when the entry is clicked, so a player who lost
+//! levels since opening the menu is rejected without being charged.
+
+use flecs_ecs::{
+    core::{
+        EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+        WorldProvider,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    l10n::Arg,
+    msg,
+    net::{Compose, NetworkStreamRef},
+    simulation::{
+        Xp,
+        handlers::PacketSwitchQuery,
+        menu::{MenuClick, OpenMenu, open_menu},
+    },
+    system_registry::SystemId,
+    valence_protocol::{ItemKind, VarInt, packets::play},
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_item::builder::ItemBuilder;
+use hyperion_utils::EntityExt;
+use tracing::warn;
+
+use crate::{
+    component::team::Team,
+    module::{
+        class::{Class, PassiveEffect},
+        leap::LeapCooldown,
+        round::GameState,
+    },
+};
+
+const SYSTEM_ID: SystemId = SystemId(17);
+
+/// How long the speed boost and strength last.
+const BOOST_TICKS: i64 = 20 * 30;
+
+/// The arrows added by one purchase.
+const ARROW_COUNT: i8 = 16;
+
+/// The movement speed of a player without any effects.
+const BASE_MOVEMENT_SPEED: f64 = 0.1;
+
+/// Added to the movement speed while boosted.
+const SPEED_BOOST: f64 = 0.03;
+
+/// Added to melee damage while strengthened.
+const STRENGTH_BONUS: f32 = 3.0;
+
+/// The armor sets in the order they are upgraded through, as helmet, chestplate, leggings and
+/// boots.
+const ARMOR_TIERS: [[ItemKind; 4]; 3] = [
+    [
+        ItemKind::ChainmailHelmet,
+        ItemKind::ChainmailChestplate,
+        ItemKind::ChainmailLeggings,
+        ItemKind::ChainmailBoots,
+    ],
+    [
+        ItemKind::IronHelmet,
+        ItemKind::IronChestplate,
+        ItemKind::IronLeggings,
+        ItemKind::IronBoots,
+    ],
+    [
+        ItemKind::DiamondHelmet,
+        ItemKind::DiamondChestplate,
+        ItemKind::DiamondLeggings,
+        ItemKind::DiamondBoots,
+    ],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Upgrade {
+    Armor,
+    Arrows,
+    Speed,
+    LeapCharge,
+    Strength,
+}
+
+impl Upgrade {
+    pub const HUMAN: [Self; 3] = [Self::Armor, Self::Arrows, Self::Speed];
+    pub const ZOMBIE: [Self; 2] = [Self::LeapCharge, Self::Strength];
+
+    /// The upgrades offered to players on `team`, in the order they appear in the shop.
+    #[must_use]
+    pub const fn for_team(team: Team) -> &'static [Self] {
+        match team {
+            Team::Human => &Self::HUMAN,
+            Team::Zombie => &Self::ZOMBIE,
+            Team::Spectator => &[],
+        }
+    }
+
+    /// The key of the upgrade's name in the locale files.
+    #[must_use]
+    pub const fn name_key(self) -> &'static str {
+        match self {
+            Self::Armor => "shop.upgrade.armor",
+            Self::Arrows => "shop.upgrade.arrows",
+            Self::Speed => "shop.upgrade.speed",
+            Self::LeapCharge => "shop.upgrade.leap_charge",
+            Self::Strength => "shop.upgrade.strength",
+        }
+    }
+
+    #[must_use]
+    pub const fn icon(self) -> ItemKind {
+        match self {
+            Self::Armor => ItemKind::IronChestplate,
+            Self::Arrows => ItemKind::Arrow,
+            Self::Speed => ItemKind::Sugar,
+            Self::LeapCharge => ItemKind::Feather,
+            Self::Strength => ItemKind::BlazePowder,
+        }
+    }
+
+    /// The price in levels.
+    #[must_use]
+    pub const fn cost(self) -> u8 {
+        match self {
+            Self::Armor => 5,
+            Self::Arrows | Self::LeapCharge => 2,
+            Self::Speed => 3,
+            Self::Strength => 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PurchaseError {
+    NotEnoughLevels,
+    /// The upgrade cannot be bought again, e.g. the best armor is already worn.
+    MaxedOut,
+}
+
+impl PurchaseError {
+    const fn message_key(self) -> &'static str {
+        match self {
+            Self::NotEnoughLevels => "shop.purchase.not_enough_levels",
+            Self::MaxedOut => "shop.purchase.maxed_out",
+        }
+    }
+}
+
+/// Present while a player's movement speed is boosted.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct SpeedBoost {
+    pub until: i64,
+}
+
+/// Present while a player deals extra melee damage.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct Strength {
+    pub until: i64,
+}
+
+impl Strength {
+    /// The damage added to a hit landed on `tick`.
+    #[must_use]
+    pub const fn bonus(&self, tick: i64) -> f32 {
+        if tick < self.until {
+            STRENGTH_BONUS
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ShopModule;
+
+impl Module for ShopModule {
+    fn module(world: &World) {
+        world.component::<SpeedBoost>();
+        world.component::<Strength>();
+
+        system!(
+            "expire_speed_boost",
+            world,
+            &Compose($),
+            &SpeedBoost,
+            &Team,
+            &Class,
+            &NetworkStreamRef,
+        )
+        .each_entity(|entity, (compose, boost, team, class, &io)| {
+            if compose.global().tick < boost.until {
+                return;
+            }
+
+            entity.remove::<SpeedBoost>();
+
+            let world = entity.world();
+            let speed = movement_speed(*team, *class, false);
+
+            if let Err(e) = send_movement_speed(&world, compose, entity, io, speed) {
+                warn!("failed to end speed boost: {e}");
+            }
+        });
+
+        system!("expire_strength", world, &Compose($), &Strength)
+            .multi_threaded()
+            .each_entity(|entity, (compose, strength)| {
+                if compose.global().tick >= strength.until {
+                    entity.remove::<Strength>();
+                }
+            });
+    }
+}
+
+/// Takes the levels for `upgrade` and gives any items it comes with. Nothing is taken if the
+/// purchase fails.
+///
+/// Upgrades that are not items are applied to the player by the caller once this succeeds.
+pub fn purchase(
+    upgrade: Upgrade,
+    xp: &mut Xp,
+    inventory: &mut PlayerInventory,
+) -> Result<(), PurchaseError> {
+    // checked before spending so a maxed out upgrade costs nothing
+    let armor = match upgrade {
+        Upgrade::Armor => {
+            let set =
+                next_armor_tier(inventory.get_chestplate().item).ok_or(PurchaseError::MaxedOut)?;
+            Some(set)
+        }
+        _ => None,
+    };
+
+    if !xp.try_spend_levels(upgrade.cost()) {
+        return Err(PurchaseError::NotEnoughLevels);
+    }
+
+    if let Some([helmet, chestplate, leggings, boots]) = armor {
+        inventory.set_helmet(ItemBuilder::new(helmet).build());
+        inventory.set_chestplate(ItemBuilder::new(chestplate).build());
+        inventory.set_leggings(ItemBuilder::new(leggings).build());
+        inventory.set_boots(ItemBuilder::new(boots).build());
+    }
+
+    if upgrade == Upgrade::Arrows {
+        let arrows = ItemBuilder::new(ItemKind::Arrow).count(ARROW_COUNT).build();
+
+        if inventory.try_add_item(arrows).remaining.is_some() {
+            warn!("arrows did not fit in the inventory");
+        }
+    }
+
+    Ok(())
+}
+
+/// The armor set after the one with `chestplate`, or `None` if it is already the best.
+#[must_use]
+pub fn next_armor_tier(chestplate: ItemKind) -> Option<[ItemKind; 4]> {
+    let current = ARMOR_TIERS
+        .iter()
+        .position(|[_, tier_chestplate, ..]| *tier_chestplate == chestplate);
+
+    match current {
+        Some(tier) => ARMOR_TIERS.get(tier + 1).copied(),
+        // no armor, or armor below the first tier such as leather
+        None => Some(ARMOR_TIERS[0]),
+    }
+}
+
+/// Opens the shop with the upgrades of the player's team.
+pub fn open_shop(entity: EntityView<'_>) {
+    let (team, level) = entity.get::<(&Team, &Xp)>(|(team, xp)| (*team, xp.get_visual().level));
+
+    let items = Upgrade::for_team(team)
+        .iter()
+        .map(|upgrade| {
+            let cost = upgrade.cost();
+            let name = msg!(entity, upgrade.name_key());
+
+            // entries that cannot be afforded are greyed out
+            if level >= cost {
+                ItemBuilder::new(upgrade.icon())
+                    .name(msg!(entity, "shop.entry", upgrade = name, cost = cost))
+                    .build()
+            } else {
+                ItemBuilder::new(ItemKind::GrayDye)
+                    .name(msg!(
+                        entity,
+                        "shop.too_expensive",
+                        upgrade = name,
+                        cost = cost
+                    ))
+                    .build()
+            }
+        })
+        .collect();
+
+    open_menu(
+        entity,
+        &msg!(entity, "shop.title"),
+        OpenMenu::new(items, on_shop_click),
+    );
+}
+
+fn on_shop_click(query: &mut PacketSwitchQuery<'_>, click: &MenuClick) {
+    let team = query.view.get::<&Team>(|team| *team);
+
+    let Some(&upgrade) = Upgrade::for_team(team).get(click.slot) else {
+        return;
+    };
+
+    let active = query.world.get::<&GameState>(GameState::is_active);
+
+    let key = if active {
+        let result = query
+            .view
+            .get::<(&mut Xp, &mut PlayerInventory)>(|(xp, inventory)| {
+                purchase(upgrade, xp, inventory)
+            });
+
+        match result {
+            Ok(()) => {
+                apply_effect(query, upgrade);
+                "shop.purchase.success"
+            }
+            Err(e) => e.message_key(),
+        }
+    } else {
+        "shop.purchase.closed"
+    };
+
+    // reopened so the entries that can no longer be afforded are greyed out
+    open_shop(query.view);
+
+    let name = msg!(query.view, upgrade.name_key());
+    let args: [Arg<'_>; 1] = [("upgrade", &name)];
+
+    if let Err(e) = query
+        .compose
+        .chat_l10n(query.view, key, &args, query.system_id)
+    {
+        warn!("failed to send shop message: {e}");
+    }
+}
+
+/// Applies the parts of an upgrade that are not items.
+fn apply_effect(query: &PacketSwitchQuery<'_>, upgrade: Upgrade) {
+    let tick = query.compose.global().tick;
+
+    match upgrade {
+        Upgrade::Armor | Upgrade::Arrows => {}
+        Upgrade::Speed => {
+            query.view.set(SpeedBoost {
+                until: tick + BOOST_TICKS,
+            });
+
+            let (team, class) = query
+                .view
+                .get::<(&Team, &Class)>(|(team, class)| (*team, *class));
+            let speed = movement_speed(team, class, true);
+
+            if let Err(e) =
+                send_movement_speed(query.world, query.compose, query.view, query.io_ref, speed)
+            {
+                warn!("failed to start speed boost: {e}");
+            }
+        }
+        Upgrade::LeapCharge => {
+            query
+                .view
+                .get::<&mut LeapCooldown>(LeapCooldown::add_charge);
+        }
+        Upgrade::Strength => {
+            query.view.set(Strength {
+                until: tick + BOOST_TICKS,
+            });
+        }
+    }
+}
+
+/// Sends a player the movement speed of their team, class and speed boost again, such as after
+/// being unfrozen.
+pub fn resend_movement_speed(entity: EntityView<'_>, compose: &Compose, world: &World) {
+    let Some((team, class, io)) = entity
+        .try_get::<(&Team, &Class, &NetworkStreamRef)>(|(team, class, &io)| (*team, *class, io))
+    else {
+        return;
+    };
+
+    let speed = movement_speed(team, class, entity.has::<SpeedBoost>());
+
+    if let Err(e) = send_movement_speed(world, compose, entity, io, speed) {
+        warn!("failed to resend movement speed: {e}");
+    }
+}
+
+/// The movement speed of a player on `team` playing `class`. Only humans get the speed of their
+/// class.
+fn movement_speed(team: Team, class: Class, boosted: bool) -> f64 {
+    let class_speed = class.effects().iter().find_map(|effect| match effect {
+        PassiveEffect::MovementSpeed(speed) => Some(*speed),
+        _ => None,
+    });
+
+    let base = match team {
+        Team::Human => class_speed.unwrap_or(BASE_MOVEMENT_SPEED),
+        Team::Zombie | Team::Spectator => BASE_MOVEMENT_SPEED,
+    };
+
+    if boosted { base + SPEED_BOOST } else { base }
+}
+
+fn send_movement_speed(
+    world: &World,
+    compose: &Compose,
+    entity: EntityView<'_>,
+    io: NetworkStreamRef,
+    speed: f64,
+) -> anyhow::Result<()> {
+    let pkt = play::EntityAttributesS2c {
+        entity_id: VarInt(entity.minecraft_id()),
+        properties: vec![PassiveEffect::MovementSpeed(speed).property()],
+    };
+
+    compose.unicast(&pkt, io, SYSTEM_ID, world)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::{simulation::Xp, valence_protocol::ItemKind};
+    use hyperion_inventory::PlayerInventory;
+    use hyperion_item::builder::ItemBuilder;
+
+    use super::{
+        BASE_MOVEMENT_SPEED, PurchaseError, SPEED_BOOST, Upgrade, movement_speed, purchase,
+    };
+    use crate::{component::team::Team, module::class::Class};
+
+    /// XP for exactly `level` levels and no progress towards the next one.
+    fn xp(level: u8) -> Xp {
+        Xp {
+            amount: Xp::level_bounds(level).0,
+        }
+    }
+
+    #[test]
+    fn purchase_with_exactly_enough_levels() {
+        let mut xp = xp(Upgrade::Armor.cost());
+        let mut inventory = PlayerInventory::default();
+
+        assert_eq!(purchase(Upgrade::Armor, &mut xp, &mut inventory), Ok(()));
+        assert_eq!(xp.get_visual().level, 0);
+        assert_eq!(
+            inventory.get_chestplate().item,
+            ItemKind::ChainmailChestplate
+        );
+        assert_eq!(inventory.get_boots().item, ItemKind::ChainmailBoots);
+    }
+
+    #[test]
+    fn purchase_one_level_short_changes_nothing() {
+        let mut xp = xp(Upgrade::Arrows.cost() - 1);
+        let before = xp;
+        let mut inventory = PlayerInventory::default();
+
+        assert_eq!(
+            purchase(Upgrade::Arrows, &mut xp, &mut inventory),
+            Err(PurchaseError::NotEnoughLevels)
+        );
+        assert_eq!(xp, before);
+        assert!(inventory.items().next().is_none());
+    }
+
+    #[test]
+    fn spending_levels_keeps_progress() {
+        let mut xp = Xp {
+            amount: Xp::level_bounds(10).0 + 5,
+        };
+
+        assert!(xp.try_spend_levels(3));
+        assert_eq!(xp.amount, Xp::level_bounds(7).0 + 3);
+        assert_eq!(xp.get_visual().level, 7);
+    }
+
+    #[test]
+    fn best_armor_cannot_be_bought_again() {
+        let mut xp = xp(50);
+        let before = xp;
+        let mut inventory = PlayerInventory::default();
+        inventory.set_chestplate(ItemBuilder::new(ItemKind::DiamondChestplate).build());
+
+        assert_eq!(
+            purchase(Upgrade::Armor, &mut xp, &mut inventory),
+            Err(PurchaseError::MaxedOut)
+        );
+        assert_eq!(xp, before);
+    }
+
+    #[test]
+    fn only_humans_move_at_their_class_speed() {
+        let zombie = movement_speed(Team::Zombie, Class::Scout, false);
+        let boosted_zombie = movement_speed(Team::Zombie, Class::Scout, true);
+
+        assert!(movement_speed(Team::Human, Class::Scout, false) > BASE_MOVEMENT_SPEED);
+        assert!((zombie - BASE_MOVEMENT_SPEED).abs() < f64::EPSILON);
+        assert!((boosted_zombie - (BASE_MOVEMENT_SPEED + SPEED_BOOST)).abs() < f64::EPSILON);
+    }
+}
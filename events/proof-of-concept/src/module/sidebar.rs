@@ -0,0 +1,279 @@
+use std::fmt::Display;
+
+use flecs_ecs::{
+    core::{QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, flecs},
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    l10n::{Locale, Localization},
+    net::Compose,
+    simulation::{
+        PacketState, Player,
+        scoreboard::{Objective, Scoreboard},
+    },
+    util::TracingExt,
+};
+use tracing::info_span;
+
+use crate::{
+    component::team::Team,
+    module::{
+        infection::Infections,
+        messages::{FeedEntry, KillFeed},
+        round::{GameState, Phase, RoundConfig},
+    },
+};
+
+const OBJECTIVE: &str = "infection";
+
+/// The sidebar is redrawn at most this often.
+const UPDATE_TICKS: i64 = 20;
+
+/// How many players are on each team, recounted before the sidebars are drawn.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct TeamCounts {
+    pub humans: usize,
+    pub zombies: usize,
+}
+
+/// Everything a sidebar shows.
+#[derive(Copy, Clone, Debug)]
+pub struct SidebarData<'a> {
+    pub phase: Phase,
+    pub tick: i64,
+    pub counts: TeamCounts,
+    pub team: Team,
+    pub infections: u32,
+    pub online: usize,
+    pub min_players: usize,
+    /// The latest infections, newest first.
+    pub feed: &'a [FeedEntry],
+}
+
+#[derive(Component)]
+pub struct SidebarModule;
+
+impl Module for SidebarModule {
+    fn module(world: &World) {
+        world.component::<TeamCounts>();
+        world.set(TeamCounts::default());
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Scoreboard)>();
+
+        let teams = world.new_query::<&Team>();
+
+        system!("count_teams", world, &Compose($), &mut TeamCounts($)).each_iter(
+            move |_, _, (compose, counts)| {
+                if compose.global().tick % UPDATE_TICKS != 0 {
+                    return;
+                }
+
+                let span = info_span!("count_teams");
+                let _enter = span.enter();
+
+                let mut new_counts = TeamCounts::default();
+
+                teams.each(|team| match team {
+                    Team::Human => new_counts.humans += 1,
+                    Team::Zombie => new_counts.zombies += 1,
+                    Team::Spectator => {}
+                });
+
+                *counts = new_counts;
+            },
+        );
+
+        system!(
+            "sidebar",
+            world,
+            &Compose($),
+            &Localization($),
+            &GameState($),
+            &RoundConfig($),
+            &TeamCounts($),
+            &KillFeed($),
+            &Team,
+            &Infections,
+            ?&Locale,
+            &mut Scoreboard,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .tracing_each_entity(
+            info_span!("sidebar"),
+            |_, (compose, l10n, state, config, counts, feed, team, infections, locale, scoreboard)| {
+                let tick = compose.global().tick;
+
+                if tick % UPDATE_TICKS != 0 {
+                    return;
+                }
+
+                let online = compose
+                    .global()
+                    .player_count
+                    .load(std::sync::atomic::Ordering::Relaxed);
+
+                let data = SidebarData {
+                    phase: state.phase,
+                    tick,
+                    counts: *counts,
+                    team: *team,
+                    infections: infections.this_round,
+                    online,
+                    min_players: config.min_players,
+                    feed: feed.entries(),
+                };
+
+                let locale = locale.map_or(l10n.default_locale(), |locale| locale.0.as_str());
+
+                // the title is only resent if it changed, such as when the player's locale did
+                let title = l10n.translate(locale, "sidebar.title", &[]);
+                scoreboard.show(Objective::new(OBJECTIVE).title(title));
+
+                // only the lines that changed are sent
+                scoreboard.set_lines(render_lines(&data, l10n, locale));
+            },
+        );
+    }
+}
+
+/// Renders the sidebar lines in `locale`, top to bottom.
+#[must_use]
+pub fn render_lines(data: &SidebarData<'_>, l10n: &Localization, locale: &str) -> Vec<String> {
+    let line = |key: &str, count: &dyn Display| l10n.translate(locale, key, &[("count", count)]);
+
+    let mut lines = Vec::new();
+
+    match data.phase {
+        Phase::Lobby => {
+            let needed = data.min_players.saturating_sub(data.online);
+
+            lines.push(line("sidebar.players", &data.online));
+
+            if needed == 0 {
+                lines.push(l10n.translate(locale, "sidebar.ready", &[]));
+            } else {
+                lines.push(line("sidebar.needed", &needed));
+            }
+        }
+        Phase::Active { ends_at } => {
+            let remaining = (ends_at - data.tick).max(0) / 20;
+            let time = format!("{:02}:{:02}", remaining / 60, remaining % 60);
+            let team = l10n.translate(locale, team_key(data.team), &[]);
+
+            lines.push(l10n.translate(locale, "sidebar.time_left", &[("time", &time)]));
+            lines.push(line("sidebar.humans", &data.counts.humans));
+            lines.push(line("sidebar.zombies", &data.counts.zombies));
+            lines.push(l10n.translate(locale, "sidebar.team", &[("team", &team)]));
+            lines.push(line("sidebar.infections", &data.infections));
+
+            if !data.feed.is_empty() {
+                lines.push(l10n.translate(locale, "sidebar.recent", &[]));
+
+                // sidebar lines are keyed by their text, so an invisible color code keeps two
+                // identical events apart
+                for (i, entry) in data.feed.iter().enumerate() {
+                    lines.push(format!("§{i}§r{}", entry.render(l10n, locale)));
+                }
+            }
+        }
+        Phase::Ending { .. } => {
+            lines.push(l10n.translate(locale, "sidebar.round_over", &[]));
+            lines.push(line("sidebar.humans", &data.counts.humans));
+            lines.push(line("sidebar.zombies", &data.counts.zombies));
+        }
+    }
+
+    lines
+}
+
+const fn team_key(team: Team) -> &'static str {
+    match team {
+        Team::Human => "team.human",
+        Team::Zombie => "team.zombie",
+        Team::Spectator => "team.spectator",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SidebarData, TeamCounts, render_lines};
+    use crate::{
+        component::team::Team,
+        module::{locales::built_in, messages::FeedEntry, round::Phase},
+    };
+
+    fn english(data: &SidebarData<'_>) -> Vec<String> {
+        render_lines(data, &built_in().unwrap(), "en_us")
+    }
+
+    fn lobby(online: usize) -> SidebarData<'static> {
+        SidebarData {
+            phase: Phase::Lobby,
+            tick: 0,
+            counts: TeamCounts::default(),
+            team: Team::Human,
+            infections: 0,
+            online,
+            min_players: 2,
+            feed: &[],
+        }
+    }
+
+    #[test]
+    fn lobby_shows_players_needed() {
+        assert_eq!(english(&lobby(1)), [
+            "§7Players: §f1",
+            "§7Need §f1§7 more to start"
+        ]);
+        assert_eq!(english(&lobby(2)), ["§7Players: §f2", "§aReady to start"]);
+    }
+
+    #[test]
+    fn active_round_shows_timer_and_teams() {
+        let data = SidebarData {
+            phase: Phase::Active { ends_at: 20 * 95 },
+            counts: TeamCounts {
+                humans: 4,
+                zombies: 2,
+            },
+            team: Team::Zombie,
+            infections: 1,
+            ..lobby(6)
+        };
+
+        assert_eq!(english(&data), [
+            "§7Time left: §f01:35",
+            "§aHumans: §f4",
+            "§2Zombies: §f2",
+            "§7Team: §fZombie",
+            "§7Infections: §f1",
+        ]);
+    }
+
+    #[test]
+    fn kill_feed_is_listed_below_the_round() {
+        let infection = FeedEntry {
+            key: "infection.feed.by_zombie",
+            victim: "Alex".to_owned(),
+            attacker: "Steve".to_owned(),
+        };
+        let feed = [infection.clone(), infection];
+        let data = SidebarData {
+            phase: Phase::Active { ends_at: 20 * 95 },
+            feed: &feed,
+            ..lobby(6)
+        };
+
+        let lines = english(&data);
+
+        assert_eq!(lines[5..], [
+            "§8Recent:",
+            "§0§r§2Steve §7» §aAlex",
+            "§1§r§2Steve §7» §aAlex",
+        ]);
+    }
+}
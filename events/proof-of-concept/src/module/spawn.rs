@@ -7,17 +7,36 @@ use flecs_ecs::{
 };
 use hyperion::{
     runtime::AsyncRuntime,
-    simulation::{Position, Uuid, blocks::Blocks},
+    simulation::{Position, Uuid, blocks::Blocks, spawn::SpawnPoint},
     valence_protocol::{
         BlockKind,
         math::{IVec2, IVec3, Vec3},
     },
 };
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component)]
 pub struct SpawnModule;
 
+/// Where players are sent when they respawn.
+#[derive(Component, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct SpawnPoints {
+    /// Used outside of rounds and for humans.
+    pub lobby: Vec3,
+    /// Used for zombies during a round, away from the humans.
+    pub zombies: Vec3,
+}
+
+impl Default for SpawnPoints {
+    fn default() -> Self {
+        Self {
+            lobby: Vec3::new(0.0, 120.0, 0.0),
+            zombies: Vec3::new(64.0, 120.0, 64.0),
+        }
+    }
+}
+
 const RADIUS: i32 = 0;
 const SPAWN_MIN_Y: i16 = 40;
 const SPAWN_MAX_Y: i16 = 100;
@@ -41,8 +60,10 @@ fn position_in_radius() -> IVec2 {
     IVec2::new(x, z)
 }
 
-fn random_chunk_in_radius() -> IVec2 {
-    position_in_radius() >> 4
+/// A random chunk around the world spawn.
+fn random_chunk_in_radius(spawn: Vec3) -> IVec2 {
+    let spawn = spawn.as_ivec3();
+    (IVec2::new(spawn.x, spawn.z) + position_in_radius()) >> 4
 }
 
 use hyperion::valence_protocol::BlockState;
@@ -61,6 +82,9 @@ fn avoid_blocks() -> RoaringBitmap {
 
 impl Module for SpawnModule {
     fn module(world: &World) {
+        world.component::<SpawnPoints>();
+        world.set(SpawnPoints::default());
+
         let positions = Rc::new(RefCell::new(FxHashMap::default()));
         let avoid_blocks = avoid_blocks();
 
@@ -69,15 +93,16 @@ impl Module for SpawnModule {
             flecs::OnSet,
             &Uuid,
             &mut Blocks($),
-            &AsyncRuntime($) ,
+            &AsyncRuntime($),
+            &SpawnPoint($),
         )
         .each_entity({
             let positions = Rc::clone(&positions);
-            move |entity, (uuid, blocks, runtime)| {
+            move |entity, (uuid, blocks, runtime, spawn)| {
                 let mut positions = positions.borrow_mut();
-                let position = *positions
-                    .entry(uuid.0)
-                    .or_insert_with(|| find_spawn_position(blocks, runtime, &avoid_blocks));
+                let position = *positions.entry(uuid.0).or_insert_with(|| {
+                    find_spawn_position(blocks, runtime, &avoid_blocks, spawn.position)
+                });
 
                 entity.set(Position::from(position));
             }
@@ -92,22 +117,23 @@ impl Module for SpawnModule {
     }
 }
 
+/// A safe position near the world spawn, or the world spawn itself if none is found.
 fn find_spawn_position(
     blocks: &mut Blocks,
     runtime: &AsyncRuntime,
     avoid_blocks: &RoaringBitmap,
+    spawn: Vec3,
 ) -> Vec3 {
     const MAX_TRIES: usize = 1;
-    const FALLBACK_POSITION: Vec3 = Vec3::new(0.0, 120.0, 0.0);
 
     for _ in 0..MAX_TRIES {
-        let chunk = random_chunk_in_radius();
+        let chunk = random_chunk_in_radius(spawn);
         if let Some(pos) = try_chunk_for_spawn(chunk, blocks, runtime, avoid_blocks) {
             return pos;
         }
     }
 
-    FALLBACK_POSITION
+    spawn
 }
 
 fn try_chunk_for_spawn(
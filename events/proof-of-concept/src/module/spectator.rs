@@ -0,0 +1,409 @@
+//! Spectators watch a round without taking part. Players joining a round that is nearly over
+//! spectate instead of being thrown in as zombies, and moderators can spectate with `/spectate`.
+//!
+//! Spectators are hidden from everyone still playing, do not count towards either team and are put
+//! back on a team when the next round starts.
+
+use flecs_ecs::{
+    core::{
+        Entity, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, TermBuilderImpl, World,
+        WorldProvider,
+    },
+    macros::{Component, observer},
+    prelude::{Module, flecs},
+};
+use hyperion::{
+    msg,
+    net::Compose,
+    simulation::{
+        Name, Position, Uuid,
+        game_mode::set_game_mode,
+        handlers::PacketSwitchQuery,
+        menu::{MenuClick, OpenMenu, close_menu, open_menu},
+        teleport::teleport,
+        visibility::{hide_from, show_to},
+    },
+    valence_protocol::{GameMode, Hand, ItemKind, ItemStack},
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_item::builder::ItemBuilder;
+
+use crate::{
+    command::send_message,
+    component::team::Team,
+    module::{
+        death::Respawning,
+        infection::{make_human, make_zombie},
+        round::{GameState, Phase, check_win_condition},
+    },
+};
+
+/// The hotbar slot holding the teleport menu while spectating.
+pub const TELEPORT_SLOT: u16 = 0;
+
+#[derive(Component, Copy, Clone, Debug)]
+pub struct SpectatorConfig {
+    /// Players joining a round with less time than this left spectate instead of playing.
+    pub late_join_ticks: i64,
+}
+
+impl Default for SpectatorConfig {
+    fn default() -> Self {
+        Self {
+            late_join_ticks: 20 * 60,
+        }
+    }
+}
+
+/// The item handler for the teleport menu.
+#[derive(Component)]
+pub struct SpectatorHandles {
+    pub teleport: Entity,
+}
+
+/// The players listed in a spectator's teleport menu, in slot order.
+#[derive(Component, Debug, Default)]
+pub struct TeleportTargets {
+    targets: Vec<Entity>,
+}
+
+#[derive(Component)]
+pub struct SpectatorModule;
+
+impl Module for SpectatorModule {
+    fn module(world: &World) {
+        world.import::<hyperion_item::ItemModule>();
+
+        world.component::<SpectatorConfig>();
+        world.component::<SpectatorHandles>();
+        world.component::<TeleportTargets>();
+
+        world.set(SpectatorConfig::default());
+
+        let teleport = world
+            .entity()
+            .set(hyperion_item::Handler::new(on_teleport_item));
+
+        world.set(SpectatorHandles {
+            teleport: teleport.id(),
+        });
+
+        // players joining are not shown the spectators already watching
+        observer!(world, flecs::OnSet, &Uuid)
+            .with::<Team>()
+            .each_entity(|entity, _| {
+                for spectator in spectators(&entity.world()) {
+                    if spectator != entity.id() {
+                        hide_spectator(entity, spectator);
+                    }
+                }
+            });
+    }
+}
+
+#[must_use]
+pub fn is_spectating(entity: EntityView<'_>) -> bool {
+    entity
+        .try_get::<&Team>(|team| *team == Team::Spectator)
+        .unwrap_or_default()
+}
+
+/// Whether a player joining on `tick` spectates rather than playing, because the round is too
+/// close to its end to be worth joining.
+#[must_use]
+pub const fn spectates_on_join(phase: Phase, tick: i64, late_join_ticks: i64) -> bool {
+    match phase {
+        Phase::Active { ends_at } => ends_at - tick <= late_join_ticks,
+        Phase::Lobby | Phase::Ending { .. } => false,
+    }
+}
+
+/// Moves a player to the spectators, replacing their items with the teleport menu item.
+///
+/// Returns `false` if they already were spectating.
+pub fn join_spectators(team: &mut Team, inventory: &mut PlayerInventory, item: ItemStack) -> bool {
+    if *team == Team::Spectator {
+        return false;
+    }
+
+    *team = Team::Spectator;
+    inventory.clear();
+    inventory.set_hotbar(TELEPORT_SLOT, item);
+
+    true
+}
+
+/// Moves a spectator back to the humans, taking away the teleport menu item.
+///
+/// Returns `false` if they were not spectating.
+pub fn leave_spectators(team: &mut Team, inventory: &mut PlayerInventory) -> bool {
+    if *team != Team::Spectator {
+        return false;
+    }
+
+    *team = Team::Human;
+    inventory.clear();
+
+    true
+}
+
+pub fn teleport_item(player: EntityView<'_>, handles: &SpectatorHandles) -> ItemStack {
+    ItemBuilder::new(ItemKind::Compass)
+        .name(msg!(player, "spectate.teleport_item"))
+        .handler(handles.teleport)
+        .build()
+}
+
+/// Makes `entity` a spectator: they are put in the spectator game mode and hidden from everyone
+/// still playing. Does nothing if they already are one.
+///
+/// This does not check whether the round is over; a human who starts spectating may have been the
+/// last one.
+pub fn start_spectating(world: &World, entity: EntityView<'_>) {
+    // zombies are taken off the zombie team first so their name tag is reset
+    make_human(world, entity);
+
+    let item = world.get::<&SpectatorHandles>(|handles| teleport_item(entity, handles));
+
+    let joined = entity.get::<(&mut Team, &mut PlayerInventory)>(|(team, inventory)| {
+        join_spectators(team, inventory, item)
+    });
+
+    if !joined {
+        return;
+    }
+
+    // a dead zombie no longer respawns
+    entity.remove::<Respawning>();
+
+    set_game_mode(entity, GameMode::Spectator);
+
+    for (viewer, team) in teams(world) {
+        if viewer == entity.id() {
+            continue;
+        }
+
+        let viewer = world.entity_from_id(viewer);
+
+        // spectators see each other
+        if team == Team::Spectator {
+            show_to(entity, viewer);
+        } else {
+            hide_from(viewer, entity);
+        }
+    }
+}
+
+/// Puts a spectator back in the game as a human, shown to everyone again. Does nothing if they are
+/// not spectating.
+pub fn stop_spectating(world: &World, entity: EntityView<'_>) {
+    let left = entity.get::<(&mut Team, &mut PlayerInventory)>(|(team, inventory)| {
+        leave_spectators(team, inventory)
+    });
+
+    if !left {
+        return;
+    }
+
+    close_menu(entity);
+
+    set_game_mode(entity, GameMode::Survival);
+
+    for (viewer, team) in teams(world) {
+        if viewer == entity.id() {
+            continue;
+        }
+
+        let viewer = world.entity_from_id(viewer);
+
+        show_to(viewer, entity);
+
+        if team == Team::Spectator {
+            hide_from(entity, viewer);
+        }
+    }
+}
+
+/// Toggles spectating for a moderator, as with `/spectate`.
+pub fn toggle_spectating(world: &World, entity: EntityView<'_>) {
+    let spectating = is_spectating(entity);
+
+    world.get::<&Compose>(|compose| {
+        world.get::<&mut GameState>(|state| {
+            if spectating {
+                stop_spectating(world, entity);
+
+                // like anyone joining a round in progress, they play on as a zombie
+                if state.is_active() {
+                    make_zombie(world, entity);
+                }
+            } else {
+                start_spectating(world, entity);
+
+                // they may have been the last human
+                check_win_condition(world, compose, state, None);
+            }
+        });
+    });
+
+    let msg = if spectating {
+        msg!(entity, "spectate.off")
+    } else {
+        msg!(entity, "spectate.on")
+    };
+
+    send_message(world, entity.id(), &msg);
+}
+
+fn teams(world: &World) -> Vec<(Entity, Team)> {
+    let mut teams = Vec::new();
+
+    world.new_query::<&Team>().each_entity(|entity, team| {
+        teams.push((entity.id(), *team));
+    });
+
+    teams
+}
+
+fn spectators(world: &World) -> Vec<Entity> {
+    teams(world)
+        .into_iter()
+        .filter(|&(_, team)| team == Team::Spectator)
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+fn hide_spectator(viewer: EntityView<'_>, spectator: Entity) {
+    if !is_spectating(viewer) {
+        hide_from(viewer, spectator.entity_view(viewer.world()));
+    }
+}
+
+fn on_teleport_item(query: &mut PacketSwitchQuery<'_>, _: &Hand) {
+    if is_spectating(query.view) {
+        open_teleport_menu(query.view);
+    }
+}
+
+/// Lists every player still playing, humans first.
+fn open_teleport_menu(entity: EntityView<'_>) {
+    let mut players = Vec::new();
+
+    entity
+        .world()
+        .new_query::<(&Team, &Name)>()
+        .each_entity(|player, (team, name)| {
+            if *team != Team::Spectator {
+                players.push((*team, name.to_string(), player.id()));
+            }
+        });
+
+    players.sort_by(|(a_team, a_name, _), (b_team, b_name, _)| {
+        (*a_team != Team::Human, a_name).cmp(&(*b_team != Team::Human, b_name))
+    });
+
+    let items = players
+        .iter()
+        .map(|(team, name, _)| {
+            let color = if *team == Team::Human { "§a" } else { "§2" };
+
+            ItemBuilder::new(ItemKind::PlayerHead)
+                .name(format!("{color}{name}"))
+                .build()
+        })
+        .collect();
+
+    entity.set(TeleportTargets {
+        targets: players.into_iter().map(|(_, _, player)| player).collect(),
+    });
+
+    open_menu(
+        entity,
+        &msg!(entity, "spectate.teleport_menu"),
+        OpenMenu::new(items, on_teleport_click),
+    );
+}
+
+fn on_teleport_click(query: &mut PacketSwitchQuery<'_>, click: &MenuClick) {
+    let target = query
+        .view
+        .try_get::<&TeleportTargets>(|targets| targets.targets.get(click.slot).copied())
+        .flatten();
+
+    let Some(target) = target else {
+        return;
+    };
+
+    let target = query.world.entity_from_id(target);
+
+    // the player may have left since the menu was opened
+    if !target.is_alive() {
+        return;
+    }
+
+    let Some(position) = target.try_get::<&Position>(|position| **position) else {
+        return;
+    };
+
+    close_menu(query.view);
+    teleport(query.view, position, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::Entity;
+    use hyperion::valence_protocol::{ItemKind, ItemStack};
+    use hyperion_inventory::PlayerInventory;
+
+    use super::{TELEPORT_SLOT, join_spectators, leave_spectators, spectates_on_join};
+    use crate::{
+        component::team::Team,
+        module::round::{Phase, Winner, humans_remaining, round_outcome},
+    };
+
+    const ACTIVE: Phase = Phase::Active { ends_at: 6000 };
+
+    #[test]
+    fn spectators_do_not_count_towards_the_win_condition() {
+        let human = (Entity::new(1), Team::Human);
+        let zombie = (Entity::new(2), Team::Zombie);
+        let spectator = (Entity::new(3), Team::Spectator);
+
+        assert_eq!(humans_remaining([human, zombie, spectator], None), 1);
+
+        // spectators watching do not keep the round going once the last human is infected
+        let humans = humans_remaining([zombie, spectator], None);
+        assert_eq!(round_outcome(ACTIVE, humans, 100), Some(Winner::Zombies));
+    }
+
+    #[test]
+    fn spectators_are_humans_at_the_next_round() {
+        let mut team = Team::Zombie;
+        let mut inventory = PlayerInventory::default();
+        let item = ItemStack::new(ItemKind::Compass, 1, None);
+
+        assert!(join_spectators(&mut team, &mut inventory, item.clone()));
+        assert!(!join_spectators(&mut team, &mut inventory, item));
+        assert_eq!(team, Team::Spectator);
+        assert_eq!(
+            inventory.get_hand_slot(TELEPORT_SLOT).unwrap().item,
+            ItemKind::Compass
+        );
+
+        assert!(leave_spectators(&mut team, &mut inventory));
+        assert_eq!(team, Team::Human);
+        assert!(inventory.items().next().is_none());
+
+        // players who were already playing are left alone
+        assert!(!leave_spectators(&mut team, &mut inventory));
+        assert_eq!(team, Team::Human);
+    }
+
+    #[test]
+    fn joining_near_the_end_spectates() {
+        assert!(!spectates_on_join(Phase::Lobby, 100, 1200));
+        assert!(!spectates_on_join(ACTIVE, 4799, 1200));
+        assert!(spectates_on_join(ACTIVE, 4800, 1200));
+        assert!(!spectates_on_join(Phase::Ending { until: 0 }, 100, 1200));
+    }
+}
@@ -1,5 +1,5 @@
 use flecs_ecs::{
-    core::{QueryBuilderImpl, SystemAPI, TermBuilderImpl, World},
+    core::{QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, flecs},
     macros::{Component, system},
     prelude::Module,
 };
@@ -10,6 +10,8 @@ use hyperion::{
 };
 use tracing::info_span;
 
+use crate::module::round::{RoundEndEvents, Winner};
+
 #[derive(Component)]
 pub struct StatsModule;
 
@@ -21,9 +23,13 @@ impl Module for StatsModule {
         let mut tick_times = Vec::with_capacity(20 * 60); // 20 ticks per second, 60 seconds
         let mut last_frame_time_total = 0.0;
 
-        system!("stats", world, &Compose($))
+        let mut human_wins = 0_u32;
+        let mut zombie_wins = 0_u32;
+
+        system!("stats", world, &Compose($), &RoundEndEvents($))
+            .kind::<flecs::pipeline::PostUpdate>()
             .multi_threaded()
-            .each_iter(move |it, _, compose| {
+            .each_iter(move |it, _, (compose, round_ends)| {
                 let span = info_span!("stats");
                 let _enter = span.enter();
                 let world = it.world();
@@ -52,7 +58,17 @@ impl Module for StatsModule {
                      {avg_s60:.2} ms"
                 );
 
-                let footer = format!("§d§l{player_count} players online");
+                for event in round_ends.iter() {
+                    match event.winner {
+                        Winner::Humans => human_wins += 1,
+                        Winner::Zombies => zombie_wins += 1,
+                    }
+                }
+
+                let footer = format!(
+                    "§d§l{player_count} players online\n§aHumans {human_wins} §7- §2Zombies \
+                     {zombie_wins}"
+                );
 
                 let pkt = play::PlayerListHeaderS2c {
                     header: title.into_cow_text(),
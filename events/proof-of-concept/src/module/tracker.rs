@@ -0,0 +1,200 @@
+//! A compass for zombies that points to the nearest human, so late rounds do not drag on while the
+//! last humans stay hidden.
+//!
+//! The compass points at the player's spawn position, so moving the spawn position to a human
+//! turns it into a tracker.
+
+use flecs_ecs::{
+    core::{
+        EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use hyperion::{
+    msg,
+    net::{Compose, DataBundle, NetworkStreamRef, agnostic},
+    simulation::Position,
+    system_registry::SystemId,
+    valence_protocol::{ItemKind, ItemStack, math::Vec3, packets::play},
+};
+use hyperion_item::builder::ItemBuilder;
+use tracing::{info_span, warn};
+
+use crate::{
+    component::team::Team,
+    module::{death::Respawning, round::GameState},
+};
+
+const SYSTEM_ID: SystemId = SystemId(19);
+
+/// The hotbar slot holding the tracker while a zombie.
+pub const TRACKER_SLOT: u16 = 2;
+
+#[derive(Component, Copy, Clone, Debug)]
+pub struct TrackerConfig {
+    /// Humans further away than this are not tracked, so hiding far away still works.
+    pub range: f32,
+    pub update_ticks: i64,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            range: 96.0,
+            update_ticks: 20 * 2,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TrackerModule;
+
+impl Module for TrackerModule {
+    fn module(world: &World) {
+        world.component::<TrackerConfig>();
+        world.set(TrackerConfig::default());
+
+        let players = world.new_query::<(&Team, &Position)>();
+
+        system!(
+            "track_humans",
+            world,
+            &Compose($),
+            &GameState($),
+            &TrackerConfig($),
+        )
+        .each_iter(move |it, _, (compose, state, config)| {
+            let span = info_span!("track_humans");
+            let _enter = span.enter();
+
+            let world = it.world();
+            let tick = compose.global().tick;
+
+            if tick % config.update_ticks != 0 {
+                return;
+            }
+
+            // humans are left alone during the grace period and once the round is over
+            if !state.is_active() || state.in_grace(tick) {
+                return;
+            }
+
+            let mut humans = Vec::new();
+            let mut zombies = Vec::new();
+
+            players.each_entity(|entity, (team, position)| {
+                match team {
+                    Team::Human => humans.push(**position),
+                    // spectating zombies have nothing to track
+                    Team::Zombie if !entity.has::<Respawning>() => {
+                        zombies.push((entity.id(), **position));
+                    }
+                    Team::Zombie | Team::Spectator => {}
+                }
+            });
+
+            for (zombie, position) in zombies {
+                let target = nearest_human(position, humans.iter().copied(), config.range);
+
+                let zombie = world.entity_from_id(zombie);
+
+                if let Err(e) = point_tracker(&world, compose, zombie, target) {
+                    warn!("failed to update tracker: {e}");
+                }
+            }
+        });
+    }
+}
+
+pub fn tracker_item(player: EntityView<'_>) -> ItemStack {
+    ItemBuilder::new(ItemKind::Compass)
+        .name(msg!(player, "tracker.item"))
+        .build()
+}
+
+/// The position of and distance to the human nearest to `from`, or `None` if there are no humans
+/// within `range`.
+#[must_use]
+pub fn nearest_human(
+    from: Vec3,
+    humans: impl IntoIterator<Item = Vec3>,
+    range: f32,
+) -> Option<(Vec3, f32)> {
+    humans
+        .into_iter()
+        .map(|human| (human, human.distance(from)))
+        .filter(|&(_, distance)| distance <= range)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+fn point_tracker(
+    world: &World,
+    compose: &Compose,
+    zombie: EntityView<'_>,
+    target: Option<(Vec3, f32)>,
+) -> anyhow::Result<()> {
+    let io = zombie.get::<&NetworkStreamRef>(|&io| io);
+
+    let mut bundle = DataBundle::new(compose);
+
+    let msg = match target {
+        Some((position, distance)) => {
+            bundle.add_packet(
+                &play::PlayerSpawnPositionS2c {
+                    position: position.as_dvec3().into(),
+                    angle: 0.0,
+                },
+                world,
+            )?;
+
+            msg!(
+                zombie,
+                "tracker.nearest",
+                distance = format!("{distance:.0}")
+            )
+        }
+        None => msg!(zombie, "tracker.none"),
+    };
+
+    bundle.add_packet(&agnostic::action_bar(msg), world)?;
+
+    bundle.send(world, io, SYSTEM_ID)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion::valence_protocol::math::Vec3;
+
+    use super::nearest_human;
+
+    const HUMANS: [Vec3; 3] = [
+        Vec3::new(10.0, 64.0, 0.0),
+        Vec3::new(0.0, 64.0, -4.0),
+        Vec3::new(-30.0, 64.0, 40.0),
+    ];
+
+    #[test]
+    fn tracks_the_nearest_human() {
+        let zombie = Vec3::new(0.0, 64.0, 0.0);
+
+        let (target, distance) = nearest_human(zombie, HUMANS, 100.0).unwrap();
+
+        assert_eq!(target, HUMANS[1]);
+        assert!((distance - 4.0).abs() < f32::EPSILON);
+
+        // moving towards the far human makes them the nearest
+        let zombie = Vec3::new(-25.0, 64.0, 35.0);
+        let (target, _) = nearest_human(zombie, HUMANS, 100.0).unwrap();
+
+        assert_eq!(target, HUMANS[2]);
+    }
+
+    #[test]
+    fn humans_out_of_range_are_hidden() {
+        let zombie = Vec3::new(0.0, 64.0, 100.0);
+
+        assert_eq!(nearest_human(zombie, HUMANS, 50.0), None);
+        assert_eq!(nearest_human(zombie, [], 50.0), None);
+    }
+}
@@ -1,26 +1,38 @@
+//! Changing the skin of a player while they are online, such as when they are infected.
+
 use std::borrow::Cow;
 
 use flecs_ecs::{
-    core::{Entity, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World},
+    core::{EntityView, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World},
     macros::{Component, system},
     prelude::Module,
 };
 use hyperion::{
-    egress::player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
-    net::{Compose, DataBundle, NetworkStreamRef},
-    simulation::{event, skin::PlayerSkin},
+    egress::{
+        metadata::show_all,
+        player_join::{PlayerListActions, PlayerListEntry, PlayerListS2c},
+    },
+    net::{Compose, NetworkStreamRef},
+    simulation::{
+        Name, Pitch, Position, Uuid, Yaw, event, game_mode::GameMode, skin::PlayerSkin,
+        teleport::teleport_to_dimension,
+    },
     storage::EventQueue,
     system_registry::SystemId,
-    uuid::Uuid,
     valence_ident::ident,
-    valence_protocol,
     valence_protocol::{
-        GameMode, VarInt,
-        game_mode::OptGameMode,
-        packets::play::{EntitiesDestroyS2c, PlayerRemoveS2c, PlayerRespawnS2c},
+        ByteAngle, VarInt,
+        packets::play::{
+            EntitiesDestroyS2c, EntityEquipmentUpdateS2c, PlayerRemoveS2c, PlayerSpawnS2c,
+            entity_equipment_update_s2c::EquipmentEntry,
+        },
     },
 };
+use hyperion_inventory::PlayerInventory;
 use hyperion_utils::EntityExt;
+use tracing::warn;
+
+const SYSTEM_ID: SystemId = SystemId(8);
 
 #[derive(Component)]
 pub struct SkinModule;
@@ -31,96 +43,134 @@ impl Module for SkinModule {
             |it, _, (event_queue, compose)| {
                 let world = it.world();
                 for event in event_queue.drain() {
-                    println!("got {event:?}");
-                    event
-                        .by
-                        .entity_view(world)
-                        .get::<(&NetworkStreamRef, &hyperion::simulation::Uuid)>(|(io, uuid)| {
-                            on_set_skin(event.by, &world, compose, uuid.0, event.skin, *io);
-                        });
+                    let player = event.by.entity_view(world);
+
+                    if let Err(e) = on_set_skin(player, &world, compose, &event.skin) {
+                        warn!("failed to change the skin of a player: {e}");
+                    }
+
+                    // kept so players who join later see the new skin too
+                    player.set(event.skin);
                 }
             },
         );
     }
 }
 
+/// Shows everyone the new skin of `player`. Clients only read skins when a player is added to
+/// their player list, so the player is removed and added again. Others then spawn the player
+/// again with their equipment, while the player respawns to see their own new skin.
 fn on_set_skin(
-    id: Entity,
+    player: EntityView<'_>,
     world: &World,
     compose: &Compose,
-    uuid: Uuid,
-    skin: PlayerSkin,
-    io: NetworkStreamRef,
-) {
-    let minecraft_id = id.minecraft_id();
-    let mut bundle = DataBundle::new(compose);
-    // Remove player info
-    bundle
-        .add_packet(
-            &PlayerRemoveS2c {
-                uuids: Cow::Borrowed(&[uuid]),
-            },
-            world,
-        )
-        .unwrap();
-
-    // Destroy player entity
-    bundle
-        .add_packet(
-            &EntitiesDestroyS2c {
-                entity_ids: Cow::Borrowed(&[VarInt(minecraft_id)]),
-            },
-            world,
-        )
-        .unwrap();
-
-    // todo: in future, do not clone
-    let property = valence_protocol::profile::Property {
-        name: "textures".to_string(),
-        value: skin.textures,
-        signature: Some(skin.signature),
-    };
-
-    let property = &[property];
-
-    // Add player back with new skin
-    bundle
-        .add_packet(
-            &PlayerListS2c {
-                actions: PlayerListActions::default().with_add_player(true),
-                entries: Cow::Borrowed(&[PlayerListEntry {
-                    player_uuid: uuid,
-                    username: Cow::Borrowed("Player"),
-                    properties: Cow::Borrowed(property),
-                    chat_data: None,
-                    listed: true,
-                    ping: 20,
-                    game_mode: GameMode::Survival,
-                    display_name: None,
-                }]),
-            },
-            world,
-        )
-        .unwrap();
-
-    // // Respawn player
-    bundle
-        .add_packet(
-            &PlayerRespawnS2c {
-                dimension_type_name: ident!("minecraft:overworld").into(),
-                dimension_name: ident!("minecraft:overworld").into(),
-                hashed_seed: 0,
-                game_mode: GameMode::Survival,
-                previous_game_mode: OptGameMode::default(),
-                is_debug: false,
-                is_flat: false,
-                copy_metadata: false,
-                last_death_location: None,
-                portal_cooldown: VarInt::default(),
-            },
-            world,
-        )
-        .unwrap();
+    skin: &PlayerSkin,
+) -> anyhow::Result<()> {
+    let game_mode = player
+        .try_get::<&GameMode>(|mode| mode.0)
+        .unwrap_or_default();
+
+    let (position, yaw, pitch) = player.get::<(
+        &NetworkStreamRef,
+        &Uuid,
+        &Name,
+        &Position,
+        &Yaw,
+        &Pitch,
+        &PlayerInventory,
+    )>(
+        |(&io, uuid, name, position, yaw, pitch, inventory)| -> anyhow::Result<_> {
+            let minecraft_id = player.minecraft_id();
+            let properties = skin.properties();
+
+            compose
+                .broadcast(
+                    &PlayerRemoveS2c {
+                        uuids: Cow::Borrowed(&[uuid.0]),
+                    },
+                    SYSTEM_ID,
+                )
+                .send(world)?;
+
+            compose
+                .broadcast(
+                    &PlayerListS2c {
+                        actions: PlayerListActions::default()
+                            .with_add_player(true)
+                            .with_update_listed(true),
+                        entries: Cow::Borrowed(&[PlayerListEntry {
+                            player_uuid: uuid.0,
+                            username: Cow::Borrowed(name),
+                            properties: Cow::Borrowed(&properties),
+                            chat_data: None,
+                            listed: true,
+                            ping: 20,
+                            game_mode,
+                            display_name: None,
+                        }]),
+                    },
+                    SYSTEM_ID,
+                )
+                .send(world)?;
+
+            compose
+                .broadcast(
+                    &EntitiesDestroyS2c {
+                        entity_ids: Cow::Borrowed(&[VarInt(minecraft_id)]),
+                    },
+                    SYSTEM_ID,
+                )
+                .exclude(io)
+                .send(world)?;
+
+            compose
+                .broadcast(
+                    &PlayerSpawnS2c {
+                        entity_id: VarInt(minecraft_id),
+                        player_uuid: uuid.0,
+                        position: position.as_dvec3(),
+                        yaw: ByteAngle::from_degrees(**yaw),
+                        pitch: ByteAngle::from_degrees(**pitch),
+                    },
+                    SYSTEM_ID,
+                )
+                .exclude(io)
+                .send(world)?;
+
+            let show_all = show_all(minecraft_id);
+            compose
+                .broadcast(show_all.borrow_packet(), SYSTEM_ID)
+                .exclude(io)
+                .send(world)?;
+
+            // a spawned player holds and wears nothing until told otherwise
+            let equipment = EntityEquipmentUpdateS2c {
+                entity_id: VarInt(minecraft_id),
+                equipment: inventory
+                    .equipment()
+                    .iter()
+                    .map(|(slot, item)| EquipmentEntry {
+                        slot: slot as i8,
+                        item: item.clone(),
+                    })
+                    .collect(),
+            };
+            compose
+                .broadcast(&equipment, SYSTEM_ID)
+                .exclude(io)
+                .send(world)?;
+
+            Ok((**position, **yaw, **pitch))
+        },
+    )?;
+
+    // respawning makes the client forget where it is, so it is moved back in place
+    teleport_to_dimension(
+        player,
+        ident!("minecraft:overworld").into(),
+        position,
+        Some((yaw, pitch)),
+    );
 
-    bundle.send(world, io, SystemId(8)).unwrap();
+    Ok(())
 }